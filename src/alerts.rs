@@ -0,0 +1,260 @@
+//! Alert rules from the config file's `alerts` section: models that should
+//! stand out the moment a host exposing them is found.
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+use crate::analyze::parse_parameter_size;
+use crate::exit::ScanOutcome;
+use crate::modelname::{self, ModelName};
+use crate::results::read_model_rows;
+use crate::{console_log, Model, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    /// How a model that several rules match is reported
+    #[serde(rename = "match")]
+    pub matching: Matching,
+    pub rules: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Matching {
+    /// Under the first rule it matches, in file order
+    #[default]
+    First,
+    /// Under every rule it matches
+    All,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub name: String,
+    /// Glob over the whole model name, case-insensitive: `*` is any run of
+    /// characters, `?` any one. Also tried on the normalized name, so
+    /// `llama3:*` matches `registry.ollama.ai/library/llama3` too
+    pub model: Option<String>,
+    /// Regex searched for in the model name as listed, instead of `model`
+    pub regex: Option<String>,
+    /// Smallest parameter size that matches, e.g. "32B"
+    pub min_size: Option<String>,
+}
+
+struct Rule {
+    name: String,
+    pattern: Regex,
+    /// Globs match the normalized name as well
+    glob: bool,
+    min_size: Option<f64>,
+}
+
+/// A model that matched a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub rule: String,
+    pub model: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.model)
+    }
+}
+
+pub struct AlertRules {
+    matching: Matching,
+    rules: Vec<Rule>,
+}
+
+impl AlertRules {
+    /// Compiles the rules, failing on the first one that is not valid.
+    pub fn compile(config: &AlertConfig) -> Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| Rule::compile(rule).with_context(|| format!("alerts rule {} ({:?})", i + 1, rule.name)))
+            .collect::<Result<_>>()?;
+        Ok(Self { matching: config.matching, rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Names of the rules a model matches, as many as `matching` allows.
+    fn matching(&self, name: &str, parameter_size: &str) -> Vec<&str> {
+        let normalized = modelname::normalize(name);
+        let size = parameter_size_of(&normalized, parameter_size);
+        let normalized = normalized.to_string();
+        let mut rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(name, size) || (rule.glob && rule.matches(&normalized, size)))
+            .map(|rule| rule.name.as_str());
+        match self.matching {
+            Matching::First => rules.next().into_iter().collect(),
+            Matching::All => rules.collect(),
+        }
+    }
+
+    /// The alerts an endpoint listing `models` raises.
+    pub fn check(&self, models: &[Model]) -> Vec<Alert> {
+        models
+            .iter()
+            .flat_map(|model| {
+                self.matching(&model.name, &model.details.parameter_size)
+                    .into_iter()
+                    .map(|rule| Alert { rule: rule.to_string(), model: model.name.clone() })
+            })
+            .collect()
+    }
+}
+
+impl Rule {
+    fn compile(config: &RuleConfig) -> Result<Self> {
+        if config.name.trim().is_empty() {
+            bail!("a rule needs a name");
+        }
+        let pattern = match (&config.model, &config.regex) {
+            (Some(glob), None) => RegexBuilder::new(&glob_to_regex(glob)).case_insensitive(true).build()?,
+            (None, Some(regex)) => Regex::new(regex).context("invalid regex")?,
+            (Some(_), Some(_)) => bail!("give either \"model\" or \"regex\", not both"),
+            (None, None) => bail!("needs a \"model\" glob or a \"regex\""),
+        };
+        let min_size = match &config.min_size {
+            Some(size) => Some(parse_parameter_size(size).with_context(|| format!("invalid min_size {:?}, expected e.g. 32B", size))?),
+            None => None,
+        };
+        Ok(Self { name: config.name.clone(), pattern, glob: config.model.is_some(), min_size })
+    }
+
+    /// A rule with a minimum size never matches a model of unknown size.
+    fn matches(&self, name: &str, size: Option<f64>) -> bool {
+        self.pattern.is_match(name)
+            && match self.min_size {
+                Some(min) => size.is_some_and(|size| size >= min),
+                None => true,
+            }
+    }
+}
+
+/// The reported parameter size, or else the one in the tag ("deepseek-r1:70b").
+fn parameter_size_of(name: &ModelName, parameter_size: &str) -> Option<f64> {
+    parse_parameter_size(parameter_size).or_else(|| parse_parameter_size(&name.tag))
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The highlighted block for an endpoint that raised alerts.
+pub fn alert_block(base_url: &str, alerts: &[Alert]) -> Vec<String> {
+    let mut block = vec![format!("\n{}{}", HEADER_STYLE, style(format!(" ALERT: {} ", base_url)).white().on_red().bold())];
+    for (i, alert) in alerts.iter().enumerate() {
+        let prefix = if i == alerts.len() - 1 { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
+        block.push(format!("{}{} {}", prefix, style(&alert.model).red().bold(), style(format!("({})", alert.rule)).dim()));
+    }
+    block
+}
+
+/// `--test-alerts`: runs the rules over a models CSV from an earlier scan and
+/// lists what they match, without scanning anything.
+pub fn test_alerts(rules: &AlertRules, file: &Path) -> Result<ScanOutcome> {
+    if rules.is_empty() {
+        bail!("No alert rules configured; add an \"alerts\" section to the config file");
+    }
+    let rows = read_model_rows(file)?;
+    let mut matched = 0;
+    console_log(format!("{}{}", HEADER_STYLE, style(format!("Alert rules against {}", file.display())).blue().bold()));
+    for row in &rows {
+        let matching = rules.matching(&row.name, &row.parameter_size);
+        matched += usize::from(!matching.is_empty());
+        for rule in matching {
+            console_log(format!("{}{} {} {}",
+                LIST_ITEM_STYLE,
+                style(&row.endpoint).cyan(),
+                style(&row.name).red(),
+                style(format!("({})", rule)).dim()
+            ));
+        }
+    }
+    console_log(format!("{}{} of {} model rows matched", LAST_ITEM_STYLE, matched, rows.len()));
+    Ok(if matched > 0 { ScanOutcome::Found } else { ScanOutcome::NothingFound })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: &str) -> Result<AlertRules> {
+        AlertRules::compile(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn matches_on_name_and_size() {
+        let rules = rules(r#"{"rules":[{"name":"big-r1","model":"deepseek-r1*","min_size":"32B"},{"name":"any-r1","regex":"^deepseek-r1"}]}"#).unwrap();
+        assert_eq!(rules.matching("deepseek-r1:70b", "70.6B"), ["big-r1"]);
+        assert_eq!(rules.matching("DeepSeek-R1:671b", ""), ["big-r1"]);
+        // Too small for the first rule, and the regex is case-sensitive
+        assert_eq!(rules.matching("deepseek-r1:8b", "8.0B"), ["any-r1"]);
+        assert!(rules.matching("DeepSeek-R1:8b", "8.0B").is_empty());
+        // Unknown size never passes a minimum
+        assert_eq!(rules.matching("deepseek-r1:latest", ""), ["any-r1"]);
+        assert!(rules.matching("llama3:70b", "70.6B").is_empty());
+    }
+
+    #[test]
+    fn globs_also_match_normalized_names() {
+        let rules = rules(r#"{"rules":[{"name":"big-llama","model":"llama3:*","min_size":"70B"},{"name":"airoboros","model":"jondurbin/airoboros:latest"},{"name":"exact","regex":"^llama3:"}]}"#).unwrap();
+        assert_eq!(rules.matching("registry.ollama.ai/library/LLaMA3:70b-instruct", ""), ["big-llama"]);
+        assert_eq!(rules.matching("jondurbin/airoboros", ""), ["airoboros"]);
+        assert_eq!(rules.matching("JONDURBIN/AIROBOROS@sha256:0f3c", ""), ["airoboros"]);
+        // Regexes only see the name as listed
+        assert!(rules.matching("registry.ollama.ai/library/llama3:8b", "8.0B").is_empty());
+        assert_eq!(rules.matching("llama3:8b", "8.0B"), ["exact"]);
+    }
+
+    #[test]
+    fn all_matching_reports_every_rule() {
+        let json = r#"{"match":"all","rules":[{"name":"big-r1","model":"deepseek-r1*","min_size":"32B"},{"name":"any-r1","model":"deepseek-r1:*"}]}"#;
+        assert_eq!(rules(json).unwrap().matching("deepseek-r1:70b", "70.6B"), ["big-r1", "any-r1"]);
+    }
+
+    #[test]
+    fn reports_bad_rules_by_number() {
+        for (json, message) in [
+            (r#"{"rules":[{"name":"ok","model":"x"},{"name":"bad","regex":"("}]}"#, r#"alerts rule 2 ("bad")"#),
+            (r#"{"rules":[{"name":"size","model":"x","min_size":"huge"}]}"#, "invalid min_size"),
+            (r#"{"rules":[{"name":"both","model":"x","regex":"x"}]}"#, "not both"),
+            (r#"{"rules":[{"name":"neither"}]}"#, "needs a"),
+        ] {
+            let error = format!("{:#}", rules(json).err().unwrap());
+            assert!(error.contains(message), "{}: {}", json, error);
+        }
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        let glob = Regex::new(&glob_to_regex("qwen?.5:*b")).unwrap();
+        assert!(glob.is_match("qwen2.5:72b"));
+        assert!(!glob.is_match("qwen2x5:72b"));
+        assert!(!glob.is_match("my-qwen2.5:72b"));
+    }
+}
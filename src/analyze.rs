@@ -0,0 +1,223 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use console::style;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::cli::StatsArgs;
+use crate::modelname;
+use crate::results::{read_model_rows, ModelRow};
+use crate::{HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+const TOP_ENTRIES: usize = 10;
+
+/// Parses Ollama parameter sizes such as "8.0B", "137M" or "7b" into a raw count.
+pub fn parse_parameter_size(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(value.find(|c: char| c.is_ascii_alphabetic())?);
+    let number: f64 = number.trim().parse().ok()?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "K" => 1e3,
+        "M" => 1e6,
+        "B" => 1e9,
+        "T" => 1e12,
+        _ => return None,
+    };
+    Some(number * multiplier)
+}
+
+pub fn parse_parameter_size_arg(value: &str) -> Result<f64, String> {
+    parse_parameter_size(value).ok_or_else(|| format!("invalid parameter size '{}', expected e.g. 7B or 500M", value))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// llama3, whatever its tag or registry
+    Base,
+    /// llama3:8b-instruct-q4_K_M, exactly as the host listed it
+    Name,
+}
+
+#[derive(Debug, Serialize)]
+struct CountEntry {
+    name: String,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ModelsPerEndpoint {
+    min: usize,
+    median: f64,
+    max: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct StatsReport {
+    model_rows: usize,
+    endpoints: usize,
+    models_per_endpoint: ModelsPerEndpoint,
+    group_by: GroupBy,
+    top_models: Vec<CountEntry>,
+    top_families: Vec<CountEntry>,
+    quantization_levels: Vec<CountEntry>,
+    total_storage_gb: f64,
+    average_storage_per_endpoint_gb: f64,
+}
+
+fn ranked(counts: HashMap<String, usize>, limit: Option<usize>) -> Vec<CountEntry> {
+    let mut entries: Vec<_> = counts
+        .into_iter()
+        .map(|(name, count)| CountEntry {
+            name: if name.is_empty() { "unknown".to_string() } else { name },
+            count,
+        })
+        .collect();
+    entries.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    entries
+}
+
+fn matches_filters(row: &ModelRow, args: &StatsArgs) -> bool {
+    if let Some(family) = &args.family {
+        if !row.family.to_ascii_lowercase().starts_with(&family.to_ascii_lowercase()) {
+            return false;
+        }
+    }
+    if let Some(min_size) = args.min_size {
+        match parse_parameter_size(&row.parameter_size) {
+            Some(size) if size >= min_size => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn build_report(rows: &[ModelRow], group_by: GroupBy) -> StatsReport {
+    let mut per_endpoint: BTreeMap<&str, (usize, f64)> = BTreeMap::new();
+    // Endpoints per model, so one listing llama3:8b and llama3:latest counts once
+    let mut models: HashMap<String, HashSet<&str>> = HashMap::new();
+    let mut families = HashMap::new();
+    let mut quants = HashMap::new();
+
+    for row in rows {
+        let entry = per_endpoint.entry(row.endpoint.as_str()).or_default();
+        entry.0 += 1;
+        entry.1 += row.size_gb;
+        let model = match group_by {
+            GroupBy::Base => modelname::normalize(&row.name).base,
+            GroupBy::Name => row.name.clone(),
+        };
+        models.entry(model).or_default().insert(row.endpoint.as_str());
+        *families.entry(row.family.clone()).or_default() += 1;
+        *quants.entry(row.quantization_level.clone()).or_default() += 1;
+    }
+
+    let mut counts: Vec<usize> = per_endpoint.values().map(|(n, _)| *n).collect();
+    counts.sort_unstable();
+    let median = match counts.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (counts[n / 2 - 1] + counts[n / 2]) as f64 / 2.0,
+        n => counts[n / 2] as f64,
+    };
+    let total_storage_gb: f64 = per_endpoint.values().map(|(_, gb)| gb).sum();
+
+    StatsReport {
+        model_rows: rows.len(),
+        endpoints: per_endpoint.len(),
+        models_per_endpoint: ModelsPerEndpoint {
+            min: counts.first().copied().unwrap_or(0),
+            median,
+            max: counts.last().copied().unwrap_or(0),
+        },
+        group_by,
+        top_models: ranked(models.into_iter().map(|(model, endpoints)| (model, endpoints.len())).collect(), Some(TOP_ENTRIES)),
+        top_families: ranked(families, Some(TOP_ENTRIES)),
+        quantization_levels: ranked(quants, None),
+        total_storage_gb,
+        average_storage_per_endpoint_gb: if per_endpoint.is_empty() {
+            0.0
+        } else {
+            total_storage_gb / per_endpoint.len() as f64
+        },
+    }
+}
+
+fn print_ranked(title: &str, entries: &[CountEntry]) {
+    println!("{}{}", LIST_ITEM_STYLE, title);
+    for (i, entry) in entries.iter().enumerate() {
+        let prefix = if i == entries.len() - 1 { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
+        println!("  {}{} {}", prefix, style(&entry.name).blue(), style(format!("({})", entry.count)).dim());
+    }
+}
+
+pub fn run_stats(args: &StatsArgs) -> Result<()> {
+    let rows: Vec<ModelRow> = read_model_rows(&args.file)?
+        .into_iter()
+        .filter(|row| matches_filters(row, args))
+        .collect();
+    let report = build_report(&rows, args.group_by);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("\n{}{}", HEADER_STYLE, style(format!("Statistics for {}", args.file.display())).blue().bold());
+    println!("{}Model rows: {}", LIST_ITEM_STYLE, style(report.model_rows).cyan());
+    println!("{}Endpoints: {}", LIST_ITEM_STYLE, style(report.endpoints).cyan());
+    println!("{}Models per endpoint: min {} / median {:.1} / max {}",
+        LIST_ITEM_STYLE,
+        report.models_per_endpoint.min,
+        report.models_per_endpoint.median,
+        report.models_per_endpoint.max
+    );
+    print_ranked(match report.group_by {
+        GroupBy::Base => "Most common models:",
+        GroupBy::Name => "Most common models, as listed:",
+    }, &report.top_models);
+    print_ranked("Most common families:", &report.top_families);
+    print_ranked("Quantization levels:", &report.quantization_levels);
+    println!("{}Storage: {:.2} GB total, {:.2} GB average per endpoint",
+        LAST_ITEM_STYLE,
+        report.total_storage_gb,
+        report.average_storage_per_endpoint_gb
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(listed: &[(&str, &str)]) -> Vec<ModelRow> {
+        listed
+            .iter()
+            .map(|(endpoint, name)| ModelRow { endpoint: endpoint.to_string(), name: name.to_string(), ..Default::default() })
+            .collect()
+    }
+
+    fn top(report: &StatsReport) -> Vec<(&str, usize)> {
+        report.top_models.iter().map(|entry| (entry.name.as_str(), entry.count)).collect()
+    }
+
+    #[test]
+    fn groups_models_by_base_or_by_name() {
+        let rows = rows(&[
+            ("http://10.0.0.1:11434", "llama3:latest"),
+            ("http://10.0.0.1:11434", "llama3:8b-instruct-q4_K_M"),
+            ("http://10.0.0.2:11434", "registry.ollama.ai/library/llama3:8b"),
+            ("http://10.0.0.2:11434", "jondurbin/airoboros"),
+            ("http://10.0.0.3:11434", "llama3:latest"),
+        ]);
+        assert_eq!(top(&build_report(&rows, GroupBy::Base)), [("llama3", 3), ("jondurbin/airoboros", 1)]);
+        assert_eq!(top(&build_report(&rows, GroupBy::Name)), [
+            ("llama3:latest", 2),
+            ("jondurbin/airoboros", 1),
+            ("llama3:8b-instruct-q4_K_M", 1),
+            ("registry.ollama.ai/library/llama3:8b", 1),
+        ]);
+    }
+}
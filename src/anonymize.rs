@@ -0,0 +1,147 @@
+//! `--anonymize`: addresses masked in what gets shared. Every rendered text
+//! of an affected output goes through `scrub`, which rewrites anything that
+//! looks like an IPv4 address, whether dotted (in URLs and hit blocks) or
+//! dashed the way PTR hostnames embed it (`ec2-203-0-113-5.compute...`), so
+//! no output needs to know where its addresses are.
+//!
+//! Like the stream flag, the choice is global: the console, the stream sink
+//! and the CSV writer all consult it.
+
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AnonymizeScope {
+    /// The console and --stream; the CSVs keep the real addresses
+    Reports,
+    /// The CSVs as well
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AnonymizeStyle {
+    /// Replace the last octet: 203.0.113.xxx
+    Mask,
+    /// Replace the whole address with a hash salted per run: ip-3f9a2c1d
+    Hash,
+}
+
+#[derive(Debug)]
+pub struct Anonymizer {
+    style: AnonymizeStyle,
+    salt: u64,
+    pattern: Regex,
+}
+
+static ACTIVE: OnceLock<(Anonymizer, AnonymizeScope)> = OnceLock::new();
+
+impl Anonymizer {
+    pub fn new(style: AnonymizeStyle, salt: u64) -> Self {
+        // Dotted or dashed, and not part of a longer dotted number such as a
+        // version; a dash before it may follow anything, as in "ec2-203-0-113-5"
+        let pattern = Regex::new(r"(?:^|[^\d.]|[^\d]\.)((\d{1,3})([.-])(\d{1,3})([.-])(\d{1,3})([.-])(\d{1,3}))(?:$|[^\d.-]|[.-](?:$|[^\d]))").unwrap();
+        Self { style, salt, pattern }
+    }
+
+    /// `text` with every address in it anonymized.
+    pub fn scrub<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.bytes().any(|b| b.is_ascii_digit()) {
+            return Cow::Borrowed(text);
+        }
+        // Matches overlap at their separators, so the text is walked by hand
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(captures) = self.pattern.captures(rest) {
+            let whole = captures.get(1).unwrap();
+            out.push_str(&rest[..whole.start()]);
+            out.push_str(&self.replace(&captures).unwrap_or_else(|| whole.as_str().to_string()));
+            rest = &rest[whole.end()..];
+        }
+        if out.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        out.push_str(rest);
+        Cow::Owned(out)
+    }
+
+    /// The replacement for one match, or None when it is not an address
+    /// (an octet over 255, or mixed separators).
+    fn replace(&self, captures: &Captures) -> Option<String> {
+        let separator = &captures[3];
+        if captures[5] != *separator || captures[7] != *separator {
+            return None;
+        }
+        let mut octets = [0u8; 4];
+        for (octet, group) in octets.iter_mut().zip([2, 4, 6, 8]) {
+            *octet = captures[group].parse().ok()?;
+        }
+        Some(match self.style {
+            AnonymizeStyle::Mask => format!("{}{sep}{}{sep}{}{sep}xxx", octets[0], octets[1], octets[2], sep = separator),
+            AnonymizeStyle::Hash => format!("ip-{:08x}", self.hash(octets) as u32),
+        })
+    }
+
+    fn hash(&self, octets: [u8; 4]) -> u64 {
+        self.salt
+            .to_le_bytes()
+            .iter()
+            .chain(&octets)
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+}
+
+/// Turns anonymizing on for the rest of the process, with a fresh salt.
+pub fn install(scope: AnonymizeScope, style: AnonymizeStyle) {
+    let _ = ACTIVE.set((Anonymizer::new(style, rand::random()), scope));
+}
+
+/// For the console, `--stream` and exports.
+pub fn for_reports(text: &str) -> Cow<'_, str> {
+    match ACTIVE.get() {
+        Some((anonymizer, _)) => anonymizer.scrub(text),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// For the CSVs, which only `--anonymize all` touches.
+pub fn for_records(text: &str) -> Cow<'_, str> {
+    match ACTIVE.get() {
+        Some((anonymizer, AnonymizeScope::All)) => anonymizer.scrub(text),
+        _ => Cow::Borrowed(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_the_last_octet_wherever_an_address_appears() {
+        let mask = Anonymizer::new(AnonymizeStyle::Mask, 0);
+        assert_eq!(mask.scrub("http://203.0.113.5:11434/api/tags"), "http://203.0.113.xxx:11434/api/tags");
+        assert_eq!(mask.scrub("203.0.113.5, 198.51.100.77"), "203.0.113.xxx, 198.51.100.xxx");
+        assert_eq!(mask.scrub("ec2-203-0-113-5.compute-1.amazonaws.com"), "ec2-203-0-113-xxx.compute-1.amazonaws.com");
+        assert_eq!(mask.scrub("Connect to 203.0.113.5."), "Connect to 203.0.113.xxx.");
+        assert_eq!(mask.scrub("Found 12 Ollama endpoints"), "Found 12 Ollama endpoints");
+    }
+
+    #[test]
+    fn leaves_what_is_not_an_address() {
+        let mask = Anonymizer::new(AnonymizeStyle::Mask, 0);
+        for text in ["version 0.1.32", "2024-05-01T10:00:00Z", "1.2.3.4.5", "300.1.1.1", "10.0-0.1", "sha256:365c0bd3c000"] {
+            assert_eq!(mask.scrub(text), text);
+        }
+    }
+
+    #[test]
+    fn hashes_addresses_consistently_per_salt() {
+        let hash = Anonymizer::new(AnonymizeStyle::Hash, 7);
+        let once = hash.scrub("http://203.0.113.5:11434").into_owned();
+        assert!(once.starts_with("http://ip-") && once.ends_with(":11434") && !once.contains("203.0"), "{}", once);
+        assert_eq!(hash.scrub("203.0.113.5"), hash.scrub("203-0-113-5"));
+        assert_ne!(hash.scrub("203.0.113.5"), hash.scrub("203.0.113.6"));
+        assert_ne!(hash.scrub("203.0.113.5"), Anonymizer::new(AnonymizeStyle::Hash, 8).scrub("203.0.113.5"));
+    }
+}
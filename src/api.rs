@@ -0,0 +1,475 @@
+//! `--serve`: a small REST API for driving the scanner from other services.
+//!
+//! - `POST /targets` queues the CIDRs, ranges and IPs in the body, parsed as
+//!   lines of ip-ranges.txt are
+//! - `GET /status` describes the scan as `s` does
+//! - `GET /findings?since=ID&limit=N` returns the findings after `since`
+//! - `POST /control` with `{"action": "pause" | "resume" | "stop"}`
+//!
+//! Every request needs `Authorization: Bearer <api.token>` from the config
+//! file. Findings reach the API as a sink, like `--stream`, and control goes
+//! through the same calls as the keys. Posted targets wait in a bounded
+//! queue and their hosts reach the scan as fast as it takes them.
+
+use anyhow::{Context, Result};
+use console::style;
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::control::{self, State};
+use crate::dashboard::Dashboard;
+use crate::sinks::{Finding, SinkEvent};
+use crate::snapshot::Snapshot;
+use crate::targets::{Provenance, Target};
+use crate::{anonymize, console_log, ranges_in, ListedHost};
+
+/// Posted ranges waiting for the scan; more are refused with 503
+const PENDING_TARGETS: usize = 10_000;
+
+/// Hosts handed to the scan ahead of their probes
+const HOST_BUFFER: usize = 1024;
+
+/// Findings kept for `GET /findings`; older ones are gone
+const FINDINGS_KEPT: usize = 10_000;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+const MAX_BODY: usize = 1024 * 1024;
+
+/// The `api` section of the config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// The bearer token every request must carry
+    pub token: Option<String>,
+}
+
+/// The findings of the run for `GET /findings`, numbered from 1.
+#[derive(Debug, Default)]
+pub struct FindingLog {
+    kept: Mutex<Kept>,
+}
+
+#[derive(Debug, Default)]
+struct Kept {
+    findings: VecDeque<(u64, Arc<Finding>)>,
+    last_id: u64,
+}
+
+impl FindingLog {
+    fn push(&self, finding: Arc<Finding>) {
+        let mut kept = self.kept.lock().unwrap();
+        kept.last_id += 1;
+        let id = kept.last_id;
+        if kept.findings.len() == FINDINGS_KEPT {
+            kept.findings.pop_front();
+        }
+        kept.findings.push_back((id, finding));
+    }
+
+    /// Up to `limit` findings numbered after `since`, oldest first.
+    fn since(&self, since: u64, limit: usize) -> Vec<(u64, Arc<Finding>)> {
+        let kept = self.kept.lock().unwrap();
+        kept.findings.iter().filter(|(id, _)| *id > since).take(limit).cloned().collect()
+    }
+}
+
+/// The sink filling `log`.
+pub async fn collect(log: Arc<FindingLog>, mut receiver: mpsc::Receiver<SinkEvent>) {
+    while let Some(event) = receiver.recv().await {
+        if let SinkEvent::Finding(finding) = event {
+            log.push(finding);
+        }
+    }
+}
+
+pub type StatusFn = Box<dyn Fn() -> Snapshot + Send + Sync>;
+
+/// Describes the running cycle. It is taken away when the cycle ends, so
+/// that a client still connected holds none of the cycle's channels open.
+pub type StatusSource = Arc<Mutex<Option<StatusFn>>>;
+
+/// The HTTP listeners of a run, `--serve`'s API and `--dashboard`, and what
+/// they show.
+#[derive(Default)]
+pub struct Listeners {
+    pub api: Option<Server>,
+    pub dashboard: Option<Dashboard>,
+    pub findings: Arc<FindingLog>,
+    pub status: StatusSource,
+}
+
+impl Listeners {
+    pub fn is_empty(&self) -> bool {
+        self.api.is_none() && self.dashboard.is_none()
+    }
+
+    pub fn attach(&self, status: impl Fn() -> Snapshot + Send + Sync + 'static) {
+        *self.status.lock().unwrap() = Some(Box::new(status));
+    }
+
+    pub fn detach(&self) {
+        self.status.lock().unwrap().take();
+    }
+}
+
+/// A listening socket for `--serve` or `--dashboard`, bound before the scan
+/// starts so a taken address or a missing token stops it early.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    token: String,
+}
+
+impl Server {
+    /// `flag` names the option in errors.
+    pub fn bind(address: SocketAddr, config: &ApiConfig, flag: &str) -> Result<Self> {
+        let token = match config.token.as_deref() {
+            Some(token) if !token.is_empty() => token.to_string(),
+            _ => anyhow::bail!("{} needs a token in the api section of the config file, e.g. {{\"api\": {{\"token\": \"...\"}}}}", flag),
+        };
+        let listener = TcpListener::bind(address).with_context(|| format!("Failed to listen on {}", address))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, token })
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    /// Serves the API for one scan, which `status` describes and whose
+    /// findings `findings` collects. The scan takes the posted hosts from
+    /// the receiver, which ends once the scan is stopped.
+    pub fn start(&self, status: StatusSource, findings: Arc<FindingLog>) -> Result<(mpsc::Receiver<ListedHost>, JoinHandle<()>)> {
+        let (targets, pending) = mpsc::channel(PENDING_TARGETS);
+        let (hosts, scanned) = mpsc::channel(HOST_BUFFER);
+        let queued = Arc::new(AtomicU64::new(0));
+        tokio::spawn(hand_over(pending, hosts, queued.clone()));
+
+        let shared = Arc::new(Shared { status, findings, targets, queued });
+        let task = self.spawn(false, move |request| handle(shared.clone(), request))?;
+        Ok((scanned, task))
+    }
+
+    /// Answers the requests that carry the token with `handle`. With
+    /// `query_token` the token may also come as `?token=`, as from a browser.
+    pub fn spawn<F, Fut>(&self, query_token: bool, handle: F) -> Result<JoinHandle<()>>
+    where
+        F: Fn(Request<Body>) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = Response<Body>> + Send + 'static,
+    {
+        let token: Arc<str> = self.token.as_str().into();
+        let make_service = make_service_fn(move |_| {
+            let (handle, token) = (handle.clone(), token.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let answer = authorized(&request, &token, query_token).then(|| handle(request));
+                    async move {
+                        Ok::<_, Infallible>(match answer {
+                            Some(answer) => answer.await,
+                            None => error(StatusCode::UNAUTHORIZED, "missing or wrong token"),
+                        })
+                    }
+                }))
+            }
+        });
+        let server = hyper::Server::from_tcp(self.listener.try_clone()?)?.serve(make_service);
+        Ok(tokio::spawn(async move {
+            if let Err(e) = server.await {
+                console_log(style(format!("HTTP server failed: {}", e)).red().to_string());
+            }
+        }))
+    }
+}
+
+struct Shared {
+    status: StatusSource,
+    findings: Arc<FindingLog>,
+    targets: mpsc::Sender<Target>,
+    /// Hosts posted but not yet handed to the scan
+    queued: Arc<AtomicU64>,
+}
+
+/// Passes the hosts of the posted targets to the scan until it stops.
+async fn hand_over(mut pending: mpsc::Receiver<Target>, hosts: mpsc::Sender<ListedHost>, queued: Arc<AtomicU64>) {
+    let mut index = 0;
+    loop {
+        let target = tokio::select! {
+            target = pending.recv() => target,
+            _ = stopped() => None,
+        };
+        let Some(target) = target else { return };
+        let provenance = Arc::new(target.provenance.clone());
+        for ip in target.hosts() {
+            // Waits while the scan is behind; fails once it has stopped
+            if hosts.send((index, ip, provenance.clone())).await.is_err() {
+                return;
+            }
+            queued.fetch_sub(1, Ordering::Relaxed);
+            index += 1;
+        }
+    }
+}
+
+async fn stopped() {
+    while control::state() != State::Stopping {
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+async fn handle(shared: Arc<Shared>, request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path().to_string();
+    let response = match (request.method().clone(), path.as_str()) {
+        (Method::GET, "/status") => status(&shared),
+        (Method::GET, "/findings") => findings(&shared.findings, request.uri().query().unwrap_or("")),
+        (Method::POST, "/targets") => match read_body(request.into_body()).await {
+            Some(body) => targets(&shared, &String::from_utf8_lossy(&body)),
+            None => error(StatusCode::PAYLOAD_TOO_LARGE, "the body is over 1 MiB"),
+        },
+        (Method::POST, "/control") => match read_body(request.into_body()).await {
+            Some(body) => control(&body),
+            None => error(StatusCode::PAYLOAD_TOO_LARGE, "the body is over 1 MiB"),
+        },
+        (_, "/status" | "/findings" | "/targets" | "/control") => error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+        _ => error(StatusCode::NOT_FOUND, "not found"),
+    };
+    response
+}
+
+fn authorized(request: &Request<Body>, token: &str, query_token: bool) -> bool {
+    let header = request.headers().get(hyper::header::AUTHORIZATION).and_then(|value| value.as_bytes().strip_prefix(b"Bearer "));
+    let query = || {
+        request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix("token=")).map(str::as_bytes)
+    };
+    let Some(given) = header.or_else(|| query_token.then(query).flatten()) else {
+        return false;
+    };
+    // Compared in full so the time taken doesn't tell how much matched
+    given.len() == token.len() && given.iter().zip(token.as_bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn read_body(mut body: Body) -> Option<Vec<u8>> {
+    let mut read = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let Ok(chunk) = chunk else { break };
+        if read.len() + chunk.len() > MAX_BODY {
+            return None;
+        }
+        read.extend_from_slice(&chunk);
+    }
+    Some(read)
+}
+
+pub fn json(status: StatusCode, value: &impl Serialize) -> Response<Body> {
+    let body = serde_json::to_string(value).unwrap_or_default();
+    let body = anonymize::for_reports(&body).into_owned();
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+pub fn error(status: StatusCode, message: &str) -> Response<Body> {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+#[derive(Serialize)]
+struct Status {
+    state: State,
+    active_secs: u64,
+    paused_secs: u64,
+    in_flight: usize,
+    scanned: u64,
+    queued_hosts: u64,
+    hits: u64,
+    other_hits: u64,
+    /// Newest first
+    recent: Vec<String>,
+    errors: String,
+    rate: f64,
+    concurrency: usize,
+}
+
+fn status(shared: &Shared) -> Response<Body> {
+    let Some(snapshot) = shared.status.lock().unwrap().as_ref().map(|status| status()) else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "the scan has ended");
+    };
+    json(StatusCode::OK, &Status {
+        state: control::state(),
+        active_secs: snapshot.active.as_secs(),
+        paused_secs: snapshot.paused_total.as_secs(),
+        in_flight: snapshot.in_flight,
+        scanned: snapshot.scanned,
+        queued_hosts: shared.queued.load(Ordering::Relaxed),
+        hits: snapshot.hits,
+        other_hits: snapshot.other_hits,
+        recent: snapshot.recent,
+        errors: snapshot.errors,
+        rate: snapshot.rate,
+        concurrency: snapshot.concurrency,
+    })
+}
+
+#[derive(Serialize)]
+struct Numbered<'a> {
+    id: u64,
+    #[serde(flatten)]
+    finding: &'a Finding,
+}
+
+/// `GET /findings`, also served by the dashboard.
+pub fn findings(log: &FindingLog, query: &str) -> Response<Body> {
+    let (mut since, mut limit) = (0, DEFAULT_LIMIT);
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        let parsed = match key {
+            "since" => value.parse().map(|value| since = value),
+            "limit" => value.parse().map(|value: usize| limit = value.clamp(1, MAX_LIMIT)),
+            _ => Ok(()),
+        };
+        if parsed.is_err() {
+            return error(StatusCode::BAD_REQUEST, &format!("{} must be a number", key));
+        }
+    }
+    let found = log.since(since, limit);
+    let next = found.last().map_or(since, |(id, _)| *id);
+    let findings: Vec<_> = found.iter().map(|(id, finding)| Numbered { id: *id, finding }).collect();
+    json(StatusCode::OK, &serde_json::json!({ "findings": findings, "next": next }))
+}
+
+fn targets(shared: &Shared, body: &str) -> Response<Body> {
+    if control::state() == State::Stopping {
+        return error(StatusCode::CONFLICT, "the scan is stopping");
+    }
+    let (listed, warnings) = ranges_in(body, Path::new("api"), false, 1);
+    if listed.is_empty() {
+        return json(StatusCode::BAD_REQUEST, &serde_json::json!({ "error": "no valid targets", "warnings": warnings }));
+    }
+    if shared.targets.capacity() < listed.len() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "too many targets are waiting; try again later");
+    }
+    let (ranges, mut hosts) = (listed.len(), 0);
+    for (blocks, label, source) in listed {
+        let target = Target::new(blocks, Provenance::new(label, source));
+        let count = target.host_count();
+        shared.queued.fetch_add(count, Ordering::Relaxed);
+        if shared.targets.try_send(target).is_err() {
+            shared.queued.fetch_sub(count, Ordering::Relaxed);
+            return error(StatusCode::SERVICE_UNAVAILABLE, "the scan is no longer taking targets");
+        }
+        hosts += count;
+    }
+    json(StatusCode::ACCEPTED, &serde_json::json!({ "ranges": ranges, "hosts": hosts, "warnings": warnings }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Pause,
+    Resume,
+    Stop,
+}
+
+#[derive(Deserialize)]
+struct ControlRequest {
+    action: Action,
+}
+
+fn control(body: &[u8]) -> Response<Body> {
+    let request: ControlRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("expected {{\"action\": \"pause\" | \"resume\" | \"stop\"}}: {}", e)),
+    };
+    let changed = match request.action {
+        Action::Pause => control::pause("api"),
+        Action::Resume => control::resume("api"),
+        Action::Stop => control::stop("api"),
+    };
+    if changed {
+        let message = match request.action {
+            Action::Pause => style("Scan paused through the API").yellow(),
+            Action::Resume => style("Scan resumed through the API").green(),
+            Action::Stop => style("Stop requested through the API").yellow(),
+        };
+        console_log(message.to_string());
+    }
+    json(StatusCode::OK, &serde_json::json!({ "state": control::state(), "changed": changed }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(ip: &str) -> Arc<Finding> {
+        Arc::new(Finding {
+            ip: ip.to_string(),
+            port: 11434,
+            base_url: format!("http://{}:11434", ip),
+            tags_url: format!("http://{}:11434/api/tags", ip),
+            status: 200,
+            location: "CIDR".to_string(),
+            source: "api:1".to_string(),
+            latency_ms: 12,
+            timestamp: "2024-05-01T12:00:00Z".to_string(),
+            run_id: "20240501T120000Z".to_string(),
+            tag: String::new(),
+            models: Vec::new(),
+            alerts: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn pages_through_findings_by_id() {
+        let log = FindingLog::default();
+        for host in 1..=5 {
+            log.push(finding(&format!("203.0.113.{}", host)));
+        }
+        let ids = |found: Vec<(u64, Arc<Finding>)>| found.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids(log.since(0, 2)), [1, 2]);
+        assert_eq!(ids(log.since(2, 100)), [3, 4, 5]);
+        assert!(log.since(5, 100).is_empty());
+    }
+
+    #[test]
+    fn keeps_only_the_latest_findings() {
+        let log = FindingLog::default();
+        for _ in 0..FINDINGS_KEPT + 3 {
+            log.push(finding("203.0.113.1"));
+        }
+        let found = log.since(0, 1);
+        assert_eq!(found[0].0, 4);
+    }
+
+    #[test]
+    fn checks_the_bearer_token() {
+        let request = |header: Option<&str>| {
+            let mut builder = Request::builder().uri("/status");
+            if let Some(header) = header {
+                builder = builder.header("Authorization", header);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        assert!(authorized(&request(Some("Bearer s3cret")), "s3cret", false));
+        assert!(!authorized(&request(Some("Bearer s3cre")), "s3cret", false));
+        assert!(!authorized(&request(Some("s3cret")), "s3cret", false));
+        assert!(!authorized(&request(None), "s3cret", false));
+
+        let query = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        assert!(authorized(&query("/?token=s3cret"), "s3cret", true));
+        assert!(!authorized(&query("/?token=s3cret"), "s3cret", false));
+        assert!(!authorized(&query("/?token=wrong&x=s3cret"), "s3cret", true));
+    }
+}
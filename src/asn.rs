@@ -0,0 +1,145 @@
+//! `--asn-db`: which autonomous system an address belongs to, for
+//! `--max-rate-per-asn`.
+//!
+//! The table is either a MaxMind ASN database (GeoLite2-ASN .mmdb) or a text
+//! prefix table with one `prefix ASN [name]` per line, separated by spaces,
+//! tabs or commas, as pyasn and most route dumps write them:
+//!
+//! ```text
+//! 1.0.0.0/24      13335   Cloudflare
+//! 3.0.0.0/15,AS16509,Amazon
+//! ```
+//!
+//! Either is flattened at startup into sorted, non-overlapping intervals the
+//! way the provider feeds are, so a lookup is one binary search and the more
+//! specific prefix wins where announcements overlap.
+
+use anyhow::{bail, Context, Result};
+use ipnet::Ipv4Net;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use crate::geo::Mmdb;
+use crate::provider::flatten;
+
+pub struct AsnTable {
+    /// Sorted, non-overlapping (first, last, ASN)
+    intervals: Vec<(u32, u32, u32)>,
+    names: HashMap<u32, String>,
+}
+
+impl AsnTable {
+    pub fn load(path: &Path) -> Result<Self> {
+        let networks = match path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mmdb")) {
+            true => Mmdb::open(path)?.asn_networks().with_context(|| format!("Failed to read the networks of {}", path.display()))?,
+            false => {
+                let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                let (networks, skipped) = parse_prefix_table(&text);
+                if skipped > 0 {
+                    eprintln!("Warning: skipped {} line{} of {} that are not 'prefix ASN'", skipped, if skipped == 1 { "" } else { "s" }, path.display());
+                }
+                networks
+            }
+        };
+        if networks.is_empty() {
+            bail!("{} lists no IPv4 prefix with an ASN", path.display());
+        }
+        Ok(Self::from_networks(networks))
+    }
+
+    fn from_networks(networks: Vec<(Ipv4Net, u32, Option<String>)>) -> Self {
+        let mut names = HashMap::new();
+        let mut prefixes = Vec::with_capacity(networks.len());
+        for (network, asn, name) in networks {
+            if let Some(name) = name.filter(|name| !name.is_empty()) {
+                names.entry(asn).or_insert(name);
+            }
+            prefixes.push((network, asn));
+        }
+        Self { intervals: flatten(prefixes), names }
+    }
+
+    #[cfg(test)]
+    pub fn from_prefix_table(text: &str) -> Self {
+        Self::from_networks(parse_prefix_table(text).0)
+    }
+
+    /// The autonomous system `ip` is announced by, if the table has it.
+    pub fn asn(&self, ip: Ipv4Addr) -> Option<u32> {
+        let ip = u32::from(ip);
+        let after = self.intervals.partition_point(|&(first, _, _)| first <= ip);
+        match after.checked_sub(1).map(|i| self.intervals[i]) {
+            Some((_, last, asn)) if ip <= last => Some(asn),
+            _ => None,
+        }
+    }
+
+    /// "AS13335 (Cloudflare)", or "AS13335" without a name.
+    pub fn label(&self, asn: u32) -> String {
+        match self.names.get(&asn) {
+            Some(name) => format!("AS{} ({})", asn, name),
+            None => format!("AS{}", asn),
+        }
+    }
+
+    /// "812345 ranges", for the banner.
+    pub fn describe(&self) -> String {
+        format!("{} ranges", self.intervals.len())
+    }
+}
+
+/// The IPv4 lines of a `prefix ASN [name]` table, and how many other lines
+/// were neither blank, comments nor IPv6.
+fn parse_prefix_table(text: &str) -> (Vec<(Ipv4Net, u32, Option<String>)>, usize) {
+    let mut networks = Vec::new();
+    let mut skipped = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let (prefix, rest) = split_field(line);
+        if prefix.contains(':') {
+            continue;
+        }
+        let (asn, name) = split_field(rest);
+        let asn = asn.strip_prefix("AS").or_else(|| asn.strip_prefix("as")).unwrap_or(asn);
+        match (prefix.parse::<Ipv4Net>(), asn.parse::<u32>()) {
+            (Ok(network), Ok(asn)) => {
+                let name = name.trim_matches('"');
+                networks.push((network.trunc(), asn, (!name.is_empty()).then(|| name.to_string())));
+            }
+            _ => skipped += 1,
+        }
+    }
+    (networks, skipped)
+}
+
+/// The first field of `line` and the rest after its separator.
+fn split_field(line: &str) -> (&str, &str) {
+    match line.find([',', '\t', ' ']) {
+        Some(at) => (&line[..at], line[at + 1..].trim_start_matches([',', '\t', ' '])),
+        None => (line, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_prefix_tables_and_prefers_the_narrowest_prefix() {
+        let (networks, skipped) = parse_prefix_table(
+            "# pyasn\n10.0.0.0/8 \t 64500\n10.1.0.0/16,AS64501,Example Hosting\n2001:db8::/32 64502\n10.2.0.0/16 {64503,64504}\n",
+        );
+        assert_eq!(skipped, 1);
+        let table = AsnTable::from_networks(networks);
+        assert_eq!(table.asn(Ipv4Addr::new(10, 0, 0, 1)), Some(64500));
+        assert_eq!(table.asn(Ipv4Addr::new(10, 1, 200, 1)), Some(64501));
+        assert_eq!(table.asn(Ipv4Addr::new(10, 2, 0, 1)), Some(64500));
+        assert_eq!(table.asn(Ipv4Addr::new(11, 0, 0, 1)), None);
+        assert_eq!(table.label(64501), "AS64501 (Example Hosting)");
+        assert_eq!(table.label(64500), "AS64500");
+        assert_eq!(table.describe(), "3 ranges");
+    }
+}
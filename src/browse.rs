@@ -0,0 +1,441 @@
+//! An interactive list of found endpoints, offered when a scan ends and
+//! available over earlier CSVs with the `browse` subcommand.
+//!
+//! Keys: ↑/↓ (or k/j), PgUp/PgDn, Home/End move; Enter shows or hides the
+//! selected endpoint's models; `/` filters by substring; `e` exports what the
+//! filter shows; `q` leaves.
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::BrowseArgs;
+use crate::results::{endpoint_key, read_endpoint_rows, read_model_rows};
+use crate::{console_log, ScanResult, LAST_ITEM_STYLE};
+
+/// One endpoint as the browser lists it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub endpoint: String,
+    pub location: String,
+    /// A line per model, e.g. "llama3:8b (8.0B, Q4_0, 4.34 GB)"
+    pub models: Vec<String>,
+}
+
+impl Entry {
+    pub fn from_result(result: &ScanResult) -> Self {
+        let names: BTreeSet<&str> = result.models.iter().map(|(name, _)| name.as_str()).collect();
+        Self {
+            endpoint: result.base_url.clone(),
+            location: result.location.clone(),
+            models: names.into_iter().map(str::to_string).collect(),
+        }
+    }
+
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        [&self.endpoint, &self.location].into_iter().chain(&self.models).any(|text| text.to_lowercase().contains(&filter))
+    }
+}
+
+/// The `browse` subcommand: endpoints that answered 200 in `--endpoints`,
+/// each once, with the models `--models` lists for them.
+pub fn run_browse(args: &BrowseArgs) -> Result<()> {
+    browse(load_entries(&args.endpoints, &args.models)?)
+}
+
+fn load_entries(endpoints: &Path, models: &Path) -> Result<Vec<Entry>> {
+    let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+    for row in read_endpoint_rows(endpoints)?.into_iter().filter(|row| row.status == "200") {
+        let key = endpoint_key(&row.endpoint);
+        if key.is_empty() {
+            continue;
+        }
+        // A later run's location wins
+        entries.insert(key.clone(), Entry { endpoint: format!("http://{}", key), location: row.location, models: Vec::new() });
+    }
+    if models.exists() {
+        let mut seen = HashSet::new();
+        for row in read_model_rows(models)? {
+            let key = endpoint_key(&row.endpoint);
+            let Some(entry) = entries.get_mut(&key) else { continue };
+            if !seen.insert((key, row.name.clone())) {
+                continue;
+            }
+            let size = (row.size_gb > 0.0).then(|| format!("{:.2} GB", row.size_gb));
+            let details: Vec<String> = [Some(row.parameter_size), Some(row.quantization_level), size]
+                .into_iter()
+                .flatten()
+                .filter(|detail| !detail.is_empty())
+                .collect();
+            entry.models.push(match details.is_empty() {
+                true => row.name,
+                false => format!("{} ({})", row.name, details.join(", ")),
+            });
+        }
+    }
+    Ok(entries.into_values().collect())
+}
+
+/// After a scan in raw mode: browses what it found if the next key is `b`.
+pub fn offer(found: &[ScanResult]) -> Result<()> {
+    if found.is_empty() {
+        return Ok(());
+    }
+    console_log(format!("{}{}", LAST_ITEM_STYLE, style(format!("Press b to browse the {} endpoints found, any other key to exit", found.len())).bold()));
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            if key.code == KeyCode::Char('b') {
+                browse(found.iter().map(Entry::from_result).collect())?;
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// Takes over the terminal until `q`, then restores it as it was.
+pub fn browse(entries: Vec<Entry>) -> Result<()> {
+    if entries.is_empty() {
+        console_log(style("No endpoints to browse").yellow().to_string());
+        return Ok(());
+    }
+    if !io::stdout().is_terminal() {
+        bail!("Browsing needs an interactive terminal");
+    }
+    let mut browser = Browser::new(entries);
+    let _screen = Screen::enter()?;
+    let mut out = io::stdout();
+    loop {
+        // Some terminals report 0x0
+        let (width, height) = match terminal::size()? {
+            (0, _) | (_, 0) => (80, 24),
+            size => size,
+        };
+        draw(&mut out, &browser.render(width as usize, height as usize))?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        match browser.handle(key) {
+            Action::Continue => {}
+            Action::Quit => return Ok(()),
+            Action::Export => {
+                browser.status = match export(&browser.shown()) {
+                    Ok((path, count)) => format!("Exported {} endpoints to {}", count, path.display()),
+                    Err(e) => format!("Export failed: {:#}", e),
+                };
+            }
+        }
+    }
+}
+
+/// The alternate screen in raw mode, left again on drop even after an error.
+struct Screen {
+    was_raw: bool,
+}
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        let was_raw = terminal::is_raw_mode_enabled()?;
+        if !was_raw {
+            terminal::enable_raw_mode()?;
+        }
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Self { was_raw })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        if !self.was_raw {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+/// Repaints in place, line by line inside one synchronized update, so
+/// terminals that support it never show a half-drawn frame.
+fn draw(out: &mut impl Write, lines: &[String]) -> io::Result<()> {
+    queue!(out, BeginSynchronizedUpdate)?;
+    for (row, line) in lines.iter().enumerate() {
+        queue!(out, MoveTo(0, row as u16), Print(line), Clear(ClearType::UntilNewLine))?;
+    }
+    queue!(out, Clear(ClearType::FromCursorDown), EndSynchronizedUpdate)?;
+    out.flush()
+}
+
+/// Writes the entries to `browse-<time>.csv` in the working directory.
+fn export(entries: &[&Entry]) -> Result<(PathBuf, usize)> {
+    let path = PathBuf::from(format!("browse-{}.csv", chrono::Utc::now().format("%Y%m%dT%H%M%SZ")));
+    let mut writer = csv::Writer::from_path(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    writer.write_record(["IP:Port", "Location", "Models"])?;
+    for entry in entries {
+        writer.write_record([&entry.endpoint, &entry.location, &entry.models.join("; ")])?;
+    }
+    writer.flush()?;
+    Ok((path, entries.len()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Quit,
+    Export,
+}
+
+struct Browser {
+    entries: Vec<Entry>,
+    filter: String,
+    /// Keys go to the filter instead of moving the selection
+    editing: bool,
+    /// Indices into `entries` that pass the filter, in order
+    visible: Vec<usize>,
+    /// Position of the selection within `visible`
+    selected: usize,
+    /// First list row on screen
+    top: usize,
+    /// Entries whose models are shown
+    expanded: HashSet<usize>,
+    /// List rows that fit on the screen, as of the last render
+    page: usize,
+    status: String,
+}
+
+impl Browser {
+    fn new(entries: Vec<Entry>) -> Self {
+        let visible = (0..entries.len()).collect();
+        Self {
+            entries,
+            filter: String::new(),
+            editing: false,
+            visible,
+            selected: 0,
+            top: 0,
+            expanded: HashSet::new(),
+            page: 10,
+            status: String::new(),
+        }
+    }
+
+    fn shown(&self) -> Vec<&Entry> {
+        self.visible.iter().map(|&i| &self.entries[i]).collect()
+    }
+
+    fn refilter(&mut self) {
+        let current = self.visible.get(self.selected).copied();
+        self.visible = (0..self.entries.len()).filter(|&i| self.entries[i].matches(&self.filter)).collect();
+        // Keep the selection on the same endpoint when it still passes
+        self.selected = current.and_then(|entry| self.visible.iter().position(|&i| i == entry)).unwrap_or(0);
+        self.top = 0;
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        if self.editing {
+            match key.code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Enter => self.editing = false,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.editing = false;
+                }
+                _ => return Action::Continue,
+            }
+            self.refilter();
+            return Action::Continue;
+        }
+        let last = self.visible.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Esc if self.filter.is_empty() => return Action::Quit,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.refilter();
+            }
+            KeyCode::Char('e') => return Action::Export,
+            KeyCode::Char('/') => {
+                self.editing = true;
+                self.status.clear();
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(self.page),
+            KeyCode::PageDown => self.selected = (self.selected + self.page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if let Some(&entry) = self.visible.get(self.selected) {
+                    if !self.expanded.remove(&entry) {
+                        self.expanded.insert(entry);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// The list as rows: (position in `visible`, text). An expanded entry is
+    /// followed by a row per model.
+    fn rows(&self) -> Vec<(usize, String)> {
+        let mut rows = Vec::new();
+        for (position, &index) in self.visible.iter().enumerate() {
+            let entry = &self.entries[index];
+            let marker = match (entry.models.is_empty(), self.expanded.contains(&index)) {
+                (true, _) => ' ',
+                (false, true) => '▾',
+                (false, false) => '▸',
+            };
+            rows.push((position, format!("{} {}  {}  {} models", marker, entry.endpoint, entry.location, entry.models.len())));
+            if self.expanded.contains(&index) {
+                for (i, model) in entry.models.iter().enumerate() {
+                    let branch = if i == entry.models.len() - 1 { "╰─" } else { "├─" };
+                    rows.push((position, format!("    {} {}", branch, model)));
+                }
+            }
+        }
+        rows
+    }
+
+    /// The screen's lines: a title, the part of the list around the
+    /// selection, and a status line.
+    fn render(&mut self, width: usize, height: usize) -> Vec<String> {
+        let page = height.saturating_sub(2).max(1);
+        self.page = page;
+        let rows = self.rows();
+        let first_row = rows.iter().position(|(position, _)| *position == self.selected).unwrap_or(0);
+        let last_row = rows.iter().rposition(|(position, _)| *position == self.selected).unwrap_or(0);
+        // Scroll just enough to show the selected endpoint and as many of its models as fit
+        if first_row < self.top {
+            self.top = first_row;
+        } else if last_row >= self.top + page {
+            self.top = (last_row + 1 - page).min(first_row);
+        }
+
+        let fit = |text: &str| console::truncate_str(text, width.max(2), "…").into_owned();
+        let title = format!("{} endpoints{}", self.visible.len(), match self.filter.is_empty() {
+            true => String::new(),
+            false => format!(" matching \"{}\" of {}", self.filter, self.entries.len()),
+        });
+        let mut lines = vec![style(fit(&format!("╭─ 🌐 {}", title))).blue().bold().to_string()];
+        for (position, text) in rows.iter().skip(self.top).take(page) {
+            let text = fit(text);
+            lines.push(match *position == self.selected && !text.starts_with("    ") {
+                true => style(text).reverse().to_string(),
+                false if text.starts_with("    ") => style(text).dim().to_string(),
+                false => text,
+            });
+        }
+        while lines.len() < page + 1 {
+            lines.push(String::new());
+        }
+        let footer = if self.editing {
+            format!("/{}▏  Enter keep • Esc clear", self.filter)
+        } else if !self.status.is_empty() {
+            self.status.clone()
+        } else {
+            "↑↓ move • Enter models • / filter • e export • q quit".to_string()
+        };
+        lines.push(style(fit(&footer)).dim().to_string());
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(endpoint: &str, location: &str, models: &[&str]) -> Entry {
+        Entry { endpoint: endpoint.to_string(), location: location.to_string(), models: models.iter().map(|m| m.to_string()).collect() }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn browser() -> Browser {
+        Browser::new(vec![
+            entry("http://10.0.0.1:11434", "DE", &["llama3:8b", "mistral:7b"]),
+            entry("http://10.0.0.2:11434", "US", &["qwen2.5:72b"]),
+            entry("http://10.0.0.3:11434", "DE", &[]),
+        ])
+    }
+
+    #[test]
+    fn filters_on_endpoint_location_and_models() {
+        let mut browser = browser();
+        browser.handle(key(KeyCode::Down));
+        for c in "/QWEN".chars() {
+            browser.handle(key(KeyCode::Char(c)));
+        }
+        assert_eq!(browser.visible, [1]);
+        // The selected endpoint still passes, so it stays selected
+        assert_eq!(browser.selected, 0);
+        browser.handle(key(KeyCode::Enter));
+        assert!(!browser.editing);
+        assert_eq!(browser.shown()[0].endpoint, "http://10.0.0.2:11434");
+
+        browser.handle(key(KeyCode::Char('/')));
+        browser.handle(key(KeyCode::Esc));
+        assert_eq!(browser.visible, [0, 1, 2]);
+        assert_eq!(browser.selected, 1);
+        browser.handle(key(KeyCode::Char('/')));
+        browser.handle(key(KeyCode::Char('d')));
+        browser.handle(key(KeyCode::Char('e')));
+        assert_eq!(browser.visible, [0, 2]);
+        assert_eq!(browser.handle(key(KeyCode::Enter)), Action::Continue);
+        assert_eq!(browser.handle(key(KeyCode::Char('e'))), Action::Export);
+    }
+
+    #[test]
+    fn expands_models_and_keeps_the_selection_on_screen() {
+        let mut browser = browser();
+        browser.handle(key(KeyCode::Enter));
+        let rows: Vec<String> = browser.rows().into_iter().map(|(_, text)| text).collect();
+        assert_eq!(rows[1], "    ├─ llama3:8b");
+        assert_eq!(rows[2], "    ╰─ mistral:7b");
+        assert_eq!(rows.len(), 5);
+
+        // Room for two list rows: moving to the last endpoint scrolls past the models
+        browser.handle(key(KeyCode::End));
+        let lines = browser.render(80, 4);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(browser.top, 3);
+        assert!(lines[2].contains("10.0.0.3"), "{:?}", lines);
+        browser.handle(key(KeyCode::Home));
+        browser.render(80, 4);
+        assert_eq!(browser.top, 0);
+        assert_eq!(browser.handle(key(KeyCode::Char('q'))), Action::Quit);
+    }
+
+    #[test]
+    fn loads_confirmed_endpoints_with_their_models_once() {
+        let dir = std::env::temp_dir().join(format!("pof-browse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let endpoints = dir.join("endpoints.csv");
+        let models = dir.join("models.csv");
+        std::fs::write(&endpoints, "IP:Port,Tags URL,Status Code,Location\n\
+            http://10.0.0.1:11434,,200,DE\n10.0.0.2:11434,,403,US\nhttp://10.0.0.1:11434,,200,DE (Berlin)\n").unwrap();
+        std::fs::write(&models, "IP:Port,Model Name,Size,Parameter Size,Quantization Level\n\
+            http://10.0.0.1:11434,llama3:8b,4.34,8.0B,Q4_0\nhttp://10.0.0.2:11434,phi3,2.20,3.8B,Q4_0\nhttp://10.0.0.1:11434,llama3:8b,4.34,8.0B,Q4_0\n").unwrap();
+        let entries = load_entries(&endpoints, &models).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries, [entry("http://10.0.0.1:11434", "DE (Berlin)", &["llama3:8b (8.0B, Q4_0, 4.34 GB)"])]);
+    }
+}
@@ -0,0 +1,248 @@
+//! The `chat` subcommand: a minimal streaming chat with one endpoint's
+//! models, to see whether it actually answers. The conversation lives in
+//! memory only.
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+
+use crate::cli::{ChatArgs, Pool};
+use crate::export::confirmed_endpoints;
+use crate::{console_log, http_client, Model, TagsResponse, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+/// Longest wait for the endpoint to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest a whole reply may take, loading the model included
+const REPLY_TIMEOUT: Duration = Duration::from_secs(600);
+/// Longest wait for the next piece of a reply before the connection counts as lost
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+type Input = Lines<BufReader<Stdin>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+/// One line of `/api/chat`'s streamed answer.
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    message: Option<Message>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+pub async fn run_chat(args: &ChatArgs) -> Result<()> {
+    let client = http_client(CONNECT_TIMEOUT, Pool::REUSE, reqwest::redirect::Policy::default(), None)?;
+    let mut input = BufReader::new(tokio::io::stdin()).lines();
+    let base_url = match &args.url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => pick_endpoint(args, &mut input).await?,
+    };
+    let models = list_models(&client, &base_url).await?;
+    let mut model = match &args.model {
+        Some(name) => name.clone(),
+        None => choose_model(&models, &mut input).await?,
+    };
+    console_log(style("/model to switch models, /quit to leave").dim().to_string());
+
+    let mut history: Vec<Message> = Vec::new();
+    loop {
+        print!("{} ", style(format!("{}>", model)).green().bold());
+        std::io::stdout().flush()?;
+        let Some(line) = input.next_line().await? else { break };
+        match line.trim() {
+            "" => {}
+            "/quit" | "/exit" => break,
+            "/model" => model = choose_model(&models, &mut input).await?,
+            text if text.starts_with("/model ") => match find_model(&models, &text["/model ".len()..]) {
+                Some(name) => model = name,
+                None => console_log(style("No such model; /model lists them").yellow().to_string()),
+            },
+            text => {
+                history.push(Message { role: "user".to_string(), content: text.to_string() });
+                match stream_reply(&client, &base_url, &model, &history).await {
+                    Ok(reply) => history.push(Message { role: "assistant".to_string(), content: reply }),
+                    Err(e) => {
+                        // Dropped so that asking again resends the question, not a half answer
+                        history.pop();
+                        println!();
+                        console_log(style(format!("Connection lost: {}", e)).red().to_string());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `--pick`: lists the endpoints that answered 200 in the results CSV and
+/// reads a number.
+async fn pick_endpoint(args: &ChatArgs, input: &mut Input) -> Result<String> {
+    let endpoints: Vec<String> = confirmed_endpoints(&args.endpoints)?.into_iter().collect();
+    if endpoints.is_empty() {
+        bail!("{} has no endpoints that answered 200", args.endpoints.display());
+    }
+    let index = choose(&format!("Endpoints in {}", args.endpoints.display()), &endpoints, input).await?;
+    Ok(endpoints[index].clone())
+}
+
+async fn list_models(client: &reqwest::Client, base_url: &str) -> Result<Vec<Model>> {
+    let url = format!("{}/api/tags", base_url);
+    let response = client.get(&url).send().await.with_context(|| format!("Failed to reach {}", url))?;
+    if !response.status().is_success() {
+        bail!("{} answered {}", url, response.status());
+    }
+    let tags: TagsResponse = response.json().await.with_context(|| format!("{} is not an Ollama model list", url))?;
+    if tags.models.is_empty() {
+        bail!("{} lists no models", base_url);
+    }
+    Ok(tags.models)
+}
+
+async fn choose_model(models: &[Model], input: &mut Input) -> Result<String> {
+    let names: Vec<String> = models
+        .iter()
+        .map(|m| match m.details.parameter_size.as_str() {
+            "" => m.name.clone(),
+            size => format!("{} ({})", m.name, size),
+        })
+        .collect();
+    let index = choose("Models", &names, input).await?;
+    Ok(models[index].name.clone())
+}
+
+/// A model by its exact name, or by its number in the list.
+fn find_model(models: &[Model], choice: &str) -> Option<String> {
+    let choice = choice.trim();
+    models
+        .iter()
+        .find(|m| m.name == choice)
+        .or_else(|| choice.parse::<usize>().ok().and_then(|n| models.get(n.checked_sub(1)?)))
+        .map(|m| m.name.clone())
+}
+
+/// Lists `items` numbered from 1 and reads numbers until one is valid. A
+/// single item is chosen without asking.
+async fn choose(title: &str, items: &[String], input: &mut Input) -> Result<usize> {
+    console_log(format!("{}{}", HEADER_STYLE, style(title).blue().bold()));
+    for (i, item) in items.iter().enumerate() {
+        let prefix = if i == items.len() - 1 { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
+        console_log(format!("{}{}. {}", prefix, i + 1, item));
+    }
+    if items.len() == 1 {
+        return Ok(0);
+    }
+    loop {
+        print!("Number: ");
+        std::io::stdout().flush()?;
+        let Some(line) = input.next_line().await? else { bail!("No choice made") };
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=items.len()).contains(&n) => return Ok(n - 1),
+            _ => console_log(style(format!("Enter a number from 1 to {}", items.len())).yellow().to_string()),
+        }
+    }
+}
+
+/// Sends the conversation and prints the reply as it streams in; returns it
+/// once complete.
+async fn stream_reply(client: &reqwest::Client, base_url: &str, model: &str, history: &[Message]) -> Result<String> {
+    let body = serde_json::json!({ "model": model, "messages": history, "stream": true });
+    let mut response = client
+        .post(format!("{}/api/chat", base_url))
+        .json(&body)
+        .timeout(REPLY_TIMEOUT)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        let error = serde_json::from_str::<ChatChunk>(&text).ok().and_then(|chunk| chunk.error).unwrap_or(text);
+        bail!("{}: {}", status, error.trim());
+    }
+
+    let mut lines = LineBuffer::default();
+    let mut reply = String::new();
+    let mut stdout = std::io::stdout();
+    loop {
+        let chunk = tokio::time::timeout(IDLE_TIMEOUT, response.chunk())
+            .await
+            .context("no answer for two minutes")??;
+        let Some(chunk) = chunk else {
+            bail!("the reply ended before it was done");
+        };
+        for line in lines.push(&chunk) {
+            let chunk: ChatChunk = serde_json::from_str(&line).with_context(|| format!("unexpected answer: {}", line))?;
+            if let Some(error) = chunk.error {
+                bail!("{}", error);
+            }
+            if let Some(message) = chunk.message {
+                print!("{}", message.content);
+                stdout.flush()?;
+                reply.push_str(&message.content);
+            }
+            if chunk.done {
+                println!();
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+/// Reassembles the newline-delimited JSON of a streamed answer from chunks
+/// that may end anywhere, even inside a UTF-8 character.
+#[derive(Default)]
+struct LineBuffer(Vec<u8>);
+
+impl LineBuffer {
+    /// The complete, non-empty lines `bytes` finishes.
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.0.extend_from_slice(bytes);
+        let Some(end) = self.0.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+        let complete: Vec<u8> = self.0.drain(..=end).collect();
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reassembles_lines_split_anywhere() {
+        let mut lines = LineBuffer::default();
+        let text = "{\"message\":{\"role\":\"assistant\",\"content\":\"Hé\"}}\n\n{\"done\":true}\n".as_bytes();
+        // Split inside the two-byte é
+        let split = text.iter().position(|&b| b == 0xc3).unwrap() + 1;
+        assert!(lines.push(&text[..split]).is_empty());
+        let complete = lines.push(&text[split..]);
+        assert_eq!(complete.len(), 2);
+        let first: ChatChunk = serde_json::from_str(&complete[0]).unwrap();
+        assert_eq!(first.message.unwrap().content, "Hé");
+        assert!(serde_json::from_str::<ChatChunk>(&complete[1]).unwrap().done);
+    }
+
+    #[test]
+    fn finds_models_by_name_or_number() {
+        let models: Vec<Model> = serde_json::from_str(r#"[{"name":"llama3:8b"},{"name":"phi3"}]"#).unwrap();
+        assert_eq!(find_model(&models, "phi3").as_deref(), Some("phi3"));
+        assert_eq!(find_model(&models, " 1 ").as_deref(), Some("llama3:8b"));
+        assert_eq!(find_model(&models, "0"), None);
+        assert_eq!(find_model(&models, "3"), None);
+        assert_eq!(find_model(&models, "llama3"), None);
+    }
+}
@@ -0,0 +1,247 @@
+//! `scan-checkpoint.json`: how far a scan got, so `--resume` can continue an
+//! interrupted run without probing any host twice or skipping one.
+//!
+//! Ranges are scanned one after another, so the file holds the index of the
+//! range in progress and the position inside it. Probes finish out of order,
+//! so that position is a low-water mark (every host before `offset` is done)
+//! plus the hosts past it that already finished. Host indices refer to the
+//! range's planned hosts, which sampling and sharding derive from the seed
+//! stored alongside.
+//!
+//! Which addresses shared by several ranges were probed needs no entry of
+//! its own: replaying the finished ranges recovers it (see `probed`).
+//!
+//! The running scan's checkpoint task is the only writer: pressing `w` asks
+//! it for a save through a channel, next to the saves it makes on its own.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+use crate::cli::ScanArgs;
+use crate::targets::Target;
+
+pub const CHECKPOINT_FILE: &str = "scan-checkpoint.json";
+
+static SAVE_REQUESTS: Mutex<Option<mpsc::UnboundedSender<()>>> = Mutex::new(None);
+
+/// Asks the running scan to save now; false when no scan is running.
+pub fn request_save() -> bool {
+    SAVE_REQUESTS.lock().unwrap().as_ref().is_some_and(|sender| sender.send(()).is_ok())
+}
+
+/// Takes the save requests from now on, until `stop_listening`.
+pub fn listen() -> mpsc::UnboundedReceiver<()> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    *SAVE_REQUESTS.lock().unwrap() = Some(tx);
+    rx
+}
+
+pub fn stop_listening() {
+    SAVE_REQUESTS.lock().unwrap().take();
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Identifies the targets, sampling and sharding the indices refer to
+    fingerprint: String,
+    seed: u64,
+    /// Ranges before this one are finished
+    range: usize,
+    /// Hosts of `range` before this index are finished
+    offset: u64,
+    /// Finished hosts of `range` at or past `offset`
+    done: BTreeSet<u64>,
+}
+
+/// Hashes everything that decides which hosts a range expands to, in order.
+pub fn fingerprint(targets: &[Target], args: &ScanArgs) -> String {
+    // FNV-1a: stable across builds, unlike std's hasher
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |text: &str| {
+        for byte in text.bytes().chain(std::iter::once(b'\n')) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for target in targets {
+        for block in &target.blocks {
+            feed(&format!("{}{}", block.network, if block.whole { "+" } else { "" }));
+        }
+        feed("--");
+    }
+    feed(&args.sample_plan().map(|plan| plan.describe()).unwrap_or_default());
+    feed(&args.shard.map(|shard| shard.to_string()).unwrap_or_default());
+    format!("{:016x}", hash)
+}
+
+impl Checkpoint {
+    pub fn new(fingerprint: String, seed: u64) -> Self {
+        Self { fingerprint, seed, ..Self::default() }
+    }
+
+    /// Reads a checkpoint and checks that it belongs to the same targets and settings.
+    pub fn load(path: &Path, fingerprint: &str, seed: Option<u64>) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("No checkpoint to resume from at {}", path.display()))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&text)
+            .with_context(|| format!("Checkpoint {} is not readable", path.display()))?;
+        if checkpoint.fingerprint != fingerprint {
+            bail!(
+                "Checkpoint {} was written for different targets or --sample/--shard settings; \
+                 restore them or start over without --resume",
+                path.display()
+            );
+        }
+        if let Some(seed) = seed.filter(|s| *s != checkpoint.seed) {
+            bail!("--seed {} does not match the seed {} recorded in the checkpoint", seed, checkpoint.seed);
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn range(&self) -> usize {
+        self.range
+    }
+
+    /// Hosts of the current range already probed.
+    pub fn done_in_range(&self) -> u64 {
+        self.offset + self.done.len() as u64
+    }
+
+    fn is_done(&self, range: usize, index: u64) -> bool {
+        range < self.range || (range == self.range && (index < self.offset || self.done.contains(&index)))
+    }
+}
+
+/// The live checkpoint of a running scan.
+#[derive(Debug)]
+pub struct CheckpointTracker {
+    state: Mutex<Checkpoint>,
+}
+
+impl CheckpointTracker {
+    pub fn new(checkpoint: Checkpoint) -> Self {
+        Self { state: Mutex::new(checkpoint) }
+    }
+
+    pub fn is_done(&self, range: usize, index: u64) -> bool {
+        self.state.lock().unwrap().is_done(range, index)
+    }
+
+    pub fn range_finished(&self, range: usize) -> bool {
+        range < self.state.lock().unwrap().range
+    }
+
+    /// The range in progress and its hosts probed so far.
+    pub fn position(&self) -> (usize, u64) {
+        let state = self.state.lock().unwrap();
+        (state.range, state.done_in_range())
+    }
+
+    /// Hosts of `range` probed so far, when it is the current range.
+    pub fn done_in(&self, range: usize) -> u64 {
+        let state = self.state.lock().unwrap();
+        if state.range == range { state.done_in_range() } else { 0 }
+    }
+
+    /// Follows ranges appended to the targets, so a resume with the longer
+    /// input files matches.
+    pub fn set_fingerprint(&self, fingerprint: String) {
+        self.state.lock().unwrap().fingerprint = fingerprint;
+    }
+
+    /// Records that host `index` of the current range has been probed.
+    pub fn completed(&self, index: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.done.insert(index);
+        while state.done.first() == Some(&state.offset) {
+            state.done.pop_first();
+            state.offset += 1;
+        }
+    }
+
+    /// Moves on past `range` once all of its probes have finished.
+    pub fn finish_range(&self, range: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.range = range + 1;
+        state.offset = 0;
+        state.done.clear();
+    }
+
+    /// Writes the checkpoint atomically, so a crash mid-write leaves the previous one.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(&*self.state.lock().unwrap())?;
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, text)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_advances_over_contiguous_completions() {
+        let tracker = CheckpointTracker::new(Checkpoint::new("f".into(), 1));
+        for index in [1, 3, 0] {
+            tracker.completed(index);
+        }
+        let state = tracker.state.lock().unwrap().clone();
+        assert_eq!(state.offset, 2);
+        assert_eq!(state.done, BTreeSet::from([3]));
+        assert_eq!(state.done_in_range(), 3);
+        assert!(state.is_done(0, 1) && state.is_done(0, 3));
+        assert!(!state.is_done(0, 2) && !state.is_done(0, 4));
+    }
+
+    #[test]
+    fn finished_ranges_are_skipped_entirely() {
+        let tracker = CheckpointTracker::new(Checkpoint::new("f".into(), 1));
+        tracker.completed(5);
+        tracker.finish_range(0);
+        assert!(tracker.range_finished(0) && !tracker.range_finished(1));
+        assert!(tracker.is_done(0, 1_000_000));
+        assert!(!tracker.is_done(1, 0));
+        assert_eq!(tracker.state.lock().unwrap().done_in_range(), 0);
+    }
+
+    #[test]
+    fn reports_where_the_scan_is() {
+        let tracker = CheckpointTracker::new(Checkpoint::new("f".into(), 1));
+        tracker.finish_range(0);
+        for index in [0, 1, 4] {
+            tracker.completed(index);
+        }
+        assert_eq!(tracker.position(), (1, 3));
+        assert_eq!(tracker.done_in(1), 3);
+        assert_eq!(tracker.done_in(2), 0);
+    }
+
+    #[test]
+    fn round_trips_through_the_file() {
+        let path = std::env::temp_dir().join(format!("checkpoint-test-{}.json", std::process::id()));
+        let tracker = CheckpointTracker::new(Checkpoint::new("abc".into(), 7));
+        tracker.finish_range(0);
+        tracker.completed(0);
+        tracker.completed(2);
+        tracker.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path, "abc", None).unwrap();
+        assert_eq!(loaded, *tracker.state.lock().unwrap());
+        assert_eq!(loaded.seed(), 7);
+        assert!(Checkpoint::load(&path, "other", None).is_err());
+        assert!(Checkpoint::load(&path, "abc", Some(8)).is_err());
+        assert!(Checkpoint::load(&path, "abc", Some(7)).is_ok());
+        let _ = fs::remove_file(&path);
+    }
+}
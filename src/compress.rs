@@ -0,0 +1,123 @@
+//! Gzip for output files. A file whose name ends in `.gz` is written and read
+//! as gzip, and `--compress` adds `.gz` to the CSV and event log names.
+//!
+//! Every flush appends a complete gzip member, so the file can be read up to
+//! the last flush however the process ends, and a later run appending to it
+//! adds members of its own. `zcat`, `gzip -d` and the readers here all take
+//! multi-member files.
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Buffer of a compressed output: each flush is a member, and bigger members
+/// compress better
+pub const GZIP_BUFFER: usize = 256 << 10;
+
+pub fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
+
+/// `path` with `.gz` added, unless it has it already.
+pub fn gz_path(path: &Path) -> PathBuf {
+    match is_gzip(path) {
+        true => path.to_path_buf(),
+        false => PathBuf::from(format!("{}.gz", path.display())),
+    }
+}
+
+/// A file appended to as is or as gzip members.
+pub enum Output {
+    Plain(File),
+    Gzip(File),
+}
+
+impl Output {
+    pub fn new(file: File, gzip: bool) -> Self {
+        match gzip {
+            true => Output::Gzip(file),
+            false => Output::Plain(file),
+        }
+    }
+
+    pub fn file(&self) -> &File {
+        match self {
+            Output::Plain(file) | Output::Gzip(file) => file,
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self::new(self.file().try_clone()?, matches!(self, Output::Gzip(_))))
+    }
+
+    /// Buffer size to give it
+    pub fn capacity(&self) -> usize {
+        match self {
+            Output::Plain(_) => 8 << 10,
+            Output::Gzip(_) => GZIP_BUFFER,
+        }
+    }
+}
+
+impl Write for Output {
+    /// A gzip output takes `buf` whole, as one member.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(file) => file.write(buf),
+            Output::Gzip(file) => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(buf)?;
+                file.write_all(&encoder.finish()?)?;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(file) | Output::Gzip(file) => file.flush(),
+        }
+    }
+}
+
+/// Opens `path` for reading, decompressing it if it is a `.gz`.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let file = File::open(path)?;
+    Ok(match is_gzip(path) {
+        true => Box::new(MultiGzDecoder::new(BufReader::new(file))),
+        false => Box::new(file),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_every_member() {
+        let path = std::env::temp_dir().join(format!("pof-compress-{}.csv.gz", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Two runs appending, each flushing twice
+        for run in ["a", "b"] {
+            let file = std::fs::OpenOptions::new().append(true).create(true).open(&path).unwrap();
+            let mut output = io::BufWriter::new(Output::new(file, true));
+            for row in 1..=2 {
+                writeln!(output, "{}{}", run, row).unwrap();
+                output.flush().unwrap();
+            }
+        }
+        let mut text = String::new();
+        open(&path).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, "a1\na2\nb1\nb2\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn adds_the_extension_once() {
+        assert_eq!(gz_path(Path::new("events.jsonl")), Path::new("events.jsonl.gz"));
+        assert_eq!(gz_path(Path::new("events.jsonl.gz")), Path::new("events.jsonl.gz"));
+    }
+}
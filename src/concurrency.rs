@@ -0,0 +1,235 @@
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const INITIAL_LIMIT: usize = 100;
+const MIN_LIMIT: usize = 10;
+/// Requests per evaluation window
+const WINDOW: u64 = 200;
+const INCREASE_STEP: usize = 20;
+/// How far the window's timeout ratio may rise above the baseline before it
+/// counts as a spike
+const SPIKE_MARGIN: f64 = 0.15;
+/// Weight of the latest window in the baseline; a new normal, e.g. after moving
+/// on to a sparser range, is absorbed within a handful of windows
+const BASELINE_WEIGHT: f64 = 0.2;
+
+#[cfg(unix)]
+const EMFILE: i32 = 24;
+#[cfg(unix)]
+const ENFILE: i32 = 23;
+
+/// How a single probe ended, as far as load on our side is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Any answer, including a refused or reset connection
+    Responded,
+    Timeout,
+    /// The local machine ran out of sockets, file descriptors or ports
+    LocalExhaustion,
+}
+
+impl Outcome {
+    pub fn from_error(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Outcome::Timeout;
+        }
+        if let Some(io) = crate::errors::io_source(err) {
+            #[cfg(unix)]
+            if matches!(io.raw_os_error(), Some(EMFILE) | Some(ENFILE)) {
+                return Outcome::LocalExhaustion;
+            }
+            if io.kind() == ErrorKind::AddrNotAvailable {
+                return Outcome::LocalExhaustion;
+            }
+        }
+        Outcome::Responded
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    limit: usize,
+    /// Permits still to be removed once in-flight requests release them
+    debt: usize,
+    requests: u64,
+    timeouts: u64,
+    exhausted: u64,
+    /// Moving average of the timeout ratio, unset until the first window closes
+    baseline: Option<f64>,
+}
+
+/// A probe's claim on the concurrency limit, released on drop.
+pub struct Slot {
+    _permit: OwnedSemaphorePermit,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// AIMD control of the number of in-flight probes.
+///
+/// Scanning mostly hits addresses that never answer, so an absolute timeout
+/// ratio says little. Instead each window of requests is compared with a
+/// moving average of the previous windows: the limit grows by INCREASE_STEP
+/// while the ratio stays close to that baseline and halves when it jumps (our
+/// own uplink or router is dropping packets) or when local socket errors show
+/// up.
+#[derive(Debug)]
+pub struct ConcurrencyController {
+    semaphore: Arc<Semaphore>,
+    in_flight: Arc<AtomicUsize>,
+    max: usize,
+    adaptive: bool,
+    window: Mutex<Window>,
+}
+
+impl ConcurrencyController {
+    pub fn adaptive(max: usize) -> Self {
+        let limit = INITIAL_LIMIT.min(max);
+        Self::with_limit(limit, max, true)
+    }
+
+    pub fn fixed(limit: usize) -> Self {
+        Self::with_limit(limit, limit, false)
+    }
+
+    fn with_limit(limit: usize, max: usize, adaptive: bool) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max,
+            adaptive,
+            window: Mutex::new(Window {
+                limit,
+                debt: 0,
+                requests: 0,
+                timeouts: 0,
+                exhausted: 0,
+                baseline: None,
+            }),
+        }
+    }
+
+    pub async fn acquire(&self) -> Option<Slot> {
+        let permit = self.semaphore.clone().acquire_owned().await.ok()?;
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Some(Slot { _permit: permit, in_flight: self.in_flight.clone() })
+    }
+
+    /// Probes currently holding a slot.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Current target for in-flight probes.
+    pub fn limit(&self) -> usize {
+        self.window.lock().unwrap().limit
+    }
+
+    pub fn record(&self, outcome: Outcome) {
+        if !self.adaptive {
+            return;
+        }
+        let mut window = self.window.lock().unwrap();
+        window.requests += 1;
+        match outcome {
+            Outcome::Responded => {}
+            Outcome::Timeout => window.timeouts += 1,
+            Outcome::LocalExhaustion => window.exhausted += 1,
+        }
+        if window.requests < WINDOW {
+            return;
+        }
+
+        let ratio = window.timeouts as f64 / window.requests as f64;
+        let baseline = window.baseline.unwrap_or(ratio);
+        let spiked = window.exhausted > 0 || ratio > baseline + SPIKE_MARGIN;
+        window.baseline = Some(baseline + BASELINE_WEIGHT * (ratio - baseline));
+        let target = if spiked {
+            (window.limit / 2).max(MIN_LIMIT)
+        } else {
+            (window.limit + INCREASE_STEP).min(self.max)
+        };
+        self.resize(&mut window, target);
+        window.requests = 0;
+        window.timeouts = 0;
+        window.exhausted = 0;
+    }
+
+    fn resize(&self, window: &mut Window, target: usize) {
+        if target > window.limit {
+            let mut grow = target - window.limit;
+            let repaid = grow.min(window.debt);
+            window.debt -= repaid;
+            grow -= repaid;
+            self.semaphore.add_permits(grow);
+        } else {
+            window.debt += window.limit - target;
+        }
+        // Permits held by in-flight requests can only be forgotten once released
+        let forgotten = self.semaphore.forget_permits(window.debt);
+        window.debt -= forgotten;
+        window.limit = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_window(controller: &ConcurrencyController, timeouts: u64) {
+        for i in 0..WINDOW {
+            controller.record(if i < timeouts { Outcome::Timeout } else { Outcome::Responded });
+        }
+    }
+
+    #[test]
+    fn grows_while_the_timeout_ratio_is_steady() {
+        let controller = ConcurrencyController::adaptive(1000);
+        for _ in 0..3 {
+            run_window(&controller, 100);
+        }
+        assert_eq!(controller.limit(), INITIAL_LIMIT + 3 * INCREASE_STEP);
+    }
+
+    #[test]
+    fn halves_on_a_spike_and_on_local_exhaustion() {
+        let controller = ConcurrencyController::adaptive(1000);
+        run_window(&controller, 20);
+        run_window(&controller, 120);
+        assert_eq!(controller.limit(), (INITIAL_LIMIT + INCREASE_STEP) / 2);
+
+        controller.record(Outcome::LocalExhaustion);
+        run_window(&controller, 0);
+        assert_eq!(controller.limit(), MIN_LIMIT.max((INITIAL_LIMIT + INCREASE_STEP) / 4));
+    }
+
+    #[test]
+    fn baseline_follows_a_lasting_change() {
+        let controller = ConcurrencyController::adaptive(1000);
+        // A dense range first, then a much sparser one for the rest of the scan
+        for _ in 0..5 {
+            run_window(&controller, 0);
+        }
+        for _ in 0..20 {
+            run_window(&controller, 160);
+        }
+        let settled = controller.limit();
+        run_window(&controller, 160);
+        assert_eq!(controller.limit(), settled + INCREASE_STEP);
+    }
+
+    #[test]
+    fn fixed_limit_ignores_outcomes() {
+        let controller = ConcurrencyController::fixed(50);
+        controller.record(Outcome::LocalExhaustion);
+        run_window(&controller, WINDOW);
+        assert_eq!(controller.limit(), 50);
+    }
+}
@@ -0,0 +1,51 @@
+//! The optional JSON config file, for settings too structured for flags.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::alerts::AlertConfig;
+use crate::api::ApiConfig;
+use crate::email::EmailConfig;
+use crate::honeypot::HoneypotConfig;
+
+/// Read from the working directory when it exists and `--config` is not given
+pub const CONFIG_FILE: &str = "config.json";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub alerts: AlertConfig,
+    pub honeypot: HoneypotConfig,
+    pub api: ApiConfig,
+    pub email: Option<EmailConfig>,
+}
+
+impl Config {
+    /// Reads `path`, or `config.json` if there is one. A file that does not
+    /// parse, or has keys this version does not know, is an error rather than
+    /// being ignored.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(CONFIG_FILE).exists() => Path::new(CONFIG_FILE),
+            None => return Ok(Self::default()),
+        };
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let config: Config = serde_json::from_str(r#"{"alerts":{"rules":[{"name":"r1","model":"llama*"}]}}"#).unwrap();
+        assert_eq!(config.alerts.rules.len(), 1);
+        assert!(serde_json::from_str::<Config>(r#"{"alert":{}}"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{"alerts":{"rules":[{"name":"r1","modle":"llama*"}]}}"#).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+//! Pausing, resuming and stopping the scan, whoever asks for it: the
+//! keyboard, a signal or `--serve`'s API. The event log records who did.
+
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+
+use crate::eventlog::{self, Event};
+use crate::pause::PAUSE_CLOCK;
+use crate::{PAUSE_SCAN, STOP_SCAN};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Running,
+    Paused,
+    /// Asked to stop; requests in flight are finishing
+    Stopping,
+}
+
+pub fn state() -> State {
+    if STOP_SCAN.load(Ordering::Relaxed) {
+        State::Stopping
+    } else if PAUSE_SCAN.load(Ordering::Relaxed) {
+        State::Paused
+    } else {
+        State::Running
+    }
+}
+
+/// False when the scan was paused already.
+pub fn pause(by: &'static str) -> bool {
+    if PAUSE_SCAN.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    PAUSE_CLOCK.pause();
+    eventlog::emit(Event::Paused { by });
+    true
+}
+
+/// False when the scan was not paused.
+pub fn resume(by: &'static str) -> bool {
+    if !PAUSE_SCAN.swap(false, Ordering::Relaxed) {
+        return false;
+    }
+    PAUSE_CLOCK.resume();
+    eventlog::emit(Event::Resumed { by });
+    true
+}
+
+/// False when a stop was asked for already.
+pub fn stop(by: &'static str) -> bool {
+    if STOP_SCAN.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    eventlog::emit(Event::StopRequested { by });
+    true
+}
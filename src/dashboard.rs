@@ -0,0 +1,120 @@
+//! `--dashboard`: a page to watch a long scan from a browser. It polls
+//! `/status.json` and `/findings.json` every few seconds; the page, its
+//! styles and script are all in `dashboard.html`, so nothing is fetched from
+//! elsewhere. The status comes from the counters `s` shows and the findings
+//! from the same bounded log as `--serve`'s, so watching costs the scan
+//! nothing. It keeps serving between `--interval` cycles.
+
+use anyhow::Result;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::api::{self, FindingLog, Server, StatusFn, StatusSource};
+use crate::control::{self, State};
+use crate::snapshot::Snapshot;
+
+const PAGE: &str = include_str!("dashboard.html");
+
+/// Serves until dropped, across cycles.
+pub struct Dashboard {
+    task: JoinHandle<()>,
+    address: Option<SocketAddr>,
+}
+
+impl Dashboard {
+    pub fn start(server: &Server, status: StatusSource, findings: Arc<FindingLog>) -> Result<Self> {
+        let task = server.spawn(true, move |request| {
+            let answer = route(&request, &status, &findings);
+            async move { answer }
+        })?;
+        Ok(Self { task, address: server.address() })
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn route(request: &Request<Body>, status: &Mutex<Option<StatusFn>>, findings: &FindingLog) -> Response<Body> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/") => Response::builder()
+            .header(hyper::header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(PAGE))
+            .unwrap(),
+        // Between cycles there is none
+        (&Method::GET, "/status.json") => match status.lock().unwrap().as_ref() {
+            Some(status) => api::json(StatusCode::OK, &Status::from(status())),
+            None => api::json(StatusCode::OK, &serde_json::json!({ "state": "waiting" })),
+        },
+        (&Method::GET, "/findings.json") => api::findings(findings, request.uri().query().unwrap_or("")),
+        (_, "/" | "/status.json" | "/findings.json") => api::error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+        _ => api::error(StatusCode::NOT_FOUND, "not found"),
+    }
+}
+
+#[derive(Serialize)]
+struct Range {
+    number: usize,
+    of: usize,
+    location: String,
+    percent: u64,
+}
+
+#[derive(Serialize)]
+struct ErrorCount {
+    category: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct Status {
+    state: State,
+    active_secs: u64,
+    paused_secs: u64,
+    in_flight: usize,
+    range: Option<Range>,
+    scanned: u64,
+    /// None when targets keep arriving
+    targets: Option<u64>,
+    hits: u64,
+    other_hits: u64,
+    errors: Vec<ErrorCount>,
+    rate: f64,
+    concurrency: usize,
+}
+
+impl From<Snapshot> for Status {
+    fn from(snapshot: Snapshot) -> Self {
+        Self {
+            state: control::state(),
+            active_secs: snapshot.active.as_secs(),
+            paused_secs: snapshot.paused_total.as_secs(),
+            in_flight: snapshot.in_flight,
+            range: snapshot.range.map(|range| Range {
+                number: range.number,
+                of: range.of,
+                location: range.location,
+                percent: match range.planned {
+                    0 => 100,
+                    planned => range.done.min(planned) * 100 / planned,
+                },
+            }),
+            scanned: snapshot.scanned,
+            targets: snapshot.targets,
+            hits: snapshot.hits,
+            other_hits: snapshot.other_hits,
+            errors: snapshot.error_mix.into_iter().map(|(category, count)| ErrorCount { category, count }).collect(),
+            rate: snapshot.rate,
+            concurrency: snapshot.concurrency,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::Model;
+
+/// Findings already written during this run. Overlapping ranges or repeated
+/// input entries can reach the same host twice; only the first sighting is
+/// persisted.
+#[derive(Debug, Default)]
+pub struct SeenFindings {
+    endpoints: Mutex<HashSet<String>>,
+    models: Mutex<HashSet<(String, String)>>,
+}
+
+impl SeenFindings {
+    /// Returns `true` if the endpoint had not been recorded yet in this run.
+    pub fn insert_endpoint(&self, base_url: &str) -> bool {
+        self.endpoints.lock().unwrap().insert(base_url.to_string())
+    }
+
+    /// Drops models whose (endpoint, digest) pair was already written.
+    pub fn retain_new_models(&self, base_url: &str, models: Vec<Model>) -> Vec<Model> {
+        let mut seen = self.models.lock().unwrap();
+        models
+            .into_iter()
+            .filter(|m| seen.insert((base_url.to_string(), m.digest.clone())))
+            .collect()
+    }
+}
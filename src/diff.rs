@@ -0,0 +1,193 @@
+use anyhow::{bail, Result};
+use console::style;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::results::{endpoint_key, read_endpoint_rows, read_model_rows};
+use crate::{console_log, ScanResult, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+pub const DIFF_CSV: &str = "diff.csv";
+pub const DIFF_JSON: &str = "diff.json";
+
+#[derive(Debug, Default, Serialize)]
+struct ModelChange {
+    endpoint: String,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ScanDiff {
+    /// False when the scan was stopped early and "gone" was not computed
+    complete: bool,
+    new: Vec<String>,
+    gone: Vec<String>,
+    persisting: Vec<String>,
+    model_changes: Vec<ModelChange>,
+}
+
+/// True if `path` is one of the files this run appends to.
+fn is_live_output(path: &Path) -> bool {
+    let Ok(path) = path.canonicalize() else { return false };
+    [crate::writer::ENDPOINTS_CSV, crate::writer::MODELS_CSV]
+        .iter()
+        .map(crate::outdir::path)
+        .flat_map(|live| [crate::compress::gz_path(&live), live])
+        .filter_map(|live| live.canonicalize().ok())
+        .any(|live| live == path)
+}
+
+/// Models file to pair with the previous endpoints file: the explicit path if
+/// given, otherwise an llm_models.csv (.gz if the endpoints file is) next to
+/// it unless that is this run's own output.
+fn default_models_path(endpoints: &Path, explicit: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = explicit {
+        return Some(path.to_path_buf());
+    }
+    let candidate = endpoints.with_file_name(crate::writer::MODELS_CSV);
+    let candidate = match crate::compress::is_gzip(endpoints) {
+        true => crate::compress::gz_path(&candidate),
+        false => candidate,
+    };
+    (candidate.exists() && !is_live_output(&candidate)).then_some(candidate)
+}
+
+/// endpoint key -> digest -> model name
+type ModelIndex = BTreeMap<String, BTreeMap<String, String>>;
+
+fn build_diff(current: &ModelIndex, previous: &BTreeSet<String>, previous_models: Option<&ModelIndex>) -> ScanDiff {
+    let mut diff = ScanDiff {
+        complete: true,
+        new: Vec::new(),
+        gone: Vec::new(),
+        persisting: Vec::new(),
+        model_changes: Vec::new(),
+    };
+    for endpoint in current.keys() {
+        if previous.contains(endpoint) {
+            diff.persisting.push(endpoint.clone());
+        } else {
+            diff.new.push(endpoint.clone());
+        }
+    }
+    diff.gone = previous.iter().filter(|e| !current.contains_key(*e)).cloned().collect();
+
+    if let Some(previous_models) = previous_models {
+        for endpoint in &diff.persisting {
+            let now = &current[endpoint];
+            let empty = BTreeMap::new();
+            let before = previous_models.get(endpoint).unwrap_or(&empty);
+            let change = ModelChange {
+                endpoint: endpoint.clone(),
+                added: now.iter().filter(|(d, _)| !before.contains_key(*d)).map(|(_, n)| n.clone()).collect(),
+                removed: before.iter().filter(|(d, _)| !now.contains_key(*d)).map(|(_, n)| n.clone()).collect(),
+            };
+            if !change.added.is_empty() || !change.removed.is_empty() {
+                diff.model_changes.push(change);
+            }
+        }
+    }
+    diff
+}
+
+fn write_diff_csv(diff: &ScanDiff) -> Result<()> {
+    let mut writer = csv::Writer::from_path(crate::outdir::path(DIFF_CSV))?;
+    writer.write_record(["Change", "IP:Port", "Model Name"])?;
+    for (change, endpoints) in [("new", &diff.new), ("gone", &diff.gone), ("persisting", &diff.persisting)] {
+        for endpoint in endpoints {
+            writer.write_record([change, endpoint, ""])?;
+        }
+    }
+    for change in &diff.model_changes {
+        for model in &change.added {
+            writer.write_record(["model added", &change.endpoint, model])?;
+        }
+        for model in &change.removed {
+            writer.write_record(["model removed", &change.endpoint, model])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// A previous run's findings, read once before scanning starts so this run's
+/// own rows can never leak into the comparison.
+#[derive(Debug)]
+pub struct Baseline {
+    path: PathBuf,
+    endpoints: BTreeSet<String>,
+    models: Option<ModelIndex>,
+}
+
+impl Baseline {
+    pub fn load(previous_endpoints: &Path, previous_models: Option<&Path>) -> Result<Self> {
+        if !previous_endpoints.exists() {
+            bail!("Diff baseline {} does not exist", previous_endpoints.display());
+        }
+        let models_path = default_models_path(previous_endpoints, previous_models);
+        for path in std::iter::once(previous_endpoints).chain(models_path.as_deref()) {
+            if is_live_output(path) {
+                bail!(
+                    "Diff baseline {} is the file this scan writes to; copy it elsewhere first",
+                    path.display()
+                );
+            }
+        }
+
+        let endpoints = read_endpoint_rows(previous_endpoints)?
+            .iter()
+            .map(|row| endpoint_key(&row.endpoint))
+            .filter(|key| !key.is_empty())
+            .collect();
+        let models = match models_path {
+            Some(path) => {
+                let mut index = ModelIndex::new();
+                for row in read_model_rows(&path)? {
+                    index.entry(endpoint_key(&row.endpoint)).or_default().insert(row.digest, row.name);
+                }
+                Some(index)
+            }
+            None => None,
+        };
+        Ok(Self { path: previous_endpoints.to_path_buf(), endpoints, models })
+    }
+}
+
+/// Compares this run's findings with the baseline and writes diff.csv /
+/// diff.json. Matching is on ip:port only, so row order and the header version
+/// of the previous file don't matter. A stopped scan didn't probe everything,
+/// so "gone" is left out rather than listing every host it never reached.
+pub fn report_diff(found: &[ScanResult], baseline: &Baseline, stopped: bool) -> Result<()> {
+    let current: ModelIndex = found
+        .iter()
+        .map(|r| {
+            let models = r.models.iter().map(|(name, digest)| (digest.clone(), name.clone())).collect();
+            (endpoint_key(&r.base_url), models)
+        })
+        .collect();
+
+    let mut diff = build_diff(&current, &baseline.endpoints, baseline.models.as_ref());
+    if stopped {
+        diff.gone.clear();
+        diff.complete = false;
+    }
+    write_diff_csv(&diff)?;
+    std::fs::write(crate::outdir::path(DIFF_JSON), serde_json::to_string_pretty(&diff)?)?;
+
+    console_log(format!("\n{}{}", HEADER_STYLE,
+        style(format!("Changes since {}", baseline.path.display())).blue().bold()
+    ));
+    console_log(format!("{}New: {}", LIST_ITEM_STYLE, style(diff.new.len()).green()));
+    if stopped {
+        console_log(format!("{}Gone: {}", LIST_ITEM_STYLE, style("not computed, the scan was stopped early").dim()));
+    } else {
+        console_log(format!("{}Gone: {}", LIST_ITEM_STYLE, style(diff.gone.len()).red()));
+    }
+    console_log(format!("{}Persisting: {}", LIST_ITEM_STYLE, style(diff.persisting.len()).cyan()));
+    if baseline.models.is_some() {
+        console_log(format!("{}Endpoints with model changes: {}", LIST_ITEM_STYLE, style(diff.model_changes.len()).yellow()));
+    }
+    console_log(format!("{}Written to {} and {}", LAST_ITEM_STYLE, crate::outdir::path(DIFF_CSV).display(), crate::outdir::path(DIFF_JSON).display()));
+    Ok(())
+}
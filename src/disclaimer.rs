@@ -2,6 +2,9 @@ use anyhow::Result;
 use console::style;
 use crossterm::{cursor, terminal::{Clear, ClearType}, ExecutableCommand};
 use std::io::Write;
+use std::sync::atomic::Ordering;
+
+use crate::PLAIN_PROGRESS;
 
 // Border constants to match main.rs style
 const BOX_HEADER: &str = "╭─ ";
@@ -10,60 +13,72 @@ const BOX_END: &str = "╰─ ";
 const BULLET: &str = "• ";
 const SUB_ITEM: &str = "   ";
 
-pub fn display_disclaimer() -> Result<bool> {
-    let mut stdout = std::io::stdout();
-    stdout.execute(Clear(ClearType::All))?;
-    stdout.execute(cursor::MoveTo(0, 0))?;
+/// Shows the notice and asks for agreement. `write_check` adds what
+/// `--check-write-access` sends to every endpoint found.
+pub fn display_disclaimer(out: &mut dyn Write, write_check: bool) -> Result<bool> {
+    // A log file gets the notice without the escape codes
+    if !PLAIN_PROGRESS.load(Ordering::Relaxed) {
+        out.execute(Clear(ClearType::All))?;
+        out.execute(cursor::MoveTo(0, 0))?;
+    }
 
     // Header section with better spacing
-    println!("\n{}{}\n", BOX_HEADER, style("IMPORTANT NOTICE").red().bold());
+    writeln!(out, "\n{}{}\n", BOX_HEADER, style("IMPORTANT NOTICE").red().bold())?;
     
     // Section 1 - Purpose
-    println!("{}Before proceeding:", BOX_MIDDLE);
-    println!("{}This tool is for educational and authorized", SUB_ITEM);
-    println!("{}security testing purposes only.", SUB_ITEM);
-    println!();
+    writeln!(out, "{}Before proceeding:", BOX_MIDDLE)?;
+    writeln!(out, "{}This tool is for educational and authorized", SUB_ITEM)?;
+    writeln!(out, "{}security testing purposes only.", SUB_ITEM)?;
+    writeln!(out)?;
     
     // Section 2 - Warnings with improved spacing
-    println!("{}Critical Warning:", BOX_MIDDLE);
-    println!("{}{}", SUB_ITEM, style("Scanning servers without explicit permission").red().bold());
-    println!("{}may result in serious consequences:", SUB_ITEM);  // Fixed: removed to_string() and fixed format
-    println!("{}{} {}", SUB_ITEM, BULLET, style("Legal actions and prosecution").red());
-    println!("{}{} {}", SUB_ITEM, BULLET, style("Network-wide IP bans").red());
-    println!("{}{} {}", SUB_ITEM, BULLET, style("Defensive countermeasures").red());
-    println!();
+    writeln!(out, "{}Critical Warning:", BOX_MIDDLE)?;
+    writeln!(out, "{}{}", SUB_ITEM, style("Scanning servers without explicit permission").red().bold())?;
+    writeln!(out, "{}may result in serious consequences:", SUB_ITEM)?;  // Fixed: removed to_string() and fixed format
+    writeln!(out, "{}{} {}", SUB_ITEM, BULLET, style("Legal actions and prosecution").red())?;
+    writeln!(out, "{}{} {}", SUB_ITEM, BULLET, style("Network-wide IP bans").red())?;
+    writeln!(out, "{}{} {}", SUB_ITEM, BULLET, style("Defensive countermeasures").red())?;
+    writeln!(out)?;
     
     // Section 3 - Server Information
-    println!("{}Ollama instances are personal servers:", BOX_MIDDLE);
-    println!("{}{} All access attempts are logged", SUB_ITEM, BULLET);
-    println!("{}{} Resources are monitored", SUB_ITEM, BULLET);
-    println!("{}{} Rate limits are enforced", SUB_ITEM, BULLET);
-    println!();
+    writeln!(out, "{}Ollama instances are personal servers:", BOX_MIDDLE)?;
+    writeln!(out, "{}{} All access attempts are logged", SUB_ITEM, BULLET)?;
+    writeln!(out, "{}{} Resources are monitored", SUB_ITEM, BULLET)?;
+    writeln!(out, "{}{} Rate limits are enforced", SUB_ITEM, BULLET)?;
+    writeln!(out)?;
     
     // Section 4 - Usage Guidelines
-    println!("{}Responsible Usage Requirements:", BOX_MIDDLE);
-    println!("{}{} Only scan authorized networks", SUB_ITEM, BULLET);
-    println!("{}{} Follow best practices", SUB_ITEM, BULLET);
-    println!("{}{} Respect system administrators", SUB_ITEM, BULLET);
-    println!();
+    writeln!(out, "{}Responsible Usage Requirements:", BOX_MIDDLE)?;
+    writeln!(out, "{}{} Only scan authorized networks", SUB_ITEM, BULLET)?;
+    writeln!(out, "{}{} Follow best practices", SUB_ITEM, BULLET)?;
+    writeln!(out, "{}{} Respect system administrators", SUB_ITEM, BULLET)?;
+    writeln!(out)?;
+
+    if write_check {
+        writeln!(out, "{}{}", BOX_MIDDLE, style("--check-write-access is enabled:").yellow().bold())?;
+        writeln!(out, "{}{} Every server found gets a POST to /api/copy", SUB_ITEM, BULLET)?;
+        writeln!(out, "{}{} It names no model, so nothing is copied, pulled or deleted", SUB_ITEM, BULLET)?;
+        writeln!(out, "{}{} {}", SUB_ITEM, BULLET, style("It is still an attempt to change the server and may be logged as one").yellow())?;
+        writeln!(out)?;
+    }
 
     // Agreement section with clear separation
-    println!("{}{}", BOX_MIDDLE, style("LEGAL CONFIRMATION:").red().bold());
-    println!("{}By proceeding, you explicitly confirm:", SUB_ITEM);
-    println!("{}1. {}", SUB_ITEM, style("I have authorization for all target networks").red());
-    println!("{}2. {}", SUB_ITEM, style("I accept full responsibility for my actions").red());
-    println!("{}3. {}", SUB_ITEM, style("I understand all legal implications").red());
-    println!();
+    writeln!(out, "{}{}", BOX_MIDDLE, style("LEGAL CONFIRMATION:").red().bold())?;
+    writeln!(out, "{}By proceeding, you explicitly confirm:", SUB_ITEM)?;
+    writeln!(out, "{}1. {}", SUB_ITEM, style("I have authorization for all target networks").red())?;
+    writeln!(out, "{}2. {}", SUB_ITEM, style("I accept full responsibility for my actions").red())?;
+    writeln!(out, "{}3. {}", SUB_ITEM, style("I understand all legal implications").red())?;
+    writeln!(out)?;
 
     // Final prompt
-    print!("{}{} ", BOX_END, style("Type 'y' to accept these terms:").bold());
-    stdout.flush()?;
+    write!(out, "{}{} ", BOX_END, style("Type 'y' to accept these terms:").bold())?;
+    out.flush()?;
 
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     
     if input.trim().to_lowercase() != "y" {
-        println!("\n{}", style("Access denied: Agreement required to proceed.").red().bold());
+        writeln!(out, "\n{}", style("Access denied: Agreement required to proceed.").red().bold())?;
         return Ok(false);
     }
     
@@ -0,0 +1,301 @@
+//! The `disclose` subcommand: one report per operator from the CSVs of
+//! earlier scans, to send to whoever answers for the exposed endpoints.
+//!
+//! Endpoints that answered 200 are grouped by the provider column, or by the
+//! abuse contact that `--abuse-contacts` found, and each group's report lists
+//! only that group's endpoints. Reports come from a template with `{{name}}`
+//! placeholders; the built-in one is below.
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+
+use crate::cli::DiscloseArgs;
+use crate::export::check_columns;
+use crate::provider::UNKNOWN as UNKNOWN_PROVIDER;
+use crate::results::{endpoint_key, read_abuse_contact_rows, read_endpoint_rows, read_model_rows};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiscloseBy {
+    /// The hosting provider of the Provider column
+    Provider,
+    /// The abuse contact found by --abuse-contacts
+    Contact,
+}
+
+const DEFAULT_TEMPLATE: &str = "\
+To: {{contact}}
+Subject: Publicly reachable Ollama servers on your network
+
+Hello {{operator}},
+
+During a scan on {{scan_date}} we found {{endpoint_count}} Ollama server(s) on
+your network that answer anyone on the internet without authentication.
+Anyone can list, run, and on some servers pull or delete their models:
+
+{{endpoints}}
+
+Models served:
+
+{{models}}
+
+Ollama listens on all interfaces when OLLAMA_HOST is set to 0.0.0.0. Binding
+it to 127.0.0.1, or putting it behind a firewall or an authenticating proxy,
+closes it off.
+
+This notice is sent in good faith; no data was changed on these servers.
+";
+
+/// The placeholders a template may use.
+const PLACEHOLDERS: &[&str] = &["operator", "contact", "scan_date", "endpoint_count", "endpoints", "models"];
+
+/// Group name of the endpoints without an abuse contact
+const NO_CONTACT: &str = "no abuse contact";
+
+/// One endpoint as a report lists it.
+#[derive(Debug, Clone, Default)]
+struct Finding {
+    endpoint: String,
+    provider: String,
+    /// YYYY-MM-DD of the latest sighting
+    date: String,
+    contact: String,
+    operator: String,
+    models: BTreeSet<String>,
+}
+
+/// The endpoints of one report.
+#[derive(Debug)]
+struct Group {
+    key: String,
+    findings: Vec<Finding>,
+}
+
+impl Group {
+    fn values(&self) -> HashMap<&'static str, String> {
+        let joined = |field: fn(&Finding) -> &str| {
+            let distinct: BTreeSet<&str> = self.findings.iter().map(field).filter(|v| !v.is_empty()).collect();
+            distinct.into_iter().collect::<Vec<_>>().join(", ")
+        };
+        let dates: BTreeSet<&str> = self.findings.iter().map(|f| f.date.as_str()).filter(|d| !d.is_empty()).collect();
+        let scan_date = match (dates.first(), dates.last()) {
+            (Some(first), Some(last)) if first != last => format!("{} to {}", first, last),
+            (Some(first), _) => first.to_string(),
+            _ => "an unknown date".to_string(),
+        };
+        let operator = match joined(|f| &f.operator) {
+            operator if operator.is_empty() => joined(|f| &f.provider),
+            operator => operator,
+        };
+
+        let mut served: BTreeMap<&str, usize> = BTreeMap::new();
+        for finding in &self.findings {
+            for model in &finding.models {
+                *served.entry(model).or_default() += 1;
+            }
+        }
+        let mut served: Vec<(&str, usize)> = served.into_iter().collect();
+        served.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let models = match served.is_empty() {
+            true => "  (no models listed)".to_string(),
+            false => served
+                .iter()
+                .map(|(model, count)| format!("  - {} on {} endpoint{}", model, count, if *count == 1 { "" } else { "s" }))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        let endpoints = self
+            .findings
+            .iter()
+            .map(|f| match f.models.len() {
+                0 => format!("  - {}", f.endpoint),
+                n => format!("  - {} ({} model{})", f.endpoint, n, if n == 1 { "" } else { "s" }),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        HashMap::from([
+            ("operator", if operator.is_empty() { "network operator".to_string() } else { operator }),
+            ("contact", joined(|f| &f.contact)),
+            ("scan_date", scan_date),
+            ("endpoint_count", self.findings.len().to_string()),
+            ("endpoints", endpoints),
+            ("models", models),
+        ])
+    }
+}
+
+/// Fills the `{{name}}` placeholders of `template`; an unknown or unclosed
+/// placeholder is an error naming it and its line.
+fn render(template: &str, values: &HashMap<&str, String>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let line = template[..template.len() - rest.len() + start].matches('\n').count() + 1;
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            bail!("Unclosed placeholder on line {}: '{}'", line, after.lines().next().unwrap_or_default().trim());
+        };
+        let name = after[..end].trim();
+        if !PLACEHOLDERS.contains(&name) {
+            bail!("Unknown placeholder {{{{{}}}}} on line {}; the template may use {}", name, line, PLACEHOLDERS.join(", "));
+        }
+        output.push_str(values.get(name).map(String::as_str).unwrap_or_default());
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// "20240501T100000Z" or "2024-05-01T10:00:00.000Z" as "2024-05-01".
+fn finding_date(discovered_at: &str, run_id: &str) -> String {
+    if discovered_at.len() >= 10 && discovered_at.is_char_boundary(10) {
+        return discovered_at[..10].to_string();
+    }
+    NaiveDateTime::parse_from_str(run_id, "%Y%m%dT%H%M%SZ").map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or_default()
+}
+
+/// A file name for a group: "abuse@example.net" becomes "abuse-example-net".
+fn file_stem(key: &str) -> String {
+    let stem: String = key.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' }).collect();
+    let stem = stem.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    if stem.is_empty() { "unknown".to_string() } else { stem }
+}
+
+fn group(findings: Vec<Finding>, by: DiscloseBy) -> Vec<Group> {
+    let mut groups: BTreeMap<String, Vec<Finding>> = BTreeMap::new();
+    for finding in findings {
+        let key = match by {
+            DiscloseBy::Provider if finding.provider.is_empty() => UNKNOWN_PROVIDER.to_string(),
+            DiscloseBy::Provider => finding.provider.clone(),
+            DiscloseBy::Contact if finding.contact.is_empty() => NO_CONTACT.to_string(),
+            DiscloseBy::Contact => finding.contact.to_ascii_lowercase(),
+        };
+        groups.entry(key).or_default().push(finding);
+    }
+    groups.into_iter().map(|(key, findings)| Group { key, findings }).collect()
+}
+
+pub fn run_disclose(args: &DiscloseArgs) -> Result<()> {
+    let template = match &args.template {
+        Some(path) => fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?,
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+    let template_name = args.template.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "the built-in template".to_string());
+    // Checked before anything is read, so a typo costs nothing
+    render(&template, &HashMap::new()).with_context(|| format!("Invalid template {}", template_name))?;
+
+    check_columns(&args.endpoints, &["IP:Port", "Status Code"])?;
+    let have_contacts = args.abuse_contacts.exists();
+    let by = args.group_by.unwrap_or(if have_contacts { DiscloseBy::Contact } else { DiscloseBy::Provider });
+    if by == DiscloseBy::Contact && !have_contacts {
+        bail!("--group-by contact needs {}; scan with --abuse-contacts first", args.abuse_contacts.display());
+    }
+
+    // The latest row of each endpoint that answered 200
+    let mut findings: BTreeMap<String, Finding> = BTreeMap::new();
+    for row in read_endpoint_rows(&args.endpoints)?.into_iter().filter(|row| row.status == "200") {
+        let key = endpoint_key(&row.endpoint);
+        if key.is_empty() {
+            continue;
+        }
+        findings.insert(key.clone(), Finding {
+            endpoint: format!("http://{}", key),
+            provider: row.provider,
+            date: finding_date(&row.discovered_at, &row.run_id),
+            ..Finding::default()
+        });
+    }
+    if have_contacts {
+        for row in read_abuse_contact_rows(&args.abuse_contacts)? {
+            if let Some(finding) = findings.get_mut(&endpoint_key(&row.endpoint)) {
+                finding.contact = row.abuse_email;
+                finding.operator = if row.registrant.is_empty() { row.network } else { row.registrant };
+            }
+        }
+    }
+    if args.models.exists() {
+        for row in read_model_rows(&args.models)? {
+            if let Some(finding) = findings.get_mut(&endpoint_key(&row.endpoint)) {
+                finding.models.insert(row.name);
+            }
+        }
+    }
+    if findings.is_empty() {
+        bail!("{} lists no endpoint that answered 200; nothing to report", args.endpoints.display());
+    }
+
+    let groups = group(findings.into_values().collect(), by);
+    let mut stems: HashMap<String, usize> = HashMap::new();
+    let mut written = 0;
+    if !args.dry_run {
+        fs::create_dir_all(&args.output_dir).with_context(|| format!("Failed to create {}", args.output_dir.display()))?;
+    }
+    for group in &groups {
+        let stem = file_stem(&group.key);
+        let seen = stems.entry(stem.clone()).or_default();
+        *seen += 1;
+        let path = args.output_dir.join(match *seen {
+            1 => format!("{}.txt", stem),
+            n => format!("{}-{}.txt", stem, n),
+        });
+        if args.dry_run {
+            eprintln!("{} ({} endpoint{}) -> {}", group.key, group.findings.len(), if group.findings.len() == 1 { "" } else { "s" }, path.display());
+            for finding in &group.findings {
+                eprintln!("  {}", finding.endpoint);
+            }
+            continue;
+        }
+        let report = render(&template, &group.values()).with_context(|| format!("Invalid template {}", template_name))?;
+        fs::write(&path, report).with_context(|| format!("Failed to write {}", path.display()))?;
+        written += 1;
+    }
+    match args.dry_run {
+        true => eprintln!("{} reports would be written to {} (dry run)", groups.len(), args.output_dir.display()),
+        false => eprintln!("Wrote {} reports to {}", written, args.output_dir.display()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_and_names_bad_placeholders() {
+        let values = HashMap::from([("contact", "abuse@example.net".to_string())]);
+        assert_eq!(render("To: {{ contact }}\n", &values).unwrap(), "To: abuse@example.net\n");
+        assert_eq!(render("{{scan_date}}", &values).unwrap(), "");
+        let error = render("Hi\nTo: {{contcat}}\n", &values).unwrap_err().to_string();
+        assert!(error.starts_with("Unknown placeholder {{contcat}} on line 2"), "{}", error);
+        let error = render("To: {{contact\n", &values).unwrap_err().to_string();
+        assert_eq!(error, "Unclosed placeholder on line 1: 'contact'");
+        assert!(render(DEFAULT_TEMPLATE, &values).is_ok());
+    }
+
+    #[test]
+    fn groups_only_each_operators_endpoints() {
+        let finding = |endpoint: &str, provider: &str, contact: &str| Finding {
+            endpoint: endpoint.to_string(),
+            provider: provider.to_string(),
+            contact: contact.to_string(),
+            ..Finding::default()
+        };
+        let findings = vec![
+            finding("http://10.0.0.1:11434", "OVH", "abuse@ovh.example"),
+            finding("http://10.0.0.2:11434", "OVH", "Abuse@OVH.example"),
+            finding("http://10.0.0.3:11434", "", ""),
+        ];
+        let groups = group(findings.clone(), DiscloseBy::Contact);
+        assert_eq!(groups.iter().map(|g| (g.key.as_str(), g.findings.len())).collect::<Vec<_>>(), [("abuse@ovh.example", 2), (NO_CONTACT, 1)]);
+        let groups = group(findings, DiscloseBy::Provider);
+        assert_eq!(groups[0].key, "OVH");
+        assert_eq!(groups[1].key, UNKNOWN_PROVIDER);
+        assert_eq!(groups[1].values()["endpoints"], "  - http://10.0.0.3:11434");
+        assert_eq!(file_stem("abuse@ovh.example"), "abuse-ovh-example");
+        assert_eq!(finding_date("", "20240501T100000Z"), "2024-05-01");
+    }
+}
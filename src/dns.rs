@@ -0,0 +1,173 @@
+//! `--dns` and `--dns-over-https`: the resolver behind every hostname the
+//! scanner looks up, from redirects the probes follow to the RDAP server, the
+//! provider feeds, Elasticsearch and S3. Without either flag lookups go
+//! through the system resolver as before.
+//!
+//! Upstreams are asked one at a time, in the order given, and a lookup moves
+//! to the next one when an upstream fails or does not answer within
+//! `--dns-timeout`. Answers are cached for their TTL, so names sharing a
+//! zone cost one query each at most, and at most `DNS_CONCURRENCY` lookups
+//! run at once across all clients.
+
+use anyhow::{bail, Result};
+use hickory_resolver::config::{NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts, ServerOrderingStrategy};
+use hickory_resolver::TokioAsyncResolver;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::cli::ScanArgs;
+
+/// Lookups running at once, shared by every client
+const DNS_CONCURRENCY: usize = 64;
+/// Names kept in the cache, each for its TTL
+const DNS_CACHE: usize = 4096;
+const DNS_PORT: u16 = 53;
+const DOH_PORT: u16 = 443;
+
+/// A DNS-over-HTTPS upstream from `--dns-over-https`: `IP[:PORT]`, or
+/// `IP[:PORT]#NAME` when its certificate is issued for a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DohServer {
+    address: SocketAddr,
+    tls_name: String,
+}
+
+pub fn parse_doh_server(value: &str) -> Result<DohServer, String> {
+    let (address, name) = match value.split_once('#') {
+        Some((address, name)) => (address, Some(name)),
+        None => (value, None),
+    };
+    let address = parse_server(address, DOH_PORT)?;
+    let tls_name = match name {
+        Some("") => return Err(format!("'{}' has an empty name after '#'", value)),
+        Some(name) => name.to_string(),
+        None => address.ip().to_string(),
+    };
+    Ok(DohServer { address, tls_name })
+}
+
+/// A plain upstream from `--dns`: `IP` or `IP:PORT`.
+pub fn parse_dns_server(value: &str) -> Result<SocketAddr, String> {
+    parse_server(value, DNS_PORT)
+}
+
+fn parse_server(value: &str, default_port: u16) -> Result<SocketAddr, String> {
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, default_port));
+    }
+    value.parse::<SocketAddr>().map_err(|_| format!("'{}' is not an IP address, with an optional port", value))
+}
+
+#[derive(Clone)]
+pub struct DnsResolver {
+    resolver: TokioAsyncResolver,
+    permits: Arc<Semaphore>,
+    /// "DoH 1.1.1.1:443 (cloudflare-dns.com), 9.9.9.9:53", for the banner
+    upstreams: String,
+}
+
+impl DnsResolver {
+    /// The resolver `--dns`/`--dns-over-https` ask for; None without them.
+    pub fn from_args(args: &ScanArgs) -> Result<Option<Arc<Self>>> {
+        if args.dns.is_empty() && args.dns_over_https.is_empty() {
+            return Ok(None);
+        }
+        if args.dns_timeout.is_zero() {
+            bail!("--dns-timeout must be longer than 0s");
+        }
+        Ok(Some(Arc::new(Self::new(&args.dns_over_https, &args.dns, args.dns_timeout))))
+    }
+
+    /// DNS-over-HTTPS upstreams are asked first, then the plain ones.
+    fn new(doh: &[DohServer], plain: &[SocketAddr], timeout: Duration) -> Self {
+        let mut servers = NameServerConfigGroup::new();
+        let mut upstreams = Vec::new();
+        for server in doh {
+            let mut config = NameServerConfig::new(server.address, Protocol::Https);
+            config.tls_dns_name = Some(server.tls_name.clone());
+            servers.push(config);
+            upstreams.push(match server.tls_name == server.address.ip().to_string() {
+                true => format!("DoH {}", server.address),
+                false => format!("DoH {} ({})", server.address, server.tls_name),
+            });
+        }
+        for &address in plain {
+            // TCP as well, for answers too long for a datagram
+            servers.push(NameServerConfig::new(address, Protocol::Udp));
+            servers.push(NameServerConfig::new(address, Protocol::Tcp));
+            upstreams.push(address.to_string());
+        }
+        let mut opts = ResolverOpts::default();
+        opts.timeout = timeout;
+        // One upstream at a time, in order; a failure moves on to the next
+        opts.attempts = 1;
+        opts.num_concurrent_reqs = 1;
+        opts.server_ordering_strategy = ServerOrderingStrategy::UserProvidedOrder;
+        opts.cache_size = DNS_CACHE;
+        let resolver = TokioAsyncResolver::tokio(ResolverConfig::from_parts(None, Vec::new(), servers), opts);
+        Self { resolver, permits: Arc::new(Semaphore::new(DNS_CONCURRENCY)), upstreams: upstreams.join(", ") }
+    }
+
+    pub fn describe(&self) -> String {
+        self.upstreams.clone()
+    }
+
+    /// Makes `builder`'s client look names up through `dns`, if set.
+    pub fn apply(dns: Option<&Arc<Self>>, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        match dns {
+            Some(dns) => builder.dns_resolver(dns.clone()),
+            None => builder,
+        }
+    }
+
+    /// The addresses of `host`, from the cache when it has them.
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let _permit = self.permits.acquire().await?;
+        let found = self.resolver.lookup_ip(host).await?;
+        Ok(found.iter().collect())
+    }
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns = self.clone();
+        Box::pin(async move {
+            let ips = dns.lookup(name.as_str()).await?;
+            // The client sets the port of each address itself
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_upstreams() {
+        assert_eq!(parse_dns_server("9.9.9.9"), Ok("9.9.9.9:53".parse().unwrap()));
+        assert_eq!(parse_dns_server("127.0.0.1:5353"), Ok("127.0.0.1:5353".parse().unwrap()));
+        assert_eq!(parse_dns_server("2620:fe::fe"), Ok("[2620:fe::fe]:53".parse().unwrap()));
+        assert!(parse_dns_server("dns.quad9.net").is_err());
+
+        let server = parse_doh_server("1.1.1.1#cloudflare-dns.com").unwrap();
+        assert_eq!((server.address, server.tls_name.as_str()), ("1.1.1.1:443".parse().unwrap(), "cloudflare-dns.com"));
+        assert_eq!(parse_doh_server("9.9.9.9:8443").unwrap().tls_name, "9.9.9.9");
+        assert!(parse_doh_server("1.1.1.1#").is_err());
+    }
+
+    #[tokio::test]
+    async fn lists_the_upstreams_in_the_order_they_are_asked() {
+        let resolver = DnsResolver::new(
+            &[parse_doh_server("1.1.1.1#cloudflare-dns.com").unwrap()],
+            &["9.9.9.9:53".parse().unwrap(), "149.112.112.112:53".parse().unwrap()],
+            Duration::from_secs(2),
+        );
+        assert_eq!(resolver.describe(), "DoH 1.1.1.1:443 (cloudflare-dns.com), 9.9.9.9:53, 149.112.112.112:53");
+    }
+}
@@ -0,0 +1,166 @@
+//! `--dry-run`: what a scan would make of its input files, without scanning.
+//! The ranges are loaded, deduplicated, sampled, sharded and checked against
+//! the country filter as a scan would, then listed per input file with what
+//! was skipped and why. Nothing is probed, and no output file is created.
+
+use anyhow::Result;
+use console::style;
+use std::collections::BTreeMap;
+
+use crate::cli::ScanArgs;
+use crate::exit::ScanOutcome;
+use crate::geo::GeoFilter;
+use crate::probed::ProbedSet;
+use crate::targets::{planned_count, planned_hosts, Target};
+use crate::{console_log, load_ranges, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+/// What happens to the addresses of one input file's ranges.
+#[derive(Debug, Default, PartialEq)]
+struct Tally {
+    listed: u64,
+    /// Left out by `--sample` or `--shard`
+    unplanned: u64,
+    /// Listed by an earlier range too, and probed for that one
+    duplicates: u64,
+    /// Left out by the country filter, per country
+    excluded: BTreeMap<String, u64>,
+    probed: u64,
+}
+
+impl Tally {
+    fn add(&mut self, target: &Target, args: &ScanArgs, seed: u64, probed: &ProbedSet, geo: Option<&GeoFilter>) {
+        let listed = target.host_count();
+        self.listed += listed;
+        let Some(geo) = geo else {
+            // Without a country filter there is no need to walk the range
+            let planned = planned_count(target, args, seed);
+            let duplicates = probed.replay(std::slice::from_ref(target), args, seed);
+            self.unplanned += listed - planned;
+            self.duplicates += duplicates;
+            self.probed += planned - duplicates;
+            return;
+        };
+        let mut planned = 0;
+        for ip in planned_hosts(target, args, seed) {
+            planned += 1;
+            // In the order a scan checks them
+            if !probed.first_probe(ip) {
+                self.duplicates += 1;
+            } else if let Some(country) = geo.excludes(ip) {
+                *self.excluded.entry(country).or_default() += 1;
+            } else {
+                self.probed += 1;
+            }
+        }
+        self.unplanned += listed - planned;
+    }
+
+    /// "1040 addresses listed, 16 in other shards, 3 excluded by country
+    /// (CN 2, unknown 1): 1021 to probe"
+    fn describe(&self, args: &ScanArgs) -> String {
+        let mut text = format!("{} addresses listed", self.listed);
+        if self.unplanned > 0 {
+            let reason = match (args.sample_plan().is_some(), args.shard.is_some()) {
+                (true, true) => "not sampled or in other shards",
+                (true, false) => "not sampled",
+                _ => "in other shards",
+            };
+            text.push_str(&format!(", {} {}", self.unplanned, reason));
+        }
+        if self.duplicates > 0 {
+            text.push_str(&format!(", {} listed by an earlier range", self.duplicates));
+        }
+        if !self.excluded.is_empty() {
+            let mut countries: Vec<(&String, &u64)> = self.excluded.iter().collect();
+            countries.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+            let countries: Vec<String> = countries.iter().map(|(country, count)| format!("{} {}", country, count)).collect();
+            text.push_str(&format!(", {} excluded by country ({})", self.excluded.values().sum::<u64>(), countries.join(", ")));
+        }
+        format!("{}: {} to probe", text, self.probed)
+    }
+}
+
+/// Lists what the scan `args` describe would probe. Found when there is
+/// anything to probe at all.
+pub fn run(args: &ScanArgs) -> Result<ScanOutcome> {
+    #[cfg(feature = "redis")]
+    if args.queue_pull.is_some() {
+        anyhow::bail!("--dry-run cannot be used with --queue-pull, whose targets come from Redis");
+    }
+    let loaded = load_ranges(&args.input)?;
+    let geo = GeoFilter::from_args(args)?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+
+    // Each range counts toward the file it was first listed in
+    let probed = ProbedSet::new(&loaded.targets);
+    let mut tallies: BTreeMap<String, Tally> = BTreeMap::new();
+    for target in &loaded.targets {
+        let origin = target.provenance.sources.first().map(|source| source.origin.clone()).unwrap_or_default();
+        tallies.entry(origin).or_default().add(target, args, seed, &probed, geo.as_ref());
+    }
+
+    console_log(format!("\n{}{}", HEADER_STYLE, style("Dry run: nothing is probed or written").blue().bold()));
+    for input in &loaded.inputs {
+        let name = input.path.display().to_string();
+        if let Some(problem) = &input.unreadable {
+            console_log(format!("{}{}: {}", LIST_ITEM_STYLE, name, style(problem).red()));
+            continue;
+        }
+        let mut counts = format!("{} ranges accepted", style(input.accepted).cyan());
+        if input.repeated > 0 {
+            counts.push_str(&format!(", {} listed again", input.repeated));
+        }
+        if !input.skipped.is_empty() {
+            counts.push_str(&format!(", {} skipped", style(input.skipped.len()).yellow()));
+        }
+        console_log(format!("{}{}: {}", LIST_ITEM_STYLE, style(&name).yellow(), counts));
+        for problem in &input.skipped {
+            console_log(format!("{}  Skipped: {}", LIST_ITEM_STYLE, problem));
+        }
+        if let Some(tally) = tallies.get(&name) {
+            console_log(format!("{}  {}", LIST_ITEM_STYLE, tally.describe(args)));
+        }
+    }
+    if let Some(plan) = args.sample_plan() {
+        console_log(format!("{}Sampling: {} (seed {})", LIST_ITEM_STYLE, style(plan.describe()).yellow(), style(seed).cyan()));
+    }
+    if let Some(shard) = args.shard {
+        console_log(format!("{}Shard: {}", LIST_ITEM_STYLE, style(shard).yellow()));
+    }
+    if let Some(geo) = &geo {
+        console_log(format!("{}Countries: {}", LIST_ITEM_STYLE, style(geo.describe()).yellow()));
+    }
+    let total: u64 = tallies.values().map(|tally| tally.probed).sum();
+    if total > args.max_targets && !args.i_know_what_im_doing {
+        console_log(format!("{}{}",
+            LIST_ITEM_STYLE,
+            style(format!("More than --max-targets ({}): the scan would ask before starting, or exit with code 4 where it cannot ask", args.max_targets)).yellow()
+        ));
+    }
+    console_log(format!("{}Total: {} ranges, {} addresses to probe", LAST_ITEM_STYLE, style(loaded.targets.len()).cyan(), style(total).cyan()));
+    Ok(if total > 0 { ScanOutcome::Found } else { ScanOutcome::NothingFound })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use crate::targets::{Block, Provenance};
+    use clap::Parser;
+
+    fn target(cidr: &str) -> Target {
+        Target::new(vec![Block::cidr(cidr.parse().unwrap())], Provenance::default())
+    }
+
+    #[test]
+    fn counts_what_sharding_and_overlaps_leave_out() {
+        let args = Cli::parse_from(["public-ollama-finder", "--shard", "1/2"]).scan;
+        let targets = [target("10.0.0.0/24"), target("10.0.0.128/25")];
+        let probed = ProbedSet::new(&targets);
+        let mut tally = Tally::default();
+        for target in &targets {
+            tally.add(target, &args, 1, &probed, None);
+        }
+        assert_eq!(tally.describe(&args), "380 addresses listed, 190 in other shards, 63 listed by an earlier range: 127 to probe");
+    }
+}
@@ -0,0 +1,233 @@
+//! Email with the run summary when a scan completes or is stopped (cargo
+//! feature `email`), set up in the `email` section of the config file:
+//!
+//! ```json
+//! {"email": {"server": "smtp.example.com", "from": "scanner@example.com", "to": ["me@example.com"]}}
+//! ```
+//!
+//! The SMTP login comes from `SMTP_USERNAME` and `SMTP_PASSWORD`. Sending is
+//! best effort: a message that can't be sent is reported, and the scan's
+//! result and exit code stay what they were.
+
+use serde::Deserialize;
+
+/// How the connection to the server is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Upgraded with STARTTLS, port 587 by default
+    #[default]
+    Starttls,
+    /// TLS from the start, port 465 by default
+    Tls,
+    /// Plain text, port 25 by default; for a relay on the same host
+    None,
+}
+
+/// The `email` section of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "email"), allow(dead_code))]
+pub struct EmailConfig {
+    pub server: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: Security,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Attach the run's endpoints CSV when it is at most this big, e.g. "5MB"
+    pub attach_endpoints: Option<String>,
+}
+
+#[cfg(feature = "email")]
+pub use mailer::{install, send_summary, send_test};
+
+#[cfg(feature = "email")]
+mod mailer {
+    use anyhow::{bail, Context, Result};
+    use console::style;
+    use lettre::message::header::ContentType;
+    use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    use super::{EmailConfig, Security};
+    use crate::cli::format_duration;
+    use crate::errors::ErrorCategory;
+    use crate::exit::ScanOutcome;
+    use crate::metrics::Metrics;
+    use crate::ratelimit::format_bytes;
+    use crate::rotate::parse_size;
+    use crate::{console_log, writer};
+
+    const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+
+    static MAILER: OnceLock<Mailer> = OnceLock::new();
+
+    struct Mailer {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: Mailbox,
+        to: Vec<Mailbox>,
+        attach_limit: Option<u64>,
+    }
+
+    impl Mailer {
+        fn new(config: &EmailConfig) -> Result<Self> {
+            let from = config.from.parse().with_context(|| format!("Invalid email.from address '{}'", config.from))?;
+            let to = config
+                .to
+                .iter()
+                .map(|to| to.parse().with_context(|| format!("Invalid email.to address '{}'", to)))
+                .collect::<Result<Vec<Mailbox>>>()?;
+            if to.is_empty() {
+                bail!("email.to lists no addresses");
+            }
+            let attach_limit = config.attach_endpoints.as_deref().map(parse_size).transpose().map_err(|e| anyhow::anyhow!("Invalid email.attach_endpoints: {}", e))?;
+            let mut builder = match config.security {
+                Security::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.server)?,
+                Security::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.server)?,
+                Security::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.server),
+            };
+            if let Some(port) = config.port {
+                builder = builder.port(port);
+            }
+            if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+                builder = builder.credentials(Credentials::new(username, password));
+            }
+            Ok(Self { transport: builder.timeout(Some(SEND_TIMEOUT)).build(), from, to, attach_limit })
+        }
+
+        async fn send(&self, subject: String, body: String, attachments: Vec<(String, Vec<u8>)>) -> Result<()> {
+            let message = self.to.iter().fold(Message::builder().from(self.from.clone()).subject(subject), |message, to| message.to(to.clone()));
+            let message = match attachments.is_empty() {
+                true => message.header(ContentType::TEXT_PLAIN).body(body)?,
+                false => {
+                    let csv = ContentType::parse("text/csv").expect("valid content type");
+                    let gzip = ContentType::parse("application/gzip").expect("valid content type");
+                    let parts = attachments.into_iter().fold(MultiPart::mixed().singlepart(SinglePart::plain(body)), |parts, (name, content)| {
+                        let kind = if name.ends_with(".gz") { gzip.clone() } else { csv.clone() };
+                        parts.singlepart(Attachment::new(name).body(content, kind))
+                    });
+                    message.multipart(parts)?
+                }
+            };
+            self.transport.send(message).await?;
+            Ok(())
+        }
+
+        fn recipients(&self) -> String {
+            self.to.iter().map(|to| to.email.to_string()).collect::<Vec<_>>().join(", ")
+        }
+    }
+
+    /// Checks the `email` section, so a mistake in it stops the run before it
+    /// scans rather than when it is over.
+    pub fn install(config: Option<&EmailConfig>) -> Result<()> {
+        if let Some(config) = config {
+            let _ = MAILER.set(Mailer::new(config)?);
+        }
+        Ok(())
+    }
+
+    /// `--test-email`: sends a short message to check the settings.
+    pub async fn send_test() -> Result<ScanOutcome> {
+        let Some(mailer) = MAILER.get() else {
+            bail!("No email configured; add an \"email\" section to the config file");
+        };
+        let body = format!("This is a test message from public-ollama-finder {}. Scan summaries will arrive like this one.\n", env!("CARGO_PKG_VERSION"));
+        mailer.send("Ollama finder test message".to_string(), body, Vec::new()).await.context("Failed to send the test message")?;
+        console_log(style(format!("Test message sent to {}", mailer.recipients())).green().to_string());
+        Ok(ScanOutcome::Found)
+    }
+
+    /// The subject and text of the summary.
+    pub(super) fn summary(metrics: &Metrics, tag: Option<&str>) -> (String, String) {
+        let endpoints = metrics.findings.endpoints;
+        let tag = tag.map(|tag| format!(" [{}]", tag)).unwrap_or_default();
+        let subject = format!("Ollama scan {}{}: {} endpoint{} found", metrics.termination, tag, endpoints, if endpoints == 1 { "" } else { "s" });
+        let mut lines = vec![
+            format!("Run ID: {}", metrics.run_id),
+            format!("Result: {}", metrics.termination),
+            format!("Duration: {}", format_duration(Duration::from_secs_f64(metrics.duration_secs))),
+            format!("IPs scanned: {} of {}", metrics.scanned, metrics.targets),
+            format!("Endpoints found: {}", endpoints),
+        ];
+        if metrics.findings.other_services > 0 {
+            lines.push(format!("Other LLM servers found: {}", metrics.findings.other_services));
+        }
+        lines.push(format!("Received: {}", format_bytes(metrics.bytes_received)));
+        lines.push(format!("Connection errors: {}", metrics.errors_total));
+        for category in ErrorCategory::ALL {
+            if let Some(count) = metrics.errors.get(category.label()) {
+                lines.push(format!("  {:<18} {} ({:.1}%)", category.label(), count, *count as f64 / metrics.probes.max(1) as f64 * 100.0));
+            }
+        }
+        if metrics.rows_not_written > 0 {
+            lines.push(format!("CSV rows not written: {}", metrics.rows_not_written));
+        }
+        (subject, lines.join("\n") + "\n")
+    }
+
+    /// Mails the summary of the run just over, with its endpoints CSVs when
+    /// they fit the attachment limit. Does nothing without an `email` section.
+    pub async fn send_summary(metrics: &Metrics, tag: Option<&str>, files: &[PathBuf]) {
+        let Some(mailer) = MAILER.get() else { return };
+        let (subject, mut body) = summary(metrics, tag);
+        let mut attachments = Vec::new();
+        if let Some(limit) = mailer.attach_limit {
+            let stem = writer::ENDPOINTS_CSV.trim_end_matches(".csv");
+            let mut room = limit;
+            for path in files.iter().filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(stem))) {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                match std::fs::read(path) {
+                    Ok(content) if content.len() as u64 <= room => {
+                        room -= content.len() as u64;
+                        attachments.push((name, content));
+                    }
+                    Ok(content) => body.push_str(&format!("\n{} ({}) is over the attachment limit and was not attached.\n", name, format_bytes(content.len() as u64))),
+                    Err(e) => body.push_str(&format!("\n{} could not be read: {}\n", name, e)),
+                }
+            }
+        }
+        match mailer.send(subject, body, attachments).await {
+            Ok(()) => console_log(format!("Summary emailed to {}", mailer.recipients())),
+            Err(e) => console_log(style(format!("Failed to email the summary: {:#}", e)).red().to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "email"))]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn reads_the_config_section() {
+        let config: EmailConfig = serde_json::from_str(r#"{"server":"smtp.example.com","security":"tls","from":"a@example.com","to":["b@example.com"]}"#).unwrap();
+        assert_eq!(config.security, Security::Tls);
+        assert!(serde_json::from_str::<EmailConfig>(r#"{"server":"s","security":"ssl","from":"a@example.com","to":[]}"#).is_err());
+    }
+
+    #[test]
+    fn summarizes_the_run() {
+        let metrics = Metrics {
+            run_id: "20250115T183000Z".to_string(),
+            targets: 512,
+            scanned: 200,
+            probes: 200,
+            duration_secs: 3725.0,
+            errors: [("connect timeout", 50)].into_iter().collect(),
+            errors_total: 50,
+            termination: "stopped",
+            ..Default::default()
+        };
+        let (subject, body) = mailer::summary(&metrics, Some("office"));
+        assert_eq!(subject, "Ollama scan stopped [office]: 0 endpoints found");
+        assert!(body.contains("Duration: 1h2m5s\n"), "{}", body);
+        assert!(body.contains("IPs scanned: 200 of 512\n"), "{}", body);
+        assert!(body.contains("  connect timeout    50 (25.0%)\n"), "{}", body);
+    }
+}
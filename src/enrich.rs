@@ -0,0 +1,263 @@
+//! `--enrich-show`: asks every confirmed endpoint's `/api/show` about its
+//! largest models. Runs as its own queue behind the sweep, so a slow or huge
+//! answer never holds up the probing of other hosts. An endpoint's rows in
+//! llm_models.csv are written from here once its answers are in, so they can
+//! carry each model's license.
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::probes::read_body;
+use crate::ratelimit::{AsnLimiter, SubnetLimiter, TokenBucket};
+use crate::writer::{ShowRecord, WriteEvent, WriteSender};
+use crate::{license, Model, STOP_SCAN};
+
+/// `/api/show` answers more slowly than `/api/tags`; it reads the modelfile
+const SHOW_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most of an `/api/show` answer read; some modelfiles embed huge system prompts
+const MAX_SHOW_LEN: usize = 1 << 20;
+/// `/api/show` requests in flight at once, across all endpoints
+const SHOW_CONCURRENCY: usize = 8;
+const MAX_LICENSE_LEN: usize = 100;
+const MAX_PARAMETERS_LEN: usize = 500;
+const MAX_SYSTEM_LEN: usize = 200;
+
+/// The models of one endpoint to ask about.
+#[derive(Debug, Clone)]
+pub struct EnrichJob {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+    pub base_url: String,
+    /// Names of the models to ask about
+    pub models: Vec<String>,
+    /// This endpoint's rows for llm_models.csv, written once the answers are in
+    pub rows: Vec<Model>,
+    pub discovered_at: String,
+    /// The endpoint's Location, for the rows
+    pub location: String,
+}
+
+pub type EnrichSender = mpsc::UnboundedSender<EnrichJob>;
+
+/// The names of the `limit` largest models, largest first.
+pub fn largest_models(models: &[Model], limit: usize) -> Vec<String> {
+    let mut by_size: Vec<&Model> = models.iter().collect();
+    by_size.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    by_size.into_iter().take(limit).map(|m| m.name.clone()).collect()
+}
+
+struct Enricher {
+    client: Arc<reqwest::Client>,
+    rate_limiter: Arc<TokenBucket>,
+    subnet_limiter: Option<Arc<SubnetLimiter>>,
+    asn_limiter: Option<Arc<AsnLimiter>>,
+    writer: WriteSender,
+}
+
+impl Enricher {
+    /// Asks about each of the job's models in turn, then writes its model
+    /// rows with whatever licenses were learned. The rows are written even
+    /// when every request failed or the scan was stopped.
+    async fn enrich(&self, job: EnrichJob, slots: Arc<Semaphore>) {
+        let mut licenses = BTreeMap::new();
+        for model in &job.models {
+            if STOP_SCAN.load(Ordering::Relaxed) {
+                break;
+            }
+            let _slot = slots.acquire().await.expect("semaphore is never closed");
+            if let Some(license) = self.show(&job, model.clone()).await {
+                licenses.insert(model.clone(), license.to_string());
+            }
+        }
+        let rows = WriteEvent::Models {
+            base_url: job.base_url,
+            models: job.rows,
+            licenses,
+            discovered_at: job.discovered_at,
+            location: job.location,
+        };
+        let _ = self.writer.send(rows).await;
+    }
+
+    /// Records one model's `/api/show` answer and returns its license's short name.
+    async fn show(&self, job: &EnrichJob, model: String) -> Option<&'static str> {
+        self.rate_limiter.acquire().await;
+        if let Some(limiter) = &self.subnet_limiter {
+            limiter.acquire(job.ip).await;
+        }
+        if let Some(limiter) = &self.asn_limiter {
+            limiter.acquire(job.ip).await;
+        }
+        let url = format!("http://{}:{}/api/show", job.ip, job.port);
+        // Older releases only know "name", newer ones prefer "model"
+        let request = self.client.post(&url).json(&json!({ "model": model, "name": model })).timeout(SHOW_TIMEOUT);
+        let response = request.send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let body = read_body(response, MAX_SHOW_LEN).await;
+        let mut record = ShowRecord { base_url: job.base_url.clone(), model, ..ShowRecord::default() };
+        let mut license_name = None;
+        match parse_show(&body) {
+            Some(show) => {
+                license_name = show.license_name;
+                record.license = show.license;
+                record.template_hash = show.template_hash;
+                record.parameters = show.parameters;
+                record.context_length = show.context_length.map(|n| n.to_string()).unwrap_or_default();
+                record.system = show.system;
+            }
+            None if body.len() >= MAX_SHOW_LEN => record.note = format!("answer over {} KiB, not parsed", MAX_SHOW_LEN >> 10),
+            None => record.note = "answer not understood".to_string(),
+        }
+        let _ = self.writer.send(WriteEvent::Show(record)).await;
+        license_name
+    }
+}
+
+/// Starts the enrichment queue. It ends once the returned sender is dropped
+/// and every queued endpoint is done; on a stop, requests in flight finish
+/// and the rest are skipped.
+pub fn spawn_enricher(
+    client: Arc<reqwest::Client>,
+    rate_limiter: Arc<TokenBucket>,
+    subnet_limiter: Option<Arc<SubnetLimiter>>,
+    asn_limiter: Option<Arc<AsnLimiter>>,
+    writer: WriteSender,
+) -> (EnrichSender, JoinHandle<()>) {
+    let enricher = Arc::new(Enricher { client, rate_limiter, subnet_limiter, asn_limiter, writer });
+    // Unbounded so handing off a job never blocks a probe; there is one per hit
+    let (tx, mut rx) = mpsc::unbounded_channel::<EnrichJob>();
+    let handle = tokio::spawn(async move {
+        let slots = Arc::new(Semaphore::new(SHOW_CONCURRENCY));
+        let mut endpoints = JoinSet::new();
+        while let Some(job) = rx.recv().await {
+            let (enricher, slots) = (enricher.clone(), slots.clone());
+            endpoints.spawn(async move { enricher.enrich(job, slots).await });
+            while endpoints.try_join_next().is_some() {}
+        }
+        while endpoints.join_next().await.is_some() {}
+    });
+    (tx, handle)
+}
+
+/// The parts of an `/api/show` answer worth keeping.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ShowDetails {
+    /// First line of the license text, usually its name
+    license: String,
+    /// Short name of the license, see `license::normalize`
+    license_name: Option<&'static str>,
+    /// Fingerprint of the prompt template, to spot hosts running the same one
+    template_hash: String,
+    /// Modelfile PARAMETER overrides as "name value; ..."
+    parameters: String,
+    context_length: Option<u64>,
+    /// Start of the system prompt, if the modelfile sets one
+    system: String,
+}
+
+fn parse_show(body: &[u8]) -> Option<ShowDetails> {
+    let show: Value = serde_json::from_slice(body).ok()?;
+    show.as_object()?;
+    // A modelfile with several LICENSE blocks gives an array
+    let licenses: Vec<&str> = match &show["license"] {
+        Value::Array(licenses) => licenses.iter().filter_map(Value::as_str).collect(),
+        license => license.as_str().into_iter().collect(),
+    };
+    let license = licenses.first().copied().unwrap_or_default();
+    // The first block that names a known license, as a model's own usually comes first
+    let names: Vec<&'static str> = licenses.iter().filter_map(|text| license::normalize(text)).collect();
+    let license_name = names.iter().find(|name| **name != license::UNKNOWN).or(names.first()).copied();
+    let parameters = show["parameters"]
+        .as_str()
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let template = show["template"].as_str().unwrap_or_default();
+    Some(ShowDetails {
+        license: cap(license.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default(), MAX_LICENSE_LEN),
+        license_name,
+        template_hash: if template.is_empty() { String::new() } else { format!("{:016x}", fnv1a(template)) },
+        parameters: cap(&parameters, MAX_PARAMETERS_LEN),
+        context_length: context_length(&show["model_info"]),
+        system: cap(&show["system"].as_str().unwrap_or_default().split_whitespace().collect::<Vec<_>>().join(" "), MAX_SYSTEM_LEN),
+    })
+}
+
+/// `<architecture>.context_length` from `model_info`, e.g. `llama.context_length`.
+fn context_length(model_info: &Value) -> Option<u64> {
+    let info = model_info.as_object()?;
+    let architecture = info.get("general.architecture").and_then(Value::as_str);
+    match architecture.and_then(|arch| info.get(&format!("{}.context_length", arch))) {
+        Some(length) => length.as_u64(),
+        None => info.iter().find(|(key, _)| key.ends_with(".context_length")).and_then(|(_, v)| v.as_u64()),
+    }
+}
+
+/// FNV-1a: stable across builds, unlike std's hasher.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn cap(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/api/show` for llama3.1:8b on Ollama 0.3, license and template shortened
+    const SHOW_LLAMA: &str = r#"{"license":"LLAMA 3.1 COMMUNITY LICENSE AGREEMENT\nLlama 3.1 Version Release Date: July 23, 2024\n...","modelfile":"FROM /root/.ollama/models/blobs/sha256-8eeb52dfb3bb","parameters":"num_ctx                        16384\nstop                           \"<|start_header_id|>\"\nstop                           \"<|eot_id|>\"","template":"{{ if .System }}<|start_header_id|>system<|end_header_id|>\n\n{{ .System }}<|eot_id|>{{ end }}","system":"You are a helpful   assistant\nfor ACME support.","details":{"parent_model":"","format":"gguf","family":"llama","families":["llama"],"parameter_size":"8.0B","quantization_level":"Q4_K_M"},"model_info":{"general.architecture":"llama","general.parameter_count":8030261248,"llama.context_length":131072,"llama.embedding_length":4096},"modified_at":"2024-08-01T09:14:12.482936511Z"}"#;
+
+    #[test]
+    fn keeps_the_interesting_fields() {
+        let show = parse_show(SHOW_LLAMA.as_bytes()).unwrap();
+        assert_eq!(show.license, "LLAMA 3.1 COMMUNITY LICENSE AGREEMENT");
+        assert_eq!(show.license_name, Some("llama3"));
+        assert_eq!(show.parameters, r#"num_ctx 16384; stop "<|start_header_id|>"; stop "<|eot_id|>""#);
+        assert_eq!(show.context_length, Some(131_072));
+        assert_eq!(show.system, "You are a helpful assistant for ACME support.");
+        assert_eq!(show.template_hash.len(), 16);
+
+        let same_template = SHOW_LLAMA.replace("ACME support", "someone else");
+        assert_eq!(parse_show(same_template.as_bytes()).unwrap().template_hash, show.template_hash);
+    }
+
+    #[test]
+    fn tolerates_sparse_and_odd_answers() {
+        let bare = parse_show(br#"{"modelfile":"FROM llama2","license":["Custom terms\n...","MIT License\n..."]}"#).unwrap();
+        assert_eq!(
+            bare,
+            ShowDetails { license: "Custom terms".to_string(), license_name: Some("mit"), ..ShowDetails::default() }
+        );
+        assert_eq!(parse_show(br#"{"license":"Custom terms"}"#).unwrap().license_name, Some(license::UNKNOWN));
+        assert_eq!(parse_show(br#"{"modelfile":"FROM llama2"}"#).unwrap().license_name, None);
+        // No general.architecture, as in some older releases
+        assert_eq!(context_length(&json!({"qwen2.context_length": 32768})), Some(32_768));
+        assert!(parse_show(b"[]").is_none());
+        assert!(parse_show(&SHOW_LLAMA.as_bytes()[..100]).is_none());
+    }
+
+    #[test]
+    fn asks_about_the_largest_models_first() {
+        let model = |name: &str, size| serde_json::from_value::<Model>(json!({ "name": name, "size": size })).unwrap();
+        let models = [model("small", 1), model("huge", 300), model("mid", 20), model("also-huge", 300)];
+        assert_eq!(largest_models(&models, 3), ["also-huge", "huge", "mid"]);
+        assert_eq!(largest_models(&models, 0), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+use std::error::Error as _;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub const ERRORS_CSV: &str = "errors.csv";
+/// Occurrences kept per category for errors.csv
+const SAMPLES_PER_CATEGORY: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// The host did not accept the connection within --connect-timeout
+    ConnectTimeout,
+    /// The host accepted it but did not finish answering within --read-timeout
+    ReadTimeout,
+    Refused,
+    Reset,
+    Tls,
+    /// The answer was longer than --max-body-size
+    Oversized,
+    Other,
+}
+
+impl ErrorCategory {
+    pub const ALL: [ErrorCategory; 7] = [
+        ErrorCategory::ConnectTimeout,
+        ErrorCategory::ReadTimeout,
+        ErrorCategory::Refused,
+        ErrorCategory::Reset,
+        ErrorCategory::Tls,
+        ErrorCategory::Oversized,
+        ErrorCategory::Other,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorCategory::ConnectTimeout => "connect timeout",
+            ErrorCategory::ReadTimeout => "read timeout",
+            ErrorCategory::Refused => "refused",
+            ErrorCategory::Reset => "reset",
+            ErrorCategory::Tls => "tls",
+            ErrorCategory::Oversized => "oversized response",
+            ErrorCategory::Other => "other",
+        }
+    }
+
+    /// Whether the host may well answer another time: the request was lost,
+    /// not turned away.
+    pub fn is_transient(self) -> bool {
+        matches!(self, ErrorCategory::ConnectTimeout | ErrorCategory::ReadTimeout | ErrorCategory::Reset)
+    }
+}
+
+/// The first `std::io::Error` in a reqwest error's source chain, if any.
+pub fn io_source(err: &reqwest::Error) -> Option<&std::io::Error> {
+    let mut source = err.source();
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            return Some(io);
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// Error text including its sources, for matching and errors.csv.
+pub fn describe(err: &reqwest::Error) -> String {
+    let mut text = err.to_string();
+    let mut source = err.source();
+    while let Some(e) = source {
+        // hyper and reqwest already inline some of their sources
+        let part = e.to_string();
+        if !text.contains(&part) {
+            text.push_str(": ");
+            text.push_str(&part);
+        }
+        source = e.source();
+    }
+    text
+}
+
+pub fn classify(err: &reqwest::Error) -> ErrorCategory {
+    let io_kind = io_source(err).map(|io| io.kind());
+    if err.is_timeout() || io_kind == Some(ErrorKind::TimedOut) {
+        // The connector's own timeout surfaces as a connect error
+        return match err.is_connect() {
+            true => ErrorCategory::ConnectTimeout,
+            false => ErrorCategory::ReadTimeout,
+        };
+    }
+    if let Some(category) = io_kind.and_then(category_for_io_kind) {
+        return category;
+    }
+    category_for_text(&describe(err))
+}
+
+/// The category an I/O error kind clearly belongs to, if any.
+fn category_for_io_kind(kind: ErrorKind) -> Option<ErrorCategory> {
+    match kind {
+        ErrorKind::ConnectionRefused => Some(ErrorCategory::Refused),
+        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+            Some(ErrorCategory::Reset)
+        }
+        _ => None,
+    }
+}
+
+/// Fallback for errors without a telling I/O kind, based on their description.
+fn category_for_text(text: &str) -> ErrorCategory {
+    let text = text.to_lowercase();
+    if text.contains("tls") || text.contains("ssl") || text.contains("certificate") {
+        ErrorCategory::Tls
+    } else {
+        ErrorCategory::Other
+    }
+}
+
+/// Per-category error counters plus the first few occurrences of each.
+#[derive(Debug, Default)]
+pub struct ErrorTally {
+    counts: [AtomicU64; 7],
+    samples: Mutex<Vec<(ErrorCategory, String, String)>>,
+}
+
+impl ErrorTally {
+    pub fn record(&self, ip: &str, err: &reqwest::Error) {
+        self.record_as(classify(err), ip, || describe(err));
+    }
+
+    /// A failure that is not a reqwest error, such as an oversized answer.
+    pub fn record_as(&self, category: ErrorCategory, ip: &str, message: impl FnOnce() -> String) {
+        let count = self.counts[category as usize].fetch_add(1, Ordering::Relaxed);
+        if (count as usize) < SAMPLES_PER_CATEGORY {
+            self.samples.lock().unwrap().push((category, ip.to_string(), message()));
+        }
+    }
+
+    pub fn count(&self, category: ErrorCategory) -> u64 {
+        self.counts[category as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        ErrorCategory::ALL.iter().map(|c| self.count(*c)).sum()
+    }
+
+    /// Non-zero counts, e.g. "connect timeout 120, refused 30".
+    pub fn compact(&self) -> String {
+        ErrorCategory::ALL
+            .iter()
+            .filter(|c| self.count(**c) > 0)
+            .map(|c| format!("{} {}", c.label(), self.count(*c)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn write_samples(&self, path: &Path) -> Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["Category", "IP", "Error"])?;
+        let mut samples = self.samples.lock().unwrap().clone();
+        samples.sort_by_key(|(category, _, _)| *category as usize);
+        for (category, ip, message) in &samples {
+            writer.write_record([category.label(), ip, message])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn maps_io_kinds_to_categories() {
+        assert_eq!(category_for_io_kind(ErrorKind::ConnectionRefused), Some(ErrorCategory::Refused));
+        assert_eq!(category_for_io_kind(ErrorKind::ConnectionReset), Some(ErrorCategory::Reset));
+        assert_eq!(category_for_io_kind(ErrorKind::ConnectionAborted), Some(ErrorCategory::Reset));
+        assert_eq!(category_for_io_kind(ErrorKind::BrokenPipe), Some(ErrorCategory::Reset));
+        assert_eq!(category_for_io_kind(ErrorKind::AddrNotAvailable), None);
+        assert_eq!(category_for_io_kind(ErrorKind::Other), None);
+    }
+
+    #[test]
+    fn recognizes_tls_failures_by_text() {
+        assert_eq!(category_for_text("error trying to connect: invalid peer certificate"), ErrorCategory::Tls);
+        assert_eq!(category_for_text("SSL routines:ssl3_get_record:wrong version number"), ErrorCategory::Tls);
+        assert_eq!(category_for_text("TLS handshake eof"), ErrorCategory::Tls);
+        assert_eq!(category_for_text("dns error: failed to lookup address"), ErrorCategory::Other);
+    }
+
+    #[test]
+    fn a_silent_host_is_a_read_timeout() {
+        // Connections complete in the listener's backlog but nothing answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let err = runtime.block_on(async {
+            let client = reqwest::Client::builder().connect_timeout(Duration::from_millis(400)).build().unwrap();
+            client.get(&url).timeout(Duration::from_millis(100)).send().await
+        }).unwrap_err();
+        assert_eq!(classify(&err), ErrorCategory::ReadTimeout);
+    }
+
+    #[test]
+    fn samples_are_capped_per_category() {
+        let tally = ErrorTally::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        // Port 1 on loopback refuses connections
+        let err = runtime.block_on(reqwest::get("http://127.0.0.1:1/")).unwrap_err();
+        for _ in 0..SAMPLES_PER_CATEGORY + 5 {
+            tally.record("127.0.0.1", &err);
+        }
+        assert_eq!(tally.count(ErrorCategory::Refused), SAMPLES_PER_CATEGORY as u64 + 5);
+        assert_eq!(tally.total(), SAMPLES_PER_CATEGORY as u64 + 5);
+        assert_eq!(tally.samples.lock().unwrap().len(), SAMPLES_PER_CATEGORY);
+        assert_eq!(tally.compact(), format!("refused {}", SAMPLES_PER_CATEGORY + 5));
+    }
+}
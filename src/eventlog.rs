@@ -0,0 +1,248 @@
+//! `--event-log`: a timeline of the whole run as JSON Lines, for auditing an
+//! engagement. Anything may publish, including the keyboard thread and the
+//! Ctrl+C handler, so the sender lives in a static like the stop and pause
+//! flags; one task owns the file and writes the events in the order they were
+//! published. Publishing is a no-op when the log is off.
+//!
+//! Every line carries `schema` and `timestamp` next to the event's `type`.
+//! The schema is documented in the README and `SCHEMA_VERSION` goes up
+//! whenever an event or field changes meaning or goes away; added events and
+//! fields do not bump it.
+
+use anyhow::{Context, Result};
+use console::style;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::compress::{self, Output};
+use crate::console_log;
+use crate::errors::{ErrorCategory, ErrorTally};
+use crate::sinks::Finding;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How often the error counters are compared for a burst
+pub const ERROR_BURST_WINDOW: Duration = Duration::from_secs(10);
+/// Errors within one window, not counting connect timeouts and refusals,
+/// that make a burst
+const ERROR_BURST_MIN: u64 = 50;
+
+static SENDER: Mutex<Option<mpsc::UnboundedSender<Message>>> = Mutex::new(None);
+
+#[derive(Debug)]
+enum Message {
+    Line(Line),
+    /// Answered once every line published before it is flushed
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    RunStarted {
+        version: &'static str,
+        arguments: Vec<String>,
+        ranges: usize,
+        targets: u64,
+    },
+    ScanStarted { run_id: String, targets: u64 },
+    /// `range` counts from 1, as the banner's "Resuming: range N" does
+    RangeStarted { run_id: String, range: usize, location: String, source: String },
+    RangeFinished { run_id: String, range: usize, stopped: bool },
+    /// Ranges appended to an input file mid-scan and queued by this run, and
+    /// the hosts they add
+    TargetsAdded { run_id: String, file: String, ranges: usize, targets: u64 },
+    /// The finding as `--stream` prints it
+    Finding {
+        #[serde(serialize_with = "shared")]
+        finding: Arc<Finding>,
+    },
+    Paused { by: &'static str },
+    Resumed { by: &'static str },
+    StopRequested { by: &'static str },
+    /// Errors per category label within the last `window_secs`
+    ErrorBurst { run_id: String, window_secs: u64, probes: u64, errors: BTreeMap<&'static str, u64> },
+    ScanFinished { run_id: String, attempted: u64, hits: u64, stopped: bool },
+    /// `termination` is "completed", "stopped" or "failed"
+    RunEnded {
+        termination: &'static str,
+        exit_code: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+fn shared<S: serde::Serializer>(finding: &Arc<Finding>, serializer: S) -> Result<S::Ok, S::Error> {
+    finding.as_ref().serialize(serializer)
+}
+
+#[derive(Debug, Serialize)]
+struct Line {
+    schema: u32,
+    /// RFC3339 UTC with milliseconds
+    timestamp: String,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// Opens `path`, or `path.gz` with `compress`, for appending and starts the
+/// task that writes to it.
+pub fn start(path: &Path, compress: bool) -> Result<JoinHandle<()>> {
+    let path = match compress {
+        true => compress::gz_path(path),
+        false => path.to_path_buf(),
+    };
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let output = Output::new(file, compress::is_gzip(&path));
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    *SENDER.lock().unwrap() = Some(tx);
+    let path = path.display().to_string();
+    Ok(tokio::spawn(async move {
+        let mut out = BufWriter::with_capacity(output.capacity(), output);
+        let mut failed = false;
+        while let Some(message) = rx.recv().await {
+            let mut result = Ok(());
+            let mut flushed = Vec::new();
+            // Flushed whenever the log catches up, so a killed run loses little
+            let mut next = Some(message);
+            while let Some(message) = next {
+                match message {
+                    Message::Line(line) => result = result.and_then(|_| write_line(&mut out, &line)),
+                    Message::Flush(done) => flushed.push(done),
+                }
+                next = rx.try_recv().ok();
+            }
+            if let Err(e) = result.and_then(|_| Ok(out.flush()?)) {
+                if !failed {
+                    console_log(style(format!("Failed to write {}: {}", path, e)).red().to_string());
+                    failed = true;
+                }
+            }
+            for done in flushed {
+                let _ = done.send(());
+            }
+        }
+    }))
+}
+
+fn write_line(out: &mut impl Write, line: &Line) -> Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    SENDER.lock().unwrap().is_some()
+}
+
+/// Publishes `event`, stamped with the current time.
+pub fn emit(event: Event) {
+    if let Some(sender) = SENDER.lock().unwrap().as_ref() {
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let _ = sender.send(Message::Line(Line { schema: SCHEMA_VERSION, timestamp, event }));
+    }
+}
+
+/// Waits until the events published so far are flushed; returns at once
+/// when the log is off.
+pub async fn flush() {
+    let (tx, rx) = oneshot::channel();
+    let sent = SENDER.lock().unwrap().as_ref().is_some_and(|sender| sender.send(Message::Flush(tx)).is_ok());
+    if sent {
+        let _ = rx.await;
+    }
+}
+
+/// Stops accepting events and waits until the ones published so far are
+/// written and flushed.
+pub async fn close(task: JoinHandle<()>) {
+    SENDER.lock().unwrap().take();
+    let _ = task.await;
+}
+
+/// Compares the error counters window by window. Connect timeouts and
+/// refusals are how most of the internet answers a probe, so only the other
+/// categories decide whether a window is a burst; the event lists them all.
+#[derive(Debug, Default)]
+pub struct BurstWatch {
+    errors: [u64; 7],
+    probes: u64,
+}
+
+impl BurstWatch {
+    /// The burst in the window since the last call, if there was one.
+    pub fn check(&mut self, run_id: &str, tally: &ErrorTally, probes: u64) -> Option<Event> {
+        let mut errors = BTreeMap::new();
+        let mut unusual = 0;
+        for category in ErrorCategory::ALL {
+            let count = tally.count(category);
+            let new = count - std::mem::replace(&mut self.errors[category as usize], count);
+            if new > 0 {
+                errors.insert(category.label(), new);
+            }
+            if !matches!(category, ErrorCategory::ConnectTimeout | ErrorCategory::Refused) {
+                unusual += new;
+            }
+        }
+        let probes = probes - std::mem::replace(&mut self.probes, probes);
+        (unusual >= ERROR_BURST_MIN).then(|| Event::ErrorBurst {
+            run_id: run_id.to_string(),
+            window_secs: ERROR_BURST_WINDOW.as_secs(),
+            probes,
+            errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(tally: &ErrorTally, category: ErrorCategory, times: u64) {
+        for _ in 0..times {
+            tally.record_as(category, "10.0.0.1", String::new);
+        }
+    }
+
+    #[test]
+    fn reports_a_burst_once_per_window() {
+        let tally = ErrorTally::default();
+        let mut watch = BurstWatch::default();
+        record(&tally, ErrorCategory::ConnectTimeout, 500);
+        assert!(watch.check("run", &tally, 600).is_none());
+
+        record(&tally, ErrorCategory::Reset, 40);
+        record(&tally, ErrorCategory::ReadTimeout, 10);
+        record(&tally, ErrorCategory::ConnectTimeout, 5);
+        let Some(Event::ErrorBurst { probes, errors, .. }) = watch.check("run", &tally, 700) else {
+            panic!("no burst");
+        };
+        assert_eq!(probes, 100);
+        assert_eq!(errors, BTreeMap::from([("connect timeout", 5), ("read timeout", 10), ("reset", 40)]));
+        assert!(watch.check("run", &tally, 800).is_none());
+    }
+
+    #[test]
+    fn lines_carry_the_schema_and_type() {
+        let line = Line {
+            schema: SCHEMA_VERSION,
+            timestamp: "2025-01-15T10:00:00.000Z".to_string(),
+            event: Event::RangeFinished { run_id: "20250115T100000Z".to_string(), range: 2, stopped: false },
+        };
+        assert_eq!(
+            serde_json::to_string(&line).unwrap(),
+            r#"{"schema":1,"timestamp":"2025-01-15T10:00:00.000Z","type":"range_finished","run_id":"20250115T100000Z","range":2,"stopped":false}"#
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::process::ExitCode;
+
+/// Documented in `--help`; scripts rely on these values.
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  scan completed and found at least one endpoint
+  1  fatal error (invalid arguments, bad input file, outputs not writable, ...)
+  2  scan completed without findings
+  3  stopped early (q, Ctrl+C, SIGTERM, a limit, or the disclaimer was declined)
+  4  not started: more targets than --max-targets, without confirmation";
+
+pub const EXIT_FATAL: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOutcome {
+    Found,
+    NothingFound,
+    Stopped,
+    /// More targets than `--max-targets`, and nobody confirmed them
+    TooLarge,
+}
+
+impl ScanOutcome {
+    pub fn from_state(hits: u64, stopped: bool) -> Self {
+        match (stopped, hits) {
+            (true, _) => ScanOutcome::Stopped,
+            (false, 0) => ScanOutcome::NothingFound,
+            (false, _) => ScanOutcome::Found,
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            ScanOutcome::Found => 0,
+            ScanOutcome::NothingFound => 2,
+            ScanOutcome::Stopped => 3,
+            ScanOutcome::TooLarge => 4,
+        }
+    }
+
+    pub fn exit_code(self) -> ExitCode {
+        ExitCode::from(self.code())
+    }
+}
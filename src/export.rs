@@ -0,0 +1,150 @@
+//! The `export` subcommand: turns the CSVs of earlier scans into input for
+//! other tools.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::anonymize::{self, AnonymizeScope};
+use crate::cli::ExportArgs;
+use crate::results::{csv_reader, endpoint_key, read_endpoint_rows, read_model_rows};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A LiteLLM `model_list` with one entry per endpoint and model
+    Litellm,
+    /// Base URLs, one per line
+    Urls,
+}
+
+pub fn run_export(args: &ExportArgs) -> Result<()> {
+    check_columns(&args.endpoints, &["IP:Port", "Status Code"])?;
+    let endpoints = confirmed_endpoints(&args.endpoints)?;
+    let (output, entries) = match args.format {
+        ExportFormat::Urls => (endpoints.iter().map(|url| format!("{}\n", url)).collect(), endpoints.len()),
+        ExportFormat::Litellm => {
+            check_columns(&args.models, &["IP:Port", "Model Name"])?;
+            let pairs = model_pairs(&args.models, &endpoints)?;
+            (litellm_config(&pairs), pairs.len())
+        }
+    };
+    if args.anonymize {
+        anonymize::install(AnonymizeScope::Reports, args.anonymize_style);
+    }
+    let output = anonymize::for_reports(&output).into_owned();
+    match &args.output {
+        Some(path) => {
+            fs::write(path, output).with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Wrote {} entries to {}", entries, path.display());
+        }
+        None => std::io::stdout().write_all(output.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Fails with the names of the columns `path` lacks, so a wrong or mangled
+/// file is obvious before anything is exported.
+pub fn check_columns(path: &Path, required: &[&str]) -> Result<()> {
+    let mut reader = csv_reader(path)?;
+    let headers = reader.headers().with_context(|| format!("Failed to read the header of {}", path.display()))?;
+    let missing: Vec<&str> = required
+        .iter()
+        .filter(|column| !headers.iter().any(|h| h.trim().eq_ignore_ascii_case(column)))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "{} has no {} column{}; expected a CSV written by this tool with at least: {}",
+            path.display(),
+            missing.join(", "),
+            if missing.len() > 1 { "s" } else { "" },
+            required.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Base URLs of the endpoints that answered 200, each once, sorted.
+pub fn confirmed_endpoints(path: &Path) -> Result<BTreeSet<String>> {
+    Ok(read_endpoint_rows(path)?
+        .iter()
+        .filter(|row| row.status == "200")
+        .map(|row| endpoint_key(&row.endpoint))
+        .filter(|key| !key.is_empty())
+        .map(|key| format!("http://{}", key))
+        .collect())
+}
+
+/// (base URL, model name) for every model on a confirmed endpoint, each pair
+/// once even when several runs recorded it, in file order.
+fn model_pairs(path: &Path, endpoints: &BTreeSet<String>) -> Result<Vec<(String, String)>> {
+    let mut seen = HashSet::new();
+    Ok(read_model_rows(path)?
+        .into_iter()
+        .map(|row| (format!("http://{}", endpoint_key(&row.endpoint)), row.name))
+        .filter(|(url, name)| endpoints.contains(url) && !name.is_empty())
+        .filter(|pair| seen.insert(pair.clone()))
+        .collect())
+}
+
+/// LiteLLM's `model_list`. A model served by several endpoints gets a
+/// numbered name after its first ("llama3:8b", "llama3:8b-2", ...) so each
+/// entry can be addressed on its own.
+fn litellm_config(pairs: &[(String, String)]) -> String {
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+    let mut yaml = String::from("model_list:\n");
+    for (url, name) in pairs {
+        let count = uses.entry(name).or_default();
+        *count += 1;
+        let model_name = match count {
+            1 => name.clone(),
+            n => format!("{}-{}", name, n),
+        };
+        yaml.push_str(&format!("  - model_name: {}\n", quoted(&model_name)));
+        yaml.push_str("    litellm_params:\n");
+        yaml.push_str(&format!("      model: {}\n", quoted(&format!("ollama/{}", name))));
+        yaml.push_str(&format!("      api_base: {}\n", quoted(url)));
+    }
+    yaml
+}
+
+/// A YAML double-quoted scalar; JSON's string escapes are valid YAML.
+fn quoted(value: &str) -> String {
+    serde_json::to_string(value).expect("strings always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_models_served_by_several_endpoints() {
+        let pairs = [
+            ("http://10.0.0.1:11434", "llama3:8b"),
+            ("http://10.0.0.2:11434", "llama3:8b"),
+            ("http://10.0.0.2:11434", "mistral:7b"),
+            ("http://10.0.0.3:11434", "llama3:8b"),
+        ]
+        .map(|(url, name)| (url.to_string(), name.to_string()));
+        let yaml = litellm_config(&pairs);
+        assert_eq!(
+            yaml.lines().filter(|line| line.contains("model_name")).collect::<Vec<_>>(),
+            [
+                r#"  - model_name: "llama3:8b""#,
+                r#"  - model_name: "llama3:8b-2""#,
+                r#"  - model_name: "mistral:7b""#,
+                r#"  - model_name: "llama3:8b-3""#,
+            ]
+        );
+        assert!(yaml.contains("      model: \"ollama/llama3:8b\"\n      api_base: \"http://10.0.0.3:11434\"\n"), "{}", yaml);
+    }
+
+    #[test]
+    fn quotes_names_yaml_would_misread() {
+        assert_eq!(quoted("yes"), r#""yes""#);
+        assert_eq!(quoted(r#"odd "name": #1"#), r#""odd \"name\": #1""#);
+    }
+}
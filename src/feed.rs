@@ -0,0 +1,251 @@
+//! `--watch`: follows a feed file like `tail -F` and probes the targets on
+//! each line as it is written. The file is read from the start, then polled
+//! for new lines; a truncated file is read again from the start and a
+//! rotated one from the old file's last line on to the new file.
+//!
+//! Lines are only read as fast as the scan takes their hosts: the channel
+//! to it holds `FEED_BUFFER` hosts, and a burst the rate limits can't keep
+//! up with waits in the file rather than in memory.
+
+use console::style;
+use std::fs::{self, File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::targets::{Provenance, Target};
+use crate::{console_log, ranges_in, ListedHost, STOP_SCAN};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Hosts read ahead of the scan
+pub const FEED_BUFFER: usize = 1024;
+
+const CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Complete lines written since the last look; `first_line` numbers the first
+    Lines { text: String, first_line: usize },
+    /// The file got shorter; it is read again from the start
+    Truncated,
+    /// Another file took its name; the old one was read to its end first
+    Rotated,
+}
+
+/// The feed file and how far it has been read.
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    offset: u64,
+    /// A last line still being written
+    partial: Vec<u8>,
+    /// The number of the next complete line
+    line: usize,
+}
+
+impl Tail {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, file: None, identity: None, offset: 0, partial: Vec::new(), line: 1 }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What was written since the last look, if anything. Called until it
+    /// returns None, it reads everything there is.
+    pub fn poll(&mut self) -> std::io::Result<Option<Change>> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = File::open(&self.path)?;
+                self.identity = identity(&file.metadata()?);
+                self.file.insert(file)
+            }
+        };
+        if file.metadata()?.len() < self.offset {
+            file.seek(SeekFrom::Start(0))?;
+            self.restart();
+            return Ok(Some(Change::Truncated));
+        }
+        let mut buffer = vec![0; CHUNK];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            self.offset += read as u64;
+            self.partial.extend_from_slice(&buffer[..read]);
+            if let Some(lines) = complete_lines(&mut self.partial, &mut self.line) {
+                return Ok(Some(lines));
+            }
+        }
+        // Only once the old file is drained, so no line written to it is lost
+        match fs::metadata(&self.path) {
+            Ok(metadata) if identity(&metadata) != self.identity => {
+                if !self.partial.is_empty() {
+                    // Its writer has moved on, so the last line is complete
+                    self.partial.push(b'\n');
+                    return Ok(complete_lines(&mut self.partial, &mut self.line));
+                }
+                self.file = None;
+                self.restart();
+                Ok(Some(Change::Rotated))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn restart(&mut self) {
+        self.offset = 0;
+        self.partial.clear();
+        self.line = 1;
+    }
+}
+
+/// Takes the complete lines off `partial`; `line` numbers the next one.
+fn complete_lines(partial: &mut Vec<u8>, line: &mut usize) -> Option<Change> {
+    let end = partial.iter().rposition(|&byte| byte == b'\n')? + 1;
+    let text = String::from_utf8_lossy(&partial[..end]).into_owned();
+    partial.drain(..end);
+    let first_line = *line;
+    *line += text.matches('\n').count();
+    Some(Change::Lines { text, first_line })
+}
+
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Without inodes a rotation can only be told from a truncation
+#[cfg(not(unix))]
+fn identity(_: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Follows `path` until the scan stops, sending the hosts of each new line.
+pub fn follow(path: PathBuf) -> mpsc::Receiver<ListedHost> {
+    let (tx, rx) = mpsc::channel(FEED_BUFFER);
+    tokio::spawn(async move {
+        let mut tail = Tail::new(path);
+        let mut index = 0;
+        let mut failing = false;
+        while !STOP_SCAN.load(Ordering::Relaxed) {
+            let (text, first_line) = match tail.poll() {
+                Ok(Some(Change::Lines { text, first_line })) => (text, first_line),
+                Ok(Some(Change::Truncated)) => {
+                    console_log(style(format!("{} was truncated; following it from the start", tail.path().display())).yellow().to_string());
+                    continue;
+                }
+                Ok(Some(Change::Rotated)) => {
+                    console_log(style(format!("{} was rotated; following the new file", tail.path().display())).yellow().to_string());
+                    continue;
+                }
+                Ok(None) => {
+                    failing = false;
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+                Err(e) => {
+                    // Reported once, not at every look
+                    if !failing {
+                        console_log(style(format!("Can't read {}: {}; retrying", tail.path().display(), e)).yellow().to_string());
+                        failing = true;
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            let (listed, warnings) = ranges_in(&text, tail.path(), true, first_line);
+            for warning in warnings {
+                console_log(style(format!("Warning: {}: {}", tail.path().display(), warning)).yellow().to_string());
+            }
+            for (blocks, label, source) in listed {
+                let target = Target::new(blocks, Provenance::new(label, source));
+                let provenance = Arc::new(target.provenance.clone());
+                for ip in target.hosts() {
+                    // Waits while the scan is behind; fails once it has stopped
+                    if tx.send((index, ip, provenance.clone())).await.is_err() {
+                        return;
+                    }
+                    index += 1;
+                }
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn lines(text: &str, first_line: usize) -> Option<Change> {
+        Some(Change::Lines { text: text.to_string(), first_line })
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("feed-test-{}-{}.txt", name, std::process::id()))
+    }
+
+    fn append(path: &Path, text: &str) {
+        fs::OpenOptions::new().create(true).append(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn reads_complete_lines_as_they_are_written() {
+        let path = temp("lines");
+        fs::write(&path, "10.0.0.1\n10.0.0.2\n10.0.0").unwrap();
+        let mut tail = Tail::new(path.clone());
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.1\n10.0.0.2\n", 1));
+        assert_eq!(tail.poll().unwrap(), None);
+
+        append(&path, ".3\n10.0.0.4\n");
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.3\n10.0.0.4\n", 3));
+        assert_eq!(tail.poll().unwrap(), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn starts_over_after_truncation() {
+        let path = temp("truncated");
+        fs::write(&path, "10.0.0.1\n10.0.0.2\n").unwrap();
+        let mut tail = Tail::new(path.clone());
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.1\n10.0.0.2\n", 1));
+
+        fs::OpenOptions::new().write(true).truncate(true).open(&path).unwrap();
+        append(&path, "10.0.0.9\n");
+        assert_eq!(tail.poll().unwrap(), Some(Change::Truncated));
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.9\n", 1));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn drains_the_old_file_before_following_the_new_one() {
+        let (path, rotated) = (temp("rotation"), temp("rotation-old"));
+        fs::write(&path, "10.0.0.1\n").unwrap();
+        let mut tail = Tail::new(path.clone());
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.1\n", 1));
+
+        append(&path, "10.0.0.2\n10.0.0.3");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "10.0.1.1\n").unwrap();
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.2\n", 2));
+        assert_eq!(tail.poll().unwrap(), lines("10.0.0.3\n", 3));
+        assert_eq!(tail.poll().unwrap(), Some(Change::Rotated));
+        assert_eq!(tail.poll().unwrap(), lines("10.0.1.1\n", 1));
+        assert_eq!(tail.poll().unwrap(), None);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&rotated).unwrap();
+    }
+}
@@ -0,0 +1,426 @@
+//! `--only-countries`/`--skip-countries`: keeps the scan inside chosen
+//! jurisdictions using a local MaxMind country database (GeoLite2-Country or
+//! GeoIP2-Country), checked for every address before it is probed.
+//!
+//! The database is read with a small reader for the MaxMind DB format
+//! (https://maxmind.github.io/MaxMind-DB/) that only decodes what a country
+//! lookup needs. Answers are cached per /24 when the database gives the whole
+//! /24 the same answer, which it does for nearly all of the address space.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use ipnet::Ipv4Net;
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::cli::ScanArgs;
+
+/// Precedes the metadata map at the end of the file
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+/// The metadata starts at most this far from the end
+const METADATA_MAX_SIZE: usize = 128 * 1024;
+/// Zero bytes between the search tree and the data section
+const DATA_SEPARATOR: usize = 16;
+/// Deepest nesting of maps, arrays and pointers a record may have
+const MAX_DEPTH: usize = 32;
+/// Shown and counted for addresses the database has no country for
+pub const UNKNOWN: &str = "unknown";
+
+/// What `--unknown-country` does with addresses the database has no country for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum UnknownCountry {
+    Skip,
+    Scan,
+}
+
+/// A decoded value of the data section. Only what a country lookup reads is
+/// kept; everything else is skipped over.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Uint(u64),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_uint(&self) -> Option<u64> {
+        match self {
+            Value::Uint(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Reads values from the data section or the metadata, whose pointers are
+/// offsets from their own start.
+struct Decoder<'a> {
+    section: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self.section.get(self.pos..self.pos + n).context("record runs past the end of the database")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, n: usize) -> Result<usize> {
+        Ok(self.take(n)?.iter().fold(0, |value, &b| value << 8 | b as usize))
+    }
+
+    fn decode(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("records nest too deeply");
+        }
+        let control = self.take(1)?[0];
+        let mut kind = control >> 5;
+        if kind == 1 {
+            let high = (control & 0x7) as usize;
+            let target = match (control >> 3) & 0x3 {
+                0 => high << 8 | self.uint(1)?,
+                1 => (high << 16 | self.uint(2)?) + 2048,
+                2 => (high << 24 | self.uint(3)?) + 526_336,
+                _ => self.uint(4)?,
+            };
+            return Decoder { section: self.section, pos: target }.decode(depth + 1);
+        }
+        if kind == 0 {
+            kind = 7 + self.take(1)?[0];
+        }
+        let size = match control & 0x1f {
+            29 => 29 + self.uint(1)?,
+            30 => 285 + self.uint(2)?,
+            31 => 65_821 + self.uint(3)?,
+            size => size as usize,
+        };
+        Ok(match kind {
+            2 => Value::String(String::from_utf8_lossy(self.take(size)?).into_owned()),
+            5 | 6 | 9 => Value::Uint(self.uint(size)? as u64),
+            7 => {
+                let mut entries = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let Value::String(key) = self.decode(depth + 1)? else {
+                        bail!("map key is not a string");
+                    };
+                    entries.push((key, self.decode(depth + 1)?));
+                }
+                Value::Map(entries)
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    items.push(self.decode(depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            // Booleans keep their value in the size, and these two carry nothing
+            12..=14 => Value::Other,
+            // Doubles, bytes, int32, uint128 and floats
+            3 | 4 | 8 | 10 | 15 => {
+                self.take(size)?;
+                Value::Other
+            }
+            kind => bail!("unknown data type {}", kind),
+        })
+    }
+}
+
+/// A MaxMind DB held in memory.
+pub struct Mmdb {
+    bytes: Vec<u8>,
+    node_count: u32,
+    record_size: u16,
+    tree_size: usize,
+    data_end: usize,
+    /// The node IPv4 lookups start from: the root of an IPv4 database, or
+    /// the node for ::/96 of an IPv6 one
+    ipv4_start: u32,
+}
+
+impl Mmdb {
+    pub fn open(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_bytes(bytes).with_context(|| format!("{} is not a MaxMind database", path.display()))
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let search_from = bytes.len().saturating_sub(METADATA_MAX_SIZE);
+        let marker = bytes[search_from..]
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .map(|at| search_from + at)
+            .context("no metadata marker")?;
+        let metadata = Decoder { section: &bytes[marker + METADATA_MARKER.len()..], pos: 0 }.decode(0)?;
+        let field = |name: &str| metadata.get(name).and_then(Value::as_uint).with_context(|| format!("metadata lacks {}", name));
+        let node_count = u32::try_from(field("node_count")?)?;
+        let record_size = u16::try_from(field("record_size")?)?;
+        let ip_version = field("ip_version")?;
+        if ![24, 28, 32].contains(&record_size) {
+            bail!("unsupported record size {}", record_size);
+        }
+        let tree_size = node_count as usize * record_size as usize / 4;
+        if tree_size + DATA_SEPARATOR > marker {
+            bail!("search tree is larger than the file");
+        }
+        let mut db = Self { bytes, node_count, record_size, tree_size, data_end: marker, ipv4_start: 0 };
+        if ip_version == 6 {
+            for _ in 0..96 {
+                if db.ipv4_start >= node_count {
+                    break;
+                }
+                db.ipv4_start = db.record(db.ipv4_start, 0)?;
+            }
+        }
+        Ok(db)
+    }
+
+    /// The left (`bit` 0) or right record of `node`.
+    fn record(&self, node: u32, bit: u32) -> Result<u32> {
+        let width = self.record_size as usize / 4;
+        let start = node as usize * width;
+        let b = self.bytes.get(start..start + width).context("node outside the search tree")?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0u32, |value, &byte| value << 8 | byte as u32);
+        Ok(match (self.record_size, bit) {
+            (24, 0) => be(&b[..3]),
+            (24, _) => be(&b[3..]),
+            (28, 0) => (b[3] as u32 & 0xf0) << 20 | be(&b[..3]),
+            (28, _) => (b[3] as u32 & 0x0f) << 24 | be(&b[4..]),
+            (_, 0) => be(&b[..4]),
+            (_, _) => be(&b[4..]),
+        })
+    }
+
+    /// The country code of `ip` and the prefix length the answer holds for.
+    pub fn country(&self, ip: Ipv4Addr) -> Result<(Option<String>, u8)> {
+        let address = u32::from(ip);
+        let mut node = self.ipv4_start;
+        let mut prefix = 0;
+        while node < self.node_count {
+            if prefix == 32 {
+                bail!("search tree is deeper than an IPv4 address");
+            }
+            node = self.record(node, address >> (31 - prefix) & 1)?;
+            prefix += 1;
+        }
+        if node == self.node_count {
+            return Ok((None, prefix as u8));
+        }
+        let record = self.data(node)?;
+        let code = ["country", "registered_country"]
+            .iter()
+            .find_map(|key| record.get(key)?.get("iso_code")?.as_str())
+            .map(str::to_ascii_uppercase);
+        Ok((code, prefix as u8))
+    }
+
+    /// The data record a search tree record past the nodes points to.
+    fn data(&self, node: u32) -> Result<Value> {
+        let offset = ((node - self.node_count) as usize).checked_sub(DATA_SEPARATOR).context("record points into the separator")?;
+        let section = &self.bytes[self.tree_size + DATA_SEPARATOR..self.data_end];
+        Decoder { section, pos: offset }.decode(0)
+    }
+
+    /// Every IPv4 network of an ASN database (GeoLite2-ASN) with its
+    /// autonomous system number and organization, for `--asn-db`. Networks
+    /// whose record has no number are left out.
+    pub fn asn_networks(&self) -> Result<Vec<(Ipv4Net, u32, Option<String>)>> {
+        let mut networks = Vec::new();
+        // Many networks share a record; each is decoded once
+        let mut decoded: HashMap<u32, Option<(u32, Option<String>)>> = HashMap::new();
+        let mut pending = vec![(self.ipv4_start, 0u32, 0u8)];
+        while let Some((node, address, prefix)) = pending.pop() {
+            if node < self.node_count {
+                if prefix == 32 {
+                    bail!("search tree is deeper than an IPv4 address");
+                }
+                pending.push((self.record(node, 1)?, address | 1 << (31 - prefix), prefix + 1));
+                pending.push((self.record(node, 0)?, address, prefix + 1));
+                continue;
+            }
+            if node == self.node_count {
+                continue;
+            }
+            let asn = match decoded.get(&node) {
+                Some(asn) => asn.clone(),
+                None => {
+                    let record = self.data(node)?;
+                    let number = record.get("autonomous_system_number").and_then(Value::as_uint).and_then(|n| u32::try_from(n).ok());
+                    let organization = record.get("autonomous_system_organization").and_then(Value::as_str).map(str::to_string);
+                    let asn = number.map(|number| (number, organization));
+                    decoded.insert(node, asn.clone());
+                    asn
+                }
+            };
+            if let Some((number, organization)) = asn {
+                networks.push((Ipv4Net::new(Ipv4Addr::from(address), prefix)?, number, organization));
+            }
+        }
+        Ok(networks)
+    }
+}
+
+/// Decides per address whether the country filter lets it be probed.
+pub struct GeoFilter {
+    db: Mmdb,
+    countries: HashSet<String>,
+    /// `--only-countries` rather than `--skip-countries`
+    only: bool,
+    unknown: UnknownCountry,
+    /// Country per /24, for the /24s the database answers as a whole
+    cache: Mutex<HashMap<u32, Option<String>>>,
+}
+
+impl GeoFilter {
+    /// None without `--only-countries` or `--skip-countries`.
+    pub fn from_args(args: &ScanArgs) -> Result<Option<Self>> {
+        let (only, codes) = match (&args.only_countries[..], &args.skip_countries[..]) {
+            ([], []) => return Ok(None),
+            ([], skip) => (false, skip),
+            (only, _) => (true, only),
+        };
+        let path = args.geoip_db.as_deref().context("--only-countries and --skip-countries need --geoip-db")?;
+        Ok(Some(Self {
+            db: Mmdb::open(path)?,
+            countries: codes.iter().map(|code| code.trim().to_ascii_uppercase()).collect(),
+            only,
+            unknown: args.unknown_country,
+            cache: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// "only NL, DE; unknown skipped", for the banner.
+    pub fn describe(&self) -> String {
+        let mut codes: Vec<&str> = self.countries.iter().map(String::as_str).collect();
+        codes.sort_unstable();
+        format!("{} {}; unknown {}",
+            if self.only { "only" } else { "skip" },
+            codes.join(", "),
+            match self.unknown {
+                UnknownCountry::Skip => "skipped",
+                UnknownCountry::Scan => "scanned",
+            }
+        )
+    }
+
+    /// The country `ip` is left out for ("unknown" when the database has
+    /// none), or None to probe it.
+    pub fn excludes(&self, ip: Ipv4Addr) -> Option<String> {
+        match self.country(ip) {
+            Some(code) => (self.countries.contains(&code) != self.only).then_some(code),
+            None => (self.unknown == UnknownCountry::Skip).then(|| UNKNOWN.to_string()),
+        }
+    }
+
+    fn country(&self, ip: Ipv4Addr) -> Option<String> {
+        let subnet = u32::from(ip) >> 8;
+        if let Some(code) = self.cache.lock().unwrap().get(&subnet) {
+            return code.clone();
+        }
+        // A damaged record counts as unknown
+        let (code, prefix) = self.db.country(ip).unwrap_or((None, 32));
+        if prefix <= 24 {
+            self.cache.lock().unwrap().insert(subnet, code.clone());
+        }
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8], pos: usize) -> Result<Value> {
+        Decoder { section: bytes, pos }.decode(0)
+    }
+
+    #[test]
+    fn decodes_maps_strings_and_pointers() {
+        // {"iso_code": "NL"} at 0, then a map whose key and value are pointers to it
+        let mut bytes = vec![0xe1, 0x48];
+        bytes.extend(b"iso_code");
+        bytes.extend([0x42, b'N', b'L']);
+        let country = decode(&bytes, 0).unwrap();
+        assert_eq!(country.get("iso_code").and_then(Value::as_str), Some("NL"));
+        let at = bytes.len();
+        bytes.extend([0xe1, 0x47]);
+        bytes.extend(b"country");
+        bytes.extend([0x20, 0x00]);
+        assert_eq!(decode(&bytes, at).unwrap().get("country"), Some(&country));
+    }
+
+    #[test]
+    fn reads_extended_types_and_long_sizes() {
+        // uint64 (extended type 9) 65536, then a 300-byte string (size 30: 285 + 15)
+        assert_eq!(decode(&[0x03, 0x02, 0x01, 0x00, 0x00], 0).unwrap(), Value::Uint(65_536));
+        let mut bytes = vec![0x5e, 0x00, 0x0f];
+        bytes.extend([b'x'; 300]);
+        assert_eq!(decode(&bytes, 0).unwrap(), Value::String("x".repeat(300)));
+        assert!(decode(&bytes[..100], 0).is_err());
+    }
+
+    #[test]
+    fn lists_the_networks_of_an_asn_database() {
+        fn string(s: &str) -> Vec<u8> {
+            let mut bytes = match s.len() {
+                len @ 0..=28 => vec![0x40 | len as u8],
+                len => vec![0x40 | 29, len as u8 - 29],
+            };
+            bytes.extend(s.as_bytes());
+            bytes
+        }
+        // A path of 8 nodes to 10.0.0.0/8, every other branch empty
+        let node_count = 8u32;
+        let mut bytes = Vec::new();
+        for depth in 0..8 {
+            let next = if depth == 7 { node_count + DATA_SEPARATOR as u32 } else { depth + 1 };
+            let (left, right) = match (10u32 >> (7 - depth)) & 1 {
+                0 => (next, node_count),
+                _ => (node_count, next),
+            };
+            bytes.extend(&left.to_be_bytes()[1..]);
+            bytes.extend(&right.to_be_bytes()[1..]);
+        }
+        bytes.extend([0; DATA_SEPARATOR]);
+        bytes.push(0xe2);
+        bytes.extend(string("autonomous_system_number"));
+        bytes.extend([0xc2, 0xfb, 0xf4]);
+        bytes.extend(string("autonomous_system_organization"));
+        bytes.extend(string("Example"));
+        bytes.extend(METADATA_MARKER);
+        bytes.push(0xe3);
+        bytes.extend(string("node_count"));
+        bytes.extend([0xc1, node_count as u8]);
+        bytes.extend(string("record_size"));
+        bytes.extend([0xa1, 24]);
+        bytes.extend(string("ip_version"));
+        bytes.extend([0xa1, 4]);
+
+        let db = Mmdb::from_bytes(bytes).unwrap();
+        let networks = db.asn_networks().unwrap();
+        assert_eq!(networks, [("10.0.0.0/8".parse().unwrap(), 64500, Some("Example".to_string()))]);
+    }
+
+    #[test]
+    fn refuses_files_without_metadata() {
+        assert!(Mmdb::from_bytes(vec![0; 64]).is_err());
+    }
+}
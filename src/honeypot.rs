@@ -0,0 +1,212 @@
+//! Signs that an Ollama hit is a honeypot or a fake serving a canned model
+//! list. Hits are never dropped for them; they get a `Suspicion` column and a
+//! warning line in the console.
+//!
+//! Some signs compare hits with each other, so an index of model list
+//! fingerprints is kept for the run. The first hits of a canned list cannot
+//! be told apart from a real server yet; only the ones after them are marked.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::Model;
+
+/// The `honeypot` section of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HoneypotConfig {
+    /// A model list at least this long...
+    pub long_list: usize,
+    /// ...served by this many hosts of one /24 is taken for canned
+    pub same_list_per_subnet: usize,
+    /// Lists of at least this many models whose `modified_at` are all the same
+    pub same_timestamp_models: usize,
+    /// Answers faster than this, in microseconds...
+    pub fast_response_us: u64,
+    /// ...with a model list this many hosts of the run served
+    pub fast_same_list: usize,
+    /// Case-insensitive parts of the Server header of known honeypot frameworks
+    pub servers: Vec<String>,
+    /// Digests (or their first characters) each model name is known to have;
+    /// another digest under the name is suspicious
+    pub known_digests: HashMap<String, Vec<String>>,
+}
+
+impl Default for HoneypotConfig {
+    fn default() -> Self {
+        Self {
+            long_list: 10,
+            same_list_per_subnet: 3,
+            same_timestamp_models: 3,
+            fast_response_us: 1000,
+            fast_same_list: 2,
+            servers: vec!["beelzebub".to_string(), "galah".to_string()],
+            known_digests: HashMap::new(),
+        }
+    }
+}
+
+/// Why a hit looks fake; no reasons means nothing stood out.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Suspicion {
+    pub reasons: Vec<String>,
+}
+
+impl Suspicion {
+    pub fn score(&self) -> usize {
+        self.reasons.len()
+    }
+}
+
+/// "2: same 12-model list on 3 hosts of this /24; ..." for the CSV, or
+/// nothing when there are no reasons.
+impl fmt::Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.reasons.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{}: {}", self.score(), self.reasons.join("; "))
+    }
+}
+
+/// What one hit looked like.
+pub struct Response<'a> {
+    pub ip: Ipv4Addr,
+    pub models: &'a [Model],
+    pub latency: Duration,
+    pub server: Option<&'a str>,
+}
+
+/// The heuristics and the run's index of model lists.
+#[derive(Debug, Default)]
+pub struct HoneypotCheck {
+    config: HoneypotConfig,
+    /// Hosts per model list fingerprint and /24
+    per_subnet: Mutex<HashMap<(u32, u64), usize>>,
+    /// Hosts per model list fingerprint in the whole run
+    per_run: Mutex<HashMap<u64, usize>>,
+}
+
+impl HoneypotCheck {
+    pub fn new(config: HoneypotConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Scores `response` and adds its model list to the index.
+    pub fn check(&self, response: &Response) -> Suspicion {
+        let config = &self.config;
+        let models = response.models;
+        let mut reasons = Vec::new();
+        let list = fingerprint(models);
+
+        let in_subnet = {
+            let mut per_subnet = self.per_subnet.lock().unwrap();
+            let count = per_subnet.entry((u32::from(response.ip) >> 8, list)).or_default();
+            *count += 1;
+            *count
+        };
+        if models.len() >= config.long_list && in_subnet >= config.same_list_per_subnet {
+            reasons.push(format!("same {}-model list on {} hosts of this /24", models.len(), in_subnet));
+        }
+
+        let in_run = {
+            let mut per_run = self.per_run.lock().unwrap();
+            let count = per_run.entry(list).or_default();
+            *count += 1;
+            *count
+        };
+        // Fresh installs all answer an empty list, so that one proves nothing
+        if !models.is_empty() && response.latency < Duration::from_micros(config.fast_response_us) && in_run >= config.fast_same_list {
+            reasons.push(format!("answered in {}µs with a model list {} hosts served", response.latency.as_micros(), in_run));
+        }
+
+        if models.len() >= config.same_timestamp_models.max(2)
+            && models.iter().all(|m| !m.modified_at.is_empty() && m.modified_at == models[0].modified_at)
+        {
+            reasons.push(format!("same modified_at on all {} models", models.len()));
+        }
+
+        for model in models {
+            let Some(known) = config.known_digests.get(&model.name) else {
+                continue;
+            };
+            let digest = model.digest.strip_prefix("sha256:").unwrap_or(&model.digest).to_ascii_lowercase();
+            let matches = |prefix: &String| {
+                let prefix = prefix.strip_prefix("sha256:").unwrap_or(prefix).to_ascii_lowercase();
+                !prefix.is_empty() && digest.starts_with(&prefix)
+            };
+            if !known.iter().any(matches) {
+                reasons.push(format!("unknown digest for {}", model.name));
+            }
+        }
+
+        if let Some(server) = response.server {
+            let lower = server.to_ascii_lowercase();
+            if let Some(name) = config.servers.iter().find(|name| lower.contains(&name.to_ascii_lowercase())) {
+                reasons.push(format!("Server header names {}", name));
+            }
+        }
+        Suspicion { reasons }
+    }
+}
+
+/// FNV-1a 64 over the sorted names and digests, so the same list in another
+/// order is the same list.
+fn fingerprint(models: &[Model]) -> u64 {
+    let mut entries: Vec<(&str, &str)> = models.iter().map(|m| (m.name.as_str(), m.digest.as_str())).collect();
+    entries.sort_unstable();
+    entries
+        .iter()
+        .flat_map(|(name, digest)| name.bytes().chain([0]).chain(digest.bytes()).chain([b'\n']))
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models(count: usize, modified_at: &str) -> Vec<Model> {
+        let list: Vec<serde_json::Value> = (0..count)
+            .map(|i| serde_json::json!({"name": format!("model-{}", i), "digest": format!("{:064x}", i), "modified_at": modified_at}))
+            .collect();
+        serde_json::from_value(serde_json::Value::Array(list)).unwrap()
+    }
+
+    fn response<'a>(ip: &str, models: &'a [Model], latency_us: u64) -> Response<'a> {
+        Response { ip: ip.parse().unwrap(), models, latency: Duration::from_micros(latency_us), server: None }
+    }
+
+    #[test]
+    fn flags_a_long_list_repeated_in_one_subnet() {
+        let check = HoneypotCheck::default();
+        let list = models(12, "");
+        assert_eq!(check.check(&response("10.0.0.1", &list, 50_000)).score(), 0);
+        assert_eq!(check.check(&response("10.0.1.1", &list, 50_000)).score(), 0);
+        assert_eq!(check.check(&response("10.0.0.2", &list, 50_000)).score(), 0);
+        let third = check.check(&response("10.0.0.3", &list, 50_000));
+        assert_eq!(third.to_string(), "1: same 12-model list on 3 hosts of this /24");
+    }
+
+    #[test]
+    fn flags_fast_repeats_shared_timestamps_digests_and_servers() {
+        let mut config = HoneypotConfig::default();
+        config.known_digests.insert("model-0".to_string(), vec!["sha256:abc".to_string(), "def".to_string()]);
+        let check = HoneypotCheck::new(config);
+        let list = models(3, "2024-05-01T10:00:00Z");
+        let first = check.check(&response("10.0.0.1", &list, 200));
+        assert_eq!(first.reasons, ["same modified_at on all 3 models", "unknown digest for model-0"]);
+        let mut second = response("10.9.0.1", &list, 200);
+        second.server = Some("Beelzebub/3.2");
+        assert_eq!(check.check(&second).reasons, [
+            "answered in 200µs with a model list 2 hosts served",
+            "same modified_at on all 3 models",
+            "unknown digest for model-0",
+            "Server header names beelzebub",
+        ]);
+        assert_eq!(HoneypotCheck::default().check(&response("10.9.0.2", &models(3, ""), 50_000)), Suspicion::default());
+    }
+}
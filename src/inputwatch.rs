@@ -0,0 +1,145 @@
+//! Ranges appended to the input files while a scan runs. Each cycle looks at
+//! ip-ranges.txt (or the `--input` files) every few seconds and queues the
+//! ranges on lines added at the end, so authorized ranges found mid-scan
+//! need no restart. Anything else, an edited or removed line, is too
+//! ambiguous to act on mid-run and is only warned about.
+//!
+//! A line counts once its newline is written, so a range is never read
+//! half-typed. JSON inputs are not line-oriented and are not watched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum Change {
+    /// Complete lines added at the end; `first_line` numbers the first
+    Appended { text: String, first_line: usize },
+    /// Anything but an append. The new content is taken as read, so lines
+    /// appended later are still picked up
+    Rewritten,
+    Unreadable(std::io::Error),
+}
+
+/// The input files a scan watches.
+#[derive(Debug)]
+pub struct InputWatch {
+    pub files: Vec<WatchedFile>,
+    /// Whether they came from `--input`, which labels ranges by file name
+    pub explicit: bool,
+}
+
+/// One input file and how much of it the scan has taken.
+#[derive(Debug)]
+pub struct WatchedFile {
+    path: PathBuf,
+    /// The content taken so far
+    taken: String,
+    /// Length and modification time when last looked at; None while the
+    /// file can't be read
+    stamp: Option<(u64, SystemTime)>,
+}
+
+impl WatchedFile {
+    /// A file whose `content` was loaded at the start of the run.
+    pub fn new(path: PathBuf, content: String) -> Self {
+        let stamp = stamp(&path).ok();
+        Self { path, taken: content, stamp }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What changed since the last look, if anything.
+    pub fn check(&mut self) -> Option<Change> {
+        let stamp = match stamp(&self.path) {
+            Ok(stamp) => stamp,
+            // Reported once, not at every look
+            Err(e) => return self.stamp.take().map(|_| Change::Unreadable(e)),
+        };
+        if self.stamp == Some(stamp) {
+            return None;
+        }
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) => return self.stamp.take().map(|_| Change::Unreadable(e)),
+        };
+        self.stamp = Some(stamp);
+        match appended(&self.taken, &content) {
+            Some("") => None,
+            Some(lines) => {
+                let first_line = self.taken.matches('\n').count() + 1;
+                let text = lines.to_string();
+                self.taken.push_str(lines);
+                Some(Change::Appended { text, first_line })
+            }
+            None => {
+                self.taken = content;
+                Some(Change::Rewritten)
+            }
+        }
+    }
+}
+
+fn stamp(path: &Path) -> std::io::Result<(u64, SystemTime)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified()?))
+}
+
+/// The complete lines `content` adds to `taken`, or None when it is not
+/// `taken` with lines appended. A last line `taken` left without a newline
+/// must not have been continued.
+fn appended<'a>(taken: &str, content: &'a str) -> Option<&'a str> {
+    let tail = content.strip_prefix(taken)?;
+    if !taken.is_empty() && !taken.ends_with('\n') && !tail.is_empty() && !tail.starts_with(['\n', '\r']) {
+        return None;
+    }
+    Some(&tail[..tail.rfind('\n').map_or(0, |i| i + 1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_only_complete_appended_lines() {
+        assert_eq!(appended("10.0.0.0/24\n", "10.0.0.0/24\n10.0.1.0/24\n10.0.2"), Some("10.0.1.0/24\n"));
+        assert_eq!(appended("10.0.0.0/24\n", "10.0.0.0/24\n10.0.2"), Some(""));
+        assert_eq!(appended("10.0.0.0/24", "10.0.0.0/24\n10.0.1.0/24\n"), Some("\n10.0.1.0/24\n"));
+        assert_eq!(appended("", "10.0.1.0/24\n"), Some("10.0.1.0/24\n"));
+    }
+
+    #[test]
+    fn anything_but_an_append_is_a_rewrite() {
+        assert_eq!(appended("10.0.0.0/24\n10.0.1.0/24\n", "10.0.0.0/24\n"), None);
+        assert_eq!(appended("10.0.0.0/24\n", "10.0.9.0/24\n10.0.1.0/24\n"), None);
+        // "10.0.0.1" turned into "10.0.0.10/24"
+        assert_eq!(appended("10.0.0.1", "10.0.0.10/24\n"), None);
+    }
+
+    #[test]
+    fn reports_appended_lines_with_their_numbers() {
+        let path = std::env::temp_dir().join(format!("inputwatch-test-{}.txt", std::process::id()));
+        fs::write(&path, "# ranges\n10.0.0.0/24\n").unwrap();
+        let mut file = WatchedFile::new(path.clone(), fs::read_to_string(&path).unwrap());
+        assert!(file.check().is_none());
+
+        fs::write(&path, "# ranges\n10.0.0.0/24\n10.0.1.0/24\n").unwrap();
+        // The stamp may not change within the filesystem's time resolution
+        file.stamp = None;
+        let Some(Change::Appended { text, first_line }) = file.check() else {
+            panic!("no append");
+        };
+        assert_eq!((text.as_str(), first_line), ("10.0.1.0/24\n", 3));
+
+        fs::write(&path, "10.0.1.0/24\n").unwrap();
+        file.stamp = None;
+        assert!(matches!(file.check(), Some(Change::Rewritten)));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(file.check(), Some(Change::Unreadable(_))));
+        assert!(file.check().is_none());
+    }
+}
@@ -0,0 +1,132 @@
+//! The keys that control a running scan. The keyboard thread dispatches on
+//! `BINDINGS`, and the banner's controls line and the `h` panel are drawn
+//! from it, so neither can list a key that does nothing.
+
+use console::style;
+
+use crate::snapshot::{self, Request};
+use crate::{checkpoint, control};
+use crate::{console_log, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+/// What the keyboard thread does after a key's action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    /// The help panel is up; the next key only closes it
+    HelpShown,
+    Stop,
+}
+
+pub struct Binding {
+    /// Matched in either case
+    pub key: char,
+    /// The banner's name for it, with the key in brackets
+    pub label: &'static str,
+    pub description: &'static str,
+    pub action: fn() -> Flow,
+}
+
+pub const BINDINGS: &[Binding] = &[
+    Binding { key: 'p', label: "[p]ause", description: "Pause the scan; requests in flight still finish", action: pause },
+    Binding { key: 'r', label: "[r]esume", description: "Resume a paused scan", action: resume },
+    Binding { key: 's', label: "[s]tatus", description: "Print where the scan is, its hits and errors", action: status },
+    Binding { key: 'w', label: "[w]rite checkpoint", description: "Save the checkpoint and flush the CSVs and event log", action: write },
+    Binding { key: 'h', label: "[h]elp", description: "Show this help", action: help },
+    Binding { key: 'q', label: "[q]uit", description: "Stop the scan; --resume continues it", action: quit },
+];
+
+/// The binding for `key`, if there is one.
+pub fn binding(key: char) -> Option<&'static Binding> {
+    BINDINGS.iter().find(|binding| binding.key == key.to_ascii_lowercase())
+}
+
+/// The banner's controls line, e.g. "[p]ause [r]esume [q]uit".
+pub fn controls() -> String {
+    BINDINGS.iter().map(|binding| binding.label).collect::<Vec<_>>().join(" ")
+}
+
+/// The settings the panel shows next to the keys, when a scan is running.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub rate: f64,
+    pub concurrency: usize,
+}
+
+pub fn help_block(settings: Option<Settings>) -> Vec<String> {
+    let mut block = vec![format!("\n{}{}", HEADER_STYLE, style("Keys").blue().bold())];
+    for binding in BINDINGS {
+        block.push(format!("{}{}  {}", LIST_ITEM_STYLE, style(binding.key).cyan().bold(), binding.description));
+    }
+    if let Some(settings) = settings {
+        block.push(format!("{}Now: {:.0}/s, concurrency {}", LIST_ITEM_STYLE, settings.rate, settings.concurrency));
+    }
+    block.push(format!("{}{}", LAST_ITEM_STYLE, style("Press any key to close").dim()));
+    block
+}
+
+fn pause() -> Flow {
+    control::pause("keyboard");
+    console_log(style("Scan paused. Press 'r' to resume...").yellow().to_string());
+    Flow::Continue
+}
+
+fn resume() -> Flow {
+    control::resume("keyboard");
+    console_log(style("Scan resumed").green().to_string());
+    Flow::Continue
+}
+
+fn status() -> Flow {
+    if !snapshot::request(Request::Status) {
+        console_log(style("No scan is running").dim().to_string());
+    }
+    Flow::Continue
+}
+
+fn write() -> Flow {
+    if !checkpoint::request_save() {
+        console_log(style("No scan is running").dim().to_string());
+    }
+    Flow::Continue
+}
+
+/// The running cycle draws the panel so it can add its live settings
+fn help() -> Flow {
+    if !snapshot::request(Request::Help) {
+        for line in help_block(None) {
+            console_log(line);
+        }
+    }
+    Flow::HelpShown
+}
+
+fn quit() -> Flow {
+    console_log(style("Exiting...").yellow().to_string());
+    control::stop("keyboard");
+    Flow::Stop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_distinct_and_named_in_their_labels() {
+        for (i, binding) in BINDINGS.iter().enumerate() {
+            assert!(binding.label.starts_with(&format!("[{}]", binding.key)), "{}", binding.label);
+            assert!(BINDINGS[..i].iter().all(|other| other.key != binding.key), "{} bound twice", binding.key);
+        }
+        assert_eq!(binding('Q').map(|b| b.key), Some('q'));
+        assert!(binding('x').is_none());
+    }
+
+    #[test]
+    fn help_lists_every_binding() {
+        let block = help_block(Some(Settings { rate: 99.6, concurrency: 480 }));
+        let plain: Vec<_> = block.iter().map(|line| console::strip_ansi_codes(line).to_string()).collect();
+        assert_eq!(plain.len(), BINDINGS.len() + 3);
+        assert_eq!(plain[1], "├─ p  Pause the scan; requests in flight still finish");
+        assert_eq!(plain[BINDINGS.len() + 1], "├─ Now: 100/s, concurrency 480");
+        assert_eq!(plain.last().unwrap(), "╰─ Press any key to close");
+    }
+}
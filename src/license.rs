@@ -0,0 +1,75 @@
+//! Short names for the license texts models ship in their modelfile, as
+//! written to the License column of llm_models.csv.
+
+/// Said of a license text that matches nothing in `KNOWN`
+pub const UNKNOWN: &str = "unknown";
+
+/// Each name with the phrases that identify its text, all of which must
+/// appear. Checked in order, against lowercase text with whitespace collapsed.
+const KNOWN: &[(&str, &[&str])] = &[
+    // "META LLAMA 3 COMMUNITY LICENSE" and "LLAMA 3.1/3.2/3.3 COMMUNITY LICENSE"
+    ("llama3", &["llama 3", "community license agreement"]),
+    ("llama2", &["llama 2 community license agreement"]),
+    ("gemma", &["gemma terms of use"]),
+    ("apache-2.0", &["apache license", "version 2.0"]),
+    ("mit", &["mit license"]),
+    // MIT's body without its title, as some modelfiles have it
+    ("mit", &["permission is hereby granted, free of charge", "without restriction"]),
+];
+
+/// The short name of a license text, `UNKNOWN` for text that matches no
+/// known license, or `None` when there is no text at all.
+pub fn normalize(text: &str) -> Option<&'static str> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    let known = KNOWN
+        .iter()
+        .find(|(_, phrases)| phrases.iter().all(|phrase| text.contains(phrase)))
+        .map(|(name, _)| *name);
+    Some(known.unwrap_or(UNKNOWN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The start of the license each model ships with on ollama.com
+    const LLAMA3: &str = "META LLAMA 3 COMMUNITY LICENSE AGREEMENT\nMeta Llama 3 Version Release Date: April 18, 2024\n\n\"Agreement\" means the terms and conditions for use, reproduction, distribution and modification of the Llama Materials set forth herein.";
+    const LLAMA3_1: &str = "LLAMA 3.1 COMMUNITY LICENSE AGREEMENT\nLlama 3.1 Version Release Date: July 23, 2024\n“Agreement” means the terms and conditions for use, reproduction, distribution and modification of the\nLlama Materials set forth herein.";
+    const LLAMA3_2: &str = "LLAMA 3.2 COMMUNITY LICENSE AGREEMENT\nLlama 3.2 Version Release Date: September 25, 2024\n\n“Agreement” means the terms and conditions for use, reproduction, distribution \nand modification of the Llama Materials set forth herein.";
+    const LLAMA2: &str = "LLAMA 2 COMMUNITY LICENSE AGREEMENT\t\nLlama 2 Version Release Date: July 18, 2023\n\n\"Agreement\" means the terms and conditions for use, reproduction, distribution and \nmodification of the Llama Materials set forth herein.";
+    const GEMMA: &str = "Gemma Terms of Use \n\nLast modified: February 21, 2024\n\nBy using, reproducing, modifying, distributing, performing or displaying any portion or element of Gemma, Model Derivatives including via any Hosted Service,";
+    const GEMMA3: &str = "Gemma Terms of Use\n\nLast modified: March 24, 2025\n\nBy using, reproducing, modifying, distributing, performing or displaying any portion or element of Gemma,";
+    // mistral, qwen2.5, nomic-embed-text
+    const APACHE: &str = "\n                                 Apache License\n                           Version 2.0, January 2004\n                        http://www.apache.org/licenses/\n\n   TERMS AND CONDITIONS FOR USE, REPRODUCTION, AND DISTRIBUTION";
+    const PHI3: &str = "Microsoft.\nCopyright (c) Microsoft Corporation.\n\nMIT License\n\nPermission is hereby granted, free of charge, to any person obtaining a copy\nof this software and associated documentation files (the \"Software\"), to deal\nin the Software without restriction,";
+    const DEEPSEEK_R1: &str = "MIT License\n\nCopyright (c) 2023 DeepSeek\n\nPermission is hereby granted, free of charge, to any person obtaining a copy";
+    const QWEN1_5: &str = "Tongyi Qianwen LICENSE AGREEMENT\n\nTongyi Qianwen Release Date: August 3, 2023\n\nBy clicking to agree or by using or distributing any portion or element of the Tongyi Qianwen Materials,";
+
+    #[test]
+    fn names_the_licenses_popular_models_ship() {
+        for (text, name) in [
+            (LLAMA3, "llama3"),
+            (LLAMA3_1, "llama3"),
+            (LLAMA3_2, "llama3"),
+            (LLAMA2, "llama2"),
+            (GEMMA, "gemma"),
+            (GEMMA3, "gemma"),
+            (APACHE, "apache-2.0"),
+            (PHI3, "mit"),
+            (DEEPSEEK_R1, "mit"),
+        ] {
+            assert_eq!(normalize(text), Some(name), "{}", text);
+        }
+    }
+
+    #[test]
+    fn anything_else_is_unknown() {
+        assert_eq!(normalize(QWEN1_5), Some(UNKNOWN));
+        assert_eq!(normalize("Proprietary. All rights reserved."), Some(UNKNOWN));
+        assert_eq!(normalize(" \n\t"), None);
+        assert_eq!(normalize(""), None);
+    }
+}
@@ -1,25 +1,23 @@
 use anyhow::{Context, Result};
 use console::style;
-use futures::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
 use ipnet::Ipv4Net;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::Semaphore;
 use std::net::Ipv4Addr;
 use regex::Regex;
-use std::fs::{self, OpenOptions};
-use std::path::Path;
-use std::time::Instant;
-use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
 use crossterm::{
     cursor,
     terminal::{Clear, ClearType},
     ExecutableCommand,
 };
-use std::io::Write;  // Add this import
+use std::io::{IsTerminal, Write};
 
 // Repository Information
 const REPO_URL: &str = "github.com/zonay/public-ollama-finder";
@@ -31,34 +29,129 @@ const LAST_ITEM_STYLE: &str = "╰─ ";
 
 static STOP_SCAN: AtomicBool = AtomicBool::new(false);
 static PAUSE_SCAN: AtomicBool = AtomicBool::new(false);
+// In --stream mode stdout carries only findings; everything human-oriented goes to stderr
+static STREAM_MODE: AtomicBool = AtomicBool::new(false);
+// With --plain-progress nothing redraws the console, so lines are written as they are
+static PLAIN_PROGRESS: AtomicBool = AtomicBool::new(false);
+// Set once the scan is over so the p/r/q handler stops reading keys meant for the browser
+static KEYBOARD_RELEASED: AtomicBool = AtomicBool::new(false);
 // Reduce concurrent connections to be more CPU friendly
 const CONCURRENT_LIMIT: usize = 2000;
 const RATE_LIMIT_PER_SECOND: u32 = 800;
+const OLLAMA_PORT: u16 = 11434;
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+/// Most contiguous runs one octet pattern may expand to (all of 10.*.*.1-100)
+const MAX_OCTET_RUNS: usize = 65_536;
+/// Wait before retrying a 429 that came without a usable Retry-After
+const DEFAULT_RETRY_WAIT: Duration = Duration::from_secs(5);
+/// Longest a Retry-After is honored for before the single retry
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+/// The second pass goes at a quarter of the sweep's rate
+const SECOND_PASS_RATE_PER_SECOND: u32 = RATE_LIMIT_PER_SECOND / 4;
+/// ASNs named next to the progress bar while `--max-rate-per-asn` holds them back
+const TOP_THROTTLED_ASNS: usize = 3;
 
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 struct ScanResult {
     ip: String,
+    base_url: String,
     status: u16,
     location: String,
+    /// (name, digest) of every model the endpoint listed
+    models: Vec<(String, String)>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Shared handles every probe task needs.
+#[derive(Clone)]
+struct ScanContext {
+    client: Arc<reqwest::Client>,
+    concurrency: Arc<ConcurrencyController>,
+    writer: WriteSender,
+    /// Where probe tasks send what they print
+    printer: PrintSender,
+    seen: Arc<SeenFindings>,
+    stats: Arc<ScanStats>,
+    args: Arc<ScanArgs>,
+    seed: u64,
+    sinks: SinkPublisher,
+    run_id: Arc<str>,
+    rate_limiter: Arc<TokenBucket>,
+    subnet_limiter: Option<Arc<SubnetLimiter>>,
+    asn_limiter: Option<Arc<AsnLimiter>>,
+    jitter: Option<Arc<Jitter>>,
+    /// `--prescan masscan`, unless masscan is not installed, or `--syn`
+    prescanner: Option<Arc<Prescanner>>,
+    /// `--precheck`, unless pings cannot be sent; absent during the second pass
+    pinger: Option<Arc<Pinger>>,
+    /// Bytes received, and the `--max-bandwidth` budget
+    bandwidth: Arc<BandwidthMeter>,
+    /// Absent for queue workers, whose batches live in Redis
+    checkpoint: Option<Arc<CheckpointTracker>>,
+    /// Addresses reached so far where targets overlap (none for queue
+    /// workers, whose batches were deduplicated when queued); absent during
+    /// the second pass
+    probed: Option<Arc<ProbedSet>>,
+    /// Run on every host, in order
+    probes: Arc<Vec<Box<dyn Probe>>>,
+    /// Takes `/api/show` requests with `--enrich-show`
+    enricher: Option<EnrichSender>,
+    /// Takes RDAP lookups with `--abuse-contacts`
+    rdap: Option<RdapSender>,
+    alerts: Arc<AlertRules>,
+    /// With `--quant`, `--min-quant` or `--family`
+    model_filter: Option<Arc<ModelFilter>>,
+    /// With `--only-countries`/`--skip-countries`
+    geo: Option<Arc<GeoFilter>>,
+    honeypot: Arc<HoneypotCheck>,
+    providers: Arc<ProviderIndex>,
+    /// Hosts lost to timeouts and resets; absent during the second pass and
+    /// with `--retry-queue 0`
+    retry_queue: Option<Arc<RetryQueue>>,
+    /// 1 for the sweep, 2 for the second pass
+    pass: u8,
+    /// The range being swept, for the status snapshot
+    current_range: Arc<Mutex<Option<CurrentRange>>>,
+}
+
+#[derive(Debug, Clone)]
+struct CurrentRange {
+    /// Counting from 1
+    number: usize,
+    location: String,
+    /// Progress bar position where the range's probes start, counting those
+    /// a resumed scan already did
+    start: u64,
+    /// Probes in the range
+    planned: u64,
+}
+
+/// Older Ollama releases leave out some of these, so all default to empty.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
 struct ModelDetails {
     parent_model: String,
     format: String,
     family: String,
     parameter_size: String,
     quantization_level: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    families: Vec<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct Model {
     name: String,
+    // Everything but the name was added over time; absent fields stay empty
+    #[serde(default)]
     model: String,
+    #[serde(default)]
     modified_at: String,
+    #[serde(default)]
     size: u64,
+    #[serde(default)]
     digest: String,
+    #[serde(default)]
     details: ModelDetails,
 }
 
@@ -67,482 +160,2693 @@ struct TagsResponse {
     models: Vec<Model>,
 }
 
+/// The client every request goes through. Its 2 s timeout is a fallback;
+/// probes and longer requests set their own.
+fn http_client(connect_timeout: Duration, pool: Pool, redirect: reqwest::redirect::Policy, dns: Option<&Arc<DnsResolver>>) -> Result<reqwest::Client> {
+    Ok(DnsResolver::apply(dns, reqwest::Client::builder())
+        .connect_timeout(connect_timeout)
+        .redirect(redirect)
+        .timeout(Duration::from_secs(2))
+        .pool_max_idle_per_host(pool.idle_per_host)
+        .pool_idle_timeout(pool.idle_timeout)
+        .tcp_keepalive(pool.keepalive)
+        .build()?)
+}
+
+/// Bytes as the GB (GiB) the CSVs and console report sizes in.
+fn gigabytes(bytes: u64) -> f64 {
+    bytes as f64 / 1_073_741_824.0
+}
+
+/// Where banners, hit blocks and summaries are written.
+fn chrome() -> Box<dyn Write> {
+    if STREAM_MODE.load(Ordering::Relaxed) {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
 fn console_log(msg: String) {
-    let mut stdout = std::io::stdout();
-    let _ = stdout.execute(cursor::MoveToColumn(0));
-    print!("{}\n", msg);
-    let _ = stdout.flush();
+    let mut out = chrome();
+    if !PLAIN_PROGRESS.load(Ordering::Relaxed) {
+        let _ = out.execute(cursor::MoveToColumn(0));
+    }
+    let _ = writeln!(out, "{}", anonymize::for_reports(&msg));
+    let _ = out.flush();
 }
 
-async fn check_host(
-    ip: String,
-    location: String,
-    client: &reqwest::Client,
-    semaphore: Arc<Semaphore>,
-    model_writer: Arc<tokio::sync::Mutex<csv::Writer<std::fs::File>>>,
-    endpoint_writer: Arc<tokio::sync::Mutex<csv::Writer<std::fs::File>>>,
-) -> Option<ScanResult> {
-    if STOP_SCAN.load(Ordering::Relaxed) {
-        return None;
+/// Waits out a pause. Returns false if the scan is stopped instead.
+async fn wait_while_paused() -> bool {
+    while PAUSE_SCAN.load(Ordering::Relaxed) && !STOP_SCAN.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
+    !STOP_SCAN.load(Ordering::Relaxed)
+}
 
-    let _permit = semaphore.acquire().await.ok()?;
-    let url = format!("http://{}:11434/api/tags", ip);
-
-    match client.get(&url).timeout(Duration::from_millis(500)).send().await {
-        Ok(response) => {
-            let status = response.status().as_u16();
-            match status {
-                200 => {
-                    if let Ok(tags_response) = response.json::<TagsResponse>().await {
-                        let mut model_writer = model_writer.lock().await;
-                        
-                        // Enhanced server info display
-                        console_log(format!("\n{}{}", 
-                            HEADER_STYLE,
-                            style("Found Ollama Server").green().bold()
-                        ));
-                        console_log(format!("{}API Endpoint: {}", 
-                            LIST_ITEM_STYLE,
-                            style(&url).cyan()
-                        ));
-                        console_log(format!("{}Server URL: {}", 
-                            LIST_ITEM_STYLE,
-                            style(format!("http://{}:11434", ip)).cyan()
-                        ));
-
-                        // Enhanced model list display
-                        if !tags_response.models.is_empty() {
-                            let mut models: Vec<_> = tags_response.models
-                                .iter()
-                                .map(|m| {
-                                    let size_gb = m.size as f64 / 1_073_741_824.0;
-                                    (m.name.as_str(), size_gb)
-                                })
-                                .collect();
-                            models.sort_by(|a, b| a.0.cmp(b.0));
-                            
-                            console_log(format!("{}Available Models:", LIST_ITEM_STYLE));
-                            for (i, (name, size)) in models.iter().enumerate() {
-                                let is_last = i == models.len() - 1;
-                                let prefix = if is_last { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
-                                let size_str = if *size > 0.0 {
-                                    style(format!(" ({:.2} GB)", size)).dim().to_string()
-                                } else {
-                                    "".to_string()
-                                };
-                                console_log(format!("{}{}{}{}",
-                                    "  ",  // Indent for nested items
-                                    prefix,
-                                    style(format!("{}. {}", i + 1, name)).blue(),
-                                    size_str
-                                ));
-                            }
-                            console_log("".to_string());
-                        }
-                        
-                        for model in tags_response.models {
-                            let size_gb = model.size as f64 / 1_073_741_824.0;
-                            model_writer.write_record(&[
-                                &format!("http://{}:11434", ip),
-                                &model.name,
-                                &model.model,
-                                &model.modified_at,
-                                &format!("{:.2}", size_gb), // Format size to 2 decimal places
-                                &model.digest,
-                                &model.details.parent_model,
-                                &model.details.format,
-                                &model.details.family,
-                                &model.details.parameter_size,
-                                &model.details.quantization_level,
-                            ]).unwrap();
-                            model_writer.flush().unwrap();
-                        }
-                    }
-                    let mut endpoint_writer = endpoint_writer.lock().await;
-                    endpoint_writer.write_record(&[
-                        &format!("http://{}:11434", ip),
-                        &url,
-                        &status.to_string(),
-                        &location,
-                    ]).unwrap();
-                    endpoint_writer.flush().unwrap();
-                    Some(ScanResult {
-                        ip,
-                        status,
-                        location,
-                    })
+/// Records what Ollama's `/api/tags` answered on a host. `webui` names the
+/// Open WebUI frontends found on the same host, if any, and `throttled` the
+/// wait after a 429 the answer came from a retry after.
+async fn record_ollama(
+    ip: Ipv4Addr,
+    found: ServiceFinding,
+    provenance: &Provenance,
+    webui: &str,
+    throttled: Option<Duration>,
+    ctx: &ScanContext,
+) -> Option<ScanResult> {
+    let Details::Ollama { models, missing, latency } = found.details else {
+        return None;
+    };
+    // Summed in bytes and formatted once, so rounding does not add up
+    let model_total = models.as_ref().map(|models| (models.len(), models.iter().map(|m| m.size).sum::<u64>()));
+    let url = format!("http://{}:{}{}", ip, found.port, found.path);
+    let latency_ms = latency.as_millis() as u64;
+    let status = found.status;
+    let proxy = ProxyInfo::from_headers(&found.headers);
+    match status {
+        200 => {
+            let base_url = format!("http://{}:{}", ip, found.port);
+            if !ctx.seen.insert_endpoint(&base_url) {
+                let _ = ctx.printer.send(vec![format!("{}{}",
+                    LIST_ITEM_STYLE,
+                    style(format!("{} already recorded earlier in this run", base_url)).dim()
+                )]);
+                return None;
+            }
+            if let Some(filter) = ctx.model_filter.as_deref().filter(|filter| filter.require_match) {
+                if !filter.matches_any(models.as_deref().unwrap_or_default()) {
+                    ctx.stats.record_filter_mismatch();
+                    let _ = ctx.printer.send(vec![format!("{}{}",
+                        LIST_ITEM_STYLE,
+                        style(format!("{} has no model matching the model filters; skipped", base_url)).dim()
+                    )]);
+                    return None;
                 }
-                404 => {
-                    console_log(format!("{}{}",
+            }
+            let discovered_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+            let fetcher = Fetcher::new(&ctx.client, &ctx.rate_limiter, ctx.subnet_limiter.as_deref(), ctx.asn_limiter.as_deref(), &ctx.bandwidth, ip, ctx.args.read_timeout, ctx.args.max_body_size as usize, None);
+            let write_access = match ctx.args.check_write_access {
+                true => Some(check_write_access(&fetcher, found.port).await),
+                false => None,
+            };
+            let embeddings = match (&models, ctx.args.check_embeddings) {
+                (Some(tag_models), true) => check_embeddings(&fetcher, found.port, tag_models).await,
+                _ => None,
+            };
+            let capabilities = match ctx.args.deep {
+                true => Some(probe_capabilities(&fetcher, found.port).await),
+                false => None,
+            };
+            let alerts = models.as_deref().map(|models| ctx.alerts.check(models)).unwrap_or_default();
+            let suspicion = ctx.honeypot.check(&Response {
+                ip,
+                models: models.as_deref().unwrap_or_default(),
+                latency,
+                server: found.headers.get(reqwest::header::SERVER).and_then(|value| value.to_str().ok()),
+            });
+            let provider = ctx.providers.provider(ip);
+            let throttling = throttled.map(|wait| format!("429, retried after {}s", wait.as_secs()));
+            let mut model_refs = Vec::new();
+            let mut finding_models = Vec::new();
+            if let Some(tag_models) = models {
+                ctx.stats.record_hit(&tag_models);
+                ctx.stats.remember_finding(format!("{} ({} models)", base_url, tag_models.len()));
+                model_refs = tag_models
+                    .iter()
+                    .map(|m| (m.name.clone(), m.digest.clone()))
+                    .collect();
+                // Enhanced server info display, printed in one piece
+                let mut block = Vec::new();
+                let (count, bytes) = model_total.unwrap_or_default();
+                block.push(format!("\n{}{} {}",
+                    HEADER_STYLE,
+                    style("Found Ollama Server").green().bold(),
+                    style(format!("({} models, {:.2} GB)", count, gigabytes(bytes))).dim()
+                ));
+                block.push(format!("{}API Endpoint: {}", 
+                    LIST_ITEM_STYLE,
+                    style(&url).cyan()
+                ));
+                block.push(format!("{}Server URL: {}", 
+                    LIST_ITEM_STYLE,
+                    style(&base_url).cyan()
+                ));
+                block.push(format!("{}Location: {} {}",
+                    LIST_ITEM_STYLE,
+                    provenance.location,
+                    style(format!("({})", provenance.source_text())).dim()
+                ));
+                if let Some(tag) = &ctx.args.label {
+                    block.push(format!("{}Tag: {}", LIST_ITEM_STYLE, style(tag).cyan()));
+                }
+                if provider != UNKNOWN_PROVIDER {
+                    block.push(format!("{}Provider: {}", LIST_ITEM_STYLE, style(provider).cyan()));
+                }
+                block.push(format!("{}Discovered At: {}", LIST_ITEM_STYLE, style(&discovered_at).dim()));
+                if suspicion.score() > 0 {
+                    block.push(format!("{}{} {}",
                         LIST_ITEM_STYLE,
-                        style(format!("Possible Ollama server (404): {}", url)).yellow()
+                        style("Possible honeypot:").yellow().bold(),
+                        style(suspicion.reasons.join("; ")).yellow()
                     ));
-                    None
                 }
-                _ => None,
+                if let Some(line) = proxy_line(&proxy) {
+                    block.push(format!("{}{}", LIST_ITEM_STYLE, line));
+                }
+                if let Some(note) = &throttling {
+                    block.push(format!("{}Throttled: {}", LIST_ITEM_STYLE, style(note).yellow()));
+                }
+                if !webui.is_empty() {
+                    block.push(format!("{}Open WebUI: {}", LIST_ITEM_STYLE, style(webui).magenta()));
+                }
+                if let Some(capabilities) = &capabilities {
+                    block.push(format!("{}Capabilities: {}", LIST_ITEM_STYLE, style(capabilities).cyan()));
+                }
+                if let Some(access) = write_access {
+                    let label = match access {
+                        WriteAccess::Open => style(access.as_str()).red().bold(),
+                        _ => style(access.as_str()).dim(),
+                    };
+                    block.push(format!("{}Write Access: {}", LIST_ITEM_STYLE, label));
+                }
+                if let Some(check) = &embeddings {
+                    let result = match check.dimension {
+                        Some(dimension) => style(format!("{} dimensions", dimension)).green(),
+                        None => style("failed".to_string()).dim(),
+                    };
+                    block.push(format!("{}Embeddings: {} {}", LIST_ITEM_STYLE, check.model, result));
+                }
+                if !missing.is_empty() {
+                    block.push(format!("{}{}",
+                        LIST_ITEM_STYLE,
+                        style(format!("Older model list format; left empty: {}", missing.join(", "))).dim()
+                    ));
+                }
+
+                if !tag_models.is_empty() {
+                    block.extend(model_block(&tag_models, ctx.args.sort_models, ctx.args.max_models_display));
+                }
+                if !alerts.is_empty() {
+                    block.extend(alert_block(&base_url, &alerts));
+                }
+                let _ = ctx.printer.send(block);
+
+                let largest = largest_models(&tag_models, ctx.args.enrich_models);
+                let mut models = ctx.seen.retain_new_models(&base_url, tag_models);
+                finding_models = models.clone();
+                if let Some(filter) = &ctx.model_filter {
+                    models.retain(|model| match filter.rejects(model) {
+                        Some(rule) => {
+                            ctx.stats.record_filtered(rule);
+                            false
+                        }
+                        None => true,
+                    });
+                }
+                // With --enrich-show the rows wait for the licenses
+                match &ctx.enricher {
+                    Some(enricher) => {
+                        let _ = enricher.send(EnrichJob {
+                            ip,
+                            port: found.port,
+                            base_url: base_url.clone(),
+                            models: largest,
+                            rows: models,
+                            discovered_at: discovered_at.clone(),
+                            location: provenance.location.clone(),
+                        });
+                    }
+                    None => {
+                        let _ = ctx.writer.send(WriteEvent::Models {
+                            base_url: base_url.clone(),
+                            models,
+                            licenses: BTreeMap::new(),
+                            discovered_at: discovered_at.clone(),
+                            location: provenance.location.clone(),
+                        }).await;
+                    }
+                }
+            } else {
+                ctx.stats.record_hit(&[]);
+            }
+            let finding = Arc::new(Finding {
+                ip: ip.to_string(),
+                port: found.port,
+                base_url: base_url.clone(),
+                tags_url: url.clone(),
+                status,
+                location: provenance.location.clone(),
+                source: provenance.source_text(),
+                latency_ms,
+                timestamp: discovered_at.clone(),
+                run_id: ctx.run_id.to_string(),
+                tag: ctx.args.label.clone().unwrap_or_default(),
+                models: finding_models,
+                alerts: alerts.iter().map(Alert::to_string).collect(),
+            });
+            eventlog::emit(eventlog::Event::Finding { finding: finding.clone() });
+            ctx.sinks.publish(finding).await;
+            let _ = ctx.writer.send(WriteEvent::Endpoint(EndpointRecord {
+                base_url: base_url.clone(),
+                tags_url: url,
+                status,
+                location: provenance.location.clone(),
+                source: provenance.source_text(),
+                proxy: proxy.summary,
+                fronting: proxy.fronting.unwrap_or_default().to_string(),
+                webui: webui.to_string(),
+                write_access: write_access.map(WriteAccess::as_str).unwrap_or_default(),
+                capabilities: capabilities.unwrap_or_default(),
+                alerts: alerts.iter().map(Alert::to_string).collect::<Vec<_>>().join("; "),
+                embeddings: embeddings.map(|check| check.dimension.map_or("failed".to_string(), |d| d.to_string())).unwrap_or_default(),
+                discovered_at,
+                model_total,
+                suspicion: suspicion.to_string(),
+                provider,
+                throttling: throttling.unwrap_or_default(),
+                pass: ctx.pass,
+            })).await;
+            if let Some(rdap) = &ctx.rdap {
+                let _ = rdap.send(RdapJob { ip, base_url: base_url.clone() });
             }
+            if ctx.pass > 1 {
+                ctx.stats.record_recovered();
+            }
+            Some(ScanResult {
+                ip: ip.to_string(),
+                base_url,
+                status,
+                location: provenance.location.clone(),
+                models: model_refs,
+            })
+        }
+        _ => {
+            ctx.stats.record_possible(status);
+            if ctx.args.records_status(status) {
+                let _ = ctx.writer.send(WriteEvent::Interesting(InterestingRecord {
+                    base_url: format!("http://{}:{}", ip, found.port),
+                    status,
+                    redirect: header_cell(&found.headers, "location"),
+                    server: header_cell(&found.headers, "server"),
+                    size: found.body_len,
+                    location: provenance.location.clone(),
+                    source: provenance.source_text(),
+                })).await;
+            }
+            if !ctx.args.reports_status(status) {
+                ctx.stats.record_silenced(status);
+                return None;
+            }
+            let _ = ctx.printer.send(vec![format!("{}{}{}",
+                LIST_ITEM_STYLE,
+                style(format!("Possible Ollama server ({}): {}", status, url)).yellow(),
+                proxy_line(&proxy).map(|line| format!(" • {}", line)).unwrap_or_default()
+            )]);
+            None
         }
-        Err(_) => None,
     }
 }
 
-fn parse_ip_range(input: &str) -> Result<Ipv4Net> {
-    // Try CIDR format first (e.g., "192.168.1.0/24")
-    if let Ok(network) = input.parse::<Ipv4Net>() {
-        return Ok(network);
+/// Records an `/api/tags` answer that failed verification, apart from the
+/// endpoints.
+async fn record_unverified(ip: Ipv4Addr, found: ServiceFinding, provenance: &Provenance, ctx: &ScanContext) {
+    let Details::Unverified { reason, body } = found.details else {
+        return;
+    };
+    let base_url = format!("http://{}:{}", ip, found.port);
+    let url = format!("{}{}", base_url, found.path);
+    if !ctx.seen.insert_endpoint(&base_url) {
+        return;
+    }
+    ctx.stats.record_unverified();
+    let _ = ctx.printer.send(vec![format!("{}{}",
+        LIST_ITEM_STYLE,
+        style(format!("Unverified response ({}): {}", reason, url)).yellow()
+    )]);
+    let _ = ctx.writer.send(WriteEvent::Unverified(UnverifiedRecord {
+        base_url,
+        tags_url: url,
+        status: found.status,
+        reason,
+        body,
+        location: provenance.location.clone(),
+        source: provenance.source_text(),
+    })).await;
+}
+
+/// Records a server found by one of the `--also` probes.
+async fn record_service(ip: Ipv4Addr, found: ServiceFinding, provenance: &Provenance, ctx: &ScanContext) {
+    let Details::Service { models, summary } = found.details else {
+        return;
+    };
+    let base_url = format!("http://{}:{}", ip, found.port);
+    let url = format!("{}{}", base_url, found.path);
+    if !ctx.seen.insert_endpoint(&base_url) {
+        return;
+    }
+    ctx.stats.record_other_hit();
+    ctx.stats.remember_finding(format!("{} ({})", base_url, found.service));
+
+    let mut block = vec![
+        format!("\n{}{}", HEADER_STYLE, style(format!("Found {} Server", found.service)).green().bold()),
+        format!("{}URL: {}", LIST_ITEM_STYLE, style(&url).cyan()),
+    ];
+    if !summary.is_empty() {
+        block.push(format!("{}Details: {}", LIST_ITEM_STYLE, summary));
+    }
+    block.push(format!("{}Location: {} {}",
+        LIST_ITEM_STYLE,
+        provenance.location,
+        style(format!("({})", provenance.source_text())).dim()
+    ));
+    block.push(format!("{}Models: {}\n",
+        LAST_ITEM_STYLE,
+        match models.is_empty() {
+            true => style("none listed".to_string()).dim(),
+            false => style(models.join(", ")).blue(),
+        }
+    ));
+    let _ = ctx.printer.send(block);
+
+    let _ = ctx.writer.send(WriteEvent::Service(ServiceRecord {
+        base_url,
+        service: found.service,
+        url,
+        status: found.status,
+        models,
+        details: summary,
+        location: provenance.location.clone(),
+        source: provenance.source_text(),
+        provider: ctx.providers.provider(ip),
+    })).await;
+}
+
+/// Sends `probe`'s requests for `ip` on one port, accounted for if the probe
+/// is. Only the first port's request was `prepaid` by the dispatcher.
+fn fetcher<'a>(ip: Ipv4Addr, probe: &dyn Probe, ctx: &'a ScanContext, prepaid: bool) -> Fetcher<'a> {
+    Fetcher::new(
+        &ctx.client,
+        &ctx.rate_limiter,
+        ctx.subnet_limiter.as_deref(),
+        ctx.asn_limiter.as_deref(),
+        &ctx.bandwidth,
+        ip,
+        ctx.args.read_timeout,
+        ctx.args.max_body_size as usize,
+        probe.accounted().then(|| Accounting { concurrency: &ctx.concurrency, stats: &ctx.stats, prepaid }),
+    )
+}
+
+/// "Proxy: server=nginx • behind Cloudflare" for the hit block, if the
+/// response headers showed anything in front of Ollama.
+fn proxy_line(proxy: &ProxyInfo) -> Option<String> {
+    let mut parts = Vec::new();
+    if !proxy.summary.is_empty() {
+        parts.push(proxy.summary.clone());
+    }
+    if let Some(fronting) = proxy.fronting {
+        parts.push(style(format!("behind {}", fronting)).magenta().to_string());
     }
+    (!parts.is_empty()).then(|| format!("Proxy: {}", parts.join(" • ")))
+}
+
+/// Splits an inclusive address range into the minimal list of CIDR blocks.
+fn range_to_cidrs(start: u32, end: u32) -> Vec<Ipv4Net> {
+    let mut blocks = Vec::new();
+    let mut current = start as u64;
+    let end = end as u64;
+    while current <= end {
+        // Largest block aligned at `current` that doesn't run past `end`
+        let mut size = 1u64 << current.trailing_zeros().min(32);
+        while current + size - 1 > end {
+            size >>= 1;
+        }
+        let prefix = 32 - size.trailing_zeros() as u8;
+        blocks.push(Ipv4Net::new(Ipv4Addr::from(current as u32), prefix).expect("prefix is at most 32"));
+        current += size;
+    }
+    blocks
+}
+
+/// Parses one octet field of the shorthand form: "10", "10-250" or "*".
+fn parse_octet_field(field: &str) -> Result<(u8, u8)> {
+    if field.trim() == "*" {
+        return Ok((0, 255));
+    }
+    let octet = |value: &str| -> Result<u8> {
+        value
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| anyhow::anyhow!("octet '{}' is not a number between 0 and 255", value.trim()))
+    };
+    let (low, high) = match field.split_once('-') {
+        Some((low, high)) => (octet(low)?, octet(high)?),
+        None => {
+            let value = octet(field)?;
+            (value, value)
+        }
+    };
+    if low > high {
+        anyhow::bail!("octet range {}-{} starts after it ends", low, high);
+    }
+    Ok((low, high))
+}
+
+/// Expands shorthand like "192.168.1.10-250", "10.0.0-3.0-255" or
+/// "10.12.*.0-128", where any octet may be a range or a wildcard, into
+/// contiguous address runs. Patterns that would need more than
+/// MAX_OCTET_RUNS runs, such as "*.*.*.1-100", are rejected.
+fn parse_octet_ranges(input: &str) -> Result<Vec<(u32, u32)>> {
+    let fields: Vec<&str> = input.split('.').collect();
+    if fields.len() != 4 {
+        anyhow::bail!("expected four octets in '{}'", input);
+    }
+    let fields = fields
+        .iter()
+        .map(|f| parse_octet_field(f))
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("Invalid octet range '{}'", input))?;
 
-    // Try range format (e.g., "192.168.1.1-192.168.1.255")
-    if input.contains('-') {
-        let parts: Vec<&str> = input.split('-').collect();
-        if parts.len() == 2 {
-            let start: Ipv4Addr = parts[0].trim().parse()?;
-            let end: Ipv4Addr = parts[1].trim().parse()?;
-            
-            // Convert range to CIDR blocks
-            let start_u32: u32 = start.into();
-            let end_u32: u32 = end.into();
-            
-            // Find the largest matching CIDR block
-            let prefix_len = 32 - (end_u32 - start_u32 + 1).trailing_zeros();
-            let network = Ipv4Net::new(start, prefix_len as u8)?;
-            return Ok(network);
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for a in fields[0].0..=fields[0].1 {
+        for b in fields[1].0..=fields[1].1 {
+            for c in fields[2].0..=fields[2].1 {
+                let start = u32::from(Ipv4Addr::new(a, b, c, fields[3].0));
+                let end = u32::from(Ipv4Addr::new(a, b, c, fields[3].1));
+                match runs.last_mut() {
+                    Some(last) if last.1.checked_add(1) == Some(start) => last.1 = end,
+                    _ => runs.push((start, end)),
+                }
+                if runs.len() > MAX_OCTET_RUNS {
+                    anyhow::bail!(
+                        "'{}' expands to more than {} separate address runs; split it up or use CIDR notation",
+                        input,
+                        MAX_OCTET_RUNS
+                    );
+                }
+            }
         }
     }
+    Ok(runs)
+}
+
+fn parse_ip_range(input: &str) -> Result<Vec<Block>> {
+    // Try CIDR format first (e.g., "192.168.1.0/24")
+    if let Ok(network) = input.parse::<Ipv4Net>() {
+        return Ok(vec![Block::cidr(network)]);
+    }
 
     // Try single IP (convert to /32 CIDR)
     if let Ok(ip) = input.parse::<Ipv4Addr>() {
-        return Ok(Ipv4Net::new(ip, 32)?);
+        return Ok(vec![Block::cidr(Ipv4Net::new(ip, 32)?)]);
+    }
+
+    if input.contains('-') || input.contains('*') {
+        // Full range format (e.g., "192.168.1.1-192.168.1.255")
+        if let Some((start, end)) = input.split_once('-') {
+            if let (Ok(start), Ok(end)) = (start.trim().parse::<Ipv4Addr>(), end.trim().parse::<Ipv4Addr>()) {
+                if start > end {
+                    anyhow::bail!("Range {} starts after it ends", input);
+                }
+                return Ok(range_to_cidrs(start.into(), end.into()).into_iter().map(Block::whole).collect());
+            }
+        }
+
+        // Shorthand with ranged or wildcard octets (e.g., "192.168.1.10-250", "10.*.0.1")
+        return Ok(parse_octet_ranges(input)?
+            .into_iter()
+            .flat_map(|(start, end)| range_to_cidrs(start, end))
+            .map(Block::whole)
+            .collect());
     }
 
     anyhow::bail!("Invalid IP range format: {}", input)
 }
 
-fn extract_ip_ranges(text: &str) -> Vec<(String, String)> {
+/// Four octets, each a number, a range or `*`
+const WILDCARD_PATTERN: &str = r"^(?:\*|\d{1,3}(?:-\d{1,3})?)(?:\.(?:\*|\d{1,3}(?:-\d{1,3})?)){3}$";
+
+/// The wildcard pattern a line starts with, or why it isn't a valid one.
+/// `None` when the line's first token has no `*`, so a `*` later on the line,
+/// e.g. in a comment, doesn't turn it into a wildcard line.
+fn wildcard_target<'a>(line: &'a str, pattern: &Regex) -> Option<Result<&'a str, &'static str>> {
+    let token = line.split_whitespace().next().filter(|token| token.contains('*'))?;
+    Some(if token.contains("**") {
+        Err("'**' is not a valid octet wildcard, use a single '*'")
+    } else if token.contains('/') {
+        Err("wildcards can't be combined with prefix notation")
+    } else if !pattern.is_match(token) {
+        Err("expected four octets, each a number, a range like 0-128, or '*'")
+    } else {
+        Ok(token)
+    })
+}
+
+/// Keys whose value names the entry of a JSON array they appear in.
+const JSON_LABEL_KEYS: [&str; 3] = ["region", "name", "location"];
+
+/// Collects the strings under `value`, labeled with the dotted key path that
+/// leads to them ("regions.eu.frankfurt"). Array elements that are objects
+/// with one of `JSON_LABEL_KEYS` use that value as the label for everything
+/// inside them instead. Strings without any key above them are labeled "JSON".
+fn extract_from_json(value: &serde_json::Value, path: &str, out: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::String(s) => {
+            let label = if path.is_empty() { "JSON" } else { path };
+            out.push((s.clone(), label.to_string()));
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                match json_label(item) {
+                    Some(label) => json_strings(item, &mut |s| out.push((s.to_string(), label.to_string()))),
+                    None => extract_from_json(item, path, out),
+                }
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for (key, field) in fields {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                extract_from_json(field, &path, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The first label key with a non-empty string value, if `value` is an object.
+fn json_label(value: &serde_json::Value) -> Option<&str> {
+    let fields = value.as_object()?;
+    JSON_LABEL_KEYS
+        .iter()
+        .find_map(|key| fields.get(*key)?.as_str().filter(|label| !label.is_empty()))
+}
+
+/// Every string under `value`, except the values of label keys.
+fn json_strings(value: &serde_json::Value, found: &mut impl FnMut(&str)) {
+    match value {
+        serde_json::Value::String(s) => found(s),
+        serde_json::Value::Array(items) => items.iter().for_each(|item| json_strings(item, found)),
+        serde_json::Value::Object(fields) => fields
+            .iter()
+            .filter(|(key, _)| !JSON_LABEL_KEYS.contains(&key.as_str()))
+            .for_each(|(_, field)| json_strings(field, found)),
+        _ => {}
+    }
+}
+
+/// The line each string literal of a JSON document first appears on, keyed by
+/// its raw text. Literals with escapes are left out; ranges never need them.
+fn json_string_lines(text: &str) -> HashMap<&str, usize> {
+    let mut lines = HashMap::new();
+    let mut line = 1;
+    let mut rest = text;
+    while let Some(quote) = rest.find(['"', '\n']) {
+        if rest.as_bytes()[quote] == b'\n' {
+            line += 1;
+            rest = &rest[quote + 1..];
+            continue;
+        }
+        let after = &rest[quote + 1..];
+        // The closing quote is the first one not escaped by a backslash
+        let mut escaped = false;
+        let end = after
+            .char_indices()
+            .find(|&(_, c)| {
+                let closes = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                closes
+            })
+            .map_or(after.len(), |(i, _)| i);
+        let literal = &after[..end];
+        if !literal.contains(['\\', '\n']) {
+            lines.entry(literal).or_insert(line);
+        }
+        line += literal.matches('\n').count();
+        rest = after.get(end + 1..).unwrap_or("");
+    }
+    lines
+}
+
+/// `customer-a: 203.0.113.0/24`: a name, then a colon and the range. The range
+/// must start with a digit or `*`, so `http://203.0.113.5` is no label.
+const PREFIX_LABEL_PATTERN: &str = r"^([A-Za-z][\w .-]*?)\s*:\s*([\d*].*)$";
+
+/// Splits the label off a line, written before it (`customer-a: 203.0.113.0/24`)
+/// or as a trailing comment (`203.0.113.0/24 # customer-a`).
+fn line_label<'a>(line: &'a str, prefix: &Regex) -> (&'a str, Option<&'a str>) {
+    if let Some(cap) = prefix.captures(line) {
+        let (label, rest) = (cap.get(1).unwrap().as_str(), cap.get(2).unwrap().as_str());
+        return (rest.split(" #").next().unwrap_or(rest).trim(), Some(label));
+    }
+    match line.split_once(" #").or_else(|| line.split_once("\t#")) {
+        Some((range, comment)) if !comment.trim().is_empty() => (range.trim(), Some(comment.trim())),
+        Some((range, _)) => (range.trim(), None),
+        None => (line, None),
+    }
+}
+
+/// " (customer-a)" to name a written label in a warning.
+fn labeled(label: Option<&str>) -> String {
+    label.map(|label| format!(" ({})", label)).unwrap_or_default()
+}
+
+/// A range as written in the input, its label and the line it is on.
+type ExtractedRange = (String, String, Option<usize>);
+
+/// Finds every range in `text` with a format label and the line it is on,
+/// plus warnings about lines that look like ranges but aren't valid.
+fn extract_ip_ranges(text: &str) -> (Vec<ExtractedRange>, Vec<String>) {
     let mut ranges = Vec::new();
+    let mut warnings = Vec::new();
     
     // Updated regex patterns to be compatible with Rust's regex engine
     let cidr_pattern = Regex::new(r"(\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}/\d{1,2})").unwrap();
     let range_pattern = Regex::new(r"(\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3})\s*-\s*(\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3})").unwrap();
+    let octet_range_pattern = Regex::new(r"(\d{1,3}(?:-\d{1,3})?\.\d{1,3}(?:-\d{1,3})?\.\d{1,3}(?:-\d{1,3})?\.\d{1,3}(?:-\d{1,3})?)").unwrap();
+    let wildcard_pattern = Regex::new(WILDCARD_PATTERN).unwrap();
     let single_ip_pattern = Regex::new(r"(\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3})(?:[^/\d]|$)").unwrap();
+    let prefix_label_pattern = Regex::new(PREFIX_LABEL_PATTERN).unwrap();
     
     // Try parsing as JSON first
     if let Ok(json) = serde_json::from_str::<serde_json::Value>(text) {
-        fn extract_from_value(value: &serde_json::Value) -> Vec<String> {
-            match value {
-                serde_json::Value::String(s) => vec![s.clone()],
-                serde_json::Value::Array(arr) => arr.iter()
-                    .flat_map(extract_from_value)
-                    .collect(),
-                serde_json::Value::Object(obj) => obj.values()
-                    .flat_map(extract_from_value)
-                    .collect(),
-                _ => vec![],
-            }
+        let mut found = Vec::new();
+        extract_from_json(&json, "", &mut found);
+        let lines = json_string_lines(text);
+        for (range, label) in found {
+            let line = lines.get(range.as_str()).copied();
+            ranges.push((range, label, line));
         }
-        
-        for ip_text in extract_from_value(&json) {
-            ranges.push((ip_text, "JSON".to_string()));
-        }
-        return ranges;
+        return (ranges, warnings);
     }
 
     // Process line by line for other formats
-    for line in text.lines() {
+    for (number, line) in text.lines().enumerate() {
         let line = line.trim();
+        let at = Some(number + 1);
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
+        let (line, written) = line_label(line, &prefix_label_pattern);
+        let found = ranges.len();
 
-        // Try CIDR notation
-        if let Some(cap) = cidr_pattern.captures(line) {
-            ranges.push((cap[1].to_string(), "CIDR".to_string()));
+        // Wildcard patterns keep the pattern itself as their location label
+        if let Some(wildcard) = wildcard_target(line, &wildcard_pattern) {
+            match wildcard {
+                Ok(pattern) => ranges.push((pattern.to_string(), written.unwrap_or(pattern).to_string(), at)),
+                Err(problem) => warnings.push(format!("line {}{}: invalid wildcard pattern '{}': {}", number + 1, labeled(written), line, problem)),
+            }
             continue;
         }
 
-        // Try IP range format
-        if let Some(cap) = range_pattern.captures(line) {
-            ranges.push((format!("{}-{}", &cap[1], &cap[2]), "Range".to_string()));
-            continue;
+        if let Some(cap) = cidr_pattern.captures(line) {
+            // Try CIDR notation
+            ranges.push((cap[1].to_string(), "CIDR".to_string(), at));
+        } else if let Some(cap) = range_pattern.captures(line) {
+            // Try IP range format
+            ranges.push((format!("{}-{}", &cap[1], &cap[2]), "Range".to_string(), at));
+        } else if let Some(cap) = octet_range_pattern.captures(line).filter(|cap| cap[1].contains('-')) {
+            // Try shorthand octet ranges like 192.168.1.10-250
+            ranges.push((cap[1].to_string(), "Range".to_string(), at));
+        } else if let Some(cap) = single_ip_pattern.captures(line) {
+            // Try single IP
+            ranges.push((format!("{}/32", &cap[1]), "Single IP".to_string(), at));
         }
 
-        // Try single IP
-        if let Some(cap) = single_ip_pattern.captures(line) {
-            ranges.push((format!("{}/32", &cap[1]), "Single IP".to_string()));
+        match (written, ranges.get_mut(found)) {
+            (Some(written), Some((_, label, _))) => *label = written.to_string(),
+            // Unlabeled lines without an address may be anything, but a label says one was meant
+            (Some(_), None) => warnings.push(format!("line {}{}: no valid address in '{}'", number + 1, labeled(written), line)),
+            _ => {}
         }
     }
 
-    ranges
+    (ranges, warnings)
 }
 
-fn load_ranges() -> Result<Vec<(Ipv4Net, String)>> {
-    let mut ranges = Vec::new();
-    let input_path = Path::new("ip-ranges.txt");
-    
-    // Read the entire file content
-    let content = fs::read_to_string(input_path)
-        .context("Failed to read IP ranges file")?;
+/// Format labels from `extract_ip_ranges` that say nothing about where a range
+/// belongs; ranges from `--input` files get the file's stem instead.
+const GENERIC_LABELS: [&str; 4] = ["CIDR", "Range", "Single IP", "JSON"];
 
-    // Extract IP ranges from any format
-    let extracted_ranges = extract_ip_ranges(&content);
-    
-    for (range_str, source) in extracted_ranges {
-        match parse_ip_range(&range_str) {
-            Ok(network) => ranges.push((network, source)),
-            Err(e) => eprintln!("Warning: Failed to parse IP range '{}': {}", range_str, e),
+/// The files behind `--input`: files as given, directories expanded to the
+/// `*.txt` and `*.json` files directly inside them in name order. A directory
+/// that can't be listed comes back as an error with its path.
+fn input_files(inputs: &[PathBuf]) -> Vec<Result<PathBuf, (PathBuf, std::io::Error)>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(Ok(input.clone()));
+            continue;
+        }
+        match fs::read_dir(input) {
+            Ok(entries) => {
+                let mut listed: Vec<PathBuf> = entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| {
+                        path.is_file()
+                            && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("txt") || ext.eq_ignore_ascii_case("json"))
+                    })
+                    .collect();
+                listed.sort();
+                files.extend(listed.into_iter().map(Ok));
+            }
+            Err(e) => files.push(Err((input.clone(), e))),
         }
     }
+    files
+}
+
+/// A range found in an input file: its blocks, label and where it was listed.
+type ListedRange = (Vec<Block>, String, Source);
+
+/// The ranges in `text` from the input file at `path`, whose first line is
+/// line `first_line`, plus warnings about what could not be parsed. With
+/// `explicit` `--input` files, generic labels become the file's name.
+fn ranges_in(text: &str, path: &Path, explicit: bool, first_line: usize) -> (Vec<ListedRange>, Vec<String>) {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+    let origin = path.display().to_string();
 
-    if ranges.is_empty() {
-        anyhow::bail!("No valid IP ranges found in input file");
+    // Extract IP ranges from any format
+    let (extracted_ranges, mut warnings) = extract_ip_ranges(text);
+    let mut listed = Vec::new();
+    for (range_str, label, line) in extracted_ranges {
+        let blocks = match parse_ip_range(&range_str) {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                // Format labels and wildcard patterns say nothing the range doesn't
+                let named = match label == range_str || GENERIC_LABELS.contains(&label.as_str()) {
+                    true => String::new(),
+                    false => format!(" ({})", label),
+                };
+                warnings.push(format!("Failed to parse IP range '{}'{}: {:#}", range_str, named, e));
+                continue;
+            }
+        };
+        let label = match &stem {
+            Some(stem) if explicit && GENERIC_LABELS.contains(&label.as_str()) => stem.clone(),
+            _ => label,
+        };
+        listed.push((blocks, label, Source::new(origin.clone(), line.map(|line| line + first_line - 1))));
     }
+    (listed, warnings)
+}
 
-    let mut stdout = std::io::stdout();
-    let _ = stdout.execute(Clear(ClearType::All));
-    let _ = stdout.execute(cursor::MoveTo(0, 0));
-    console_log(format!("Found {} valid IP ranges", ranges.len()));
-    Ok(ranges)
+/// What `load_ranges` made of one input file. A scan prints its problems as
+/// warnings; `--dry-run` lists all of it.
+pub struct InputReport {
+    pub path: PathBuf,
+    /// Why the file, or the directory it was to come from, could not be read
+    pub unreadable: Option<String>,
+    /// Ranges first listed in this file
+    pub accepted: usize,
+    /// Ranges listed before, in this file or an earlier one, and scanned once
+    pub repeated: usize,
+    /// What could not be parsed, and why
+    pub skipped: Vec<String>,
 }
 
-async fn scan_range(
-    network: Ipv4Net,
-    location: String,
-    client: Arc<reqwest::Client>,
-    semaphore: Arc<Semaphore>,
-    progress: Arc<ProgressBar>,
-    model_writer: Arc<tokio::sync::Mutex<csv::Writer<std::fs::File>>>,
-    endpoint_writer: Arc<tokio::sync::Mutex<csv::Writer<std::fs::File>>>,
-) -> Vec<ScanResult> {
-    let mut results = Vec::new();
-    let mut futures = Vec::new();
-    let mut last_scan = Instant::now();
-    let mut scan_count = 0;
-    
-    for ip in network.hosts() {
-        if STOP_SCAN.load(Ordering::Relaxed) {
-            break;
-        }
+/// The targets `load_ranges` found, with a report per input file.
+pub struct LoadedRanges {
+    pub targets: Vec<Target>,
+    pub watched: Vec<WatchedFile>,
+    pub inputs: Vec<InputReport>,
+    /// Read from `--input` rather than ip-ranges.txt
+    pub explicit: bool,
+}
 
-        while PAUSE_SCAN.load(Ordering::Relaxed) {
-            progress.set_message("PAUSED");
-            tokio::time::sleep(Duration::from_millis(100)).await;
-            if STOP_SCAN.load(Ordering::Relaxed) {
-                break;
+impl LoadedRanges {
+    /// The targets to scan, after the warnings about the input files. Fails
+    /// when no file yields a range.
+    fn into_targets(self) -> Result<(Vec<Target>, Vec<WatchedFile>)> {
+        for input in &self.inputs {
+            // Only name the file in warnings when there's more than one
+            let prefix = if self.explicit { format!("{}: ", input.path.display()) } else { String::new() };
+            for problem in input.unreadable.iter().chain(&input.skipped) {
+                eprintln!("Warning: {}{}", prefix, problem);
             }
         }
-        progress.set_message("");
+        if self.targets.is_empty() {
+            anyhow::bail!("No valid IP ranges found in {}", if self.explicit { "any input file" } else { "input file" });
+        }
 
-        // Rate limiting
-        scan_count += 1;
-        if scan_count >= RATE_LIMIT_PER_SECOND {
-            let elapsed = last_scan.elapsed();
-            if elapsed < Duration::from_secs(1) {
-                tokio::time::sleep(Duration::from_secs(1) - elapsed).await;
-            }
-            last_scan = Instant::now();
-            scan_count = 0;
+        if !PLAIN_PROGRESS.load(Ordering::Relaxed) {
+            let mut out = chrome();
+            let _ = out.execute(Clear(ClearType::All));
+            let _ = out.execute(cursor::MoveTo(0, 0));
+        }
+        if self.inputs.len() > 1 {
+            console_log(format!("Found {} valid IP ranges in {} files", self.targets.len(), self.inputs.len()));
+        } else {
+            console_log(format!("Found {} valid IP ranges", self.targets.len()));
         }
+        Ok((self.targets, self.watched))
+    }
+}
 
-        let ip = ip.to_string();
-        let location = location.clone();
-        let client = client.clone();
-        let semaphore = semaphore.clone();
-        let progress = progress.clone();
-        let model_writer = model_writer.clone();
-        let endpoint_writer = endpoint_writer.clone();
-
-        futures.push(tokio::spawn(async move {
-            let result = check_host(ip, location, &client, semaphore, model_writer, endpoint_writer).await;
-            progress.inc(1);
-            result
-        }));
-
-        // Process in smaller chunks to avoid memory buildup
-        if futures.len() >= 500 {
-            let chunk = futures.split_off(futures.len() - 500);
-            let mut buffer = futures::stream::iter(chunk)
-                .buffer_unordered(100)
-                .collect::<Vec<_>>()
-                .await;
-
-            for result in buffer.drain(..) {
-                if let Ok(Some(scan_result)) = result {
-                    results.push(scan_result);
+/// Loads the targets from ip-ranges.txt, or from every `--input` file. A file
+/// that can't be read is only reported, as are the lines that can't be
+/// parsed; without `--input` an unreadable ip-ranges.txt is an error. Ranges
+/// listed more than once are scanned once, keeping all labels. The text files
+/// read come back to be watched for appended ranges.
+fn load_ranges(inputs: &[PathBuf]) -> Result<LoadedRanges> {
+    let explicit = !inputs.is_empty();
+    let files = if explicit {
+        input_files(inputs)
+    } else {
+        vec![Ok(PathBuf::from("ip-ranges.txt"))]
+    };
+
+    let mut loaded = LoadedRanges { targets: Vec::new(), watched: Vec::new(), inputs: Vec::new(), explicit };
+    let mut seen: HashMap<Vec<Block>, usize> = HashMap::new();
+    for file in files {
+        let (path, content) = match file {
+            Ok(path) => match fs::read_to_string(&path) {
+                Ok(content) => (path, Ok(content)),
+                Err(e) if !explicit => return Err(e).context("Failed to read IP ranges file"),
+                Err(e) => (path, Err(format!("failed to read file: {}", e))),
+            },
+            Err((dir, e)) => (dir, Err(format!("failed to list the directory: {}", e))),
+        };
+        let mut report = InputReport { path, unreadable: None, accepted: 0, repeated: 0, skipped: Vec::new() };
+        let content = match content {
+            Ok(content) => content,
+            Err(problem) => {
+                report.unreadable = Some(problem);
+                loaded.inputs.push(report);
+                continue;
+            }
+        };
+
+        let (listed, warnings) = ranges_in(&content, &report.path, explicit, 1);
+        report.skipped = warnings;
+        for (blocks, label, source) in listed {
+            match seen.get(&blocks) {
+                Some(&index) => {
+                    let provenance = &mut loaded.targets[index].provenance;
+                    if !provenance.location.split(", ").any(|existing| existing == label) {
+                        provenance.location.push_str(", ");
+                        provenance.location.push_str(&label);
+                    }
+                    provenance.sources.push(source);
+                    report.repeated += 1;
+                }
+                None => {
+                    seen.insert(blocks.clone(), loaded.targets.len());
+                    loaded.targets.push(Target::new(blocks, Provenance::new(label, source)));
+                    report.accepted += 1;
                 }
             }
         }
+        if !report.path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            loaded.watched.push(WatchedFile::new(report.path.clone(), content));
+        }
+        loaded.inputs.push(report);
     }
+    Ok(loaded)
+}
 
-    // Process remaining futures
-    let mut buffer = futures::stream::iter(futures)
-        .buffer_unordered(100)
-        .collect::<Vec<_>>()
-        .await;
-
-    for result in buffer.drain(..) {
-        if let Ok(Some(scan_result)) = result {
-            results.push(scan_result);
+/// Queues the ranges appended to the watched input files since the last
+/// look, leaving out those already listed.
+fn add_appended_ranges(watch: &mut InputWatch, targets: &TargetQueue, ctx: &ScanContext, progress: &ProgressBar) {
+    for file in &mut watch.files {
+        let (text, first_line) = match file.check() {
+            None => continue,
+            Some(Change::Appended { text, first_line }) => (text, first_line),
+            Some(Change::Rewritten) => {
+                console_log(style(format!("{} changed other than by appending lines; edits and removals are ignored until the next run", file.path().display())).yellow().to_string());
+                continue;
+            }
+            Some(Change::Unreadable(e)) => {
+                console_log(style(format!("Can't read {} to look for new ranges: {}", file.path().display(), e)).yellow().to_string());
+                continue;
+            }
+        };
+        let (listed, warnings) = ranges_in(&text, file.path(), watch.explicit, first_line);
+        for warning in warnings {
+            console_log(style(format!("Warning: {}: {}", file.path().display(), warning)).yellow().to_string());
+        }
+        let mut all = targets.all();
+        let known = all.len();
+        for (blocks, label, source) in listed {
+            if !all.iter().any(|target| target.blocks == blocks) {
+                all.push(Target::new(blocks, Provenance::new(label, source)));
+            }
+        }
+        let added = all.len() - known;
+        if added == 0 {
+            continue;
+        }
+        let total = unique_count(&all, &ctx.args, ctx.seed).0;
+        let hosts = total.saturating_sub(targets.total());
+        if let Some(probed) = &ctx.probed {
+            let finished = ctx.checkpoint.as_ref().map_or(0, |c| c.position().0);
+            probed.extend(&all, &all[..finished.min(known)], &ctx.args, ctx.seed);
+        }
+        if let Some(checkpoint) = &ctx.checkpoint {
+            checkpoint.set_fingerprint(checkpoint::fingerprint(&all, &ctx.args));
+        }
+        if targets.append(all.split_off(known), total) {
+            // Only ranges this run goes on to scan count as added to it
+            eventlog::emit(eventlog::Event::TargetsAdded {
+                run_id: ctx.run_id.to_string(),
+                file: file.path().display().to_string(),
+                ranges: added,
+                targets: hosts,
+            });
+            progress.inc_length(hosts * ctx.args.ports_per_host());
+            console_log(style(format!("{} new targets added from {} ({} hosts)", added, file.path().display(), hosts)).green().to_string());
+        } else if ctx.args.interval.is_some() {
+            console_log(style(format!("{} new targets in {} will be scanned from the next cycle", added, file.path().display())).yellow().to_string());
+        } else {
+            console_log(style(format!("{} new targets in {} came after the last range was scanned; this run does not scan them", added, file.path().display())).yellow().to_string());
         }
     }
+}
 
+/// Scans range number `range`, skipping hosts the checkpoint already has.
+async fn scan_range(
+    range: usize,
+    target: &Target,
+    ctx: ScanContext,
+    progress: Arc<ProgressBar>,
+) -> Vec<ScanResult> {
+    if let Some(prescanner) = ctx.prescanner.clone() {
+        return prescan_range(range, target, &prescanner, ctx, progress).await;
+    }
+    let checkpoint = ctx.checkpoint.clone();
+    let hosts = (0u64..)
+        .zip(planned_hosts(target, &ctx.args, ctx.seed))
+        .filter(|(index, _)| !checkpoint.as_ref().is_some_and(|c| c.is_done(range, *index)));
+    let results = scan_hosts(listed_in(hosts, Arc::new(target.provenance.clone())), ctx, progress).await;
+    if let Some(checkpoint) = checkpoint.filter(|_| !STOP_SCAN.load(Ordering::Relaxed)) {
+        checkpoint.finish_range(range);
+    }
     results
 }
 
-fn setup_keyboard_handler() {
-    std::thread::spawn(|| {
-        while !STOP_SCAN.load(Ordering::Relaxed) {
-            // Poll for keyboard events with a timeout
-            if event::poll(std::time::Duration::from_millis(100)).unwrap_or(false) {
-                if let Ok(Event::Key(KeyEvent { code, .. })) = event::read() {
-                    match code {
-                        KeyCode::Char('p') | KeyCode::Char('P') => {
-                            PAUSE_SCAN.store(true, Ordering::Relaxed);
-                            console_log(style("Scan paused. Press 'r' to resume...").yellow().to_string());
-                        }
-                        KeyCode::Char('r') | KeyCode::Char('R') => {
-                            PAUSE_SCAN.store(false, Ordering::Relaxed);
-                            console_log(style("Scan resumed").green().to_string());
-                        }
-                        KeyCode::Char('q') | KeyCode::Char('Q') => {
-                            console_log(style("Exiting...").yellow().to_string());
-                            STOP_SCAN.store(true, Ordering::Relaxed);
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
-            }
+/// Dispatch rate and throttling shown after the progress bar. "PAUSED" only
+/// appears once the requests that were in flight have finished.
+fn status_message(ctx: &ScanContext) -> String {
+    if PAUSE_SCAN.load(Ordering::Relaxed) {
+        return match ctx.concurrency.in_flight() {
+            0 => "PAUSED".to_string(),
+            n => format!("PAUSING • waiting for {} in-flight requests", n),
+        };
+    }
+    let mut message = format!("• {:.0}/s • concurrency {}", ctx.rate_limiter.current_rate(), ctx.concurrency.limit());
+    message.push_str(&format!(" • {}/s", format_bytes(ctx.bandwidth.current_rate())));
+    if let Some(limit) = ctx.bandwidth.limit() {
+        message.push_str(&format!(" of {}/s", format_bytes(limit)));
+    }
+    let errors = ctx.stats.errors().compact();
+    if !errors.is_empty() {
+        message.push_str(&format!(" • errors: {}", errors));
+    }
+    if let Some(throttled) = ctx.subnet_limiter.as_ref().map(|l| l.throttled_subnets()).filter(|n| *n > 0) {
+        message.push_str(&format!(" • throttled subnets: {}", throttled));
+    }
+    if let Some(limiter) = &ctx.asn_limiter {
+        let throttled = limiter.most_throttled(TOP_THROTTLED_ASNS);
+        if !throttled.is_empty() {
+            let asns: Vec<String> = throttled.iter().map(|(asn, waiting)| format!("{} {} waiting", limiter.table().label(*asn), waiting)).collect();
+            message.push_str(&format!(" • throttled ASNs: {}", asns.join(", ")));
         }
-    });
+    }
+    message
 }
 
-mod disclaimer;
-use disclaimer::display_disclaimer;
+/// The `--plain-progress` line. Its format stays fixed for grep and log
+/// parsers; `total` and `percent` are `-` without a known total.
+fn plain_progress_line(now: chrono::DateTime<chrono::Utc>, position: u64, total: Option<u64>, rate: f64, hits: u64, errors: u64) -> String {
+    let (total, percent) = match total {
+        Some(total) => (total.to_string(), format!("{:.1}", position as f64 / total.max(1) as f64 * 100.0)),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    format!(
+        "{} progress scanned={}/{} percent={} rate={:.1}/s hits={} errors={}",
+        now.format("%Y-%m-%dT%H:%M:%SZ"),
+        position,
+        total,
+        percent,
+        rate,
+        hits,
+        errors
+    )
+}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Display disclaimer and check agreement
-    if !display_disclaimer()? {
-        return Ok(());
+/// What 'w' does: saves the checkpoint and flushes the CSVs and the event
+/// log. A failure is reported and the scan carries on.
+async fn save_on_request(checkpoint: Option<&CheckpointTracker>, writer: &WriteSender, ranges: usize) {
+    let saved = checkpoint.map(|checkpoint| checkpoint.save(&outdir::path(CHECKPOINT_FILE)).map(|_| checkpoint.position()));
+    let (tx, rx) = oneshot::channel();
+    let flushed = match writer.send(WriteEvent::Flush(tx)).await {
+        Ok(()) => rx.await.unwrap_or(Ok(())),
+        Err(_) => Ok(()),
+    };
+    eventlog::flush().await;
+    match saved {
+        Some(Ok((range, done))) => console_log(style(format!("Checkpoint saved to {}: {} of {} ranges finished, {} hosts of range {} done",
+            outdir::path(CHECKPOINT_FILE).display(),
+            range.min(ranges),
+            ranges,
+            done,
+            range + 1
+        )).green().to_string()),
+        Some(Err(e)) => console_log(style(format!("Failed to save {}: {:#}; the scan continues", outdir::path(CHECKPOINT_FILE).display(), e)).red().to_string()),
+        None => console_log(style("No checkpoint is kept with --queue-pull or --watch; results flushed").dim().to_string()),
     }
-
-    // Enable raw mode for keyboard input
-    crossterm::terminal::enable_raw_mode()?;
-    
-    ctrlc::set_handler(|| {
-        console_log(format!("{}",
-            style("Stopping scan... Press Ctrl+C again to force quit").yellow()
-        ));
-        STOP_SCAN.store(true, Ordering::Relaxed);
-    })?;
-
-    let ranges = load_ranges()?;
-    let total_ips: u64 = ranges.iter().map(|(net, _)| net.hosts().count() as u64).sum();
-    
-    // Print with proper alignment
-    let mut stdout = std::io::stdout();
-    let _ = stdout.execute(cursor::MoveTo(0, 1));
-    
-    console_log(format!("\n{}{}", 
-        HEADER_STYLE,
-        style("Public Ollama Finder").blue().bold()
-    ));
-    console_log(format!("{}Repository: {}", 
-        LIST_ITEM_STYLE,
-        style(REPO_URL).yellow()
-    ));
-    console_log(format!("{}Targets: {} IP ranges ({} total IPs)", 
-        LIST_ITEM_STYLE,
-        style(ranges.len()).cyan(),
-        style(total_ips).cyan()
-    ));
-    console_log(format!("{}Port: {}", 
-        LIST_ITEM_STYLE,
-        style("11434 /api/tags").yellow()
-    ));
-    console_log(format!("{}Controls: {}", 
-        LAST_ITEM_STYLE,
-        style("[p]ause [r]esume [q]uit | Ctrl+C to stop").dim()
-    ));
-    console_log("".to_string()); // Empty line before progress bar
-
-    setup_keyboard_handler();
-
-    let progress = ProgressBar::new(total_ips);
-    progress.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{bar:40.cyan/blue}] {percent:>3}% • {pos:>9}/{len} IPs {msg}")?
-            .progress_chars("█▓░"),
-    );
-
-    let client = Arc::new(
-        reqwest::Client::builder()
-            .timeout(Duration::from_secs(2))
-            .pool_max_idle_per_host(100)  // Reduced from 500
-            .tcp_keepalive(Duration::from_secs(10))
-            .build()?,
-    );
-    let semaphore = Arc::new(Semaphore::new(CONCURRENT_LIMIT));
-    let progress = Arc::new(progress);
-    
-    let endpoint_file = OpenOptions::new().append(true).create(true).open("ollama_endpoints.csv")?;
-    let mut endpoint_writer = csv::WriterBuilder::new().has_headers(false).from_writer(endpoint_file);
-    if fs::metadata("ollama_endpoints.csv")?.len() == 0 {
-        endpoint_writer.write_record(&["IP:Port", "Tags URL", "Status Code", "Location"])?;
+    if let Err(e) = flushed {
+        console_log(style(format!("Failed to flush results: {:#}", e)).red().to_string());
     }
-    let endpoint_writer = Arc::new(tokio::sync::Mutex::new(endpoint_writer));
+}
 
-    let model_file = OpenOptions::new().append(true).create(true).open("llm_models.csv")?;
-    let mut model_writer = csv::WriterBuilder::new().has_headers(false).from_writer(model_file);
-    if fs::metadata("llm_models.csv")?.len() == 0 {
-        model_writer.write_record(&[
-            "IP:Port", "Model Name", "Model", "Modified At", "Size", "Digest", 
-            "Parent Model", "Format", "Family", "Parameter Size", "Quantization Level"
-        ])?;
+/// What 's' prints: where the scan is and how it is going.
+fn take_snapshot(ctx: &ScanContext, progress: &ProgressBar, targets: &TargetQueue) -> snapshot::Snapshot {
+    let range = ctx.current_range.lock().unwrap().clone().map(|range| snapshot::RangePosition {
+        number: range.number,
+        of: targets.count(),
+        location: range.location,
+        done: progress.position().saturating_sub(range.start),
+        planned: range.planned,
+    });
+    snapshot::Snapshot {
+        active: ctx.stats.elapsed(),
+        paused_total: ctx.stats.paused(),
+        paused_for: PAUSE_SCAN.load(Ordering::Relaxed).then(|| PAUSE_CLOCK.current().unwrap_or_default()),
+        in_flight: ctx.concurrency.in_flight(),
+        range,
+        scanned: ctx.stats.attempted(),
+        targets: (!ctx.args.open_ended()).then(|| targets.total()),
+        hits: ctx.stats.hits(),
+        other_hits: ctx.stats.other_hits(),
+        recent: ctx.stats.recent_findings(),
+        errors: ctx.stats.errors().compact(),
+        error_mix: ErrorCategory::ALL.iter().map(|c| (c.label(), ctx.stats.errors().count(*c))).collect(),
+        rate: ctx.rate_limiter.current_rate(),
+        concurrency: ctx.concurrency.limit(),
     }
-    let model_writer = Arc::new(tokio::sync::Mutex::new(model_writer));
+}
 
-    let mut found_endpoints = Vec::new();
+/// Waits for a concurrency slot. Queued probes hold off while paused, so only
+/// requests that were already sent finish during a pause.
+async fn acquire_slot(ctx: &ScanContext) -> Option<Slot> {
+    loop {
+        if !wait_while_paused().await {
+            return None;
+        }
+        let slot = ctx.concurrency.acquire().await?;
+        if !PAUSE_SCAN.load(Ordering::Relaxed) {
+            return Some(slot);
+        }
+    }
+}
+
+/// Gives `slot` back for `wait` after a 429, then takes the rate tokens and a
+/// slot for the retry as the dispatcher would. None once the scan is stopped.
+async fn requeue(ip: Ipv4Addr, slot: Slot, wait: Duration, ctx: &ScanContext) -> Option<Slot> {
+    drop(slot);
+    let until = tokio::time::Instant::now() + wait;
+    while tokio::time::Instant::now() < until {
+        if STOP_SCAN.load(Ordering::Relaxed) {
+            return None;
+        }
+        tokio::time::sleep_until(until.min(tokio::time::Instant::now() + Duration::from_millis(100))).await;
+    }
+    ctx.rate_limiter.acquire().await;
+    if let Some(limiter) = &ctx.subnet_limiter {
+        limiter.acquire(ip).await;
+    }
+    if let Some(limiter) = &ctx.asn_limiter {
+        limiter.acquire(ip).await;
+    }
+    let slot = acquire_slot(ctx).await?;
+    ctx.bandwidth.acquire().await;
+    Some(slot)
+}
+
+/// Scans range number `range` as the pre-scan finds its open hosts. The
+/// range is checkpointed as a whole, since they come in no set order.
+async fn prescan_range(range: usize, target: &Target, prescanner: &Prescanner, ctx: ScanContext, progress: Arc<ProgressBar>) -> Vec<ScanResult> {
+    let mut ports: Vec<u16> = ctx.probes.iter().flat_map(|probe| probe.default_ports().iter().copied()).collect();
+    ports.sort_unstable();
+    ports.dedup();
+    let (open, sweep) = prescanner.sweep(target, &ports, progress.clone(), ctx.args.ports_per_host());
+    let provenance = Arc::new(target.provenance.clone());
+    let hosts = stream::unfold(open, |mut open| async move { open.recv().await.map(|ip| (ip, open)) })
+        .enumerate()
+        .map(move |(index, ip)| (index as u64, ip, provenance.clone()));
+    let checkpoint = ctx.checkpoint.clone();
+    let results = scan_hosts(hosts, ScanContext { checkpoint: None, ..ctx }, progress).await;
+    match sweep.await.map_err(anyhow::Error::from).and_then(|outcome| outcome) {
+        Ok(_) if STOP_SCAN.load(Ordering::Relaxed) => {}
+        Ok(swept) => console_log(style(format!("{} reported {} open host{} in {} ({}){}",
+            prescanner.name(),
+            swept.open,
+            if swept.open == 1 { "" } else { "s" },
+            target.provenance.location,
+            target.provenance.source_text(),
+            match (swept.refused, swept.unreachable) {
+                (0, 0) => String::new(),
+                (refused, unreachable) => format!("; {} ports refused, {} unreachable", refused, unreachable),
+            }
+        )).dim().to_string()),
+        Err(e) => {
+            console_log(style(format!("Pre-scan failed: {:#}", e)).red().to_string());
+            STOP_SCAN.store(true, Ordering::Relaxed);
+        }
+    }
+    if let Some(checkpoint) = checkpoint.filter(|_| !STOP_SCAN.load(Ordering::Relaxed)) {
+        checkpoint.finish_range(range);
+    }
+    results
+}
+
+/// A host to probe: its index, address and the target that listed it.
+type ListedHost = (u64, Ipv4Addr, Arc<Provenance>);
+
+/// `hosts` of one target, for `scan_hosts`.
+fn listed_in(hosts: impl Iterator<Item = (u64, Ipv4Addr)>, provenance: Arc<Provenance>) -> impl Stream<Item = ListedHost> {
+    stream::iter(hosts.map(move |(index, ip)| (index, ip, provenance.clone())))
+}
+
+/// Whether `ip` is still to be probed: not reached through an earlier target
+/// and not in an excluded country. Hosts turned away are counted and
+/// checkpointed as done.
+fn admit(index: u64, ip: Ipv4Addr, ctx: &ScanContext, progress: &ProgressBar) -> bool {
+    // Counted neither as progress nor in the total
+    if ctx.probed.as_ref().is_some_and(|probed| !probed.first_probe(ip)) {
+        ctx.stats.record_duplicate();
+        if let Some(checkpoint) = &ctx.checkpoint {
+            checkpoint.completed(index);
+        }
+        return false;
+    }
 
-    for (network, location) in ranges {
+    if let Some(country) = ctx.geo.as_ref().and_then(|geo| geo.excludes(ip)) {
+        ctx.stats.record_country_excluded(country);
+        progress.inc(ctx.args.ports_per_host());
+        if let Some(checkpoint) = &ctx.checkpoint {
+            checkpoint.completed(index);
+        }
+        return false;
+    }
+    true
+}
+
+/// Dispatches probes one by one as the rate limits and the concurrency
+/// controller allow, so the number of running probes always follows the
+/// controller's current limit.
+///
+/// Each host comes with its index among the range's planned hosts, which the
+/// checkpoint records once the probe has finished, and the target that
+/// listed it. Hosts may arrive as they are found, as they do with `--watch`.
+/// With `--precheck` they are pinged ahead of the dispatcher, after the
+/// duplicate and country checks so excluded hosts get no traffic at all.
+async fn scan_hosts(
+    hosts: impl Stream<Item = ListedHost>,
+    ctx: ScanContext,
+    progress: Arc<ProgressBar>,
+) -> Vec<ScanResult> {
+    let mut results = Vec::new();
+    let mut probes = JoinSet::new();
+    let hosts = hosts.filter(|(index, ip, _)| future::ready(admit(*index, *ip, &ctx, &progress)));
+    let hosts = match ctx.pinger.clone() {
+        Some(pinger) => pinged(hosts, |(_, ip, _)| *ip, pinger).left_stream(),
+        None => hosts.map(|host| (host, true)).right_stream(),
+    };
+    // Every HTTP request a host gets, for the count of those a ping saved
+    let requests_per_host: u64 = ctx.probes.iter().map(|probe| probe.default_ports().len() as u64).sum();
+    let mut hosts = std::pin::pin!(hosts);
+    while let Some(((index, ip, provenance), answered)) = hosts.next().await {
         if STOP_SCAN.load(Ordering::Relaxed) {
             break;
         }
 
-        let results = scan_range(
-            network,
-            location,
-            client.clone(),
-            semaphore.clone(),
-            progress.clone(),
-            model_writer.clone(),
-            endpoint_writer.clone(),
-        ).await;
+        if PAUSE_SCAN.load(Ordering::Relaxed) {
+            if !wait_while_paused().await {
+                break;
+            }
+            ctx.rate_limiter.restart();
+        }
 
-        for result in results {
-            found_endpoints.push(result.clone());
+        if !answered && ctx.pinger.as_ref().is_some_and(|pinger| pinger.skips_silent()) {
+            ctx.stats.record_ping_skipped(requests_per_host);
+            progress.inc(ctx.args.ports_per_host());
+            if let Some(checkpoint) = &ctx.checkpoint {
+                checkpoint.completed(index);
+            }
+            continue;
+        }
+        if !answered {
+            ctx.stats.record_ping_silent_probed();
+        }
+
+        if let Some(jitter) = &ctx.jitter {
+            if !jitter.wait().await {
+                break;
+            }
+        }
+        ctx.rate_limiter.acquire().await;
+        if let Some(limiter) = &ctx.subnet_limiter {
+            limiter.acquire(ip).await;
+        }
+        if let Some(limiter) = &ctx.asn_limiter {
+            limiter.acquire(ip).await;
+        }
+        let Some(slot) = acquire_slot(&ctx).await else {
+            break;
+        };
+        // Both limits apply. The byte budget is checked last so it counts the
+        // answers that arrived while this probe waited for a slot.
+        ctx.bandwidth.acquire().await;
+
+        let ctx = ctx.clone();
+        let progress = progress.clone();
+        probes.spawn(async move {
+            // Holds this host's share of the concurrency limit until all of its probes are done,
+            // except while waiting out a 429
+            let mut slot = slot;
+            // Extra probes go through the same rate limits as the Ollama one.
+            // They run first so an Ollama row can name the Open WebUI in front of it.
+            let mut webui = Vec::new();
+            let mut found = Vec::new();
+            // Whether an Ollama request timed out or was reset
+            let mut lost = false;
+            // The progress bar counts one step per Ollama port
+            let mut steps = ctx.args.ports_per_host();
+            for probe in ctx.probes.iter() {
+                for (i, &port) in probe.default_ports().iter().enumerate() {
+                    // A host whose probes did not all go out must not be recorded as done
+                    if STOP_SCAN.load(Ordering::Relaxed) {
+                        return Vec::new();
+                    }
+                    if probe.accounted() && i > 0 {
+                        progress.inc(1);
+                        steps -= 1;
+                    }
+                    // Retries, on a 429 or in the second pass, are not new attempts
+                    if probe.accounted() && i == 0 && ctx.pass == 1 {
+                        ctx.stats.record_attempt();
+                    }
+                    let mut throttled = None;
+                    let finding = loop {
+                        let fetcher = fetcher(ip, probe.as_ref(), &ctx, i == 0 || throttled.is_some());
+                        let finding = probe.probe(&fetcher, port).await;
+                        lost |= fetcher.failure().is_some_and(ErrorCategory::is_transient);
+                        let Some(Details::Throttled { retry_after }) = finding.as_ref().map(|f| &f.details) else {
+                            break finding;
+                        };
+                        ctx.stats.record_throttled(ip);
+                        if throttled.is_some() {
+                            let _ = ctx.printer.send(vec![format!("{}{}",
+                                LIST_ITEM_STYLE,
+                                style(format!("http://{}:{} answered 429 again after waiting; skipped", ip, port)).dim()
+                            )]);
+                            break None;
+                        }
+                        // Retried once, with the slot handed back while waiting
+                        let wait = retry_after.unwrap_or(DEFAULT_RETRY_WAIT).min(MAX_RETRY_WAIT);
+                        if let Some(limiter) = &ctx.subnet_limiter {
+                            limiter.back_off(ip, wait);
+                        }
+                        slot = match requeue(ip, slot, wait, &ctx).await {
+                            Some(slot) => slot,
+                            None => return Vec::new(),
+                        };
+                        throttled = Some(wait);
+                    };
+                    let Some(finding) = finding else {
+                        continue;
+                    };
+                    match &finding.details {
+                        Details::Ollama { models, .. } => {
+                            // A bare 401/403/404 is not enough to stop looking on the other ports
+                            let confirmed = models.is_some();
+                            found.extend(record_ollama(ip, finding, &provenance, &webui.join("; "), throttled, &ctx).await);
+                            if confirmed && !ctx.args.all_ports {
+                                break;
+                            }
+                        }
+                        Details::Unverified { .. } => record_unverified(ip, finding, &provenance, &ctx).await,
+                        Details::Throttled { .. } => {}
+                        Details::Service { summary, .. } => {
+                            if finding.service == OPEN_WEBUI {
+                                webui.push(format!("http://{}:{} ({})", ip, finding.port, summary));
+                            }
+                            record_service(ip, finding, &provenance, &ctx).await;
+                        }
+                    }
+                }
+            }
+            progress.inc(steps);
+            if let Some(checkpoint) = &ctx.checkpoint {
+                checkpoint.completed(index);
+            }
+            if !answered && !found.is_empty() {
+                ctx.stats.record_ping_silent_found();
+            }
+            if let Some(queue) = ctx.retry_queue.as_ref().filter(|_| lost && found.is_empty()) {
+                if !queue.push(ip, provenance.clone()) {
+                    ctx.stats.record_retry_overflow();
+                }
+            }
+            found
+        });
+
+        while let Some(done) = probes.try_join_next() {
+            results.extend(done.into_iter().flatten());
+        }
+    }
+
+    while let Some(done) = probes.join_next().await {
+        results.extend(done.into_iter().flatten());
+    }
+    results
+}
+
+fn setup_keyboard_handler() -> std::thread::JoinHandle<()> {
+    std::thread::spawn(|| {
+        let mut help_shown = false;
+        while !STOP_SCAN.load(Ordering::Relaxed) && !KEYBOARD_RELEASED.load(Ordering::Relaxed) {
+            // Poll for keyboard events with a timeout
+            if event::poll(std::time::Duration::from_millis(100)).unwrap_or(false) {
+                if let Ok(Event::Key(KeyEvent { code, .. })) = event::read() {
+                    // Whatever closes the help panel does nothing else
+                    if std::mem::take(&mut help_shown) {
+                        continue;
+                    }
+                    let KeyCode::Char(key) = code else { continue };
+                    match keys::binding(key).map(|binding| (binding.action)()) {
+                        Some(keys::Flow::Stop) => break,
+                        Some(keys::Flow::HelpShown) => help_shown = true,
+                        Some(keys::Flow::Continue) | None => {}
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// One complete pass over all ranges. Everything that accumulates per run
+/// (writers, dedup set, stats) lives here so daemon mode doesn't grow memory.
+#[allow(clippy::too_many_arguments)]
+async fn run_cycle(
+    targets: &Arc<TargetQueue>,
+    watch: Option<Arc<Mutex<InputWatch>>>,
+    listeners: &api::Listeners,
+    client: Arc<reqwest::Client>,
+    args: Arc<ScanArgs>,
+    alerts: Arc<AlertRules>,
+    geo: Option<Arc<GeoFilter>>,
+    honeypot: &HoneypotConfig,
+    providers: Arc<ProviderIndex>,
+    asn_limiter: Option<Arc<AsnLimiter>>,
+    prescanner: Option<Arc<Prescanner>>,
+    pinger: Option<Arc<Pinger>>,
+    dns: Option<Arc<DnsResolver>>,
+    seed: u64,
+    baseline: Option<&diff::Baseline>,
+    checkpoint: Option<Checkpoint>,
+) -> Result<(u64, Vec<ScanResult>)> {
+    // Hosts a resumed scan already covered count as progress, but not toward the ETA.
+    // With several ports per host the bar counts probes rather than hosts.
+    let per_host = args.ports_per_host();
+    // As they stand now; the watch task queues appended ones
+    targets.reopen();
+    let ranges = &targets.all()[..];
+    let total_ips = targets.total();
+    let finished = &ranges[..checkpoint.as_ref().map_or(0, |c| c.range().min(ranges.len()))];
+    let resumed_from = per_host * checkpoint.as_ref().map_or(0, |c| unique_count(finished, &args, seed).0 + c.done_in_range());
+    // The finished ranges' hosts count as reached, so later ranges skip them as before
+    let probed = ProbedSet::new(ranges);
+    probed.replay(finished, &args, seed);
+    // With a pre-scan the bar counts the hosts verified of those reported open
+    let resumed_from = if prescanner.is_some() { 0 } else { resumed_from };
+    let progress = ProgressBar::new(if prescanner.is_some() { 0 } else { total_ips * per_host });
+    progress.set_position(resumed_from);
+    let unit = match (prescanner.is_some(), per_host > 1) {
+        (true, true) => "probes verified / reported open",
+        (true, false) => "verified / reported open",
+        (false, true) => "probes",
+        (false, false) => "IPs",
+    };
+    // Elapsed time and ETA exclude pauses; indicatif's own {elapsed}/{eta} would not
+    let timer = ActiveTimer::start();
+    let active = move |_: &ProgressState, w: &mut dyn std::fmt::Write| {
+        let _ = write!(w, "{} active", format_duration(timer.active()));
+        let paused = timer.paused();
+        if paused >= Duration::from_secs(1) {
+            let _ = write!(w, ", paused for {}", format_duration(paused));
+        }
+    };
+    let stats = Arc::new(ScanStats::default());
+    if args.open_ended() {
+        // A feed has no total, so no bar or ETA either
+        let stats = stats.clone();
+        progress.set_style(
+            ProgressStyle::default_spinner()
+                .template(&format!("{{spinner:.green}} {{pos}} {} processed • {{found}} found • {{active}} {{msg}}", unit))?
+                .with_key("active", active)
+                .with_key("found", move |_: &ProgressState, w: &mut dyn std::fmt::Write| {
+                    let _ = write!(w, "{}", stats.hits());
+                }),
+        );
+        progress.enable_steady_tick(Duration::from_millis(200));
+    } else {
+        progress.set_style(
+            ProgressStyle::default_bar()
+                .template(&format!("{{spinner:.green}} [{{bar:40.cyan/blue}}] {{percent:>3}}% • {{pos:>9}}/{{len}} {} • {{active}} • ETA {{active_eta}} {{msg}}", unit))?
+                .with_key("active", active)
+                .with_key("active_eta", move |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                    let (pos, len) = (state.pos(), state.len().unwrap_or(0));
+                    let scanned = pos.saturating_sub(resumed_from);
+                    if scanned == 0 || pos >= len {
+                        let _ = write!(w, "-");
+                    } else {
+                        let remaining = timer.active().as_secs_f64() / scanned as f64 * (len - pos) as f64;
+                        let _ = write!(w, "{}", format_duration(Duration::from_secs_f64(remaining)));
+                    }
+                })
+                .progress_chars("█▓░"),
+        );
+    }
+    if args.plain_progress {
+        progress.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    let progress = Arc::new(progress);
+    let (printer, printer_task) = spawn_printer(progress.clone());
+
+    let run_id = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let (writer, writer_task) = spawn_writer(run_id.clone(), args.label.clone().unwrap_or_default(), OptionalOutputs {
+        other_services: !args.also.is_empty(),
+        unverified: args.verification() != Verification::Off,
+        model_details: args.enrich_show,
+        interesting: args.record_non_200.then_some(args.max_non_200),
+        abuse_contacts: args.abuse_contacts,
+    }, args.rotation(), args.migrate_output)?;
+    let mut sinks = build_sinks(&args, dns.as_ref()).await?;
+    if !listeners.is_empty() {
+        let findings = listeners.findings.clone();
+        sinks.add_lossless("HTTP", move |rx| api::collect(findings, rx));
+    }
+    sinks.publisher().emit(SinkEvent::ScanStarted {
+        run_id: run_id.clone(),
+        targets: total_ips,
+    }).await;
+    eventlog::emit(eventlog::Event::ScanStarted { run_id: run_id.clone(), targets: total_ips });
+    let rate_limiter = Arc::new(TokenBucket::new(RATE_LIMIT_PER_SECOND, args.burst));
+    let subnet_limiter = (args.subnet_rate > 0).then(|| Arc::new(SubnetLimiter::new(args.subnet_rate)));
+    // /api/show requests wait behind the sweep but share its rate limits
+    let (enricher, enrich_task) = match args.enrich_show {
+        true => {
+            let (enricher, task) = spawn_enricher(client.clone(), rate_limiter.clone(), subnet_limiter.clone(), asn_limiter.clone(), writer.clone());
+            (Some(enricher), Some(task))
+        }
+        false => (None, None),
+    };
+    let (rdap, rdap_task) = match args.abuse_contacts {
+        true => {
+            let (rdap, task) = rdap::spawn_lookups(&args.rdap_server, writer.clone(), dns.as_ref())?;
+            (Some(rdap), Some(task))
+        }
+        false => (None, None),
+    };
+    let ctx = ScanContext {
+        client,
+        concurrency: Arc::new(match args.concurrency_fixed {
+            Some(limit) => ConcurrencyController::fixed(limit),
+            None => ConcurrencyController::adaptive(CONCURRENT_LIMIT),
+        }),
+        writer,
+        printer,
+        seen: Arc::new(SeenFindings::default()),
+        stats,
+        args: args.clone(),
+        seed,
+        sinks: sinks.publisher(),
+        run_id: run_id.into(),
+        rate_limiter,
+        subnet_limiter,
+        asn_limiter,
+        jitter: args.jitter.map(|range| Arc::new(Jitter::new(range, seed))),
+        prescanner,
+        pinger,
+        bandwidth: Arc::new(BandwidthMeter::new(args.max_bandwidth)),
+        checkpoint: checkpoint.map(|c| Arc::new(CheckpointTracker::new(c))),
+        probed: Some(Arc::new(probed)),
+        probes: Arc::new(probes::registry(&args.extra_services(), args.verification(), args.port_profile)),
+        enricher,
+        rdap,
+        alerts,
+        model_filter: ModelFilter::from_args(&args).map(Arc::new),
+        geo,
+        honeypot: Arc::new(HoneypotCheck::new(honeypot.clone())),
+        providers,
+        // An open-ended scan never finishes, so there is no second pass to hold hosts for
+        retry_queue: (args.retry_queue > 0 && !args.open_ended()).then(|| Arc::new(RetryQueue::new(args.retry_queue))),
+        pass: 1,
+        current_range: Arc::new(Mutex::new(None)),
+    };
+    let mut run_info = RunInfo::start(&ctx.run_id, &args, &ctx.probes, &ctx.providers, seed, total_ips);
+    if let Err(e) = run_info.save() {
+        console_log(style(format!("{:#}", e)).yellow().to_string());
+    }
+
+    // Saved periodically so even a killed process loses at most a few seconds,
+    // and whenever 'w' asks; this task is the only one writing while the scan runs
+    let checkpoint_task = {
+        let (checkpoint, writer) = (ctx.checkpoint.clone(), ctx.writer.clone());
+        let targets = targets.clone();
+        let mut requests = checkpoint::listen();
+        tokio::spawn(async move {
+            let mut warned = false;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(CHECKPOINT_INTERVAL), if checkpoint.is_some() => {
+                        if let Some(Err(e)) = checkpoint.as_ref().map(|c| c.save(&outdir::path(CHECKPOINT_FILE))) {
+                            if !warned {
+                                console_log(style(format!("Failed to save {}: {}", outdir::path(CHECKPOINT_FILE).display(), e)).yellow().to_string());
+                                warned = true;
+                            }
+                        }
+                    }
+                    Some(()) = requests.recv() => save_on_request(checkpoint.as_deref(), &writer, targets.count()).await,
+                    else => break,
+                }
+            }
+        })
+    };
+
+    // Keeps the status current even while dispatch is blocked on a pause
+    let status_task = {
+        let (ctx, progress) = (ctx.clone(), progress.clone());
+        tokio::spawn(async move {
+            loop {
+                progress.set_message(status_message(&ctx));
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+        })
+    };
+
+    // Stands in for the bar in logs: a line every --progress-interval
+    let plain_task = ctx.args.plain_progress.then(|| {
+        let (ctx, progress) = (ctx.clone(), progress.clone());
+        let open_ended = ctx.args.open_ended();
+        tokio::spawn(async move {
+            let interval = ctx.args.progress_interval;
+            let mut last = progress.position();
+            loop {
+                tokio::time::sleep(interval).await;
+                let position = progress.position();
+                let total = (!open_ended).then(|| progress.length().unwrap_or(0));
+                let rate = position.saturating_sub(last) as f64 / interval.as_secs_f64();
+                console_log(plain_progress_line(chrono::Utc::now(), position, total, rate, ctx.stats.hits(), ctx.stats.errors().total()));
+                last = position;
+            }
+        })
+    });
+
+    // Answers 's' and 'h'; the block goes through the printer like a hit
+    let snapshot_task = {
+        let (ctx, progress) = (ctx.clone(), progress.clone());
+        let targets = targets.clone();
+        let mut requests = snapshot::listen();
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                let block = match request {
+                    snapshot::Request::Status => take_snapshot(&ctx, &progress, &targets).block(),
+                    snapshot::Request::Help => keys::help_block(Some(keys::Settings {
+                        rate: ctx.rate_limiter.current_rate(),
+                        concurrency: ctx.concurrency.limit(),
+                    })),
+                };
+                let _ = ctx.printer.send(block);
+            }
+        })
+    };
+
+    // Queues the ranges appended to the input files
+    let watch_task = watch.map(|watch| {
+        let (targets, ctx, progress) = (targets.clone(), ctx.clone(), progress.clone());
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(inputwatch::WATCH_INTERVAL).await;
+                add_appended_ranges(&mut watch.lock().unwrap(), &targets, &ctx, &progress);
+            }
+        })
+    });
+
+    let burst_task = eventlog::is_enabled().then(|| {
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let mut watch = eventlog::BurstWatch::default();
+            loop {
+                tokio::time::sleep(eventlog::ERROR_BURST_WINDOW).await;
+                if let Some(burst) = watch.check(&ctx.run_id, ctx.stats.errors(), ctx.stats.probes()) {
+                    eventlog::emit(burst);
+                }
+            }
+        })
+    });
+
+    if !listeners.is_empty() {
+        let (ctx, progress, targets) = (ctx.clone(), progress.clone(), targets.clone());
+        listeners.attach(move || take_snapshot(&ctx, &progress, &targets));
+    }
+    // Serves until the scan ends
+    let (posted, api_task) = match &listeners.api {
+        Some(server) => {
+            let (posted, task) = server.start(listeners.status.clone(), listeners.findings.clone())?;
+            (Some(posted), Some(task))
+        }
+        None => (None, None),
+    };
+
+    let hosts = match ctx.args.watch.clone() {
+        Some(feed) => Some(feed::follow(feed)),
+        None => posted,
+    };
+    let mut found_endpoints = match hosts {
+        Some(mut hosts) => {
+            let hosts = stream::poll_fn(move |cx| hosts.poll_recv(cx));
+            scan_hosts(hosts, ctx.clone(), progress.clone()).await
         }
+        None => scan_targets(targets, &ctx, &progress).await,
+    };
+
+    if let Some(queue) = ctx.retry_queue.as_ref().filter(|_| !STOP_SCAN.load(Ordering::Relaxed)) {
+        found_endpoints.extend(second_pass(queue, &ctx, &progress).await);
     }
 
+    status_task.abort();
+    let _ = status_task.await;
+    if let Some(task) = plain_task {
+        task.abort();
+        let _ = task.await;
+    }
+    snapshot::stop_listening();
+    snapshot_task.abort();
+    let _ = snapshot_task.await;
+    if let Some(task) = watch_task {
+        task.abort();
+        let _ = task.await;
+    }
+    if let Some(task) = api_task {
+        task.abort();
+        let _ = task.await;
+    }
+    listeners.detach();
+    if let Some(task) = burst_task {
+        task.abort();
+        let _ = task.await;
+    }
     progress.finish_and_clear();
 
+    checkpoint::stop_listening();
+    checkpoint_task.abort();
+    let _ = checkpoint_task.await;
+    if let Some(checkpoint) = &ctx.checkpoint {
+        if STOP_SCAN.load(Ordering::Relaxed) {
+            match checkpoint.save(&outdir::path(CHECKPOINT_FILE)) {
+                Ok(()) => console_log(style(format!("Progress saved to {}; continue with --resume", outdir::path(CHECKPOINT_FILE).display())).yellow().to_string()),
+                Err(e) => console_log(style(format!("Failed to save {}: {}", outdir::path(CHECKPOINT_FILE).display(), e)).red().to_string()),
+            }
+        } else if let Err(e) = fs::remove_file(outdir::path(CHECKPOINT_FILE)) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                console_log(style(format!("Failed to remove {}: {}", outdir::path(CHECKPOINT_FILE).display(), e)).yellow().to_string());
+            }
+        }
+    }
+
+    // Closing the last sender lets the writer task drain the channel and flush
+    let stats = ctx.stats.clone();
+    let bandwidth = ctx.bandwidth.clone();
+    let run_id = ctx.run_id.clone();
+    let args = ctx.args.clone();
+    let sample_plan = args.sample_plan();
+    ctx.sinks.emit(SinkEvent::ScanFinished {
+        run_id: ctx.run_id.to_string(),
+        attempted: stats.attempted(),
+        hits: stats.hits(),
+        stopped: STOP_SCAN.load(Ordering::Relaxed),
+    }).await;
+    eventlog::emit(eventlog::Event::ScanFinished {
+        run_id: ctx.run_id.to_string(),
+        attempted: stats.attempted(),
+        hits: stats.hits(),
+        stopped: STOP_SCAN.load(Ordering::Relaxed),
+    });
+    drop(ctx);
+    // Hits confirmed while the scan wound down are still printed, before the summary
+    let _ = printer_task.await;
+    if let Some(task) = enrich_task {
+        if !task.is_finished() {
+            console_log(style("Waiting for --enrich-show to finish its /api/show requests...").dim().to_string());
+        }
+        let _ = task.await;
+    }
+    if let Some(task) = rdap_task {
+        if !task.is_finished() {
+            console_log(style("Waiting for --abuse-contacts to finish its RDAP lookups...").dim().to_string());
+        }
+        let _ = task.await;
+    }
+    run_info.finish(stats.attempted(), stats.hits(), STOP_SCAN.load(Ordering::Relaxed));
+    if let Err(e) = run_info.save() {
+        console_log(style(format!("{:#}", e)).yellow().to_string());
+    }
+    let written = writer_task.await?;
+    stats.record_unwritten(written.unwritten);
+    sinks.close().await;
+
     if !found_endpoints.is_empty() {
         console_log(style(format!("Found {} Ollama endpoints", found_endpoints.len())).green().to_string());
     }
+    let metrics = stats.metrics(&run_id, targets.total(), &bandwidth, STOP_SCAN.load(Ordering::Relaxed));
+    stats.print_summary(&metrics, sample_plan, args.shard, ModelFilter::from_args(&args).as_ref());
+    if let Err(e) = metrics.save(&outdir::path(metrics::METRICS_JSON)) {
+        console_log(style(format!("{:#}", e)).yellow().to_string());
+    }
+    if args.errors_csv {
+        if let Err(e) = stats.errors().write_samples(&outdir::path(errors::ERRORS_CSV)) {
+            console_log(style(format!("Failed to write {}: {}", outdir::path(errors::ERRORS_CSV).display(), e)).red().to_string());
+        }
+    }
+
+    let diffed = baseline.is_some();
+    if let Some(baseline) = baseline {
+        if let Err(e) = diff::report_diff(&found_endpoints, baseline, STOP_SCAN.load(Ordering::Relaxed)) {
+            console_log(style(format!("Failed to compute diff: {}", e)).red().to_string());
+        }
+    }
+
+    #[cfg(feature = "email")]
+    email::send_summary(&metrics, args.label.as_deref(), &written.files).await;
+    #[cfg(feature = "s3")]
+    if let Some(uploader) = s3::Uploader::from_args(&args, dns.as_ref())? {
+        let mut files = written.files;
+        files.push(run_info.path());
+        files.push(outdir::path(metrics::METRICS_JSON));
+        if args.errors_csv {
+            files.push(outdir::path(errors::ERRORS_CSV));
+        }
+        if diffed {
+            files.extend([diff::DIFF_CSV, diff::DIFF_JSON].map(outdir::path));
+        }
+        uploader.upload_run(&run_id, &files).await;
+    }
+    #[cfg(not(feature = "s3"))]
+    let _ = (written, diffed);
 
     if STOP_SCAN.load(Ordering::Relaxed) {
         console_log(style("Scan stopped by user").yellow().to_string());
     } else {
         console_log(style("Scan completed!").green().bold().to_string());
     }
+    Ok((stats.hits() + stats.other_hits(), found_endpoints))
+}
+
+/// The ranges, or with `--queue-pull` the batches taken from Redis.
+async fn scan_targets(targets: &TargetQueue, ctx: &ScanContext, progress: &Arc<ProgressBar>) -> Vec<ScanResult> {
+    #[cfg(feature = "redis")]
+    if let Some(url) = ctx.args.queue_pull.as_deref() {
+        let mut found = Vec::new();
+        if let Err(e) = queue::pull(url, ctx, progress.clone(), &mut found).await {
+            // Batches scanned so far stay in `found`; the writer and sinks still get flushed below
+            console_log(style(format!("Queue error: {:#}", e)).red().to_string());
+            STOP_SCAN.store(true, Ordering::Relaxed);
+        }
+        return found;
+    }
+    scan_ranges(targets, ctx, progress).await
+}
+
+/// Probes the hosts the sweep lost to timeouts and resets once more, at a
+/// gentler rate. Their hits are recorded with Pass 2.
+async fn second_pass(queue: &RetryQueue, ctx: &ScanContext, progress: &Arc<ProgressBar>) -> Vec<ScanResult> {
+    let groups = queue.take();
+    let count: usize = groups.iter().map(|(_, hosts)| hosts.len()).sum();
+    if count == 0 {
+        return Vec::new();
+    }
+    ctx.stats.record_retried(count as u64);
+    ctx.current_range.lock().unwrap().take();
+    console_log(style(format!("Second pass: retrying {} hosts that timed out or reset the connection", count)).dim().to_string());
+    progress.inc_length(count as u64 * ctx.args.ports_per_host());
+    let ctx = ScanContext {
+        rate_limiter: Arc::new(TokenBucket::new(SECOND_PASS_RATE_PER_SECOND, ctx.args.burst)),
+        // The sweep already marked these hosts done
+        checkpoint: None,
+        probed: None,
+        retry_queue: None,
+        pinger: None,
+        pass: 2,
+        ..ctx.clone()
+    };
+    let mut found = Vec::new();
+    for (provenance, hosts) in groups {
+        if STOP_SCAN.load(Ordering::Relaxed) {
+            break;
+        }
+        found.extend(scan_hosts(listed_in((0..).zip(hosts), provenance), ctx.clone(), progress.clone()).await);
+    }
+    found
+}
+
+/// Scans the targets in order, including those queued while it runs.
+async fn scan_ranges(targets: &TargetQueue, ctx: &ScanContext, progress: &Arc<ProgressBar>) -> Vec<ScanResult> {
+    let mut found_endpoints = Vec::new();
+
+    for range in 0.. {
+        if STOP_SCAN.load(Ordering::Relaxed) {
+            break;
+        }
+        let Some(target) = targets.next(range) else { break };
+        let target = &target;
+        if ctx.checkpoint.as_ref().is_some_and(|c| c.range_finished(range)) {
+            continue;
+        }
+
+        let resumed = ctx.checkpoint.as_ref().map_or(0, |c| c.done_in(range));
+        let per_host = ctx.args.ports_per_host();
+        *ctx.current_range.lock().unwrap() = Some(CurrentRange {
+            number: range + 1,
+            location: target.provenance.location.clone(),
+            start: progress.position().saturating_sub(resumed * per_host),
+            planned: planned_count(target, &ctx.args, ctx.seed) * per_host,
+        });
+        eventlog::emit(eventlog::Event::RangeStarted {
+            run_id: ctx.run_id.to_string(),
+            range: range + 1,
+            location: target.provenance.location.clone(),
+            source: target.provenance.source_text(),
+        });
+        let results = scan_range(
+            range,
+            target,
+            ctx.clone(),
+            progress.clone(),
+        ).await;
+        eventlog::emit(eventlog::Event::RangeFinished {
+            run_id: ctx.run_id.to_string(),
+            range: range + 1,
+            stopped: STOP_SCAN.load(Ordering::Relaxed),
+        });
+
+        for result in results {
+            found_endpoints.push(result.clone());
+        }
+    }
+    found_endpoints
+}
+
+async fn build_sinks(args: &ScanArgs, dns: Option<&Arc<DnsResolver>>) -> Result<SinkSet> {
+    let mut sinks = SinkSet::default();
+    if args.stream {
+        sinks.add_lossless("Stream", sinks::stream::run);
+    }
+    if let Some(url) = &args.es_url {
+        let config = ElasticsearchConfig::new(url, &args.es_index);
+        let dns = dns.cloned();
+        sinks.add("Elasticsearch", |rx| sinks::elasticsearch::run(config, dns, rx));
+    }
+    if let Some(url) = &args.syslog {
+        let config = SyslogConfig::new(url, args.syslog_facility, args.syslog_severity)?;
+        sinks.add("Syslog", |rx| sinks::syslog::run(config, rx));
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(path) = &args.parquet {
+        let path = path.clone();
+        let rotation = args.rotation();
+        sinks.add("Parquet", |rx| sinks::parquet::run(path, rotation, rx));
+    }
+    #[cfg(feature = "postgres")]
+    if let Some(url) = &args.pg {
+        let pool = sinks::postgres::connect(url).await?;
+        sinks.add("PostgreSQL", |rx| sinks::postgres::run(pool, rx));
+    }
+    Ok(sinks)
+}
+
+/// Sleeps until the next daemon cycle with a countdown. Pausing holds the
+/// countdown and `q`/Ctrl+C ends the wait immediately.
+async fn wait_for_next_cycle(interval: Duration) {
+    let countdown = ProgressBar::new_spinner();
+    if PLAIN_PROGRESS.load(Ordering::Relaxed) {
+        countdown.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    countdown.enable_steady_tick(Duration::from_millis(200));
+    let mut remaining = interval.as_secs();
+    while remaining > 0 && !STOP_SCAN.load(Ordering::Relaxed) {
+        if PAUSE_SCAN.load(Ordering::Relaxed) {
+            countdown.set_message("PAUSED");
+        } else {
+            countdown.set_message(format!(
+                "Next scan in {:02}:{:02}:{:02}",
+                remaining / 3600,
+                remaining % 3600 / 60,
+                remaining % 60
+            ));
+            remaining -= 1;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    countdown.finish_and_clear();
+}
+
+mod alerts;
+mod analyze;
+mod api;
+mod asn;
+mod anonymize;
+mod browse;
+mod chat;
+mod checkpoint;
+mod cli;
+mod compress;
+mod concurrency;
+mod config;
+mod control;
+mod dashboard;
+mod dedup;
+mod diff;
+mod disclaimer;
+mod disclose;
+mod dns;
+mod dryrun;
+mod email;
+mod enrich;
+mod errors;
+mod eventlog;
+mod exit;
+mod export;
+mod feed;
+mod geo;
+mod honeypot;
+mod inputwatch;
+mod keys;
+mod license;
+mod metrics;
+mod modelfilter;
+mod modellist;
+mod modelname;
+mod outdir;
+mod outputlock;
+mod oversize;
+mod pause;
+mod precheck;
+mod prescan;
+mod printer;
+mod probed;
+mod probes;
+mod provider;
+mod proxy;
+#[cfg(feature = "redis")]
+mod queue;
+mod ratelimit;
+mod rdap;
+mod results;
+mod retry;
+mod rotate;
+mod runinfo;
+#[cfg(feature = "s3")]
+mod s3;
+mod sampling;
+mod shard;
+mod sinks;
+mod snapshot;
+mod stats;
+#[cfg(feature = "syn")]
+mod syn;
+mod targets;
+mod writer;
+use clap::Parser;
+use checkpoint::{Checkpoint, CheckpointTracker, CHECKPOINT_FILE};
+use dashboard::Dashboard;
+use dns::DnsResolver;
+use inputwatch::{Change, InputWatch, WatchedFile};
+use alerts::{alert_block, Alert, AlertRules};
+use cli::{format_duration, Cli, Command, Pool, ScanArgs};
+use config::Config;
+use concurrency::{ConcurrencyController, Slot};
+use dedup::SeenFindings;
+use disclaimer::display_disclaimer;
+use errors::ErrorCategory;
+use exit::{ScanOutcome, EXIT_FATAL};
+use std::process::ExitCode;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use tokio::sync::oneshot;
+use tokio::task::JoinSet;
+use sinks::elasticsearch::ElasticsearchConfig;
+use sinks::syslog::SyslogConfig;
+use sinks::{Finding, SinkEvent, SinkPublisher, SinkSet};
+use oversize::Oversize;
+use pause::{ActiveTimer, PAUSE_CLOCK};
+use precheck::{pinged, Pinger};
+use prescan::{Masscan, Prescan, Prescanner};
+use proxy::{header_cell, ProxyInfo};
+use asn::AsnTable;
+use ratelimit::{format_bytes, AsnLimiter, BandwidthMeter, Jitter, SubnetLimiter, TokenBucket};
+use rdap::{RdapJob, RdapSender};
+use stats::ScanStats;
+#[cfg(feature = "syn")]
+use syn::SynScanner;
+use printer::{spawn_printer, PrintSender};
+use probed::{unique_count, ProbedSet};
+use targets::{planned_count, planned_hosts, Block, TargetQueue, Provenance, Source, Target};
+use probes::{check_embeddings, check_write_access, probe_capabilities, Accounting, Details, Fetcher, Probe, ServiceFinding, Verification, WriteAccess, OPEN_WEBUI};
+use enrich::{largest_models, spawn_enricher, EnrichJob, EnrichSender};
+use modelfilter::ModelFilter;
+use modellist::model_block;
+use geo::GeoFilter;
+use honeypot::{HoneypotCheck, HoneypotConfig, Response};
+use provider::{ProviderIndex, UNKNOWN as UNKNOWN_PROVIDER};
+use retry::RetryQueue;
+use runinfo::RunInfo;
+use writer::{spawn_writer, EndpointRecord, InterestingRecord, OptionalOutputs, ServiceRecord, UnverifiedRecord, WriteEvent, WriteSender};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // clap would exit with 2 on a usage error, which scripts read as a scan without findings
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return match e.kind() {
+                clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion => ExitCode::SUCCESS,
+                _ => ExitCode::from(EXIT_FATAL),
+            };
+        }
+    };
+    // A panic in any task must not leave the terminal in raw mode
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = crossterm::terminal::disable_raw_mode();
+        report(info);
+    }));
+    match run(cli).await {
+        Ok(outcome) => outcome.exit_code(),
+        Err(e) => {
+            let _ = crossterm::terminal::disable_raw_mode();
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(EXIT_FATAL)
+        }
+    }
+}
+
+async fn run(mut cli: Cli) -> Result<ScanOutcome> {
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::Stats(args) => analyze::run_stats(args),
+            Command::Export(args) => export::run_export(args),
+            Command::Browse(args) => browse::run_browse(args),
+            Command::Chat(args) => chat::run_chat(args).await,
+            Command::Disclose(args) => disclose::run_disclose(args),
+        }
+        .map(|_| ScanOutcome::Found);
+    }
+
+    let config = Config::load(cli.scan.config.as_deref())?;
+    let alerts = Arc::new(AlertRules::compile(&config.alerts)?);
+    #[cfg(feature = "email")]
+    email::install(config.email.as_ref())?;
+    #[cfg(not(feature = "email"))]
+    if config.email.is_some() {
+        anyhow::bail!("The config file has an \"email\" section, but this build can't send email (build with --features email)");
+    }
+    let stream = cli.scan.stream;
+    STREAM_MODE.store(stream, Ordering::Relaxed);
+    // Logs and pipes get --plain-progress lines instead of a bar and keys
+    let console = if stream { std::io::stderr().is_terminal() } else { std::io::stdout().is_terminal() };
+    cli.scan.plain_progress |= !console;
+    PLAIN_PROGRESS.store(cli.scan.plain_progress, Ordering::Relaxed);
+    let interactive = !stream && !cli.scan.plain_progress;
+    if cli.scan.dry_run {
+        return dryrun::run(&cli.scan);
+    }
+    let mut listeners = api::Listeners {
+        api: cli.scan.serve.map(|address| api::Server::bind(address, &config.api, "--serve")).transpose()?,
+        ..Default::default()
+    };
+    if let Some(address) = cli.scan.dashboard {
+        let server = api::Server::bind(address, &config.api, "--dashboard")?;
+        listeners.dashboard = Some(Dashboard::start(&server, listeners.status.clone(), listeners.findings.clone())?);
+    }
+    let geo = GeoFilter::from_args(&cli.scan)?.map(Arc::new);
+    let dns = DnsResolver::from_args(&cli.scan)?;
+    if cli.scan.update_provider_feeds {
+        provider::update_feeds(&cli.scan.provider_feeds, dns.as_ref()).await?;
+    }
+    let providers = Arc::new(ProviderIndex::load(&cli.scan.provider_feeds));
+    let prescanner = match cli.scan.prescan {
+        Some(Prescan::Masscan) => Masscan::find(&cli.scan.masscan_path, cli.scan.masscan_rate).await?.map(Prescanner::Masscan),
+        None => None,
+    };
+    // Queue workers probe their batches as they come, with no ranges to sweep
+    #[cfg(all(feature = "syn", feature = "redis"))]
+    if cli.scan.syn && cli.scan.queue_pull.is_some() {
+        anyhow::bail!("--syn cannot be used with --queue-pull");
+    }
+    #[cfg(feature = "syn")]
+    let prescanner = match cli.scan.syn {
+        true => Some(Prescanner::Syn(SynScanner::open(cli.scan.syn_rate, cli.scan.syn_wait)?)),
+        false => prescanner,
+    };
+    let prescanner = prescanner.map(Arc::new);
+    let pinger = cli.scan.precheck.and_then(|mode| Pinger::open(mode, cli.scan.ping_timeout)).map(Arc::new);
+    let asn_limiter = match (cli.scan.max_rate_per_asn, &cli.scan.asn_db) {
+        (Some(rate), Some(path)) => Some(Arc::new(AsnLimiter::new(AsnTable::load(path)?, rate))),
+        _ => None,
+    };
+
+    // Read before anything is written so the comparison can't include this run
+    let baseline = match &cli.scan.diff {
+        Some(previous) => Some(diff::Baseline::load(previous, cli.scan.diff_models.as_deref())?),
+        None => None,
+    };
+
+    if let Some(scope) = cli.scan.anonymize {
+        anonymize::install(scope, cli.scan.anonymize_style);
+    }
+    if let Some(file) = &cli.scan.test_alerts {
+        return alerts::test_alerts(&alerts, file);
+    }
+    #[cfg(feature = "email")]
+    if cli.scan.test_email {
+        return email::send_test().await;
+    }
+
+    outdir::prepare(cli.scan.output_dir.as_deref(), cli.scan.run_dir, cli.scan.resume)?;
+    #[cfg(feature = "s3")]
+    s3::Uploader::from_args(&cli.scan, None)?;
+    // Held until the run returns, whichever way
+    let output_lock = outputlock::acquire(cli.scan.force_suffix)?;
+    cli.scan.output_suffix = output_lock.suffix;
+
+    // Display disclaimer and check agreement
+    if !display_disclaimer(&mut chrome(), cli.scan.check_write_access)? {
+        return Ok(ScanOutcome::Stopped);
+    }
+
+    // Enable raw mode for keyboard input; with --stream stdin/stdout are likely pipes
+    if interactive {
+        crossterm::terminal::enable_raw_mode()?;
+    }
+    
+    ctrlc::set_handler(|| {
+        console_log(format!("{}",
+            style("Stopping scan... Press Ctrl+C again to force quit").yellow()
+        ));
+        control::stop("signal");
+    })?;
+
+    #[cfg(feature = "redis")]
+    let pulling = cli.scan.queue_pull.is_some();
+    #[cfg(not(feature = "redis"))]
+    let pulling = false;
+
+    let open_ended = cli.scan.open_ended();
+    if let Some(feed) = &cli.scan.watch {
+        fs::File::open(feed).with_context(|| format!("Failed to open {}", feed.display()))?;
+    }
+
+    // Queue workers take their targets from Redis, --watch from its feed and --serve from its API, not ip-ranges.txt
+    let (ranges, watched) = if pulling || open_ended { (Vec::new(), Vec::new()) } else { load_ranges(&cli.scan.input)?.into_targets()? };
+    let sample_plan = cli.scan.sample_plan();
+    let fingerprint = checkpoint::fingerprint(&ranges, &cli.scan);
+    let mut resume = match cli.scan.resume {
+        true => Some(Checkpoint::load(&outdir::path(CHECKPOINT_FILE), &fingerprint, cli.scan.seed)?),
+        false => None,
+    };
+    // A resumed scan must sample and shard exactly as before
+    let seed = resume
+        .as_ref()
+        .map(Checkpoint::seed)
+        .or(cli.scan.seed)
+        .unwrap_or_else(rand::random);
+    let (total_ips, duplicate_ips) = unique_count(&ranges, &cli.scan, seed);
+    let rate = cli.scan.jitter.map_or(RATE_LIMIT_PER_SECOND as f64, |range| Jitter::new(range, 0).ceiling().min(RATE_LIMIT_PER_SECOND as f64));
+    if let Some(oversize) = Oversize::check(&ranges, total_ips, &cli.scan, seed, rate) {
+        if !oversize.confirm(&mut chrome(), interactive)? {
+            if interactive {
+                crossterm::terminal::disable_raw_mode()?;
+            }
+            return Ok(ScanOutcome::TooLarge);
+        }
+    }
+
+
+    #[cfg(feature = "redis")]
+    if let Some(url) = &cli.scan.queue_push {
+        if interactive {
+            crossterm::terminal::disable_raw_mode()?;
+        }
+        queue::push(url, &ranges, &cli.scan, seed).await?;
+        return Ok(ScanOutcome::Found);
+    }
+    
+    // Print with proper alignment
+    if !cli.scan.plain_progress {
+        let _ = chrome().execute(cursor::MoveTo(0, 1));
+    }
+    
+    console_log(format!("\n{}{}", 
+        HEADER_STYLE,
+        style("Public Ollama Finder").blue().bold()
+    ));
+    console_log(format!("{}Repository: {}", 
+        LIST_ITEM_STYLE,
+        style(REPO_URL).yellow()
+    ));
+    #[cfg(feature = "redis")]
+    if let Some(url) = &cli.scan.queue_pull {
+        console_log(format!("{}Targets: batches from Redis queue {}", LIST_ITEM_STYLE, style(url).yellow()));
+    }
+    if let Some(feed) = &cli.scan.watch {
+        console_log(format!("{}Targets: each line written to {} (read from the start)", LIST_ITEM_STYLE, style(feed.display()).yellow()));
+    } else if let Some(address) = listeners.api.as_ref().and_then(api::Server::address) {
+        console_log(format!("{}Targets: posted to {}", LIST_ITEM_STYLE, style(format!("http://{}/targets", address)).yellow()));
+    } else if !pulling {
+        let duplicates = match duplicate_ips {
+            0 => String::new(),
+            n => format!(", {} duplicate listings skipped", n),
+        };
+        console_log(format!("{}Targets: {} IP ranges ({} total IPs{})", 
+            LIST_ITEM_STYLE,
+            style(ranges.len()).cyan(),
+            style(total_ips).cyan(),
+            duplicates
+        ));
+    }
+    console_log(format!("{}Port: {}", 
+        LIST_ITEM_STYLE,
+        style("11434 /api/tags").yellow()
+    ));
+    if let Some(plan) = sample_plan {
+        console_log(format!("{}Sampling: {} (seed {})",
+            LIST_ITEM_STYLE,
+            style(plan.describe()).yellow(),
+            style(seed).cyan()
+        ));
+    }
+    if let Some(shard) = cli.scan.shard {
+        console_log(format!("{}Shard: {}", LIST_ITEM_STYLE, style(shard).yellow()));
+    }
+    if let Some(tag) = &cli.scan.label {
+        console_log(format!("{}Tag: {}", LIST_ITEM_STYLE, style(tag).yellow()));
+    }
+    console_log(format!("{}Concurrency: {}",
+        LIST_ITEM_STYLE,
+        style(match cli.scan.concurrency_fixed {
+            Some(limit) => format!("fixed at {}", limit),
+            None => format!("adaptive, up to {}", CONCURRENT_LIMIT),
+        }).yellow()
+    ));
+    if let Some(geo) = &geo {
+        console_log(format!("{}Countries: {}", LIST_ITEM_STYLE, style(geo.describe()).yellow()));
+    }
+    console_log(format!("{}Providers: {}", LIST_ITEM_STYLE, style(providers.describe()).yellow()));
+    if cli.scan.ports_per_host() > 1 {
+        let ports: Vec<String> = cli.scan.port_profile.ports().iter().map(u16::to_string).collect();
+        console_log(format!("{}Ollama ports: {} ({})",
+            LIST_ITEM_STYLE,
+            style(ports.join(", ")).yellow(),
+            if cli.scan.all_ports { "all probed" } else { "until one answers" }
+        ));
+    }
+    if cli.scan.subnet_rate > 0 {
+        console_log(format!("{}Per-subnet limit: {} req/s per /24",
+            LIST_ITEM_STYLE,
+            style(cli.scan.subnet_rate).yellow()
+        ));
+    }
+    if let Some(prescanner) = &prescanner {
+        console_log(format!("{}Pre-scan: {}", LIST_ITEM_STYLE, style(prescanner.describe()).yellow()));
+    }
+    if let Some(dns) = &dns {
+        console_log(format!("{}DNS: {}", LIST_ITEM_STYLE, style(dns.describe()).yellow()));
+    }
+    if let Some(pinger) = &pinger {
+        console_log(format!("{}Pre-check: {}", LIST_ITEM_STYLE, style(pinger.describe()).yellow()));
+    }
+    if let Some(range) = cli.scan.jitter {
+        let jitter = Jitter::new(range, 0);
+        console_log(format!("{}Jitter: {} before each probe {}",
+            LIST_ITEM_STYLE,
+            style(jitter.describe()).yellow(),
+            style(format!("(at most {:.0} probes/s on average)", jitter.ceiling().min(RATE_LIMIT_PER_SECOND as f64))).dim()
+        ));
+    }
+    if let (Some(rate), Some(limiter)) = (cli.scan.max_rate_per_asn, &asn_limiter) {
+        console_log(format!("{}Per-ASN limit: {} req/s per autonomous system {}",
+            LIST_ITEM_STYLE,
+            style(rate).yellow(),
+            style(format!("({})", limiter.table().describe())).dim()
+        ));
+    }
+    if let Some(checkpoint) = &resume {
+        console_log(format!("{}Resuming: range {} of {} from {}",
+            LIST_ITEM_STYLE,
+            style(checkpoint.range() + 1).cyan(),
+            style(ranges.len()).cyan(),
+            style(outdir::path(CHECKPOINT_FILE).display()).yellow()
+        ));
+    } else if !pulling && !open_ended && outdir::path(CHECKPOINT_FILE).exists() {
+        console_log(format!("{}{}",
+            LIST_ITEM_STYLE,
+            style(format!("{} from an interrupted scan will be replaced (use --resume to continue it)", outdir::path(CHECKPOINT_FILE).display())).yellow()
+        ));
+    }
+    if let Some(dir) = outdir::dir() {
+        console_log(format!("{}Output directory: {}", LIST_ITEM_STYLE, style(dir.display()).yellow()));
+    }
+    let rotation = cli.scan.rotation();
+    if !rotation.is_off() || rotation.suffix.is_some() || rotation.compress {
+        let note = match rotation.is_off() {
+            true if rotation.suffix.is_some() => "(another scan is writing the usual files)".to_string(),
+            true => "(gzip)".to_string(),
+            false => format!("(rotating {})", rotation.describe()),
+        };
+        console_log(format!("{}Output: {}, {} {}",
+            LIST_ITEM_STYLE,
+            style(writer::active_path(writer::ENDPOINTS_CSV, rotation).display()).yellow(),
+            style(writer::active_path(writer::MODELS_CSV, rotation).display()).yellow(),
+            style(note).dim()
+        ));
+    }
+    if let Some(address) = listeners.dashboard.as_ref().and_then(Dashboard::address) {
+        console_log(format!("{}Dashboard: {} {}",
+            LIST_ITEM_STYLE,
+            style(format!("http://{}/", address)).yellow(),
+            style("(add ?token= with api.token from the config file)").dim()
+        ));
+    }
+    if let Some(interval) = cli.scan.interval {
+        console_log(format!("{}Daemon mode: re-scan every {}",
+            LIST_ITEM_STYLE,
+            style(format_duration(interval)).yellow()
+        ));
+    }
+    console_log(format!("{}Controls: {}", 
+        LAST_ITEM_STYLE,
+        style(if !interactive { "Ctrl+C to stop".to_string() } else { format!("{} | Ctrl+C to stop", keys::controls()) }).dim()
+    ));
+    console_log("".to_string()); // Empty line before progress bar
+
+    let keyboard = interactive.then(setup_keyboard_handler);
+
+    let client = Arc::new(http_client(cli.scan.connect_timeout, cli.scan.pool(), cli.scan.redirect_policy(), dns.as_ref())?);
+    let args = Arc::new(cli.scan.clone());
+    let event_log = args.event_log.as_deref().map(|path| eventlog::start(path, args.compress)).transpose()?;
+    eventlog::emit(eventlog::Event::RunStarted {
+        version: env!("CARGO_PKG_VERSION"),
+        arguments: std::env::args().skip(1).collect(),
+        ranges: ranges.len(),
+        targets: total_ips,
+    });
+    let targets = Arc::new(TargetQueue::new(ranges, total_ips));
+    let watch = (!watched.is_empty()).then(|| Arc::new(Mutex::new(InputWatch { files: watched, explicit: !args.input.is_empty() })));
 
     // Cleanup raw mode at the end
     let result = async {
-        // ...existing main function code...
-        Ok(())
+        let mut hits = 0;
+        let mut found;
+        loop {
+            // Later cycles include the ranges appended during earlier ones
+            let checkpoint = match pulling || open_ended {
+                true => None,
+                false => Some(resume.take().unwrap_or_else(|| Checkpoint::new(checkpoint::fingerprint(&targets.all(), &args), seed))),
+            };
+            let (cycle_hits, cycle_found) = run_cycle(&targets, watch.clone(), &listeners, client.clone(), args.clone(), alerts.clone(), geo.clone(), &config.honeypot, providers.clone(), asn_limiter.clone(), prescanner.clone(), pinger.clone(), dns.clone(), seed, baseline.as_ref(), checkpoint).await?;
+            hits += cycle_hits;
+            found = cycle_found;
+            match args.interval {
+                Some(interval) if !STOP_SCAN.load(Ordering::Relaxed) => {
+                    wait_for_next_cycle(interval).await;
+                    if STOP_SCAN.load(Ordering::Relaxed) {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        // Offered once a single scan is over and someone is at the keyboard
+        if let Some(keyboard) = keyboard.filter(|_| args.interval.is_none() && std::io::stdin().is_terminal()) {
+            KEYBOARD_RELEASED.store(true, Ordering::Relaxed);
+            let _ = keyboard.join();
+            browse::offer(&found)?;
+        }
+        Ok(ScanOutcome::from_state(hits, STOP_SCAN.load(Ordering::Relaxed)))
     }.await;
+
+    if let Some(task) = event_log {
+        eventlog::emit(match &result {
+            Ok(outcome) => eventlog::Event::RunEnded {
+                termination: if *outcome == ScanOutcome::Stopped { "stopped" } else { "completed" },
+                exit_code: outcome.code(),
+                error: None,
+            },
+            Err(e) => eventlog::Event::RunEnded { termination: "failed", exit_code: EXIT_FATAL, error: Some(format!("{:#}", e)) },
+        });
+        eventlog::close(task).await;
+    }
     
-    crossterm::terminal::disable_raw_mode()?;
+    if interactive {
+        crossterm::terminal::disable_raw_mode()?;
+    }
+    drop(output_lock);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(text: &str) -> u32 {
+        text.parse::<Ipv4Addr>().unwrap().into()
+    }
+
+    fn nets(blocks: &[Ipv4Net]) -> Vec<String> {
+        blocks.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn runs(input: &str) -> Vec<(Ipv4Addr, Ipv4Addr)> {
+        parse_octet_ranges(input)
+            .unwrap()
+            .into_iter()
+            .map(|(start, end)| (start.into(), end.into()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_ranges_into_minimal_cidrs() {
+        assert_eq!(
+            nets(&range_to_cidrs(ip("10.0.0.1"), ip("10.0.0.6"))),
+            ["10.0.0.1/32", "10.0.0.2/31", "10.0.0.4/31", "10.0.0.6/32"]
+        );
+        assert_eq!(nets(&range_to_cidrs(ip("10.0.0.0"), ip("10.0.1.255"))), ["10.0.0.0/23"]);
+    }
+
+    #[test]
+    fn cidr_split_handles_the_ends_of_the_address_space() {
+        assert_eq!(nets(&range_to_cidrs(0, u32::MAX)), ["0.0.0.0/0"]);
+        assert_eq!(nets(&range_to_cidrs(0, 0)), ["0.0.0.0/32"]);
+        assert_eq!(nets(&range_to_cidrs(u32::MAX, u32::MAX)), ["255.255.255.255/32"]);
+        assert_eq!(nets(&range_to_cidrs(ip("255.255.255.254"), u32::MAX)), ["255.255.255.254/31"]);
+
+        let inner = range_to_cidrs(1, u32::MAX - 1);
+        assert_eq!(inner.len(), 62);
+        let covered: u64 = inner.iter().map(|n| 1u64 << (32 - n.prefix_len())).sum();
+        assert_eq!(covered, (1u64 << 32) - 2);
+    }
+
+    #[test]
+    fn octet_ranges_cover_their_bounds() {
+        let addr = |s: &str| s.parse::<Ipv4Addr>().unwrap();
+        assert_eq!(runs("10.0.0.0-0"), [(addr("10.0.0.0"), addr("10.0.0.0"))]);
+        assert_eq!(runs("10.0.0.5-5"), [(addr("10.0.0.5"), addr("10.0.0.5"))]);
+        assert_eq!(runs("10.0.0.250-255"), [(addr("10.0.0.250"), addr("10.0.0.255"))]);
+        assert_eq!(runs("255.255.255.0-255"), [(addr("255.255.255.0"), addr("255.255.255.255"))]);
+        // Adjacent runs merge into one
+        assert_eq!(runs("10.0.0-1.0-255"), [(addr("10.0.0.0"), addr("10.0.1.255"))]);
+        assert_eq!(runs("10.0.0-1.7"), [(addr("10.0.0.7"), addr("10.0.0.7")), (addr("10.0.1.7"), addr("10.0.1.7"))]);
+    }
+
+    #[test]
+    fn rejects_invalid_octet_ranges() {
+        assert!(parse_octet_ranges("10.0.0.9-3").is_err());
+        assert!(parse_octet_ranges("10.0.0.1-256").is_err());
+        assert!(parse_octet_ranges("10.0.300.1").is_err());
+        assert!(parse_octet_ranges("10.0.1-2").is_err());
+        assert!(parse_ip_range("10.0.0.9-10.0.0.3").is_err());
+    }
+
+    #[test]
+    fn parses_each_range_format() {
+        let hosts = |input: &str| -> u64 { parse_ip_range(input).unwrap().iter().map(|b| b.hosts().count() as u64).sum() };
+        assert_eq!(parse_ip_range("10.0.0.7").unwrap(), [Block::cidr("10.0.0.7/32".parse().unwrap())]);
+        // A CIDR skips network and broadcast; a range covers every address
+        assert_eq!(hosts("10.0.0.0/24"), 254);
+        assert_eq!(hosts("10.0.0.0-10.0.0.255"), 256);
+        assert_eq!(hosts("10.0.0.10-250"), 241);
+        assert_eq!(hosts("0.0.0.0-0.0.0.0"), 1);
+        assert_eq!(hosts("255.255.255.255-255.255.255.255"), 1);
+    }
+
+    fn host_count(input: &str) -> u64 {
+        parse_ip_range(input).unwrap().iter().map(|b| b.hosts().count() as u64).sum()
+    }
+
+    #[test]
+    fn wildcard_patterns_expand_to_the_expected_counts() {
+        assert_eq!(host_count("10.12.*.0-128"), 256 * 129);
+        assert_eq!(host_count("203.0.*.1-100"), 256 * 100);
+        assert_eq!(host_count("*.0.0.1"), 256);
+        assert_eq!(host_count("10.0.0.*"), 256);
+        assert_eq!(host_count("10.*.*.*"), 1 << 24);
+        assert_eq!(host_count("10.*.0-1.*"), 256 * 2 * 256);
+        // Whole /24s merge into single blocks
+        assert_eq!(parse_ip_range("10.12.*.*").unwrap(), [Block::whole("10.12.0.0/16".parse().unwrap())]);
+    }
+
+    #[test]
+    fn oversized_wildcard_patterns_are_rejected() {
+        assert_eq!(parse_octet_ranges("10.*.*.1-100").unwrap().len(), MAX_OCTET_RUNS);
+        let err = parse_octet_ranges("*.*.*.1-100").unwrap_err();
+        assert!(err.to_string().contains("more than 65536"), "{}", err);
+    }
+
+    #[test]
+    fn only_lines_starting_with_a_wildcard_are_wildcard_patterns() {
+        let pattern = Regex::new(WILDCARD_PATTERN).unwrap();
+        assert_eq!(wildcard_target("10.12.*.0-128", &pattern), Some(Ok("10.12.*.0-128")));
+        assert_eq!(wildcard_target("203.0.*.1-100   office", &pattern), Some(Ok("203.0.*.1-100")));
+        assert_eq!(wildcard_target("10.0.0.0/24 # *important*", &pattern), None);
+        assert!(wildcard_target("10.**.0.1", &pattern).unwrap().unwrap_err().contains("'**'"));
+        assert!(wildcard_target("10.*.0.0/16", &pattern).unwrap().unwrap_err().contains("prefix notation"));
+        assert!(wildcard_target("10.*.0", &pattern).unwrap().is_err());
+    }
+
+    /// The ranges found in `text` with their labels, minus other strings such
+    /// as service names that the JSON walker picks up too
+    fn json_labels(text: &str) -> Vec<(String, String)> {
+        let (ranges, warnings) = extract_ip_ranges(text);
+        assert!(warnings.is_empty());
+        ranges
+            .into_iter()
+            .filter(|(range, _, _)| parse_ip_range(range).is_ok())
+            .map(|(range, label, _)| (range, label))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(range, label)| (range.to_string(), label.to_string())).collect()
+    }
+
+    #[test]
+    fn labels_json_ranges_with_their_key_path() {
+        let text = r#"{"frankfurt": ["1.2.3.0/24"], "regions": {"eu": {"helsinki": ["5.6.7.0/24", "5.6.8.0/24"]}}}"#;
+        assert_eq!(
+            json_labels(text),
+            pairs(&[
+                ("1.2.3.0/24", "frankfurt"),
+                ("5.6.7.0/24", "regions.eu.helsinki"),
+                ("5.6.8.0/24", "regions.eu.helsinki"),
+            ])
+        );
+        assert_eq!(json_labels(r#"["1.2.3.0/24"]"#), pairs(&[("1.2.3.0/24", "JSON")]));
+    }
+
+    #[test]
+    fn labels_array_entries_by_their_region_or_name() {
+        // Scaleway-style list of objects
+        let scaleway = r#"[{"region": "fr-par", "cidr": "51.15.0.0/16"}, {"region": "nl-ams", "cidr": "51.158.0.0/15"}]"#;
+        assert_eq!(
+            json_labels(scaleway),
+            pairs(&[("51.15.0.0/16", "fr-par"), ("51.158.0.0/15", "nl-ams")])
+        );
+
+        // AWS ip-ranges.json: region wins over the key path
+        let aws = r#"{"syncToken": "1700000000", "prefixes": [{"ip_prefix": "3.5.140.0/22", "region": "ap-northeast-2", "service": "AMAZON"}]}"#;
+        assert_eq!(
+            json_labels(aws),
+            pairs(&[("3.5.140.0/22", "ap-northeast-2")])
+        );
+
+        // Azure service tags: the entry's name labels its nested prefixes, and
+        // the nested region field is not mistaken for a range
+        let azure = r#"{"values": [{"name": "AzureCloud.westeurope", "properties": {"region": "westeurope", "addressPrefixes": ["13.69.0.0/17", "13.73.128.0/18"]}}]}"#;
+        assert_eq!(
+            json_labels(azure),
+            pairs(&[("13.69.0.0/17", "AzureCloud.westeurope"), ("13.73.128.0/18", "AzureCloud.westeurope")])
+        );
+
+        // Without a label key, entries keep the path of their array
+        let gcp = r#"{"prefixes": [{"ipv4Prefix": "34.80.0.0/15", "scope": "asia-east1"}]}"#;
+        assert_eq!(
+            json_labels(gcp),
+            pairs(&[("34.80.0.0/15", "prefixes.ipv4Prefix")])
+        );
+    }
+
+    #[test]
+    fn records_the_line_of_each_json_range() {
+        let text = "{\n  \"note\": \"say \\\"hi\\\"\",\n  \"eu\": [\n    \"1.2.3.0/24\",\n    \"5.6.7.0/24\"\n  ]\n}\n";
+        let (ranges, _) = extract_ip_ranges(text);
+        let lines: Vec<_> = ranges.iter().map(|(range, _, line)| (range.as_str(), *line)).collect();
+        assert_eq!(lines, [("1.2.3.0/24", Some(4)), ("5.6.7.0/24", Some(5)), ("say \"hi\"", None)]);
+    }
+
+    #[test]
+    fn extracts_wildcards_with_their_pattern_as_location() {
+        let text = "# targets\n10.12.*.0-128\n10.0.0.0/24 # * marks priority\n10.**.0.1\n10.*.0.0/16\n";
+        let (ranges, warnings) = extract_ip_ranges(text);
+        assert_eq!(
+            ranges,
+            [
+                ("10.12.*.0-128".to_string(), "10.12.*.0-128".to_string(), Some(2)),
+                // A trailing comment labels the range
+                ("10.0.0.0/24".to_string(), "* marks priority".to_string(), Some(3)),
+            ]
+        );
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("line 4: ") && warnings[0].contains("'**'"));
+        assert!(warnings[1].starts_with("line 5: ") && warnings[1].contains("prefix notation"));
+    }
+
+    #[test]
+    fn labels_ranges_written_before_or_after_them() {
+        let text = "customer-a: 203.0.113.0/24\n198.51.100.0/24 # customer-b\nresearch lab: 10.0.*.1 # ignored\n\
+                    192.0.2.1 #\nhttp://192.0.2.7:11434\ncustomer-c: 203.0.113\nnotes: see ticket\n";
+        let (ranges, warnings) = extract_ip_ranges(text);
+        let labels: Vec<_> = ranges.iter().map(|(range, label, line)| (range.as_str(), label.as_str(), line.unwrap())).collect();
+        assert_eq!(labels, [
+            ("203.0.113.0/24", "customer-a", 1),
+            ("198.51.100.0/24", "customer-b", 2),
+            ("10.0.*.1", "research lab", 3),
+            ("192.0.2.1/32", "Single IP", 4),
+            ("192.0.2.7/32", "Single IP", 5),
+        ]);
+        // Only a labeled line without an address is worth a warning
+        assert_eq!(warnings, ["line 6 (customer-c): no valid address in '203.0.113'"]);
+    }
+
+    #[test]
+    fn names_the_label_of_a_range_that_does_not_parse() {
+        let (listed, warnings) = ranges_in("customer-a: 203.0.113.0/40\n10.0.0.300/24\n", Path::new("ranges.txt"), true, 1);
+        assert!(listed.is_empty());
+        assert!(warnings[0].starts_with("Failed to parse IP range '203.0.113.0/40' (customer-a): "), "{}", warnings[0]);
+        assert!(warnings[1].starts_with("Failed to parse IP range '10.0.0.300/24': "), "{}", warnings[1]);
+    }
+
+    #[test]
+    fn plain_progress_lines_keep_their_format() {
+        let now = chrono::DateTime::parse_from_rfc3339("2025-01-15T18:30:00Z").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(
+            plain_progress_line(now, 1200, Some(65536), 20.0, 3, 17),
+            "2025-01-15T18:30:00Z progress scanned=1200/65536 percent=1.8 rate=20.0/s hits=3 errors=17"
+        );
+        assert_eq!(plain_progress_line(now, 40, None, 0.5, 0, 0), "2025-01-15T18:30:00Z progress scanned=40/- percent=- rate=0.5/s hits=0 errors=0");
+    }
+}
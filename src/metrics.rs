@@ -0,0 +1,103 @@
+//! `metrics.json`: the numbers behind the scan summary, for tools that wrap
+//! the scanner. `ScanStats::metrics` takes them from the same counters the
+//! console summary prints, and the summary is printed from this snapshot.
+//!
+//! Written when a run completes or is stopped, replacing the previous run's
+//! file in one step; with `--interval` it describes the latest cycle.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const METRICS_JSON: &str = "metrics.json";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Metrics {
+    pub run_id: String,
+    /// Distinct hosts planned after sampling and sharding
+    pub targets: u64,
+    /// Hosts probed, not counting retries
+    pub scanned: u64,
+    /// Requests sent, one per port of each host
+    pub probes: u64,
+    pub skipped: Skipped,
+    pub findings: Findings,
+    /// Hosts whose /api/tags answered 401 or 403
+    pub protected: u64,
+    /// Non-200 answers from /api/tags on port 11434 per status, reported or not
+    pub possible_servers: BTreeMap<u16, u64>,
+    /// Connection errors per category
+    pub errors: BTreeMap<&'static str, u64>,
+    pub errors_total: u64,
+    /// 429 answers and the /24s they came from
+    pub rate_limited: RateLimited,
+    /// What `--precheck` skipped, and what it would have missed
+    pub precheck: PingCheck,
+    /// Time spent scanning, excluding pauses
+    pub duration_secs: f64,
+    pub paused_secs: f64,
+    /// Hosts scanned per second of `duration_secs`
+    pub average_rate: f64,
+    /// Response headers and bodies
+    pub bytes_received: u64,
+    /// Rows lost to write errors, 0 when every finding reached the CSVs
+    pub rows_not_written: u64,
+    /// "completed" or "stopped"
+    pub termination: &'static str,
+}
+
+/// Hosts and rows left out, by reason.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Skipped {
+    /// Listed by more than one target and probed once
+    pub duplicates: u64,
+    /// Left unprobed by --only-countries/--skip-countries, per country
+    pub excluded_by_country: BTreeMap<String, u64>,
+    /// Endpoints without a model passing the model filters
+    pub filtered_endpoints: u64,
+    /// Model rows held back, per filter flag
+    pub filtered_model_rows: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Findings {
+    pub endpoints: u64,
+    /// Servers found by the --also probes
+    pub other_services: u64,
+    pub unverified: u64,
+    /// Endpoints among `endpoints` found on the second pass
+    pub recovered: u64,
+    /// Hosts retried on the second pass, and left out with its queue full
+    pub retried: u64,
+    pub retry_overflow: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RateLimited {
+    pub answers: u64,
+    pub subnets: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PingCheck {
+    /// Hosts `--precheck icmp` left unprobed for not answering a ping
+    pub skipped: u64,
+    /// The requests their probes would have sent
+    pub probes_avoided: u64,
+    /// Hosts `--precheck icmp-soft` probed without a ping answer
+    pub silent_probed: u64,
+    /// Those of them that were Ollama endpoints
+    pub silent_endpoints: u64,
+}
+
+impl Metrics {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        // Replaced in one step so a crash never leaves half a file
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json + "\n").with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,193 @@
+//! `--quant`, `--min-quant` and `--family`: which model rows reach
+//! llm_models.csv, and with `--require-quant-match`/`--require-family`
+//! which endpoints are recorded at all. All given filters must pass.
+//!
+//! Levels are compared without regard to case. Ollama leaves the level empty
+//! for some models; that counts as `unknown`, which only an explicit
+//! `--quant unknown` matches.
+//!
+//! Families are matched by case-insensitive prefix against both `family` and
+//! the `families` list, since releases disagree on where "llama" or "llama3"
+//! ends up: `--family llama` matches either.
+
+use crate::cli::ScanArgs;
+use crate::Model;
+
+const UNKNOWN: &str = "UNKNOWN";
+
+/// The filter a model row failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterRule {
+    /// Not one of the `--quant` levels
+    Levels,
+    /// Below `--min-quant`
+    Minimum,
+    /// None of the `--family` prefixes
+    Family,
+}
+
+impl FilterRule {
+    pub const ALL: [FilterRule; 3] = [FilterRule::Levels, FilterRule::Minimum, FilterRule::Family];
+
+    pub fn flag(self) -> &'static str {
+        match self {
+            FilterRule::Levels => "--quant",
+            FilterRule::Minimum => "--min-quant",
+            FilterRule::Family => "--family",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelFilter {
+    levels: Vec<String>,
+    min_bits: Option<u32>,
+    /// Lowercase prefixes
+    families: Vec<String>,
+    /// Skip endpoints with no model passing every filter
+    pub require_match: bool,
+}
+
+impl ModelFilter {
+    /// None when no filter was given.
+    pub fn from_args(args: &ScanArgs) -> Option<Self> {
+        if args.quant.is_empty() && args.min_quant.is_none() && args.family.is_empty() {
+            return None;
+        }
+        Some(Self {
+            levels: args.quant.iter().map(|level| normalize(level)).collect(),
+            min_bits: args.min_quant,
+            families: args.family.iter().map(|family| family.trim().to_lowercase()).collect(),
+            require_match: args.require_quant_match || args.require_family,
+        })
+    }
+
+    /// Whether `rule` was asked for.
+    pub fn is_active(&self, rule: FilterRule) -> bool {
+        match rule {
+            FilterRule::Levels => !self.levels.is_empty(),
+            FilterRule::Minimum => self.min_bits.is_some(),
+            FilterRule::Family => !self.families.is_empty(),
+        }
+    }
+
+    /// The first filter `model` fails, if any.
+    pub fn rejects(&self, model: &Model) -> Option<FilterRule> {
+        let level = normalize(&model.details.quantization_level);
+        if self.is_active(FilterRule::Levels) && !self.levels.contains(&level) {
+            return Some(FilterRule::Levels);
+        }
+        match (self.min_bits, bits(&level)) {
+            (None, _) => {}
+            (Some(min), Some(bits)) if bits >= min => {}
+            (Some(_), _) => return Some(FilterRule::Minimum),
+        }
+        if self.is_active(FilterRule::Family) && !self.matches_family(model) {
+            return Some(FilterRule::Family);
+        }
+        None
+    }
+
+    pub fn matches_any(&self, models: &[Model]) -> bool {
+        models.iter().any(|model| self.rejects(model).is_none())
+    }
+
+    fn matches_family(&self, model: &Model) -> bool {
+        std::iter::once(&model.details.family)
+            .chain(&model.details.families)
+            .map(|family| family.to_lowercase())
+            .any(|family| !family.is_empty() && self.families.iter().any(|prefix| family.starts_with(prefix.as_str())))
+    }
+}
+
+fn normalize(level: &str) -> String {
+    match level.trim() {
+        "" => UNKNOWN.to_string(),
+        level => level.to_ascii_uppercase(),
+    }
+}
+
+/// Bits per weight a level stands for: F32 32, F16/BF16 16, Q8_0 8, Q4_K_M 4,
+/// IQ2_XXS 2. None for `unknown` and levels that name no width.
+fn bits(level: &str) -> Option<u32> {
+    let level = level.to_ascii_uppercase();
+    let rest = ["BF", "FP", "F", "IQ", "TQ", "Q"]
+        .iter()
+        .find_map(|prefix| level.strip_prefix(prefix))?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// `--min-quant`: a level with a known width, such as Q8_0 or F16.
+pub fn parse_min_quant(value: &str) -> Result<u32, String> {
+    bits(value.trim()).ok_or_else(|| format!("'{}' is not a quantization level such as Q4_K_M, Q8_0 or F16", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(level: &str) -> Model {
+        serde_json::from_value(serde_json::json!({"name": "m", "details": {"quantization_level": level}})).unwrap()
+    }
+
+    fn family(family: &str, families: &[&str]) -> Model {
+        serde_json::from_value(serde_json::json!({"name": "m", "details": {"family": family, "families": families}})).unwrap()
+    }
+
+    fn filter(levels: &[&str], min_bits: Option<u32>, families: &[&str]) -> ModelFilter {
+        ModelFilter {
+            levels: levels.iter().map(|level| normalize(level)).collect(),
+            min_bits,
+            families: families.iter().map(|family| family.to_lowercase()).collect(),
+            require_match: false,
+        }
+    }
+
+    #[test]
+    fn reads_the_width_of_common_levels() {
+        assert_eq!(bits("F32"), Some(32));
+        assert_eq!(bits("bf16"), Some(16));
+        assert_eq!(bits("Q8_0"), Some(8));
+        assert_eq!(bits("Q5_K_S"), Some(5));
+        assert_eq!(bits("IQ2_XXS"), Some(2));
+        assert_eq!(bits(UNKNOWN), None);
+        assert!(parse_min_quant("fast").is_err());
+    }
+
+    #[test]
+    fn matches_levels_without_case_and_empty_only_as_unknown() {
+        let levels = filter(&["q8_0", "F16"], None, &[]);
+        assert_eq!(levels.rejects(&model("Q8_0")), None);
+        assert_eq!(levels.rejects(&model("f16")), None);
+        assert_eq!(levels.rejects(&model("Q4_K_M")), Some(FilterRule::Levels));
+        assert_eq!(levels.rejects(&model("")), Some(FilterRule::Levels));
+        assert_eq!(filter(&["unknown"], None, &[]).rejects(&model(" ")), None);
+
+        let minimum = filter(&[], Some(8), &[]);
+        assert_eq!(minimum.rejects(&model("F16")), None);
+        assert_eq!(minimum.rejects(&model("Q5_K_S")), Some(FilterRule::Minimum));
+        assert_eq!(minimum.rejects(&model("")), Some(FilterRule::Minimum));
+        assert!(!minimum.matches_any(&[model("Q4_0"), model("")]));
+    }
+
+    #[test]
+    fn matches_family_prefixes_in_either_field() {
+        let families = filter(&[], None, &["Qwen2", "llama"]);
+        assert_eq!(families.rejects(&family("llama3", &[])), None);
+        assert_eq!(families.rejects(&family("", &["qwen2moe"])), None);
+        assert_eq!(families.rejects(&family("gemma", &["gemma"])), Some(FilterRule::Family));
+        assert_eq!(families.rejects(&family("qwen", &[])), Some(FilterRule::Family));
+        assert_eq!(families.rejects(&family("", &[])), Some(FilterRule::Family));
+    }
+
+    #[test]
+    fn requires_every_given_filter() {
+        let both = filter(&["F16"], None, &["llama"]);
+        let model: Model = serde_json::from_value(serde_json::json!(
+            {"name": "m", "details": {"family": "llama", "quantization_level": "Q4_0"}}
+        )).unwrap();
+        assert_eq!(both.rejects(&model), Some(FilterRule::Levels));
+        assert!(!both.matches_any(&[model, family("llama", &[])]));
+    }
+}
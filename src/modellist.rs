@@ -0,0 +1,140 @@
+//! The model list of a hit's console block: largest first by default,
+//! capped with `--max-models-display`, sizes right-aligned and long names
+//! cut to the terminal width. The CSVs always get every model.
+
+use clap::ValueEnum;
+use console::{measure_text_width, pad_str, style, truncate_str, Alignment};
+
+use crate::{gigabytes, Model, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+/// Indent of the list under the hit block
+const INDENT: &str = "  ";
+/// Narrowest a cut model name gets, however little room is left
+const MIN_NAME_WIDTH: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ModelSort {
+    Name,
+    /// Largest first, ties by name
+    #[default]
+    Size,
+}
+
+/// The "Available Models" part of a hit block.
+pub fn model_block(models: &[Model], sort: ModelSort, limit: usize) -> Vec<String> {
+    let mut block = vec![format!("{}Available Models:", LIST_ITEM_STYLE)];
+    block.extend(model_lines(models, sort, limit, terminal_width()));
+    block.push(String::new());
+    block
+}
+
+/// The terminal's width, or None when output does not go to one.
+fn terminal_width() -> Option<usize> {
+    crossterm::terminal::size()
+        .ok()
+        .map(|(columns, _)| columns as usize)
+        .filter(|&columns| columns > 0)
+}
+
+/// One line per shown model, plus "… and N more" when `limit` (0 for no
+/// limit) leaves some out. Names are cut so lines fit in `width`.
+fn model_lines(models: &[Model], sort: ModelSort, limit: usize, width: Option<usize>) -> Vec<String> {
+    let mut sorted: Vec<&Model> = models.iter().collect();
+    match sort {
+        ModelSort::Name => sorted.sort_by(|a, b| a.name.cmp(&b.name)),
+        ModelSort::Size => sorted.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name))),
+    }
+    let shown = match limit {
+        0 => sorted.len(),
+        limit => limit.min(sorted.len()),
+    };
+    let hidden = sorted.len() - shown;
+    let sorted = &sorted[..shown];
+
+    let sizes: Vec<String> = sorted
+        .iter()
+        .map(|m| match m.size {
+            0 => String::new(),
+            size => format!("{:.2} GB", gigabytes(size)),
+        })
+        .collect();
+    let size_width = sizes.iter().map(|size| size.len()).max().unwrap_or(0);
+    let number_width = shown.to_string().len();
+    let mut name_width = sorted.iter().map(|m| measure_text_width(&m.name)).max().unwrap_or(0);
+    if let Some(width) = width {
+        // Indent, branch, "12. ", and two spaces before the size
+        let fixed = INDENT.len() + measure_text_width(LIST_ITEM_STYLE) + number_width + 2 + 2 + size_width;
+        name_width = name_width.min(width.saturating_sub(fixed).max(MIN_NAME_WIDTH));
+    }
+
+    let mut lines: Vec<String> = sorted
+        .iter()
+        .zip(&sizes)
+        .enumerate()
+        .map(|(i, (model, size))| {
+            let prefix = if i == shown - 1 && hidden == 0 { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
+            let name = match measure_text_width(&model.name) > name_width {
+                true => truncate_str(&model.name, name_width, "…"),
+                false => model.name.as_str().into(),
+            };
+            let entry = format!("{:>width$}. {}", i + 1, name, width = number_width);
+            if size_width == 0 {
+                return format!("{}{}{}", INDENT, prefix, style(entry).blue());
+            }
+            let entry = pad_str(&entry, number_width + 2 + name_width, Alignment::Left, None);
+            format!("{}{}{}  {}",
+                INDENT,
+                prefix,
+                style(entry).blue(),
+                style(format!("{:>width$}", size, width = size_width)).dim()
+            )
+        })
+        .collect();
+    if hidden > 0 {
+        lines.push(format!("{}{}{}",
+            INDENT,
+            LAST_ITEM_STYLE,
+            style(format!("… and {} more (see CSV)", hidden)).dim()
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models(list: &[(&str, u64)]) -> Vec<Model> {
+        let json: Vec<serde_json::Value> = list.iter().map(|(name, size)| serde_json::json!({"name": name, "size": size})).collect();
+        serde_json::from_value(serde_json::Value::Array(json)).unwrap()
+    }
+
+    fn plain(lines: Vec<String>) -> Vec<String> {
+        lines.iter().map(|line| console::strip_ansi_codes(line).into_owned()).collect()
+    }
+
+    #[test]
+    fn sorts_largest_first_and_caps_the_list() {
+        let models = models(&[("a", 1 << 30), ("b", 40 << 30), ("c", 0), ("d", 3 << 30)]);
+        let lines = plain(model_lines(&models, ModelSort::Size, 2, None));
+        assert_eq!(lines, [
+            "  ├─ 1. b  40.00 GB",
+            "  ├─ 2. d   3.00 GB",
+            "  ╰─ … and 2 more (see CSV)",
+        ]);
+        let lines = plain(model_lines(&models, ModelSort::Name, 0, None));
+        assert_eq!(lines[0], "  ├─ 1. a   1.00 GB");
+        assert_eq!(lines[2], "  ├─ 3. c          ");
+        assert_eq!(lines[3], "  ╰─ 4. d   3.00 GB");
+    }
+
+    #[test]
+    fn cuts_long_names_to_the_width() {
+        let long = format!("registry.example.com/{}/model:latest", "team".repeat(20));
+        let models = models(&[(&long, 2 << 30), ("phi3", 1 << 30)]);
+        let lines = plain(model_lines(&models, ModelSort::Size, 15, Some(60)));
+        assert!(lines.iter().all(|line| measure_text_width(line) == 60), "{:#?}", lines);
+        assert!(lines[0].contains("registry.example.com/teamteam") && lines[0].contains('…'), "{}", lines[0]);
+        assert!(lines[1].starts_with("  ╰─ 2. phi3 "), "{}", lines[1]);
+    }
+}
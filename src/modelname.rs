@@ -0,0 +1,114 @@
+//! Model names as statistics and alert rules see them. Hosts list the same
+//! model under many spellings: `llama3`, `llama3:latest`, `LLaMA3:8b`,
+//! `registry.ollama.ai/library/llama3:8b-instruct-q4_K_M`. `normalize` splits
+//! each into a base name, a tag and a variant so they can be counted together;
+//! the CSVs keep the name exactly as the host listed it.
+//!
+//! - Names are lowercased.
+//! - The default registry and its `library/` namespace are dropped; other
+//!   registries and user namespaces (`jondurbin/airoboros`) stay part of the
+//!   base, as they name different models.
+//! - A missing or empty tag is `latest`, as for `ollama pull`.
+//! - A digest pin (`@sha256:…`, or a tag that is only a digest) says nothing
+//!   about the tag and is dropped.
+//! - A tag starting with a parameter size is split there: `8b-instruct-q4_k_m`
+//!   is tag `8b`, variant `instruct-q4_k_m`. Any other tag is kept whole.
+
+use std::fmt;
+
+use crate::analyze::parse_parameter_size;
+
+const DEFAULT_REGISTRY: &str = "registry.ollama.ai/";
+const DEFAULT_NAMESPACE: &str = "library/";
+const LATEST: &str = "latest";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModelName {
+    /// e.g. `llama3` or `jondurbin/airoboros`
+    pub base: String,
+    /// The parameter size, or the whole tag when it doesn't start with one
+    pub tag: String,
+    /// What follows the size, empty for none
+    pub variant: String,
+}
+
+/// `base:tag-variant`, the name with only its spelling evened out.
+impl fmt::Display for ModelName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.base, self.tag)?;
+        if !self.variant.is_empty() {
+            write!(f, "-{}", self.variant)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn normalize(name: &str) -> ModelName {
+    let name = name.trim().to_lowercase();
+    let name = name.split_once('@').map_or(name.as_str(), |(name, _)| name);
+    let name = name.strip_prefix(DEFAULT_REGISTRY).unwrap_or(name);
+    let name = name.strip_prefix(DEFAULT_NAMESPACE).unwrap_or(name);
+    // A registry with a port has a ':' of its own, before the last '/'
+    let path_end = name.rfind('/').map_or(0, |slash| slash + 1);
+    let (base, tag) = match name[path_end..].find(':') {
+        Some(colon) => (&name[..path_end + colon], &name[path_end + colon + 1..]),
+        None => (name, ""),
+    };
+    let (tag, variant) = match tag.split_once('-') {
+        _ if tag.is_empty() || is_digest(tag) => (LATEST, ""),
+        Some((size, variant)) if parse_parameter_size(size).is_some() => (size, variant),
+        _ => (tag, ""),
+    };
+    ModelName { base: base.to_string(), tag: tag.to_string(), variant: variant.to_string() }
+}
+
+/// `sha256:…`/`sha256-…` or a bare 64-digit hex digest.
+fn is_digest(tag: &str) -> bool {
+    tag.starts_with("sha256") || (tag.len() == 64 && tag.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(name: &str) -> (String, String, String) {
+        let name = normalize(name);
+        (name.base, name.tag, name.variant)
+    }
+
+    #[test]
+    fn evens_out_names_seen_in_the_wild() {
+        let digest = "a6990ed6be412c6a217614b0ec8e9cd6800a743d5dd7e1d7fbe7d2bb5a1a0a3b";
+        for (name, (base, tag, variant)) in [
+            ("llama3", ("llama3", "latest", "")),
+            ("llama3:", ("llama3", "latest", "")),
+            ("llama3:latest", ("llama3", "latest", "")),
+            ("llama3:8b", ("llama3", "8b", "")),
+            ("llama3:8b-instruct-q4_K_M", ("llama3", "8b", "instruct-q4_k_m")),
+            ("LLaMA3:8B", ("llama3", "8b", "")),
+            ("registry.ollama.ai/library/llama3:8b", ("llama3", "8b", "")),
+            ("library/llama3", ("llama3", "latest", "")),
+            ("qwen2.5:1.5b-instruct", ("qwen2.5", "1.5b", "instruct")),
+            ("mistral:instruct", ("mistral", "instruct", "")),
+            ("phi3:mini-4k", ("phi3", "mini-4k", "")),
+            ("jondurbin/airoboros", ("jondurbin/airoboros", "latest", "")),
+            ("jondurbin/airoboros:70b-q4_0", ("jondurbin/airoboros", "70b", "q4_0")),
+            ("registry.ollama.ai/jondurbin/airoboros:70b", ("jondurbin/airoboros", "70b", "")),
+            ("hf.co/bartowski/Llama-3.2-1B-Instruct-GGUF:Q4_K_M", ("hf.co/bartowski/llama-3.2-1b-instruct-gguf", "q4_k_m", "")),
+            ("localhost:5000/custom:7b", ("localhost:5000/custom", "7b", "")),
+            ("localhost:5000/custom", ("localhost:5000/custom", "latest", "")),
+            (&format!("llama3:8b@sha256:{}", digest), ("llama3", "8b", "")),
+            (&format!("llama3@sha256:{}", digest), ("llama3", "latest", "")),
+            (&format!("llama3:sha256-{}", digest), ("llama3", "latest", "")),
+            (&format!("llama3:{}", digest), ("llama3", "latest", "")),
+        ] {
+            assert_eq!(parts(name), (base.to_string(), tag.to_string(), variant.to_string()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn displays_the_even_spelling() {
+        assert_eq!(normalize("registry.ollama.ai/library/Llama3:8b-Instruct").to_string(), "llama3:8b-instruct");
+        assert_eq!(normalize("jondurbin/airoboros").to_string(), "jondurbin/airoboros:latest");
+    }
+}
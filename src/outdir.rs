@@ -0,0 +1,116 @@
+//! `--output-dir` and `--run-dir`: where a scan writes the files it names
+//! itself, i.e. the CSVs, errors.csv, metrics.json, the checkpoint, the run
+//! file, the diff and the lock. Paths given on the command line, such as
+//! `--event-log` or `--parquet`, are used as given. Without either flag all
+//! of them go to the working directory, as they always have.
+//!
+//! With `--run-dir` each run gets a folder of its own named after its start,
+//! `results/2025-01-15_1830/`, and `results/latest` points at the newest: a
+//! symlink where the platform has them, otherwise a file holding the name.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const LATEST: &str = "latest";
+
+static OUTPUT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Where the output `name` goes.
+pub fn path(name: impl AsRef<Path>) -> PathBuf {
+    match OUTPUT_DIR.get() {
+        Some(dir) => dir.join(name),
+        None => name.as_ref().to_path_buf(),
+    }
+}
+
+/// The directory outputs go to, if not the working directory.
+pub fn dir() -> Option<&'static Path> {
+    OUTPUT_DIR.get().map(PathBuf::as_path)
+}
+
+/// Creates the directories and sends every output there. A resumed run
+/// carries on in the run directory `latest` points at.
+pub fn prepare(output_dir: Option<&Path>, run_dir: bool, resume: bool) -> Result<()> {
+    let parent = output_dir.unwrap_or(Path::new("."));
+    let dir = match (output_dir, run_dir) {
+        (None, false) => return Ok(()),
+        (Some(dir), false) => {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            dir.to_path_buf()
+        }
+        (_, true) if resume => match latest(parent) {
+            Some(dir) => dir,
+            None => bail!("--resume with --run-dir continues the run {} points at, and there is none", parent.join(LATEST).display()),
+        },
+        (_, true) => {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+            let dir = create_run_dir(parent, &chrono::Local::now().format("%Y-%m-%d_%H%M").to_string())?;
+            point_latest(parent, &dir)?;
+            dir
+        }
+    };
+    let _ = OUTPUT_DIR.set(dir);
+    Ok(())
+}
+
+/// `parent/name`, or `name-2` and so on when a run started the same minute.
+fn create_run_dir(parent: &Path, name: &str) -> Result<PathBuf> {
+    for attempt in 1.. {
+        let dir = match attempt {
+            1 => parent.join(name),
+            n => parent.join(format!("{}-{}", name, n)),
+        };
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to create {}", dir.display())),
+        }
+    }
+    unreachable!("names run out")
+}
+
+/// Replaces `latest` in one step, so a script never finds it missing.
+fn point_latest(parent: &Path, dir: &Path) -> Result<()> {
+    let name = dir.file_name().expect("run directories have a name");
+    let partial = parent.join(format!("{}.{}", LATEST, std::process::id()));
+    let _ = fs::remove_file(&partial);
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(name, &partial)?;
+    #[cfg(not(unix))]
+    fs::write(&partial, format!("{}\n", name.to_string_lossy()))?;
+    fs::rename(&partial, parent.join(LATEST)).with_context(|| format!("Failed to update {}", parent.join(LATEST).display()))
+}
+
+/// The run directory `parent/latest` names, symlink or pointer file.
+fn latest(parent: &Path) -> Option<PathBuf> {
+    let pointer = parent.join(LATEST);
+    let name = match fs::read_link(&pointer) {
+        Ok(target) => target,
+        Err(_) => PathBuf::from(fs::read_to_string(&pointer).ok()?.trim()),
+    };
+    let dir = parent.join(name);
+    dir.is_dir().then_some(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_runs_of_the_same_minute_apart_and_points_at_the_newest() {
+        let parent = std::env::temp_dir().join(format!("pof-outdir-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&parent);
+        fs::create_dir_all(&parent).unwrap();
+        let first = create_run_dir(&parent, "2025-01-15_1830").unwrap();
+        point_latest(&parent, &first).unwrap();
+        let second = create_run_dir(&parent, "2025-01-15_1830").unwrap();
+        assert_eq!(second, parent.join("2025-01-15_1830-2"));
+        assert_eq!(latest(&parent), Some(first));
+        point_latest(&parent, &second).unwrap();
+        assert_eq!(latest(&parent), Some(second));
+        fs::remove_dir_all(&parent).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+//! One scan per output directory. Two runs appending to the same CSVs flush
+//! their buffers independently and interleave half rows, so each run holds
+//! an advisory lock on `ollama-finder.lock` for as long as it writes. The
+//! lock belongs to the open file, so the OS releases it however the run
+//! ends, a panic or a kill included, and the file itself can stay behind.
+//!
+//! With `--force-suffix` a run that finds the lock taken moves on to the
+//! first free `ollama-finder-N.lock` and writes `ollama_endpoints-N.csv` and
+//! so on instead.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::PathBuf;
+
+pub const LOCK_FILE: &str = "ollama-finder.lock";
+
+/// Held for the whole run; dropping it lets another scan in.
+pub struct OutputLock {
+    _file: File,
+    /// `N` of the `-N` names this run writes, `None` for the usual ones
+    pub suffix: Option<u32>,
+}
+
+pub fn acquire(force_suffix: bool) -> Result<OutputLock> {
+    for suffix in std::iter::once(None).chain((2..).map(Some)) {
+        let path = lock_path(suffix);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {
+                file.set_len(0)?;
+                writeln!(file, "{}", std::process::id()).with_context(|| format!("Failed to write {}", path.display()))?;
+                return Ok(OutputLock { _file: file, suffix });
+            }
+            Err(TryLockError::WouldBlock) if force_suffix => continue,
+            Err(TryLockError::WouldBlock) => {
+                // Written right after locking, so it can be missing for a moment
+                let pid = fs::read_to_string(&path).ok().filter(|pid| !pid.trim().is_empty());
+                bail!(
+                    "Another scan (PID {}) is writing the output files in this directory. Wait for it to end, run this one elsewhere, or pass --force-suffix to write ollama_endpoints-2.csv and so on",
+                    pid.as_deref().map_or("unknown", str::trim)
+                );
+            }
+            Err(TryLockError::Error(e)) => return Err(e).with_context(|| format!("Failed to lock {}", path.display())),
+        }
+    }
+    unreachable!("suffixes run out")
+}
+
+fn lock_path(suffix: Option<u32>) -> PathBuf {
+    crate::rotate::suffixed(&crate::outdir::path(LOCK_FILE), suffix)
+}
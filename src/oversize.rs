@@ -0,0 +1,107 @@
+//! `--max-targets`: one stray prefix length in ip-ranges.txt turns a /24 into
+//! a /2, so a scan of more addresses than this does not start unconfirmed.
+//! The addresses counted are the ones the scan would really probe, after
+//! duplicates, sampling and sharding.
+//!
+//! At a terminal the scan shows its total, how long it would take and the
+//! ranges contributing most, and asks. Anywhere else it exits with code 4
+//! unless `--i-know-what-im-doing` is given.
+
+use anyhow::Result;
+use console::style;
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, ExecutableCommand};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::cli::{format_duration, ScanArgs};
+use crate::probed::contributions;
+use crate::targets::Target;
+use crate::{HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE, PLAIN_PROGRESS};
+
+/// Ranges listed under "Largest ranges"
+const LARGEST_SHOWN: usize = 5;
+
+pub struct Oversize {
+    total: u64,
+    limit: u64,
+    /// Probes per second the scan sends at most
+    rate: f64,
+    /// Every port of every host, as a host that does not answer gets
+    probes: u64,
+    /// The ranges adding the most addresses, largest first
+    largest: Vec<(String, u64)>,
+}
+
+impl Oversize {
+    /// The scan of `total` addresses `targets` plan, when it needs confirming.
+    pub fn check(targets: &[Target], total: u64, args: &ScanArgs, seed: u64, rate: f64) -> Option<Self> {
+        if total <= args.max_targets || args.i_know_what_im_doing {
+            return None;
+        }
+        let mut largest: Vec<(String, u64)> = targets
+            .iter()
+            .zip(contributions(targets, args, seed))
+            .filter(|(_, count)| *count > 0)
+            .map(|(target, count)| (range_text(target), count))
+            .collect();
+        // Stable, so equal ranges keep the order they are listed in
+        largest.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        largest.truncate(LARGEST_SHOWN);
+        Some(Self { total, limit: args.max_targets, rate, probes: total * args.ports_per_host(), largest })
+    }
+
+    /// Whether the scan may start: asked at a terminal, refused elsewhere.
+    /// `raw_mode` is whether the terminal is in raw mode for the scan's keys.
+    pub fn confirm(&self, out: &mut dyn Write, raw_mode: bool) -> Result<bool> {
+        if !std::io::stdin().is_terminal() {
+            let mut err = std::io::stderr();
+            self.report(&mut err)?;
+            writeln!(err, "{}{}", LAST_ITEM_STYLE, style("Not started: pass --i-know-what-im-doing to scan them all, or raise --max-targets").red())?;
+            return Ok(false);
+        }
+        if raw_mode {
+            terminal::disable_raw_mode()?;
+        }
+        self.report(out)?;
+        write!(out, "{}{} ", LAST_ITEM_STYLE, style(format!("Type 'y' to scan all {} addresses:", self.total)).bold())?;
+        out.flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if raw_mode {
+            terminal::enable_raw_mode()?;
+        }
+        let confirmed = input.trim().eq_ignore_ascii_case("y");
+        // The banner is drawn from the top, over what is left of this
+        if confirmed && !PLAIN_PROGRESS.load(Ordering::Relaxed) {
+            out.execute(Clear(ClearType::All))?;
+            out.execute(cursor::MoveTo(0, 0))?;
+        }
+        Ok(confirmed)
+    }
+
+    fn report(&self, out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "\n{}{}", HEADER_STYLE, style(format!("This scan covers {} addresses, more than --max-targets ({})", self.total, self.limit)).red().bold())?;
+        writeln!(out, "{}Estimated duration: {} at {:.0} probes/s ({} probes)",
+            LIST_ITEM_STYLE,
+            style(format_duration(Duration::from_secs_f64(self.probes as f64 / self.rate))).yellow(),
+            self.rate,
+            self.probes
+        )?;
+        writeln!(out, "{}Largest ranges:", LIST_ITEM_STYLE)?;
+        for (range, count) in &self.largest {
+            writeln!(out, "{}  {} addresses from {}", LIST_ITEM_STYLE, style(count).cyan(), range)?;
+        }
+        Ok(())
+    }
+}
+
+/// "10.0.0.0/8 (ip-ranges.txt:3)", naming the line to fix.
+fn range_text(target: &Target) -> String {
+    let blocks = match target.blocks.as_slice() {
+        [block] => block.network.to_string(),
+        blocks => format!("{} blocks", blocks.len()),
+    };
+    format!("{} ({})", blocks, target.provenance.source_text())
+}
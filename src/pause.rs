@@ -0,0 +1,172 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct PauseState {
+    since: Option<Instant>,
+    total: Duration,
+}
+
+/// Accumulates the time spent paused so rates and ETAs only count active time.
+#[derive(Debug)]
+pub struct PauseClock {
+    state: Mutex<PauseState>,
+}
+
+pub static PAUSE_CLOCK: PauseClock = PauseClock::new();
+
+impl PauseClock {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(PauseState { since: None, total: Duration::ZERO }),
+        }
+    }
+
+    /// Starts a pause; does nothing if one is already running.
+    pub fn pause(&self) {
+        self.pause_at(Instant::now());
+    }
+
+    pub fn resume(&self) {
+        self.resume_at(Instant::now());
+    }
+
+    fn pause_at(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if state.since.is_none() {
+            state.since = Some(now);
+        }
+    }
+
+    fn resume_at(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(since) = state.since.take() {
+            state.total += now.saturating_duration_since(since);
+        }
+    }
+
+    /// How long the pause in progress has lasted, if there is one.
+    pub fn current(&self) -> Option<Duration> {
+        let since = self.state.lock().unwrap().since?;
+        Some(since.elapsed())
+    }
+
+    /// Total paused time up to `now`, including a pause still in progress.
+    fn paused_total_at(&self, now: Instant) -> Duration {
+        let state = self.state.lock().unwrap();
+        state.total + state.since.map(|s| now.saturating_duration_since(s)).unwrap_or_default()
+    }
+}
+
+/// Wall-clock time since creation, split into active and paused parts.
+#[derive(Debug, Clone, Copy)]
+pub struct ActiveTimer {
+    clock: &'static PauseClock,
+    started: Instant,
+    paused_at_start: Duration,
+}
+
+impl ActiveTimer {
+    pub fn start() -> Self {
+        Self::start_on(&PAUSE_CLOCK, Instant::now())
+    }
+
+    fn start_on(clock: &'static PauseClock, now: Instant) -> Self {
+        Self {
+            clock,
+            started: now,
+            paused_at_start: clock.paused_total_at(now),
+        }
+    }
+
+    pub fn paused(&self) -> Duration {
+        self.paused_at(Instant::now())
+    }
+
+    pub fn active(&self) -> Duration {
+        self.active_at(Instant::now())
+    }
+
+    fn paused_at(&self, now: Instant) -> Duration {
+        self.clock.paused_total_at(now).saturating_sub(self.paused_at_start)
+    }
+
+    fn active_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.started).saturating_sub(self.paused_at(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn accumulates_several_pause_cycles() {
+        static CLOCK: PauseClock = PauseClock::new();
+        let t0 = Instant::now();
+        let timer = ActiveTimer::start_on(&CLOCK, t0);
+        CLOCK.pause_at(t0 + secs(1));
+        CLOCK.resume_at(t0 + secs(3));
+        CLOCK.pause_at(t0 + secs(5));
+        CLOCK.resume_at(t0 + secs(6));
+
+        assert_eq!(CLOCK.paused_total_at(t0 + secs(10)), secs(3));
+        assert_eq!(timer.paused_at(t0 + secs(10)), secs(3));
+        assert_eq!(timer.active_at(t0 + secs(10)), secs(7));
+    }
+
+    #[test]
+    fn an_unresumed_pause_counts_up_to_now() {
+        static CLOCK: PauseClock = PauseClock::new();
+        let t0 = Instant::now();
+        let timer = ActiveTimer::start_on(&CLOCK, t0);
+        CLOCK.pause_at(t0 + secs(2));
+
+        assert_eq!(timer.paused_at(t0 + secs(5)), secs(3));
+        assert_eq!(timer.active_at(t0 + secs(5)), secs(2));
+        // Active time stands still for as long as the pause lasts
+        assert_eq!(timer.active_at(t0 + secs(60)), secs(2));
+    }
+
+    #[test]
+    fn a_repeated_pause_keeps_the_first_start() {
+        static CLOCK: PauseClock = PauseClock::new();
+        let t0 = Instant::now();
+        CLOCK.pause_at(t0 + secs(1));
+        CLOCK.pause_at(t0 + secs(2));
+        CLOCK.resume_at(t0 + secs(4));
+        CLOCK.resume_at(t0 + secs(9));
+
+        assert_eq!(CLOCK.paused_total_at(t0 + secs(10)), secs(3));
+    }
+
+    #[test]
+    fn timers_ignore_pauses_before_they_started() {
+        static CLOCK: PauseClock = PauseClock::new();
+        let t0 = Instant::now();
+        CLOCK.pause_at(t0);
+        CLOCK.resume_at(t0 + secs(1));
+        let timer = ActiveTimer::start_on(&CLOCK, t0 + secs(2));
+        CLOCK.pause_at(t0 + secs(3));
+        CLOCK.resume_at(t0 + secs(4));
+
+        assert_eq!(timer.paused_at(t0 + secs(6)), secs(1));
+        assert_eq!(timer.active_at(t0 + secs(6)), secs(3));
+    }
+
+    #[test]
+    fn a_timer_started_during_a_pause_counts_only_its_share() {
+        static CLOCK: PauseClock = PauseClock::new();
+        let t0 = Instant::now();
+        CLOCK.pause_at(t0 + secs(1));
+        let timer = ActiveTimer::start_on(&CLOCK, t0 + secs(2));
+        CLOCK.resume_at(t0 + secs(4));
+
+        assert_eq!(timer.paused_at(t0 + secs(5)), secs(2));
+        assert_eq!(timer.active_at(t0 + secs(5)), secs(1));
+    }
+}
@@ -0,0 +1,144 @@
+//! `--precheck icmp` and `icmp-soft`: an ICMP echo to each host before its
+//! probes. Dead hosts in residential and cloud ranges cost a full connect
+//! timeout per port, while a ping costs one packet and at most
+//! `--ping-timeout`.
+//!
+//! `icmp` skips the hosts that do not answer. `icmp-soft` still probes them,
+//! but after the hosts that did answer, since plenty of servers drop ICMP and
+//! serve HTTP all the same; what those hosts turn out to serve measures how
+//! much `icmp` would have missed.
+//!
+//! Pings need a raw socket, or on Linux an ICMP datagram socket allowed by
+//! `net.ipv4.ping_group_range`. Without either the scan goes on without the
+//! pre-check, after one warning.
+
+use clap::ValueEnum;
+use futures::stream::{self, Stream, StreamExt};
+use futures::FutureExt;
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence};
+
+use crate::cli::format_duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Precheck {
+    /// Skip hosts that do not answer a ping
+    Icmp,
+    /// Probe hosts that do not answer a ping after those that do
+    IcmpSoft,
+}
+
+/// Pings awaiting an answer at once; with every host silent, this many per
+/// `--ping-timeout` reach the probes
+const PINGS_IN_FLIGHT: usize = 1024;
+/// Silent hosts `icmp-soft` holds back at most before probing them anyway,
+/// so a large dead range cannot pile up in memory
+const MAX_DEFERRED: usize = 65_536;
+const PAYLOAD: [u8; 16] = *b"ollama-precheck\0";
+
+pub struct Pinger {
+    client: Client,
+    mode: Precheck,
+    timeout: Duration,
+    /// Each ping gets its own identifier, so a host listed twice cannot
+    /// collide with itself
+    ident: AtomicU16,
+}
+
+impl Pinger {
+    /// Opens the ICMP socket; None, after a warning, without permission to.
+    pub fn open(mode: Precheck, timeout: Duration) -> Option<Self> {
+        match Client::new(&Config::default()) {
+            Ok(client) => Some(Self { client, mode, timeout, ident: AtomicU16::new(0) }),
+            Err(e) => {
+                eprintln!(
+                    "Warning: cannot send pings ({}); scanning without the ping pre-check. Run as root, or allow \
+                     unprivileged pings with `sudo sysctl -w net.ipv4.ping_group_range=\"0 2147483647\"`",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Whether hosts that do not answer are left unprobed.
+    pub fn skips_silent(&self) -> bool {
+        self.mode == Precheck::Icmp
+    }
+
+    /// "ping each host (1s timeout), skipping those that do not answer", for
+    /// the banner.
+    pub fn describe(&self) -> String {
+        let timeout = match self.timeout.subsec_millis() {
+            0 => format_duration(self.timeout),
+            _ => format!("{}ms", self.timeout.as_millis()),
+        };
+        format!("ping each host ({} timeout), {}",
+            timeout,
+            match self.mode {
+                Precheck::Icmp => "skipping those that do not answer",
+                Precheck::IcmpSoft => "probing those that do not answer last",
+            }
+        )
+    }
+
+    /// Whether `ip` answered within the timeout. A ping that could not be
+    /// sent, to a broadcast address or without a route, counts as silence.
+    async fn answers(&self, ip: Ipv4Addr) -> bool {
+        let ident = PingIdentifier(self.ident.fetch_add(1, Ordering::Relaxed));
+        let mut pinger = self.client.pinger(IpAddr::V4(ip), ident).await;
+        pinger.timeout(self.timeout);
+        pinger.ping(PingSequence(0), &PAYLOAD).await.is_ok()
+    }
+}
+
+/// `hosts`, each with whether `address` of it answered a ping. Pings go out
+/// ahead of the probes, so hosts come in the order they answer; with
+/// `icmp-soft` the silent ones wait until no answering host is ready.
+pub fn pinged<T>(hosts: impl Stream<Item = T>, address: fn(&T) -> Ipv4Addr, pinger: Arc<Pinger>) -> impl Stream<Item = (T, bool)> {
+    let defer = !pinger.skips_silent();
+    let answers = hosts
+        .map(move |host| {
+            let pinger = pinger.clone();
+            async move {
+                let answered = pinger.answers(address(&host)).await;
+                (host, answered)
+            }
+        })
+        .buffer_unordered(PINGS_IN_FLIGHT);
+    if !defer {
+        return answers.left_stream();
+    }
+    let state = (Box::pin(answers), VecDeque::new(), false);
+    stream::unfold(state, |(mut answers, mut deferred, mut done)| async move {
+        loop {
+            if !done && deferred.len() < MAX_DEFERRED {
+                match answers.next().now_or_never() {
+                    Some(Some((host, true))) => return Some(((host, true), (answers, deferred, done))),
+                    Some(Some(silent)) => {
+                        deferred.push_back(silent);
+                        continue;
+                    }
+                    Some(None) => done = true,
+                    None => {}
+                }
+            }
+            if let Some(silent) = deferred.pop_front() {
+                return Some((silent, (answers, deferred, done)));
+            }
+            if done {
+                return None;
+            }
+            match answers.next().await {
+                Some(silent @ (_, false)) => deferred.push_back(silent),
+                Some(answered) => return Some((answered, (answers, deferred, done))),
+                None => done = true,
+            }
+        }
+    })
+    .right_stream()
+}
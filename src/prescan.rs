@@ -0,0 +1,297 @@
+//! `--prescan masscan`: masscan's SYN sweep finds the open ports of each
+//! range, and only the hosts it reports open go through the probes, as they
+//! are reported rather than once masscan is done. `--syn` does the same with
+//! the scanner's own SYN sweep (see `syn.rs`).
+//!
+//! masscan needs raw sockets. Without them it fails at once, and the error
+//! says what to grant. When it is not installed at all the scan falls back to
+//! the built-in connect scanning.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "syn")]
+use crate::syn::SynScanner;
+use crate::targets::Target;
+use crate::STOP_SCAN;
+
+/// Tools `--prescan` can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Prescan {
+    Masscan,
+}
+
+/// How often a running sweep checks whether the scan was stopped
+pub const STOP_CHECK: Duration = Duration::from_millis(100);
+/// Most of masscan's error output kept for the message when it fails
+const MAX_STDERR: u64 = 16 * 1024;
+
+/// One line of masscan's JSON output.
+#[derive(Debug, Deserialize)]
+struct Report {
+    ip: Ipv4Addr,
+    #[serde(default)]
+    ports: Vec<ReportedPort>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportedPort {
+    #[serde(default)]
+    status: String,
+}
+
+/// The address of an open port on one line of masscan's JSON output, which
+/// comes as an array with one object per line: `[`, `{...}`, `,`, `]`.
+fn open_address(line: &str) -> Option<Ipv4Addr> {
+    let line = line.trim().trim_end_matches(',');
+    if !line.starts_with('{') {
+        return None;
+    }
+    let report: Report = serde_json::from_str(line).ok()?;
+    report.ports.iter().any(|port| port.status.is_empty() || port.status == "open").then_some(report.ip)
+}
+
+/// Whether masscan failed for lack of raw socket access.
+fn lacks_privileges(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    ["permission denied", "operation not permitted", "you don't have permission"].iter().any(|sign| stderr.contains(sign))
+}
+
+/// `path` as found on PATH, for the setcap instructions.
+fn resolve(path: &Path) -> PathBuf {
+    if path.components().count() > 1 {
+        return path.to_path_buf();
+    }
+    std::env::var_os("PATH")
+        .and_then(|dirs| std::env::split_paths(&dirs).map(|dir| dir.join(path)).find(|candidate| candidate.is_file()))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// What one range's sweep found.
+#[derive(Debug, Default)]
+pub struct Sweep {
+    /// Hosts with an open port
+    pub open: u64,
+    /// Ports that answered with a reset, and those an ICMP unreachable or a
+    /// failed send ruled out; masscan's output leaves both out
+    pub refused: u64,
+    pub unreachable: u64,
+}
+
+/// Hands each open host of a range to the probes once, as it is found, and
+/// counts it on the progress bar as `steps` more to verify.
+pub struct OpenHosts {
+    target: Target,
+    tx: mpsc::UnboundedSender<Ipv4Addr>,
+    progress: Arc<ProgressBar>,
+    steps: u64,
+    reported: HashSet<Ipv4Addr>,
+}
+
+impl OpenHosts {
+    fn new(target: &Target, progress: Arc<ProgressBar>, steps: u64) -> (Self, mpsc::UnboundedReceiver<Ipv4Addr>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { target: target.clone(), tx, progress, steps, reported: HashSet::new() }, rx)
+    }
+
+    /// Passes `ip` on unless it was already, or is not one of the range's
+    /// hosts; false once nobody is listening any more.
+    pub fn report(&mut self, ip: Ipv4Addr) -> bool {
+        // Sweeps also reach the network and broadcast addresses of a CIDR
+        if !self.target.lists(ip) || !self.reported.insert(ip) {
+            return true;
+        }
+        self.progress.inc_length(self.steps);
+        self.tx.send(ip).is_ok()
+    }
+
+    /// Whether the scan was stopped or the probes no longer take hosts.
+    pub fn abandoned(&self) -> bool {
+        STOP_SCAN.load(Ordering::Relaxed) || self.tx.is_closed()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.reported.len() as u64
+    }
+}
+
+/// What finds the open hosts of each range before they are probed.
+pub enum Prescanner {
+    Masscan(Masscan),
+    #[cfg(feature = "syn")]
+    Syn(SynScanner),
+}
+
+impl Prescanner {
+    /// For the banner.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Masscan(masscan) => masscan.describe(),
+            #[cfg(feature = "syn")]
+            Self::Syn(syn) => syn.describe(),
+        }
+    }
+
+    /// Who reported the open hosts, for the line logged after each range.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Masscan(_) => "masscan",
+            #[cfg(feature = "syn")]
+            Self::Syn(_) => "The SYN scan",
+        }
+    }
+
+    /// Starts sweeping `target`'s hosts on `ports`. Each host found open is
+    /// sent once, and counted on `progress` as `steps` more to verify. The
+    /// task stops early once the scan is stopped or the receiver is dropped.
+    pub fn sweep(&self, target: &Target, ports: &[u16], progress: Arc<ProgressBar>, steps: u64) -> (mpsc::UnboundedReceiver<Ipv4Addr>, JoinHandle<Result<Sweep>>) {
+        let (open, rx) = OpenHosts::new(target, progress, steps);
+        let task = match self {
+            Self::Masscan(masscan) => masscan.sweep(target, ports, open),
+            #[cfg(feature = "syn")]
+            Self::Syn(syn) => syn.sweep(target, ports, open),
+        };
+        (rx, task)
+    }
+}
+
+pub struct Masscan {
+    path: PathBuf,
+    rate: u32,
+}
+
+impl Masscan {
+    /// Checks that masscan runs at all; None, after a warning, when it is
+    /// not installed, so the scan connects to every host itself.
+    pub async fn find(path: &Path, rate: u32) -> Result<Option<Self>> {
+        match Command::new(path).arg("--version").stdout(Stdio::null()).stderr(Stdio::null()).status().await {
+            Ok(_) => Ok(Some(Self { path: path.to_path_buf(), rate })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("Warning: {} is not installed; falling back to the built-in connect scan", path.display());
+                Ok(None)
+            }
+            Err(e) => Err(e).with_context(|| format!("Failed to run {}", path.display())),
+        }
+    }
+
+    /// "masscan at 10000 packets/s", for the banner.
+    pub fn describe(&self) -> String {
+        format!("{} at {} packets/s", self.path.display(), self.rate)
+    }
+
+    /// Starts masscan on `target`'s blocks and `ports`; it is killed once
+    /// the hosts are no longer wanted.
+    fn sweep(&self, target: &Target, ports: &[u16], mut open: OpenHosts) -> JoinHandle<Result<Sweep>> {
+        let blocks: Vec<String> = target.blocks.iter().map(|block| block.network.to_string()).collect();
+        let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+        let mut command = Command::new(&self.path);
+        command
+            .arg(blocks.join(","))
+            .arg(format!("-p{}", ports.join(",")))
+            .args(["--rate", &self.rate.to_string(), "--output-format", "json", "--output-filename", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let path = self.path.clone();
+        tokio::spawn(async move {
+            let mut child = command.spawn().with_context(|| format!("Failed to run {}", path.display()))?;
+            let stderr = child.stderr.take().map(|stderr| {
+                tokio::spawn(async move {
+                    let mut text = String::new();
+                    let _ = stderr.take(MAX_STDERR).read_to_string(&mut text).await;
+                    text
+                })
+            });
+            let reported = forward(&mut child, &mut open).await;
+            let status = match reported {
+                Some(_) => child.wait().await?,
+                None => {
+                    // Stopped, or nobody is listening any more
+                    let _ = child.kill().await;
+                    return Ok(Sweep::default());
+                }
+            };
+            let stderr = match stderr {
+                Some(task) => task.await.unwrap_or_default(),
+                None => String::new(),
+            };
+            if !status.success() {
+                if lacks_privileges(&stderr) {
+                    let path = resolve(&path);
+                    bail!(
+                        "masscan needs raw socket access (CAP_NET_RAW and CAP_NET_ADMIN). Run as root, grant them once with \
+                         `sudo setcap cap_net_raw,cap_net_admin+eip {0}`, or make it setuid root with \
+                         `sudo chown root {0} && sudo chmod u+s {0}`",
+                        path.display()
+                    );
+                }
+                let last = stderr.lines().map(str::trim).rfind(|line| !line.is_empty()).unwrap_or("no error output");
+                bail!("masscan exited with {}: {}", status, last);
+            }
+            Ok(Sweep { open: reported.unwrap_or(0), ..Default::default() })
+        })
+    }
+}
+
+/// Passes on the open hosts masscan prints until it is done, and returns how
+/// many it reported; None once they are no longer wanted.
+async fn forward(child: &mut Child, open: &mut OpenHosts) -> Option<u64> {
+    let stdout = child.stdout.take()?;
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            _ = tokio::time::sleep(STOP_CHECK) => {
+                if open.abandoned() {
+                    return None;
+                }
+                continue;
+            }
+        };
+        let Ok(Some(line)) = line else {
+            return Some(open.count());
+        };
+        if let Some(ip) = open_address(&line) {
+            if !open.report(ip) {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_open_ports_from_json_lines() {
+        let line = r#"{   "ip": "203.0.113.7",   "timestamp": "1700000000", "ports": [ {"port": 11434, "proto": "tcp", "status": "open", "reason": "syn-ack", "ttl": 64} ] }"#;
+        assert_eq!(open_address(line), Some(Ipv4Addr::new(203, 0, 113, 7)));
+        assert_eq!(open_address(&format!("{},", line)), Some(Ipv4Addr::new(203, 0, 113, 7)));
+        assert_eq!(open_address(&line.replace("\"open\"", "\"closed\"")), None);
+        assert_eq!(open_address("["), None);
+        assert_eq!(open_address(","), None);
+        assert_eq!(open_address("{ \"finished\": 1 }"), None);
+    }
+
+    #[test]
+    fn recognizes_missing_privileges() {
+        assert!(lacks_privileges("FAIL: permission denied\n [hint] need to sudo or run as root or something\n"));
+        assert!(lacks_privileges("pcap_open_live(eth0): socket: Operation not permitted"));
+        assert!(!lacks_privileges("FAIL: failed to detect router for interface"));
+    }
+}
@@ -0,0 +1,45 @@
+//! Console output from probe tasks. Many tasks confirm hits at the same
+//! moment, so each one's lines go as a block to a single printer task that
+//! writes it in one piece with the progress bar cleared around it; blocks
+//! never interleave and the bar redraws underneath.
+
+use crossterm::{cursor, QueueableCommand};
+use indicatif::ProgressBar;
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::{anonymize, chrome, PLAIN_PROGRESS};
+
+/// Takes blocks of lines for the printer.
+pub type PrintSender = mpsc::UnboundedSender<Vec<String>>;
+
+/// Starts the printer. It ends once every sender is dropped and the blocks
+/// sent until then are written, so hits confirmed while the scan shuts down
+/// still appear.
+pub fn spawn_printer(progress: Arc<ProgressBar>) -> (PrintSender, JoinHandle<()>) {
+    // Unbounded so printing never holds up a probe
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<String>>();
+    let handle = tokio::spawn(async move {
+        while let Some(block) = rx.recv().await {
+            progress.suspend(|| print_block(&block));
+        }
+    });
+    (tx, handle)
+}
+
+/// Writes `lines` like `console_log` would, flushing once at the end.
+fn print_block(lines: &[String]) {
+    let mut out = chrome();
+    let plain = PLAIN_PROGRESS.load(Ordering::Relaxed);
+    for line in lines {
+        // The terminal may be in raw mode, where a newline does not return the cursor
+        if !plain {
+            let _ = out.queue(cursor::MoveToColumn(0));
+        }
+        let _ = writeln!(out, "{}", anonymize::for_reports(line));
+    }
+    let _ = out.flush();
+}
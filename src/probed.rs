@@ -0,0 +1,226 @@
+//! Addresses listed by more than one target. Mixed inputs, such as /32s from
+//! a search engine next to a provider's CIDRs that contain them, would
+//! otherwise get the same host probed and counted twice; the first target to
+//! reach an address probes it and later ones skip it.
+//!
+//! Only the spans where targets overlap are tracked, in a bitmap with one
+//! 8 KiB page per /16 they touch: inputs that do not overlap cost nothing,
+//! and a /8 listed twice costs 2 MiB. Targets appended to the input files
+//! mid-scan can add spans (see `extend`).
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Mutex, RwLock};
+
+use crate::cli::ScanArgs;
+use crate::targets::{planned_count, planned_hosts, Target};
+
+/// Addresses per bitmap page: one /16
+const PAGE_BITS: usize = 1 << 16;
+
+#[derive(Debug, Default)]
+pub struct ProbedSet {
+    /// Sorted, disjoint spans of addresses more than one target lists
+    overlaps: RwLock<Vec<(u32, u32)>>,
+    /// Bitmap pages by /16, allocated on first use
+    pages: Mutex<HashMap<u16, Box<[u64]>>>,
+}
+
+impl ProbedSet {
+    pub fn new(targets: &[Target]) -> Self {
+        Self { overlaps: RwLock::new(overlaps(targets)), pages: Mutex::default() }
+    }
+
+    /// Takes in targets appended while the scan runs; `targets` is the whole
+    /// list now. The hosts `finished` ranges have in spans that only now
+    /// overlap are marked reached, as a resumed scan would mark them.
+    pub fn extend(&self, targets: &[Target], finished: &[Target], args: &ScanArgs, seed: u64) {
+        *self.overlaps.write().unwrap() = overlaps(targets);
+        self.replay(finished, args, seed);
+    }
+
+    /// Whether `ip` has not been reached before, marking it reached. Always
+    /// true outside the overlaps.
+    pub fn first_probe(&self, ip: Ipv4Addr) -> bool {
+        let ip = u32::from(ip);
+        if !self.overlapping(ip) {
+            return true;
+        }
+        let mut pages = self.pages.lock().unwrap();
+        let page = pages.entry((ip >> 16) as u16).or_insert_with(|| vec![0; PAGE_BITS / 64].into_boxed_slice());
+        let (word, bit) = ((ip & 0xffff) as usize / 64, ip % 64);
+        let first = page[word] & (1 << bit) == 0;
+        page[word] |= 1 << bit;
+        first
+    }
+
+    /// Marks the planned hosts of `targets` reached, as they are once a scan
+    /// has finished them; returns how many of them had been reached already.
+    /// A resumed scan replays the ranges its checkpoint has finished this way,
+    /// so the checkpoint needs no copy of the bitmap.
+    pub fn replay(&self, targets: &[Target], args: &ScanArgs, seed: u64) -> u64 {
+        let mut duplicates = 0;
+        for target in targets {
+            for ip in self.overlapping_hosts(target, args, seed) {
+                duplicates += !self.first_probe(ip) as u64;
+            }
+        }
+        duplicates
+    }
+
+    fn overlapping(&self, ip: u32) -> bool {
+        let overlaps = self.overlaps.read().unwrap();
+        let i = overlaps.partition_point(|(_, last)| *last < ip);
+        overlaps.get(i).is_some_and(|(first, _)| *first <= ip)
+    }
+
+    /// The planned hosts of `target` inside the overlaps, without walking the
+    /// rest of it unless it is sampled.
+    fn overlapping_hosts<'a>(&'a self, target: &Target, args: &ScanArgs, seed: u64) -> Box<dyn Iterator<Item = Ipv4Addr> + 'a> {
+        let overlaps = self.overlaps.read().unwrap().clone();
+        if overlaps.is_empty() {
+            return Box::new(std::iter::empty());
+        }
+        if args.sample_plan().is_some() {
+            return Box::new(planned_hosts(target, args, seed).filter(|ip| self.overlapping(u32::from(*ip))));
+        }
+        let shard = args.shard;
+        let spans: Vec<(u32, u32)> = target.blocks.iter().filter_map(|block| span(block.hosts())).collect();
+        Box::new(
+            spans
+                .into_iter()
+                .flat_map(move |(first, last)| {
+                    let start = overlaps.partition_point(|(_, end)| *end < first);
+                    overlaps[start..]
+                        .iter()
+                        .take_while(move |(begin, _)| *begin <= last)
+                        .map(move |(begin, end)| (*begin.max(&first), *end.min(&last)))
+                        .collect::<Vec<_>>()
+                })
+                .flat_map(|(first, last)| (first..=last).map(Ipv4Addr::from))
+                .filter(move |ip| shard.is_none_or(|shard| shard.contains(*ip))),
+        )
+    }
+}
+
+/// How many distinct hosts `targets` plan to probe, and how many more they
+/// list because they overlap.
+pub fn unique_count(targets: &[Target], args: &ScanArgs, seed: u64) -> (u64, u64) {
+    let listed: u64 = targets.iter().map(|target| planned_count(target, args, seed)).sum();
+    let duplicates = ProbedSet::new(targets).replay(targets, args, seed);
+    (listed - duplicates, duplicates)
+}
+
+/// How many hosts each of `targets` adds to `unique_count`'s total: its
+/// planned hosts, less those an earlier target lists too.
+pub fn contributions(targets: &[Target], args: &ScanArgs, seed: u64) -> Vec<u64> {
+    let probed = ProbedSet::new(targets);
+    targets
+        .iter()
+        .map(|target| planned_count(target, args, seed) - probed.replay(std::slice::from_ref(target), args, seed))
+        .collect()
+}
+
+fn span(mut hosts: ipnet::Ipv4AddrRange) -> Option<(u32, u32)> {
+    let first = u32::from(hosts.next()?);
+    let last = hosts.next_back().map_or(first, u32::from);
+    Some((first, last))
+}
+
+/// The spans covered by more than one target. A target's own blocks never
+/// overlap, so any two spans that do belong to different targets.
+fn overlaps(targets: &[Target]) -> Vec<(u32, u32)> {
+    let mut spans: Vec<(u32, u32)> = targets
+        .iter()
+        .flat_map(|target| target.blocks.iter().filter_map(|block| span(block.hosts())))
+        .collect();
+    spans.sort_unstable();
+    let mut overlaps: Vec<(u32, u32)> = Vec::new();
+    let mut reach: Option<u32> = None;
+    for (first, last) in spans {
+        if let Some(reach) = reach.filter(|reach| first <= *reach) {
+            let overlap = (first, last.min(reach));
+            match overlaps.last_mut() {
+                Some(previous) if overlap.0 <= previous.1.saturating_add(1) => previous.1 = previous.1.max(overlap.1),
+                _ => overlaps.push(overlap),
+            }
+        }
+        reach = Some(reach.map_or(last, |reach| reach.max(last)));
+    }
+    overlaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use crate::targets::{Block, Provenance};
+    use clap::Parser;
+
+    fn target(cidrs: &[&str]) -> Target {
+        let blocks = cidrs.iter().map(|cidr| Block::whole(cidr.parse().unwrap())).collect();
+        Target::new(blocks, Provenance::default())
+    }
+
+    fn args(extra: &[&str]) -> ScanArgs {
+        Cli::parse_from(["public-ollama-finder"].iter().chain(extra)).scan
+    }
+
+    #[test]
+    fn finds_the_spans_targets_share() {
+        let targets = [
+            target(&["10.0.0.0/24"]),
+            target(&["10.0.0.16/28", "10.0.1.0/24"]),
+            target(&["10.0.0.200/32"]),
+            target(&["10.0.2.0/24"]),
+        ];
+        let probed = ProbedSet::new(&targets);
+        let ip = |text: &str| u32::from(text.parse::<Ipv4Addr>().unwrap());
+        assert_eq!(*probed.overlaps.read().unwrap(), [(ip("10.0.0.16"), ip("10.0.0.31")), (ip("10.0.0.200"), ip("10.0.0.200"))]);
+    }
+
+    #[test]
+    fn each_address_is_probed_once() {
+        let probed = ProbedSet::new(&[target(&["10.0.0.0/30"]), target(&["10.0.0.2/31"])]);
+        assert!(probed.first_probe("10.0.0.2".parse().unwrap()));
+        assert!(!probed.first_probe("10.0.0.2".parse().unwrap()));
+        // Outside the overlap nothing is remembered
+        assert!(probed.first_probe("10.0.0.1".parse().unwrap()));
+        assert!(probed.first_probe("10.0.0.1".parse().unwrap()));
+        assert_eq!(probed.pages.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn counts_overlapping_hosts_once() {
+        let targets = [target(&["10.0.0.0/24"]), target(&["10.0.0.128/25"]), target(&["10.0.0.130/32"])];
+        assert_eq!(unique_count(&targets, &args(&[]), 1), (256, 129));
+        assert_eq!(unique_count(&targets, &args(&["--shard", "1/2"]), 1), (128, 65));
+        assert_eq!(unique_count(&targets[..1], &args(&[]), 1), (256, 0));
+    }
+
+    #[test]
+    fn credits_shared_hosts_to_the_first_target() {
+        let targets = [target(&["10.0.0.0/24"]), target(&["10.0.0.128/25"]), target(&["10.0.1.0/30"])];
+        assert_eq!(contributions(&targets, &args(&[]), 1), [256, 0, 4]);
+        assert_eq!(contributions(&targets, &args(&[]), 1).iter().sum::<u64>(), unique_count(&targets, &args(&[]), 1).0);
+    }
+
+    #[test]
+    fn replaying_finished_ranges_skips_their_hosts() {
+        let targets = [target(&["10.0.0.0/30"]), target(&["10.0.0.0/31"])];
+        let probed = ProbedSet::new(&targets);
+        assert_eq!(probed.replay(&targets[..1], &args(&[]), 1), 0);
+        assert!(!probed.first_probe("10.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn appended_targets_skip_what_finished_ranges_reached() {
+        let mut targets = vec![target(&["10.0.0.0/30"]), target(&["10.0.1.0/30"])];
+        let probed = ProbedSet::new(&targets);
+        assert!(probed.first_probe("10.0.0.1".parse().unwrap()));
+        targets.push(target(&["10.0.0.0/31"]));
+        probed.extend(&targets, &targets[..1], &args(&[]), 1);
+        assert!(!probed.first_probe("10.0.0.1".parse().unwrap()));
+        assert!(probed.first_probe("10.0.1.1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,101 @@
+//! `--deep`: which of Ollama's API surfaces a confirmed endpoint exposes.
+
+use std::time::Duration;
+
+use super::Fetcher;
+
+/// Most of each answer read; only the status matters, but a body read to the
+/// end lets the next request reuse the connection
+const MAX_ANSWER_LEN: usize = 64 << 10;
+const CHECK_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// One API surface, and how to ask for it without changing anything.
+struct Capability {
+    /// Its name in the Capabilities column
+    name: &'static str,
+    path: &'static str,
+    /// POSTed an empty JSON object, which Ollama rejects with a 400 before
+    /// doing any work; a 400 or 422 then means the endpoint is there
+    post: bool,
+}
+
+/// Checked in order, one request each, all over the same connection.
+const CAPABILITIES: &[Capability] = &[
+    Capability { name: "tags", path: "/api/tags", post: false },
+    Capability { name: "version", path: "/api/version", post: false },
+    Capability { name: "ps", path: "/api/ps", post: false },
+    Capability { name: "openai", path: "/v1/models", post: false },
+    Capability { name: "embeddings", path: "/api/embeddings", post: true },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exposure {
+    Open,
+    /// Present, but refused without credentials (401/403)
+    Protected,
+    Absent,
+}
+
+impl Exposure {
+    fn from_status(status: Option<u16>, post: bool) -> Self {
+        match status {
+            Some(200..=299) => Exposure::Open,
+            Some(400 | 422) if post => Exposure::Open,
+            Some(401 | 403) => Exposure::Protected,
+            _ => Exposure::Absent,
+        }
+    }
+}
+
+/// The API surfaces the endpoint exposes, e.g. "tags,version,ps:protected,openai".
+/// A surface that is there but refused without credentials gets ":protected".
+pub async fn probe_capabilities(fetcher: &Fetcher<'_>, port: u16) -> String {
+    let mut exposures = Vec::with_capacity(CAPABILITIES.len());
+    for capability in CAPABILITIES {
+        let reply = match capability.post {
+            true => fetcher.post(port, capability.path, &serde_json::json!({}), CHECK_TIMEOUT).await,
+            false => fetcher.get_limited(port, capability.path, MAX_ANSWER_LEN).await,
+        };
+        exposures.push(Exposure::from_status(reply.map(|reply| reply.status), capability.post));
+    }
+    summarize(&exposures)
+}
+
+fn summarize(exposures: &[Exposure]) -> String {
+    CAPABILITIES
+        .iter()
+        .zip(exposures)
+        .filter_map(|(capability, exposure)| match exposure {
+            Exposure::Open => Some(capability.name.to_string()),
+            Exposure::Protected => Some(format!("{}:protected", capability.name)),
+            Exposure::Absent => None,
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_protected_from_absent() {
+        assert_eq!(Exposure::from_status(Some(200), false), Exposure::Open);
+        assert_eq!(Exposure::from_status(Some(401), false), Exposure::Protected);
+        assert_eq!(Exposure::from_status(Some(403), true), Exposure::Protected);
+        assert_eq!(Exposure::from_status(Some(404), false), Exposure::Absent);
+        assert_eq!(Exposure::from_status(None, false), Exposure::Absent);
+        // A rejected empty request only counts where one was sent on purpose
+        assert_eq!(Exposure::from_status(Some(400), true), Exposure::Open);
+        assert_eq!(Exposure::from_status(Some(400), false), Exposure::Absent);
+        assert_eq!(Exposure::from_status(Some(405), true), Exposure::Absent);
+    }
+
+    #[test]
+    fn lists_what_is_there_in_table_order() {
+        use Exposure::*;
+        assert_eq!(summarize(&[Open, Open, Protected, Open, Absent]), "tags,version,ps:protected,openai");
+        assert_eq!(summarize(&[Open, Absent, Absent, Absent, Open]), "tags,embeddings");
+        assert_eq!(summarize(&[Absent; 5]), "");
+    }
+}
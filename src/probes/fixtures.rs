@@ -0,0 +1,18 @@
+//! Response bodies captured from each server, shared by the probes' tests.
+
+pub const LM_STUDIO_MODELS: &str = r#"{"data":[{"id":"qwen2.5-7b-instruct","object":"model","owned_by":"organization_owner"},{"id":"text-embedding-nomic-embed-text-v1.5","object":"model","owned_by":"organization_owner"}],"object":"list"}"#;
+
+// Port 8080, which LocalAI and llama-server share
+pub const LOCALAI_MODELS: &str = r#"{"object":"list","data":[{"id":"llama-3.2-1b-instruct:q4_k_m","object":"model"},{"id":"whisper-1","object":"model"}]}"#;
+pub const LOCALAI_GALLERY: &str = r#"[{"name":"llama-3.2-1b-instruct:q4_k_m","gallery":{"name":"localai"},"installed":true},{"name":"phi-2","gallery":{"name":"localai"},"installed":false},{"name":"whisper-1","gallery":{"name":"localai"},"installed":true}]"#;
+pub const LOCALAI_METRICS: &str = "# HELP localai_api_call Number of API calls\n# TYPE localai_api_call histogram\nlocalai_api_call_count{method=\"POST\",path=\"/v1/chat/completions\"} 3\n";
+pub const LLAMA_MODELS: &str = r#"{"object":"list","data":[{"id":"/models/qwen2.5-0.5b-instruct-q8_0.gguf","object":"model","created":1735000000,"owned_by":"llamacpp","meta":{"vocab_type":2,"n_ctx_train":32768}}]}"#;
+pub const LLAMA_PROPS: &str = r#"{"default_generation_settings":{"n_ctx":4096},"total_slots":1,"model_path":"/models/qwen2.5-0.5b-instruct-q8_0.gguf","chat_template":"","build_info":"b4600-a1b2c3d"}"#;
+pub const GO_APP_METRICS: &str = "# HELP go_goroutines Number of goroutines\ngo_goroutines 12\n";
+
+// Ollama's /api/tags over the years: no details object in 0.1.14, details
+// without parent_model in 0.1.26, and extra fields in later releases
+pub const OLLAMA_0_1_14_TAGS: &str = r#"{"models":[{"name":"llama2:latest","modified_at":"2023-12-07T10:02:33.123456789Z","size":3826793677,"digest":"fe938a131f40e6f6d40083c9f0f430a515233eb2edaa6d72eb85c50d64f2300e"}]}"#;
+pub const OLLAMA_0_1_26_TAGS: &str = r#"{"models":[{"name":"mistral:7b","modified_at":"2024-02-20T14:21:05.2843417+01:00","size":4109865159,"digest":"61e88e884507ba5e06c49b40e6226884b2a16e872382c2b44a42f2d119d804a5","details":{"format":"gguf","family":"llama","families":["llama"],"parameter_size":"7B","quantization_level":"Q4_0"}}]}"#;
+pub const OLLAMA_0_3_TAGS: &str = r#"{"models":[{"name":"llama3.1:8b","model":"llama3.1:8b","modified_at":"2024-08-01T09:14:12.482936511Z","size":4661230766,"digest":"42182419e9508c30c4b1fe55015f06b65f4ca4b9e28a744be55008d21998a093","details":{"parent_model":"","format":"gguf","family":"llama","families":["llama"],"parameter_size":"8.0B","quantization_level":"Q4_0"}}]}"#;
+pub const OLLAMA_0_12_TAGS: &str = r#"{"models":[{"name":"qwen3:4b","model":"qwen3:4b","modified_at":"2025-09-30T11:02:45.61Z","size":2497293918,"digest":"e55aed6fe643f9368b2f48f8aaa56ec787b75765da69f794c0a0c23bfe7c64b2","details":{"parent_model":"","format":"gguf","family":"qwen3","families":["qwen3"],"parameter_size":"4.0B","quantization_level":"Q4_K_M"},"capabilities":["completion","tools","thinking"]},{"name":"gpt-oss:120b-cloud","model":"gpt-oss:120b-cloud","remote_model":"gpt-oss:120b","remote_host":"https://ollama.com:443","modified_at":"2025-09-25T08:00:00Z","size":384,"digest":"569662207105f3a2b2e6b16b47b5d8f2b3d0f45c3c71a1b2a0f2e9d6e1ab1c2d","details":{"parent_model":"","format":"","family":"gptoss","families":["gptoss"],"parameter_size":"116.8B","quantization_level":"MXFP4"}}]}"#;
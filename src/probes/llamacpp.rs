@@ -0,0 +1,116 @@
+//! llama.cpp's llama-server, including ones guarded by `--api-key`.
+
+use async_trait::async_trait;
+
+use super::{model_ids, Details, Fetcher, Probe, ServiceFinding, PROTECTED};
+
+pub struct LlamaCpp;
+
+#[async_trait]
+impl Probe for LlamaCpp {
+    fn name(&self) -> &'static str {
+        "llama.cpp"
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        &[8080]
+    }
+
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        let found = |reply, models, summary| ServiceFinding::new(self.name(), port, "/v1/models", reply, Details::service(models, summary));
+        let props = fetcher.get(port, "/props").await?;
+        if props.status == 401 && is_llama_auth_error(&props.body) {
+            return Some(found(props, Vec::new(), PROTECTED.to_string()));
+        }
+        let (reply, model, build) = match llama_props(&props.body).filter(|_| props.status == 200) {
+            Some(props_info) => (props, props_info.model, props_info.build),
+            None => {
+                // Older builds have no /props but an unmistakable /health
+                let health = fetcher.get(port, "/health").await?;
+                if !is_llama_health(&health.body) {
+                    return None;
+                }
+                (health, None, String::new())
+            }
+        };
+        let models = match model {
+            Some(model) => vec![model],
+            None => match fetcher.get(port, "/v1/models").await {
+                Some(reply) if reply.status == 200 => model_ids(&reply.body),
+                _ => Vec::new(),
+            },
+        };
+        Some(found(reply, models, build))
+    }
+}
+
+pub(super) struct PropsInfo {
+    model: Option<String>,
+    build: String,
+}
+
+/// Recognizes llama-server's `/props`, which no other server shares.
+pub(super) fn llama_props(body: &[u8]) -> Option<PropsInfo> {
+    let props: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let props = props.as_object()?;
+    if !props.contains_key("default_generation_settings") && !props.contains_key("total_slots") {
+        return None;
+    }
+    let model = props
+        .get("model_path")
+        .and_then(|v| v.as_str())
+        .and_then(|path| path.rsplit(['/', '\\']).next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string);
+    let build = props.get("build_info").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    Some(PropsInfo { model, build })
+}
+
+/// The error llama-server answers with when `--api-key` is set.
+fn is_llama_auth_error(body: &[u8]) -> bool {
+    let Ok(reply) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return false;
+    };
+    reply["error"]["type"] == "authentication_error" && reply["error"]["message"] == "Invalid API Key"
+}
+
+/// llama-server's `/health` while a model loads, and in older builds always.
+fn is_llama_health(body: &[u8]) -> bool {
+    let Ok(reply) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return false;
+    };
+    reply.get("slots_idle").is_some()
+        || reply.get("slots_processing").is_some()
+        || (reply["error"]["type"] == "unavailable_error" && reply["error"]["message"] == "Loading model")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_llama_server_props() {
+        let body = r#"{"default_generation_settings":{"n_ctx":4096},"total_slots":1,"model_path":"/models/Meta-Llama-3.1-8B-Instruct-Q4_K_M.gguf","build_info":"b4600-a1b2c3d"}"#;
+        let info = llama_props(body.as_bytes()).unwrap();
+        assert_eq!(info.model.as_deref(), Some("Meta-Llama-3.1-8B-Instruct-Q4_K_M.gguf"));
+        assert_eq!(info.build, "b4600-a1b2c3d");
+
+        let older = r#"{"default_generation_settings":{},"total_slots":4}"#;
+        let info = llama_props(older.as_bytes()).unwrap();
+        assert_eq!((info.model, info.build), (None, String::new()));
+
+        assert!(llama_props(br#"{"status":"ok"}"#).is_none());
+        assert!(llama_props(b"not json").is_none());
+    }
+
+    #[test]
+    fn recognizes_llama_server_health_and_auth_errors() {
+        assert!(is_llama_health(br#"{"status":"ok","slots_idle":1,"slots_processing":0}"#));
+        assert!(is_llama_health(br#"{"error":{"code":503,"message":"Loading model","type":"unavailable_error"}}"#));
+        // A bare status is what half of all health checks return
+        assert!(!is_llama_health(br#"{"status":"ok"}"#));
+
+        assert!(is_llama_auth_error(br#"{"error":{"code":401,"message":"Invalid API Key","type":"authentication_error"}}"#));
+        assert!(!is_llama_auth_error(br#"{"error":"unauthorized"}"#));
+    }
+}
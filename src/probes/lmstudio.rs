@@ -0,0 +1,68 @@
+//! LM Studio's OpenAI-compatible server.
+
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+
+use super::{Details, Fetcher, ModelList, Probe, ServiceFinding};
+
+pub struct LmStudio;
+
+#[async_trait]
+impl Probe for LmStudio {
+    fn name(&self) -> &'static str {
+        "LM Studio"
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        &[1234]
+    }
+
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        let reply = fetcher.get(port, "/v1/models").await.filter(|r| r.status == 200)?;
+        let (service, models) = lm_studio(&reply.headers, &reply.body)?;
+        Some(ServiceFinding::new(service, port, "/v1/models", reply, Details::service(models, "")))
+    }
+}
+
+/// Recognizes LM Studio's model list. Other OpenAI-compatible servers on its
+/// port are still worth recording, just not as LM Studio.
+fn lm_studio(headers: &HeaderMap, body: &[u8]) -> Option<(&'static str, Vec<String>)> {
+    let list = ModelList::parse(body)?;
+    // LM Studio runs on Express and lists its models as owned by "organization_owner"
+    let express = headers
+        .get("x-powered-by")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("express"));
+    let lm_studio_owner = list.data.iter().any(|m| m.owned_by == "organization_owner");
+    let service = if express || lm_studio_owner { "LM Studio" } else { "OpenAI-compatible" };
+    Some((service, list.ids()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probes::fixtures::LM_STUDIO_MODELS;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn recognizes_lm_studio() {
+        let (service, models) = lm_studio(&HeaderMap::new(), LM_STUDIO_MODELS.as_bytes()).unwrap();
+        assert_eq!(service, "LM Studio");
+        assert_eq!(models, ["qwen2.5-7b-instruct", "text-embedding-nomic-embed-text-v1.5"]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-powered-by", HeaderValue::from_static("Express"));
+        let body = r#"{"object":"list","data":[]}"#;
+        assert_eq!(lm_studio(&headers, body.as_bytes()).unwrap().0, "LM Studio");
+    }
+
+    #[test]
+    fn other_model_lists_are_generic_and_anything_else_is_rejected() {
+        let vllm = r#"{"object":"list","data":[{"id":"meta-llama/Llama-3.1-8B","object":"model","owned_by":"vllm"}]}"#;
+        assert_eq!(lm_studio(&HeaderMap::new(), vllm.as_bytes()).unwrap().0, "OpenAI-compatible");
+
+        for body in ["<html>It works!</html>", r#"{"models":[]}"#, r#"{"object":"error","data":[]}"#] {
+            assert_eq!(lm_studio(&HeaderMap::new(), body.as_bytes()), None, "{}", body);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! LocalAI, which shares port 8080 and the model list format with llama-server.
+
+use async_trait::async_trait;
+
+use super::llamacpp::llama_props;
+use super::{cap_model_id, Details, Fetcher, ModelList, Probe, ServiceFinding};
+
+pub struct LocalAi;
+
+#[async_trait]
+impl Probe for LocalAi {
+    fn name(&self) -> &'static str {
+        "LocalAI"
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        &[8080]
+    }
+
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        let listing = fetcher.get(port, "/v1/models").await.filter(|r| r.status == 200);
+        let readyz = fetcher.get(port, "/readyz").await.filter(|r| r.status == 200 && r.body.trim_ascii() == b"OK");
+        let mut evidence = LocalAiEvidence {
+            models: listing.as_ref().and_then(|reply| ModelList::parse(&reply.body)),
+            ready: readyz.is_some(),
+            ..LocalAiEvidence::default()
+        };
+        if evidence.models.is_none() && !evidence.ready {
+            return None;
+        }
+        evidence.llama_props = matches!(fetcher.get(port, "/props").await, Some(r) if r.status == 200 && llama_props(&r.body).is_some());
+        if evidence.ready && evidence.models.is_none() && !evidence.llama_props {
+            evidence.metrics = matches!(fetcher.get(port, "/metrics").await, Some(r) if r.status == 200 && is_local_ai_metrics(&r.body));
+        }
+        let service = local_ai_verdict(&evidence)?;
+
+        let summary = match service {
+            "LocalAI" => match fetcher.get(port, "/models/available").await {
+                Some(reply) if reply.status == 200 => local_ai_gallery(&reply.body).unwrap_or_default(),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        };
+        // Recorded by the model list if there is one, else by /readyz; both answered 200
+        let reply = if evidence.models.is_some() { listing } else { readyz }?;
+        let models = evidence.models.map(ModelList::ids).unwrap_or_default();
+        Some(ServiceFinding::new(service, port, "/v1/models", reply, Details::service(models, summary)))
+    }
+}
+
+/// What port 8080 answered on the endpoints that tell LocalAI apart.
+#[derive(Debug, Default)]
+struct LocalAiEvidence {
+    models: Option<ModelList>,
+    /// `/readyz` answered 200 "OK"
+    ready: bool,
+    /// `/metrics` exports LocalAI's own series
+    metrics: bool,
+    /// `/props` answered like llama-server
+    llama_props: bool,
+}
+
+/// The Service label port 8080's evidence supports, if any. llama.cpp shares
+/// the port and the model list format, so LocalAI needs `/readyz` plus a
+/// second signal, and anything with llama-server's `/props` is left to the
+/// llama.cpp probe.
+fn local_ai_verdict(evidence: &LocalAiEvidence) -> Option<&'static str> {
+    if evidence.llama_props {
+        return None;
+    }
+    match (evidence.ready, evidence.models.is_some(), evidence.metrics) {
+        (true, true, _) | (true, _, true) => Some("LocalAI"),
+        (_, true, _) => Some("OpenAI-compatible"),
+        _ => None,
+    }
+}
+
+/// Prometheus text with LocalAI's `localai_` metric prefix.
+fn is_local_ai_metrics(body: &[u8]) -> bool {
+    String::from_utf8_lossy(body)
+        .lines()
+        .any(|line| line.starts_with("localai_") || line.starts_with("# HELP localai_"))
+}
+
+/// Summarizes LocalAI's model gallery (`/models/available`): how many models
+/// it offers and which of them are installed.
+fn local_ai_gallery(body: &[u8]) -> Option<String> {
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(body).ok()?;
+    let installed: Vec<String> = entries
+        .iter()
+        .filter(|entry| entry["installed"] == true)
+        .filter_map(|entry| entry["name"].as_str())
+        .map(|name| cap_model_id(name.to_string()))
+        .collect();
+    let mut summary = format!("gallery: {} available, {} installed", entries.len(), installed.len());
+    if !installed.is_empty() {
+        summary.push_str(&format!(" ({})", installed.join(", ")));
+    }
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probes::fixtures::*;
+
+    fn evidence(models: Option<&str>, ready: bool, metrics: Option<&str>, props: Option<&str>) -> LocalAiEvidence {
+        LocalAiEvidence {
+            models: models.map(|body| serde_json::from_str(body).unwrap()),
+            ready,
+            metrics: metrics.is_some_and(|body| is_local_ai_metrics(body.as_bytes())),
+            llama_props: props.is_some_and(|body| llama_props(body.as_bytes()).is_some()),
+        }
+    }
+
+    #[test]
+    fn tells_local_ai_from_llama_server() {
+        assert_eq!(local_ai_verdict(&evidence(Some(LOCALAI_MODELS), true, None, None)), Some("LocalAI"));
+        assert_eq!(local_ai_verdict(&evidence(None, true, Some(LOCALAI_METRICS), None)), Some("LocalAI"));
+        // llama-server has no /readyz and identifies itself through /props
+        assert_eq!(local_ai_verdict(&evidence(Some(LLAMA_MODELS), false, None, Some(LLAMA_PROPS))), None);
+        assert_eq!(local_ai_verdict(&evidence(Some(LLAMA_MODELS), true, None, Some(LLAMA_PROPS))), None);
+        // A model list alone, or a bare readiness check, is not enough to say LocalAI
+        assert_eq!(local_ai_verdict(&evidence(Some(LOCALAI_MODELS), false, None, None)), Some("OpenAI-compatible"));
+        assert_eq!(local_ai_verdict(&evidence(None, true, Some(GO_APP_METRICS), None)), None);
+        assert_eq!(local_ai_verdict(&evidence(None, false, None, None)), None);
+    }
+
+    #[test]
+    fn summarizes_the_local_ai_gallery() {
+        assert_eq!(
+            local_ai_gallery(LOCALAI_GALLERY.as_bytes()).as_deref(),
+            Some("gallery: 3 available, 2 installed (llama-3.2-1b-instruct:q4_k_m, whisper-1)")
+        );
+        assert_eq!(local_ai_gallery(b"[]").as_deref(), Some("gallery: 0 available, 0 installed"));
+        assert_eq!(local_ai_gallery(LOCALAI_MODELS.as_bytes()), None);
+    }
+}
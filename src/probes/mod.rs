@@ -0,0 +1,459 @@
+//! What the scan looks for on each host. Every server it recognizes has a
+//! `Probe` in its own module; `registry` lists the ones a scan runs, and
+//! `Fetcher` sends their requests through the scan's rate limits.
+
+mod capabilities;
+mod llamacpp;
+mod lmstudio;
+mod localai;
+mod ollama;
+mod openwebui;
+mod textgen;
+mod vllm;
+
+#[cfg(test)]
+mod fixtures;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::concurrency::{ConcurrencyController, Outcome};
+use crate::errors::{classify, ErrorCategory};
+use crate::ratelimit::{AsnLimiter, BandwidthMeter, SubnetLimiter, TokenBucket};
+use crate::stats::ScanStats;
+use crate::Model;
+
+pub use capabilities::probe_capabilities;
+pub use ollama::{check_embeddings, check_write_access, Verification, WriteAccess};
+pub use openwebui::OPEN_WEBUI;
+
+pub const OTHER_ENDPOINTS_CSV: &str = "other_llm_endpoints.csv";
+
+/// Longest model ID kept; anything past it is cut off with "…"
+const MAX_MODEL_ID_LEN: usize = 200;
+
+const PROTECTED: &str = "protected (API key required)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum ExtraService {
+    /// LM Studio's OpenAI-compatible server on port 1234
+    #[value(name = "lmstudio")]
+    LmStudio,
+    /// llama.cpp's llama-server on port 8080
+    #[value(name = "llamacpp")]
+    LlamaCpp,
+    /// vLLM's OpenAI-compatible server on port 8000
+    #[value(name = "vllm")]
+    Vllm,
+    /// text-generation-webui's API on port 5000 and Gradio UI on 7860
+    #[value(name = "textgen")]
+    TextGen,
+    /// LocalAI on port 8080
+    #[value(name = "localai")]
+    LocalAi,
+    /// Open WebUI on port 3000 or 8080
+    #[value(name = "openwebui")]
+    OpenWebUi,
+}
+
+/// The Ollama ports `--port-profile` sweeps on each host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PortProfile {
+    /// Only 11434
+    #[default]
+    Default,
+    /// 11434, then a second instance's 11435, then 8080 and 80 behind proxies
+    Common,
+}
+
+impl PortProfile {
+    /// Ports in the order they are tried. Only plain HTTP is probed, so
+    /// proxies on 443 are not in any profile.
+    pub fn ports(self) -> &'static [u16] {
+        match self {
+            PortProfile::Default => &[crate::OLLAMA_PORT],
+            PortProfile::Common => &[crate::OLLAMA_PORT, 11435, 8080, 80],
+        }
+    }
+}
+
+/// Recognizes one kind of server.
+#[async_trait]
+pub trait Probe: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Ports `probe` is called with, in order
+    fn default_ports(&self) -> &'static [u16];
+
+    /// Whether this probe's request is the one the scan dispatches each host
+    /// for: the first port's is already paid for in the rate limits and
+    /// counts as the host's attempt, and each port's counts toward the
+    /// probes, errors and concurrency feedback. Only Ollama's is.
+    fn accounted(&self) -> bool {
+        false
+    }
+
+    /// Checks whether `port` of the fetcher's host runs this server.
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding>;
+}
+
+/// The probe behind each `--also` value.
+fn extra_probe(service: ExtraService) -> Box<dyn Probe> {
+    match service {
+        ExtraService::LmStudio => Box::new(lmstudio::LmStudio),
+        ExtraService::LlamaCpp => Box::new(llamacpp::LlamaCpp),
+        ExtraService::Vllm => Box::new(vllm::Vllm),
+        ExtraService::TextGen => Box::new(textgen::TextGen),
+        ExtraService::LocalAi => Box::new(localai::LocalAi),
+        ExtraService::OpenWebUi => Box::new(openwebui::OpenWebUi),
+    }
+}
+
+/// The probes run on every host: the `--also` services first, so an Ollama
+/// row can name the Open WebUI in front of it, then Ollama on the ports of
+/// `ports`.
+pub fn registry(also: &[ExtraService], verification: Verification, ports: PortProfile) -> Vec<Box<dyn Probe>> {
+    also.iter()
+        .map(|service| extra_probe(*service))
+        .chain([Box::new(ollama::Ollama { verification, ports: ports.ports() }) as Box<dyn Probe>])
+        .collect()
+}
+
+/// A server recognized by a probe.
+#[derive(Debug, Clone)]
+pub struct ServiceFinding {
+    /// Written to the Service column
+    pub service: &'static str,
+    pub port: u16,
+    /// The model listing, or the page the server was recognized by
+    pub path: &'static str,
+    /// Status of the response the server was recognized by
+    pub status: u16,
+    /// Headers of that response
+    pub headers: HeaderMap,
+    /// Length of its body as read, at most `--max-body-size`
+    pub body_len: usize,
+    pub details: Details,
+}
+
+impl ServiceFinding {
+    fn new(service: &'static str, port: u16, path: &'static str, reply: Reply, details: Details) -> Self {
+        Self { service, port, path, status: reply.status, headers: reply.headers, body_len: reply.body.len(), details }
+    }
+}
+
+/// What a finding is recorded as.
+#[derive(Debug, Clone)]
+pub enum Details {
+    /// An Ollama `/api/tags` answer; `models` is `None` unless it was a model list
+    Ollama {
+        models: Option<Vec<Model>>,
+        /// Model fields this release left out, which were filled in empty
+        missing: Vec<String>,
+        latency: Duration,
+    },
+    /// An `/api/tags` answer that did not pass for Ollama
+    Unverified {
+        reason: String,
+        /// The start of the body, for a human to judge
+        body: String,
+    },
+    /// A 429 from `/api/tags`, with the wait its Retry-After asked for
+    Throttled {
+        retry_after: Option<Duration>,
+    },
+    /// A row of the other-endpoints CSV
+    Service {
+        models: Vec<String>,
+        /// Build info, or why nothing more is known
+        summary: String,
+    },
+}
+
+impl Details {
+    fn service(models: Vec<String>, summary: impl Into<String>) -> Self {
+        Details::Service { models, summary: summary.into() }
+    }
+}
+
+struct Reply {
+    status: u16,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    /// Until the response headers arrived
+    latency: Duration,
+}
+
+/// Where an accounted probe's request is tallied.
+pub struct Accounting<'a> {
+    pub concurrency: &'a ConcurrencyController,
+    pub stats: &'a ScanStats,
+    /// Whether the dispatcher took the request's rate tokens, which it does
+    /// for the host's first port only
+    pub prepaid: bool,
+}
+
+/// Sends the requests of one host's probe on one port, each through the
+/// scan's rate limits.
+pub struct Fetcher<'a> {
+    client: &'a reqwest::Client,
+    rate_limiter: &'a TokenBucket,
+    subnet_limiter: Option<&'a SubnetLimiter>,
+    asn_limiter: Option<&'a AsnLimiter>,
+    /// Counts the bytes of every answer
+    bandwidth: &'a BandwidthMeter,
+    ip: Ipv4Addr,
+    /// Limit for a whole GET, from connecting to the last body byte
+    timeout: Duration,
+    /// Longest answer read in full; see `read_body_capped`
+    max_body: usize,
+    /// Set for an accounted probe, whose first request is the dispatched one;
+    /// see `Probe::accounted`
+    accounting: Option<Accounting<'a>>,
+    dispatched: AtomicBool,
+    /// How the accounted request failed, if it did
+    failure: Mutex<Option<ErrorCategory>>,
+}
+
+impl<'a> Fetcher<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        client: &'a reqwest::Client,
+        rate_limiter: &'a TokenBucket,
+        subnet_limiter: Option<&'a SubnetLimiter>,
+        asn_limiter: Option<&'a AsnLimiter>,
+        bandwidth: &'a BandwidthMeter,
+        ip: Ipv4Addr,
+        timeout: Duration,
+        max_body: usize,
+        accounting: Option<Accounting<'a>>,
+    ) -> Self {
+        Self {
+            client,
+            rate_limiter,
+            subnet_limiter,
+            asn_limiter,
+            bandwidth,
+            ip,
+            timeout,
+            max_body,
+            accounting,
+            dispatched: AtomicBool::new(false),
+            failure: Mutex::new(None),
+        }
+    }
+
+    /// How the accounted request failed, or None if it got an answer or was
+    /// not sent.
+    pub fn failure(&self) -> Option<ErrorCategory> {
+        *self.failure.lock().unwrap()
+    }
+
+    /// GETs `path`, giving up on an answer longer than `--max-body-size`.
+    async fn get(&self, port: u16, path: &str) -> Option<Reply> {
+        let url = format!("http://{}:{}{}", self.ip, port, path);
+        self.send(self.client.get(&url), None, self.timeout).await
+    }
+
+    /// Like `get`, but stops reading the body after `max_body` bytes and
+    /// keeps what it has.
+    async fn get_limited(&self, port: u16, path: &str, max_body: usize) -> Option<Reply> {
+        let url = format!("http://{}:{}{}", self.ip, port, path);
+        self.send(self.client.get(&url), Some(max_body), self.timeout).await
+    }
+
+    /// POSTs `body` as JSON, giving up after `timeout`.
+    async fn post(&self, port: u16, path: &str, body: &serde_json::Value, timeout: Duration) -> Option<Reply> {
+        let url = format!("http://{}:{}{}", self.ip, port, path);
+        self.send(self.client.post(&url).json(body), None, timeout).await
+    }
+
+    /// Sends `request` and reads its answer: cut off after `truncate_at`
+    /// bytes if given, and otherwise in full unless it passes the cap.
+    async fn send(&self, request: reqwest::RequestBuilder, truncate_at: Option<usize>, timeout: Duration) -> Option<Reply> {
+        let accounting = self.accounting.as_ref().filter(|_| !self.dispatched.swap(true, Ordering::Relaxed));
+        if let Some(accounting) = accounting {
+            accounting.stats.record_probe();
+        }
+        // The dispatcher took this request's rate tokens before spawning the host
+        if !accounting.is_some_and(|accounting| accounting.prepaid) {
+            self.rate_limiter.acquire().await;
+            if let Some(limiter) = self.subnet_limiter {
+                limiter.acquire(self.ip).await;
+            }
+            if let Some(limiter) = self.asn_limiter {
+                limiter.acquire(self.ip).await;
+            }
+        }
+        let started = Instant::now();
+        let response = request.timeout(timeout).send().await;
+        if let Some(accounting) = accounting {
+            accounting.concurrency.record(match &response {
+                Ok(_) => Outcome::Responded,
+                Err(e) => Outcome::from_error(e),
+            });
+            if crate::PAUSE_SCAN.load(Ordering::Relaxed) {
+                accounting.stats.record_finished_while_pausing();
+            }
+            if let Err(e) = &response {
+                accounting.stats.record_error(&self.ip.to_string(), e);
+                *self.failure.lock().unwrap() = Some(classify(e));
+            }
+        }
+        let response = response.ok()?;
+        let latency = started.elapsed();
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let head_len = head_len(&headers);
+        let body = match truncate_at {
+            Some(max_body) => read_body(response, max_body.min(self.max_body)).await,
+            None => match read_body_capped(response, self.max_body).await {
+                Ok(body) => body,
+                Err(reason) => {
+                    // What was read of the body before giving up is not known here
+                    self.bandwidth.record(head_len);
+                    if let Some(accounting) = accounting {
+                        accounting.stats.record_oversized(&self.ip.to_string(), || reason);
+                        *self.failure.lock().unwrap() = Some(ErrorCategory::Oversized);
+                    }
+                    return None;
+                }
+            },
+        };
+        self.bandwidth.record(head_len + body.len() as u64);
+        Some(Reply { status, headers, body, latency })
+    }
+}
+
+/// Approximate size of a response's status line and headers as sent.
+fn head_len(headers: &HeaderMap) -> u64 {
+    // "HTTP/1.1 200 OK\r\n", then "name: value\r\n" each, then "\r\n"
+    let fields: usize = headers.iter().map(|(name, value)| name.as_str().len() + value.len() + 4).sum();
+    (17 + fields + 2) as u64
+}
+
+/// Reads a whole response body unless it is longer than `cap` bytes. A
+/// Content-Length over the cap is turned down without reading anything;
+/// otherwise reading stops as soon as the body passes it. The error says
+/// which.
+pub async fn read_body_capped(mut response: reqwest::Response, cap: usize) -> Result<Vec<u8>, String> {
+    if let Some(length) = response.content_length().filter(|length| *length > cap as u64) {
+        return Err(format!("Content-Length {} is over the {}-byte limit", length, cap));
+    }
+    let mut body = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) if body.len() + chunk.len() > cap => {
+                return Err(format!("body grew past the {}-byte limit", cap));
+            }
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => return Ok(body),
+        }
+    }
+}
+
+/// Reads at most `max_body` bytes of a response body. A body cut short is
+/// parsed as far as it got, which for JSON means not at all.
+pub async fn read_body(mut response: reqwest::Response, max_body: usize) -> Vec<u8> {
+    let mut body = Vec::new();
+    while body.len() < max_body {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    body.truncate(max_body);
+    body
+}
+
+/// An OpenAI-style `GET /v1/models` response.
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    object: String,
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+    #[serde(default)]
+    owned_by: String,
+    /// Only vLLM reports this
+    max_model_len: Option<u64>,
+}
+
+impl ModelList {
+    /// Parses an OpenAI-style model list, rejecting other JSON with a `data` array.
+    fn parse(body: &[u8]) -> Option<Self> {
+        serde_json::from_slice::<ModelList>(body).ok().filter(|list| list.object == "list")
+    }
+
+    fn ids(self) -> Vec<String> {
+        self.data.into_iter().map(|m| cap_model_id(m.id)).collect()
+    }
+}
+
+fn cap_model_id(id: String) -> String {
+    match id.char_indices().nth(MAX_MODEL_ID_LEN) {
+        Some((cut, _)) => format!("{}…", &id[..cut]),
+        None => id,
+    }
+}
+
+fn model_ids(body: &[u8]) -> Vec<String> {
+    serde_json::from_slice::<ModelList>(body).map(ModelList::ids).unwrap_or_default()
+}
+
+/// The wait a Retry-After header asks for, given as seconds or as an HTTP
+/// date; a date in the past asks for none.
+fn retry_after(headers: &HeaderMap, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&chrono::Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_long_model_ids() {
+        let long = "m".repeat(10_000);
+        let body = format!(r#"{{"object":"list","data":[{{"id":"{}"}},{{"id":"short"}}]}}"#, long);
+        let ids = model_ids(body.as_bytes());
+        assert_eq!(ids[0], format!("{}…", "m".repeat(MAX_MODEL_ID_LEN)));
+        assert_eq!(ids[1], "short");
+    }
+
+    #[test]
+    fn reads_retry_after_as_seconds_or_a_date() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap().with_timezone(&chrono::Utc);
+        let header = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+            retry_after(&headers, now)
+        };
+        assert_eq!(header("120"), Some(Duration::from_secs(120)));
+        assert_eq!(header("Wed, 21 Oct 2015 07:28:05 GMT"), Some(Duration::from_secs(5)));
+        assert_eq!(header("Wed, 21 Oct 2015 07:27:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(header("soon"), None);
+        assert_eq!(retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn ollama_runs_last_after_the_extra_services() {
+        let names = |also: &[ExtraService]| registry(also, Verification::Checks, PortProfile::Default).iter().map(|p| p.name()).collect::<Vec<_>>();
+        assert_eq!(names(&[]), ["Ollama"]);
+        assert_eq!(names(&[ExtraService::Vllm, ExtraService::OpenWebUi]), ["vLLM", OPEN_WEBUI, "Ollama"]);
+        assert!(registry(&[ExtraService::TextGen], Verification::Checks, PortProfile::Common).iter().all(|p| p.accounted() == (p.name() == "Ollama")));
+    }
+}
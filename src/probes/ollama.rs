@@ -0,0 +1,384 @@
+//! Ollama's `/api/tags`, the probe every scan runs.
+
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use super::{cap_model_id, retry_after, Details, Fetcher, Probe, ServiceFinding};
+use crate::{Model, TagsResponse, OLLAMA_PORT};
+
+const TAGS_PATH: &str = "/api/tags";
+/// An embedding model that is not loaded yet takes a few seconds to answer
+const EMBED_TIMEOUT: Duration = Duration::from_secs(3);
+/// The whole input of an embeddings check; nothing else is ever sent
+const EMBED_INPUT: &str = "hello";
+/// Model names that are embedding models, without tag or namespace
+const EMBEDDING_MODELS: [&str; 4] = ["nomic-embed-text", "mxbai-embed", "bge", "all-minilm"];
+/// Most of an unverified body kept for the CSV
+const MAX_SNIPPET_LEN: usize = 200;
+
+/// Fields of a listed model that some Ollama releases leave out
+const MODEL_FIELDS: [&str; 5] = ["model", "modified_at", "size", "digest", "details"];
+const DETAIL_FIELDS: [&str; 5] = ["parent_model", "format", "family", "parameter_size", "quantization_level"];
+
+/// How sure a 200 from `/api/tags` has to be Ollama before it counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// `--no-verify`: any 200 is a hit
+    Off,
+    /// The model list has to look like Ollama's
+    Checks,
+    /// `--strict`: `/api/version` has to answer too
+    Strict,
+}
+
+/// Whether an endpoint takes model changes, from `--check-write-access`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteAccess {
+    /// `/api/copy` got as far as checking the request
+    Open,
+    /// Refused (401/403) or not routed (404/405), usually by a proxy
+    Blocked,
+    /// No answer, or one that says neither
+    Unknown,
+}
+
+impl WriteAccess {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WriteAccess::Open => "open",
+            WriteAccess::Blocked => "blocked",
+            WriteAccess::Unknown => "unknown",
+        }
+    }
+
+    fn from_status(status: Option<u16>) -> Self {
+        match status {
+            Some(400 | 422) => WriteAccess::Open,
+            Some(401 | 403 | 404 | 405) => WriteAccess::Blocked,
+            _ => WriteAccess::Unknown,
+        }
+    }
+}
+
+/// Asks `/api/copy` to copy nothing to nowhere. Ollama rejects that with a
+/// 400 before touching any model, so the answer only shows whether model
+/// changes get through to it.
+pub async fn check_write_access(fetcher: &Fetcher<'_>, port: u16) -> WriteAccess {
+    let reply = fetcher.post(port, "/api/copy", &serde_json::json!({}), fetcher.timeout).await;
+    WriteAccess::from_status(reply.map(|reply| reply.status))
+}
+
+/// What embedding one word with an endpoint's smallest embedding model gave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingCheck {
+    pub model: String,
+    /// Length of the vector, `None` if the request failed
+    pub dimension: Option<usize>,
+}
+
+/// Embeds one fixed word with the smallest of `models` that is a known
+/// embedding model, via `/api/embed` or, on releases before it, the older
+/// `/api/embeddings`. `None` without such a model, in which case nothing is sent.
+pub async fn check_embeddings(fetcher: &Fetcher<'_>, port: u16, models: &[Model]) -> Option<EmbeddingCheck> {
+    let model = smallest_embedding_model(models)?.to_string();
+    let body = serde_json::json!({ "model": model, "input": EMBED_INPUT });
+    let dimension = match fetcher.post(port, "/api/embed", &body, EMBED_TIMEOUT).await {
+        Some(reply) if reply.status == 404 => {
+            let body = serde_json::json!({ "model": model, "prompt": EMBED_INPUT });
+            let reply = fetcher.post(port, "/api/embeddings", &body, EMBED_TIMEOUT).await;
+            reply.filter(|reply| reply.status == 200).and_then(|reply| embedding_dimension(&reply.body))
+        }
+        Some(reply) if reply.status == 200 => embedding_dimension(&reply.body),
+        _ => None,
+    };
+    Some(EmbeddingCheck { model, dimension })
+}
+
+fn smallest_embedding_model(models: &[Model]) -> Option<&str> {
+    let is_embedding = |name: &str| {
+        let base = name.split(':').next().unwrap_or_default();
+        let base = base.rsplit('/').next().unwrap_or_default();
+        EMBEDDING_MODELS.iter().any(|known| base.starts_with(known))
+    };
+    models
+        .iter()
+        .filter(|m| is_embedding(&m.name))
+        .min_by(|a, b| a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)))
+        .map(|m| m.name.as_str())
+}
+
+/// The vector length in an `/api/embed` (`embeddings`, one per input) or
+/// `/api/embeddings` (`embedding`) answer.
+fn embedding_dimension(body: &[u8]) -> Option<usize> {
+    let reply: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let vector = match reply.get("embeddings") {
+        Some(embeddings) => embeddings.get(0)?,
+        None => reply.get("embedding")?,
+    };
+    let vector = vector.as_array()?;
+    (!vector.is_empty() && vector.iter().all(serde_json::Value::is_number)).then_some(vector.len())
+}
+
+pub struct Ollama {
+    pub verification: Verification,
+    /// From `--port-profile`
+    pub ports: &'static [u16],
+}
+
+#[async_trait]
+impl Probe for Ollama {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        self.ports
+    }
+
+    fn accounted(&self) -> bool {
+        true
+    }
+
+    /// A 200 is a hit once it passes verification, and without verification
+    /// even when its body is not a model list; 401, 403, 404 and redirects
+    /// are possible servers, reported as `--report-status` says.
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        // The whole model list is read, up to --max-body-size
+        let reply = fetcher.get(port, TAGS_PATH).await?;
+        let (models, missing) = match reply.status {
+            200 => {
+                let parsed = parse_tags(&reply.body);
+                if let Some(reason) = self.verify(parsed.as_ref().map(|(models, _)| models.as_slice()), fetcher, port).await {
+                    let body = snippet(&reply.body);
+                    return Some(ServiceFinding::new(self.name(), port, TAGS_PATH, reply, Details::Unverified { reason, body }));
+                }
+                parsed.unzip()
+            }
+            // Any web server on 80 or 8080 answers these, so only Ollama's own port counts them
+            301 | 302 | 303 | 307 | 308 | 401 | 403 | 404 if port == OLLAMA_PORT => (None, None),
+            429 => {
+                let retry_after = retry_after(&reply.headers, chrono::Utc::now());
+                return Some(ServiceFinding::new(self.name(), port, TAGS_PATH, reply, Details::Throttled { retry_after }));
+            }
+            _ => return None,
+        };
+        let details = Details::Ollama { models, missing: missing.unwrap_or_default(), latency: reply.latency };
+        Some(ServiceFinding::new(self.name(), port, TAGS_PATH, reply, details))
+    }
+}
+
+impl Ollama {
+    /// Why a 200 from `/api/tags` is not taken for Ollama, if it is not.
+    async fn verify(&self, models: Option<&[Model]>, fetcher: &Fetcher<'_>, port: u16) -> Option<String> {
+        if self.verification == Verification::Off {
+            return None;
+        }
+        let Some(models) = models else {
+            return Some("not an Ollama model list".to_string());
+        };
+        if let Err(reason) = check_models(models) {
+            return Some(reason);
+        }
+        if self.verification == Verification::Strict {
+            return match fetcher.get(port, "/api/version").await {
+                Some(reply) if reply.status == 200 && ollama_version(&reply.body).is_some() => None,
+                Some(reply) if reply.status == 200 => Some("/api/version is not Ollama's".to_string()),
+                _ => Some("no /api/version".to_string()),
+            };
+        }
+        None
+    }
+}
+
+/// Checks that every model carries a sha256 digest and a timestamp the way
+/// Ollama writes them, which hand-written fakes rarely get right.
+fn check_models(models: &[Model]) -> Result<(), String> {
+    for model in models {
+        let name = cap_model_id(model.name.clone());
+        if !is_sha256(&model.digest) {
+            return Err(format!("digest of {:?} is not a sha256", name));
+        }
+        if chrono::DateTime::parse_from_rfc3339(&model.modified_at).is_err() {
+            return Err(format!("modified_at of {:?} is not a timestamp", name));
+        }
+    }
+    Ok(())
+}
+
+fn is_sha256(digest: &str) -> bool {
+    let hex = digest.strip_prefix("sha256:").unwrap_or(digest);
+    hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// The version in Ollama's `/api/version` reply, `{"version": "0.3.12"}`.
+fn ollama_version(body: &[u8]) -> Option<String> {
+    let reply: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let version = reply.get("version")?.as_str()?;
+    version.starts_with(|c: char| c.is_ascii_digit()).then(|| version.chars().take(32).collect())
+}
+
+/// The start of a body, on one line.
+fn snippet(body: &[u8]) -> String {
+    let text: String = String::from_utf8_lossy(body)
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    match text.char_indices().nth(MAX_SNIPPET_LEN) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+/// Parses `/api/tags` from any Ollama release, ignoring fields it does not
+/// know. Also returns the fields that were absent from at least one model,
+/// e.g. "details" or "details.parent_model", and so were left empty.
+fn parse_tags(body: &[u8]) -> Option<(Vec<Model>, Vec<String>)> {
+    let tags: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let mut missing = BTreeSet::new();
+    for model in tags.get("models")?.as_array()? {
+        missing.extend(MODEL_FIELDS.iter().filter(|field| model.get(field).is_none()).map(|field| field.to_string()));
+        if let Some(details) = model.get("details") {
+            missing.extend(
+                DETAIL_FIELDS
+                    .iter()
+                    .filter(|field| details.get(field).is_none())
+                    .map(|field| format!("details.{}", field)),
+            );
+        }
+    }
+    let tags: TagsResponse = serde_json::from_value(tags).ok()?;
+    Some((tags.models, missing.into_iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probes::fixtures::*;
+
+    #[test]
+    fn reads_current_releases_without_filling_anything_in() {
+        let (models, missing) = parse_tags(OLLAMA_0_3_TAGS.as_bytes()).unwrap();
+        assert!(missing.is_empty(), "{:?}", missing);
+        assert_eq!(models[0].name, "llama3.1:8b");
+        assert_eq!(models[0].details.parameter_size, "8.0B");
+        assert_eq!(models[0].size, 4_661_230_766);
+    }
+
+    #[test]
+    fn ignores_fields_it_does_not_know() {
+        let (models, missing) = parse_tags(OLLAMA_0_12_TAGS.as_bytes()).unwrap();
+        assert!(missing.is_empty(), "{:?}", missing);
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[1].name, "gpt-oss:120b-cloud");
+        assert_eq!(models[1].details.quantization_level, "MXFP4");
+    }
+
+    #[test]
+    fn fills_in_what_older_releases_leave_out() {
+        let (models, missing) = parse_tags(OLLAMA_0_1_14_TAGS.as_bytes()).unwrap();
+        assert_eq!(missing, ["details", "model"]);
+        assert_eq!(models[0].name, "llama2:latest");
+        assert_eq!(models[0].digest, "fe938a131f40e6f6d40083c9f0f430a515233eb2edaa6d72eb85c50d64f2300e");
+        assert_eq!((models[0].model.as_str(), models[0].details.family.as_str()), ("", ""));
+
+        let (models, missing) = parse_tags(OLLAMA_0_1_26_TAGS.as_bytes()).unwrap();
+        assert_eq!(missing, ["details.parent_model", "model"]);
+        assert_eq!(models[0].details.family, "llama");
+        assert_eq!(models[0].details.parent_model, "");
+    }
+
+    fn models(body: &str) -> Vec<Model> {
+        parse_tags(body.as_bytes()).unwrap().0
+    }
+
+    #[test]
+    fn real_model_lists_pass_verification() {
+        for body in [OLLAMA_0_1_14_TAGS, OLLAMA_0_1_26_TAGS, OLLAMA_0_3_TAGS, OLLAMA_0_12_TAGS, r#"{"models":[]}"#] {
+            assert_eq!(check_models(&models(body)), Ok(()), "{}", body);
+        }
+    }
+
+    #[test]
+    fn crafted_fakes_fail_verification() {
+        let fakes = [
+            // A honeypot that copies the shape but invents the values
+            (r#"{"models":[{"name":"llama3:8b","modified_at":"2024-05-01T10:00:00Z","size":1,"digest":"abc123"}]}"#, "digest"),
+            (r#"{"models":[{"name":"gpt-4","modified_at":"2024-05-01T10:00:00Z","size":1,"digest":"sha256:ZZ38a131f40e6f6d40083c9f0f430a515233eb2edaa6d72eb85c50d64f2300e"}]}"#, "digest"),
+            // An unrelated API whose models carry nothing but a name
+            (r#"{"models":[{"name":"resnet50","version":2}]}"#, "digest"),
+            (r#"{"models":[{"name":"llama2","modified_at":"yesterday","digest":"fe938a131f40e6f6d40083c9f0f430a515233eb2edaa6d72eb85c50d64f2300e"}]}"#, "modified_at"),
+            (r#"{"models":[{"name":"llama2","modified_at":1701943353,"digest":"fe938a131f40e6f6d40083c9f0f430a515233eb2edaa6d72eb85c50d64f2300e"}]}"#, "modified_at"),
+        ];
+        for (body, check) in fakes {
+            let reason = parse_tags(body.as_bytes()).map(|(models, _)| check_models(&models));
+            match reason {
+                Some(Err(reason)) => assert!(reason.starts_with(check), "{}: {}", body, reason),
+                // Not even a model list, which fails before the checks
+                None => assert_eq!(check, "modified_at", "{}", body),
+                Some(Ok(())) => panic!("accepted {}", body),
+            }
+        }
+        assert_eq!(ollama_version(br#"{"version":"0.3.12"}"#).as_deref(), Some("0.3.12"));
+        assert_eq!(ollama_version(br#"{"version":"v2-beta"}"#), None);
+        assert_eq!(ollama_version(b"<html>"), None);
+    }
+
+    #[test]
+    fn reads_write_access_from_the_status() {
+        for (status, access) in [
+            (Some(400), WriteAccess::Open),
+            (Some(422), WriteAccess::Open),
+            (Some(401), WriteAccess::Blocked),
+            (Some(403), WriteAccess::Blocked),
+            (Some(404), WriteAccess::Blocked),
+            (Some(405), WriteAccess::Blocked),
+            // Copying nothing should never succeed
+            (Some(200), WriteAccess::Unknown),
+            (Some(500), WriteAccess::Unknown),
+            (None, WriteAccess::Unknown),
+        ] {
+            assert_eq!(WriteAccess::from_status(status), access, "{:?}", status);
+        }
+    }
+
+    #[test]
+    fn picks_the_smallest_embedding_model() {
+        let model = |name: &str, size| serde_json::from_value::<Model>(serde_json::json!({ "name": name, "size": size })).unwrap();
+        let models = [
+            model("llama3:8b", 10),
+            model("mxbai-embed-large:latest", 669_615_493),
+            model("nomic-embed-text:latest", 274_302_450),
+            model("library/all-minilm:l6-v2", 45_960_996),
+            // Names an embedding family only as a suffix
+            model("qwen2-bge:1.5b", 1),
+        ];
+        assert_eq!(smallest_embedding_model(&models), Some("library/all-minilm:l6-v2"));
+        assert_eq!(smallest_embedding_model(&models[..3]), Some("nomic-embed-text:latest"));
+        assert_eq!(smallest_embedding_model(&[model("bge-m3", 1_157_672_605)]), Some("bge-m3"));
+        assert_eq!(smallest_embedding_model(&[models[0].clone(), models[4].clone()]), None);
+    }
+
+    #[test]
+    fn reads_the_dimension_from_either_endpoint() {
+        assert_eq!(embedding_dimension(br#"{"model":"all-minilm","embeddings":[[0.01,-0.2,0.3]],"total_duration":1}"#), Some(3));
+        assert_eq!(embedding_dimension(br#"{"embedding":[0.5,1,-2e-3,0.0]}"#), Some(4));
+        assert_eq!(embedding_dimension(br#"{"embeddings":[]}"#), None);
+        assert_eq!(embedding_dimension(br#"{"embedding":[]}"#), None);
+        assert_eq!(embedding_dimension(br#"{"embedding":["a","b"]}"#), None);
+        assert_eq!(embedding_dimension(br#"{"error":"model not found"}"#), None);
+    }
+
+    #[test]
+    fn snippets_stay_on_one_line() {
+        assert_eq!(snippet(b"{\"models\":\n[]}"), "{\"models\": []}");
+        assert_eq!(snippet("x".repeat(500).as_bytes()), format!("{}…", "x".repeat(MAX_SNIPPET_LEN)));
+    }
+
+    #[test]
+    fn rejects_anything_but_a_model_list() {
+        for body in ["<html>It works!</html>", r#"{"models":null}"#, r#"{"models":[{"size":1}]}"#, r#"{"status":"ok"}"#] {
+            assert!(parse_tags(body.as_bytes()).is_none(), "{}", body);
+        }
+    }
+}
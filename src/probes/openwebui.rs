@@ -0,0 +1,113 @@
+//! Open WebUI, the chat frontend often put in front of Ollama.
+
+use async_trait::async_trait;
+
+use super::{Details, Fetcher, Probe, ServiceFinding};
+
+/// The Service label of Open WebUI rows, which Ollama rows on the same host link to
+pub const OPEN_WEBUI: &str = "Open WebUI";
+
+/// Enough of a web app's index page for its title, without its bundled scripts
+const MAX_PAGE_LEN: usize = 16 << 10;
+
+pub struct OpenWebUi;
+
+#[async_trait]
+impl Probe for OpenWebUi {
+    fn name(&self) -> &'static str {
+        OPEN_WEBUI
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        &[3000, 8080]
+    }
+
+    /// Recognizes Open WebUI by its `/api/config` or, where that is blocked,
+    /// by the title of its index page.
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        if let Some(reply) = fetcher.get(port, "/api/config").await.filter(|r| r.status == 200) {
+            if let Some(config) = open_webui_config(&reply.body) {
+                return Some(ServiceFinding::new(OPEN_WEBUI, port, "/api/config", reply, Details::service(Vec::new(), config.describe())));
+            }
+        }
+        let page = fetcher.get_limited(port, "/", MAX_PAGE_LEN).await.filter(|r| r.status == 200)?;
+        let title = html_title(&page.body)?;
+        title
+            .contains(OPEN_WEBUI)
+            .then(|| ServiceFinding::new(OPEN_WEBUI, port, "/", page, Details::service(Vec::new(), "version unknown")))
+    }
+}
+
+/// What Open WebUI's `/api/config` says about who may use it.
+#[derive(Debug, PartialEq, Eq)]
+struct WebUiConfig {
+    version: String,
+    auth: Option<bool>,
+    signup: Option<bool>,
+    /// No admin account exists yet; whoever signs up first becomes admin
+    onboarding: bool,
+}
+
+impl WebUiConfig {
+    fn describe(&self) -> String {
+        let mut parts = vec![format!("version {}", if self.version.is_empty() { "unknown" } else { &self.version })];
+        match self.auth {
+            Some(false) => parts.push("auth disabled".to_string()),
+            Some(true) => parts.push("auth enabled".to_string()),
+            None => {}
+        }
+        if self.signup == Some(true) {
+            parts.push("signup open".to_string());
+        }
+        if self.onboarding {
+            parts.push("no admin yet (first signup becomes admin)".to_string());
+        }
+        parts.join("; ")
+    }
+}
+
+fn open_webui_config(body: &[u8]) -> Option<WebUiConfig> {
+    let config: serde_json::Value = serde_json::from_slice(body).ok()?;
+    // A custom WEBUI_NAME is shown as "<name> (Open WebUI)"
+    if !config["name"].as_str().is_some_and(|name| name.contains(OPEN_WEBUI)) {
+        return None;
+    }
+    let features = &config["features"];
+    Some(WebUiConfig {
+        version: config["version"].as_str().unwrap_or_default().chars().take(32).collect(),
+        auth: features["auth"].as_bool().or(config["auth"].as_bool()),
+        signup: features["enable_signup"].as_bool(),
+        onboarding: config["onboarding"] == true,
+    })
+}
+
+fn html_title(page: &[u8]) -> Option<String> {
+    let page = String::from_utf8_lossy(page);
+    let start = page.find("<title>")? + "<title>".len();
+    let end = page[start..].find("</title>")? + start;
+    Some(page[start..end].trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_open_webui_access_settings() {
+        let open = r#"{"status":true,"name":"Open WebUI","version":"0.3.35","default_locale":"","onboarding":true,"features":{"auth":true,"auth_trusted_header":false,"enable_signup":true,"enable_web_search":false}}"#;
+        let config = open_webui_config(open.as_bytes()).unwrap();
+        assert_eq!(config.describe(), "version 0.3.35; auth enabled; signup open; no admin yet (first signup becomes admin)");
+
+        let no_auth = r#"{"status":true,"name":"Lab Chat (Open WebUI)","version":"0.5.4","features":{"auth":false,"enable_signup":false}}"#;
+        assert_eq!(open_webui_config(no_auth.as_bytes()).unwrap().describe(), "version 0.5.4; auth disabled");
+
+        assert!(open_webui_config(br#"{"name":"Grafana","version":"11.0.0"}"#).is_none());
+    }
+
+    #[test]
+    fn finds_the_page_title() {
+        let page = b"<!doctype html><html><head><meta charset=\"utf-8\"><title>Open WebUI</title><script src=\"/_app/immutable/entry/start.js\">";
+        assert_eq!(html_title(page).as_deref(), Some("Open WebUI"));
+        assert_eq!(html_title(b"<html><body>no title</body>"), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! text-generation-webui: its OpenAI-compatible API and its Gradio UI.
+
+use async_trait::async_trait;
+
+use super::{cap_model_id, Details, Fetcher, ModelList, Probe, ServiceFinding};
+
+const API_PORT: u16 = 5000;
+
+pub struct TextGen;
+
+#[async_trait]
+impl Probe for TextGen {
+    fn name(&self) -> &'static str {
+        "text-generation-webui"
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        &[API_PORT, 7860]
+    }
+
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        match port {
+            API_PORT => self.probe_api(fetcher, port).await,
+            _ => self.probe_ui(fetcher, port).await,
+        }
+    }
+}
+
+impl TextGen {
+    /// Plenty of OpenAI proxies list models, so the API only counts as
+    /// text-generation-webui if its own model info endpoint answers too;
+    /// otherwise it is recorded as a generic OpenAI-compatible server.
+    async fn probe_api(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        let reply = fetcher.get(port, "/v1/models").await.filter(|r| r.status == 200)?;
+        let list = ModelList::parse(&reply.body)?;
+        let info = match fetcher.get(port, "/v1/internal/model/info").await {
+            Some(info) if info.status == 200 => textgen_model_info(&info.body),
+            _ => None,
+        };
+        let (service, details) = match info {
+            Some(model) => (self.name(), Details::service(vec![model], "interface: API")),
+            None => ("OpenAI-compatible", Details::service(list.ids(), "")),
+        };
+        Some(ServiceFinding::new(service, port, "/v1/models", reply, details))
+    }
+
+    /// The Gradio UI, recognized by a Gradio config that also carries the web
+    /// UI's title.
+    async fn probe_ui(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        let reply = fetcher.get(port, "/config").await.filter(|r| r.status == 200)?;
+        let ui = textgen_gradio_config(&reply.body)?;
+        let summary = format!("interface: Gradio UI {}", ui.gradio_version).trim_end().to_string();
+        Some(ServiceFinding::new(self.name(), port, "/config", reply, Details::service(ui.model.into_iter().collect(), summary)))
+    }
+}
+
+/// The loaded model from `/v1/internal/model/info`, which only
+/// text-generation-webui serves: `{"model_name": ..., "lora_names": [...]}`.
+fn textgen_model_info(body: &[u8]) -> Option<String> {
+    let info: serde_json::Value = serde_json::from_slice(body).ok()?;
+    info.get("lora_names")?.as_array()?;
+    let model = info.get("model_name")?.as_str()?;
+    Some(cap_model_id(model.to_string()))
+}
+
+struct GradioUi {
+    gradio_version: String,
+    model: Option<String>,
+}
+
+/// A Gradio `/config` that belongs to text-generation-webui: Gradio's own
+/// layout (version, components, dependencies) plus the web UI's title.
+fn textgen_gradio_config(body: &[u8]) -> Option<GradioUi> {
+    let config: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let components = config.get("components")?.as_array()?;
+    config.get("dependencies")?.as_array()?;
+    if !config["title"].as_str().is_some_and(|title| title.contains("Text generation web UI")) {
+        return None;
+    }
+    // The model menu is a dropdown labeled "Model" whose value is the loaded model
+    let model = components
+        .iter()
+        .filter(|c| c["type"] == "dropdown" && c["props"]["label"] == "Model")
+        .find_map(|c| c["props"]["value"].as_str())
+        .filter(|model| !model.is_empty() && *model != "None")
+        .map(|model| cap_model_id(model.to_string()));
+    Some(GradioUi {
+        gradio_version: config["version"].as_str().unwrap_or_default().chars().take(32).collect(),
+        model,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_both_gradio_and_the_web_ui_title_for_the_textgen_ui() {
+        let config = r#"{"version":"4.37.2","mode":"blocks","title":"Text generation web UI","dependencies":[],"components":[{"id":1,"type":"textbox","props":{"label":"Input"}},{"id":2,"type":"dropdown","props":{"label":"Model","value":"TheBloke_Mistral-7B-Instruct-v0.2-GPTQ"}}]}"#;
+        let ui = textgen_gradio_config(config.as_bytes()).unwrap();
+        assert_eq!(ui.gradio_version, "4.37.2");
+        assert_eq!(ui.model.as_deref(), Some("TheBloke_Mistral-7B-Instruct-v0.2-GPTQ"));
+
+        let unloaded = r#"{"title":"Text generation web UI","dependencies":[],"components":[{"type":"dropdown","props":{"label":"Model","value":"None"}}]}"#;
+        assert_eq!(textgen_gradio_config(unloaded.as_bytes()).unwrap().model, None);
+
+        let other_gradio_app = r#"{"version":"4.37.2","title":"Stable Diffusion","dependencies":[],"components":[]}"#;
+        assert!(textgen_gradio_config(other_gradio_app.as_bytes()).is_none());
+        let not_gradio = r#"{"title":"Text generation web UI"}"#;
+        assert!(textgen_gradio_config(not_gradio.as_bytes()).is_none());
+    }
+
+    #[test]
+    fn reads_the_textgen_model_info() {
+        let info = r#"{"model_name":"TheBloke_Llama-2-13B-chat-GPTQ","lora_names":[]}"#;
+        assert_eq!(textgen_model_info(info.as_bytes()).as_deref(), Some("TheBloke_Llama-2-13B-chat-GPTQ"));
+        assert_eq!(textgen_model_info(br#"{"model_name":"gpt-4"}"#), None);
+    }
+}
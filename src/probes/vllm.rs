@@ -0,0 +1,77 @@
+//! vLLM's OpenAI-compatible server, including ones guarded by `--api-key`.
+
+use async_trait::async_trait;
+
+use super::{Details, Fetcher, ModelEntry, ModelList, Probe, ServiceFinding, PROTECTED};
+
+pub struct Vllm;
+
+#[async_trait]
+impl Probe for Vllm {
+    fn name(&self) -> &'static str {
+        "vLLM"
+    }
+
+    fn default_ports(&self) -> &'static [u16] {
+        &[8000]
+    }
+
+    async fn probe(&self, fetcher: &Fetcher<'_>, port: u16) -> Option<ServiceFinding> {
+        let reply = fetcher.get(port, "/v1/models").await?;
+        let list = match reply.status {
+            200 => Some(ModelList::parse(&reply.body)?),
+            // --api-key guards /v1 but not /version
+            401 => None,
+            _ => return None,
+        };
+        let marked = list.as_ref().is_some_and(|list| list.data.iter().any(ModelEntry::is_vllm));
+        let version = match fetcher.get(port, "/version").await {
+            Some(version) if version.status == 200 => vllm_version(&version.body),
+            _ => None,
+        };
+        let (service, models, summary) = match (list, version) {
+            (None, Some(version)) => (self.name(), Vec::new(), format!("version {}; {}", version, PROTECTED)),
+            (None, None) => return None,
+            (Some(list), version) => (
+                if marked || version.is_some() { self.name() } else { "OpenAI-compatible" },
+                list.ids(),
+                version.map(|v| format!("version {}", v)).unwrap_or_default(),
+            ),
+        };
+        Some(ServiceFinding::new(service, port, "/v1/models", reply, Details::service(models, summary)))
+    }
+}
+
+impl ModelEntry {
+    fn is_vllm(&self) -> bool {
+        self.owned_by == "vllm" || self.max_model_len.is_some()
+    }
+}
+
+/// The version in vLLM's `/version` reply, `{"version": "0.6.3"}`.
+fn vllm_version(body: &[u8]) -> Option<String> {
+    let reply: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let version = reply.as_object().filter(|fields| fields.len() == 1)?.get("version")?.as_str()?;
+    Some(version.chars().take(32).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probes::fixtures::LM_STUDIO_MODELS;
+
+    #[test]
+    fn recognizes_vllm_model_metadata_and_version() {
+        let body = r#"{"object":"list","data":[{"id":"meta-llama/Llama-3.1-8B-Instruct","object":"model","created":1718000000,"owned_by":"vllm","root":"meta-llama/Llama-3.1-8B-Instruct","parent":null,"max_model_len":8192}]}"#;
+        let list: ModelList = serde_json::from_slice(body.as_bytes()).unwrap();
+        assert!(list.data[0].is_vllm());
+        assert_eq!(list.ids(), ["meta-llama/Llama-3.1-8B-Instruct"]);
+
+        let lm_studio: ModelList = serde_json::from_slice(LM_STUDIO_MODELS.as_bytes()).unwrap();
+        assert!(!lm_studio.data.iter().any(ModelEntry::is_vllm));
+
+        assert_eq!(vllm_version(br#"{"version":"0.6.3.post1"}"#).as_deref(), Some("0.6.3.post1"));
+        assert_eq!(vllm_version(br#"{"version":"1.0","name":"something else"}"#), None);
+        assert_eq!(vllm_version(b"0.6.3"), None);
+    }
+}
@@ -0,0 +1,405 @@
+//! The `Provider` column: which cloud or hosting company an address belongs
+//! to, from the ranges the providers publish.
+//!
+//! AWS, Google Cloud and DigitalOcean publish feeds, which
+//! `--update-provider-feeds` downloads into the `--provider-feeds` directory.
+//! Azure's ServiceTags file moves to a new URL every week, so it is only read
+//! from there once saved as `azure.json`. Hetzner and OVH publish nothing
+//! machine-readable; the networks they announce most are bundled, as are
+//! DigitalOcean's until its feed has been fetched. A `hetzner.txt` or
+//! `ovh.txt` with one CIDR per line replaces the bundled list.
+//!
+//! All ranges are flattened into sorted, non-overlapping intervals, so a
+//! lookup is one binary search. Where ranges overlap the narrowest one wins.
+
+use anyhow::{Context, Result};
+use ipnet::Ipv4Net;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::dns::DnsResolver;
+
+/// Written for addresses no provider lists
+pub const UNKNOWN: &str = "unknown";
+
+/// Feeds can be several megabytes, far more than a probe's 2 s allows for
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// One provider's ranges: a file in the feeds directory, where it is
+/// downloaded from, and what is used while the file is missing.
+struct Feed {
+    provider: &'static str,
+    file: &'static str,
+    url: Option<&'static str>,
+    parse: fn(&str) -> Result<Vec<Ipv4Net>>,
+    bundled: &'static [&'static str],
+}
+
+const FEEDS: &[Feed] = &[
+    Feed {
+        provider: "AWS",
+        file: "aws.json",
+        url: Some("https://ip-ranges.amazonaws.com/ip-ranges.json"),
+        parse: parse_aws,
+        bundled: &[],
+    },
+    Feed {
+        provider: "GCP",
+        file: "gcp.json",
+        url: Some("https://www.gstatic.com/ipranges/cloud.json"),
+        parse: parse_gcp,
+        bundled: &[],
+    },
+    Feed {
+        provider: "Azure",
+        file: "azure.json",
+        url: None,
+        parse: parse_azure,
+        bundled: &[],
+    },
+    Feed {
+        provider: "DigitalOcean",
+        file: "digitalocean.csv",
+        url: Some("https://digitalocean.com/geo/google.csv"),
+        parse: parse_list,
+        bundled: DIGITALOCEAN,
+    },
+    Feed {
+        provider: "Hetzner",
+        file: "hetzner.txt",
+        url: None,
+        parse: parse_list,
+        bundled: HETZNER,
+    },
+    Feed {
+        provider: "OVH",
+        file: "ovh.txt",
+        url: None,
+        parse: parse_list,
+        bundled: OVH,
+    },
+];
+
+// The networks each announces most (AS14061, AS24940, AS16276); not exhaustive
+
+const DIGITALOCEAN: &[&str] = &[
+    "45.55.0.0/16", "46.101.0.0/16", "64.225.0.0/16", "68.183.0.0/16", "104.131.0.0/16", "104.236.0.0/16",
+    "104.248.0.0/16", "107.170.0.0/16", "128.199.0.0/16", "134.209.0.0/16", "137.184.0.0/16", "138.68.0.0/16",
+    "138.197.0.0/16", "139.59.0.0/16", "142.93.0.0/16", "143.198.0.0/16", "146.190.0.0/16", "157.230.0.0/16",
+    "157.245.0.0/16", "159.65.0.0/16", "159.89.0.0/16", "159.203.0.0/16", "161.35.0.0/16", "162.243.0.0/16",
+    "164.90.0.0/16", "164.92.0.0/16", "165.22.0.0/16", "165.227.0.0/16", "167.71.0.0/16", "167.99.0.0/16",
+    "167.172.0.0/16", "174.138.0.0/17", "178.62.0.0/16", "178.128.0.0/16", "188.166.0.0/16", "192.241.128.0/17",
+    "198.199.64.0/18", "206.189.0.0/16", "209.97.128.0/18",
+];
+
+const HETZNER: &[&str] = &[
+    "5.9.0.0/16", "5.75.128.0/17", "23.88.0.0/17", "37.27.0.0/16", "46.4.0.0/16", "49.12.0.0/16", "49.13.0.0/16",
+    "65.21.0.0/16", "65.108.0.0/16", "65.109.0.0/16", "78.46.0.0/15", "85.10.192.0/18", "88.99.0.0/16",
+    "88.198.0.0/16", "91.107.128.0/17", "94.130.0.0/16", "95.216.0.0/16", "95.217.0.0/16", "116.202.0.0/16",
+    "116.203.0.0/16", "128.140.0.0/17", "135.181.0.0/16", "136.243.0.0/16", "138.201.0.0/16", "142.132.128.0/17",
+    "144.76.0.0/16", "148.251.0.0/16", "157.90.0.0/16", "159.69.0.0/16", "162.55.0.0/16", "167.233.0.0/16",
+    "167.235.0.0/16", "168.119.0.0/16", "176.9.0.0/16", "178.63.0.0/16", "188.40.0.0/16", "195.201.0.0/16",
+    "213.133.96.0/19", "213.239.192.0/18",
+];
+
+const OVH: &[&str] = &[
+    "5.39.0.0/17", "5.135.0.0/16", "5.196.0.0/16", "37.59.0.0/16", "37.187.0.0/16", "46.105.0.0/16", "51.38.0.0/16",
+    "51.68.0.0/16", "51.75.0.0/16", "51.77.0.0/16", "51.79.0.0/16", "51.81.0.0/16", "51.83.0.0/16", "51.89.0.0/16",
+    "51.91.0.0/16", "51.161.0.0/16", "51.178.0.0/16", "51.195.0.0/16", "51.210.0.0/16", "51.222.0.0/16",
+    "51.254.0.0/15", "54.36.0.0/16", "54.37.0.0/16", "54.38.0.0/16", "54.39.0.0/16", "87.98.128.0/17",
+    "91.121.0.0/16", "92.222.0.0/16", "135.125.0.0/16", "137.74.0.0/16", "139.99.0.0/16", "141.94.0.0/16",
+    "141.95.0.0/16", "144.217.0.0/16", "145.239.0.0/16", "147.135.0.0/16", "149.56.0.0/16", "149.202.0.0/16",
+    "151.80.0.0/16", "158.69.0.0/16", "164.132.0.0/16", "167.114.0.0/16", "176.31.0.0/16", "178.32.0.0/15",
+    "188.165.0.0/16", "192.95.0.0/18", "192.99.0.0/16", "193.70.0.0/17", "198.27.64.0/18", "198.50.128.0/17",
+    "213.32.0.0/17", "213.186.32.0/19", "213.251.128.0/18", "217.182.0.0/16",
+];
+
+/// Where a provider's ranges came from, for the run metadata.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedInfo {
+    pub provider: &'static str,
+    /// The feed file, or "bundled"
+    pub source: String,
+    /// RFC3339 UTC modification time of the feed file; None when bundled
+    pub fetched_at: Option<String>,
+    pub networks: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ProviderIndex {
+    /// Sorted, non-overlapping (first, last, provider)
+    intervals: Vec<(u32, u32, &'static str)>,
+    feeds: Vec<FeedInfo>,
+}
+
+impl ProviderIndex {
+    /// Reads every feed in `dir`, falling back to the bundled list of a
+    /// provider whose file is missing. A file that can't be read or parsed
+    /// only produces a warning.
+    pub fn load(dir: &Path) -> Self {
+        let mut networks = Vec::new();
+        let mut feeds = Vec::new();
+        for feed in FEEDS {
+            let path = dir.join(feed.file);
+            let loaded = match path.exists() {
+                true => match read_feed(feed, &path) {
+                    Ok(loaded) => Some(loaded),
+                    Err(e) => {
+                        eprintln!("Warning: {:#}", e);
+                        None
+                    }
+                },
+                false => None,
+            };
+            let (list, source, fetched_at) = match loaded {
+                Some((list, fetched_at)) => (list, path.display().to_string(), fetched_at),
+                None if !feed.bundled.is_empty() => (bundled(feed.bundled), "bundled".to_string(), None),
+                None => continue,
+            };
+            feeds.push(FeedInfo { provider: feed.provider, source, fetched_at, networks: list.len() });
+            networks.extend(list.into_iter().map(|network| (network, feed.provider)));
+        }
+        Self { intervals: flatten(networks), feeds }
+    }
+
+    /// The provider `ip` belongs to, or `unknown`.
+    pub fn provider(&self, ip: Ipv4Addr) -> &'static str {
+        let ip = u32::from(ip);
+        let after = self.intervals.partition_point(|&(first, _, _)| first <= ip);
+        match after.checked_sub(1).map(|i| self.intervals[i]) {
+            Some((_, last, provider)) if ip <= last => provider,
+            _ => UNKNOWN,
+        }
+    }
+
+    pub fn feeds(&self) -> &[FeedInfo] {
+        &self.feeds
+    }
+
+    /// "AWS (2024-05-01), Hetzner (bundled)", for the banner.
+    pub fn describe(&self) -> String {
+        self.feeds
+            .iter()
+            .map(|feed| {
+                let date = feed.fetched_at.as_deref().map(|at| at.get(..10).unwrap_or(at));
+                format!("{} ({})", feed.provider, date.unwrap_or("bundled"))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A feed file's networks and modification time.
+fn read_feed(feed: &Feed, path: &Path) -> Result<(Vec<Ipv4Net>, Option<String>)> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let networks = (feed.parse)(&text).with_context(|| format!("{} is not a {} range list", path.display(), feed.provider))?;
+    let fetched_at = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    Ok((networks, fetched_at))
+}
+
+fn bundled(list: &[&str]) -> Vec<Ipv4Net> {
+    list.iter().filter_map(|network| network.parse().ok()).collect()
+}
+
+/// Downloads every feed that has a URL into `dir`. A feed that fails keeps
+/// its previous file, if any, and only produces a warning.
+pub async fn update_feeds(dir: &Path, dns: Option<&Arc<DnsResolver>>) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let client = DnsResolver::apply(dns, reqwest::Client::builder()).timeout(DOWNLOAD_TIMEOUT).build()?;
+    for feed in FEEDS {
+        let Some(url) = feed.url else {
+            continue;
+        };
+        match download(&client, feed, url, dir).await {
+            Ok(count) => eprintln!("Updated the {} ranges: {} IPv4 networks", feed.provider, count),
+            Err(e) => eprintln!("Warning: could not update the {} ranges: {:#}", feed.provider, e),
+        }
+    }
+    Ok(())
+}
+
+/// Fetches `url` and saves it as the feed's file once it parses.
+async fn download(client: &reqwest::Client, feed: &Feed, url: &str, dir: &Path) -> Result<usize> {
+    let text = client.get(url).send().await?.error_for_status()?.text().await?;
+    let count = (feed.parse)(&text).with_context(|| format!("{} did not answer with a range list", url))?.len();
+    let path = dir.join(feed.file);
+    // Replaced in one step so a scan never reads half a feed
+    let partial = path.with_extension("tmp");
+    fs::write(&partial, text).with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(count)
+}
+
+/// Sorted, non-overlapping intervals of `networks`, adjacent ones of the same
+/// value merged. Narrower networks are placed first and wider ones only fill
+/// what is left around them. Also used for the `--asn-db` table.
+pub fn flatten<T: Copy + PartialEq>(mut networks: Vec<(Ipv4Net, T)>) -> Vec<(u32, u32, T)> {
+    networks.sort_by_key(|(network, _)| std::cmp::Reverse(network.prefix_len()));
+    let mut placed: BTreeMap<u32, (u32, T)> = BTreeMap::new();
+    for (network, provider) in networks {
+        let (first, last) = (u32::from(network.network()), u32::from(network.broadcast()));
+        let mut taken: Vec<(u32, u32)> = placed
+            .range(..=last)
+            .rev()
+            .take_while(|(_, (end, _))| *end >= first)
+            .map(|(&start, &(end, _))| (start, end))
+            .collect();
+        taken.reverse();
+        // u64 so the address after 255.255.255.255 does not wrap
+        let mut next = first as u64;
+        let mut gaps = Vec::new();
+        for (start, end) in taken {
+            if (start as u64) > next {
+                gaps.push((next as u32, start - 1));
+            }
+            next = next.max(end as u64 + 1);
+        }
+        if next <= last as u64 {
+            gaps.push((next as u32, last));
+        }
+        for (start, end) in gaps {
+            placed.insert(start, (end, provider));
+        }
+    }
+
+    let mut intervals: Vec<(u32, u32, T)> = Vec::with_capacity(placed.len());
+    for (start, (end, provider)) in placed {
+        match intervals.last_mut() {
+            Some(last) if last.2 == provider && last.1 as u64 + 1 == start as u64 => last.1 = end,
+            _ => intervals.push((start, end, provider)),
+        }
+    }
+    intervals
+}
+
+/// IPv4 networks among `prefixes`; IPv6 ones are skipped.
+fn ipv4_networks<'a>(prefixes: impl Iterator<Item = &'a str>) -> Vec<Ipv4Net> {
+    prefixes.filter_map(|prefix| prefix.trim().parse().ok()).collect()
+}
+
+/// AWS's ip-ranges.json.
+fn parse_aws(text: &str) -> Result<Vec<Ipv4Net>> {
+    #[derive(Deserialize)]
+    struct Ranges {
+        prefixes: Vec<Prefix>,
+    }
+    #[derive(Deserialize)]
+    struct Prefix {
+        ip_prefix: String,
+    }
+    let ranges: Ranges = serde_json::from_str(text)?;
+    Ok(ipv4_networks(ranges.prefixes.iter().map(|p| p.ip_prefix.as_str())))
+}
+
+/// Google Cloud's cloud.json, whose entries have either an `ipv4Prefix` or
+/// an `ipv6Prefix`.
+fn parse_gcp(text: &str) -> Result<Vec<Ipv4Net>> {
+    #[derive(Deserialize)]
+    struct Ranges {
+        prefixes: Vec<Prefix>,
+    }
+    #[derive(Deserialize)]
+    struct Prefix {
+        #[serde(rename = "ipv4Prefix")]
+        ipv4_prefix: Option<String>,
+    }
+    let ranges: Ranges = serde_json::from_str(text)?;
+    Ok(ipv4_networks(ranges.prefixes.iter().filter_map(|p| p.ipv4_prefix.as_deref())))
+}
+
+/// Azure's ServiceTags_Public_*.json. Service tags overlap heavily; every
+/// one of them is Azure.
+fn parse_azure(text: &str) -> Result<Vec<Ipv4Net>> {
+    #[derive(Deserialize)]
+    struct Tags {
+        values: Vec<Tag>,
+    }
+    #[derive(Deserialize)]
+    struct Tag {
+        properties: Properties,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Properties {
+        address_prefixes: Vec<String>,
+    }
+    let tags: Tags = serde_json::from_str(text)?;
+    Ok(ipv4_networks(tags.values.iter().flat_map(|tag| tag.properties.address_prefixes.iter().map(String::as_str))))
+}
+
+/// One network per line, first in a comma-separated line such as
+/// DigitalOcean's geofeed; `#` starts a comment.
+fn parse_list(text: &str) -> Result<Vec<Ipv4Net>> {
+    let firsts = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| line.split(',').next())
+        .filter(|first| !first.trim().is_empty());
+    let networks = ipv4_networks(firsts);
+    anyhow::ensure!(!networks.is_empty() || text.trim().is_empty(), "no IPv4 network in it");
+    Ok(networks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(networks: &[(&str, &'static str)]) -> ProviderIndex {
+        let networks = networks.iter().map(|(network, provider)| (network.parse().unwrap(), *provider)).collect();
+        ProviderIndex { intervals: flatten(networks), feeds: Vec::new() }
+    }
+
+    fn ip(text: &str) -> Ipv4Addr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn narrower_ranges_win_where_they_overlap() {
+        let index = index(&[
+            ("10.0.0.0/8", "Wide"),
+            ("10.1.0.0/16", "Narrow"),
+            ("10.1.2.0/24", "Wide"),
+            ("255.255.255.0/24", "Top"),
+        ]);
+        assert_eq!(index.provider(ip("10.0.0.1")), "Wide");
+        assert_eq!(index.provider(ip("10.1.0.1")), "Narrow");
+        assert_eq!(index.provider(ip("10.1.2.3")), "Wide");
+        assert_eq!(index.provider(ip("10.1.255.255")), "Narrow");
+        assert_eq!(index.provider(ip("10.255.255.255")), "Wide");
+        assert_eq!(index.provider(ip("11.0.0.0")), UNKNOWN);
+        assert_eq!(index.provider(ip("9.255.255.255")), UNKNOWN);
+        assert_eq!(index.provider(ip("255.255.255.255")), "Top");
+        assert_eq!(index.intervals.len(), 6);
+    }
+
+    #[test]
+    fn reads_each_feed_format() {
+        let aws = r#"{"syncToken":"1","prefixes":[{"ip_prefix":"3.5.140.0/22","service":"AMAZON"}],"ipv6_prefixes":[{"ipv6_prefix":"2600:1f00::/24"}]}"#;
+        assert_eq!(parse_aws(aws).unwrap(), ["3.5.140.0/22".parse::<Ipv4Net>().unwrap()]);
+        let gcp = r#"{"prefixes":[{"ipv4Prefix":"34.1.208.0/20","service":"Google Cloud"},{"ipv6Prefix":"2600:1900::/35"}]}"#;
+        assert_eq!(parse_gcp(gcp).unwrap(), ["34.1.208.0/20".parse::<Ipv4Net>().unwrap()]);
+        let azure = r#"{"values":[{"name":"AzureCloud","properties":{"addressPrefixes":["13.64.0.0/16","2603:1000::/40"]}}]}"#;
+        assert_eq!(parse_azure(azure).unwrap(), ["13.64.0.0/16".parse::<Ipv4Net>().unwrap()]);
+        let geofeed = "# DigitalOcean\n5.101.96.0/21,NL,NL-NH,Amsterdam,1098\n2a03:b0c0::/32,NL,NL-NH,Amsterdam,1098\n";
+        assert_eq!(parse_list(geofeed).unwrap(), ["5.101.96.0/21".parse::<Ipv4Net>().unwrap()]);
+        assert!(parse_list("<html>Not Found</html>").is_err());
+    }
+
+    #[test]
+    fn bundled_lists_are_networks() {
+        for list in [DIGITALOCEAN, HETZNER, OVH] {
+            for network in list {
+                let parsed: Ipv4Net = network.parse().unwrap_or_else(|_| panic!("{}", network));
+                assert_eq!(parsed, parsed.trunc(), "{} has host bits set", network);
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Recognizes reverse proxies and CDNs in front of an endpoint from the
+//! headers of its /api/tags response. Ollama itself sends none of these.
+
+use reqwest::header::HeaderMap;
+
+/// Headers summarized in the Proxy/Server column, in this order
+const CAPTURED_HEADERS: [&str; 4] = ["server", "via", "x-powered-by", "cf-ray"];
+/// Longest value kept per header; anything past it is cut off with "…"
+const MAX_HEADER_VALUE_LEN: usize = 120;
+
+/// What an endpoint's response headers say about what sits in front of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyInfo {
+    /// e.g. "server=nginx/1.25.3; via=1.1 varnish"; empty for a bare Ollama
+    pub summary: String,
+    /// The CDN or WAF the response came through, if one was recognized
+    pub fronting: Option<&'static str>,
+}
+
+impl ProxyInfo {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let summary = CAPTURED_HEADERS
+            .iter()
+            .filter_map(|name| Some(format!("{}={}", name, clean_value(header(headers, name)?))))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Self { summary, fronting: fronting(headers) }
+    }
+}
+
+/// The value of header `name`, cleaned up for a CSV cell; empty when absent.
+pub fn header_cell(headers: &HeaderMap, name: &str) -> String {
+    header(headers, name).map(clean_value).unwrap_or_default()
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok()).filter(|value| !value.trim().is_empty())
+}
+
+/// Trims a header value, drops control characters and the separator used
+/// between headers, and caps its length.
+fn clean_value(value: &str) -> String {
+    let cleaned: String = value.trim().chars().filter(|c| !c.is_control() && *c != ';').collect();
+    match cleaned.char_indices().nth(MAX_HEADER_VALUE_LEN) {
+        Some((cut, _)) => format!("{}…", &cleaned[..cut]),
+        None => cleaned,
+    }
+}
+
+fn fronting(headers: &HeaderMap) -> Option<&'static str> {
+    let server = header(headers, "server").unwrap_or_default().to_ascii_lowercase();
+    if headers.contains_key("cf-ray") || server == "cloudflare" {
+        return Some("Cloudflare");
+    }
+    let akamai_header = headers.keys().any(|name| name.as_str().starts_with("x-akamai-") || name.as_str().starts_with("akamai-"));
+    if akamai_header || server.starts_with("akamaighost") || server.starts_with("akamainetstorage") {
+        return Some("Akamai");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn summarizes_the_captured_headers_in_order() {
+        let info = ProxyInfo::from_headers(&headers(&[
+            ("content-type", "application/json"),
+            ("x-powered-by", "Express"),
+            ("via", "1.1 varnish"),
+            ("server", "nginx/1.25.3"),
+        ]));
+        assert_eq!(info.summary, "server=nginx/1.25.3; via=1.1 varnish; x-powered-by=Express");
+        assert_eq!(info.fronting, None);
+        assert_eq!(ProxyInfo::from_headers(&headers(&[("content-type", "application/json")])), ProxyInfo::default());
+    }
+
+    #[test]
+    fn recognizes_cloudflare_and_akamai() {
+        let cloudflare = ProxyInfo::from_headers(&headers(&[("server", "cloudflare"), ("cf-ray", "8a1b2c3d4e5f6789-FRA")]));
+        assert_eq!(cloudflare.fronting, Some("Cloudflare"));
+        assert_eq!(cloudflare.summary, "server=cloudflare; cf-ray=8a1b2c3d4e5f6789-FRA");
+
+        assert_eq!(ProxyInfo::from_headers(&headers(&[("server", "AkamaiGHost")])).fronting, Some("Akamai"));
+        assert_eq!(ProxyInfo::from_headers(&headers(&[("x-akamai-transformed", "9 - 0 pmb=mRUM,1")])).fronting, Some("Akamai"));
+        assert_eq!(ProxyInfo::from_headers(&headers(&[("server", "Caddy")])).fronting, None);
+    }
+
+    #[test]
+    fn caps_long_values_and_strips_separators() {
+        let long = "x".repeat(500);
+        let info = ProxyInfo::from_headers(&headers(&[("server", &long), ("via", "1.1 a; 1.1 b")]));
+        let server = info.summary.split("; ").next().unwrap();
+        assert_eq!(server, format!("server={}…", "x".repeat(MAX_HEADER_VALUE_LEN)));
+        assert!(info.summary.ends_with("via=1.1 a 1.1 b"));
+    }
+}
@@ -0,0 +1,202 @@
+//! Redis-backed work queue for spreading one scan across several machines.
+//!
+//! `--queue-push` expands the targets into batches and LPUSHes them onto
+//! `ollama-finder:queue`. Workers started with `--queue-pull` move a batch
+//! onto `ollama-finder:processing` with BRPOPLPUSH, hold a claim key that a
+//! heartbeat keeps alive while they scan it, and remove the batch once done.
+//! A batch left in the processing list without a live claim belonged to a
+//! worker that died, and any worker puts it back on the queue.
+
+use anyhow::{Context, Result};
+use console::style;
+use indicatif::ProgressBar;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cli::ScanArgs;
+use crate::probed::ProbedSet;
+use crate::targets::{planned_hosts, Provenance, Target};
+use crate::{console_log, listed_in, scan_hosts, ScanContext, ScanResult, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE, STOP_SCAN};
+
+const QUEUE_KEY: &str = "ollama-finder:queue";
+const PROCESSING_KEY: &str = "ollama-finder:processing";
+const CLAIM_PREFIX: &str = "ollama-finder:claim:";
+
+const BATCH_SIZE: usize = 256;
+/// A claim expires this long after the last heartbeat
+const VISIBILITY_TIMEOUT: u64 = 60;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+/// BRPOPLPUSH and SETEX are separate commands, so give a fresh pop this long
+/// to get its claim before treating it as orphaned
+const CLAIM_GRACE: Duration = Duration::from_secs(10);
+const POP_TIMEOUT_SECS: f64 = 1.0;
+
+/// Moves an item from the processing list back onto the queue, but only if it
+/// is still there so two workers reaping at once don't duplicate it.
+const REQUEUE_SCRIPT: &str = r"
+if redis.call('LREM', KEYS[1], 1, ARGV[1]) > 0 then
+    redis.call('RPUSH', KEYS[2], ARGV[1])
+    return 1
+end
+return 0
+";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Batch {
+    id: String,
+    #[serde(flatten)]
+    provenance: Provenance,
+    hosts: Vec<Ipv4Addr>,
+}
+
+fn claim_key(id: &str) -> String {
+    format!("{}{}", CLAIM_PREFIX, id)
+}
+
+async fn connect(url: &str) -> Result<MultiplexedConnection> {
+    let client = redis::Client::open(url).with_context(|| format!("Invalid Redis URL '{}'", url))?;
+    client
+        .get_multiplexed_async_connection()
+        .await
+        .with_context(|| format!("Failed to connect to Redis at {}", url))
+}
+
+/// Expands the planned hosts of every range into batches and queues them.
+pub async fn push(url: &str, targets: &[Target], args: &ScanArgs, seed: u64) -> Result<()> {
+    let mut con = connect(url).await?;
+    let prefix = format!("{}-{:08x}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"), rand::random::<u32>());
+    let (mut batches, mut hosts) = (0u64, 0u64);
+    // Workers cannot see each other's hosts, so duplicates are left out here
+    let probed = ProbedSet::new(targets);
+    let mut duplicates = 0u64;
+
+    for target in targets {
+        if STOP_SCAN.load(Ordering::Relaxed) {
+            break;
+        }
+        let mut planned = planned_hosts(target, args, seed)
+            .filter(|ip| probed.first_probe(*ip) || {
+                duplicates += 1;
+                false
+            })
+            .peekable();
+        while planned.peek().is_some() {
+            let batch = Batch {
+                id: format!("{}-{}", prefix, batches),
+                provenance: target.provenance.clone(),
+                hosts: planned.by_ref().take(BATCH_SIZE).collect(),
+            };
+            hosts += batch.hosts.len() as u64;
+            let _: () = con.lpush(QUEUE_KEY, serde_json::to_string(&batch)?).await?;
+            batches += 1;
+        }
+    }
+
+    console_log(format!("\n{}{}", HEADER_STYLE, style("Queued work").blue().bold()));
+    console_log(format!("{}Redis: {}", LIST_ITEM_STYLE, style(url).yellow()));
+    console_log(format!("{}Batches: {} ({} hosts)", LIST_ITEM_STYLE, style(batches).cyan(), style(hosts).cyan()));
+    if duplicates > 0 {
+        console_log(format!("{}Duplicates left out: {}", LIST_ITEM_STYLE, style(duplicates).yellow()));
+    }
+    console_log(format!("{}Start workers with --queue-pull {}", LAST_ITEM_STYLE, url));
+    Ok(())
+}
+
+/// Re-queues batches whose claim has been missing for longer than CLAIM_GRACE.
+async fn reap(con: &mut MultiplexedConnection, unclaimed: &mut HashMap<String, Instant>) -> Result<()> {
+    let items: Vec<String> = con.lrange(PROCESSING_KEY, 0, -1).await?;
+    unclaimed.retain(|item, _| items.contains(item));
+    for item in items {
+        let Ok(batch) = serde_json::from_str::<Batch>(&item) else { continue };
+        let claimed: bool = con.exists(claim_key(&batch.id)).await?;
+        if claimed {
+            unclaimed.remove(&item);
+            continue;
+        }
+        let since = *unclaimed.entry(item.clone()).or_insert_with(Instant::now);
+        if since.elapsed() >= CLAIM_GRACE {
+            let requeued: i64 = redis::Script::new(REQUEUE_SCRIPT)
+                .key(PROCESSING_KEY)
+                .key(QUEUE_KEY)
+                .arg(&item)
+                .invoke_async(con)
+                .await?;
+            if requeued > 0 {
+                console_log(style(format!("Re-queued abandoned batch {}", batch.id)).yellow().to_string());
+            }
+            unclaimed.remove(&item);
+        }
+    }
+    Ok(())
+}
+
+/// Pops and scans batches until both the queue and the processing list are
+/// empty, or the user stops the scan. Results are appended to `found` as each
+/// batch finishes, so they survive a Redis error part way through.
+pub async fn pull(url: &str, ctx: &ScanContext, progress: Arc<ProgressBar>, found: &mut Vec<ScanResult>) -> Result<()> {
+    let mut con = connect(url).await?;
+    let heartbeat_con = connect(url).await?;
+    let mut unclaimed = HashMap::new();
+
+    while !STOP_SCAN.load(Ordering::Relaxed) {
+        let item: Option<String> = con.brpoplpush(QUEUE_KEY, PROCESSING_KEY, POP_TIMEOUT_SECS).await?;
+        let Some(item) = item else {
+            let queued: u64 = con.llen(QUEUE_KEY).await?;
+            let processing: u64 = con.llen(PROCESSING_KEY).await?;
+            if queued == 0 && processing == 0 {
+                break;
+            }
+            reap(&mut con, &mut unclaimed).await?;
+            continue;
+        };
+
+        let batch: Batch = match serde_json::from_str(&item) {
+            Ok(batch) => batch,
+            Err(e) => {
+                console_log(style(format!("Dropping malformed queue item: {}", e)).red().to_string());
+                let _: () = con.lrem(PROCESSING_KEY, 1, &item).await?;
+                continue;
+            }
+        };
+
+        let claim = claim_key(&batch.id);
+        let _: () = con.set_ex(&claim, 1, VISIBILITY_TIMEOUT).await?;
+        let heartbeat = {
+            let mut con = heartbeat_con.clone();
+            let claim = claim.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+                    let refreshed: redis::RedisResult<()> = con.expire(&claim, VISIBILITY_TIMEOUT as i64).await;
+                    if let Err(e) = refreshed {
+                        console_log(style(format!("Queue heartbeat failed: {}", e)).yellow().to_string());
+                    }
+                }
+            })
+        };
+
+        progress.inc_length(batch.hosts.len() as u64 * ctx.args.ports_per_host());
+        found.extend(scan_hosts(listed_in((0..).zip(batch.hosts), Arc::new(batch.provenance)), ctx.clone(), progress.clone()).await);
+        heartbeat.abort();
+
+        if STOP_SCAN.load(Ordering::Relaxed) {
+            // Partially scanned; hand the whole batch to another worker
+            let _: i64 = redis::Script::new(REQUEUE_SCRIPT)
+                .key(PROCESSING_KEY)
+                .key(QUEUE_KEY)
+                .arg(&item)
+                .invoke_async(&mut con)
+                .await?;
+        } else {
+            let _: () = con.lrem(PROCESSING_KEY, 1, &item).await?;
+        }
+        let _: () = con.del(&claim).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,496 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::asn::AsnTable;
+
+/// Per-subnet burst allowance, in seconds of traffic; an idle subnet bucket is
+/// full again after this long
+const SUBNET_BURST_SECONDS: f64 = 1.0;
+/// The same for an autonomous system's bucket
+const ASN_BURST_SECONDS: f64 = 1.0;
+/// Subnet and ASN buckets are only swept once the map grows past this many entries
+const SWEEP_THRESHOLD: usize = 1024;
+/// Window over which the global dispatch rate is measured
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+/// Window over which received bytes are measured and budgeted
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn full(burst: f64, now: Instant) -> Self {
+        Self { tokens: burst, refilled: now }
+    }
+
+    /// Refills for the time since the last call, capped at `burst`, then takes
+    /// a token or returns how long until one is available.
+    fn take(&mut self, now: Instant, rate: f64, burst: f64) -> Option<Duration> {
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.refilled = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    /// Refills like `take`, then owes enough tokens that none is available
+    /// for `delay`.
+    fn hold(&mut self, now: Instant, rate: f64, burst: f64, delay: Duration) {
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst).min(-delay.as_secs_f64() * rate);
+        self.refilled = now;
+    }
+}
+
+#[derive(Debug)]
+struct Dispatch {
+    bucket: Bucket,
+    window_start: Instant,
+    window_count: u64,
+    rate: f64,
+}
+
+/// Global dispatch limiter: a steady `rate` per second with at most `burst`
+/// requests released back to back. Tokens stop accumulating at `burst`, so a
+/// pause or a slow stretch never turns into a flood afterwards.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<Dispatch>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u32, burst: u32) -> Self {
+        let now = Instant::now();
+        let burst = burst.max(1) as f64;
+        Self {
+            rate: rate as f64,
+            burst,
+            state: Mutex::new(Dispatch {
+                bucket: Bucket::full(burst, now),
+                window_start: now,
+                window_count: 0,
+                rate: 0.0,
+            }),
+        }
+    }
+
+    fn try_acquire(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let wait = state.bucket.take(now, self.rate, self.burst);
+        if wait.is_none() {
+            state.window_count += 1;
+        }
+        let window = now.duration_since(state.window_start);
+        if window >= RATE_WINDOW {
+            state.rate = state.window_count as f64 / window.as_secs_f64();
+            state.window_start = now;
+            state.window_count = 0;
+        }
+        wait
+    }
+
+    /// Waits for the next dispatch slot.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Empties the bucket and the rate window, e.g. after a pause, so dispatch
+    /// resumes at the steady rate instead of with a burst.
+    pub fn restart(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.bucket = Bucket { tokens: 0.0, refilled: now };
+        state.window_start = now;
+        state.window_count = 0;
+        state.rate = 0.0;
+    }
+
+    /// Requests dispatched per second over the last measurement window.
+    pub fn current_rate(&self) -> f64 {
+        self.state.lock().unwrap().rate
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    by_subnet: HashMap<u32, Bucket>,
+    last_sweep: Instant,
+}
+
+/// Caps the request rate toward any single /24 with one token bucket per
+/// subnet. A bucket that has sat idle long enough to refill completely is
+/// indistinguishable from a new one, so idle buckets are evicted to keep the
+/// map bounded by the number of recently active subnets.
+#[derive(Debug)]
+pub struct SubnetLimiter {
+    rate: f64,
+    buckets: Mutex<Buckets>,
+}
+
+fn subnet_of(ip: Ipv4Addr) -> u32 {
+    u32::from(ip) >> 8
+}
+
+impl SubnetLimiter {
+    /// `rate` requests per second per /24.
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            buckets: Mutex::new(Buckets {
+                by_subnet: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token for `ip`'s /24, or returns how long to wait for one.
+    fn try_acquire(&self, ip: Ipv4Addr) -> Option<Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+
+        let refill = Duration::from_secs_f64(SUBNET_BURST_SECONDS);
+        let burst = self.rate * SUBNET_BURST_SECONDS;
+        if buckets.by_subnet.len() > SWEEP_THRESHOLD && now.duration_since(buckets.last_sweep) >= refill {
+            // A held bucket owes tokens, so it takes longer than `refill` to be full
+            let rate = self.rate;
+            buckets.by_subnet.retain(|_, b| b.tokens + now.duration_since(b.refilled).as_secs_f64() * rate < burst);
+            buckets.last_sweep = now;
+        }
+
+        buckets
+            .by_subnet
+            .entry(subnet_of(ip))
+            .or_insert_with(|| Bucket::full(burst, now))
+            .take(now, self.rate, burst)
+    }
+
+    /// Waits until a request toward `ip` fits within its subnet's budget.
+    pub async fn acquire(&self, ip: Ipv4Addr) {
+        while let Some(wait) = self.try_acquire(ip) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Holds requests toward `ip`'s /24 back for `delay`, after the subnet
+    /// answered with a 429.
+    pub fn back_off(&self, ip: Ipv4Addr, delay: Duration) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        let burst = self.rate * SUBNET_BURST_SECONDS;
+        buckets
+            .by_subnet
+            .entry(subnet_of(ip))
+            .or_insert_with(|| Bucket::full(burst, now))
+            .hold(now, self.rate, burst, delay);
+    }
+
+    /// Subnets whose bucket is currently empty.
+    pub fn throttled_subnets(&self) -> usize {
+        let buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        buckets
+            .by_subnet
+            .values()
+            .filter(|b| b.tokens + now.duration_since(b.refilled).as_secs_f64() * self.rate < 1.0)
+            .count()
+    }
+}
+
+#[derive(Debug)]
+struct AsnBucket {
+    bucket: Bucket,
+    /// Requests waiting for a token
+    waiting: usize,
+}
+
+#[derive(Debug)]
+struct AsnBuckets {
+    by_asn: HashMap<u32, AsnBucket>,
+    last_sweep: Instant,
+}
+
+/// Caps the request rate toward any single autonomous system, however many
+/// of its prefixes the targets list, with one token bucket per ASN. Idle
+/// buckets are evicted as the subnet limiter's are, so the map stays bounded
+/// by the number of recently active ASNs. Addresses the table has no ASN for
+/// are not limited.
+pub struct AsnLimiter {
+    table: AsnTable,
+    rate: f64,
+    buckets: Mutex<AsnBuckets>,
+}
+
+impl AsnLimiter {
+    /// `rate` requests per second per ASN.
+    pub fn new(table: AsnTable, rate: u32) -> Self {
+        Self {
+            table,
+            rate: rate as f64,
+            buckets: Mutex::new(AsnBuckets {
+                by_asn: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn table(&self) -> &AsnTable {
+        &self.table
+    }
+
+    /// Takes a token for `asn`, or returns how long to wait for one.
+    fn try_acquire(&self, asn: u32) -> Option<Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+
+        let refill = Duration::from_secs_f64(ASN_BURST_SECONDS);
+        let burst = self.rate * ASN_BURST_SECONDS;
+        if buckets.by_asn.len() > SWEEP_THRESHOLD && now.duration_since(buckets.last_sweep) >= refill {
+            let rate = self.rate;
+            buckets.by_asn.retain(|_, b| b.waiting > 0 || b.bucket.tokens + now.duration_since(b.bucket.refilled).as_secs_f64() * rate < burst);
+            buckets.last_sweep = now;
+        }
+
+        buckets
+            .by_asn
+            .entry(asn)
+            .or_insert_with(|| AsnBucket { bucket: Bucket::full(burst, now), waiting: 0 })
+            .bucket
+            .take(now, self.rate, burst)
+    }
+
+    fn add_waiting(&self, asn: u32, delta: isize) {
+        if let Some(bucket) = self.buckets.lock().unwrap().by_asn.get_mut(&asn) {
+            bucket.waiting = bucket.waiting.saturating_add_signed(delta);
+        }
+    }
+
+    /// Waits until a request toward `ip` fits within its autonomous
+    /// system's budget.
+    pub async fn acquire(&self, ip: Ipv4Addr) {
+        let Some(asn) = self.table.asn(ip) else {
+            return;
+        };
+        let Some(mut wait) = self.try_acquire(asn) else {
+            return;
+        };
+        // Counted as waiting until it gets a token or is dropped
+        struct Waiting<'a>(&'a AsnLimiter, u32);
+        impl Drop for Waiting<'_> {
+            fn drop(&mut self) {
+                self.0.add_waiting(self.1, -1);
+            }
+        }
+        self.add_waiting(asn, 1);
+        let _waiting = Waiting(self, asn);
+        loop {
+            tokio::time::sleep(wait).await;
+            match self.try_acquire(asn) {
+                Some(next) => wait = next,
+                None => return,
+            }
+        }
+    }
+
+    /// The ASNs with the most requests waiting for their budget right now,
+    /// most first.
+    pub fn most_throttled(&self, limit: usize) -> Vec<(u32, usize)> {
+        let buckets = self.buckets.lock().unwrap();
+        let mut waiting: Vec<(u32, usize)> = buckets.by_asn.iter().filter(|(_, b)| b.waiting > 0).map(|(&asn, b)| (asn, b.waiting)).collect();
+        waiting.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        waiting.truncate(limit);
+        waiting
+    }
+}
+
+/// `--jitter`: a random extra delay before each probe the sweep dispatches,
+/// drawn uniformly from a range with a generator seeded from the run's seed,
+/// so a rerun with the same `--seed` waits the same way. The dispatcher waits
+/// it out before taking its rate tokens, so the rate limit stays the
+/// ceiling.
+#[derive(Debug)]
+pub struct Jitter {
+    min: Duration,
+    max: Duration,
+    rng: Mutex<StdRng>,
+}
+
+impl Jitter {
+    pub fn new((min, max): (Duration, Duration), seed: u64) -> Self {
+        Self { min, max, rng: Mutex::new(StdRng::seed_from_u64(seed)) }
+    }
+
+    fn next(&self) -> Duration {
+        self.rng.lock().unwrap().gen_range(self.min..=self.max)
+    }
+
+    /// Waits the next delay. Returns false, early, once the scan is stopped:
+    /// only new probes are delayed, never the draining of those in flight.
+    pub async fn wait(&self) -> bool {
+        let until = tokio::time::Instant::now() + self.next();
+        while tokio::time::Instant::now() < until {
+            if crate::STOP_SCAN.load(Ordering::Relaxed) {
+                return false;
+            }
+            tokio::time::sleep_until(until.min(tokio::time::Instant::now() + Duration::from_millis(100))).await;
+        }
+        !crate::STOP_SCAN.load(Ordering::Relaxed)
+    }
+
+    /// Probes per second the jitter alone lets through on average.
+    pub fn ceiling(&self) -> f64 {
+        1.0 / ((self.min + self.max) / 2).as_secs_f64()
+    }
+
+    /// "0-250ms", for the banner.
+    pub fn describe(&self) -> String {
+        format!("{}-{}ms", self.min.as_millis(), self.max.as_millis())
+    }
+}
+
+/// Bytes received from targets: the total, the rate over the last second
+/// and, with `--max-bandwidth`, a budget that holds back dispatch while the
+/// last second has used it up. An answer's size is only known once it has
+/// arrived, so requests already in flight can overshoot the budget briefly.
+#[derive(Debug, Default)]
+pub struct BandwidthMeter {
+    limit: Option<u64>,
+    total: AtomicU64,
+    /// Answers of the last window: when they arrived and their size
+    window: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl BandwidthMeter {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self { limit, ..Self::default() }
+    }
+
+    pub fn record(&self, bytes: u64) {
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+        self.window.lock().unwrap().push_back((Instant::now(), bytes));
+    }
+
+    /// Bytes received over the last window, and when the oldest of them
+    /// leaves it.
+    fn used(&self, now: Instant) -> (u64, Option<Instant>) {
+        let mut window = self.window.lock().unwrap();
+        while window.front().is_some_and(|(at, _)| now.duration_since(*at) >= BANDWIDTH_WINDOW) {
+            window.pop_front();
+        }
+        let used = window.iter().map(|(_, bytes)| bytes).sum();
+        (used, window.front().map(|(at, _)| *at + BANDWIDTH_WINDOW))
+    }
+
+    /// Waits until the last window has received less than the limit.
+    pub async fn acquire(&self) {
+        let Some(limit) = self.limit else {
+            return;
+        };
+        loop {
+            let now = Instant::now();
+            match self.used(now) {
+                (used, Some(frees_up)) if used >= limit => tokio::time::sleep(frees_up - now).await,
+                _ => return,
+            }
+        }
+    }
+
+    /// Bytes per second over the last window.
+    pub fn current_rate(&self) -> u64 {
+        self.used(Instant::now()).0
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+/// A byte count for the console, e.g. "812 B", "4.2 MB".
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bandwidth_budget_frees_up_as_the_window_moves() {
+        let meter = BandwidthMeter::new(Some(1000));
+        meter.record(600);
+        meter.record(600);
+        assert_eq!(meter.current_rate(), 1200);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let started = Instant::now();
+        runtime.block_on(meter.acquire());
+        assert!(started.elapsed() >= Duration::from_millis(900), "{:?}", started.elapsed());
+        assert_eq!(meter.current_rate(), 0);
+        assert_eq!(meter.total(), 1200);
+    }
+
+    #[test]
+    fn formats_byte_counts() {
+        assert_eq!(format_bytes(812), "812 B");
+        assert_eq!(format_bytes(3 << 19), "1.5 MB");
+        assert_eq!(format_bytes(5 << 30), "5.0 GB");
+    }
+
+    #[test]
+    fn jitter_stays_in_its_range_and_follows_the_seed() {
+        let range = (Duration::from_millis(20), Duration::from_millis(30));
+        let (a, b) = (Jitter::new(range, 7), Jitter::new(range, 7));
+        let delays: Vec<Duration> = (0..50).map(|_| a.next()).collect();
+        assert!(delays.iter().all(|d| (range.0..=range.1).contains(d)), "{:?}", delays);
+        assert_eq!(delays, (0..50).map(|_| b.next()).collect::<Vec<_>>());
+        assert_eq!(a.ceiling().round(), 40.0);
+        assert_eq!(a.describe(), "20-30ms");
+    }
+
+    #[test]
+    fn asn_budget_is_shared_by_all_of_its_prefixes() {
+        let table = AsnTable::from_prefix_table("10.0.0.0/24 64500\n10.9.0.0/24 64500\n10.1.0.0/24 64501\n");
+        let limiter = std::sync::Arc::new(AsnLimiter::new(table, 2));
+        assert!(limiter.try_acquire(64500).is_none());
+        assert!(limiter.try_acquire(64500).is_none());
+        assert!(limiter.try_acquire(64500).is_some());
+        assert!(limiter.try_acquire(64501).is_none());
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        runtime.block_on(async {
+            let waiting = limiter.clone();
+            let task = tokio::spawn(async move { waiting.acquire(Ipv4Addr::new(10, 9, 0, 7)).await });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(limiter.most_throttled(3), [(64500, 1)]);
+            task.await.unwrap();
+        });
+        assert!(limiter.most_throttled(3).is_empty());
+        // Not in the table, so not limited
+        runtime.block_on(limiter.acquire(Ipv4Addr::new(192, 0, 2, 1)));
+    }
+}
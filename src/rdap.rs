@@ -0,0 +1,284 @@
+//! `--abuse-contacts`: looks up who answers for each endpoint's address in
+//! RDAP, through the rdap.org bootstrap by default, and writes the abuse
+//! contact and network to abuse_contacts.csv for notifying the owners.
+//!
+//! Lookups run as their own queue behind the sweep, two at a time, since
+//! RDAP servers rate-limit hard. A 429 holds every lookup back for its
+//! Retry-After, or a growing delay without one. Answers are cached per
+//! network and per /24, so the hosts of one provider cost one query. An
+//! address without a contact, or whose lookup failed, still gets a row, with
+//! the contact fields empty and a note saying why.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde_json::Value;
+use tokio::sync::{mpsc, OnceCell, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+
+use crate::dns::DnsResolver;
+use crate::writer::{AbuseRecord, WriteEvent, WriteSender};
+use crate::STOP_SCAN;
+
+pub const DEFAULT_SERVER: &str = "https://rdap.org";
+const RDAP_TIMEOUT: Duration = Duration::from_secs(15);
+/// Lookups in flight at once
+const RDAP_CONCURRENCY: usize = 2;
+const ATTEMPTS: u32 = 5;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// An endpoint to find the contact of.
+#[derive(Debug, Clone)]
+pub struct RdapJob {
+    pub ip: Ipv4Addr,
+    pub base_url: String,
+}
+
+pub type RdapSender = mpsc::UnboundedSender<RdapJob>;
+
+/// What RDAP says about an address's network.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Contact {
+    abuse_email: String,
+    network: String,
+    registrant: String,
+    /// "first - last" address of the network
+    range: String,
+    /// Why fields are empty, if they are
+    note: String,
+}
+
+impl Contact {
+    fn note(note: impl Into<String>) -> Self {
+        Contact { note: note.into(), ..Contact::default() }
+    }
+}
+
+struct Lookups {
+    client: reqwest::Client,
+    server: String,
+    writer: WriteSender,
+    slots: Semaphore,
+    /// Networks answered so far, as first and last address
+    networks: Mutex<Vec<(u32, u32, Contact)>>,
+    /// One lookup per /24; the other addresses in it wait for its answer
+    subnets: Mutex<HashMap<u32, Arc<OnceCell<Contact>>>>,
+    /// No request goes out before this, after a 429
+    held_until: Mutex<Option<Instant>>,
+}
+
+impl Lookups {
+    async fn record(&self, job: RdapJob) {
+        let contact = match STOP_SCAN.load(Ordering::Relaxed) {
+            true => Contact::note("not looked up, scan stopped"),
+            false => self.contact(job.ip).await,
+        };
+        let record = AbuseRecord {
+            base_url: job.base_url,
+            abuse_email: contact.abuse_email,
+            network: contact.network,
+            registrant: contact.registrant,
+            range: contact.range,
+            note: contact.note,
+        };
+        let _ = self.writer.send(WriteEvent::Abuse(record)).await;
+    }
+
+    fn known(&self, ip: Ipv4Addr) -> Option<Contact> {
+        let ip = u32::from(ip);
+        let networks = self.networks.lock().unwrap();
+        networks.iter().find(|(first, last, _)| (*first..=*last).contains(&ip)).map(|(_, _, contact)| contact.clone())
+    }
+
+    async fn contact(&self, ip: Ipv4Addr) -> Contact {
+        if let Some(contact) = self.known(ip) {
+            return contact;
+        }
+        let subnet = self.subnets.lock().unwrap().entry(u32::from(ip) >> 8).or_default().clone();
+        subnet
+            .get_or_init(|| async {
+                // A lookup for another /24 of the network may have answered meanwhile
+                match self.known(ip) {
+                    Some(contact) => contact,
+                    None => self.look_up(ip).await,
+                }
+            })
+            .await
+            .clone()
+    }
+
+    async fn look_up(&self, ip: Ipv4Addr) -> Contact {
+        let _slot = self.slots.acquire().await.expect("semaphore is never closed");
+        let url = format!("{}/ip/{}", self.server.trim_end_matches('/'), ip);
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut pause = Duration::ZERO;
+        let mut failure = String::new();
+        for _ in 0..ATTEMPTS {
+            self.wait_if_held(pause).await;
+            pause = delay;
+            let response = match self.client.get(&url).header(reqwest::header::ACCEPT, "application/rdap+json").send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    failure = format!("lookup failed: {}", e);
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+            };
+            match response.status() {
+                StatusCode::TOO_MANY_REQUESTS => {
+                    // Held for every lookup, not just this one
+                    let wait = retry_after(&response).unwrap_or(delay);
+                    let mut held = self.held_until.lock().unwrap();
+                    *held = Some(held.unwrap_or_else(Instant::now).max(Instant::now() + wait));
+                    pause = Duration::ZERO;
+                    failure = "RDAP kept answering 429".to_string();
+                }
+                StatusCode::NOT_FOUND => return Contact::note("no RDAP record"),
+                status if status.is_server_error() => failure = format!("RDAP answered {}", status.as_u16()),
+                status if !status.is_success() => return Contact::note(format!("RDAP answered {}", status.as_u16())),
+                _ => {
+                    return match response.json::<Value>().await {
+                        Ok(answer) => {
+                            let (contact, network) = parse(&answer);
+                            if let Some((first, last)) = network {
+                                self.networks.lock().unwrap().push((first, last, contact.clone()));
+                            }
+                            contact
+                        }
+                        Err(_) => Contact::note("RDAP answer not understood"),
+                    };
+                }
+            }
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+        Contact::note(failure)
+    }
+
+    /// Waits out a 429 hold, and at least `pause`.
+    async fn wait_if_held(&self, pause: Duration) {
+        let held = *self.held_until.lock().unwrap();
+        let until = held.unwrap_or_else(Instant::now).max(Instant::now() + pause);
+        tokio::time::sleep_until(until).await;
+    }
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let seconds = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_DELAY))
+}
+
+/// The first entity with `role`, looking into nested entities too; ARIN
+/// lists the abuse contact under the organization.
+fn entity_with_role<'a>(entities: &'a Value, role: &str) -> Option<&'a Value> {
+    let entities = entities.as_array()?;
+    entities
+        .iter()
+        .find(|entity| entity["roles"].as_array().is_some_and(|roles| roles.iter().any(|r| r == role)))
+        .or_else(|| entities.iter().find_map(|entity| entity_with_role(&entity["entities"], role)))
+}
+
+/// A property of an entity's jCard, e.g. "fn" or "email".
+fn vcard(entity: &Value, property: &str) -> Option<String> {
+    entity["vcardArray"][1]
+        .as_array()?
+        .iter()
+        .find(|field| field[0] == property)
+        .and_then(|field| field[3].as_str())
+        .map(str::to_string)
+}
+
+/// The contact in an RDAP IP network answer, and the network's range.
+fn parse(answer: &Value) -> (Contact, Option<(u32, u32)>) {
+    let entities = &answer["entities"];
+    let abuse_email = entity_with_role(entities, "abuse").and_then(|entity| vcard(entity, "email")).unwrap_or_default();
+    let address = |key: &str| answer[key].as_str().and_then(|a| a.parse::<Ipv4Addr>().ok()).map(u32::from);
+    let network = address("startAddress").zip(address("endAddress"));
+    let contact = Contact {
+        note: if abuse_email.is_empty() { "no abuse contact listed".to_string() } else { String::new() },
+        abuse_email,
+        network: answer["name"].as_str().unwrap_or_default().to_string(),
+        registrant: entity_with_role(entities, "registrant").and_then(|entity| vcard(entity, "fn")).unwrap_or_default(),
+        range: network.map(|(first, last)| format!("{} - {}", Ipv4Addr::from(first), Ipv4Addr::from(last))).unwrap_or_default(),
+    };
+    (contact, network)
+}
+
+/// Starts the lookup queue. It ends once the returned sender is dropped and
+/// every queued endpoint has its row; on a stop, lookups in flight finish
+/// and the rest are written without one.
+pub fn spawn_lookups(server: &str, writer: WriteSender, dns: Option<&Arc<DnsResolver>>) -> Result<(RdapSender, JoinHandle<()>)> {
+    let client = DnsResolver::apply(dns, reqwest::Client::builder())
+        .timeout(RDAP_TIMEOUT)
+        .user_agent(concat!("public-ollama-finder/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let lookups = Arc::new(Lookups {
+        client,
+        server: server.to_string(),
+        writer,
+        slots: Semaphore::new(RDAP_CONCURRENCY),
+        networks: Mutex::default(),
+        subnets: Mutex::default(),
+        held_until: Mutex::default(),
+    });
+    // Unbounded so handing off a job never blocks a probe; there is one per hit
+    let (tx, mut rx) = mpsc::unbounded_channel::<RdapJob>();
+    let handle = tokio::spawn(async move {
+        let mut endpoints = JoinSet::new();
+        while let Some(job) = rx.recv().await {
+            let lookups = lookups.clone();
+            endpoints.spawn(async move { lookups.record(job).await });
+            while endpoints.try_join_next().is_some() {}
+        }
+        while endpoints.join_next().await.is_some() {}
+    });
+    Ok((tx, handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_the_abuse_contact_under_the_organization() {
+        // Shaped like ARIN's answers
+        let answer = json!({
+            "objectClassName": "ip network",
+            "name": "EXAMPLE-NET",
+            "startAddress": "203.0.113.0",
+            "endAddress": "203.0.113.255",
+            "entities": [{
+                "roles": ["registrant"],
+                "vcardArray": ["vcard", [["version", {}, "text", "4.0"], ["fn", {}, "text", "Example Hosting"]]],
+                "entities": [{
+                    "roles": ["abuse"],
+                    "vcardArray": ["vcard", [["fn", {}, "text", "Abuse"], ["email", {}, "text", "abuse@example.net"]]]
+                }]
+            }]
+        });
+        let (contact, network) = parse(&answer);
+        assert_eq!(contact, Contact {
+            abuse_email: "abuse@example.net".to_string(),
+            network: "EXAMPLE-NET".to_string(),
+            registrant: "Example Hosting".to_string(),
+            range: "203.0.113.0 - 203.0.113.255".to_string(),
+            note: String::new(),
+        });
+        assert_eq!(network, Some((u32::from(Ipv4Addr::new(203, 0, 113, 0)), u32::from(Ipv4Addr::new(203, 0, 113, 255)))));
+    }
+
+    #[test]
+    fn notes_a_missing_contact() {
+        let (contact, network) = parse(&json!({"name": "NO-CONTACT", "entities": []}));
+        assert_eq!(contact.abuse_email, "");
+        assert_eq!(contact.note, "no abuse contact listed");
+        assert_eq!(network, None);
+    }
+}
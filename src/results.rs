@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::Path;
+
+use crate::compress;
+
+/// A row of llm_models.csv as read back from disk. Columns are looked up by
+/// header name so files written by older versions (fewer columns) still load;
+/// missing columns come back empty.
+#[derive(Debug, Clone, Default)]
+#[allow(dead_code)]
+pub struct ModelRow {
+    pub endpoint: String,
+    pub name: String,
+    pub model: String,
+    pub modified_at: String,
+    pub size_gb: f64,
+    pub digest: String,
+    pub parent_model: String,
+    pub format: String,
+    pub family: String,
+    pub parameter_size: String,
+    pub quantization_level: String,
+}
+
+/// A row of ollama_endpoints.csv as read back from disk.
+#[derive(Debug, Clone, Default)]
+#[allow(dead_code)]
+pub struct EndpointRow {
+    pub endpoint: String,
+    pub tags_url: String,
+    pub status: String,
+    pub location: String,
+    pub source: String,
+    pub run_id: String,
+    pub discovered_at: String,
+    pub provider: String,
+}
+
+/// A row of abuse_contacts.csv as read back from disk.
+#[derive(Debug, Clone, Default)]
+pub struct AbuseContactRow {
+    pub endpoint: String,
+    pub abuse_email: String,
+    pub network: String,
+    pub registrant: String,
+}
+
+/// Reduces "http://1.2.3.4:11434/" and "1.2.3.4:11434" to the same key.
+pub fn endpoint_key(endpoint: &str) -> String {
+    let endpoint = endpoint.trim();
+    let endpoint = endpoint
+        .strip_prefix("http://")
+        .or_else(|| endpoint.strip_prefix("https://"))
+        .unwrap_or(endpoint);
+    endpoint.trim_end_matches('/').to_ascii_lowercase()
+}
+
+struct HeaderIndex(csv::StringRecord);
+
+impl HeaderIndex {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.0.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+    }
+
+    fn get(&self, record: &csv::StringRecord, name: &str) -> String {
+        self.position(name)
+            .and_then(|i| record.get(i))
+            .unwrap_or("")
+            .trim()
+            .to_string()
+    }
+}
+
+/// Opens a CSV written by any version of the tool, gzipped or not.
+pub fn csv_reader(path: &Path) -> Result<csv::Reader<Box<dyn Read>>> {
+    let input = compress::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(csv::ReaderBuilder::new().flexible(true).from_reader(input))
+}
+
+/// Parses sizes as written by any version of the tool: "12.34", "12.34 GB".
+pub fn parse_size_gb(value: &str) -> f64 {
+    let value = value.trim();
+    let number = value
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .trim();
+    let gb: f64 = number.parse().unwrap_or(0.0);
+    match value.to_ascii_uppercase() {
+        v if v.ends_with("MB") => gb / 1024.0,
+        v if v.ends_with("KB") => gb / (1024.0 * 1024.0),
+        _ => gb,
+    }
+}
+
+pub fn read_model_rows(path: &Path) -> Result<Vec<ModelRow>> {
+    let mut reader = csv_reader(path)?;
+    let headers = HeaderIndex(reader.headers()?.clone());
+    if headers.position("IP:Port").is_none() || headers.position("Model Name").is_none() {
+        anyhow::bail!("{} is missing the 'IP:Port' or 'Model Name' column", path.display());
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        rows.push(ModelRow {
+            endpoint: headers.get(&record, "IP:Port"),
+            name: headers.get(&record, "Model Name"),
+            model: headers.get(&record, "Model"),
+            modified_at: headers.get(&record, "Modified At"),
+            size_gb: parse_size_gb(&headers.get(&record, "Size")),
+            digest: headers.get(&record, "Digest"),
+            parent_model: headers.get(&record, "Parent Model"),
+            format: headers.get(&record, "Format"),
+            family: headers.get(&record, "Family"),
+            parameter_size: headers.get(&record, "Parameter Size"),
+            quantization_level: headers.get(&record, "Quantization Level"),
+        });
+    }
+    Ok(rows)
+}
+
+pub fn read_endpoint_rows(path: &Path) -> Result<Vec<EndpointRow>> {
+    let mut reader = csv_reader(path)?;
+    let headers = HeaderIndex(reader.headers()?.clone());
+    // Very old files may carry different header names; the endpoint is always first
+    let endpoint_column = headers.position("IP:Port").unwrap_or(0);
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        rows.push(EndpointRow {
+            endpoint: record.get(endpoint_column).unwrap_or("").trim().to_string(),
+            tags_url: headers.get(&record, "Tags URL"),
+            status: headers.get(&record, "Status Code"),
+            location: headers.get(&record, "Location"),
+            source: headers.get(&record, "Source"),
+            run_id: headers.get(&record, "Run ID"),
+            discovered_at: headers.get(&record, "Discovered At"),
+            provider: headers.get(&record, "Provider"),
+        });
+    }
+    Ok(rows)
+}
+
+pub fn read_abuse_contact_rows(path: &Path) -> Result<Vec<AbuseContactRow>> {
+    let mut reader = csv_reader(path)?;
+    let headers = HeaderIndex(reader.headers()?.clone());
+    if headers.position("IP:Port").is_none() || headers.position("Abuse Email").is_none() {
+        anyhow::bail!("{} is missing the 'IP:Port' or 'Abuse Email' column", path.display());
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        rows.push(AbuseContactRow {
+            endpoint: headers.get(&record, "IP:Port"),
+            abuse_email: headers.get(&record, "Abuse Email"),
+            network: headers.get(&record, "Network"),
+            registrant: headers.get(&record, "Registrant"),
+        });
+    }
+    Ok(rows)
+}
@@ -0,0 +1,67 @@
+//! Hosts whose Ollama request timed out or was reset, kept for a second pass
+//! after the sweep. Refused connections are not kept: nothing listens there.
+
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::targets::Provenance;
+
+#[derive(Debug)]
+pub struct RetryQueue {
+    capacity: usize,
+    hosts: Mutex<Vec<(Ipv4Addr, Arc<Provenance>)>>,
+}
+
+impl RetryQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, hosts: Mutex::new(Vec::new()) }
+    }
+
+    /// Queues `ip`, or returns false when the queue is full.
+    pub fn push(&self, ip: Ipv4Addr, provenance: Arc<Provenance>) -> bool {
+        let mut hosts = self.hosts.lock().unwrap();
+        if hosts.len() >= self.capacity {
+            return false;
+        }
+        hosts.push((ip, provenance));
+        true
+    }
+
+    /// Empties the queue, grouping hosts of the same target in the order they
+    /// were queued.
+    pub fn take(&self) -> Vec<(Arc<Provenance>, Vec<Ipv4Addr>)> {
+        let hosts = std::mem::take(&mut *self.hosts.lock().unwrap());
+        let mut groups: Vec<(Arc<Provenance>, Vec<Ipv4Addr>)> = Vec::new();
+        for (ip, provenance) in hosts {
+            match groups.iter_mut().find(|(group, _)| Arc::ptr_eq(group, &provenance)) {
+                Some((_, ips)) => ips.push(ip),
+                None => groups.push((provenance, vec![ip])),
+            }
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_the_queue_and_groups_by_target() {
+        let queue = RetryQueue::new(3);
+        let (a, b) = (Arc::new(Provenance::default()), Arc::new(Provenance::default()));
+        let ip = |last: u8| Ipv4Addr::new(10, 0, 0, last);
+        assert!(queue.push(ip(1), a.clone()));
+        assert!(queue.push(ip(2), b.clone()));
+        assert!(queue.push(ip(3), a.clone()));
+        assert!(!queue.push(ip(4), b.clone()));
+
+        let groups = queue.take();
+        assert_eq!(groups.len(), 2);
+        assert!(Arc::ptr_eq(&groups[0].0, &a));
+        assert_eq!(groups[0].1, [ip(1), ip(3)]);
+        assert_eq!(groups[1].1, [ip(2)]);
+        assert!(queue.take().is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! `--rotate daily` / `--rotate-size`: which file each output writes to.
+//! A name depends only on the base name, the UTC date and a roll number, so
+//! a restarted scan carries on in the file it left off in. A run moved aside
+//! by `--force-suffix` adds its `-N` to the base name first.
+
+use chrono::{NaiveDate, Utc};
+use clap::ValueEnum;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RotatePeriod {
+    /// A new file every day at midnight UTC
+    Daily,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rotation {
+    pub daily: bool,
+    /// Roll to the next numbered file once the active one reaches this many bytes
+    pub max_size: Option<u64>,
+    /// Set when another scan holds the usual names, see `outputlock`
+    pub suffix: Option<u32>,
+    /// `--compress`: the CSVs are `.csv.gz`
+    pub compress: bool,
+}
+
+impl Rotation {
+    pub fn is_off(&self) -> bool {
+        !self.daily && self.max_size.is_none()
+    }
+
+    /// The day the active file is named after, if rotating daily.
+    pub fn period(&self) -> Option<NaiveDate> {
+        self.daily.then(|| Utc::now().date_naive())
+    }
+
+    pub fn is_full(&self, size: u64) -> bool {
+        self.max_size.is_some_and(|max| size >= max)
+    }
+
+    /// Roll number of the file to append to in `period`: the last one
+    /// written, or the one after it if that is full.
+    pub fn append_index(&self, base: &Path, period: Option<NaiveDate>) -> u32 {
+        let mut index = 0;
+        while rotated_path(base, period, index + 1).exists() {
+            index += 1;
+        }
+        let size = fs::metadata(rotated_path(base, period, index)).map_or(0, |m| m.len());
+        if self.is_full(size) {
+            index += 1;
+        }
+        index
+    }
+
+    /// Roll number of the first file of `period` that does not exist yet, for
+    /// outputs that cannot be appended to.
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    pub fn fresh_index(&self, base: &Path, period: Option<NaiveDate>) -> u32 {
+        (0..).find(|index| !rotated_path(base, period, *index).exists()).expect("some index is free")
+    }
+
+    /// The name this run uses for the CSV `base`, before any rotation.
+    pub fn base(&self, base: &Path) -> PathBuf {
+        let base = suffixed(base, self.suffix);
+        match self.compress {
+            true => crate::compress::gz_path(&base),
+            false => base,
+        }
+    }
+
+    /// "daily, every 100 MB" for the banner.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.daily {
+            parts.push("daily".to_string());
+        }
+        if let Some(max) = self.max_size {
+            parts.push(format!("every {}", format_size(max)));
+        }
+        parts.join(", ")
+    }
+}
+
+/// The name of `base` without and with its extension, which for a `.gz`
+/// includes the one before it: results and .csv.gz.
+fn split_name(base: &Path) -> (String, String) {
+    let name = base.file_name().unwrap_or_default().to_string_lossy();
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let stem = match crate::compress::is_gzip(base) {
+        true => Path::new(stem.as_ref()).file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        false => stem.into_owned(),
+    };
+    let extension = name[stem.len()..].to_string();
+    (stem, extension)
+}
+
+/// `base` with `-N` before its extension: results-2.csv.
+pub fn suffixed(base: &Path, suffix: Option<u32>) -> PathBuf {
+    match suffix {
+        Some(n) => {
+            let (stem, extension) = split_name(base);
+            base.with_file_name(format!("{}-{}{}", stem, n, extension))
+        }
+        None => base.to_path_buf(),
+    }
+}
+
+/// `base` for `period` and roll `index`: results.csv, results.1.csv,
+/// results-2025-01-15.csv, results-2025-01-15.2.csv.gz.
+pub fn rotated_path(base: &Path, period: Option<NaiveDate>, index: u32) -> PathBuf {
+    let (mut name, extension) = split_name(base);
+    if let Some(day) = period {
+        name.push_str(&day.format("-%Y-%m-%d").to_string());
+    }
+    if index > 0 {
+        name.push_str(&format!(".{}", index));
+    }
+    name.push_str(&extension);
+    base.with_file_name(name)
+}
+
+/// Parses sizes such as "100MB", "1.5GB", "512KB" or a plain byte count.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid size '{}'", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("invalid size unit in '{}', expected KB, MB or GB", value)),
+    };
+    match (number * multiplier as f64) as u64 {
+        0 => Err(format!("size '{}' must be more than 0", value)),
+        bytes => Ok(bytes),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 && b % (1 << 30) == 0 => format!("{} GB", b >> 30),
+        b if b >= 1 << 20 && b % (1 << 20) == 0 => format!("{} MB", b >> 20),
+        b if b >= 1 << 10 && b % (1 << 10) == 0 => format!("{} KB", b >> 10),
+        b => format!("{} bytes", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_files_by_day_and_roll() {
+        let base = Path::new("ollama_endpoints.csv");
+        let day = NaiveDate::from_ymd_opt(2025, 1, 15);
+        assert_eq!(rotated_path(base, None, 0), Path::new("ollama_endpoints.csv"));
+        assert_eq!(rotated_path(base, None, 2), Path::new("ollama_endpoints.2.csv"));
+        assert_eq!(rotated_path(base, day, 0), Path::new("ollama_endpoints-2025-01-15.csv"));
+        assert_eq!(rotated_path(base, day, 3), Path::new("ollama_endpoints-2025-01-15.3.csv"));
+        assert_eq!(rotated_path(Path::new("out/results.parquet"), day, 1), Path::new("out/results-2025-01-15.1.parquet"));
+        let rotation = Rotation { daily: true, max_size: None, suffix: Some(2), compress: false };
+        assert_eq!(rotated_path(&rotation.base(base), day, 1), Path::new("ollama_endpoints-2-2025-01-15.1.csv"));
+        assert_eq!(suffixed(Path::new("ollama-finder.lock"), Some(3)), Path::new("ollama-finder-3.lock"));
+        assert_eq!(suffixed(base, None), base);
+        let compressed = Rotation { daily: false, max_size: Some(1), suffix: Some(2), compress: true };
+        assert_eq!(rotated_path(&compressed.base(base), None, 1), Path::new("ollama_endpoints-2.1.csv.gz"));
+        assert_eq!(rotated_path(Path::new("events.gz"), None, 1), Path::new("events.1.gz"));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("100MB"), Ok(100 << 20));
+        assert_eq!(parse_size("1.5GB"), Ok(3 << 29));
+        assert_eq!(parse_size("512kb"), Ok(512 << 10));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("0MB").is_err());
+        assert_eq!(Rotation { daily: true, max_size: Some(100 << 20), suffix: None, compress: false }.describe(), "daily, every 100 MB");
+    }
+
+    #[test]
+    fn continues_in_the_last_file_until_it_is_full() {
+        let dir = std::env::temp_dir().join(format!("pof-rotate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("llm_models.csv");
+        let rotation = Rotation { daily: false, max_size: Some(10), suffix: None, compress: false };
+        assert_eq!(rotation.append_index(&base, None), 0);
+        fs::write(&base, "0123456789").unwrap();
+        fs::write(rotated_path(&base, None, 1), "0123").unwrap();
+        assert_eq!(rotation.append_index(&base, None), 1);
+        assert_eq!(rotation.fresh_index(&base, None), 2);
+        fs::write(rotated_path(&base, None, 1), "0123456789").unwrap();
+        assert_eq!(rotation.append_index(&base, None), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
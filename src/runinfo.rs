@@ -0,0 +1,182 @@
+//! `run-<run id>.json`: the settings a run started with and how it ended, so
+//! rows carrying a Run ID can be traced back to what produced them.
+//!
+//! The file is written when the run starts and rewritten with the closing
+//! fields once it has finished or been stopped with `q`/Ctrl+C/SIGTERM; a run
+//! that was killed keeps only the opening fields.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::ScanArgs;
+use crate::probes::Probe;
+use crate::provider::{FeedInfo, ProviderIndex};
+use crate::{input_files, CONCURRENT_LIMIT, RATE_LIMIT_PER_SECOND};
+
+/// An input file and a hash of its content at the start of the run.
+#[derive(Debug, Serialize)]
+pub struct InputSource {
+    pub path: String,
+    /// FNV-1a 64 of the content, or None if it could not be read
+    pub fnv1a: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateLimit {
+    pub per_second: u32,
+    pub burst: u32,
+    /// Per /24, 0 when off
+    pub per_subnet: u32,
+    /// Per autonomous system, 0 when off
+    pub per_asn: u32,
+    /// `--jitter` range in milliseconds
+    pub jitter_ms: Option<[u64; 2]>,
+    /// Bytes per second received, with --max-bandwidth
+    pub bytes_per_second: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunInfo {
+    pub run_id: String,
+    /// `--label`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub version: &'static str,
+    pub started_at: String,
+    /// The command line after the program name, as given
+    pub arguments: Vec<String>,
+    pub inputs: Vec<InputSource>,
+    /// Hosts planned after sampling and sharding
+    pub total_targets: u64,
+    pub ports: Vec<u16>,
+    pub concurrency: String,
+    pub rate_limit: RateLimit,
+    pub connect_timeout_ms: u64,
+    pub read_timeout_ms: u64,
+    pub seed: u64,
+    pub sample: Option<String>,
+    pub shard: Option<String>,
+    pub verification: String,
+    /// Where the Provider column's ranges came from and when they were fetched
+    pub provider_feeds: Vec<FeedInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scanned: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hits: Option<u64>,
+    /// "completed", or "stopped" after `q`/Ctrl+C/SIGTERM or a queue error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub termination: Option<&'static str>,
+}
+
+impl RunInfo {
+    pub fn start(
+        run_id: &str,
+        args: &ScanArgs,
+        probes: &[Box<dyn Probe>],
+        providers: &ProviderIndex,
+        seed: u64,
+        total_targets: u64,
+    ) -> Self {
+        let mut ports: Vec<u16> = probes.iter().flat_map(|probe| probe.default_ports()).copied().collect();
+        ports.sort_unstable();
+        ports.dedup();
+        Self {
+            run_id: run_id.to_string(),
+            tag: args.label.clone(),
+            version: env!("CARGO_PKG_VERSION"),
+            started_at: now(),
+            arguments: std::env::args().skip(1).collect(),
+            inputs: input_sources(args),
+            total_targets,
+            ports,
+            concurrency: match args.concurrency_fixed {
+                Some(limit) => format!("fixed at {}", limit),
+                None => format!("adaptive, up to {}", CONCURRENT_LIMIT),
+            },
+            rate_limit: RateLimit {
+                per_second: RATE_LIMIT_PER_SECOND,
+                burst: args.burst,
+                per_subnet: args.subnet_rate,
+                per_asn: args.max_rate_per_asn.unwrap_or(0),
+                jitter_ms: args.jitter.map(|(min, max)| [min.as_millis() as u64, max.as_millis() as u64]),
+                bytes_per_second: args.max_bandwidth,
+            },
+            connect_timeout_ms: args.connect_timeout.as_millis() as u64,
+            read_timeout_ms: args.read_timeout.as_millis() as u64,
+            seed,
+            sample: args.sample_plan().map(|plan| plan.describe()),
+            shard: args.shard.map(|shard| shard.to_string()),
+            verification: format!("{:?}", args.verification()).to_lowercase(),
+            provider_feeds: providers.feeds().to_vec(),
+            ended_at: None,
+            scanned: None,
+            hits: None,
+            termination: None,
+        }
+    }
+
+    pub fn finish(&mut self, scanned: u64, hits: u64, stopped: bool) {
+        self.ended_at = Some(now());
+        self.scanned = Some(scanned);
+        self.hits = Some(hits);
+        self.termination = Some(if stopped { "stopped" } else { "completed" });
+    }
+
+    pub fn path(&self) -> PathBuf {
+        crate::outdir::path(format!("run-{}.json", self.run_id))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = self.path();
+        let json = serde_json::to_string_pretty(self)?;
+        // Replaced in one step so the file never holds half of either version
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json + "\n").with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// The files `load_ranges` reads, in the same order.
+fn input_sources(args: &ScanArgs) -> Vec<InputSource> {
+    let files = match args.input.is_empty() {
+        true => vec![PathBuf::from("ip-ranges.txt")],
+        false => input_files(&args.input).into_iter().filter_map(Result::ok).collect(),
+    };
+    #[cfg(feature = "redis")]
+    if args.queue_pull.is_some() {
+        return Vec::new();
+    }
+    files.iter().map(|path| source(path)).collect()
+}
+
+fn source(path: &Path) -> InputSource {
+    InputSource {
+        path: path.display().to_string(),
+        fnv1a: fs::read(path).ok().map(|content| format!("{:016x}", fnv1a(&content))),
+    }
+}
+
+/// FNV-1a 64: stable across builds and platforms, unlike std's hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_with_published_fnv1a_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
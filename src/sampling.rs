@@ -0,0 +1,98 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::net::Ipv4Addr;
+
+use crate::targets::Target;
+
+/// Probe a reproducible random subset of each range instead of every host.
+#[derive(Debug, Clone, Copy)]
+pub enum SamplePlan {
+    Fraction(f64),
+    Count(u64),
+}
+
+impl SamplePlan {
+    pub fn sample_size(&self, hosts: u64) -> u64 {
+        match *self {
+            SamplePlan::Fraction(fraction) => ((hosts as f64 * fraction).round() as u64).min(hosts),
+            SamplePlan::Count(count) => count.min(hosts),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            SamplePlan::Fraction(fraction) => format!("{}% of each range", fraction * 100.0),
+            SamplePlan::Count(count) => format!("{} hosts per range", count),
+        }
+    }
+}
+
+/// Hosts of `target` selected by `plan`, in ascending address order. The
+/// sample is drawn over all of the range's blocks at once. The RNG is seeded
+/// per range so the selection does not depend on the order in which ranges
+/// are scanned.
+pub fn sampled_hosts(target: &Target, plan: SamplePlan, seed: u64) -> Vec<Ipv4Addr> {
+    let Some(first_block) = target.blocks.first() else {
+        return Vec::new();
+    };
+    let total = target.host_count();
+    let amount = plan.sample_size(total) as usize;
+
+    let network = first_block.network;
+    let range_seed = seed ^ ((u32::from(network.network()) as u64) << 8 | network.prefix_len() as u64);
+    let mut rng = StdRng::seed_from_u64(range_seed);
+    let mut indices = rand::seq::index::sample(&mut rng, total as usize, amount).into_vec();
+    indices.sort_unstable();
+
+    // Walk the blocks alongside the sorted indices; each block's hosts are contiguous
+    let mut selected = Vec::with_capacity(amount);
+    let mut indices = indices.into_iter().map(|i| i as u64).peekable();
+    let mut offset = 0u64;
+    for block in &target.blocks {
+        let mut hosts = block.hosts();
+        let Some(first) = hosts.next() else { continue };
+        let size = block.hosts().count() as u64;
+        while let Some(index) = indices.next_if(|i| *i < offset + size) {
+            selected.push(Ipv4Addr::from(u32::from(first) + (index - offset) as u32));
+        }
+        offset += size;
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::targets::{Block, Provenance};
+
+    fn target(blocks: &[&str]) -> Target {
+        Target::new(blocks.iter().map(|b| Block::whole(b.parse().unwrap())).collect(), Provenance::default())
+    }
+
+    #[test]
+    fn samples_across_all_blocks_of_a_range() {
+        // 10.0.0.1-10.0.0.6 as a range: .1/32, .2/31, .4/31, .6/32
+        let range = target(&["10.0.0.1/32", "10.0.0.2/31", "10.0.0.4/31", "10.0.0.6/32"]);
+        let all = sampled_hosts(&range, SamplePlan::Count(100), 7);
+        assert_eq!(all, range.hosts().collect::<Vec<_>>());
+
+        let half = sampled_hosts(&range, SamplePlan::Fraction(0.5), 7);
+        assert_eq!(half.len(), 3);
+        assert!(half.windows(2).all(|w| w[0] < w[1]));
+        assert!(half.iter().all(|ip| range.hosts().any(|h| h == *ip)));
+    }
+
+    #[test]
+    fn count_applies_to_the_whole_range() {
+        let range = target(&["10.0.0.0/24", "10.0.1.0/25"]);
+        assert_eq!(sampled_hosts(&range, SamplePlan::Count(10), 1).len(), 10);
+    }
+
+    #[test]
+    fn selection_is_reproducible() {
+        let range = target(&["10.0.0.0/24", "10.0.1.0/24"]);
+        let plan = SamplePlan::Count(20);
+        assert_eq!(sampled_hosts(&range, plan, 42), sampled_hosts(&range, plan, 42));
+        assert_ne!(sampled_hosts(&range, plan, 42), sampled_hosts(&range, plan, 43));
+    }
+}
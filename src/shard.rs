@@ -0,0 +1,53 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+/// `--shard i/n`: this machine scans the addresses whose numeric value is
+/// congruent to i-1 modulo n. The assignment depends only on the address, so
+/// n machines given the same input cover every host exactly once, and re-runs
+/// or resumes line up without any coordination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    index: u32,
+    count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (index, count) = value
+            .split_once('/')
+            .ok_or_else(|| format!("invalid shard '{}', expected i/n such as 2/3", value))?;
+        let index: u32 = index.trim().parse().map_err(|_| format!("invalid shard index in '{}'", value))?;
+        let count: u32 = count.trim().parse().map_err(|_| format!("invalid shard count in '{}'", value))?;
+        if count == 0 || index == 0 || index > count {
+            return Err(format!("invalid shard '{}': index must be between 1 and {}", value, count.max(1)));
+        }
+        Ok(Shard { index: index - 1, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index + 1, self.count)
+    }
+}
+
+impl Shard {
+    pub fn contains(&self, addr: Ipv4Addr) -> bool {
+        u32::from(addr) % self.count == self.index
+    }
+
+    /// Number of addresses in `first..=last` that belong to this shard.
+    pub fn count_in(&self, first: Ipv4Addr, last: Ipv4Addr) -> u64 {
+        let (first, last) = (u32::from(first) as u64, u32::from(last) as u64);
+        if last < first {
+            return 0;
+        }
+        let (n, r) = (self.count as u64, self.index as u64);
+        // Members up to and including x: floor((x - r) / n) + 1 for x >= r
+        let upto = |x: u64| if x < r { 0 } else { (x - r) / n + 1 };
+        upto(last) - if first == 0 { 0 } else { upto(first - 1) }
+    }
+}
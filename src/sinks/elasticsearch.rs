@@ -0,0 +1,138 @@
+//! Bulk-indexes findings into Elasticsearch/OpenSearch.
+//!
+//! Credentials come from the environment: `ES_API_KEY`, or
+//! `ES_USERNAME` + `ES_PASSWORD` for basic auth.
+
+use console::style;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::{Finding, SinkEvent};
+use crate::console_log;
+use crate::dns::DnsResolver;
+
+const BATCH_SIZE: usize = 500;
+const BATCH_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone)]
+enum Auth {
+    ApiKey(String),
+    Basic(String, String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ElasticsearchConfig {
+    bulk_url: String,
+    index: String,
+    auth: Option<Auth>,
+}
+
+impl ElasticsearchConfig {
+    pub fn new(url: &str, index: &str) -> Self {
+        let auth = match (
+            std::env::var("ES_API_KEY"),
+            std::env::var("ES_USERNAME"),
+            std::env::var("ES_PASSWORD"),
+        ) {
+            (Ok(key), _, _) => Some(Auth::ApiKey(key)),
+            (_, Ok(user), Ok(password)) => Some(Auth::Basic(user, password)),
+            _ => None,
+        };
+        Self {
+            bulk_url: format!("{}/_bulk", url.trim_end_matches('/')),
+            index: index.to_string(),
+            auth,
+        }
+    }
+}
+
+fn bulk_lines(index: &str, finding: &Finding) -> Option<String> {
+    let mut doc = serde_json::to_value(finding).ok()?;
+    // Kibana time filters key off @timestamp
+    doc["@timestamp"] = serde_json::Value::String(finding.timestamp.clone());
+    let action = serde_json::json!({ "index": { "_index": index } });
+    Some(format!("{}\n{}\n", action, doc))
+}
+
+async fn send_bulk(client: &reqwest::Client, config: &ElasticsearchConfig, body: String) -> Result<(), String> {
+    let mut delay = Duration::from_secs(1);
+    for attempt in 1..=MAX_ATTEMPTS {
+        let mut request = client
+            .post(&config.bulk_url)
+            .header("Content-Type", "application/x-ndjson")
+            .body(body.clone());
+        request = match &config.auth {
+            Some(Auth::ApiKey(key)) => request.header("Authorization", format!("ApiKey {}", key)),
+            Some(Auth::Basic(user, password)) => request.basic_auth(user, Some(password)),
+            None => request,
+        };
+
+        let retryable = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                let summary: serde_json::Value = response.json().await.unwrap_or_default();
+                if summary["errors"].as_bool() == Some(true) {
+                    return Err("bulk request reported item errors".to_string());
+                }
+                return Ok(());
+            }
+            Ok(response) => {
+                let status = response.status().as_u16();
+                if status != 429 && status != 503 {
+                    return Err(format!("bulk request failed with status {}", status));
+                }
+                format!("status {}", status)
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if attempt == MAX_ATTEMPTS {
+            return Err(format!("giving up after {} attempts ({})", MAX_ATTEMPTS, retryable));
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    unreachable!()
+}
+
+pub async fn run(config: ElasticsearchConfig, dns: Option<Arc<DnsResolver>>, mut receiver: mpsc::Receiver<SinkEvent>) {
+    let client = match DnsResolver::apply(dns.as_ref(), reqwest::Client::builder()).timeout(Duration::from_secs(30)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            console_log(style(format!("Elasticsearch sink disabled: {}", e)).red().to_string());
+            return;
+        }
+    };
+
+    let mut batch = String::new();
+    let mut batched = 0;
+    let mut ticker = tokio::time::interval(BATCH_INTERVAL);
+    loop {
+        // Flush when the batch is full, on every tick, and once more on close
+        let (flush, closed) = tokio::select! {
+            event = receiver.recv() => match event {
+                Some(SinkEvent::Finding(finding)) => {
+                    if let Some(lines) = bulk_lines(&config.index, &finding) {
+                        batch.push_str(&lines);
+                        batched += 1;
+                    }
+                    (batched >= BATCH_SIZE, false)
+                }
+                Some(_) => (false, false),
+                None => (true, true),
+            },
+            _ = ticker.tick() => (true, false),
+        };
+
+        if flush && batched > 0 {
+            if let Err(e) = send_bulk(&client, &config, std::mem::take(&mut batch)).await {
+                console_log(style(format!("Elasticsearch: {} ({} documents lost)", e, batched)).red().to_string());
+            }
+            batched = 0;
+        }
+        if closed {
+            break;
+        }
+    }
+}
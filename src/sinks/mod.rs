@@ -0,0 +1,156 @@
+//! Optional destinations for confirmed findings besides the CSV files.
+//!
+//! Every sink runs as its own task fed by a bounded channel. Publishing to a
+//! best-effort sink never waits: if it falls behind, findings for that sink
+//! are dropped (and counted) rather than slowing down the scan. A lossless
+//! sink such as `--stream` is the scan's primary output, so publishing to it
+//! waits for room in its channel instead.
+
+use console::style;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::{console_log, Model};
+
+pub mod elasticsearch;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod stream;
+pub mod syslog;
+
+const SINK_CHANNEL_CAPACITY: usize = 10_000;
+
+/// What sinks receive: every finding plus the scan's lifecycle. Sinks that
+/// only care about findings ignore the rest.
+#[derive(Debug, Clone)]
+pub enum SinkEvent {
+    ScanStarted { run_id: String, targets: u64 },
+    Finding(Arc<Finding>),
+    ScanFinished { run_id: String, attempted: u64, hits: u64, stopped: bool },
+}
+
+/// A confirmed endpoint as handed to the sinks.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub ip: String,
+    pub port: u16,
+    pub base_url: String,
+    pub tags_url: String,
+    pub status: u16,
+    pub location: String,
+    /// The input entries the target came from, e.g. "ovh.txt:3"
+    pub source: String,
+    pub latency_ms: u64,
+    /// RFC3339 UTC
+    pub timestamp: String,
+    pub run_id: String,
+    /// `--label`, left out without one
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub tag: String,
+    pub models: Vec<Model>,
+    /// "rule: model" for each alert rule the models matched
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<String>,
+}
+
+#[derive(Clone)]
+struct SinkHandle {
+    name: &'static str,
+    sender: mpsc::Sender<SinkEvent>,
+    lossless: bool,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Cheap to clone; held by every probe task.
+#[derive(Clone, Default)]
+pub struct SinkPublisher {
+    sinks: Vec<SinkHandle>,
+}
+
+impl SinkPublisher {
+    pub async fn publish(&self, finding: Arc<Finding>) {
+        if !self.sinks.is_empty() {
+            self.emit(SinkEvent::Finding(finding)).await;
+        }
+    }
+
+    pub async fn emit(&self, event: SinkEvent) {
+        for sink in &self.sinks {
+            if sink.lossless {
+                // Only fails once the sink task is gone
+                let _ = sink.sender.send(event.clone()).await;
+            } else if sink.sender.try_send(event.clone()).is_err()
+                && sink.dropped.fetch_add(1, Ordering::Relaxed) == 0
+            {
+                console_log(style(format!("{} sink is falling behind; dropping findings", sink.name)).yellow().to_string());
+            }
+        }
+    }
+}
+
+/// Owns the sink tasks for one run.
+#[derive(Default)]
+pub struct SinkSet {
+    publisher: SinkPublisher,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl SinkSet {
+    /// Registers a best-effort sink task. `run` receives events until every
+    /// publisher clone is dropped, then must flush and return.
+    pub fn add<F, Fut>(&mut self, name: &'static str, run: F)
+    where
+        F: FnOnce(mpsc::Receiver<SinkEvent>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.register(name, false, run);
+    }
+
+    /// Like `add`, but publishers wait for the sink rather than drop events.
+    /// `run` must keep receiving until the channel closes.
+    pub fn add_lossless<F, Fut>(&mut self, name: &'static str, run: F)
+    where
+        F: FnOnce(mpsc::Receiver<SinkEvent>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.register(name, true, run);
+    }
+
+    fn register<F, Fut>(&mut self, name: &'static str, lossless: bool, run: F)
+    where
+        F: FnOnce(mpsc::Receiver<SinkEvent>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(SINK_CHANNEL_CAPACITY);
+        self.publisher.sinks.push(SinkHandle {
+            name,
+            sender,
+            lossless,
+            dropped: Arc::new(AtomicU64::new(0)),
+        });
+        self.tasks.push(tokio::spawn(run(receiver)));
+    }
+
+    pub fn publisher(&self) -> SinkPublisher {
+        self.publisher.clone()
+    }
+
+    /// Waits for every sink to drain. Call after all publisher clones are gone.
+    pub async fn close(self) {
+        for sink in &self.publisher.sinks {
+            let dropped = sink.dropped.load(Ordering::Relaxed);
+            if dropped > 0 {
+                console_log(style(format!("{} sink dropped {} findings", sink.name, dropped)).yellow().to_string());
+            }
+        }
+        drop(self.publisher);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+    }
+}
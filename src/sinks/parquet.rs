@@ -0,0 +1,249 @@
+//! Parquet export (cargo feature `parquet`).
+//!
+//! Writes a single denormalized file with one row per (endpoint, model).
+//! Endpoints that list no models still get one row with null model columns,
+//! so `SELECT DISTINCT ip, port` recovers the endpoint set. Rows are buffered
+//! and written as a row group every `ROW_GROUP_SIZE` rows; the footer is
+//! written when the sink closes, including after `q`/Ctrl+C.
+//!
+//! With `--rotate`/`--rotate-size` each file is closed at a row group
+//! boundary and the next one started under a new name; a file is never
+//! appended to, so a rotated run starts at the first unused name.
+
+use anyhow::Result;
+use arrow_array::builder::{StringBuilder, TimestampMillisecondBuilder, UInt16Builder, UInt32Builder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::NaiveDate;
+use console::style;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::{Finding, SinkEvent};
+use crate::console_log;
+use crate::rotate::{rotated_path, Rotation};
+
+const ROW_GROUP_SIZE: usize = 10_000;
+
+fn schema() -> Arc<Schema> {
+    let utc = Some("UTC".into());
+    Arc::new(Schema::new(vec![
+        Field::new("ip", DataType::UInt32, false),
+        Field::new("ip_text", DataType::Utf8, false),
+        Field::new("port", DataType::UInt16, false),
+        Field::new("status", DataType::UInt16, false),
+        Field::new("location", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("latency_ms", DataType::UInt64, false),
+        Field::new("discovered_at", DataType::Timestamp(TimeUnit::Millisecond, utc.clone()), false),
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("tag", DataType::Utf8, true),
+        Field::new("model_name", DataType::Utf8, true),
+        Field::new("model", DataType::Utf8, true),
+        Field::new("digest", DataType::Utf8, true),
+        Field::new("size_bytes", DataType::UInt64, true),
+        Field::new("modified_at", DataType::Timestamp(TimeUnit::Millisecond, utc), true),
+        Field::new("family", DataType::Utf8, true),
+        Field::new("parameter_size", DataType::Utf8, true),
+        Field::new("quantization_level", DataType::Utf8, true),
+    ]))
+}
+
+fn timestamp_millis(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(value).ok().map(|t| t.timestamp_millis())
+}
+
+#[derive(Default)]
+struct Columns {
+    ip: UInt32Builder,
+    ip_text: StringBuilder,
+    port: UInt16Builder,
+    status: UInt16Builder,
+    location: StringBuilder,
+    source: StringBuilder,
+    latency_ms: UInt64Builder,
+    discovered_at: TimestampMillisecondBuilder,
+    run_id: StringBuilder,
+    tag: StringBuilder,
+    model_name: StringBuilder,
+    model: StringBuilder,
+    digest: StringBuilder,
+    size_bytes: UInt64Builder,
+    modified_at: TimestampMillisecondBuilder,
+    family: StringBuilder,
+    parameter_size: StringBuilder,
+    quantization_level: StringBuilder,
+    rows: usize,
+}
+
+impl Columns {
+    fn push_endpoint(&mut self, finding: &Finding) {
+        self.ip.append_value(finding.ip.parse::<Ipv4Addr>().map(u32::from).unwrap_or(0));
+        self.ip_text.append_value(&finding.ip);
+        self.port.append_value(finding.port);
+        self.status.append_value(finding.status);
+        self.location.append_value(&finding.location);
+        self.source.append_value(&finding.source);
+        self.latency_ms.append_value(finding.latency_ms);
+        self.discovered_at.append_value(timestamp_millis(&finding.timestamp).unwrap_or(0));
+        self.run_id.append_value(&finding.run_id);
+        self.tag.append_option((!finding.tag.is_empty()).then_some(&finding.tag));
+        self.rows += 1;
+    }
+
+    fn push(&mut self, finding: &Finding) {
+        if finding.models.is_empty() {
+            self.push_endpoint(finding);
+            self.model_name.append_null();
+            self.model.append_null();
+            self.digest.append_null();
+            self.size_bytes.append_null();
+            self.modified_at.append_null();
+            self.family.append_null();
+            self.parameter_size.append_null();
+            self.quantization_level.append_null();
+            return;
+        }
+        for model in &finding.models {
+            self.push_endpoint(finding);
+            self.model_name.append_value(&model.name);
+            self.model.append_value(&model.model);
+            self.digest.append_value(&model.digest);
+            self.size_bytes.append_value(model.size);
+            self.modified_at.append_option(timestamp_millis(&model.modified_at));
+            self.family.append_value(&model.details.family);
+            self.parameter_size.append_value(&model.details.parameter_size);
+            self.quantization_level.append_value(&model.details.quantization_level);
+        }
+    }
+
+    fn finish(&mut self, schema: &Arc<Schema>) -> Result<RecordBatch> {
+        let utc: Arc<str> = "UTC".into();
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.ip.finish()),
+            Arc::new(self.ip_text.finish()),
+            Arc::new(self.port.finish()),
+            Arc::new(self.status.finish()),
+            Arc::new(self.location.finish()),
+            Arc::new(self.source.finish()),
+            Arc::new(self.latency_ms.finish()),
+            Arc::new(self.discovered_at.finish().with_timezone(utc.clone())),
+            Arc::new(self.run_id.finish()),
+            Arc::new(self.tag.finish()),
+            Arc::new(self.model_name.finish()),
+            Arc::new(self.model.finish()),
+            Arc::new(self.digest.finish()),
+            Arc::new(self.size_bytes.finish()),
+            Arc::new(self.modified_at.finish().with_timezone(utc)),
+            Arc::new(self.family.finish()),
+            Arc::new(self.parameter_size.finish()),
+            Arc::new(self.quantization_level.finish()),
+        ];
+        self.rows = 0;
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+}
+
+fn open_writer(path: &Path, schema: &Arc<Schema>) -> Result<ArrowWriter<File>> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_row_count(Some(ROW_GROUP_SIZE))
+        .build();
+    Ok(ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(properties))?)
+}
+
+fn write_batch(writer: &mut ArrowWriter<File>, columns: &mut Columns, schema: &Arc<Schema>) -> Result<()> {
+    let batch = columns.finish(schema)?;
+    writer.write(&batch)?;
+    // Each buffered batch becomes its own row group
+    writer.flush()?;
+    Ok(())
+}
+
+/// The file being written and the day it is named after.
+struct Output {
+    writer: ArrowWriter<File>,
+    path: PathBuf,
+    period: Option<NaiveDate>,
+}
+
+impl Output {
+    /// `base` itself without rotation, otherwise the first unused name for today.
+    fn open(base: &Path, rotation: Rotation, schema: &Arc<Schema>) -> Result<Self> {
+        let base = &crate::rotate::suffixed(base, rotation.suffix);
+        let period = rotation.period();
+        let path = match rotation.is_off() {
+            true => base.to_path_buf(),
+            false => rotated_path(base, period, rotation.fresh_index(base, period)),
+        };
+        Ok(Self { writer: open_writer(&path, schema)?, path, period })
+    }
+
+    /// Writes the buffered rows, if any, and the footer.
+    fn close(mut self, columns: &mut Columns, schema: &Arc<Schema>) {
+        let result = if columns.rows > 0 {
+            write_batch(&mut self.writer, columns, schema)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result.and_then(|_| self.writer.close().map(|_| ()).map_err(Into::into)) {
+            console_log(style(format!("Failed to finalize {}: {}", self.path.display(), e)).red().to_string());
+        }
+    }
+}
+
+/// Finishes the current file and opens the next one.
+fn roll_over(output: &mut Option<Output>, base: &Path, rotation: Rotation, columns: &mut Columns, schema: &Arc<Schema>) {
+    if let Some(current) = output.take() {
+        current.close(columns, schema);
+    }
+    *output = open_or_disable(base, rotation, schema);
+}
+
+fn open_or_disable(base: &Path, rotation: Rotation, schema: &Arc<Schema>) -> Option<Output> {
+    match Output::open(base, rotation, schema) {
+        Ok(output) => Some(output),
+        Err(e) => {
+            console_log(style(format!("Parquet output disabled: {}", e)).red().to_string());
+            None
+        }
+    }
+}
+
+pub async fn run(path: PathBuf, rotation: Rotation, mut receiver: mpsc::Receiver<SinkEvent>) {
+    let schema = schema();
+    let mut output = open_or_disable(&path, rotation, &schema);
+    let mut columns = Columns::default();
+
+    while let Some(event) = receiver.recv().await {
+        let SinkEvent::Finding(finding) = &event else {
+            continue;
+        };
+        // A new day's first row already goes to the new day's file
+        if output.as_ref().is_some_and(|active| active.period != rotation.period()) {
+            roll_over(&mut output, &path, rotation, &mut columns, &schema);
+        }
+        let Some(active) = output.as_mut() else {
+            continue;
+        };
+        columns.push(finding);
+        if columns.rows >= ROW_GROUP_SIZE {
+            if let Err(e) = write_batch(&mut active.writer, &mut columns, &schema) {
+                console_log(style(format!("Parquet write failed: {}", e)).red().to_string());
+                output = None;
+            } else if rotation.is_full(active.writer.bytes_written() as u64) {
+                roll_over(&mut output, &path, rotation, &mut columns, &schema);
+            }
+        }
+    }
+
+    if let Some(output) = output {
+        output.close(&mut columns, &schema);
+    }
+}
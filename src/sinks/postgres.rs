@@ -0,0 +1,349 @@
+//! PostgreSQL output (cargo feature `postgres`, `--pg postgres://…`).
+//!
+//! Findings are upserted into `endpoints`, keyed on (ip, port), and `models`,
+//! keyed on (ip, port, digest), keeping the `first_seen` and `last_seen` of
+//! each row. Missing tables are created; tables created beforehand are
+//! checked for the columns written before the scan starts.
+//!
+//! The sink writes one batch at a time on a pooled connection while it goes
+//! on receiving, so a slow database never holds up the probes. A batch that
+//! fails goes back to the front of the queue and is retried with growing
+//! delays. Once more than `MAX_QUEUED` findings wait, the oldest are written
+//! to `pg_fallback.csv` instead, as is whatever still waits when the run
+//! ends with the database unreachable.
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use deadpool_postgres::{Manager, Pool};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_postgres::NoTls;
+
+use super::{Finding, SinkEvent};
+use crate::{console_log, outdir};
+
+pub const FALLBACK_CSV: &str = "pg_fallback.csv";
+const FALLBACK_HEADERS: &[&str] = &["Timestamp", "IP", "Port", "Base URL", "Status Code", "Location", "Source", "Tag", "Run ID", "Alerts", "Models"];
+
+const BATCH_SIZE: usize = 500;
+/// Findings kept for the database before the oldest spill to the fallback CSV
+const MAX_QUEUED: usize = 50_000;
+const POOL_SIZE: usize = 2;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// A batch taking longer counts as failed, so a dead connection can't hold the queue
+const WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+const TICK: Duration = Duration::from_secs(1);
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Tries left for what waits once the run is over
+const CLOSING_ATTEMPTS: u32 = 3;
+
+/// Each table with its columns, as created and as checked.
+const TABLES: &[(&str, &str, &[&str])] = &[
+    (
+        "endpoints",
+        "CREATE TABLE endpoints (
+            ip TEXT NOT NULL,
+            port INTEGER NOT NULL,
+            base_url TEXT NOT NULL,
+            status INTEGER NOT NULL,
+            location TEXT NOT NULL,
+            source TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            run_id TEXT NOT NULL,
+            latency_ms BIGINT NOT NULL,
+            model_count INTEGER NOT NULL,
+            alerts TEXT NOT NULL,
+            first_seen TIMESTAMPTZ NOT NULL,
+            last_seen TIMESTAMPTZ NOT NULL,
+            PRIMARY KEY (ip, port)
+        )",
+        &["ip", "port", "base_url", "status", "location", "source", "tag", "run_id", "latency_ms", "model_count", "alerts", "first_seen", "last_seen"],
+    ),
+    (
+        "models",
+        "CREATE TABLE models (
+            ip TEXT NOT NULL,
+            port INTEGER NOT NULL,
+            digest TEXT NOT NULL,
+            name TEXT NOT NULL,
+            model TEXT NOT NULL,
+            size_bytes BIGINT NOT NULL,
+            family TEXT NOT NULL,
+            parameter_size TEXT NOT NULL,
+            quantization_level TEXT NOT NULL,
+            run_id TEXT NOT NULL,
+            first_seen TIMESTAMPTZ NOT NULL,
+            last_seen TIMESTAMPTZ NOT NULL,
+            PRIMARY KEY (ip, port, digest)
+        )",
+        &["ip", "port", "digest", "name", "model", "size_bytes", "family", "parameter_size", "quantization_level", "run_id", "first_seen", "last_seen"],
+    ),
+];
+
+const UPSERT_ENDPOINT: &str = "
+    INSERT INTO endpoints (ip, port, base_url, status, location, source, tag, run_id, latency_ms, model_count, alerts, first_seen, last_seen)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12::text::timestamptz, $12::text::timestamptz)
+    ON CONFLICT (ip, port) DO UPDATE SET
+        base_url = EXCLUDED.base_url, status = EXCLUDED.status, location = EXCLUDED.location,
+        source = EXCLUDED.source, tag = EXCLUDED.tag, run_id = EXCLUDED.run_id,
+        latency_ms = EXCLUDED.latency_ms, model_count = EXCLUDED.model_count, alerts = EXCLUDED.alerts,
+        first_seen = LEAST(endpoints.first_seen, EXCLUDED.first_seen),
+        last_seen = GREATEST(endpoints.last_seen, EXCLUDED.last_seen)";
+
+const UPSERT_MODEL: &str = "
+    INSERT INTO models (ip, port, digest, name, model, size_bytes, family, parameter_size, quantization_level, run_id, first_seen, last_seen)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11::text::timestamptz, $11::text::timestamptz)
+    ON CONFLICT (ip, port, digest) DO UPDATE SET
+        name = EXCLUDED.name, model = EXCLUDED.model, size_bytes = EXCLUDED.size_bytes,
+        family = EXCLUDED.family, parameter_size = EXCLUDED.parameter_size,
+        quantization_level = EXCLUDED.quantization_level, run_id = EXCLUDED.run_id,
+        first_seen = LEAST(models.first_seen, EXCLUDED.first_seen),
+        last_seen = GREATEST(models.last_seen, EXCLUDED.last_seen)";
+
+/// Connects and makes sure the tables can take findings, so a wrong URL or
+/// schema stops the run before it scans anything.
+pub async fn connect(url: &str) -> Result<Pool> {
+    let mut config: tokio_postgres::Config = url.parse().context("Invalid --pg URL")?;
+    if config.get_connect_timeout().is_none() {
+        config.connect_timeout(CONNECT_TIMEOUT);
+    }
+    let pool = Pool::builder(Manager::new(config, NoTls)).max_size(POOL_SIZE).build()?;
+    let client = pool.get().await.context("Failed to connect to PostgreSQL")?;
+    for (table, create, columns) in TABLES {
+        let exists = "SELECT EXISTS (SELECT 1 FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = $1)";
+        if !client.query_one(exists, &[table]).await?.get::<_, bool>(0) {
+            client.batch_execute(create).await.with_context(|| format!("Failed to create the {} table", table))?;
+            continue;
+        }
+        let present: Vec<String> = client
+            .query("SELECT column_name::text FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = $1", &[table])
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        let missing: Vec<&str> = columns.iter().filter(|column| !present.iter().any(|p| p == *column)).copied().collect();
+        if !missing.is_empty() {
+            bail!("The existing {} table has no {} column(s)", table, missing.join(", "));
+        }
+    }
+    // Fails here, not mid-run, when the keys have no unique constraint
+    for statement in [UPSERT_ENDPOINT, UPSERT_MODEL] {
+        client.prepare_cached(statement).await.context("The existing tables can't take findings")?;
+    }
+    Ok(pool)
+}
+
+/// Upserts `batch` in one transaction.
+async fn write_batch(pool: &Pool, batch: &[Arc<Finding>]) -> Result<()> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    let endpoint = transaction.prepare_cached(UPSERT_ENDPOINT).await?;
+    let model = transaction.prepare_cached(UPSERT_MODEL).await?;
+    for finding in batch {
+        let port = finding.port as i32;
+        transaction
+            .execute(&endpoint, &[
+                &finding.ip,
+                &port,
+                &finding.base_url,
+                &(finding.status as i32),
+                &finding.location,
+                &finding.source,
+                &finding.tag,
+                &finding.run_id,
+                &(finding.latency_ms as i64),
+                &(finding.models.len() as i32),
+                &finding.alerts.join("; "),
+                &finding.timestamp,
+            ])
+            .await?;
+        for m in &finding.models {
+            // A model listed without a digest is told apart by its name
+            let digest = if m.digest.is_empty() { &m.name } else { &m.digest };
+            transaction
+                .execute(&model, &[
+                    &finding.ip,
+                    &port,
+                    digest,
+                    &m.name,
+                    &m.model,
+                    &(m.size as i64),
+                    &m.details.family,
+                    &m.details.parameter_size,
+                    &m.details.quantization_level,
+                    &finding.run_id,
+                    &finding.timestamp,
+                ])
+                .await?;
+        }
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Appends `findings` to the fallback CSV, with the models as JSON.
+fn spill(findings: impl IntoIterator<Item = Arc<Finding>>) -> Result<u64> {
+    let path = outdir::path(FALLBACK_CSV);
+    let new = std::fs::metadata(&path).map_or(true, |metadata| metadata.len() == 0);
+    let file = OpenOptions::new().append(true).create(true).open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut writer = csv::Writer::from_writer(file);
+    if new {
+        writer.write_record(FALLBACK_HEADERS)?;
+    }
+    let mut spilled = 0;
+    for finding in findings {
+        writer.write_record([
+            &finding.timestamp,
+            &finding.ip,
+            &finding.port.to_string(),
+            &finding.base_url,
+            &finding.status.to_string(),
+            &finding.location,
+            &finding.source,
+            &finding.tag,
+            &finding.run_id,
+            &finding.alerts.join("; "),
+            &serde_json::to_string(&finding.models)?,
+        ])?;
+        spilled += 1;
+    }
+    writer.flush().with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(spilled)
+}
+
+fn spill_or_report(findings: Vec<Arc<Finding>>) {
+    let count = findings.len();
+    match spill(findings) {
+        Ok(spilled) => console_log(style(format!("PostgreSQL: {} findings written to {} instead", spilled, outdir::path(FALLBACK_CSV).display())).yellow().to_string()),
+        Err(e) => console_log(style(format!("PostgreSQL: {:#}; {} findings lost", e, count)).red().to_string()),
+    }
+}
+
+/// The oldest findings beyond `MAX_QUEUED`.
+fn overflow(queue: &mut VecDeque<Arc<Finding>>, max: usize) -> Vec<Arc<Finding>> {
+    let excess = queue.len().saturating_sub(max);
+    queue.drain(..excess).collect()
+}
+
+type InFlight = JoinHandle<(Vec<Arc<Finding>>, Result<()>)>;
+
+pub async fn run(pool: Pool, mut receiver: mpsc::Receiver<SinkEvent>) {
+    let mut queue: VecDeque<Arc<Finding>> = VecDeque::new();
+    let mut in_flight: Option<InFlight> = None;
+    let mut retry_at = Instant::now();
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut failing = false;
+    let mut closed = false;
+    let mut closing_attempts = 0;
+    let mut ticker = tokio::time::interval(TICK);
+    loop {
+        tokio::select! {
+            event = receiver.recv(), if !closed => match event {
+                Some(SinkEvent::Finding(finding)) => {
+                    queue.push_back(finding);
+                    let excess = overflow(&mut queue, MAX_QUEUED);
+                    if !excess.is_empty() {
+                        spill_or_report(excess);
+                    }
+                }
+                Some(_) => {}
+                None => closed = true,
+            },
+            done = async { in_flight.as_mut().expect("guarded").await }, if in_flight.is_some() => {
+                in_flight = None;
+                let (batch, result) = match done {
+                    Ok(done) => done,
+                    Err(e) => (Vec::new(), Err(e.into())),
+                };
+                match result {
+                    Ok(()) => {
+                        if failing {
+                            console_log(style("PostgreSQL: writing again").green().to_string());
+                        }
+                        failing = false;
+                        delay = FIRST_RETRY_DELAY;
+                    }
+                    Err(e) => {
+                        if !failing {
+                            console_log(style(format!("PostgreSQL: {:#}; keeping the findings to retry", e)).red().to_string());
+                        }
+                        failing = true;
+                        for finding in batch.into_iter().rev() {
+                            queue.push_front(finding);
+                        }
+                        retry_at = Instant::now() + delay;
+                        delay = (delay * 2).min(MAX_RETRY_DELAY);
+                        if closed {
+                            closing_attempts += 1;
+                        }
+                    }
+                }
+            },
+            _ = ticker.tick() => {}
+        }
+
+        if in_flight.is_some() {
+            continue;
+        }
+        if closed && (queue.is_empty() || closing_attempts >= CLOSING_ATTEMPTS) {
+            break;
+        }
+        let due = Instant::now() >= retry_at;
+        if !queue.is_empty() && (due || closed) {
+            if !due {
+                // The run is over, so the growing delays aren't waited out
+                tokio::time::sleep(FIRST_RETRY_DELAY).await;
+            }
+            let batch: Vec<Arc<Finding>> = queue.drain(..queue.len().min(BATCH_SIZE)).collect();
+            let pool = pool.clone();
+            in_flight = Some(tokio::spawn(async move {
+                let result = match tokio::time::timeout(WRITE_TIMEOUT, write_batch(&pool, &batch)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::anyhow!("no answer in {}s", WRITE_TIMEOUT.as_secs())),
+                };
+                (batch, result)
+            }));
+        }
+    }
+    if !queue.is_empty() {
+        spill_or_report(queue.into_iter().collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(ip: &str) -> Arc<Finding> {
+        Arc::new(Finding {
+            ip: ip.to_string(),
+            port: 11434,
+            base_url: format!("http://{}:11434", ip),
+            tags_url: format!("http://{}:11434/api/tags", ip),
+            status: 200,
+            location: "ovh".to_string(),
+            source: "ovh.txt:1".to_string(),
+            latency_ms: 12,
+            timestamp: "2025-01-15T18:30:00Z".to_string(),
+            run_id: "20250115T183000Z".to_string(),
+            tag: String::new(),
+            models: Vec::new(),
+            alerts: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn gives_up_the_oldest_beyond_the_cap() {
+        let mut queue: VecDeque<_> = (1..=5).map(|i| finding(&format!("10.0.0.{}", i))).collect();
+        let excess = overflow(&mut queue, 3);
+        assert_eq!(excess.iter().map(|f| f.ip.as_str()).collect::<Vec<_>>(), ["10.0.0.1", "10.0.0.2"]);
+        assert_eq!(queue.front().unwrap().ip, "10.0.0.3");
+        assert!(overflow(&mut queue, 3).is_empty());
+    }
+}
@@ -0,0 +1,23 @@
+//! `--stream`: one compact JSON object per finding on stdout, flushed per line
+//! so downstream consumers see results immediately.
+
+use std::io::Write;
+use tokio::sync::mpsc;
+
+use super::SinkEvent;
+use crate::anonymize;
+
+pub async fn run(mut receiver: mpsc::Receiver<SinkEvent>) {
+    while let Some(event) = receiver.recv().await {
+        let SinkEvent::Finding(finding) = event else { continue };
+        let Ok(line) = serde_json::to_string(&*finding) else { continue };
+        let line = anonymize::for_reports(&line);
+        let mut stdout = std::io::stdout().lock();
+        // A closed pipe (e.g. `| head`) just ends the stream
+        if writeln!(stdout, "{}", line).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+    // Keep draining so publishers never block on a dead consumer
+    while receiver.recv().await.is_some() {}
+}
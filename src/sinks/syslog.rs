@@ -0,0 +1,387 @@
+//! RFC 5424 syslog output over UDP or a local unix datagram socket.
+//!
+//! Delivery is fire-and-forget: the first send failure prints a single
+//! warning and later failures are ignored.
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use tokio::sync::mpsc;
+
+use super::{Finding, SinkEvent};
+use crate::console_log;
+
+const APP_NAME: &str = "ollama-finder";
+/// Private enterprise number reserved for documentation (RFC 5612)
+const SD_ID: &str = "finding@32473";
+/// RFC 5426 receivers must accept 480 bytes and should accept 2048
+const MAX_MESSAGE_LEN: usize = 2048;
+/// Longest PARAM-VALUE kept, before escaping
+const MAX_PARAM_VALUE_LEN: usize = 255;
+
+const SEVERITY_INFO: u8 = 6;
+
+#[derive(Debug, Clone)]
+enum Target {
+    Udp(String),
+    #[cfg(unix)]
+    Unix(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SyslogConfig {
+    target: Target,
+    facility: u8,
+    severity: u8,
+    hostname: String,
+}
+
+pub fn parse_facility(value: &str) -> Result<u8, String> {
+    const FACILITIES: &[&str] = &[
+        "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+        "uucp", "cron", "authpriv", "ftp", "ntp", "audit", "alert", "clock",
+        "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+    ];
+    FACILITIES
+        .iter()
+        .position(|f| f.eq_ignore_ascii_case(value))
+        .map(|i| i as u8)
+        .ok_or_else(|| format!("unknown syslog facility '{}'", value))
+}
+
+pub fn parse_severity(value: &str) -> Result<u8, String> {
+    const SEVERITIES: &[&str] = &["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+    SEVERITIES
+        .iter()
+        .position(|s| s.eq_ignore_ascii_case(value))
+        .map(|i| i as u8)
+        .ok_or_else(|| format!("unknown syslog severity '{}'", value))
+}
+
+impl SyslogConfig {
+    /// Accepts `udp://host:port` and `unix:///path/to/socket`.
+    pub fn new(url: &str, facility: u8, severity: u8) -> Result<Self> {
+        let target = if let Some(address) = url.strip_prefix("udp://") {
+            Target::Udp(address.to_string())
+        } else if let Some(path) = url.strip_prefix("unix://") {
+            #[cfg(unix)]
+            {
+                Target::Unix(path.to_string())
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = path;
+                bail!("unix syslog sockets are not supported on this platform")
+            }
+        } else {
+            bail!("Unsupported syslog URL '{}', expected udp://host:port or unix:///path", url)
+        };
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty())
+            .unwrap_or_else(|| "-".to_string());
+        Ok(Self { target, facility, severity, hostname })
+    }
+}
+
+/// Escapes a PARAM-VALUE: `"`, `\` and `]` must be backslash-escaped.
+fn escape_param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Header fields are PRINTUSASCII without spaces, bounded in length; "-" is NILVALUE.
+fn header_field(value: &str, max_len: usize) -> String {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if cleaned.is_empty() { "-".to_string() } else { cleaned }
+}
+
+/// Cuts `value` to at most `max_len` bytes without splitting a character.
+fn truncate_to(value: &str, max_len: usize) -> &str {
+    if value.len() <= max_len {
+        return value;
+    }
+    let mut cut = max_len;
+    while !value.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    &value[..cut]
+}
+
+/// Builds a message of at most MAX_MESSAGE_LEN bytes. Only MSG is shortened to
+/// fit: PARAM-VALUEs are capped on their own and a parameter that still does
+/// not fit is left out, so the STRUCTURED-DATA element always stays closed.
+pub fn format_message(
+    config: &SyslogConfig,
+    severity: u8,
+    msg_id: &str,
+    params: &[(&str, String)],
+    text: &str,
+) -> String {
+    let priority = config.facility as u16 * 8 + severity as u16;
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let mut message = format!(
+        "<{}>1 {} {} {} {} {} ",
+        priority,
+        timestamp,
+        header_field(&config.hostname, 255),
+        header_field(APP_NAME, 48),
+        header_field(&std::process::id().to_string(), 128),
+        header_field(msg_id, 32),
+    );
+
+    if params.is_empty() {
+        message.push('-');
+    } else {
+        // "[", SD-ID, "]" and the space before MSG
+        let mut budget = MAX_MESSAGE_LEN.saturating_sub(message.len() + SD_ID.len() + 3);
+        message.push('[');
+        message.push_str(SD_ID);
+        for (name, value) in params {
+            let param = format!(
+                " {}=\"{}\"",
+                header_field(name, 32),
+                escape_param_value(truncate_to(value, MAX_PARAM_VALUE_LEN))
+            );
+            if param.len() <= budget {
+                budget -= param.len();
+                message.push_str(&param);
+            }
+        }
+        message.push(']');
+    }
+    message.push(' ');
+
+    let room = MAX_MESSAGE_LEN.saturating_sub(message.len());
+    message.push_str(truncate_to(text, room));
+    message
+}
+
+/// The structured data every finding's message carries; `tag` only with `--label`.
+fn finding_params(finding: &Finding) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("ip", finding.ip.clone()),
+        ("port", finding.port.to_string()),
+        ("models", finding.models.len().to_string()),
+        ("location", finding.location.clone()),
+        ("source", finding.source.clone()),
+    ];
+    if !finding.tag.is_empty() {
+        params.push(("tag", finding.tag.clone()));
+    }
+    params
+}
+
+fn render(config: &SyslogConfig, event: &SinkEvent) -> String {
+    match event {
+        SinkEvent::ScanStarted { run_id, targets } => format_message(
+            config,
+            SEVERITY_INFO,
+            "scan-start",
+            &[("run_id", run_id.clone()), ("targets", targets.to_string())],
+            "Scan started",
+        ),
+        SinkEvent::Finding(finding) if !finding.alerts.is_empty() => format_message(
+            config,
+            config.severity,
+            "alert",
+            &[finding_params(finding), vec![("alerts", finding.alerts.join("; "))]].concat(),
+            &format!("ALERT Ollama endpoint found at {}: {}", finding.base_url, finding.alerts.join("; ")),
+        ),
+        SinkEvent::Finding(finding) => format_message(
+            config,
+            config.severity,
+            "endpoint",
+            &finding_params(finding),
+            &format!("Ollama endpoint found at {}", finding.base_url),
+        ),
+        SinkEvent::ScanFinished { run_id, attempted, hits, stopped } => format_message(
+            config,
+            SEVERITY_INFO,
+            "scan-stop",
+            &[
+                ("run_id", run_id.clone()),
+                ("attempted", attempted.to_string()),
+                ("hits", hits.to_string()),
+                ("stopped", stopped.to_string()),
+            ],
+            if *stopped { "Scan stopped" } else { "Scan completed" },
+        ),
+    }
+}
+
+enum Socket {
+    Udp(tokio::net::UdpSocket),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixDatagram, String),
+}
+
+impl Socket {
+    async fn connect(target: &Target) -> Result<Self> {
+        match target {
+            Target::Udp(address) => {
+                let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(address).await.with_context(|| format!("Failed to resolve {}", address))?;
+                Ok(Socket::Udp(socket))
+            }
+            #[cfg(unix)]
+            Target::Unix(path) => Ok(Socket::Unix(tokio::net::UnixDatagram::unbound()?, path.clone())),
+        }
+    }
+
+    async fn send(&self, message: &str) -> std::io::Result<()> {
+        match self {
+            Socket::Udp(socket) => socket.send(message.as_bytes()).await.map(|_| ()),
+            #[cfg(unix)]
+            Socket::Unix(socket, path) => socket.send_to(message.as_bytes(), path).await.map(|_| ()),
+        }
+    }
+}
+
+pub async fn run(config: SyslogConfig, mut receiver: mpsc::Receiver<SinkEvent>) {
+    let socket = match Socket::connect(&config.target).await {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            console_log(style(format!("Syslog output disabled: {}", e)).yellow().to_string());
+            None
+        }
+    };
+    let mut warned = false;
+    // Keep draining even without a socket so publishers never see a full channel
+    while let Some(event) = receiver.recv().await {
+        let Some(socket) = &socket else { continue };
+        if let Err(e) = socket.send(&render(&config, &event)).await {
+            if !warned {
+                console_log(style(format!("Syslog receiver unreachable ({}); further errors suppressed", e)).yellow().to_string());
+                warned = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn config() -> SyslogConfig {
+        SyslogConfig::new("udp://127.0.0.1:514", 1, 5).unwrap()
+    }
+
+    /// Splits a message into its header fields, STRUCTURED-DATA and MSG.
+    fn parts(message: &str) -> (Vec<&str>, &str, &str) {
+        let header: Vec<&str> = message.splitn(7, ' ').take(6).collect();
+        let rest = &message[header.iter().map(|h| h.len() + 1).sum::<usize>()..];
+        let sd_end = if rest.starts_with('-') {
+            1
+        } else {
+            let bytes = rest.as_bytes();
+            let mut i = 0;
+            loop {
+                match bytes[i] {
+                    b'\\' => i += 2,
+                    b']' => break i + 1,
+                    _ => i += 1,
+                }
+            }
+        };
+        (header, &rest[..sd_end], &rest[sd_end + 1..])
+    }
+
+    #[test]
+    fn escapes_param_values() {
+        assert_eq!(escape_param_value(r#"a"b\c]d"#), r#"a\"b\\c\]d"#);
+        assert_eq!(escape_param_value("plain [text]"), r"plain [text\]");
+    }
+
+    #[test]
+    fn header_fields_are_bounded_printable_ascii() {
+        assert_eq!(header_field("my host", 255), "myhost");
+        assert_eq!(header_field("héllo", 255), "hllo");
+        assert_eq!(header_field("", 32), "-");
+        assert_eq!(header_field(&"x".repeat(300), 255).len(), 255);
+    }
+
+    #[test]
+    fn formats_header_and_structured_data() {
+        let message = format_message(&config(), 5, "endpoint", &[("ip", "10.0.0.1".to_string())], "found");
+        let (header, structured, msg) = parts(&message);
+        assert_eq!(header[0], "<13>1");
+        assert_eq!(header[3], APP_NAME);
+        assert_eq!(header[5], "endpoint");
+        assert_eq!(structured, r#"[finding@32473 ip="10.0.0.1"]"#);
+        assert_eq!(msg, "found");
+    }
+
+    #[test]
+    fn prefixes_findings_that_raised_alerts() {
+        let mut finding = Finding {
+            ip: "10.0.0.1".to_string(),
+            port: 11434,
+            base_url: "http://10.0.0.1:11434".to_string(),
+            tags_url: "http://10.0.0.1:11434/api/tags".to_string(),
+            status: 200,
+            location: "ovh".to_string(),
+            source: "ovh.txt:1".to_string(),
+            latency_ms: 12,
+            timestamp: "2024-05-01T10:00:00.000Z".to_string(),
+            run_id: "20240501T100000Z".to_string(),
+            tag: String::new(),
+            models: Vec::new(),
+            alerts: Vec::new(),
+        };
+        let message = render(&config(), &SinkEvent::Finding(Arc::new(finding.clone())));
+        let (header, _, msg) = parts(&message);
+        assert_eq!((header[5], msg), ("endpoint", "Ollama endpoint found at http://10.0.0.1:11434"));
+
+        finding.alerts = vec!["big-r1: deepseek-r1:70b".to_string()];
+        finding.tag = "customer-a".to_string();
+        let message = render(&config(), &SinkEvent::Finding(Arc::new(finding)));
+        let (header, structured, msg) = parts(&message);
+        assert_eq!(header[5], "alert");
+        assert!(structured.ends_with(r#" tag="customer-a" alerts="big-r1: deepseek-r1:70b"]"#), "{}", structured);
+        assert_eq!(msg, "ALERT Ollama endpoint found at http://10.0.0.1:11434: big-r1: deepseek-r1:70b");
+    }
+
+    #[test]
+    fn uses_nilvalue_without_params() {
+        let message = format_message(&config(), 6, "scan-start", &[], "Scan started");
+        let (_, structured, msg) = parts(&message);
+        assert_eq!(structured, "-");
+        assert_eq!(msg, "Scan started");
+    }
+
+    #[test]
+    fn long_msg_is_truncated_on_a_char_boundary() {
+        let text = "é".repeat(MAX_MESSAGE_LEN);
+        let message = format_message(&config(), 5, "endpoint", &[("ip", "10.0.0.1".to_string())], &text);
+        assert!(message.len() <= MAX_MESSAGE_LEN);
+        assert!(message.len() >= MAX_MESSAGE_LEN - 1);
+        let (_, structured, msg) = parts(&message);
+        assert_eq!(structured, r#"[finding@32473 ip="10.0.0.1"]"#);
+        assert!(msg.chars().all(|c| c == 'é'));
+    }
+
+    #[test]
+    fn long_param_values_keep_structured_data_well_formed() {
+        let location = "]\"".repeat(2000);
+        let params: Vec<(&str, String)> = ["a", "b", "c", "d", "e", "f"].iter().map(|n| (*n, location.clone())).collect();
+        let message = format_message(&config(), 5, "endpoint", &params, "found");
+        assert!(message.len() <= MAX_MESSAGE_LEN);
+        let (_, structured, msg) = parts(&message);
+        assert!(structured.starts_with("[finding@32473 a=\""));
+        assert!(structured.ends_with("\"]"));
+        assert_eq!(msg, "found");
+    }
+}
@@ -0,0 +1,182 @@
+//! The status snapshot printed when `s` is pressed. The keyboard thread only
+//! asks for one: the request goes through a channel to the task of the
+//! running cycle, which owns the stats and prints the block through the
+//! printer like a hit. The `h` panel is drawn the same way, for the live
+//! settings it shows. Between daemon cycles nobody is listening.
+
+use console::style;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::cli::format_duration;
+use crate::{HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+static REQUESTS: Mutex<Option<mpsc::UnboundedSender<Request>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Status,
+    Help,
+}
+
+/// Asks the running cycle for a block; false when no scan is running.
+pub fn request(request: Request) -> bool {
+    REQUESTS.lock().unwrap().as_ref().is_some_and(|sender| sender.send(request).is_ok())
+}
+
+/// Takes the requests from now on, until `stop_listening`.
+pub fn listen() -> mpsc::UnboundedReceiver<Request> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    *REQUESTS.lock().unwrap() = Some(tx);
+    rx
+}
+
+pub fn stop_listening() {
+    REQUESTS.lock().unwrap().take();
+}
+
+/// Where the sweep is within the current range.
+#[derive(Debug, Clone)]
+pub struct RangePosition {
+    /// Counting from 1
+    pub number: usize,
+    pub of: usize,
+    pub location: String,
+    /// Probes done and planned in this range
+    pub done: u64,
+    pub planned: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub active: Duration,
+    pub paused_total: Duration,
+    /// How long the pause in progress has lasted
+    pub paused_for: Option<Duration>,
+    pub in_flight: usize,
+    /// None during the second pass and for queue workers
+    pub range: Option<RangePosition>,
+    pub scanned: u64,
+    /// None with `--watch`, whose feed has no total
+    pub targets: Option<u64>,
+    pub hits: u64,
+    pub other_hits: u64,
+    /// Newest first
+    pub recent: Vec<String>,
+    /// As the status line shows them
+    pub errors: String,
+    /// Each category's count, for the dashboard
+    pub error_mix: Vec<(&'static str, u64)>,
+    pub rate: f64,
+    pub concurrency: usize,
+}
+
+impl Snapshot {
+    pub fn block(&self) -> Vec<String> {
+        let mut block = vec![format!("\n{}{}", HEADER_STYLE, style("Scan Status").blue().bold())];
+        if let Some(paused) = self.paused_for {
+            let waiting = match self.in_flight {
+                0 => String::new(),
+                n => format!(", waiting for {} in-flight requests", n),
+            };
+            block.push(format!("{}{}",
+                LIST_ITEM_STYLE,
+                style(format!("PAUSED for {}{} (press 'r' to resume)", format_duration(paused), waiting)).yellow().bold()
+            ));
+        }
+        let mut time = format!("{} active", format_duration(self.active));
+        if self.paused_total >= Duration::from_secs(1) {
+            time.push_str(&format!(", paused for {} in all", format_duration(self.paused_total)));
+        }
+        block.push(format!("{}Time: {}", LIST_ITEM_STYLE, time));
+        if let Some(range) = &self.range {
+            let percent = match range.planned {
+                0 => 100,
+                planned => (range.done.min(planned) * 100 / planned) as u32,
+            };
+            block.push(format!("{}Range: {} of {} ({}), {}% through",
+                LIST_ITEM_STYLE,
+                range.number,
+                range.of,
+                style(&range.location).cyan(),
+                percent
+            ));
+        }
+        block.push(match self.targets {
+            Some(targets) => format!("{}Scanned: {} of {} hosts", LIST_ITEM_STYLE, self.scanned, targets),
+            None => format!("{}Scanned: {} hosts", LIST_ITEM_STYLE, self.scanned),
+        });
+        let mut hits = format!("{}Hits: {}", LIST_ITEM_STYLE, style(self.hits).green().bold());
+        if self.other_hits > 0 {
+            hits.push_str(&format!(", {} other services", self.other_hits));
+        }
+        block.push(hits);
+        for finding in &self.recent {
+            block.push(format!("{}   {}", LIST_ITEM_STYLE, style(finding).cyan()));
+        }
+        block.push(format!("{}Errors: {}", LIST_ITEM_STYLE, if self.errors.is_empty() { "none" } else { &self.errors }));
+        block.push(format!("{}Rate: {:.0}/s, concurrency {}", LAST_ITEM_STYLE, self.rate, self.concurrency));
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            active: Duration::from_secs(754),
+            paused_total: Duration::ZERO,
+            paused_for: None,
+            in_flight: 0,
+            range: Some(RangePosition { number: 2, of: 5, location: "US".to_string(), done: 300, planned: 1200 }),
+            scanned: 5300,
+            targets: Some(20000),
+            hits: 4,
+            other_hits: 0,
+            recent: vec!["http://203.0.113.9:11434".to_string(), "http://203.0.113.7:11434".to_string()],
+            errors: "timeout 12".to_string(),
+            error_mix: vec![("timeout", 12)],
+            rate: 99.6,
+            concurrency: 480,
+        }
+    }
+
+    fn plain(block: &[String]) -> Vec<String> {
+        block.iter().map(|line| console::strip_ansi_codes(line).trim_start_matches('\n').to_string()).collect()
+    }
+
+    #[test]
+    fn shows_the_scan_in_one_block() {
+        assert_eq!(plain(&snapshot().block()), [
+            "╭─ 🌐 Scan Status",
+            "├─ Time: 12m34s active",
+            "├─ Range: 2 of 5 (US), 25% through",
+            "├─ Scanned: 5300 of 20000 hosts",
+            "├─ Hits: 4",
+            "├─    http://203.0.113.9:11434",
+            "├─    http://203.0.113.7:11434",
+            "├─ Errors: timeout 12",
+            "╰─ Rate: 100/s, concurrency 480",
+        ]);
+    }
+
+    #[test]
+    fn shows_the_pause_first() {
+        let paused = Snapshot {
+            paused_for: Some(Duration::from_secs(65)),
+            paused_total: Duration::from_secs(95),
+            in_flight: 3,
+            range: None,
+            recent: Vec::new(),
+            errors: String::new(),
+            ..snapshot()
+        };
+        let lines = plain(&paused.block());
+        assert_eq!(lines[1], "├─ PAUSED for 1m5s, waiting for 3 in-flight requests (press 'r' to resume)");
+        assert_eq!(lines[2], "├─ Time: 12m34s active, paused for 1m35s in all");
+        assert_eq!(lines[5], "├─ Errors: none");
+    }
+}
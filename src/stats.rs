@@ -0,0 +1,560 @@
+use console::style;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::cli::format_duration;
+use crate::errors::{ErrorCategory, ErrorTally};
+use crate::metrics::{Findings, Metrics, PingCheck, RateLimited, Skipped};
+use crate::pause::ActiveTimer;
+use crate::ratelimit::{format_bytes, BandwidthMeter};
+use crate::modelfilter::{FilterRule, ModelFilter};
+use crate::modelname;
+use crate::sampling::SamplePlan;
+use crate::shard::Shard;
+use crate::{console_log, Model, HEADER_STYLE, LAST_ITEM_STYLE, LIST_ITEM_STYLE};
+
+const TOP_MODELS: usize = 10;
+/// Findings kept for the status snapshot
+const RECENT_FINDINGS: usize = 3;
+
+#[derive(Debug, Default)]
+struct ModelTally {
+    /// By base name, so `llama3:8b` and `llama3:latest` count as one
+    endpoints_per_model: HashMap<String, u64>,
+    /// Distinct `base:tag-variant` names
+    tagged: HashSet<String>,
+    families: HashMap<String, u64>,
+    parameter_sizes: HashMap<String, u64>,
+    total_bytes: u64,
+}
+
+/// Aggregated while the scan runs so the summary never has to re-read the CSVs.
+#[derive(Debug)]
+pub struct ScanStats {
+    timer: ActiveTimer,
+    attempted: AtomicU64,
+    /// Ollama requests sent, one per port of each host
+    probes: AtomicU64,
+    hits: AtomicU64,
+    other_hits: AtomicU64,
+    unverified: AtomicU64,
+    /// Non-200 /api/tags answers on the Ollama port, per status
+    possible: Mutex<HashMap<u16, u64>>,
+    /// Those left off the console by `--report-status`, per status
+    silenced: Mutex<HashMap<u16, u64>>,
+    finished_while_pausing: AtomicU64,
+    /// Model rows held back by each of `--quant`, `--min-quant` and `--family`
+    filtered: [AtomicU64; 3],
+    /// Endpoints skipped by `--require-quant-match`/`--require-family`
+    filter_mismatches: AtomicU64,
+    /// Addresses left unprobed by `--only-countries`/`--skip-countries`, per country
+    countries_excluded: Mutex<HashMap<String, u64>>,
+    /// Addresses skipped because an earlier target had already listed them
+    duplicates: AtomicU64,
+    /// Hosts `--precheck icmp` skipped for not answering a ping, and the
+    /// requests they would have taken
+    ping_skipped: AtomicU64,
+    ping_avoided: AtomicU64,
+    /// Hosts `--precheck icmp-soft` probed without a ping answer, and those
+    /// of them that turned out to be Ollama endpoints
+    ping_silent_probed: AtomicU64,
+    ping_silent_found: AtomicU64,
+    /// 429 answers per /24
+    throttled: Mutex<HashMap<u32, u64>>,
+    /// Hosts probed again in the second pass, and the endpoints found there
+    retried: AtomicU64,
+    recovered: AtomicU64,
+    /// Hosts left out of the second pass with its queue full
+    retry_overflow: AtomicU64,
+    /// CSV rows the writer gave up on
+    unwritten: AtomicU64,
+    errors: ErrorTally,
+    models: Mutex<ModelTally>,
+    /// The latest findings, oldest first
+    recent: Mutex<VecDeque<String>>,
+}
+
+impl Default for ScanStats {
+    fn default() -> Self {
+        Self {
+            timer: ActiveTimer::start(),
+            attempted: AtomicU64::new(0),
+            probes: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            other_hits: AtomicU64::new(0),
+            unverified: AtomicU64::new(0),
+            possible: Mutex::new(HashMap::new()),
+            silenced: Mutex::new(HashMap::new()),
+            finished_while_pausing: AtomicU64::new(0),
+            filtered: Default::default(),
+            filter_mismatches: AtomicU64::new(0),
+            countries_excluded: Mutex::new(HashMap::new()),
+            duplicates: AtomicU64::new(0),
+            ping_skipped: AtomicU64::new(0),
+            ping_avoided: AtomicU64::new(0),
+            ping_silent_probed: AtomicU64::new(0),
+            ping_silent_found: AtomicU64::new(0),
+            throttled: Mutex::new(HashMap::new()),
+            retried: AtomicU64::new(0),
+            recovered: AtomicU64::new(0),
+            retry_overflow: AtomicU64::new(0),
+            unwritten: AtomicU64::new(0),
+            errors: ErrorTally::default(),
+            models: Mutex::new(ModelTally::default()),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_FINDINGS)),
+        }
+    }
+}
+
+fn sorted_desc(map: &HashMap<String, u64>) -> Vec<(&str, u64)> {
+    let mut entries: Vec<_> = map.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    entries
+}
+
+fn format_distribution(map: &HashMap<String, u64>) -> String {
+    sorted_desc(map)
+        .iter()
+        .map(|(k, v)| format!("{} ({})", if k.is_empty() { "unknown" } else { k }, v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 12403 as "12,403".
+fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+fn or_unknown(value: &str) -> String {
+    if value.is_empty() { "unknown".to_string() } else { value.to_string() }
+}
+
+impl ScanStats {
+    pub fn record_attempt(&self) {
+        self.attempted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_probe(&self) {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_hit(&self, models: &[Model]) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        let mut tally = self.models.lock().unwrap();
+        let mut bases = HashSet::new();
+        for model in models {
+            let name = modelname::normalize(&model.name);
+            tally.tagged.insert(name.to_string());
+            if bases.insert(name.base.clone()) {
+                *tally.endpoints_per_model.entry(name.base).or_default() += 1;
+            }
+            // Older Ollama releases list models without these
+            *tally.families.entry(or_unknown(&model.details.family)).or_default() += 1;
+            *tally.parameter_sizes.entry(or_unknown(&model.details.parameter_size)).or_default() += 1;
+            tally.total_bytes += model.size;
+        }
+    }
+
+    /// A finding as the status snapshot lists it, e.g. its URL.
+    pub fn remember_finding(&self, finding: String) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_FINDINGS {
+            recent.pop_front();
+        }
+        recent.push_back(finding);
+    }
+
+    /// A server found by one of the `--also` probes.
+    pub fn record_other_hit(&self) {
+        self.other_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An /api/tags answer that did not look like Ollama.
+    pub fn record_unverified(&self) {
+        self.unverified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A non-200 answer from /api/tags on the Ollama port.
+    pub fn record_possible(&self, status: u16) {
+        *self.possible.lock().unwrap().entry(status).or_default() += 1;
+    }
+
+    /// A possible server whose status `--report-status` leaves off the console.
+    pub fn record_silenced(&self, status: u16) {
+        *self.silenced.lock().unwrap().entry(status).or_default() += 1;
+    }
+
+    /// A probe that was already in flight when the scan was paused.
+    pub fn record_finished_while_pausing(&self) {
+        self.finished_while_pausing.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A model row `rule` kept out of llm_models.csv.
+    pub fn record_filtered(&self, rule: FilterRule) {
+        self.filtered[rule as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An endpoint skipped because none of its models passed the model filters.
+    pub fn record_filter_mismatch(&self) {
+        self.filter_mismatches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// An address the country filter kept from being probed.
+    pub fn record_country_excluded(&self, country: String) {
+        *self.countries_excluded.lock().unwrap().entry(country).or_default() += 1;
+    }
+
+    pub fn record_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A host left unprobed for not answering a ping, and the requests its
+    /// probes would have sent.
+    pub fn record_ping_skipped(&self, requests: u64) {
+        self.ping_skipped.fetch_add(1, Ordering::Relaxed);
+        self.ping_avoided.fetch_add(requests, Ordering::Relaxed);
+    }
+
+    pub fn record_ping_silent_probed(&self) {
+        self.ping_silent_probed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A host that did not answer a ping but served Ollama.
+    pub fn record_ping_silent_found(&self) {
+        self.ping_silent_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retried(&self, hosts: u64) {
+        self.retried.fetch_add(hosts, Ordering::Relaxed);
+    }
+
+    pub fn record_recovered(&self) {
+        self.recovered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry_overflow(&self) {
+        self.retry_overflow.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_unwritten(&self, rows: u64) {
+        self.unwritten.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    pub fn record_throttled(&self, ip: Ipv4Addr) {
+        *self.throttled.lock().unwrap().entry(u32::from(ip) >> 8).or_default() += 1;
+    }
+
+    pub fn record_error(&self, ip: &str, err: &reqwest::Error) {
+        self.errors.record(ip, err);
+    }
+
+    pub fn record_oversized(&self, ip: &str, message: impl FnOnce() -> String) {
+        self.errors.record_as(ErrorCategory::Oversized, ip, message);
+    }
+
+    pub fn errors(&self) -> &ErrorTally {
+        &self.errors
+    }
+
+    pub fn probes(&self) -> u64 {
+        self.probes.load(Ordering::Relaxed)
+    }
+
+    pub fn attempted(&self) -> u64 {
+        self.attempted.load(Ordering::Relaxed)
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn other_hits(&self) -> u64 {
+        self.other_hits.load(Ordering::Relaxed)
+    }
+
+    /// Time spent scanning, excluding pauses.
+    pub fn elapsed(&self) -> Duration {
+        self.timer.active()
+    }
+
+    pub fn paused(&self) -> Duration {
+        self.timer.paused()
+    }
+
+    /// Up to the last `RECENT_FINDINGS` findings, newest first.
+    pub fn recent_findings(&self) -> Vec<String> {
+        self.recent.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// A snapshot of the counters for metrics.json and the summary.
+    pub fn metrics(&self, run_id: &str, targets: u64, bandwidth: &BandwidthMeter, stopped: bool) -> Metrics {
+        let scanned = self.attempted();
+        let elapsed = self.elapsed();
+        let possible: BTreeMap<u16, u64> = self.possible.lock().unwrap().iter().map(|(k, v)| (*k, *v)).collect();
+        let throttled = self.throttled.lock().unwrap();
+        Metrics {
+            run_id: run_id.to_string(),
+            targets,
+            scanned,
+            probes: self.probes().max(scanned),
+            skipped: Skipped {
+                duplicates: self.duplicates.load(Ordering::Relaxed),
+                excluded_by_country: self.countries_excluded.lock().unwrap().iter().map(|(k, v)| (k.clone(), *v)).collect(),
+                filtered_endpoints: self.filter_mismatches.load(Ordering::Relaxed),
+                filtered_model_rows: FilterRule::ALL
+                    .into_iter()
+                    .map(|rule| (rule.flag(), self.filtered[rule as usize].load(Ordering::Relaxed)))
+                    .filter(|(_, rows)| *rows > 0)
+                    .collect(),
+            },
+            findings: Findings {
+                endpoints: self.hits(),
+                other_services: self.other_hits(),
+                unverified: self.unverified.load(Ordering::Relaxed),
+                recovered: self.recovered.load(Ordering::Relaxed),
+                retried: self.retried.load(Ordering::Relaxed),
+                retry_overflow: self.retry_overflow.load(Ordering::Relaxed),
+            },
+            protected: [401, 403].iter().filter_map(|status| possible.get(status)).sum(),
+            possible_servers: possible,
+            errors: ErrorCategory::ALL
+                .into_iter()
+                .map(|category| (category.label(), self.errors.count(category)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            errors_total: self.errors.total(),
+            rate_limited: RateLimited { answers: throttled.values().sum(), subnets: throttled.len() as u64 },
+            precheck: PingCheck {
+                skipped: self.ping_skipped.load(Ordering::Relaxed),
+                probes_avoided: self.ping_avoided.load(Ordering::Relaxed),
+                silent_probed: self.ping_silent_probed.load(Ordering::Relaxed),
+                silent_endpoints: self.ping_silent_found.load(Ordering::Relaxed),
+            },
+            duration_secs: elapsed.as_secs_f64(),
+            paused_secs: self.timer.paused().as_secs_f64(),
+            average_rate: scanned as f64 / elapsed.as_secs_f64().max(0.001),
+            bytes_received: bandwidth.total(),
+            rows_not_written: self.unwritten.load(Ordering::Relaxed),
+            termination: if stopped { "stopped" } else { "completed" },
+        }
+    }
+
+    pub fn print_summary(&self, metrics: &Metrics, sample_plan: Option<SamplePlan>, shard: Option<Shard>, model_filter: Option<&ModelFilter>) {
+        let attempted = metrics.scanned;
+        let tally = self.models.lock().unwrap();
+
+        console_log(format!("\n{}{}", HEADER_STYLE, style("Scan Summary").blue().bold()));
+        console_log(format!("{}IPs attempted: {}", LIST_ITEM_STYLE, style(attempted).cyan()));
+        // Errors are counted per port, so their shares are of the ports probed
+        let probes = metrics.probes;
+        if probes > attempted {
+            console_log(format!("{}Ports probed: {}", LIST_ITEM_STYLE, style(probes).cyan()));
+        }
+        if let Some(shard) = shard {
+            console_log(format!("{}Shard: {}", LIST_ITEM_STYLE, style(shard).cyan()));
+        }
+        console_log(format!("{}Elapsed: {} ({} IPs/s)",
+            LIST_ITEM_STYLE,
+            style(format!("{:.1}s", metrics.duration_secs)).cyan(),
+            style(format!("{:.1}", metrics.average_rate)).cyan()
+        ));
+        let paused = Duration::from_secs_f64(metrics.paused_secs);
+        if paused >= Duration::from_secs(1) {
+            console_log(format!("{}Paused for: {} (not counted above; {} in-flight requests finished after pausing)",
+                LIST_ITEM_STYLE,
+                style(format_duration(paused)).cyan(),
+                self.finished_while_pausing.load(Ordering::Relaxed)
+            ));
+        }
+        console_log(format!("{}Received: {} {}",
+            LIST_ITEM_STYLE,
+            style(format_bytes(metrics.bytes_received)).cyan(),
+            style("(response headers and bodies)").dim()
+        ));
+        console_log(format!("{}Connection errors: {}", LIST_ITEM_STYLE, style(metrics.errors_total).cyan()));
+        if metrics.errors_total > 0 {
+            let categories: Vec<_> = ErrorCategory::ALL.iter().filter_map(|c| Some((c.label(), *metrics.errors.get(c.label())?))).collect();
+            for (i, (label, count)) in categories.iter().enumerate() {
+                let prefix = if i == categories.len() - 1 { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
+                console_log(format!("  {}{:<18} {} {}",
+                    prefix,
+                    label,
+                    style(count).cyan(),
+                    style(format!("({:.1}%)", *count as f64 / probes.max(1) as f64 * 100.0)).dim()
+                ));
+            }
+        }
+        let findings = &metrics.findings;
+        console_log(format!("{}Endpoints found: {}", LIST_ITEM_STYLE, style(findings.endpoints).green()));
+        if metrics.rows_not_written > 0 {
+            console_log(format!("{}{} {}",
+                LIST_ITEM_STYLE,
+                style(format!("CSV rows not written: {}", metrics.rows_not_written)).red().bold(),
+                style("(see the write errors above)").dim()
+            ));
+        }
+        if findings.other_services > 0 {
+            console_log(format!("{}Other LLM servers found: {}", LIST_ITEM_STYLE, style(findings.other_services).green()));
+        }
+        let unverified = findings.unverified;
+        if unverified > 0 {
+            console_log(format!("{}Unverified responses: {}", LIST_ITEM_STYLE, style(unverified).yellow()));
+        }
+        let silenced = self.silenced.lock().unwrap();
+        let mut statuses: Vec<_> = silenced.iter().collect();
+        statuses.sort();
+        for (status, hosts) in statuses {
+            console_log(format!("{}{} {}",
+                LIST_ITEM_STYLE,
+                style(format!("{} {} answered {}", group_digits(*hosts), if *hosts == 1 { "host" } else { "hosts" }, status)).yellow(),
+                style("(not reported, see --report-status)").dim()
+            ));
+        }
+        let (retried, overflow) = (findings.retried, findings.retry_overflow);
+        if retried > 0 || overflow > 0 {
+            console_log(format!("{}Recovered on the second pass: {} {}",
+                LIST_ITEM_STYLE,
+                style(findings.recovered).green(),
+                style(match overflow {
+                    0 => format!("({} hosts retried)", retried),
+                    _ => format!("({} hosts retried, {} more left out with --retry-queue full)", retried, overflow),
+                }).dim()
+            ));
+        }
+        let throttled = &metrics.rate_limited;
+        if throttled.answers > 0 {
+            console_log(format!("{}Rate-limited (429): {} {}",
+                LIST_ITEM_STYLE,
+                style(throttled.answers).yellow(),
+                style(format!("(from {} /24s)", throttled.subnets)).dim()
+            ));
+        }
+        let duplicates = metrics.skipped.duplicates;
+        if duplicates > 0 {
+            console_log(format!("{}Duplicates skipped: {} {}",
+                LIST_ITEM_STYLE,
+                style(duplicates).yellow(),
+                style("(listed by more than one target, probed once)").dim()
+            ));
+        }
+        let ping = &metrics.precheck;
+        if ping.skipped > 0 {
+            console_log(format!("{}HTTP probes avoided by the ping pre-check: {} {}",
+                LIST_ITEM_STYLE,
+                style(group_digits(ping.probes_avoided)).yellow(),
+                style(format!("({} hosts did not answer; --precheck icmp-soft would estimate how many endpoints that misses)", group_digits(ping.skipped))).dim()
+            ));
+        }
+        if ping.silent_probed > 0 {
+            console_log(format!("{}Probed without a ping answer: {} {}",
+                LIST_ITEM_STYLE,
+                style(group_digits(ping.silent_probed)).yellow(),
+                style(format!("({} of them Ollama endpoints; --precheck icmp would have missed {:.1}% of the endpoints found)",
+                    ping.silent_endpoints,
+                    ping.silent_endpoints as f64 / findings.endpoints.max(1) as f64 * 100.0
+                )).dim()
+            ));
+        }
+        let countries: HashMap<String, u64> = metrics.skipped.excluded_by_country.clone().into_iter().collect();
+        if !countries.is_empty() {
+            console_log(format!("{}Excluded by country: {} {}",
+                LIST_ITEM_STYLE,
+                style(countries.values().sum::<u64>()).yellow(),
+                style(format!("({})", format_distribution(&countries))).dim()
+            ));
+        }
+        if let Some(filter) = model_filter {
+            for rule in FilterRule::ALL.into_iter().filter(|rule| filter.is_active(*rule)) {
+                console_log(format!("{}Model rows filtered by {}: {}",
+                    LIST_ITEM_STYLE,
+                    rule.flag(),
+                    style(metrics.skipped.filtered_model_rows.get(rule.flag()).copied().unwrap_or_default()).yellow()
+                ));
+            }
+            if filter.require_match {
+                console_log(format!("{}Endpoints without a matching model, skipped: {}",
+                    LIST_ITEM_STYLE,
+                    style(metrics.skipped.filtered_endpoints).yellow()
+                ));
+            }
+        }
+        if let Some(plan) = sample_plan {
+            let per_million = findings.endpoints as f64 / attempted.max(1) as f64 * 1_000_000.0;
+            console_log(format!("{}Sample: {} → ~{} endpoints per million addresses",
+                LIST_ITEM_STYLE,
+                plan.describe(),
+                style(format!("{:.0}", per_million)).green()
+            ));
+            console_log(format!("{}{}",
+                LIST_ITEM_STYLE,
+                style("(extrapolated from a random sample; small samples carry wide error margins)").dim()
+            ));
+        }
+        console_log(format!("{}Distinct models: {} ({} with their tags, {:.2} GB observed)",
+            LIST_ITEM_STYLE,
+            style(tally.endpoints_per_model.len()).cyan(),
+            tally.tagged.len(),
+            tally.total_bytes as f64 / 1_073_741_824.0
+        ));
+
+        if !tally.endpoints_per_model.is_empty() {
+            console_log(format!("{}Top models by endpoints:", LIST_ITEM_STYLE));
+            let top = sorted_desc(&tally.endpoints_per_model);
+            let top = &top[..top.len().min(TOP_MODELS)];
+            for (i, (name, count)) in top.iter().enumerate() {
+                let prefix = if i == top.len() - 1 { LAST_ITEM_STYLE } else { LIST_ITEM_STYLE };
+                console_log(format!("  {}{} {}", prefix, style(name).blue(), style(format!("({})", count)).dim()));
+            }
+            console_log(format!("{}Families: {}", LIST_ITEM_STYLE, format_distribution(&tally.families)));
+            console_log(format!("{}Parameter sizes: {}", LAST_ITEM_STYLE, format_distribution(&tally.parameter_sizes)));
+        } else {
+            console_log(format!("{}No models observed", LAST_ITEM_STYLE));
+        }
+        console_log("".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_digits_in_threes() {
+        for (n, grouped) in [(0, "0"), (999, "999"), (1000, "1,000"), (12403, "12,403"), (1234567, "1,234,567")] {
+            assert_eq!(group_digits(n), grouped);
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_three_findings() {
+        let stats = ScanStats::default();
+        for n in 1..=5 {
+            stats.remember_finding(format!("http://10.0.0.{}:11434", n));
+        }
+        assert_eq!(stats.recent_findings(), ["http://10.0.0.5:11434", "http://10.0.0.4:11434", "http://10.0.0.3:11434"]);
+    }
+
+    #[test]
+    fn counts_models_by_base_name_once_per_endpoint() {
+        let models = |names: &[&str]| -> Vec<Model> {
+            serde_json::from_value(names.iter().map(|name| serde_json::json!({"name": name})).collect()).unwrap()
+        };
+        let stats = ScanStats::default();
+        stats.record_hit(&models(&["llama3:latest", "llama3:8b-instruct-q4_K_M", "registry.ollama.ai/library/llama3:8b"]));
+        stats.record_hit(&models(&["LLAMA3", "jondurbin/airoboros"]));
+        let tally = stats.models.lock().unwrap();
+        assert_eq!(tally.endpoints_per_model, HashMap::from([("llama3".to_string(), 2), ("jondurbin/airoboros".to_string(), 1)]));
+        assert_eq!(tally.tagged.len(), 4);
+    }
+}
@@ -0,0 +1,373 @@
+//! `--syn`: a built-in half-open SYN sweep, for scans large enough that a
+//! full handshake per address is the bottleneck. A SYN goes to each host's
+//! Ollama ports at `--syn-rate`; hosts answering with a SYN-ACK are passed
+//! on to the probes, which verify them over HTTP as usual. The kernel resets
+//! the half-open connections itself, since no socket of ours expects them.
+//!
+//! Nothing is kept per packet. The sequence number of each SYN is a keyed
+//! hash of its destination, so an answer is matched by checking that it
+//! acknowledges that number. Resets and ICMP unreachables are matched the
+//! same way and counted, and anything else is ignored.
+//!
+//! Sending and receiving need raw sockets: root, or CAP_NET_RAW on the
+//! binary. Linux only, since it relies on raw TCP sockets receiving every
+//! inbound segment.
+
+use anyhow::{bail, Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket as StdUdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+
+use crate::cli::format_duration;
+use crate::prescan::{OpenHosts, Sweep, STOP_CHECK};
+use crate::ratelimit::TokenBucket;
+use crate::targets::Target;
+use crate::STOP_SCAN;
+
+/// Room for late answers while the SYNs go out at full rate
+const RECEIVE_BUFFER: usize = 8 * 1024 * 1024;
+/// Largest packet read; answers are a few dozen bytes
+const MAX_PACKET: usize = 1500;
+const SYN_WINDOW: u16 = 1024;
+/// Sent as the one TCP option, as real stacks do
+const MSS: u16 = 1460;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+const ICMP_UNREACHABLE: u8 = 3;
+
+pub struct SynScanner {
+    rate: u32,
+    wait: Duration,
+    /// Key for the sequence numbers, new for each run
+    cookies: RandomState,
+}
+
+impl SynScanner {
+    /// Checks for raw socket access before anything is sent, so a missing
+    /// privilege ends the run at once.
+    pub fn open(rate: u32, wait: Duration) -> Result<Self> {
+        if let Err(e) = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP)) {
+            let binary = std::env::current_exe().map_or_else(|_| "public-ollama-finder".to_string(), |path| path.display().to_string());
+            bail!(
+                "--syn needs raw sockets ({}). Run as root, or grant them to the binary once with \
+                 `sudo setcap cap_net_raw+eip {}`",
+                e,
+                binary
+            );
+        }
+        Ok(Self { rate, wait, cookies: RandomState::new() })
+    }
+
+    /// "built-in SYN scan at 10000 packets/s, waiting 2s for late answers",
+    /// for the banner.
+    pub fn describe(&self) -> String {
+        format!("built-in SYN scan at {} packets/s, waiting {} for late answers", self.rate, format_duration(self.wait))
+    }
+
+    /// Sends a SYN to every port of `ports` on `target`'s hosts, passing on
+    /// those that answer, and waits for late answers once all went out.
+    pub fn sweep(&self, target: &Target, ports: &[u16], open: OpenHosts) -> JoinHandle<Result<Sweep>> {
+        let (rate, wait, cookies) = (self.rate, self.wait, self.cookies.clone());
+        let target = target.clone();
+        let ports = ports.to_vec();
+        tokio::spawn(async move {
+            let tcp = Arc::new(raw_socket(Protocol::TCP)?);
+            let icmp = raw_socket(Protocol::ICMPV4)?;
+            // Bound but never listening, so no other connection takes the
+            // port and the kernel resets what comes back to it
+            let reservation = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+            reservation.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)).into())?;
+            let source_port = reservation.local_addr()?.as_socket().context("No local port for the SYN scan")?.port();
+            let probes = Probes { source_port, cookies };
+
+            let sender = tokio::spawn(send_all(tcp.clone(), target.hosts(), ports.clone(), probes.clone(), rate));
+            let sweep = receive(&tcp, &icmp, &ports, &probes, open, sender, wait).await;
+            drop(reservation);
+            sweep
+        })
+    }
+}
+
+/// A raw IPv4 socket for `protocol`, read and written through tokio.
+fn raw_socket(protocol: Protocol) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(protocol)).context("Failed to open a raw socket for --syn")?;
+    socket.set_nonblocking(true)?;
+    // A smaller buffer only means more answers dropped at high rates
+    let _ = socket.set_recv_buffer_size(RECEIVE_BUFFER);
+    Ok(UdpSocket::from_std(StdUdpSocket::from(socket))?)
+}
+
+/// What every SYN of a sweep shares, and how its answers are recognized.
+#[derive(Clone)]
+struct Probes {
+    source_port: u16,
+    cookies: RandomState,
+}
+
+impl Probes {
+    /// The sequence number of the SYN to `ip`:`port`.
+    fn cookie(&self, ip: Ipv4Addr, port: u16) -> u32 {
+        self.cookies.hash_one((ip, port)) as u32
+    }
+}
+
+/// Sends the SYNs at `rate` per second. Returns how many could not be sent,
+/// which happens when there is no route to the host.
+async fn send_all(socket: Arc<UdpSocket>, hosts: impl Iterator<Item = Ipv4Addr>, ports: Vec<u16>, probes: Probes, rate: u32) -> u64 {
+    // Tokens come in 10ms batches so timer resolution does not cap the rate
+    let bucket = TokenBucket::new(rate, (rate / 100).max(1));
+    // The address the kernel sends from, which the checksum covers, per /24
+    let mut sources: HashMap<u32, Option<Ipv4Addr>> = HashMap::new();
+    let mut failed = 0;
+    for ip in hosts {
+        let source = *sources.entry(u32::from(ip) >> 8).or_insert_with(|| route_source(ip));
+        for &port in &ports {
+            if STOP_SCAN.load(Ordering::Relaxed) {
+                return failed;
+            }
+            let Some(source) = source else {
+                failed += 1;
+                continue;
+            };
+            bucket.acquire().await;
+            let packet = syn_packet(source, ip, probes.source_port, port, probes.cookie(ip, port));
+            if socket.send_to(&packet, SocketAddrV4::new(ip, 0)).await.is_err() {
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+/// The local address packets to `ip` leave from.
+fn route_source(ip: Ipv4Addr) -> Option<Ipv4Addr> {
+    let socket = StdUdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((ip, 9)).ok()?;
+    match socket.local_addr().ok()? {
+        SocketAddr::V4(local) => Some(*local.ip()),
+        SocketAddr::V6(_) => None,
+    }
+}
+
+/// Matches answers until the SYNs are all out and `wait` has passed since,
+/// or the hosts are no longer wanted.
+async fn receive(tcp: &UdpSocket, icmp: &UdpSocket, ports: &[u16], probes: &Probes, mut open: OpenHosts, mut sender: JoinHandle<u64>, wait: Duration) -> Result<Sweep> {
+    let mut sweep = Sweep::default();
+    let mut segment = [0u8; MAX_PACKET];
+    let mut message = [0u8; MAX_PACKET];
+    let mut deadline = None;
+    let mut stop_check = tokio::time::interval(STOP_CHECK);
+    loop {
+        tokio::select! {
+            received = tcp.recv_from(&mut segment) => {
+                let Ok((len, _)) = received else { continue };
+                match answer(&segment[..len], ports, probes) {
+                    Some(Answer::Open(ip)) if !open.report(ip) => break,
+                    Some(Answer::Open(_)) | None => {}
+                    Some(Answer::Refused) => sweep.refused += 1,
+                }
+            }
+            received = icmp.recv_from(&mut message) => {
+                let Ok((len, _)) = received else { continue };
+                if unreachable(&message[..len], ports, probes) {
+                    sweep.unreachable += 1;
+                }
+            }
+            failed = &mut sender, if deadline.is_none() => {
+                sweep.unreachable += failed.context("The SYN sender failed")?;
+                deadline = Some(tokio::time::Instant::now() + wait);
+            }
+            _ = stop_check.tick() => {
+                if open.abandoned() || deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                    break;
+                }
+            }
+        }
+    }
+    sender.abort();
+    sweep.open = open.count();
+    Ok(sweep)
+}
+
+/// What a TCP segment says about one of the SYNs.
+#[derive(Debug, PartialEq)]
+enum Answer {
+    Open(Ipv4Addr),
+    Refused,
+}
+
+/// The IPv4 header's length and the packet's source, protocol and payload.
+fn ipv4(packet: &[u8]) -> Option<(Ipv4Addr, Ipv4Addr, u8, &[u8])> {
+    let header = usize::from(*packet.first()? & 0x0f) * 4;
+    if packet[0] >> 4 != 4 || header < 20 || packet.len() < header {
+        return None;
+    }
+    let source = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    let destination = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+    Some((source, destination, packet[9], &packet[header..]))
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// Whether `packet`, as a raw TCP socket reads it, answers one of the SYNs:
+/// it comes from one of `ports` to ours and acknowledges the SYN's cookie.
+fn answer(packet: &[u8], ports: &[u16], probes: &Probes) -> Option<Answer> {
+    let (source, _, protocol, tcp) = ipv4(packet)?;
+    if protocol != 6 || tcp.len() < 20 {
+        return None;
+    }
+    let (from_port, to_port) = (u16_at(tcp, 0), u16_at(tcp, 2));
+    if to_port != probes.source_port || !ports.contains(&from_port) {
+        return None;
+    }
+    let flags = tcp[13];
+    if flags & TCP_ACK == 0 || u32_at(tcp, 8) != probes.cookie(source, from_port).wrapping_add(1) {
+        return None;
+    }
+    match flags & (TCP_SYN | TCP_RST | TCP_FIN) {
+        TCP_SYN => Some(Answer::Open(source)),
+        TCP_RST => Some(Answer::Refused),
+        _ => None,
+    }
+}
+
+/// Whether `packet`, as a raw ICMP socket reads it, reports one of the SYNs
+/// undeliverable. The quoted header is ours, so it carries the cookie as is.
+fn unreachable(packet: &[u8], ports: &[u16], probes: &Probes) -> bool {
+    let Some((_, _, 1, icmp)) = ipv4(packet) else {
+        return false;
+    };
+    if icmp.len() < 8 || icmp[0] != ICMP_UNREACHABLE {
+        return false;
+    }
+    let Some((_, destination, 6, tcp)) = ipv4(&icmp[8..]) else {
+        return false;
+    };
+    // Routers only have to quote the first 8 bytes of the segment
+    if tcp.len() < 8 {
+        return false;
+    }
+    let (from_port, to_port) = (u16_at(tcp, 0), u16_at(tcp, 2));
+    from_port == probes.source_port && ports.contains(&to_port) && u32_at(tcp, 4) == probes.cookie(destination, to_port)
+}
+
+/// A TCP SYN segment with an MSS option; the kernel adds the IP header.
+fn syn_packet(source: Ipv4Addr, destination: Ipv4Addr, source_port: u16, port: u16, sequence: u32) -> [u8; 24] {
+    let mut segment = [0u8; 24];
+    segment[0..2].copy_from_slice(&source_port.to_be_bytes());
+    segment[2..4].copy_from_slice(&port.to_be_bytes());
+    segment[4..8].copy_from_slice(&sequence.to_be_bytes());
+    // Six 32-bit words of header
+    segment[12] = 6 << 4;
+    segment[13] = TCP_SYN;
+    segment[14..16].copy_from_slice(&SYN_WINDOW.to_be_bytes());
+    segment[20..22].copy_from_slice(&[2, 4]);
+    segment[22..24].copy_from_slice(&MSS.to_be_bytes());
+    let checksum = tcp_checksum(source, destination, &segment);
+    segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+    segment
+}
+
+/// The TCP checksum of `segment` between `source` and `destination`, which
+/// covers a pseudo-header with both addresses.
+fn tcp_checksum(source: Ipv4Addr, destination: Ipv4Addr, segment: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut add = |bytes: &[u8]| {
+        for pair in bytes.chunks(2) {
+            sum += u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]));
+        }
+    };
+    add(&source.octets());
+    add(&destination.octets());
+    add(&[0, 6]);
+    add(&(segment.len() as u16).to_be_bytes());
+    add(segment);
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probes() -> Probes {
+        Probes { source_port: 40_000, cookies: RandomState::new() }
+    }
+
+    /// An IPv4 packet from `source` to `destination` around `payload`.
+    fn packet(source: Ipv4Addr, destination: Ipv4Addr, protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0];
+        packet.extend(source.octets());
+        packet.extend(destination.octets());
+        packet.extend(payload);
+        packet
+    }
+
+    /// A segment from `port` on `ip` to the scan's port.
+    fn reply(probes: &Probes, ip: Ipv4Addr, port: u16, flags: u8, ack: u32) -> Vec<u8> {
+        let mut tcp = [0u8; 20];
+        tcp[0..2].copy_from_slice(&port.to_be_bytes());
+        tcp[2..4].copy_from_slice(&probes.source_port.to_be_bytes());
+        tcp[8..12].copy_from_slice(&ack.to_be_bytes());
+        tcp[12] = 5 << 4;
+        tcp[13] = flags;
+        packet(ip, Ipv4Addr::new(192, 0, 2, 1), 6, &tcp)
+    }
+
+    #[test]
+    fn checksums_the_syn_with_its_pseudo_header() {
+        let (source, destination) = (Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(198, 51, 100, 7));
+        let segment = syn_packet(source, destination, 40_000, 11434, 0x0102_0304);
+        assert_eq!(&segment[..4], &[0x9c, 0x40, 0x2c, 0xaa]);
+        assert_eq!(segment[12], 0x60);
+        assert_eq!(segment[13], TCP_SYN);
+        // A correct checksum sums the whole segment, itself included, to zero
+        assert_eq!(tcp_checksum(source, destination, &segment), 0);
+    }
+
+    #[test]
+    fn matches_answers_by_their_acknowledgement() {
+        let probes = probes();
+        let ip = Ipv4Addr::new(198, 51, 100, 7);
+        let cookie = probes.cookie(ip, 11434);
+        assert_eq!(answer(&reply(&probes, ip, 11434, TCP_SYN | TCP_ACK, cookie.wrapping_add(1)), &[11434], &probes), Some(Answer::Open(ip)));
+        assert_eq!(answer(&reply(&probes, ip, 11434, TCP_RST | TCP_ACK, cookie.wrapping_add(1)), &[11434], &probes), Some(Answer::Refused));
+        // Not acknowledging our SYN, from another port, or not an answer at all
+        assert_eq!(answer(&reply(&probes, ip, 11434, TCP_SYN | TCP_ACK, cookie), &[11434], &probes), None);
+        assert_eq!(answer(&reply(&probes, ip, 8080, TCP_SYN | TCP_ACK, probes.cookie(ip, 8080).wrapping_add(1)), &[11434], &probes), None);
+        assert_eq!(answer(&reply(&probes, ip, 11434, TCP_SYN, cookie.wrapping_add(1)), &[11434], &probes), None);
+        assert_eq!(answer(&[0x45, 0, 0], &[11434], &probes), None);
+    }
+
+    #[test]
+    fn matches_unreachables_by_the_quoted_syn() {
+        let probes = probes();
+        let (us, ip) = (Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(198, 51, 100, 7));
+        let quoted = |sequence: u32| {
+            let syn = syn_packet(us, ip, probes.source_port, 11434, sequence);
+            let mut icmp = vec![ICMP_UNREACHABLE, 1, 0, 0, 0, 0, 0, 0];
+            icmp.extend(packet(us, ip, 6, &syn[..8]));
+            packet(Ipv4Addr::new(203, 0, 113, 1), us, 1, &icmp)
+        };
+        assert!(unreachable(&quoted(probes.cookie(ip, 11434)), &[11434], &probes));
+        assert!(!unreachable(&quoted(probes.cookie(ip, 11434) ^ 1), &[11434], &probes));
+        assert!(!unreachable(&quoted(probes.cookie(ip, 11434)), &[8080], &probes));
+    }
+}
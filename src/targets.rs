@@ -0,0 +1,200 @@
+use ipnet::{Ipv4AddrRange, Ipv4Net};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use crate::cli::ScanArgs;
+use crate::sampling::sampled_hosts;
+
+/// A CIDR block to scan. Blocks cut from an address range cover every address
+/// in them; a CIDR given as-is skips its network and broadcast addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Block {
+    pub network: Ipv4Net,
+    pub whole: bool,
+}
+
+impl Block {
+    pub fn cidr(network: Ipv4Net) -> Self {
+        Self { network, whole: false }
+    }
+
+    pub fn whole(network: Ipv4Net) -> Self {
+        Self { network, whole: true }
+    }
+
+    pub fn hosts(&self) -> Ipv4AddrRange {
+        if self.whole {
+            Ipv4AddrRange::new(self.network.network(), self.network.broadcast())
+        } else {
+            self.network.hosts()
+        }
+    }
+}
+
+/// An input entry a target was read from: a file or URL, and the line of it
+/// when there is one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    pub origin: String,
+    pub line: Option<usize>,
+}
+
+impl Source {
+    pub fn new(origin: impl Into<String>, line: Option<usize>) -> Self {
+        Self { origin: origin.into(), line }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.origin, line),
+            None => f.write_str(&self.origin),
+        }
+    }
+}
+
+/// Where a target came from: its Location label and every input entry that
+/// listed it. Carried along with the target's hosts into each finding.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub location: String,
+    #[serde(default)]
+    pub sources: Vec<Source>,
+}
+
+impl Provenance {
+    pub fn new(location: String, source: Source) -> Self {
+        Self { location, sources: vec![source] }
+    }
+
+    /// The sources as written to the Source column, e.g. "ovh.txt:3; hetzner.json:12".
+    pub fn source_text(&self) -> String {
+        self.sources.iter().map(Source::to_string).collect::<Vec<_>>().join("; ")
+    }
+}
+
+/// One entry of the target lists: the blocks it expands to and where it came
+/// from. Its blocks are scanned, sampled and counted as a single range.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub blocks: Vec<Block>,
+    pub provenance: Provenance,
+}
+
+impl Target {
+    pub fn new(blocks: Vec<Block>, provenance: Provenance) -> Self {
+        Self { blocks, provenance }
+    }
+
+    /// Every address of every block, in order.
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> + Send + 'static {
+        self.blocks.clone().into_iter().flat_map(|block| block.hosts())
+    }
+
+    /// Whether `ip` is one of the addresses `hosts` yields.
+    pub fn lists(&self, ip: Ipv4Addr) -> bool {
+        self.blocks.iter().any(|block| {
+            block.network.contains(&ip) && (block.whole || block.network.prefix_len() >= 31 || (ip != block.network.network() && ip != block.network.broadcast()))
+        })
+    }
+
+    pub fn host_count(&self) -> u64 {
+        self.blocks.iter().map(|block| block.hosts().count() as u64).sum()
+    }
+}
+
+/// The targets of a scan in order. Ranges appended to the input files while
+/// it runs join at the end, so indices into it stay valid.
+#[derive(Debug, Default)]
+pub struct TargetQueue {
+    inner: RwLock<Queued>,
+    /// Distinct hosts planned, as `unique_count` counts them
+    total: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Queued {
+    targets: Vec<Target>,
+    /// Set once the cycle has run out of targets; later ones wait for the next
+    closed: bool,
+}
+
+impl TargetQueue {
+    pub fn new(targets: Vec<Target>, total: u64) -> Self {
+        Self { inner: RwLock::new(Queued { targets, closed: false }), total: AtomicU64::new(total) }
+    }
+
+    /// The target at `index`, or None when the cycle has scanned them all;
+    /// targets appended after that wait for the next cycle.
+    pub fn next(&self, index: usize) -> Option<Target> {
+        let mut inner = self.inner.write().unwrap();
+        let target = inner.targets.get(index).cloned();
+        inner.closed = target.is_none();
+        target
+    }
+
+    /// Starts a cycle over all the targets.
+    pub fn reopen(&self) {
+        self.inner.write().unwrap().closed = false;
+    }
+
+    /// How many targets there are so far.
+    pub fn count(&self) -> usize {
+        self.inner.read().unwrap().targets.len()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// All of them, cloned.
+    pub fn all(&self) -> Vec<Target> {
+        self.inner.read().unwrap().targets.clone()
+    }
+
+    /// Queues `targets`, which bring the distinct hosts up to `total`.
+    /// Returns whether the running cycle will still scan them.
+    pub fn append(&self, targets: Vec<Target>, total: u64) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        inner.targets.extend(targets);
+        self.total.store(total, Ordering::Relaxed);
+        !inner.closed
+    }
+}
+
+/// The hosts of `target` this run will actually probe, after sampling and sharding.
+pub fn planned_hosts(target: &Target, args: &ScanArgs, seed: u64) -> Box<dyn Iterator<Item = Ipv4Addr> + Send> {
+    let hosts: Box<dyn Iterator<Item = Ipv4Addr> + Send> = match args.sample_plan() {
+        Some(plan) => Box::new(sampled_hosts(target, plan, seed).into_iter()),
+        None => Box::new(target.hosts()),
+    };
+    match args.shard {
+        Some(shard) => Box::new(hosts.filter(move |ip| shard.contains(*ip))),
+        None => hosts,
+    }
+}
+
+/// `planned_hosts(..).count()` without walking the range where that can be avoided.
+pub fn planned_count(target: &Target, args: &ScanArgs, seed: u64) -> u64 {
+    match (args.sample_plan(), args.shard) {
+        (None, None) => target.host_count(),
+        (Some(plan), None) => plan.sample_size(target.host_count()),
+        (None, Some(shard)) => target
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut hosts = block.hosts();
+                match (hosts.next(), hosts.next_back()) {
+                    (Some(first), Some(last)) => shard.count_in(first, last),
+                    (Some(only), None) => shard.contains(only) as u64,
+                    _ => 0,
+                }
+            })
+            .sum(),
+        (Some(_), Some(_)) => planned_hosts(target, args, seed).count() as u64,
+    }
+}
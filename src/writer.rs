@@ -0,0 +1,819 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use console::style;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::compress::{self, Output};
+use crate::outdir;
+use crate::probes::OTHER_ENDPOINTS_CSV;
+use crate::rotate::{rotated_path, Rotation};
+use crate::{anonymize, console_log, control, gigabytes, Model};
+
+pub const ENDPOINTS_CSV: &str = "ollama_endpoints.csv";
+pub const MODELS_CSV: &str = "llm_models.csv";
+pub const UNVERIFIED_CSV: &str = "unverified_endpoints.csv";
+pub const MODEL_DETAILS_CSV: &str = "model_details.csv";
+pub const INTERESTING_CSV: &str = "interesting_responses.csv";
+pub const ABUSE_CONTACTS_CSV: &str = "abuse_contacts.csv";
+
+const ENDPOINT_HEADERS: &[&str] = &["IP:Port", "Tags URL", "Status Code", "Location", "Run ID", "Source", "Proxy/Server", "Fronting", "WebUI", "Write Access", "Embedding Dimension", "Capabilities", "Alert", "Discovered At", "Model Count", "Total Size (GB)", "Suspicion", "Provider", "Throttling", "Pass", "Tag"];
+const MODEL_HEADERS: &[&str] = &[
+    "IP:Port", "Model Name", "Model", "Modified At", "Size", "Digest",
+    "Parent Model", "Format", "Family", "Parameter Size", "Quantization Level", "Run ID", "License", "Discovered At",
+    "Location", "Tag",
+];
+const OTHER_ENDPOINT_HEADERS: &[&str] = &[
+    "IP:Port", "Service", "URL", "Status Code", "Models", "Location", "Run ID", "Source", "Details", "Provider",
+];
+const UNVERIFIED_HEADERS: &[&str] = &["IP:Port", "Tags URL", "Status Code", "Reason", "Body", "Location", "Run ID", "Source"];
+const INTERESTING_HEADERS: &[&str] = &[
+    "IP:Port", "Status Code", "Redirect Location", "Server", "Response Size", "Location", "Run ID", "Source",
+];
+const MODEL_DETAILS_HEADERS: &[&str] = &[
+    "IP:Port", "Model Name", "License", "Template Hash", "Parameters", "Context Length", "System Prompt", "Note", "Run ID",
+];
+
+const ABUSE_CONTACT_HEADERS: &[&str] = &["IP:Port", "Abuse Email", "Network", "Registrant", "Network Range", "Note", "Run ID"];
+
+// Rows are buffered and flushed on a timer instead of after every record.
+// A hard kill can lose at most FLUSH_INTERVAL worth of findings; `q` and Ctrl+C
+// drain the channel and flush everything before exit.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+const FLUSH_ROW_THRESHOLD: usize = 500;
+const CHANNEL_CAPACITY: usize = 4096;
+/// Write failures in a row, each after reopening the files, that stop the scan
+const MAX_WRITE_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct EndpointRecord {
+    pub base_url: String,
+    pub tags_url: String,
+    pub status: u16,
+    pub location: String,
+    /// The input entries the target came from, e.g. "ovh.txt:3"
+    pub source: String,
+    /// Server, Via, X-Powered-By and CF-Ray as "name=value; ..." pairs
+    pub proxy: String,
+    /// The CDN/WAF in front of the endpoint, empty for a direct exposure
+    pub fronting: String,
+    /// Open WebUI frontends found on the same host with `--also openwebui`
+    pub webui: String,
+    /// open/blocked/unknown with `--check-write-access`, otherwise empty
+    pub write_access: &'static str,
+    /// With `--check-embeddings`: the vector length, "failed", or empty when
+    /// the endpoint has no embedding model
+    pub embeddings: String,
+    /// With `--deep`: the API surfaces it exposes, e.g. "tags,version,ps:protected"
+    pub capabilities: String,
+    /// "rule: model; ..." for each alert rule the models matched
+    pub alerts: String,
+    /// RFC3339 UTC, when the endpoint's /api/tags answer confirmed it
+    pub discovered_at: String,
+    /// How many models /api/tags listed and their summed size in bytes;
+    /// None when the answer was not a model list
+    pub model_total: Option<(usize, u64)>,
+    /// "score: reason; ..." when the endpoint looks like a honeypot
+    pub suspicion: String,
+    /// The cloud or hosting provider whose ranges hold the address, or "unknown"
+    pub provider: &'static str,
+    /// "429, retried after 5s" when the answer came from a retry after a 429
+    pub throttling: String,
+    /// 1 when found in the sweep, 2 when recovered on the second pass
+    pub pass: u8,
+}
+
+/// A non-Ollama server found by an `--also` probe.
+#[derive(Debug, Clone)]
+pub struct ServiceRecord {
+    pub base_url: String,
+    pub service: &'static str,
+    /// The model listing, or the page the server was recognized by
+    pub url: String,
+    pub status: u16,
+    pub models: Vec<String>,
+    pub location: String,
+    pub source: String,
+    /// Build info, or a note such as "protected (API key required)"
+    pub details: String,
+    pub provider: &'static str,
+}
+
+/// An /api/tags answer that failed verification.
+#[derive(Debug, Clone)]
+pub struct UnverifiedRecord {
+    pub base_url: String,
+    pub tags_url: String,
+    pub status: u16,
+    /// The first check it failed
+    pub reason: String,
+    /// The start of the response body
+    pub body: String,
+    pub location: String,
+    pub source: String,
+}
+
+/// A non-200 `/api/tags` answer on the Ollama port, from `--record-non-200`.
+/// Never an endpoint: it is only a lead worth revisiting.
+#[derive(Debug, Clone)]
+pub struct InterestingRecord {
+    pub base_url: String,
+    pub status: u16,
+    /// The Location header of a redirect
+    pub redirect: String,
+    pub server: String,
+    /// Bytes of body read
+    pub size: usize,
+    pub location: String,
+    pub source: String,
+}
+
+/// What `/api/show` said about one model, from `--enrich-show`.
+#[derive(Debug, Clone, Default)]
+pub struct ShowRecord {
+    pub base_url: String,
+    pub model: String,
+    pub license: String,
+    pub template_hash: String,
+    pub parameters: String,
+    pub context_length: String,
+    pub system: String,
+    /// Why the other fields are empty, if they are
+    pub note: String,
+}
+
+/// Who answers for an endpoint's address, from `--abuse-contacts`.
+#[derive(Debug, Clone, Default)]
+pub struct AbuseRecord {
+    pub base_url: String,
+    pub abuse_email: String,
+    /// The RDAP network's name
+    pub network: String,
+    pub registrant: String,
+    /// "first - last" address of the network
+    pub range: String,
+    /// Why the other fields are empty, if they are
+    pub note: String,
+}
+
+#[derive(Debug)]
+pub enum WriteEvent {
+    Endpoint(EndpointRecord),
+    Service(ServiceRecord),
+    Unverified(UnverifiedRecord),
+    Interesting(InterestingRecord),
+    Show(ShowRecord),
+    Abuse(AbuseRecord),
+    Models {
+        base_url: String,
+        models: Vec<Model>,
+        /// Short license name by model name, from `--enrich-show`
+        licenses: BTreeMap<String, String>,
+        /// The endpoint's `discovered_at`
+        discovered_at: String,
+        /// The endpoint's Location
+        location: String,
+    },
+    /// Flushes every row sent before it and reports back how that went
+    Flush(oneshot::Sender<Result<()>>),
+}
+
+impl WriteEvent {
+    /// The CSV rows it adds.
+    fn rows(&self) -> u64 {
+        match self {
+            WriteEvent::Models { models, .. } => models.len() as u64,
+            WriteEvent::Flush(_) => 0,
+            _ => 1,
+        }
+    }
+}
+
+pub type WriteSender = mpsc::Sender<WriteEvent>;
+
+/// What the writer task did, once it is done.
+#[derive(Debug, Default)]
+pub struct WriterReport {
+    /// Rows that could not be written
+    pub unwritten: u64,
+    /// Every CSV written to, rotated ones included
+    #[cfg_attr(not(any(feature = "s3", feature = "email")), allow(dead_code))]
+    pub files: Vec<PathBuf>,
+}
+
+/// Which of the optional output files a run writes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionalOutputs {
+    /// other_llm_endpoints.csv
+    pub other_services: bool,
+    /// unverified_endpoints.csv
+    pub unverified: bool,
+    /// model_details.csv
+    pub model_details: bool,
+    /// interesting_responses.csv, with at most this many rows
+    pub interesting: Option<u64>,
+    /// abuse_contacts.csv
+    pub abuse_contacts: bool,
+}
+
+/// Owns the CSV writers. Only the writer task touches the files; scan tasks
+/// just send `WriteEvent`s, so a file is never rotated under a write.
+struct CsvOutputs {
+    endpoints: RotatingCsv,
+    models: RotatingCsv,
+    /// Only opened when `--also` asked for other services
+    others: Option<RotatingCsv>,
+    /// Not opened with `--no-verify`
+    unverified: Option<RotatingCsv>,
+    /// Only opened with `--enrich-show`
+    model_details: Option<RotatingCsv>,
+    /// Only opened with `--record-non-200`
+    interesting: Option<RotatingCsv>,
+    /// Only opened with `--abuse-contacts`
+    abuse_contacts: Option<RotatingCsv>,
+    /// Rows interesting_responses.csv still takes this run
+    interesting_left: u64,
+    pending_rows: usize,
+    run_id: String,
+    /// `--label`, empty without one
+    tag: String,
+    /// Write failures in a row that reopening the files did not fix
+    failures: u32,
+    /// Rows given up on
+    dropped: u64,
+}
+
+/// Makes an existing output file safe to append to and returns how many of
+/// `headers` its rows get. A file written before trailing columns were added
+/// keeps its layout, with a warning, unless `migrate` is set; then it is
+/// rewritten in place, giving old rows an empty value for the new columns. A
+/// file with any other header is moved aside to `<name>.<timestamp>.bak`
+/// instead of mixing rows of different shapes.
+fn prepare_existing(path: &Path, headers: &[&str], migrate: bool) -> Result<usize> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() > 0 => {}
+        _ => return Ok(headers.len()),
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(compress::open(path).with_context(|| format!("Failed to read {}", path.display()))?);
+    let existing: Vec<String> = reader
+        .headers()
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    if existing == headers {
+        return Ok(headers.len());
+    }
+
+    let is_older_layout = existing.len() < headers.len() && existing.iter().zip(headers).all(|(a, b)| a == b);
+    if is_older_layout && !migrate {
+        console_log(style(format!(
+            "{} was written by an older version; continuing without the {} column(s). Run with --migrate-output to add them",
+            path.display(),
+            headers[existing.len()..].join(", ")
+        )).yellow().to_string());
+        return Ok(existing.len());
+    }
+    if is_older_layout {
+        let migrated = PathBuf::from(format!("{}.migrating", path.display()));
+        let output = Output::new(File::create(&migrated)?, compress::is_gzip(path));
+        let mut writer = csv::Writer::from_writer(BufWriter::with_capacity(output.capacity(), output));
+        writer.write_record(headers)?;
+        for record in reader.records() {
+            let mut record = record?;
+            while record.len() < headers.len() {
+                record.push_field("");
+            }
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&migrated, path)?;
+        console_log(style(format!("Added column(s) {} to existing {}", headers[existing.len()..].join(", "), path.display())).yellow().to_string());
+    } else {
+        let backup = format!("{}.{}.bak", path.display(), chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+        fs::rename(path, &backup)?;
+        console_log(style(format!("{} has an unexpected header; moved it to {} and started a new file", path.display(), backup)).yellow().to_string());
+    }
+    Ok(headers.len())
+}
+
+/// An output file open for appending.
+struct OpenCsv {
+    file: BufWriter<Output>,
+    size: u64,
+    /// How many leading columns its rows have, fewer than the headers in a
+    /// file kept in an older layout
+    columns: usize,
+}
+
+/// Opens `path` for appending, writing `header` first if the file is new.
+fn open_csv(path: &Path, headers: &[&str], header: &[u8], migrate: bool) -> Result<OpenCsv> {
+    let columns = prepare_existing(path, headers, migrate)?;
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut size = file.metadata()?.len();
+    let output = Output::new(file, compress::is_gzip(path));
+    let mut file = BufWriter::with_capacity(output.capacity(), output);
+    if size == 0 {
+        file.write_all(header)?;
+        size = header.len() as u64;
+    }
+    Ok(OpenCsv { file, size, columns })
+}
+
+/// The file `base` is appended to right now under `rotation`.
+pub fn active_path(base: &str, rotation: Rotation) -> PathBuf {
+    let period = rotation.period();
+    let base = rotation.base(&outdir::path(base));
+    rotated_path(&base, period, rotation.append_index(&base, period))
+}
+
+/// One output file under the `--rotate`/`--rotate-size` policy. Rows are
+/// encoded before they are written, so the size of the file is known exactly
+/// and a row never spans two files. With rotation off this is a plain append
+/// to `base`.
+struct RotatingCsv {
+    /// The name before rotation, with this run's suffix
+    base: PathBuf,
+    headers: &'static [&'static str],
+    rotation: Rotation,
+    migrate: bool,
+    period: Option<NaiveDate>,
+    index: u32,
+    output: OpenCsv,
+    header: Vec<u8>,
+    /// Rows written since the last flush
+    unflushed: u64,
+    /// Had rows given up on, and hasn't taken any since
+    failing: bool,
+    /// Every file this run has written to, in order
+    written: Vec<PathBuf>,
+}
+
+impl RotatingCsv {
+    fn open(base: &'static str, headers: &'static [&'static str], rotation: Rotation, migrate: bool) -> Result<Self> {
+        let header = encode(headers)?;
+        let period = rotation.period();
+        let base = rotation.base(&outdir::path(base));
+        let index = rotation.append_index(&base, period);
+        let path = rotated_path(&base, period, index);
+        let output = open_csv(&path, headers, &header, migrate)?;
+        Ok(Self { base, headers, rotation, migrate, period, index, output, header, unflushed: 0, failing: false, written: vec![path] })
+    }
+
+    fn write_record<I, T>(&mut self, record: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        self.write_records([record])
+    }
+
+    /// Writes `records` in one piece: a write that fails leaves none of them
+    /// buffered, so trying all of them again after `reopen` writes each once.
+    fn write_records<R, I, T>(&mut self, records: R) -> Result<()>
+    where
+        R: IntoIterator<Item = I>,
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        self.roll_if_due()?;
+        let mut rows = Vec::new();
+        let mut count = 0;
+        for record in records {
+            rows.extend(encode(record.into_iter().take(self.output.columns).map(|field| match std::str::from_utf8(field.as_ref()) {
+                Ok(text) => anonymize::for_records(text).into_owned().into_bytes(),
+                Err(_) => field.as_ref().to_vec(),
+            }))?);
+            count += 1;
+        }
+        self.output.file.write_all(&rows)?;
+        self.output.size += rows.len() as u64;
+        self.unflushed += count;
+        Ok(())
+    }
+
+    /// Moves to the next file when the day has changed, or when this one is
+    /// full. A file always gets at least one row, however small the limit.
+    fn roll_if_due(&mut self) -> Result<()> {
+        let period = self.rotation.period();
+        let base = &self.base;
+        if period != self.period {
+            self.period = period;
+            self.index = self.rotation.append_index(base, period);
+        } else if self.output.size > self.header.len() as u64 && self.rotation.is_full(self.output.size) {
+            self.index += 1;
+        } else {
+            return Ok(());
+        }
+        self.output.file.flush()?;
+        let path = rotated_path(base, self.period, self.index);
+        self.output = open_csv(&path, self.headers, &self.header, self.migrate)?;
+        console_log(style(format!("Now writing {}", path.display())).dim().to_string());
+        if !self.written.contains(&path) {
+            self.written.push(path);
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> PathBuf {
+        rotated_path(&self.base, self.period, self.index)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let had_rows = !self.output.file.buffer().is_empty();
+        self.output.file.flush().with_context(|| format!("Failed to write {}", self.path().display()))?;
+        self.failing &= !had_rows;
+        self.unflushed = 0;
+        Ok(())
+    }
+
+    /// Opens the file again, e.g. after it was deleted or its disk filled up,
+    /// and writes out what the old handle could not.
+    fn reopen(&mut self) -> Result<()> {
+        let output = open_csv(&self.path(), self.headers, &self.header, self.migrate)?;
+        let (_, unwritten) = std::mem::replace(&mut self.output, output).file.into_parts();
+        let mut unwritten = unwritten.unwrap_or_default();
+        // A new file has just been given its header
+        if !self.output.file.buffer().is_empty() && unwritten.starts_with(&self.header) {
+            unwritten.drain(..self.header.len());
+        }
+        self.output.file.write_all(&unwritten)?;
+        self.output.size += unwritten.len() as u64;
+        self.flush()
+    }
+
+    /// Forgets what the file could not take, so it isn't tried again, and
+    /// returns how many rows that was.
+    fn discard_unwritten(&mut self) -> u64 {
+        self.failing |= !self.output.file.buffer().is_empty();
+        if let Ok(file) = self.output.file.get_ref().try_clone() {
+            let _ = std::mem::replace(&mut self.output.file, BufWriter::with_capacity(file.capacity(), file)).into_parts();
+        }
+        std::mem::take(&mut self.unflushed)
+    }
+}
+
+/// One CSV row as bytes, quoted the way `csv` quotes it.
+fn encode<I, T>(record: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut encoder = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    encoder.write_record(record)?;
+    encoder.into_inner().map_err(|e| e.into_error().into())
+}
+
+impl CsvOutputs {
+    fn open(run_id: String, tag: String, optional: OptionalOutputs, rotation: Rotation, migrate: bool) -> Result<Self> {
+        let open = |base, headers| RotatingCsv::open(base, headers, rotation, migrate);
+        let open_if = |wanted: bool, base, headers| wanted.then(|| open(base, headers)).transpose();
+        Ok(Self {
+            endpoints: open(ENDPOINTS_CSV, ENDPOINT_HEADERS)?,
+            models: open(MODELS_CSV, MODEL_HEADERS)?,
+            others: open_if(optional.other_services, OTHER_ENDPOINTS_CSV, OTHER_ENDPOINT_HEADERS)?,
+            unverified: open_if(optional.unverified, UNVERIFIED_CSV, UNVERIFIED_HEADERS)?,
+            model_details: open_if(optional.model_details, MODEL_DETAILS_CSV, MODEL_DETAILS_HEADERS)?,
+            interesting: open_if(optional.interesting.is_some(), INTERESTING_CSV, INTERESTING_HEADERS)?,
+            abuse_contacts: open_if(optional.abuse_contacts, ABUSE_CONTACTS_CSV, ABUSE_CONTACT_HEADERS)?,
+            interesting_left: optional.interesting.unwrap_or_default(),
+            pending_rows: 0,
+            run_id,
+            tag,
+            failures: 0,
+            dropped: 0,
+        })
+    }
+
+    fn files(&mut self) -> impl Iterator<Item = &mut RotatingCsv> {
+        [Some(&mut self.models), Some(&mut self.endpoints)]
+            .into_iter()
+            .chain([self.others.as_mut(), self.unverified.as_mut(), self.model_details.as_mut(), self.interesting.as_mut(), self.abuse_contacts.as_mut()])
+            .flatten()
+    }
+
+    /// Writes `event`, reopening the files and trying once more if that
+    /// fails. While writes are failing, each is flushed at once to learn
+    /// whether it landed.
+    fn write_or_recover(&mut self, event: &WriteEvent) {
+        if let Err(e) = self.write(event) {
+            console_log(style(format!("{:#}; reopening the output files to try again", e)).red().to_string());
+            if let Err(e) = self.reopen().and_then(|()| self.write(event)) {
+                return self.failed(event.rows(), &e);
+            }
+        }
+        if self.failures > 0 {
+            let _ = self.flush_or_recover();
+        }
+    }
+
+    /// Flushes the files, reopening them and trying once more if that fails.
+    fn flush_or_recover(&mut self) -> Result<()> {
+        let result = self.flush().or_else(|e| {
+            console_log(style(format!("{:#}; reopening the output files to try again", e)).red().to_string());
+            self.reopen()
+        });
+        match &result {
+            // Only rows reaching a file that failed show it works again
+            Ok(()) if self.failures > 0 && !self.files().any(|file| file.failing) => {
+                self.failures = 0;
+                console_log(style("Results are being written again").green().to_string());
+            }
+            Ok(()) => {}
+            Err(e) => self.failed(0, e),
+        }
+        self.pending_rows = 0;
+        result
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.files().try_for_each(RotatingCsv::reopen)
+    }
+
+    /// Gives up on `rows` and whatever else is waiting to be written. After
+    /// `MAX_WRITE_FAILURES` failures in a row the scan is stopped, as its
+    /// findings would be lost.
+    fn failed(&mut self, rows: u64, error: &anyhow::Error) {
+        let rows = rows + self.files().map(RotatingCsv::discard_unwritten).sum::<u64>();
+        self.pending_rows = 0;
+        self.dropped += rows;
+        self.failures += 1;
+        console_log(style(format!("{:#}; {} rows were not written", error, rows)).red().bold().to_string());
+        if self.failures >= MAX_WRITE_FAILURES && control::stop("writer") {
+            console_log(style("Stopping the scan: results can't be written. Free up space or fix the permissions, then run again").red().bold().to_string());
+        }
+    }
+
+    fn write(&mut self, event: &WriteEvent) -> Result<()> {
+        match event {
+            WriteEvent::Endpoint(record) => {
+                self.endpoints.write_record([
+                    &record.base_url,
+                    &record.tags_url,
+                    &record.status.to_string(),
+                    &record.location,
+                    &self.run_id,
+                    &record.source,
+                    &record.proxy,
+                    &record.fronting,
+                    &record.webui,
+                    record.write_access,
+                    &record.embeddings,
+                    &record.capabilities,
+                    &record.alerts,
+                    &record.discovered_at,
+                    &record.model_total.map(|(count, _)| count.to_string()).unwrap_or_default(),
+                    &record.model_total.map(|(_, bytes)| format!("{:.2}", gigabytes(bytes))).unwrap_or_default(),
+                    &record.suspicion,
+                    record.provider,
+                    &record.throttling,
+                    &record.pass.to_string(),
+                    &self.tag,
+                ])?;
+                self.pending_rows += 1;
+            }
+            WriteEvent::Service(record) => {
+                if let Some(others) = &mut self.others {
+                    others.write_record([
+                        &record.base_url,
+                        record.service,
+                        &record.url,
+                        &record.status.to_string(),
+                        &record.models.join("; "),
+                        &record.location,
+                        &self.run_id,
+                        &record.source,
+                        &record.details,
+                        record.provider,
+                    ])?;
+                    self.pending_rows += 1;
+                }
+            }
+            WriteEvent::Unverified(record) => {
+                if let Some(unverified) = &mut self.unverified {
+                    unverified.write_record([
+                        &record.base_url,
+                        &record.tags_url,
+                        &record.status.to_string(),
+                        &record.reason,
+                        &record.body,
+                        &record.location,
+                        &self.run_id,
+                        &record.source,
+                    ])?;
+                    self.pending_rows += 1;
+                }
+            }
+            WriteEvent::Interesting(record) => {
+                if let Some(interesting) = &mut self.interesting {
+                    if self.interesting_left == 0 {
+                        return Ok(());
+                    }
+                    interesting.write_record([
+                        &record.base_url,
+                        &record.status.to_string(),
+                        &record.redirect,
+                        &record.server,
+                        &record.size.to_string(),
+                        &record.location,
+                        &self.run_id,
+                        &record.source,
+                    ])?;
+                    self.interesting_left -= 1;
+                    if self.interesting_left == 0 {
+                        console_log(style(format!("{} reached --max-non-200; further non-200 answers are not recorded this run", INTERESTING_CSV)).yellow().to_string());
+                    }
+                    self.pending_rows += 1;
+                }
+            }
+            WriteEvent::Show(record) => {
+                if let Some(model_details) = &mut self.model_details {
+                    model_details.write_record([
+                        &record.base_url,
+                        &record.model,
+                        &record.license,
+                        &record.template_hash,
+                        &record.parameters,
+                        &record.context_length,
+                        &record.system,
+                        &record.note,
+                        &self.run_id,
+                    ])?;
+                    self.pending_rows += 1;
+                }
+            }
+            WriteEvent::Abuse(record) => {
+                if let Some(abuse_contacts) = &mut self.abuse_contacts {
+                    abuse_contacts.write_record([
+                        &record.base_url,
+                        &record.abuse_email,
+                        &record.network,
+                        &record.registrant,
+                        &record.range,
+                        &record.note,
+                        &self.run_id,
+                    ])?;
+                    self.pending_rows += 1;
+                }
+            }
+            WriteEvent::Models { base_url, models, licenses, discovered_at, location } => {
+                // One write for all of them, so a retry cannot repeat the rows that made it
+                let rows = models.iter().map(|model| {
+                    let license = licenses.get(&model.name).map(String::as_str).unwrap_or_default();
+                    let size_gb = gigabytes(model.size);
+                    [
+                        base_url.clone(),
+                        model.name.clone(),
+                        model.model.clone(),
+                        model.modified_at.clone(),
+                        format!("{:.2}", size_gb), // Format size to 2 decimal places
+                        model.digest.clone(),
+                        model.details.parent_model.clone(),
+                        model.details.format.clone(),
+                        model.details.family.clone(),
+                        model.details.parameter_size.clone(),
+                        model.details.quantization_level.clone(),
+                        self.run_id.clone(),
+                        license.to_string(),
+                        discovered_at.clone(),
+                        location.clone(),
+                        self.tag.clone(),
+                    ]
+                });
+                self.models.write_records(rows)?;
+                self.pending_rows += models.len();
+            }
+            WriteEvent::Flush(_) => return Ok(()),
+        }
+        if self.pending_rows >= FLUSH_ROW_THRESHOLD {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.files().try_for_each(RotatingCsv::flush)?;
+        self.pending_rows = 0;
+        Ok(())
+    }
+}
+
+/// Opens the output files and spawns the task that persists every finding,
+/// tagging each row with `run_id` and `tag`, the run's `--label`. Of the
+/// `optional` files only the ones asked for are opened, and every file
+/// follows `rotation`. Files in an older layout are only rewritten with
+/// `migrate`. The task exits once all senders are dropped and the channel is
+/// drained, reporting the files written and the rows that could not be.
+pub fn spawn_writer(run_id: String, tag: String, optional: OptionalOutputs, rotation: Rotation, migrate: bool) -> Result<(WriteSender, JoinHandle<WriterReport>)> {
+    let mut outputs = CsvOutputs::open(run_id, tag, optional, rotation, migrate)?;
+    let (tx, mut rx) = mpsc::channel::<WriteEvent>(CHANNEL_CAPACITY);
+
+    let handle = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(WriteEvent::Flush(done)) => {
+                        let _ = done.send(outputs.flush_or_recover());
+                    }
+                    Some(event) => outputs.write_or_recover(&event),
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    if outputs.pending_rows > 0 {
+                        let _ = outputs.flush_or_recover();
+                    }
+                }
+            }
+        }
+        let _ = outputs.flush_or_recover();
+        WriterReport {
+            unwritten: outputs.dropped,
+            files: outputs.files().flat_map(|file| std::mem::take(&mut file.written)).collect(),
+        }
+    });
+
+    Ok((tx, handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An output whose file is `/dev/full`, where every write that reaches
+    /// the disk fails, and which reopens as `path`. Its buffer takes two
+    /// model rows, not three.
+    fn full_disk(path: &Path, headers: &'static [&'static str]) -> RotatingCsv {
+        let header = encode(headers).unwrap();
+        let file = OpenOptions::new().write(true).open("/dev/full").unwrap();
+        let output = OpenCsv { file: BufWriter::with_capacity(300, Output::new(file, false)), size: header.len() as u64, columns: headers.len() };
+        RotatingCsv {
+            base: path.to_path_buf(),
+            headers,
+            rotation: Rotation::default(),
+            migrate: false,
+            period: None,
+            index: 0,
+            output,
+            header,
+            unflushed: 0,
+            failing: false,
+            written: vec![path.to_path_buf()],
+        }
+    }
+
+    #[test]
+    fn a_models_event_that_fails_midway_is_written_once_after_reopening() {
+        let dir = std::env::temp_dir().join(format!("pof-writer-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut outputs = CsvOutputs {
+            endpoints: full_disk(&dir.join(ENDPOINTS_CSV), ENDPOINT_HEADERS),
+            models: full_disk(&dir.join(MODELS_CSV), MODEL_HEADERS),
+            others: None,
+            unverified: None,
+            model_details: None,
+            interesting: None,
+            abuse_contacts: None,
+            interesting_left: 0,
+            pending_rows: 0,
+            run_id: "run".to_string(),
+            tag: String::new(),
+            failures: 0,
+            dropped: 0,
+        };
+        let models = ["llama3:8b", "qwen2:7b", "gemma2:9b"]
+            .iter()
+            .map(|name| serde_json::from_value(serde_json::json!({ "name": name, "digest": "d".repeat(64) })).unwrap())
+            .collect();
+        let event = WriteEvent::Models {
+            base_url: "http://192.0.2.1:11434".to_string(),
+            models,
+            licenses: BTreeMap::new(),
+            discovered_at: String::new(),
+            location: String::new(),
+        };
+
+        outputs.write_or_recover(&event);
+        outputs.flush().unwrap();
+        assert_eq!(outputs.dropped, 0);
+        let written = fs::read_to_string(dir.join(MODELS_CSV)).unwrap();
+        for name in ["llama3:8b", "qwen2:7b", "gemma2:9b"] {
+            assert_eq!(written.matches(name).count(), 1, "{}", written);
+        }
+        assert_eq!(written.lines().count(), 4, "{}", written);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,59 @@
+//! `--abuse-contacts` against a stand-in RDAP server.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use common::{field, run, serve_ollama, workdir};
+
+const NETWORK: &str = r#"{"objectClassName":"ip network","name":"LOOPBACK-NET","startAddress":"127.0.5.0","endAddress":"127.0.5.255","entities":[{"roles":["registrant"],"vcardArray":["vcard",[["fn",{},"text","Loopback Hosting"]]],"entities":[{"roles":["abuse"],"vcardArray":["vcard",[["email",{},"text","abuse@loopback.example"]]]}]}]}"#;
+
+/// Answers the first lookup with a 429 and the rest with `NETWORK`; keeps
+/// the path of every request.
+fn serve_rdap() -> (u16, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let paths = Arc::new(Mutex::new(Vec::new()));
+    let seen = paths.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]).into_owned();
+            let mut seen = seen.lock().unwrap();
+            let (status, extra, body) = match seen.is_empty() {
+                true => ("429 Too Many Requests", "Retry-After: 1\r\n", ""),
+                false => ("200 OK", "Content-Type: application/rdap+json\r\n", NETWORK),
+            };
+            seen.push(request.split(' ').nth(1).unwrap_or_default().to_string());
+            let _ = write!(stream, "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", status, extra, body.len(), body);
+        }
+    });
+    (port, paths)
+}
+
+#[test]
+fn looks_up_each_network_once_and_waits_out_a_429() {
+    serve_ollama("127.0.5.30");
+    serve_ollama("127.0.5.31");
+    let (port, paths) = serve_rdap();
+    let dir = workdir("abuse-contacts", Some("127.0.5.30-31\n"));
+    let server = format!("http://127.0.0.1:{}", port);
+    let outcome = run(&dir, &["--abuse-contacts", "--rdap-server", &server], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    // The 429, then one lookup for both hosts
+    let paths = paths.lock().unwrap();
+    assert_eq!(paths.len(), 2, "{:?}", paths);
+    assert_eq!(paths[0], paths[1]);
+    let csv = dir.join("abuse_contacts.csv");
+    for ip in ["127.0.5.30", "127.0.5.31"] {
+        assert_eq!(field(&csv, ip, "Abuse Email"), "abuse@loopback.example");
+        assert_eq!(field(&csv, ip, "Registrant"), "Loopback Hosting");
+        assert_eq!(field(&csv, ip, "Network Range"), "127.0.5.0 - 127.0.5.255");
+        assert_eq!(field(&csv, ip, "Note"), "");
+    }
+}
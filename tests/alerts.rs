@@ -0,0 +1,57 @@
+//! Alert rules from config.json, during a scan and with `--test-alerts`.
+
+mod common;
+
+use common::{field, run, serve_ollama, workdir};
+
+const RULES: &str = r#"{"alerts": {"match": "all", "rules": [
+    {"name": "big-llama", "model": "llama3*", "min_size": "7B"},
+    {"name": "huge-llama", "model": "llama3*", "min_size": "70B"},
+    {"name": "any-8b", "regex": ":8b$"}
+]}}"#;
+
+#[test]
+fn flags_matching_endpoints() {
+    serve_ollama("127.0.0.32");
+    let dir = workdir("alerts", Some("127.0.0.32\n"));
+    std::fs::write(dir.join("config.json"), RULES).unwrap();
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("ALERT: http://127.0.0.32:11434"), "{}", outcome.stderr);
+
+    let alert = field(&dir.join("ollama_endpoints.csv"), "127.0.0.32", "Alert");
+    assert_eq!(alert, "big-llama: llama3:8b; any-8b: llama3:8b");
+}
+
+#[test]
+fn tests_rules_against_an_earlier_scan() {
+    let dir = workdir("test-alerts", None);
+    std::fs::write(dir.join("config.json"), RULES).unwrap();
+    std::fs::write(
+        dir.join("old_models.csv"),
+        "IP:Port,Model Name,Parameter Size\nhttp://10.0.0.1:11434,llama3:70b,70.6B\nhttp://10.0.0.2:11434,mistral:7b,7.2B\n",
+    )
+    .unwrap();
+    let outcome = run(&dir, &["--test-alerts", "old_models.csv"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("http://10.0.0.1:11434 llama3:70b (huge-llama)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("1 of 2 model rows matched"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("mistral"), "{}", outcome.stderr);
+
+    std::fs::write(dir.join("config.json"), r#"{"alerts": {"rules": [{"name": "none", "model": "gemma*"}]}}"#).unwrap();
+    let outcome = run(&dir, &["--test-alerts", "old_models.csv"], "");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+}
+
+#[test]
+fn refuses_to_start_with_a_bad_rule() {
+    let dir = workdir("bad-alerts", Some("127.0.0.33\n"));
+    std::fs::write(dir.join("rules.json"), r#"{"alerts": {"rules": [{"name": "broken", "regex": "(unclosed"}]}}"#).unwrap();
+    let outcome = run(&dir, &["--config", "rules.json"], "y\n");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains(r#"alerts rule 1 ("broken")"#), "{}", outcome.stderr);
+
+    let outcome = run(&dir, &["--config", "missing.json"], "y\n");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains("Failed to read missing.json"), "{}", outcome.stderr);
+}
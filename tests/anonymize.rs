@@ -0,0 +1,71 @@
+//! `--anonymize`: no full address in what gets shared.
+
+mod common;
+
+use common::{field, run, serve_ollama, serve_routes, workdir};
+use regex::Regex;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Runs a scan with `args`, returning the exit code, stdout and stderr.
+fn scan(dir: &Path, args: &[&str]) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_public-ollama-finder"))
+        .arg("--stream")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    (output.status.code().unwrap(), text(&output.stdout), text(&output.stderr))
+}
+
+fn assert_masked(text: &str) {
+    let address = Regex::new(r"127[.-]0[.-]0[.-]\d").unwrap();
+    assert!(!address.is_match(text), "unmasked address in:\n{}", text);
+}
+
+#[test]
+fn masks_the_console_and_stream_but_not_the_csvs() {
+    serve_ollama("127.0.0.98");
+    serve_routes("127.0.0.99", 11434, &[]);
+    let dir = workdir("anonymize", Some("127.0.0.98-127.0.0.99\n"));
+    let (code, stdout, stderr) = scan(&dir, &["--anonymize"]);
+    assert_eq!(code, 0, "{}", stderr);
+
+    assert_masked(&stdout);
+    assert_masked(&stderr);
+    assert!(stdout.contains(r#""base_url":"http://127.0.0.xxx:11434""#), "{}", stdout);
+    assert!(stderr.contains("Possible Ollama server (404): http://127.0.0.xxx:11434"), "{}", stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.0.98", "Status Code"), "200");
+
+    // Exports are reports too
+    let export = Command::new(env!("CARGO_BIN_EXE_public-ollama-finder"))
+        .args(["export", "--format", "urls", "--anonymize", "--anonymize-style", "hash"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let urls = String::from_utf8_lossy(&export.stdout);
+    assert_masked(&urls);
+    assert!(Regex::new(r"^http://ip-[0-9a-f]{8}:11434\n$").unwrap().is_match(&urls), "{}", urls);
+}
+
+#[test]
+fn masks_the_csvs_with_all() {
+    serve_ollama("127.0.0.100");
+    let dir = workdir("anonymize-all", Some("127.0.0.100\n"));
+    let outcome = run(&dir, &["--anonymize=all"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert_masked(&outcome.stderr);
+    for csv in ["ollama_endpoints.csv", "llm_models.csv"] {
+        let text = fs::read_to_string(dir.join(csv)).unwrap();
+        assert_masked(&text);
+        assert!(text.contains("127.0.0.xxx:11434"), "{}", text);
+    }
+}
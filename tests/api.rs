@@ -0,0 +1,90 @@
+//! `--serve` takes targets over its API, reports status and findings, and
+//! stops when told to.
+
+mod common;
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use common::{field, finish, free_port, http, serve_ollama, spawn, workdir};
+
+const TOKEN: &str = "test-token";
+
+fn request(port: u16, method: &str, path: &str, token: Option<&str>, body: &str) -> std::io::Result<(u16, serde_json::Value)> {
+    let (status, body) = http(port, method, path, token, body)?;
+    Ok((status, serde_json::from_str(&body).unwrap_or(serde_json::Value::Null)))
+}
+
+fn call(port: u16, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+    request(port, method, path, Some(TOKEN), body).unwrap()
+}
+
+/// Calls `GET path` until `done` accepts the answer.
+fn wait_for(port: u16, path: &str, done: impl Fn(&serde_json::Value) -> bool) -> serde_json::Value {
+    let start = Instant::now();
+    loop {
+        if let Ok((200, body)) = request(port, "GET", path, Some(TOKEN), "") {
+            if done(&body) {
+                return body;
+            }
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "timed out waiting on {}", path);
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn walks_the_endpoints() {
+    serve_ollama("127.0.5.10");
+    let dir = workdir("api", None);
+    std::fs::write(dir.join("config.json"), format!(r#"{{"api": {{"token": "{}"}}}}"#, TOKEN)).unwrap();
+    let port = free_port();
+    let child = spawn(&dir, &["--serve", &format!("127.0.0.1:{}", port)], "y\n");
+
+    let status = wait_for(port, "/status", |_| true);
+    assert_eq!(status["state"], "running");
+    assert_eq!(status["scanned"], 0);
+    assert_eq!(request(port, "GET", "/status", None, "").unwrap().0, 401);
+    assert_eq!(request(port, "GET", "/status", Some("wrong"), "").unwrap().0, 401);
+    assert_eq!(call(port, "GET", "/nowhere", "").0, 404);
+
+    let (code, body) = call(port, "POST", "/targets", "no targets here\n");
+    assert_eq!(code, 400, "{}", body);
+    let (code, body) = call(port, "POST", "/targets", "127.0.5.10\n999.1.1.1/24\n");
+    assert_eq!(code, 202, "{}", body);
+    assert_eq!((body["ranges"].as_u64(), body["hosts"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(body["warnings"].as_array().map(Vec::len), Some(1), "{}", body);
+
+    let found = wait_for(port, "/findings?since=0", |body| body["findings"].as_array().is_some_and(|f| !f.is_empty()));
+    assert_eq!(found["findings"][0]["id"], 1);
+    assert_eq!(found["findings"][0]["base_url"], "http://127.0.5.10:11434");
+    assert_eq!(found["findings"][0]["source"], "api:1");
+    assert_eq!(found["next"], 1);
+    let (_, later) = call(port, "GET", "/findings?since=1", "");
+    assert_eq!(later["findings"].as_array().map(Vec::len), Some(0));
+    assert_eq!(later["next"], 1);
+    assert_eq!(call(port, "GET", "/findings?limit=many", "").0, 400);
+
+    let status = wait_for(port, "/status", |body| body["scanned"] == 1);
+    assert_eq!((status["hits"].as_u64(), status["queued_hosts"].as_u64()), (Some(1), Some(0)));
+
+    let (code, body) = call(port, "POST", "/control", r#"{"action": "pause"}"#);
+    assert_eq!((code, body["state"].as_str()), (200, Some("paused")));
+    assert_eq!(call(port, "GET", "/status", "").1["state"], "paused");
+    assert_eq!(call(port, "POST", "/control", r#"{"action": "resume"}"#).1["state"], "running");
+    assert_eq!(call(port, "POST", "/control", r#"{"action": "jump"}"#).0, 400);
+    assert_eq!(call(port, "POST", "/control", r#"{"action": "stop"}"#).1["state"], "stopping");
+
+    let outcome = finish(child);
+    assert_eq!(outcome.code, 3, "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "http://127.0.5.10:11434", "Source"), "api:1");
+}
+
+#[test]
+fn refuses_to_start_without_a_token() {
+    let dir = workdir("api-no-token", None);
+    let outcome = finish(spawn(&dir, &["--serve", "127.0.0.1:0"], "y\n"));
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("--serve needs a token"), "{}", outcome.stderr);
+    assert!(!Path::new(&dir.join("ollama_endpoints.csv")).exists());
+}
@@ -0,0 +1,33 @@
+//! `--max-rate-per-asn`: one budget for all of an autonomous system's
+//! prefixes.
+
+mod common;
+
+use common::{run, workdir};
+use std::time::{Duration, Instant};
+
+#[test]
+fn holds_scattered_prefixes_of_one_asn_to_its_rate() {
+    // Two /28s of the same ASN: 28 hosts without network and broadcast addresses
+    let dir = workdir("asn-rate", Some("127.0.6.0/28\n127.0.6.64/28\n"));
+    std::fs::write(dir.join("asn.txt"), "127.0.6.0/28 AS64500 Example Hosting\n127.0.6.64/28,64500\n").unwrap();
+    let started = Instant::now();
+    let outcome = run(&dir, &["--max-rate-per-asn", "8", "--asn-db", "asn.txt"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Per-ASN limit: 8 req/s per autonomous system (2 ranges)"), "{}", outcome.stderr);
+    // A second's burst, then 20 more at 8/s
+    assert!(started.elapsed() >= Duration::from_millis(2000), "{:?}", started.elapsed());
+}
+
+#[test]
+fn needs_a_database_with_prefixes() {
+    let dir = workdir("asn-rate-empty", Some("127.0.6.0/30\n"));
+    std::fs::write(dir.join("asn.txt"), "# nothing\n2001:db8::/32 64500\n").unwrap();
+    let outcome = run(&dir, &["--max-rate-per-asn", "16", "--asn-db", "asn.txt"], "y\n");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains("asn.txt lists no IPv4 prefix with an ASN"), "{}", outcome.stderr);
+
+    let outcome = run(&dir, &["--max-rate-per-asn", "16"], "y\n");
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("--asn-db"), "{}", outcome.stderr);
+}
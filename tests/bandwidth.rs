@@ -0,0 +1,20 @@
+//! `--max-bandwidth` and the bytes received in the summary.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+use std::time::{Duration, Instant};
+
+#[test]
+fn holds_back_probes_over_the_byte_budget() {
+    for host in 70..=73 {
+        serve_ollama(&format!("127.0.0.{}", host));
+    }
+    // One at a time, so each answer has arrived before the next probe is dispatched
+    let dir = workdir("bandwidth", Some("127.0.0.70-127.0.0.73\n"));
+    let started = Instant::now();
+    let outcome = run(&dir, &["--max-bandwidth", "1KB/s", "--concurrency-fixed", "1"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(started.elapsed() >= Duration::from_secs(1), "took {:?}: {}", started.elapsed(), outcome.stderr);
+    assert!(outcome.stderr.contains("Received: "), "{}", outcome.stderr);
+}
@@ -0,0 +1,34 @@
+//! `--max-body-size` against answers that never end.
+
+mod common;
+
+use common::{run, serve_endless_tags, workdir};
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[test]
+fn gives_up_on_an_endless_body() {
+    serve_endless_tags("127.0.0.66", None);
+    let dir = workdir("body-endless", Some("127.0.0.66\n"));
+    let started = Instant::now();
+    let outcome = run(&dir, &["--max-body-size", "256KB", "--read-timeout", "20s", "--errors-csv"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(started.elapsed() < Duration::from_secs(15), "took {:?}", started.elapsed());
+
+    assert!(outcome.stderr.contains("oversized response"), "{}", outcome.stderr);
+    let errors = fs::read_to_string(dir.join("errors.csv")).unwrap();
+    assert!(errors.contains("oversized response,127.0.0.66,body grew past the 262144-byte limit"), "{}", errors);
+}
+
+#[test]
+fn turns_down_a_long_content_length_without_reading() {
+    serve_endless_tags("127.0.0.67", Some(10 << 30));
+    let dir = workdir("body-declared", Some("127.0.0.67\n"));
+    let started = Instant::now();
+    let outcome = run(&dir, &["--read-timeout", "20s", "--errors-csv"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(started.elapsed() < Duration::from_secs(15), "took {:?}", started.elapsed());
+
+    let errors = fs::read_to_string(dir.join("errors.csv")).unwrap();
+    assert!(errors.contains("oversized response,127.0.0.67,Content-Length 10737418240 is over the 2097152-byte limit"), "{}", errors);
+}
@@ -0,0 +1,25 @@
+//! `--deep` against fake Ollamas exposing different API surfaces.
+
+mod common;
+
+use common::{field, run, serve_ollama, serve_routes, workdir, TAGS};
+
+#[test]
+fn lists_the_api_surfaces_each_endpoint_exposes() {
+    // Behind a proxy that guards /api/ps and does not route /v1
+    serve_routes("127.0.0.30", 11434, &[
+        ("GET /api/tags ", "200 OK", TAGS),
+        ("GET /api/version ", "200 OK", r#"{"version":"0.1.32"}"#),
+        ("GET /api/ps ", "403 Forbidden", ""),
+        ("POST /api/embeddings ", "400 Bad Request", r#"{"error":"model is required"}"#),
+    ]);
+    serve_ollama("127.0.0.31");
+    let dir = workdir("capabilities", Some("127.0.0.30-127.0.0.31\n"));
+    let outcome = run(&dir, &["--deep"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Capabilities: tags,version,ps:protected,embeddings"), "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    assert_eq!(field(&endpoints, "127.0.0.30", "Capabilities"), "tags,version,ps:protected,embeddings");
+    assert_eq!(field(&endpoints, "127.0.0.31", "Capabilities"), "tags,version");
+}
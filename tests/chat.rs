@@ -0,0 +1,61 @@
+//! The `chat` subcommand against fake Ollamas.
+
+mod common;
+
+use common::{serve_routes, TAGS};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+
+/// Runs `chat` with `args`, typing `input`; returns the exit code and stdout.
+fn chat(args: &[&str], input: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_public-ollama-finder"))
+        .arg("chat")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.code().unwrap(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+const REPLY: &str = "{\"message\":{\"role\":\"assistant\",\"content\":\"Hi \"},\"done\":false}\n\
+    {\"message\":{\"role\":\"assistant\",\"content\":\"there\"},\"done\":false}\n\
+    {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n";
+
+#[test]
+fn streams_the_reply_of_the_only_model() {
+    serve_routes("127.0.0.41", 11434, &[("GET /api/tags ", "200 OK", TAGS), ("POST /api/chat ", "200 OK", REPLY)]);
+    let (code, stdout) = chat(&["http://127.0.0.41:11434/"], "hello\n/quit\n");
+    assert_eq!(code, 0, "{}", stdout);
+    assert!(stdout.contains("1. llama3:8b (8.0B)"), "{}", stdout);
+    assert!(stdout.contains("llama3:8b> Hi there\n"), "{}", stdout);
+}
+
+#[test]
+fn survives_a_connection_lost_mid_reply() {
+    // Sends the first line of a chunked reply, then hangs up
+    let listener = TcpListener::bind(("127.0.0.42", 11434)).unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = [0u8; 4096];
+            let n = stream.read(&mut request).unwrap_or(0);
+            if request[..n].starts_with(b"GET /api/tags ") {
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", TAGS.len(), TAGS);
+            } else {
+                let line = REPLY.lines().next().unwrap();
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\n\r\n", line.len() + 1, line);
+            }
+        }
+    });
+    let (code, stdout) = chat(&["http://127.0.0.42:11434", "--model", "llama3:8b"], "hello\n/quit\n");
+    assert_eq!(code, 0, "{}", stdout);
+    assert!(stdout.contains("llama3:8b> Hi \n"), "{}", stdout);
+    assert!(stdout.contains("Connection lost: "), "{}", stdout);
+    // The lost exchange is dropped and the loop carries on to /quit
+    assert!(stdout.trim_end().ends_with("llama3:8b>"), "{}", stdout);
+}
@@ -0,0 +1,298 @@
+//! Helpers shared by the end-to-end tests. The binary runs with `--stream` so
+//! it never puts the terminal into raw mode, and the disclaimer is answered on
+//! stdin.
+
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const TAGS: &str = r#"{"models":[{"name":"llama3:8b","model":"llama3:8b","modified_at":"2024-05-01T10:00:00Z","size":4661224676,"digest":"365c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1","details":{"parent_model":"","format":"gguf","family":"llama","parameter_size":"8.0B","quantization_level":"Q4_0"}}]}"#;
+
+const SHOW: &str = r#"{"license":"META LLAMA 3 COMMUNITY LICENSE AGREEMENT\n...","parameters":"num_ctx 8192\nstop \"<|eot_id|>\"","template":"{{ .Prompt }}","details":{"family":"llama"},"model_info":{"general.architecture":"llama","llama.context_length":8192}}"#;
+
+pub struct Outcome {
+    pub code: i32,
+    /// Everything but findings goes to stderr in stream mode
+    pub stderr: String,
+}
+
+/// A fresh working directory, optionally with an ip-ranges.txt.
+pub fn workdir(name: &str, ranges: Option<&str>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pof-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    if let Some(ranges) = ranges {
+        std::fs::write(dir.join("ip-ranges.txt"), ranges).unwrap();
+    }
+    dir
+}
+
+/// Starts the scanner in `dir` and answers the disclaimer.
+pub fn spawn(dir: &Path, args: &[&str], answer: &str) -> Child {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_public-ollama-finder"))
+        .arg("--stream")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(answer.as_bytes()).unwrap();
+    child
+}
+
+pub fn finish(child: Child) -> Outcome {
+    let output = child.wait_with_output().unwrap();
+    Outcome {
+        code: output.status.code().expect("terminated by a signal"),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
+
+pub fn run(dir: &Path, args: &[&str], answer: &str) -> Outcome {
+    finish(spawn(dir, args, answer))
+}
+
+/// The `column` field of the first row of the CSV file at `path` whose first
+/// field contains `key`, e.g. an endpoint's IP.
+pub fn field(path: &Path, key: &str, column: &str) -> String {
+    let mut reader = csv::Reader::from_path(path).unwrap();
+    let index = reader.headers().unwrap().iter().position(|h| h == column).unwrap_or_else(|| panic!("no {} column", column));
+    let row = reader
+        .records()
+        .map(Result::unwrap)
+        .find(|row| row[0].contains(key))
+        .unwrap_or_else(|| panic!("no row for {} in {}", key, path.display()));
+    row[index].to_string()
+}
+
+/// Answers /api/tags on port 11434 with one model, /api/show and
+/// /api/version like Ollama 0.3.12 and everything else with 404.
+pub fn serve_ollama(ip: &str) {
+    serve_ollama_with(ip, Duration::ZERO, Arc::default());
+}
+
+/// Like `serve_ollama`, but answers each /api/tags request after `delay` and
+/// appends the listening address to `probes` for every one received.
+pub fn serve_ollama_with(ip: &str, delay: Duration, probes: Arc<Mutex<Vec<String>>>) {
+    let address = ip.to_string();
+    serve(ip, 11434, move |request| {
+        if request.starts_with(b"GET /api/tags ") {
+            probes.lock().unwrap().push(address.clone());
+            std::thread::sleep(delay);
+            ("200 OK", "", TAGS.to_string())
+        } else {
+            ollama_answer(request)
+        }
+    });
+}
+
+/// Like `serve_ollama`, but answers POST /api/copy with `status`, as a
+/// server behind a proxy that filters writes would.
+pub fn serve_ollama_copy(ip: &str, status: &'static str) {
+    serve(ip, 11434, move |request| {
+        if request.starts_with(b"POST /api/copy ") {
+            (status, "", r#"{"error":"source and destination are required"}"#.to_string())
+        } else {
+            ollama_answer(request)
+        }
+    });
+}
+
+fn ollama_answer(request: &[u8]) -> (&'static str, &'static str, String) {
+    if request.starts_with(b"GET /api/tags ") {
+        ("200 OK", "", TAGS.to_string())
+    } else if request.starts_with(b"POST /api/show ") {
+        ("200 OK", "", SHOW.to_string())
+    } else if request.starts_with(b"GET /api/version ") {
+        ("200 OK", "", r#"{"version":"0.3.12"}"#.to_string())
+    } else {
+        ("404 Not Found", "", r#"{"error":"not found"}"#.to_string())
+    }
+}
+
+/// Like `serve_ollama`, but holds back the first /api/tags answer for
+/// `delay`, long enough for the probe to time out.
+pub fn serve_ollama_slow_once(ip: &str, delay: Duration) {
+    let served = AtomicUsize::new(0);
+    serve(ip, 11434, move |request| {
+        if request.starts_with(b"GET /api/tags ") && served.fetch_add(1, Ordering::Relaxed) == 0 {
+            std::thread::sleep(delay);
+        }
+        ollama_answer(request)
+    });
+}
+
+/// Answers /api/tags on port 11434 with each of `answers` (status, header
+/// lines, body) in turn, repeating the last one, and everything else with 404.
+pub fn serve_tags_in_turn(ip: &str, answers: &'static [(&'static str, &'static str, &'static str)]) {
+    let served = AtomicUsize::new(0);
+    serve(ip, 11434, move |request| {
+        if !request.starts_with(b"GET /api/tags ") {
+            return ("404 Not Found", "", String::new());
+        }
+        let (status, headers, body) = answers[served.fetch_add(1, Ordering::Relaxed).min(answers.len() - 1)];
+        (status, headers, body.to_string())
+    });
+}
+
+/// Answers `GET path` on `ip:port` with `body` plus any `headers` (each line
+/// ending in \r\n), and everything else with 404.
+pub fn serve_json(ip: &str, port: u16, path: &'static str, headers: &'static str, body: &'static str) {
+    let request_line = format!("GET {} ", path);
+    serve(ip, port, move |request| {
+        if request.starts_with(request_line.as_bytes()) {
+            ("200 OK", headers, body.to_string())
+        } else {
+            ("404 Not Found", "", String::new())
+        }
+    });
+}
+
+/// Answers on `ip:port` each request whose request line starts with a
+/// route's prefix (e.g. "POST /api/embed ") with its status and body, and
+/// everything else with 404.
+pub fn serve_routes(ip: &str, port: u16, routes: &'static [(&'static str, &'static str, &'static str)]) {
+    serve(ip, port, move |request| {
+        match routes.iter().find(|(prefix, _, _)| request.starts_with(prefix.as_bytes())) {
+            Some((_, status, body)) => (status, "", body.to_string()),
+            None => ("404 Not Found", "", String::new()),
+        }
+    });
+}
+
+/// Answers /api/tags on port 11434 with a body that never ends: announced
+/// by a `Content-Length` of `declared` bytes and then withheld, or without
+/// one and streamed until the client hangs up.
+pub fn serve_endless_tags(ip: &str, declared: Option<u64>) {
+    let listener = TcpListener::bind((ip, 11434)).unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let length = declared.map(|length| format!("Content-Length: {}\r\n", length)).unwrap_or_default();
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n{}Connection: close\r\n\r\n", length);
+                if declared.is_some() {
+                    std::thread::sleep(Duration::from_secs(30));
+                    return;
+                }
+                let _ = stream.write_all(br#"{"models":["#);
+                let chunk = TAGS[11..TAGS.len() - 2].to_string() + ",";
+                while stream.write_all(chunk.as_bytes()).is_ok() {}
+            });
+        }
+    });
+}
+
+/// Like `serve_ollama`, but keeps connections open between requests, each on
+/// its own thread. Returns the number of connections accepted and of
+/// requests answered so far.
+pub fn serve_ollama_keepalive(ip: &str) -> (Arc<AtomicUsize>, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind((ip, 11434)).unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counts = (connections.clone(), requests.clone());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            connections.fetch_add(1, Ordering::Relaxed);
+            let requests = requests.clone();
+            std::thread::spawn(move || {
+                let mut pending = Vec::new();
+                let mut buf = [0u8; 1024];
+                loop {
+                    // The head, then as much body as it announces
+                    let head_end = loop {
+                        if let Some(end) = pending.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break end + 4;
+                        }
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => pending.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let head = String::from_utf8_lossy(&pending[..head_end]).to_lowercase();
+                    let body_len: usize = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .and_then(|len| len.trim().parse().ok())
+                        .unwrap_or(0);
+                    while pending.len() < head_end + body_len {
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => pending.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let (status, _, body) = ollama_answer(&pending[..head_end]);
+                    pending.drain(..head_end + body_len);
+                    requests.fetch_add(1, Ordering::Relaxed);
+                    let answer = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    if stream.write_all(answer.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    counts
+}
+
+/// A one-connection-at-a-time HTTP/1.1 server; `respond` maps the raw request
+/// head to a status line, extra header lines and a body.
+fn serve(ip: &str, port: u16, respond: impl Fn(&[u8]) -> (&'static str, &'static str, String) + Send + 'static) {
+    let listener = TcpListener::bind((ip, port)).unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let (status, headers, body) = respond(&request);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                headers,
+                body.len(),
+                body
+            );
+        }
+    });
+}
+
+/// A port nothing listens on right now.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Sends one request to 127.0.0.1:`port` with `token` as its bearer token;
+/// returns the status code and the body.
+pub fn http(port: u16, method: &str, path: &str, token: Option<&str>, body: &str) -> std::io::Result<(u16, String)> {
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port))?;
+    let auth = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", method, path, auth, body.len(), body)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response.split(' ').nth(1).and_then(|code| code.parse().ok()).unwrap_or(0);
+    let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+    Ok((status, body.to_string()))
+}
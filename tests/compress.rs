@@ -0,0 +1,39 @@
+//! `--compress`, and the subcommands reading what it wrote.
+
+mod common;
+
+use std::io::Read;
+use std::path::Path;
+
+use common::{run, serve_ollama, workdir};
+
+fn gunzip(path: &Path) -> String {
+    let mut text = String::new();
+    flate2::read::MultiGzDecoder::new(std::fs::File::open(path).unwrap()).read_to_string(&mut text).unwrap();
+    text
+}
+
+#[test]
+fn appends_gzip_members_that_read_back_as_one_file() {
+    serve_ollama("127.0.5.23");
+    let dir = workdir("compress", Some("127.0.5.23\n"));
+    for _ in 0..2 {
+        let outcome = run(&dir, &["--compress", "--event-log", "events.jsonl"], "y\n");
+        assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+        assert!(outcome.stderr.contains("Output: ollama_endpoints.csv.gz, llm_models.csv.gz (gzip)"), "{}", outcome.stderr);
+    }
+    assert!(!dir.join("ollama_endpoints.csv").exists() && !dir.join("events.jsonl").exists());
+
+    let endpoints = gunzip(&dir.join("ollama_endpoints.csv.gz"));
+    let lines: Vec<&str> = endpoints.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", endpoints);
+    assert!(lines[0].starts_with("IP:Port,") && lines[1..].iter().all(|line| line.contains("127.0.5.23")), "{}", endpoints);
+    let events = gunzip(&dir.join("events.jsonl.gz"));
+    assert_eq!(events.lines().filter(|line| line.contains(r#""type":"run_started""#)).count(), 2, "{}", events);
+
+    let outcome = run(&dir, &["stats", "llm_models.csv.gz", "--json"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let outcome = run(&dir, &["export", "--format", "urls", "--endpoints", "ollama_endpoints.csv.gz", "--models", "llm_models.csv.gz", "-o", "urls.txt"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert_eq!(std::fs::read_to_string(dir.join("urls.txt")).unwrap(), "http://127.0.5.23:11434\n");
+}
@@ -0,0 +1,27 @@
+//! `--pool-idle-per-host` and connection reuse for `--deep`.
+
+mod common;
+
+use common::{run, serve_ollama_keepalive, workdir};
+use std::sync::atomic::Ordering;
+
+#[test]
+fn deep_probes_reuse_the_connection() {
+    let (connections, requests) = serve_ollama_keepalive("127.0.0.64");
+    let dir = workdir("pool-deep", Some("127.0.0.64\n"));
+    let outcome = run(&dir, &["--deep"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let (connections, requests) = (connections.load(Ordering::Relaxed), requests.load(Ordering::Relaxed));
+    assert!(requests > 3, "{} requests", requests);
+    assert_eq!(connections, 1, "{} requests over {} connections", requests, connections);
+}
+
+#[test]
+fn opens_a_connection_per_request_without_a_pool() {
+    let (connections, requests) = serve_ollama_keepalive("127.0.0.65");
+    let dir = workdir("pool-off", Some("127.0.0.65\n"));
+    let outcome = run(&dir, &["--deep", "--pool-idle-per-host", "0"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert_eq!(connections.load(Ordering::Relaxed), requests.load(Ordering::Relaxed));
+}
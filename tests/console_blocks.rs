@@ -0,0 +1,27 @@
+//! Hit blocks printed by many probes at once come out whole.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+
+#[test]
+fn hit_blocks_do_not_interleave() {
+    for host in 74..=81 {
+        serve_ollama(&format!("127.0.0.{}", host));
+    }
+    let dir = workdir("console-blocks", Some("127.0.0.74-127.0.0.81\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let lines: Vec<&str> = outcome.stderr.lines().collect();
+    let headers: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].contains("Found Ollama Server")).collect();
+    assert_eq!(headers.len(), 8, "{}", outcome.stderr);
+    for i in headers {
+        let endpoint = lines[i + 1].split("http://").nth(1).unwrap_or_else(|| panic!("{}", outcome.stderr));
+        let host = endpoint.trim_end_matches("/api/tags");
+        let block = &lines[i + 1..i + 5];
+        assert!(block[1].contains(&format!("Server URL: http://{}", host)), "{:#?}", block);
+        assert!(block[2].contains("Location: "), "{:#?}", block);
+        assert!(block[3].contains("Discovered At: "), "{:#?}", block);
+    }
+}
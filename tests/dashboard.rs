@@ -0,0 +1,53 @@
+//! `--dashboard` serves its page and the scan's status and findings to
+//! requests that carry the token.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use common::{finish, free_port, http, serve_ollama, serve_ollama_with, spawn, workdir};
+
+#[test]
+fn serves_the_page_status_and_findings() {
+    serve_ollama("127.0.5.11");
+    // Keeps the scan going while the dashboard is looked at
+    serve_ollama_with("127.0.5.12", Duration::from_millis(2500), Arc::new(Mutex::new(Vec::new())));
+    let dir = workdir("dashboard", Some("127.0.5.11-127.0.5.12\n"));
+    std::fs::write(dir.join("config.json"), r#"{"api": {"token": "dash"}}"#).unwrap();
+    let port = free_port();
+    let child = spawn(&dir, &["--dashboard", &format!("127.0.0.1:{}", port)], "y\n");
+
+    let start = Instant::now();
+    let found = loop {
+        if let Ok((200, body)) = http(port, "GET", "/findings.json?token=dash", None, "") {
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            if body["findings"].as_array().is_some_and(|f| !f.is_empty()) {
+                break body;
+            }
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "no findings on the dashboard");
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    assert_eq!(found["findings"][0]["base_url"], "http://127.0.5.11:11434");
+
+    let (code, page) = http(port, "GET", "/?token=dash", None, "").unwrap();
+    assert_eq!(code, 200);
+    assert!(page.contains("<title>Public Ollama Finder</title>"), "{}", page);
+    assert!(!page.contains("https://"), "the page must not load anything from elsewhere");
+    assert_eq!(http(port, "GET", "/", None, "").unwrap().0, 401);
+    assert_eq!(http(port, "GET", "/?token=wrong", None, "").unwrap().0, 401);
+
+    let (code, status) = http(port, "GET", "/status.json", Some("dash"), "").unwrap();
+    assert_eq!(code, 200);
+    let status: serde_json::Value = serde_json::from_str(&status).unwrap();
+    assert_eq!(status["state"], "running");
+    assert_eq!(status["targets"], 2);
+    assert_eq!(status["hits"], 1);
+    // Every category, counted or not, so the page can lay them out
+    assert!(status["errors"].as_array().is_some_and(|e| e.iter().any(|e| e["category"] == "connect timeout" && e["count"].is_u64())), "{}", status);
+
+    let outcome = finish(child);
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains(&format!("Dashboard: http://127.0.0.1:{}/", port)), "{}", outcome.stderr);
+}
@@ -0,0 +1,48 @@
+//! The `Discovered At` column, and files from versions without it.
+
+mod common;
+
+use common::{field, run, serve_ollama, workdir};
+use std::fs;
+
+const OLD_ENDPOINTS: &str = "IP:Port,Tags URL,Status Code,Location,Run ID,Source,Proxy/Server,Fronting,WebUI,Write Access,Embedding Dimension,Capabilities,Alert\n\
+    http://10.0.0.1:11434,http://10.0.0.1:11434/api/tags,200,Unknown,20250101T000000Z,ip-ranges.txt:1,,,,,,,\n";
+
+#[test]
+fn stamps_endpoint_and_model_rows_alike() {
+    serve_ollama("127.0.0.38");
+    let dir = workdir("discovered-at", Some("127.0.0.38\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let stamp = field(&dir.join("ollama_endpoints.csv"), "127.0.0.38", "Discovered At");
+    assert!(chrono::DateTime::parse_from_rfc3339(&stamp).is_ok(), "{}", stamp);
+    assert!(stamp.ends_with('Z'), "{}", stamp);
+    assert_eq!(field(&dir.join("llm_models.csv"), "127.0.0.38", "Discovered At"), stamp);
+    assert!(outcome.stderr.contains(&format!("Discovered At: {}", stamp)), "{}", outcome.stderr);
+}
+
+#[test]
+fn keeps_older_files_in_their_layout_unless_asked_to_migrate() {
+    serve_ollama("127.0.0.39");
+    let dir = workdir("discovered-at-old", Some("127.0.0.39\n"));
+    fs::write(dir.join("ollama_endpoints.csv"), OLD_ENDPOINTS).unwrap();
+
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("continuing without the Discovered At, Model Count, Total Size (GB), Suspicion, Provider, Throttling, Pass, Tag column(s)"), "{}", outcome.stderr);
+    let text = fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(text.starts_with(OLD_ENDPOINTS), "{}", text);
+    let mut reader = csv::Reader::from_path(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(reader.records().all(|row| row.unwrap().len() == 13));
+
+    let outcome = run(&dir, &["--migrate-output"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let endpoints = dir.join("ollama_endpoints.csv");
+    assert_eq!(field(&endpoints, "10.0.0.1", "Discovered At"), "");
+    let mut reader = csv::Reader::from_path(&endpoints).unwrap();
+    let stamps: Vec<String> = reader.records().map(|row| row.unwrap()[13].to_string()).collect();
+    // The row of the first run stays without a time; the second run's has one
+    assert_eq!(stamps.len(), 3);
+    assert!(stamps[1].is_empty() && !stamps[2].is_empty(), "{:?}", stamps);
+}
@@ -0,0 +1,26 @@
+//! `--dns` and `--dns-over-https`. A plain scan of IP addresses resolves no
+//! names, so these only check what the scan reports about its resolver.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+
+#[test]
+fn names_the_upstreams_in_the_banner() {
+    serve_ollama("127.0.7.32");
+    let dir = workdir("dns", Some("127.0.7.32\n"));
+    let outcome = run(&dir, &["--dns-over-https", "1.1.1.1#cloudflare-dns.com", "--dns", "9.9.9.9"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("DNS: DoH 1.1.1.1:443 (cloudflare-dns.com), 9.9.9.9:53"), "{}", outcome.stderr);
+}
+
+#[test]
+fn rejects_bad_upstreams() {
+    let dir = workdir("dns-bad", Some("127.0.7.33\n"));
+    let outcome = run(&dir, &["--dns", "dns.quad9.net"], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("is not an IP address"), "{}", outcome.stderr);
+    let outcome = run(&dir, &["--dns", "9.9.9.9", "--dns-timeout", "0s"], "y\n");
+    assert_ne!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("--dns-timeout must be longer than 0s"), "{}", outcome.stderr);
+}
@@ -0,0 +1,43 @@
+//! `--dry-run`: the targets are loaded and counted, but nothing is probed and
+//! no output file is created.
+
+mod common;
+
+use common::{run, serve_ollama_with, workdir};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+fn lists_what_each_input_contributes_without_probing() {
+    let probes = Arc::new(Mutex::new(Vec::new()));
+    serve_ollama_with("127.0.7.80", Duration::ZERO, probes.clone());
+    let dir = workdir("dry-run", None);
+    std::fs::write(dir.join("a.txt"), "127.0.7.80\n127.0.7.96/28\ncustomer-b: 10.0.0\n").unwrap();
+    std::fs::write(dir.join("b.txt"), "127.0.7.80\n127.0.7.100-127.0.7.119\n").unwrap();
+
+    let outcome = run(&dir, &["--dry-run", "--input", "a.txt", "--input", "b.txt", "--input", "missing.txt"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Dry run: nothing is probed or written"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("a.txt: 2 ranges accepted, 1 skipped\n"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("  Skipped: line 3 (customer-b): no valid address in '10.0.0'\n"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("  15 addresses listed: 15 to probe\n"), "{}", outcome.stderr);
+    // The /28 already lists 127.0.7.100-110; .111 is its broadcast address
+    assert!(outcome.stderr.contains("b.txt: 1 ranges accepted, 1 listed again\n"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("  20 addresses listed, 11 listed by an earlier range: 9 to probe\n"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("missing.txt: failed to read file"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Total: 3 ranges, 24 addresses to probe"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("Warning:"), "{}", outcome.stderr);
+
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(probes.lock().unwrap().is_empty());
+    let written: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(written.len(), 2, "{:?}", written);
+}
+
+#[test]
+fn exits_2_without_any_target() {
+    let dir = workdir("dry-run-empty", Some("# nothing yet\ncustomer-a: 10.0.0\n"));
+    let outcome = run(&dir, &["--dry-run"], "");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Total: 0 ranges, 0 addresses to probe"), "{}", outcome.stderr);
+}
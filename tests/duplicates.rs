@@ -0,0 +1,55 @@
+//! Addresses listed by more than one target are probed once, also across
+//! `--resume`.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{run, serve_ollama_with, workdir};
+
+#[test]
+fn probes_an_address_listed_twice_once() {
+    let probes = Arc::new(Mutex::new(Vec::new()));
+    serve_ollama_with("127.0.0.68", Duration::ZERO, probes.clone());
+    // A seed /32 inside a range that also lists it
+    let dir = workdir("duplicates", Some("127.0.0.68\n127.0.0.68-127.0.0.69\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    assert_eq!(*probes.lock().unwrap(), ["127.0.0.68"]);
+    assert!(outcome.stderr.contains("(2 total IPs, 1 duplicate listings skipped)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("IPs attempted: 2"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Duplicates skipped: 1"), "{}", outcome.stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn resume_skips_what_finished_ranges_probed() {
+    use common::{finish, spawn};
+    use std::process::Command;
+
+    let probes = Arc::new(Mutex::new(Vec::new()));
+    for host in 1..=24 {
+        serve_ollama_with(&format!("127.0.2.{}", host), Duration::from_millis(250), probes.clone());
+    }
+    // The second range repeats the first one's hosts
+    let dir = workdir("duplicates-resume", Some("127.0.2.1-127.0.2.8\n127.0.2.1-127.0.2.24\n"));
+    let args = ["--concurrency-fixed", "4"];
+
+    let child = spawn(&dir, &args, "y\n");
+    std::thread::sleep(Duration::from_millis(1000));
+    Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    let stopped = finish(child);
+    assert_eq!(stopped.code, 3, "{}", stopped.stderr);
+    assert!(probes.lock().unwrap().len() >= 8, "{:?}", probes.lock().unwrap());
+
+    let resumed = run(&dir, &["--resume", "--concurrency-fixed", "4"], "y\n");
+    assert_eq!(resumed.code, 0, "{}", resumed.stderr);
+
+    let mut probed = probes.lock().unwrap().clone();
+    probed.sort();
+    let mut expected: Vec<String> = (1..=24).map(|host| format!("127.0.2.{}", host)).collect();
+    expected.sort();
+    assert_eq!(probed, expected);
+}
@@ -0,0 +1,99 @@
+//! The summary email and `--test-email`, sent to a stand-in SMTP server.
+#![cfg(feature = "email")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use common::{free_port, run, serve_ollama, workdir};
+
+/// Accepts every message without TLS or login and keeps what follows DATA.
+fn serve_smtp() -> (u16, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let kept = messages.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let _ = stream.write_all(b"220 localhost ESMTP\r\n");
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250-localhost\r\n250 8BITMIME\r\n"
+                } else if command.starts_with("DATA") {
+                    let _ = stream.write_all(b"354 End with .\r\n");
+                    let mut message = String::new();
+                    let mut data = String::new();
+                    while reader.read_line(&mut data).is_ok_and(|n| n > 0) && data != ".\r\n" {
+                        message.push_str(&data);
+                        data.clear();
+                    }
+                    kept.lock().unwrap().push(message);
+                    b"250 Queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    let _ = stream.write_all(b"221 Bye\r\n");
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                let _ = stream.write_all(reply);
+                line.clear();
+            }
+        }
+    });
+    (port, messages)
+}
+
+fn write_config(dir: &Path, port: u16, extra: &str) {
+    let config = format!(r#"{{"email": {{"server": "127.0.0.1", "port": {}, "security": "none", "from": "scanner@example.com", "to": ["me@example.com"]{}}}}}"#, port, extra);
+    std::fs::write(dir.join("mail.json"), config).unwrap();
+}
+
+#[test]
+fn mails_the_summary_with_the_endpoints_csv() {
+    serve_ollama("127.0.5.27");
+    let (port, messages) = serve_smtp();
+    let dir = workdir("email-summary", Some("127.0.5.27\n"));
+    write_config(&dir, port, r#", "attach_endpoints": "1MB""#);
+    let outcome = run(&dir, &["--config", "mail.json", "--label", "office"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Summary emailed to me@example.com"), "{}", outcome.stderr);
+
+    let messages = messages.lock().unwrap();
+    assert_eq!(messages.len(), 1);
+    let message = &messages[0];
+    assert!(message.contains("Subject: Ollama scan completed [office]: 1 endpoint found"), "{}", message);
+    assert!(message.contains("IPs scanned: 1 of 1"), "{}", message);
+    assert!(message.contains(r#"filename="ollama_endpoints.csv""#), "{}", message);
+}
+
+#[test]
+fn a_failed_email_leaves_the_result_alone() {
+    let dir = workdir("email-unreachable", Some("127.0.5.28\n"));
+    write_config(&dir, free_port(), "");
+    let outcome = run(&dir, &["--config", "mail.json"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Failed to email the summary"), "{}", outcome.stderr);
+}
+
+#[test]
+fn sends_a_test_message_without_scanning() {
+    let (port, messages) = serve_smtp();
+    let dir = workdir("email-test", None);
+    write_config(&dir, port, "");
+    let outcome = run(&dir, &["--config", "mail.json", "--test-email"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Test message sent to me@example.com"), "{}", outcome.stderr);
+    assert!(messages.lock().unwrap()[0].contains("Subject: Ollama finder test message"));
+
+    write_config(&dir, port, r#", "attach_endpoints": "lots""#);
+    let outcome = run(&dir, &["--config", "mail.json", "--test-email"], "");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains("Invalid email.attach_endpoints"), "{}", outcome.stderr);
+}
@@ -0,0 +1,35 @@
+//! `--check-embeddings` against fake Ollamas with and without embedding models.
+
+mod common;
+
+use common::{field, run, serve_ollama, serve_routes, workdir};
+
+const NOMIC_TAGS: &str = r#"{"models":[{"name":"llama3:8b","modified_at":"2024-05-01T10:00:00Z","size":4661224676,"digest":"365c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1"},{"name":"mxbai-embed-large:latest","modified_at":"2024-05-01T10:00:00Z","size":669615493,"digest":"468836162de7f81e041c43663fedbbba921dcea9b9fefea135685a39b2d83dd8"},{"name":"nomic-embed-text:latest","modified_at":"2024-05-01T10:00:00Z","size":274302450,"digest":"0a109f422b47e3a30ba2b10eca18548e944e8a23073ee3f3e947efcf3c45e59f"}]}"#;
+const MINILM_TAGS: &str = r#"{"models":[{"name":"all-minilm:latest","modified_at":"2024-05-01T10:00:00Z","size":45960996,"digest":"1b226e2802dbb772b5fc32a58f103ca1804ef7501331012de126ab22f67475ef"}]}"#;
+const BGE_TAGS: &str = r#"{"models":[{"name":"bge-m3:latest","modified_at":"2024-05-01T10:00:00Z","size":1157672605,"digest":"790764642607ee2bff07f6ec7d2b7ed8f8e3fe8a33b6f2ee8b1a5e1fb2b7a61e"}]}"#;
+
+#[test]
+fn records_the_dimension_or_the_failure() {
+    serve_routes("127.0.0.26", 11434, &[
+        ("GET /api/tags ", "200 OK", NOMIC_TAGS),
+        ("POST /api/embed ", "200 OK", r#"{"model":"nomic-embed-text:latest","embeddings":[[0.1,-0.2,0.3]]}"#),
+    ]);
+    // A release from before /api/embed
+    serve_routes("127.0.0.27", 11434, &[
+        ("GET /api/tags ", "200 OK", MINILM_TAGS),
+        ("POST /api/embeddings ", "200 OK", r#"{"embedding":[0.1,-0.2,0.3,0.4]}"#),
+    ]);
+    serve_routes("127.0.0.28", 11434, &[
+        ("GET /api/tags ", "200 OK", BGE_TAGS),
+        ("POST /api/embed ", "500 Internal Server Error", r#"{"error":"model requires more system memory"}"#),
+    ]);
+    serve_ollama("127.0.0.29");
+    let dir = workdir("embeddings", Some("127.0.0.26-127.0.0.29\n"));
+    let outcome = run(&dir, &["--check-embeddings"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    for (ip, dimension) in [("127.0.0.26", "3"), ("127.0.0.27", "4"), ("127.0.0.28", "failed"), ("127.0.0.29", "")] {
+        assert_eq!(field(&endpoints, ip, "Embedding Dimension"), dimension, "{}", ip);
+    }
+}
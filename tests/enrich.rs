@@ -0,0 +1,54 @@
+//! `--enrich-show` against a fake Ollama that answers /api/show.
+
+mod common;
+
+use common::{field, run, serve_json, serve_ollama, workdir, TAGS};
+
+#[test]
+fn writes_model_details_for_each_endpoint() {
+    serve_ollama("127.0.0.16");
+    let dir = workdir("enrich-show", Some("127.0.0.16\n"));
+    let outcome = run(&dir, &["--enrich-show"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let details = std::fs::read_to_string(dir.join("model_details.csv")).unwrap();
+    let mut lines = details.lines();
+    assert_eq!(
+        lines.next(),
+        Some("IP:Port,Model Name,License,Template Hash,Parameters,Context Length,System Prompt,Note,Run ID")
+    );
+    let row = lines.next().unwrap();
+    assert!(
+        row.starts_with(r#"http://127.0.0.16:11434,llama3:8b,META LLAMA 3 COMMUNITY LICENSE AGREEMENT,"#),
+        "{}",
+        row
+    );
+    assert!(row.contains(r#","num_ctx 8192; stop ""<|eot_id|>""",8192,,,"#), "{}", row);
+    assert_eq!(lines.next(), None);
+
+    assert_eq!(field(&dir.join("llm_models.csv"), "127.0.0.16", "License"), "llama3");
+}
+
+#[test]
+fn keeps_model_rows_when_api_show_fails() {
+    // /api/tags only; /api/show answers 404
+    serve_json("127.0.0.18", 11434, "/api/tags", "", TAGS);
+    let dir = workdir("enrich-show-fails", Some("127.0.0.18\n"));
+    let outcome = run(&dir, &["--enrich-show"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let models = dir.join("llm_models.csv");
+    assert_eq!(field(&models, "127.0.0.18", "Model Name"), "llama3:8b");
+    assert_eq!(field(&models, "127.0.0.18", "License"), "");
+    let details = std::fs::read_to_string(dir.join("model_details.csv")).unwrap();
+    assert_eq!(details.lines().count(), 1, "{}", details);
+}
+
+#[test]
+fn writes_no_model_details_without_the_flag() {
+    serve_ollama("127.0.0.17");
+    let dir = workdir("enrich-off", Some("127.0.0.17\n"));
+    assert_eq!(run(&dir, &[], "y\n").code, 0);
+    assert!(!dir.join("model_details.csv").exists());
+    assert_eq!(field(&dir.join("llm_models.csv"), "127.0.0.17", "License"), "");
+}
@@ -0,0 +1,43 @@
+//! `--event-log`: the run's timeline as JSON Lines.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+use std::fs;
+
+#[test]
+fn logs_the_run_from_start_to_end() {
+    serve_ollama("127.0.0.93");
+    let dir = workdir("event-log", Some("127.0.0.93\n127.0.0.94\n"));
+    let outcome = run(&dir, &["--event-log", "events.jsonl"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let events: Vec<serde_json::Value> = fs::read_to_string(dir.join("events.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(events.iter().all(|event| event["schema"] == 1 && event["timestamp"].is_string()), "{:#?}", events);
+    let types: Vec<&str> = events.iter().map(|event| event["type"].as_str().unwrap()).collect();
+    assert_eq!(types, [
+        "run_started",
+        "scan_started",
+        "range_started",
+        "finding",
+        "range_finished",
+        "range_started",
+        "range_finished",
+        "scan_finished",
+        "run_ended",
+    ]);
+
+    assert_eq!(events[0]["targets"], 2);
+    assert_eq!(events[2]["range"], 1);
+    assert_eq!(events[2]["source"], "ip-ranges.txt:1");
+    assert_eq!(events[3]["finding"]["base_url"], "http://127.0.0.93:11434");
+    assert_eq!(events[3]["finding"]["models"][0]["name"], "llama3:8b");
+    assert_eq!(events[4]["stopped"], false);
+    assert_eq!(events[7]["hits"], 1);
+    assert_eq!(events[8]["termination"], "completed");
+    assert_eq!(events[8]["exit_code"], 0);
+}
@@ -0,0 +1,59 @@
+//! Runs the binary end to end and checks the documented exit codes.
+//!
+//! The scanner always probes port 11434, so the fake Ollama server listens on
+//! 127.0.0.2 to stay clear of anything already bound to 127.0.0.1.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+
+#[test]
+fn exits_0_when_an_endpoint_is_found() {
+    serve_ollama("127.0.0.2");
+    let dir = workdir("found", Some("127.0.0.2\n"));
+    assert_eq!(run(&dir, &[], "y\n").code, 0);
+}
+
+#[test]
+fn exits_2_when_nothing_is_found() {
+    let dir = workdir("empty", Some("127.0.0.3\n"));
+    assert_eq!(run(&dir, &[], "y\n").code, 2);
+}
+
+#[test]
+fn exits_1_without_an_input_file() {
+    let dir = workdir("fatal", None);
+    assert_eq!(run(&dir, &[], "y\n").code, 1);
+}
+
+#[test]
+fn exits_1_on_invalid_arguments() {
+    let dir = workdir("usage", Some("127.0.0.3\n"));
+    let outcome = run(&dir, &["--no-such-flag"], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("--no-such-flag"), "{}", outcome.stderr);
+    assert_eq!(run(&dir, &["--help"], "").code, 0);
+}
+
+#[test]
+fn exits_3_when_the_disclaimer_is_declined() {
+    let dir = workdir("declined", Some("127.0.0.3\n"));
+    assert_eq!(run(&dir, &[], "n\n").code, 3);
+}
+
+#[test]
+fn exits_4_when_the_scan_is_larger_than_max_targets() {
+    let dir = workdir("oversize", Some("127.0.7.48/28\n127.0.7.56/29\n127.0.7.64/30\n"));
+    let outcome = run(&dir, &["--max-targets", "15"], "y\n");
+    assert_eq!(outcome.code, 4, "{}", outcome.stderr);
+    // The /29 inside the /28 adds nothing; the /30 loses its network and broadcast addresses
+    assert!(outcome.stderr.contains("This scan covers 16 addresses, more than --max-targets (15)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Estimated duration: 0s at 800 probes/s (16 probes)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("  14 addresses from 127.0.7.48/28 (ip-ranges.txt:1)\n"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("  2 addresses from 127.0.7.64/30 (ip-ranges.txt:3)\n"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("127.0.7.56/29"), "{}", outcome.stderr);
+    assert!(!dir.join("ollama_endpoints.csv").exists());
+
+    assert_eq!(run(&dir, &["--max-targets", "16"], "y\n").code, 2);
+    assert_eq!(run(&dir, &["--max-targets", "15", "--i-know-what-im-doing"], "y\n").code, 2);
+}
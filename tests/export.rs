@@ -0,0 +1,62 @@
+//! The `export` subcommand over CSVs from earlier scans.
+
+mod common;
+
+use common::{run, workdir};
+
+const ENDPOINTS: &str = "\
+IP:Port,Tags URL,Status Code,Location,Run ID
+http://10.0.0.1:11434,http://10.0.0.1:11434/api/tags,200,ovh,20240501T100000Z
+http://10.0.0.2:11434,http://10.0.0.2:11434/api/tags,200,ovh,20240501T100000Z
+http://10.0.0.3:11434,http://10.0.0.3:11434/api/tags,403,ovh,20240501T100000Z
+http://10.0.0.1:11434,http://10.0.0.1:11434/api/tags,200,ovh,20240502T100000Z
+";
+const MODELS: &str = "\
+IP:Port,Model Name,Model,Run ID
+http://10.0.0.1:11434,llama3:8b,llama3:8b,20240501T100000Z
+http://10.0.0.2:11434,llama3:8b,llama3:8b,20240501T100000Z
+http://10.0.0.2:11434,mistral:7b,mistral:7b,20240501T100000Z
+http://10.0.0.1:11434,llama3:8b,llama3:8b,20240502T100000Z
+";
+
+#[test]
+fn writes_a_litellm_model_list_and_urls() {
+    let dir = workdir("export", None);
+    std::fs::write(dir.join("ollama_endpoints.csv"), ENDPOINTS).unwrap();
+    std::fs::write(dir.join("llm_models.csv"), MODELS).unwrap();
+
+    let outcome = run(&dir, &["export", "--format", "litellm", "-o", "config.yaml"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("config.yaml")).unwrap(),
+        r#"model_list:
+  - model_name: "llama3:8b"
+    litellm_params:
+      model: "ollama/llama3:8b"
+      api_base: "http://10.0.0.1:11434"
+  - model_name: "llama3:8b-2"
+    litellm_params:
+      model: "ollama/llama3:8b"
+      api_base: "http://10.0.0.2:11434"
+  - model_name: "mistral:7b"
+    litellm_params:
+      model: "ollama/mistral:7b"
+      api_base: "http://10.0.0.2:11434"
+"#
+    );
+
+    let outcome = run(&dir, &["export", "--format", "urls", "-o", "urls.txt"], "");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert_eq!(std::fs::read_to_string(dir.join("urls.txt")).unwrap(), "http://10.0.0.1:11434\nhttp://10.0.0.2:11434\n");
+}
+
+#[test]
+fn names_the_missing_columns() {
+    let dir = workdir("export-bad", None);
+    std::fs::write(dir.join("ollama_endpoints.csv"), ENDPOINTS).unwrap();
+    std::fs::write(dir.join("models.csv"), "Endpoint,Name\nhttp://10.0.0.1:11434,llama3:8b\n").unwrap();
+
+    let outcome = run(&dir, &["export", "--format", "litellm", "--models", "models.csv"], "");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains("models.csv has no IP:Port, Model Name columns"), "{}", outcome.stderr);
+}
@@ -0,0 +1,107 @@
+//! `--only-countries`/`--skip-countries` with a small MaxMind database.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+use std::fs;
+use std::net::Ipv4Addr;
+
+enum Record {
+    Empty,
+    Node(usize),
+    Data(usize),
+}
+
+/// An IPv4 MaxMind DB with 24-bit records mapping each /32 to a country,
+/// or to a record without one for None.
+fn mmdb(entries: &[(&str, Option<&str>)]) -> Vec<u8> {
+    let mut nodes = vec![[Record::Empty, Record::Empty]];
+    let mut data = Vec::new();
+    for (ip, country) in entries {
+        let address = u32::from(ip.parse::<Ipv4Addr>().unwrap());
+        let mut node = 0;
+        for depth in 0..32 {
+            let bit = (address >> (31 - depth) & 1) as usize;
+            if depth == 31 {
+                nodes[node][bit] = Record::Data(data.len());
+            } else if let Record::Node(next) = nodes[node][bit] {
+                node = next;
+            } else {
+                nodes.push([Record::Empty, Record::Empty]);
+                nodes[node][bit] = Record::Node(nodes.len() - 1);
+                node = nodes.len() - 1;
+            }
+        }
+        match country {
+            Some(code) => {
+                data.push(0xe1);
+                string(&mut data, "country");
+                data.push(0xe1);
+                string(&mut data, "iso_code");
+                string(&mut data, code);
+            }
+            None => data.push(0xe0),
+        }
+    }
+
+    let node_count = nodes.len();
+    let mut bytes = Vec::new();
+    for node in &nodes {
+        for record in node {
+            let value = match record {
+                Record::Empty => node_count,
+                Record::Node(next) => *next,
+                Record::Data(offset) => node_count + 16 + offset,
+            };
+            bytes.extend(&(value as u32).to_be_bytes()[1..]);
+        }
+    }
+    bytes.extend([0; 16]);
+    bytes.extend(data);
+    bytes.extend(b"\xAB\xCD\xEFMaxMind.com");
+    bytes.push(0xe3);
+    string(&mut bytes, "node_count");
+    bytes.push(0xc4);
+    bytes.extend((node_count as u32).to_be_bytes());
+    string(&mut bytes, "record_size");
+    bytes.extend([0xa2, 0, 24]);
+    string(&mut bytes, "ip_version");
+    bytes.extend([0xa2, 0, 4]);
+    bytes
+}
+
+fn string(out: &mut Vec<u8>, s: &str) {
+    out.push(0x40 | s.len() as u8);
+    out.extend(s.as_bytes());
+}
+
+#[test]
+fn probes_only_the_chosen_countries() {
+    serve_ollama("127.0.0.48");
+    serve_ollama("127.0.0.49");
+    let dir = workdir("geo", Some("127.0.0.48-127.0.0.51\n"));
+    fs::write(dir.join("countries.mmdb"), mmdb(&[("127.0.0.48", Some("NL")), ("127.0.0.49", Some("DE")), ("127.0.0.50", None)])).unwrap();
+
+    let outcome = run(&dir, &["--geoip-db", "countries.mmdb", "--only-countries", "nl"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let endpoints = fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(endpoints.contains("127.0.0.48") && !endpoints.contains("127.0.0.49"), "{}", endpoints);
+    // .50 has a record without a country and .51 none at all
+    assert!(outcome.stderr.contains("Excluded by country: 3 (unknown (2), DE (1))"), "{}", outcome.stderr);
+
+    fs::remove_file(dir.join("ollama_endpoints.csv")).unwrap();
+    let outcome = run(&dir, &["--geoip-db", "countries.mmdb", "--skip-countries", "NL", "--unknown-country", "scan"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let endpoints = fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(endpoints.contains("127.0.0.49") && !endpoints.contains("127.0.0.48"), "{}", endpoints);
+    assert!(outcome.stderr.contains("Excluded by country: 1 (NL (1))"), "{}", outcome.stderr);
+}
+
+#[test]
+fn refuses_a_file_that_is_not_a_database() {
+    let dir = workdir("geo-invalid", Some("127.0.0.52\n"));
+    fs::write(dir.join("countries.mmdb"), "not a database").unwrap();
+    let outcome = run(&dir, &["--geoip-db", "countries.mmdb", "--only-countries", "NL"], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("countries.mmdb is not a MaxMind database"), "{}", outcome.stderr);
+}
@@ -0,0 +1,29 @@
+//! The `Suspicion` column and its config section.
+
+mod common;
+
+use common::{field, run, serve_json, workdir, TAGS};
+use std::fs;
+
+#[test]
+fn marks_but_keeps_suspicious_hits() {
+    serve_json("127.0.0.53", 11434, "/api/tags", "Server: FakePot/1.0\r\n", TAGS);
+    let dir = workdir("honeypot", Some("127.0.0.53\n"));
+    fs::write(dir.join("config.json"), r#"{"honeypot": {"servers": ["fakepot"], "known_digests": {"llama3:8b": ["sha256:a6990ed6be41"]}}}"#).unwrap();
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let suspicion = field(&dir.join("ollama_endpoints.csv"), "127.0.0.53", "Suspicion");
+    assert_eq!(suspicion, "2: unknown digest for llama3:8b; Server header names fakepot");
+    assert!(outcome.stderr.contains("Possible honeypot: unknown digest for llama3:8b; Server header names fakepot"), "{}", outcome.stderr);
+    assert!(!field(&dir.join("llm_models.csv"), "127.0.0.53", "Model Name").is_empty());
+}
+
+#[test]
+fn rejects_unknown_settings() {
+    let dir = workdir("honeypot-config", Some("127.0.0.54\n"));
+    fs::write(dir.join("config.json"), r#"{"honeypot": {"long_lists": 5}}"#).unwrap();
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Invalid config file config.json"), "{}", outcome.stderr);
+}
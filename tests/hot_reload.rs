@@ -0,0 +1,45 @@
+//! Ranges appended to ip-ranges.txt while a scan runs are scanned by it.
+
+mod common;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{field, finish, serve_ollama_with, spawn, workdir};
+
+#[test]
+fn scans_ranges_appended_mid_scan() {
+    let probes = Arc::new(Mutex::new(Vec::new()));
+    for host in 1..=9 {
+        // Slow enough that the first range outlasts a look at the file
+        let delay = if host < 9 { Duration::from_millis(900) } else { Duration::ZERO };
+        serve_ollama_with(&format!("127.0.4.{}", host), delay, probes.clone());
+    }
+    let dir = workdir("hot-reload", Some("127.0.4.1-127.0.4.8\n"));
+    let child = spawn(&dir, &["--concurrency-fixed", "1", "--event-log", "events.jsonl"], "y\n");
+    std::thread::sleep(Duration::from_millis(1000));
+    // A range already listed is left out
+    let mut file = OpenOptions::new().append(true).open(dir.join("ip-ranges.txt")).unwrap();
+    file.write_all(b"127.0.4.1-127.0.4.8\n127.0.4.9\n").unwrap();
+    drop(file);
+
+    let outcome = finish(child);
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("1 new targets added from ip-ranges.txt (1 hosts)"), "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "http://127.0.4.9:11434", "Status Code"), "200");
+    let events = std::fs::read_to_string(dir.join("events.jsonl")).unwrap();
+    let added: Vec<serde_json::Value> = events
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|event| event["type"] == "targets_added")
+        .collect();
+    assert_eq!(added.len(), 1, "{}", events);
+    assert_eq!((added[0]["ranges"].as_u64(), added[0]["targets"].as_u64()), (Some(1), Some(1)), "{}", events);
+    let mut probed = probes.lock().unwrap().clone();
+    probed.sort();
+    let mut expected: Vec<String> = (1..=9).map(|host| format!("127.0.4.{}", host)).collect();
+    expected.sort();
+    assert_eq!(probed, expected);
+}
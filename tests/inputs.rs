@@ -0,0 +1,37 @@
+//! `--input` with several files and directories of target lists.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+
+#[test]
+fn loads_every_list_in_a_directory_and_merges_duplicates() {
+    serve_ollama("127.0.0.7");
+    let dir = workdir("input-dir", None);
+    let lists = dir.join("lists");
+    std::fs::create_dir(&lists).unwrap();
+    std::fs::write(lists.join("ovh.txt"), "127.0.0.7\n").unwrap();
+    std::fs::write(lists.join("hetzner.json"), r#"["127.0.0.7/32"]"#).unwrap();
+    std::fs::write(lists.join("notes.md"), "127.0.0.8\n").unwrap();
+
+    let outcome = run(&dir, &["--input", "lists"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Found 1 valid IP ranges in 2 files"), "{}", outcome.stderr);
+    let endpoints = std::fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(endpoints.contains("hetzner, ovh"), "{}", endpoints);
+    // Source column: every entry that listed the range, with its line
+    assert!(endpoints.contains("lists/hetzner.json:1; lists/ovh.txt:1"), "{}", endpoints);
+}
+
+#[test]
+fn an_unreadable_input_only_warns_while_another_one_loads() {
+    let dir = workdir("input-missing", None);
+    std::fs::write(dir.join("good.txt"), "127.0.0.3\n").unwrap();
+
+    let outcome = run(&dir, &["--input", "missing.txt", "--input", "good.txt"], "y\n");
+    assert!(outcome.stderr.contains("missing.txt: failed to read file"), "{}", outcome.stderr);
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+
+    let outcome = run(&dir, &["--input", "missing.txt"], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+}
@@ -0,0 +1,42 @@
+//! `--record-non-200` and interesting_responses.csv.
+
+mod common;
+
+use common::{field, run, serve_ollama, serve_routes, serve_tags_in_turn, workdir};
+use std::fs;
+
+#[test]
+fn records_non_200_answers_apart_from_endpoints() {
+    serve_ollama("127.0.0.88");
+    serve_tags_in_turn("127.0.0.89", &[("302 Found", "Location: https://sso.example.com/login\r\nServer: nginx/1.25.3\r\n", "")]);
+    serve_routes("127.0.0.90", 11434, &[]);
+    let dir = workdir("interesting-responses", Some("127.0.0.88-127.0.0.90\n"));
+
+    let outcome = run(&dir, &["--record-non-200"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let interesting = dir.join("interesting_responses.csv");
+    assert_eq!(field(&interesting, "127.0.0.89", "Status Code"), "302");
+    assert_eq!(field(&interesting, "127.0.0.89", "Redirect Location"), "https://sso.example.com/login");
+    assert_eq!(field(&interesting, "127.0.0.89", "Server"), "nginx/1.25.3");
+    assert_eq!(field(&interesting, "127.0.0.90", "Status Code"), "404");
+    assert_eq!(field(&interesting, "127.0.0.90", "Response Size"), "0");
+    assert!(!fs::read_to_string(&interesting).unwrap().contains("127.0.0.88"));
+
+    // Only the genuine hit is an endpoint
+    let endpoints = fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert_eq!(endpoints.lines().count(), 2, "{}", endpoints);
+    assert!(outcome.stderr.contains("Endpoints found: 1"), "{}", outcome.stderr);
+}
+
+#[test]
+fn caps_the_rows_per_run() {
+    serve_routes("127.0.0.91", 11434, &[]);
+    serve_routes("127.0.0.92", 11434, &[]);
+    let dir = workdir("interesting-responses-cap", Some("127.0.0.91-127.0.0.92\n"));
+
+    let outcome = run(&dir, &["--record-non-200", "--record-status", "404", "--max-non-200", "1"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert_eq!(fs::read_to_string(dir.join("interesting_responses.csv")).unwrap().lines().count(), 2);
+    assert!(outcome.stderr.contains("reached --max-non-200"), "{}", outcome.stderr);
+}
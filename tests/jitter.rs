@@ -0,0 +1,21 @@
+//! `--jitter`: a random delay before each probe, which lowers the rate.
+
+mod common;
+
+use common::{run, workdir};
+use std::time::{Duration, Instant};
+
+#[test]
+fn spaces_probes_out_and_reports_the_ceiling() {
+    // 14 hosts after a 100-200ms wait each
+    let dir = workdir("jitter", Some("127.0.6.128/28\n"));
+    let started = Instant::now();
+    let outcome = run(&dir, &["--jitter", "100-200ms", "--seed", "3"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Jitter: 100-200ms before each probe (at most 7 probes/s on average)"), "{}", outcome.stderr);
+    assert!(started.elapsed() >= Duration::from_millis(1400), "{:?}", started.elapsed());
+
+    let outcome = run(&dir, &["--jitter", "250ms-100ms"], "y\n");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains("'250ms-100ms' is not a range like 0-250ms"), "{}", outcome.stderr);
+}
@@ -0,0 +1,24 @@
+//! Labels written next to the ranges in the input, and `--label`.
+
+mod common;
+
+use common::{field, run, serve_ollama, workdir};
+
+#[test]
+fn carries_line_labels_and_the_run_tag_into_both_csvs() {
+    serve_ollama("127.0.5.13");
+    serve_ollama("127.0.5.14");
+    let dir = workdir("labels", Some("customer-a: 127.0.5.13\n127.0.5.14 # research\ncustomer-b: 127.0.5\n"));
+    let outcome = run(&dir, &["--label", "q3-audit"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("line 3 (customer-b): no valid address in '127.0.5'"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Tag: q3-audit"), "{}", outcome.stderr);
+
+    let (endpoints, models) = (dir.join("ollama_endpoints.csv"), dir.join("llm_models.csv"));
+    for (ip, label) in [("127.0.5.13", "customer-a"), ("127.0.5.14", "research")] {
+        assert_eq!(field(&endpoints, ip, "Location"), label);
+        assert_eq!(field(&models, ip, "Location"), label);
+        assert_eq!(field(&endpoints, ip, "Tag"), "q3-audit");
+        assert_eq!(field(&models, ip, "Tag"), "q3-audit");
+    }
+}
@@ -0,0 +1,33 @@
+//! metrics.json, written when the run ends.
+
+mod common;
+
+use common::{run, serve_ollama, serve_routes, workdir};
+use std::fs;
+
+#[test]
+fn writes_the_summary_numbers_as_json() {
+    serve_ollama("127.0.0.95");
+    serve_routes("127.0.0.96", 11434, &[("GET /api/tags ", "401 Unauthorized", "")]);
+    let dir = workdir("metrics", Some("127.0.0.95-127.0.0.97\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let metrics: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("metrics.json")).unwrap()).unwrap();
+    assert!(!dir.join("metrics.json.tmp").exists());
+    assert_eq!(metrics["targets"], 3);
+    assert_eq!(metrics["scanned"], 3);
+    assert_eq!(metrics["findings"]["endpoints"], 1);
+    assert_eq!(metrics["protected"], 1);
+    assert_eq!(metrics["possible_servers"]["401"], 1);
+    assert_eq!(metrics["errors"]["refused"], 1);
+    assert_eq!(metrics["errors_total"], 1);
+    assert_eq!(metrics["termination"], "completed");
+    assert!(metrics["duration_secs"].as_f64().unwrap() > 0.0, "{}", metrics);
+    assert!(metrics["bytes_received"].as_u64().unwrap() > 0, "{}", metrics);
+
+    // The console summary shows the same numbers
+    assert!(outcome.stderr.contains("IPs attempted: 3"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Endpoints found: 1"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains(&format!("Elapsed: {:.1}s", metrics["duration_secs"].as_f64().unwrap())), "{}", outcome.stderr);
+}
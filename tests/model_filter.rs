@@ -0,0 +1,63 @@
+//! `--quant`, `--min-quant`, `--family` and the `--require-*` variants.
+
+mod common;
+
+use common::{run, serve_routes, workdir};
+use std::fs;
+
+const TAGS: &str = concat!(
+    r#"{"models":["#,
+    r#"{"name":"llama3:8b","modified_at":"2024-05-01T10:00:00Z","size":4661224676,"digest":"365c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1","details":{"family":"llama","quantization_level":"Q4_K_M"}},"#,
+    r#"{"name":"llama3:8b-f16","modified_at":"2024-05-01T10:00:00Z","size":16068890624,"digest":"465c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1","details":{"family":"llama3","quantization_level":"F16"}},"#,
+    r#"{"name":"mystery","modified_at":"2024-05-01T10:00:00Z","size":1000,"digest":"565c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1","details":{"families":["qwen2"]}}"#,
+    r#"]}"#
+);
+
+const TAGS_Q4: &str = r#"{"models":[{"name":"llama3:8b","modified_at":"2024-05-01T10:00:00Z","size":4661224676,"digest":"365c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1","details":{"quantization_level":"Q4_0"}}]}"#;
+
+#[test]
+fn writes_only_matching_model_rows() {
+    serve_routes("127.0.0.45", 11434, &[("GET /api/tags ", "200 OK", TAGS)]);
+    let dir = workdir("quant", Some("127.0.0.45\n"));
+    let outcome = run(&dir, &["--quant", "f16,unknown", "--min-quant", "Q8_0"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let models = fs::read_to_string(dir.join("llm_models.csv")).unwrap();
+    assert!(models.contains("llama3:8b-f16"), "{}", models);
+    assert_eq!(models.lines().count(), 2, "{}", models);
+    // The empty level passes --quant unknown but not --min-quant
+    assert!(outcome.stderr.contains("Model rows filtered by --quant: 1"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Model rows filtered by --min-quant: 1"), "{}", outcome.stderr);
+}
+
+#[test]
+fn skips_endpoints_without_a_match_when_required() {
+    serve_routes("127.0.0.46", 11434, &[("GET /api/tags ", "200 OK", TAGS_Q4)]);
+    let dir = workdir("quant-required", Some("127.0.0.46\n"));
+    let outcome = run(&dir, &["--min-quant", "q8_0", "--require-quant-match"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+
+    let endpoints = fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(!endpoints.contains("127.0.0.46"), "{}", endpoints);
+    assert!(outcome.stderr.contains("Endpoints without a matching model, skipped: 1"), "{}", outcome.stderr);
+}
+
+#[test]
+fn matches_families_by_prefix_in_either_field() {
+    serve_routes("127.0.0.47", 11434, &[("GET /api/tags ", "200 OK", TAGS)]);
+    let dir = workdir("family", Some("127.0.0.47\n"));
+    let outcome = run(&dir, &["--family", "QWEN,gemma", "--require-family"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    // mystery names its family only in `families`
+    let models = fs::read_to_string(dir.join("llm_models.csv")).unwrap();
+    assert!(models.contains("mystery"), "{}", models);
+    assert_eq!(models.lines().count(), 2, "{}", models);
+    assert!(outcome.stderr.contains("Model rows filtered by --family: 2"), "{}", outcome.stderr);
+
+    // With --min-quant as well, no model passes both
+    let dir = workdir("family-and-quant", Some("127.0.0.47\n"));
+    let outcome = run(&dir, &["--family", "qwen,llama", "--min-quant", "F32", "--require-family"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Endpoints without a matching model, skipped: 1"), "{}", outcome.stderr);
+}
@@ -0,0 +1,37 @@
+//! The `Model Count` and `Total Size (GB)` columns of ollama_endpoints.csv.
+
+mod common;
+
+use common::{field, run, serve_routes, workdir};
+
+#[test]
+fn sums_sizes_before_rounding() {
+    // 0.00 GB each when rounded on their own, 0.01 GB together
+    const TAGS: &str = concat!(
+        r#"{"models":["#,
+        r#"{"name":"tiny:a","modified_at":"2024-05-01T10:00:00Z","size":5000000,"digest":"365c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1"},"#,
+        r#"{"name":"tiny:b","modified_at":"2024-05-01T10:00:00Z","size":5000000,"digest":"365c0bd3c000a25d28ddbf732fe1c6add414de7275464c4e4d1c3b5fcb5d8ad1"}"#,
+        r#"]}"#
+    );
+    serve_routes("127.0.0.43", 11434, &[("GET /api/tags ", "200 OK", TAGS)]);
+    let dir = workdir("model-total", Some("127.0.0.43\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    assert_eq!(field(&endpoints, "127.0.0.43", "Model Count"), "2");
+    assert_eq!(field(&endpoints, "127.0.0.43", "Total Size (GB)"), "0.01");
+    assert!(outcome.stderr.contains("(2 models, 0.01 GB)"), "{}", outcome.stderr);
+}
+
+#[test]
+fn records_zero_for_an_empty_model_list() {
+    serve_routes("127.0.0.44", 11434, &[("GET /api/tags ", "200 OK", r#"{"models":[]}"#)]);
+    let dir = workdir("model-total-empty", Some("127.0.0.44\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    assert_eq!(field(&endpoints, "127.0.0.44", "Model Count"), "0");
+    assert_eq!(field(&endpoints, "127.0.0.44", "Total Size (GB)"), "0.00");
+}
@@ -0,0 +1,60 @@
+//! `--output-dir` and `--run-dir`.
+
+mod common;
+
+use std::fs;
+
+use common::{field, run, serve_ollama, workdir};
+
+#[test]
+fn writes_every_output_under_the_output_dir() {
+    serve_ollama("127.0.5.20");
+    let dir = workdir("output-dir", Some("127.0.5.20\n"));
+    let outcome = run(&dir, &["--output-dir", "results/nested", "--errors-csv"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Output directory: results/nested"), "{}", outcome.stderr);
+
+    let out = dir.join("results/nested");
+    assert_eq!(field(&out.join("ollama_endpoints.csv"), "127.0.5.20", "Status Code"), "200");
+    for name in ["llm_models.csv", "metrics.json", "errors.csv"] {
+        assert!(out.join(name).exists(), "no {}", name);
+    }
+    let run_files = fs::read_dir(&out).unwrap().filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("run-")).count();
+    assert_eq!(run_files, 1);
+    for name in ["ollama_endpoints.csv", "llm_models.csv", "metrics.json"] {
+        assert!(!dir.join(name).exists(), "{} in the working directory", name);
+    }
+}
+
+#[test]
+fn gives_each_run_a_folder_and_points_latest_at_the_newest() {
+    serve_ollama("127.0.5.21");
+    let dir = workdir("run-dir", Some("127.0.5.21\n"));
+    for _ in 0..2 {
+        let outcome = run(&dir, &["--output-dir", "results", "--run-dir"], "y\n");
+        assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    }
+
+    let results = dir.join("results");
+    let mut runs: Vec<_> = fs::read_dir(&results)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir() && path.file_name().unwrap() != "latest")
+        .collect();
+    runs.sort();
+    assert_eq!(runs.len(), 2, "{:?}", runs);
+    for run in &runs {
+        assert_eq!(field(&run.join("ollama_endpoints.csv"), "127.0.5.21", "Status Code"), "200");
+    }
+    // Named by start time, so the second run sorts last
+    assert_eq!(fs::canonicalize(results.join("latest")).unwrap(), fs::canonicalize(&runs[1]).unwrap());
+}
+
+#[test]
+fn fails_before_scanning_when_the_output_dir_cannot_be_made() {
+    let dir = workdir("output-dir-blocked", Some("127.0.5.22\n"));
+    fs::write(dir.join("results"), "").unwrap();
+    let outcome = run(&dir, &["--output-dir", "results", "--run-dir"], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Error: Failed to create results"), "{}", outcome.stderr);
+}
@@ -0,0 +1,40 @@
+//! Two scans started in the same directory.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{field, run, serve_ollama, spawn, workdir};
+
+#[test]
+fn a_second_scan_refuses_or_moves_aside() {
+    serve_ollama("127.0.5.19");
+    let dir = workdir("output-lock", Some("127.0.5.19\n"));
+    std::fs::write(dir.join("feed.txt"), "").unwrap();
+    // Follows an empty feed until stopped, holding the directory all along
+    let mut first = spawn(&dir, &["--watch", "feed.txt"], "y\n");
+    let lock = dir.join("ollama-finder.lock");
+    let started = Instant::now();
+    while std::fs::read_to_string(&lock).map_or(true, |pid| pid.trim() != first.id().to_string()) {
+        assert!(started.elapsed() < Duration::from_secs(10), "the first scan never took the lock");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let refused = run(&dir, &[], "y\n");
+    assert_eq!(refused.code, 1, "{}", refused.stderr);
+    assert!(refused.stderr.contains(&format!("Another scan (PID {}) is writing the output files", first.id())), "{}", refused.stderr);
+    assert!(refused.stderr.contains("--force-suffix"), "{}", refused.stderr);
+
+    let aside = run(&dir, &["--force-suffix"], "y\n");
+    assert_eq!(aside.code, 0, "{}", aside.stderr);
+    assert!(aside.stderr.contains("Output: ollama_endpoints-2.csv, llm_models-2.csv"), "{}", aside.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints-2.csv"), "127.0.5.19", "Status Code"), "200");
+    assert!(!std::fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap().contains("127.0.5.19"));
+
+    let _ = first.kill();
+    let _ = first.wait();
+    // Killed, not stopped: the OS released the lock all the same
+    let after = run(&dir, &[], "y\n");
+    assert_eq!(after.code, 0, "{}", after.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.5.19", "Status Code"), "200");
+}
@@ -0,0 +1,22 @@
+//! The progress line printed in place of the bar when the console is not a
+//! terminal, as under the test harness.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{run, serve_ollama_with, workdir};
+
+#[test]
+fn prints_a_fixed_format_line_each_interval() {
+    serve_ollama_with("127.0.5.29", Duration::from_millis(700), Arc::default());
+    let dir = workdir("plain-progress", Some("127.0.5.29\n"));
+    let outcome = run(&dir, &["--progress-interval", "200ms"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let line = regex::Regex::new(r"(?m)^\d{4}-\d\d-\d\dT\d\d:\d\d:\d\dZ progress scanned=[01]/1 percent=(0|100)\.0 rate=\d+\.\d/s hits=[01] errors=0$").unwrap();
+    assert!(line.find_iter(&outcome.stderr).count() >= 2, "{}", outcome.stderr);
+    // Nothing meant for redrawing a terminal
+    assert!(!outcome.stderr.contains('\x1b'), "{:?}", outcome.stderr);
+}
@@ -0,0 +1,36 @@
+//! `--port-profile common` and `--all-ports`.
+
+mod common;
+
+use common::{run, serve_routes, workdir, TAGS};
+
+fn endpoints(dir: &std::path::Path) -> Vec<String> {
+    let mut reader = csv::Reader::from_path(dir.join("ollama_endpoints.csv")).unwrap();
+    reader.records().map(|row| row.unwrap()[0].to_string()).collect()
+}
+
+#[test]
+fn stops_at_the_first_port_that_answers() {
+    serve_routes("127.0.0.55", 11435, &[("GET /api/tags ", "200 OK", TAGS)]);
+    serve_routes("127.0.0.55", 8080, &[("GET /api/tags ", "200 OK", TAGS)]);
+    let dir = workdir("port-profile", Some("127.0.0.55\n"));
+    let outcome = run(&dir, &["--port-profile", "common"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    assert_eq!(endpoints(&dir), ["http://127.0.0.55:11435"]);
+    assert!(outcome.stderr.contains("Ollama ports: 11434, 11435, 8080, 80 (until one answers)"), "{}", outcome.stderr);
+}
+
+#[test]
+fn probes_every_port_with_all_ports() {
+    serve_routes("127.0.0.56", 11435, &[("GET /api/tags ", "200 OK", TAGS)]);
+    serve_routes("127.0.0.56", 8080, &[("GET /api/tags ", "404 Not Found", "")]);
+    serve_routes("127.0.0.56", 80, &[("GET /api/tags ", "200 OK", TAGS)]);
+    let dir = workdir("port-profile-all", Some("127.0.0.56\n"));
+    let outcome = run(&dir, &["--port-profile", "common", "--all-ports"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    // The 404 on 8080 is not taken for Ollama
+    assert_eq!(endpoints(&dir), ["http://127.0.0.56:11435", "http://127.0.0.56:80"]);
+    assert!(outcome.stderr.contains("Ports probed: 4"), "{}", outcome.stderr);
+}
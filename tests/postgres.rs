@@ -0,0 +1,91 @@
+//! `--pg`. The upsert tests need a database to write to: they run when
+//! POF_TEST_PG_URL names one (e.g. postgres://postgres@localhost/postgres),
+//! each in a schema of its own, and pass without doing anything otherwise.
+
+#![cfg(feature = "postgres")]
+
+mod common;
+
+use common::{field, free_port, run, serve_ollama, workdir};
+
+/// Runs `sql` in a fresh schema of the test database and returns a URL
+/// working in that schema, or None without POF_TEST_PG_URL.
+fn test_schema(name: &str, sql: &str) -> Option<(String, tokio_postgres::Client, tokio::runtime::Runtime)> {
+    let Ok(url) = std::env::var("POF_TEST_PG_URL") else {
+        eprintln!("POF_TEST_PG_URL is not set; skipping");
+        return None;
+    };
+    let schema = format!("pof_{}_{}", name, std::process::id());
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let client = runtime.block_on(async {
+        let (client, connection) = tokio_postgres::connect(&url, tokio_postgres::NoTls).await.unwrap();
+        tokio::spawn(connection);
+        client
+            .batch_execute(&format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}; SET search_path TO {0}; {1}", schema, sql))
+            .await
+            .unwrap();
+        client
+    });
+    let separator = if url.contains('?') { '&' } else { '?' };
+    Some((format!("{}{}options=-csearch_path%3D{}", url, separator, schema), client, runtime))
+}
+
+#[test]
+fn refuses_to_scan_without_the_database() {
+    let dir = workdir("pg-unreachable", Some("127.0.0.1/32\n"));
+    let url = format!("postgres://scanner@127.0.0.1:{}/findings", free_port());
+    let outcome = run(&dir, &["--pg", &url], "y\n");
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("Failed to connect to PostgreSQL"), "{}", outcome.stderr);
+}
+
+#[test]
+fn upserts_findings_keeping_when_they_were_first_seen() {
+    let Some((url, client, runtime)) = test_schema("upsert", "") else { return };
+    serve_ollama("127.0.5.25");
+    let dir = workdir("pg-upsert", Some("127.0.5.25\n"));
+    for _ in 0..2 {
+        let outcome = run(&dir, &["--pg", &url], "y\n");
+        assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+    }
+    assert!(!dir.join("pg_fallback.csv").exists());
+
+    let (endpoints, models) = runtime.block_on(async {
+        let endpoints = client.query("SELECT port, model_count, first_seen < last_seen FROM endpoints WHERE ip = '127.0.5.25'", &[]).await.unwrap();
+        let models = client.query("SELECT name, digest, size_bytes FROM models", &[]).await.unwrap();
+        (endpoints, models)
+    });
+    assert_eq!(endpoints.len(), 1);
+    assert_eq!(endpoints[0].get::<_, i32>(0), 11434);
+    assert_eq!(endpoints[0].get::<_, i32>(1), 1);
+    assert!(endpoints[0].get::<_, bool>(2), "last_seen not moved on by the second run");
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].get::<_, String>(0), "llama3:8b");
+    assert!(models[0].get::<_, String>(1).starts_with("365c0bd3"));
+    assert_eq!(models[0].get::<_, i64>(2), 4661224676);
+}
+
+#[test]
+fn checks_tables_created_beforehand() {
+    let Some((url, _client, _runtime)) = test_schema("precreated", "CREATE TABLE endpoints (ip TEXT, port INTEGER)") else { return };
+    let dir = workdir("pg-precreated", Some("127.0.0.1/32\n"));
+    let outcome = run(&dir, &["--pg", &url], "y\n");
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("The existing endpoints table has no base_url, status"), "{}", outcome.stderr);
+}
+
+#[test]
+fn spills_what_the_database_refuses_to_the_fallback_csv() {
+    // Takes the schema, but no row
+    let schema = "CREATE TABLE endpoints (ip TEXT, port INTEGER CHECK (port < 0), base_url TEXT, status INTEGER, location TEXT, source TEXT, tag TEXT, run_id TEXT, latency_ms BIGINT, model_count INTEGER, alerts TEXT, first_seen TIMESTAMPTZ, last_seen TIMESTAMPTZ, PRIMARY KEY (ip, port))";
+    let Some((url, _client, _runtime)) = test_schema("refusing", schema) else { return };
+    serve_ollama("127.0.5.26");
+    let dir = workdir("pg-refusing", Some("127.0.5.26\n"));
+    let outcome = run(&dir, &["--pg", &url], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("keeping the findings to retry"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("1 findings written to pg_fallback.csv instead"), "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("pg_fallback.csv"), "2", "IP"), "127.0.5.26");
+    assert!(field(&dir.join("pg_fallback.csv"), "2", "Models").contains(r#""name":"llama3:8b""#));
+}
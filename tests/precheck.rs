@@ -0,0 +1,50 @@
+//! `--precheck icmp` and `icmp-soft`. Loopback addresses answer pings;
+//! 10.255.255.1 is private and unrouted, so its pings go unanswered.
+
+mod common;
+
+use common::{field, run, serve_ollama, workdir, Outcome};
+
+/// Whether this machine lets the scanner send pings at all.
+fn pings_allowed(outcome: &Outcome) -> bool {
+    !outcome.stderr.contains("scanning without the ping pre-check")
+}
+
+#[test]
+fn skips_hosts_that_do_not_answer_a_ping() {
+    serve_ollama("127.0.6.240");
+    let dir = workdir("precheck", Some("10.255.255.1\n127.0.6.240\n"));
+    let outcome = run(&dir, &["--precheck", "icmp", "--ping-timeout", "300ms"], "y\n");
+    if !pings_allowed(&outcome) {
+        return;
+    }
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Pre-check: ping each host (300ms timeout), skipping those that do not answer"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("HTTP probes avoided by the ping pre-check: 1 (1 hosts did not answer"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("IPs attempted: 1\n"), "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.6.240", "Status Code"), "200");
+    let metrics = std::fs::read_to_string(dir.join("metrics.json")).unwrap();
+    assert!(metrics.contains("\"skipped\": 1,\n    \"probes_avoided\": 1,"), "{}", metrics);
+}
+
+#[test]
+fn probes_silent_hosts_last_in_soft_mode() {
+    serve_ollama("127.0.6.241");
+    let dir = workdir("precheck-soft", Some("10.255.255.1\n127.0.6.241\n"));
+    let outcome = run(&dir, &["--precheck", "icmp-soft", "--ping-timeout", "300ms"], "y\n");
+    if !pings_allowed(&outcome) {
+        return;
+    }
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("IPs attempted: 2\n"), "{}", outcome.stderr);
+    assert!(
+        outcome.stderr.contains("Probed without a ping answer: 1 (0 of them Ollama endpoints; --precheck icmp would have missed 0.0% of the endpoints found)"),
+        "{}",
+        outcome.stderr
+    );
+    // The answering host was probed first, though listed second
+    let found = outcome.stderr.find("127.0.6.241:11434").unwrap();
+    let refused = outcome.stderr.find("10.255.255.1").unwrap_or(usize::MAX);
+    assert!(found < refused, "{}", outcome.stderr);
+}
+
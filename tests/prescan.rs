@@ -0,0 +1,58 @@
+//! `--prescan masscan` with a stand-in masscan script.
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use common::{field, run, serve_ollama, workdir};
+
+/// Writes an executable `masscan` into `dir` that answers `--version`,
+/// records its arguments in masscan-args.txt and otherwise runs `body`.
+fn fake_masscan(dir: &Path, body: &str) {
+    let script = format!("#!/bin/sh\n[ \"$1\" = --version ] && exit 0\necho \"$@\" >> masscan-args.txt\n{}\n", body);
+    let path = dir.join("masscan");
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn open(ip: &str) -> String {
+    format!(r#"{{   "ip": "{}",   "timestamp": "1700000000", "ports": [ {{"port": 11434, "proto": "tcp", "status": "open", "reason": "syn-ack", "ttl": 64}} ] }}"#, ip)
+}
+
+#[test]
+fn probes_only_what_masscan_reports_open() {
+    serve_ollama("127.0.6.200");
+    let dir = workdir("prescan", Some("127.0.6.192/28\n"));
+    // The broadcast address and a host outside the range are left alone
+    let output = ["[".to_string(), open("127.0.6.200"), ",".to_string(), open("127.0.6.201"), open("127.0.6.207"), open("127.0.9.9"), "]".to_string()];
+    fake_masscan(&dir, &format!("cat <<'EOF'\n{}\nEOF", output.join("\n")));
+    let outcome = run(&dir, &["--prescan", "masscan", "--masscan-path", "./masscan", "--masscan-rate", "500"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Pre-scan: ./masscan at 500 packets/s"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("masscan reported 2 open hosts in CIDR (ip-ranges.txt:1)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("IPs attempted: 2"), "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.6.200", "Status Code"), "200");
+    let args = std::fs::read_to_string(dir.join("masscan-args.txt")).unwrap();
+    assert_eq!(args.trim(), "127.0.6.192/28 -p11434 --rate 500 --output-format json --output-filename -");
+}
+
+#[test]
+fn says_what_masscan_needs_without_privileges() {
+    let dir = workdir("prescan-unprivileged", Some("127.0.6.224/30\n"));
+    fake_masscan(&dir, "echo 'FAIL: permission denied' >&2\necho ' [hint] need to sudo or run as root or something' >&2\nexit 1");
+    let outcome = run(&dir, &["--prescan", "masscan", "--masscan-path", "./masscan"], "y\n");
+    assert_eq!(outcome.code, 3, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("masscan needs raw socket access (CAP_NET_RAW and CAP_NET_ADMIN)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("sudo setcap cap_net_raw,cap_net_admin+eip ./masscan"), "{}", outcome.stderr);
+}
+
+#[test]
+fn falls_back_to_connect_scanning_without_masscan() {
+    serve_ollama("127.0.6.228");
+    let dir = workdir("prescan-missing", Some("127.0.6.228\n"));
+    let outcome = run(&dir, &["--prescan", "masscan", "--masscan-path", "./no-such-masscan"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("./no-such-masscan is not installed; falling back to the built-in connect scan"), "{}", outcome.stderr);
+}
@@ -0,0 +1,33 @@
+//! The `Provider` column and the feeds behind it.
+
+mod common;
+
+use common::{field, run, serve_ollama, workdir};
+use std::fs;
+
+#[test]
+fn names_the_provider_from_a_saved_feed() {
+    serve_ollama("127.0.0.57");
+    serve_ollama("127.0.1.57");
+    let dir = workdir("provider", Some("127.0.0.57\n127.0.1.57\n"));
+    fs::create_dir(dir.join("provider-feeds")).unwrap();
+    fs::write(
+        dir.join("provider-feeds/aws.json"),
+        r#"{"prefixes":[{"ip_prefix":"127.0.0.0/24","service":"EC2"}],"ipv6_prefixes":[]}"#,
+    ).unwrap();
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    assert_eq!(field(&endpoints, "127.0.0.57", "Provider"), "AWS");
+    assert_eq!(field(&endpoints, "127.0.1.57", "Provider"), "unknown");
+    assert!(outcome.stderr.contains("Provider: AWS"), "{}", outcome.stderr);
+
+    let run_id = field(&endpoints, "127.0.0.57", "Run ID");
+    let info: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join(format!("run-{}.json", run_id))).unwrap()).unwrap();
+    let feeds = info["provider_feeds"].as_array().unwrap();
+    assert_eq!(feeds[0]["provider"], "AWS");
+    assert_eq!(feeds[0]["networks"], 1);
+    assert!(feeds[0]["fetched_at"].is_string(), "{}", info);
+    assert!(feeds.iter().any(|feed| feed["provider"] == "Hetzner" && feed["source"] == "bundled" && feed["fetched_at"].is_null()));
+}
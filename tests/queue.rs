@@ -0,0 +1,207 @@
+//! `--queue-push` / `--queue-pull` against a small in-process Redis stand-in
+//! that speaks just enough RESP for the commands the queue uses.
+
+#![cfg(feature = "redis")]
+
+mod common;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use common::{run, serve_ollama, workdir};
+
+#[derive(Default)]
+struct Store {
+    lists: HashMap<Vec<u8>, VecDeque<Vec<u8>>>,
+    keys: HashMap<Vec<u8>, Instant>,
+    /// Answer LREM with an error, as if the server went away mid-run
+    fail_lrem: bool,
+}
+
+enum Reply {
+    Ok,
+    Int(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Vec<u8>>),
+    Error(&'static str),
+}
+
+impl Reply {
+    fn encode(self) -> Vec<u8> {
+        fn bulk(v: &[u8]) -> Vec<u8> {
+            let mut out = format!("${}\r\n", v.len()).into_bytes();
+            out.extend_from_slice(v);
+            out.extend_from_slice(b"\r\n");
+            out
+        }
+        match self {
+            Reply::Ok => b"+OK\r\n".to_vec(),
+            Reply::Int(n) => format!(":{}\r\n", n).into_bytes(),
+            Reply::Bulk(None) => b"$-1\r\n".to_vec(),
+            Reply::Bulk(Some(v)) => bulk(&v),
+            Reply::Array(items) => {
+                let mut out = format!("*{}\r\n", items.len()).into_bytes();
+                for item in items {
+                    out.extend(bulk(&item));
+                }
+                out
+            }
+            Reply::Error(e) => format!("-{}\r\n", e).into_bytes(),
+        }
+    }
+}
+
+impl Store {
+    fn live(&mut self, key: &[u8]) -> bool {
+        match self.keys.get(key) {
+            Some(expires) if *expires > Instant::now() => true,
+            Some(_) => {
+                self.keys.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn lrem(&mut self, key: &[u8], value: &[u8]) -> i64 {
+        let list = self.lists.entry(key.to_vec()).or_default();
+        match list.iter().position(|v| v == value) {
+            Some(i) => {
+                list.remove(i);
+                1
+            }
+            None => 0,
+        }
+    }
+
+    fn len(&self, key: &[u8]) -> usize {
+        self.lists.get(key).map_or(0, |l| l.len())
+    }
+
+    /// Everything but BRPOPLPUSH's wait. `None` means "nothing to pop yet".
+    fn execute(&mut self, args: &[Vec<u8>]) -> Option<Reply> {
+        let command = String::from_utf8_lossy(&args[0]).to_uppercase();
+        let seconds = |i: usize| String::from_utf8_lossy(&args[i]).parse::<u64>().unwrap();
+        Some(match command.as_str() {
+            "LPUSH" => {
+                let list = self.lists.entry(args[1].clone()).or_default();
+                for value in &args[2..] {
+                    list.push_front(value.clone());
+                }
+                Reply::Int(list.len() as i64)
+            }
+            "BRPOPLPUSH" => {
+                let value = self.lists.get_mut(&args[1]).and_then(|l| l.pop_back())?;
+                self.lists.entry(args[2].clone()).or_default().push_front(value.clone());
+                Reply::Bulk(Some(value))
+            }
+            "LLEN" => Reply::Int(self.len(&args[1]) as i64),
+            "LRANGE" => Reply::Array(self.lists.get(&args[1]).map(|l| l.iter().cloned().collect()).unwrap_or_default()),
+            "LREM" if self.fail_lrem => Reply::Error("ERR injected failure"),
+            "LREM" => Reply::Int(self.lrem(&args[1], &args[3])),
+            "SETEX" => {
+                self.keys.insert(args[1].clone(), Instant::now() + Duration::from_secs(seconds(2)));
+                Reply::Ok
+            }
+            "EXPIRE" | "EXISTS" => Reply::Int(self.live(&args[1]) as i64),
+            "DEL" => Reply::Int(self.keys.remove(&args[1]).is_some() as i64),
+            "SCRIPT" => Reply::Bulk(Some(b"requeue".to_vec())),
+            // The re-queue script is the only one the queue runs
+            "EVAL" | "EVALSHA" => {
+                let moved = self.lrem(&args[3], &args[5]);
+                if moved > 0 {
+                    self.lists.entry(args[4].clone()).or_default().push_back(args[5].clone());
+                }
+                Reply::Int(moved)
+            }
+            _ => Reply::Error("ERR unknown command"),
+        })
+    }
+}
+
+fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0u8; len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Some(args)
+}
+
+fn serve_redis(store: Arc<Mutex<Store>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("redis://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                while let Some(args) = read_command(&mut reader) {
+                    let deadline = Instant::now() + Duration::from_secs(1);
+                    let reply = loop {
+                        if let Some(reply) = store.lock().unwrap().execute(&args) {
+                            break reply;
+                        }
+                        if Instant::now() >= deadline {
+                            break Reply::Bulk(None);
+                        }
+                        std::thread::sleep(Duration::from_millis(20));
+                    };
+                    if writer.write_all(&reply.encode()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    url
+}
+
+#[test]
+fn workers_drain_the_queue() {
+    serve_ollama("127.0.0.4");
+    let store = Arc::new(Mutex::new(Store::default()));
+    let url = serve_redis(store.clone());
+
+    let dir = workdir("queue-push", Some("127.0.0.4\n127.0.0.5\n"));
+    assert_eq!(run(&dir, &["--queue-push", &url], "y\n").code, 0);
+    assert_eq!(store.lock().unwrap().len(b"ollama-finder:queue"), 2);
+
+    let dir = workdir("queue-pull", None);
+    let outcome = run(&dir, &["--queue-pull", &url], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let endpoints = std::fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(endpoints.contains("http://127.0.0.4:11434"));
+
+    let store = store.lock().unwrap();
+    assert_eq!(store.len(b"ollama-finder:queue"), 0);
+    assert_eq!(store.len(b"ollama-finder:processing"), 0);
+}
+
+#[test]
+fn a_queue_error_keeps_findings_from_finished_batches() {
+    serve_ollama("127.0.0.6");
+    let store = Arc::new(Mutex::new(Store { fail_lrem: true, ..Store::default() }));
+    let url = serve_redis(store.clone());
+
+    let dir = workdir("queue-push-failing", Some("127.0.0.6\n"));
+    assert_eq!(run(&dir, &["--queue-push", &url], "y\n").code, 0);
+
+    let dir = workdir("queue-pull-failing", None);
+    let outcome = run(&dir, &["--queue-pull", &url], "y\n");
+    assert!(outcome.stderr.contains("Queue error"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Found 1 Ollama endpoints"), "{}", outcome.stderr);
+    assert_eq!(outcome.code, 3);
+}
@@ -0,0 +1,36 @@
+//! `--report-status` and `--silence-404`.
+
+mod common;
+
+use common::{field, run, serve_ollama, serve_routes, workdir};
+
+#[test]
+fn silenced_statuses_are_only_counted() {
+    serve_ollama("127.0.0.82");
+    serve_routes("127.0.0.83", 11434, &[]);
+    serve_routes("127.0.0.84", 11434, &[]);
+    serve_routes("127.0.0.85", 11434, &[("GET /api/tags ", "401 Unauthorized", "")]);
+    let dir = workdir("report-status", Some("127.0.0.82-127.0.0.85\n"));
+
+    let outcome = run(&dir, &["--silence-404", "--report-status", "401,403,404"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("Possible Ollama server (404)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Possible Ollama server (401): http://127.0.0.85:11434"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("2 hosts answered 404"), "{}", outcome.stderr);
+    // Genuine hits are written all the same
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.0.82", "Status Code"), "200");
+    assert!(outcome.stderr.contains("Found Ollama Server"), "{}", outcome.stderr);
+}
+
+#[test]
+fn reports_403_and_404_by_default() {
+    serve_routes("127.0.0.86", 11434, &[]);
+    serve_routes("127.0.0.87", 11434, &[("GET /api/tags ", "401 Unauthorized", "")]);
+    let dir = workdir("report-status-default", Some("127.0.0.86-127.0.0.87\n"));
+
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Possible Ollama server (404): http://127.0.0.86:11434"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("Possible Ollama server (401)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("1 host answered 401"), "{}", outcome.stderr);
+}
@@ -0,0 +1,57 @@
+//! Stops a scan part way with SIGTERM, resumes it with `--resume` and checks
+//! that every host was probed exactly once across both runs.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{finish, run, serve_ollama_with, spawn, workdir};
+
+const HOSTS: u8 = 48;
+
+#[test]
+fn resume_probes_every_host_exactly_once() {
+    let probes = Arc::new(Mutex::new(Vec::new()));
+    for host in 1..=HOSTS {
+        serve_ollama_with(&format!("127.0.1.{}", host), Duration::from_millis(250), probes.clone());
+    }
+    // Two ranges, so the resumed run skips a finished one and continues inside the other
+    let dir = workdir("resume", Some("127.0.1.1-127.0.1.8\n127.0.1.9-127.0.1.48\n"));
+    let args = ["--concurrency-fixed", "4"];
+
+    let child = spawn(&dir, &args, "y\n");
+    std::thread::sleep(Duration::from_millis(1500));
+    Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    let stopped = finish(child);
+    assert_eq!(stopped.code, 3, "{}", stopped.stderr);
+    assert!(dir.join("scan-checkpoint.json").exists());
+    let first_run = probes.lock().unwrap().len();
+    assert!(first_run > 8 && first_run < HOSTS as usize, "stopped after {} probes", first_run);
+
+    let resumed = run(&dir, &["--resume", "--concurrency-fixed", "4"], "y\n");
+    assert_eq!(resumed.code, 0, "{}", resumed.stderr);
+    assert!(!dir.join("scan-checkpoint.json").exists());
+
+    let mut probed = probes.lock().unwrap().clone();
+    probed.sort();
+    let mut expected: Vec<String> = (1..=HOSTS).map(|host| format!("127.0.1.{}", host)).collect();
+    expected.sort();
+    assert_eq!(probed, expected);
+}
+
+#[test]
+fn resume_rejects_changed_targets() {
+    let dir = workdir("resume-changed", Some("127.0.3.1-127.0.3.4\n"));
+    std::fs::write(
+        dir.join("scan-checkpoint.json"),
+        r#"{"fingerprint":"0000000000000000","seed":1,"range":0,"offset":2,"done":[]}"#,
+    )
+    .unwrap();
+    let outcome = run(&dir, &["--resume"], "y\n");
+    assert_eq!(outcome.code, 1);
+    assert!(outcome.stderr.contains("different targets"), "{}", outcome.stderr);
+}
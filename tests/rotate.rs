@@ -0,0 +1,48 @@
+//! `--rotate` and `--rotate-size` splitting the output files.
+
+mod common;
+
+use common::{run, serve_ollama, workdir};
+use std::fs;
+
+#[test]
+fn rolls_over_to_numbered_files_with_a_header_each() {
+    for ip in ["127.0.0.34", "127.0.0.35", "127.0.0.36"] {
+        serve_ollama(ip);
+    }
+    let dir = workdir("rotate-size", Some("127.0.0.34-127.0.0.36\n"));
+    // Smaller than one header and row, so every row starts a new file
+    let outcome = run(&dir, &["--rotate-size", "100"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Output: ollama_endpoints.csv, llm_models.csv (rotating every 100 bytes)"), "{}", outcome.stderr);
+
+    for (base, header) in [("ollama_endpoints", "IP:Port,Tags URL,"), ("llm_models", "IP:Port,Model Name,")] {
+        let files = [format!("{}.csv", base), format!("{}.1.csv", base), format!("{}.2.csv", base)];
+        let mut ips = Vec::new();
+        for file in &files {
+            let text = fs::read_to_string(dir.join(file)).unwrap_or_else(|_| panic!("no {}", file));
+            let lines: Vec<&str> = text.lines().collect();
+            assert!(lines[0].starts_with(header), "{}: {}", file, text);
+            assert_eq!(lines.len(), 2, "{}: {}", file, text);
+            ips.push(lines[1].trim_start_matches("http://").split(':').next().unwrap().to_string());
+        }
+        ips.sort();
+        assert_eq!(ips, ["127.0.0.34", "127.0.0.35", "127.0.0.36"]);
+        assert!(!dir.join(format!("{}.3.csv", base)).exists());
+    }
+}
+
+#[test]
+fn names_daily_files_after_the_utc_date() {
+    serve_ollama("127.0.0.37");
+    let dir = workdir("rotate-daily", Some("127.0.0.37\n"));
+    let outcome = run(&dir, &["--rotate", "daily"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let today = chrono::Utc::now().format("%Y-%m-%d");
+    let endpoints = format!("ollama_endpoints-{}.csv", today);
+    assert!(outcome.stderr.contains(&format!("Output: {}", endpoints)), "{}", outcome.stderr);
+    assert!(fs::read_to_string(dir.join(&endpoints)).unwrap().contains("127.0.0.37:11434"));
+    assert!(dir.join(format!("llm_models-{}.csv", today)).exists());
+    assert!(!dir.join("ollama_endpoints.csv").exists());
+}
@@ -0,0 +1,33 @@
+//! The `run-<run id>.json` file describing each run.
+
+mod common;
+
+use common::{field, run, serve_ollama, workdir};
+use std::fs;
+
+#[test]
+fn records_settings_and_outcome_under_the_run_id() {
+    serve_ollama("127.0.0.40");
+    let dir = workdir("runinfo", Some("127.0.0.40\n"));
+    let outcome = run(&dir, &["--burst", "20", "--also", "vllm"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let run_id = field(&dir.join("ollama_endpoints.csv"), "127.0.0.40", "Run ID");
+    let text = fs::read_to_string(dir.join(format!("run-{}.json", run_id))).unwrap();
+    let info: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["arguments"], serde_json::json!(["--stream", "--burst", "20", "--also", "vllm"]));
+    // FNV-1a 64 of "127.0.0.40\n"
+    assert_eq!(info["inputs"], serde_json::json!([{"path": "ip-ranges.txt", "fnv1a": format!("{:016x}", fnv1a(b"127.0.0.40\n"))}]));
+    assert_eq!(info["total_targets"], 1);
+    assert_eq!(info["ports"], serde_json::json!([8000, 11434]));
+    assert_eq!(info["rate_limit"]["burst"], 20);
+    assert_eq!(info["scanned"], 1);
+    assert_eq!(info["hits"], 1);
+    assert_eq!(info["termination"], "completed");
+    assert!(info["started_at"].as_str().unwrap() <= info["ended_at"].as_str().unwrap(), "{}", text);
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,115 @@
+//! `--s3-upload` against a stand-in for an S3-compatible store.
+#![cfg(feature = "s3")]
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use common::{run, serve_ollama, workdir};
+
+struct Request {
+    method: String,
+    target: String,
+    authorization: String,
+    body: Vec<u8>,
+}
+
+/// Keeps every request; fails the first one with a 503, starts multipart
+/// uploads as "up-1" and answers each part with an ETag naming it.
+fn serve_s3() -> (u16, Arc<Mutex<Vec<Request>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut pending = Vec::new();
+            let mut buf = [0u8; 4096];
+            let head_end = loop {
+                if let Some(end) = pending.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break end + 4;
+                }
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break 0,
+                    Ok(n) => pending.extend_from_slice(&buf[..n]),
+                }
+            };
+            let head = String::from_utf8_lossy(&pending[..head_end]).into_owned();
+            let header = |name: &str| {
+                head.lines()
+                    .find_map(|line| line.split_once(':').filter(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.trim().to_string()))
+                    .unwrap_or_default()
+            };
+            let length: usize = header("content-length").parse().unwrap_or(0);
+            while pending.len() < head_end + length {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => pending.extend_from_slice(&buf[..n]),
+                }
+            }
+            let mut words = head.split(' ');
+            let request = Request {
+                method: words.next().unwrap_or_default().to_string(),
+                target: words.next().unwrap_or_default().to_string(),
+                authorization: header("authorization"),
+                body: pending[head_end..].to_vec(),
+            };
+            let mut seen = seen.lock().unwrap();
+            let (status, etag, body) = match (&request.method[..], &request.target) {
+                _ if seen.is_empty() => ("503 Slow Down", String::new(), String::new()),
+                ("POST", target) if target.ends_with("?uploads=") => ("200 OK", String::new(), "<InitiateMultipartUploadResult><UploadId>up-1</UploadId></InitiateMultipartUploadResult>".to_string()),
+                ("PUT", target) if target.contains("partNumber=") => {
+                    let number = target.split("partNumber=").nth(1).unwrap().split('&').next().unwrap().to_string();
+                    ("200 OK", format!("ETag: \"part-{}\"\r\n", number), String::new())
+                }
+                _ => ("200 OK", "ETag: \"whole\"\r\n".to_string(), String::new()),
+            };
+            seen.push(request);
+            let _ = write!(stream, "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", status, etag, body.len(), body);
+        }
+    });
+    (port, requests)
+}
+
+#[test]
+fn uploads_the_run_files_under_its_run_id() {
+    serve_ollama("127.0.5.24");
+    let (port, requests) = serve_s3();
+    let dir = workdir("s3-upload", Some("127.0.5.24\n"));
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+    std::env::set_var("AWS_REGION", "eu-west-1");
+    let endpoint = format!("http://127.0.0.1:{}", port);
+    // Small enough that ollama_endpoints.csv goes up in parts
+    let outcome = run(&dir, &["--s3-upload", "s3://scans/ollama/", "--s3-endpoint", &endpoint, "--s3-part-size", "300"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("503"), "{}", outcome.stderr);
+
+    let run_file = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .find(|name| name.starts_with("run-"))
+        .unwrap();
+    let run_id = run_file.trim_start_matches("run-").trim_end_matches(".json");
+    let prefix = format!("/scans/ollama/{}/", run_id);
+    for name in ["ollama_endpoints.csv", "llm_models.csv", run_file.as_str(), "metrics.json"] {
+        assert!(outcome.stderr.contains(&format!("{}{}{}", endpoint, prefix, name)), "no URL for {}: {}", name, outcome.stderr);
+    }
+
+    let requests = requests.lock().unwrap();
+    assert!(requests.iter().all(|r| r.authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDTEST/") && r.authorization.contains("/eu-west-1/s3/aws4_request")));
+    let endpoints = format!("{}ollama_endpoints.csv", prefix);
+    let mut parts = BTreeMap::new();
+    for request in requests.iter().filter(|r| r.method == "PUT" && r.target.starts_with(&format!("{}?partNumber=", endpoints))) {
+        let number: u32 = request.target.split("partNumber=").nth(1).unwrap().split('&').next().unwrap().parse().unwrap();
+        parts.insert(number, request.body.clone());
+    }
+    assert!(parts.len() > 1, "not a multipart upload");
+    assert_eq!(parts.into_values().flatten().collect::<Vec<u8>>(), std::fs::read(dir.join("ollama_endpoints.csv")).unwrap());
+    let completion = requests.iter().find(|r| r.method == "POST" && r.target == format!("{}?uploadId=up-1", endpoints)).expect("upload completed");
+    assert!(String::from_utf8_lossy(&completion.body).contains("<Part><PartNumber>1</PartNumber><ETag>\"part-1\"</ETag></Part>"));
+}
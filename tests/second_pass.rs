@@ -0,0 +1,28 @@
+//! The second pass over hosts whose probe timed out.
+
+mod common;
+
+use common::{field, run, serve_ollama_slow_once, workdir};
+use std::time::Duration;
+
+#[test]
+fn recovers_a_host_that_timed_out_in_the_sweep() {
+    serve_ollama_slow_once("127.0.0.60", Duration::from_millis(700));
+    let dir = workdir("second-pass", Some("127.0.0.60\n"));
+    let outcome = run(&dir, &["--read-timeout", "500ms"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.0.60", "Pass"), "2");
+    assert!(outcome.stderr.contains("Second pass: retrying 1 hosts"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Recovered on the second pass: 1 (1 hosts retried)"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("IPs attempted: 1"), "{}", outcome.stderr);
+}
+
+#[test]
+fn skips_the_second_pass_with_an_empty_queue() {
+    serve_ollama_slow_once("127.0.0.61", Duration::from_millis(700));
+    let dir = workdir("second-pass-off", Some("127.0.0.61\n"));
+    let outcome = run(&dir, &["--read-timeout", "500ms", "--retry-queue", "0"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("Second pass"), "{}", outcome.stderr);
+}
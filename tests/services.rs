@@ -0,0 +1,65 @@
+//! `--also` probes for LLM servers other than Ollama.
+
+mod common;
+
+use common::{run, serve_json, serve_ollama, workdir};
+
+const LM_STUDIO_MODELS: &str = r#"{"data":[{"id":"qwen2.5-7b-instruct","object":"model","owned_by":"organization_owner"}],"object":"list"}"#;
+
+#[test]
+fn finds_lm_studio_only_when_asked() {
+    serve_json("127.0.0.9", 1234, "/v1/models", "X-Powered-By: Express\r\n", LM_STUDIO_MODELS);
+
+    let dir = workdir("lmstudio-off", Some("127.0.0.9\n"));
+    assert_eq!(run(&dir, &[], "y\n").code, 2);
+    assert!(!dir.join("other_llm_endpoints.csv").exists());
+
+    let dir = workdir("lmstudio-on", Some("127.0.0.9\n"));
+    let outcome = run(&dir, &["--also", "lmstudio"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Found LM Studio Server"), "{}", outcome.stderr);
+    let others = std::fs::read_to_string(dir.join("other_llm_endpoints.csv")).unwrap();
+    assert!(others.contains("http://127.0.0.9:1234,LM Studio,http://127.0.0.9:1234/v1/models,200,qwen2.5-7b-instruct"), "{}", others);
+}
+
+#[test]
+fn finds_llama_server_by_its_props() {
+    let props = r#"{"default_generation_settings":{"n_ctx":4096},"total_slots":1,"model_path":"/models/phi-3-mini.Q4_K_M.gguf","build_info":"b4600-a1b2c3d"}"#;
+    serve_json("127.0.0.10", 8080, "/props", "", props);
+
+    let dir = workdir("llamacpp", Some("127.0.0.10\n"));
+    let outcome = run(&dir, &["--also", "llamacpp", "--also", "lmstudio"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let others = std::fs::read_to_string(dir.join("other_llm_endpoints.csv")).unwrap();
+    assert!(others.contains("http://127.0.0.10:8080,llama.cpp,http://127.0.0.10:8080/v1/models,200,phi-3-mini.Q4_K_M.gguf"), "{}", others);
+    assert!(others.trim_end().ends_with("b4600-a1b2c3d,unknown"), "{}", others);
+}
+
+#[test]
+fn labels_vllm_servers_apart_from_ollama() {
+    let models = r#"{"object":"list","data":[{"id":"Qwen/Qwen2.5-7B-Instruct","object":"model","owned_by":"vllm","max_model_len":32768}]}"#;
+    serve_json("127.0.0.11", 8000, "/v1/models", "", models);
+
+    let dir = workdir("vllm", Some("127.0.0.11\n"));
+    let outcome = run(&dir, &["--also", "vllm"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Found vLLM Server"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("Found Ollama Server"), "{}", outcome.stderr);
+    let others = std::fs::read_to_string(dir.join("other_llm_endpoints.csv")).unwrap();
+    assert!(others.contains("http://127.0.0.11:8000,vLLM,http://127.0.0.11:8000/v1/models,200,Qwen/Qwen2.5-7B-Instruct"), "{}", others);
+}
+
+#[test]
+fn links_open_webui_to_the_ollama_on_the_same_host() {
+    let config = r#"{"status":true,"name":"Open WebUI","version":"0.3.35","features":{"auth":false,"enable_signup":false}}"#;
+    serve_ollama("127.0.0.12");
+    serve_json("127.0.0.12", 3000, "/api/config", "", config);
+
+    let dir = workdir("openwebui", Some("127.0.0.12\n"));
+    let outcome = run(&dir, &["--also", "openwebui"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let others = std::fs::read_to_string(dir.join("other_llm_endpoints.csv")).unwrap();
+    assert!(others.contains("http://127.0.0.12:3000,Open WebUI,http://127.0.0.12:3000/api/config,200"), "{}", others);
+    let endpoints = std::fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(endpoints.contains("http://127.0.0.12:3000 (version 0.3.35; auth disabled)"), "{}", endpoints);
+}
@@ -0,0 +1,43 @@
+//! `--syn` against loopback addresses: an Ollama server, a port that accepts
+//! connections but is no Ollama, and hosts that refuse. Skipped without raw
+//! socket access.
+#![cfg(feature = "syn")]
+
+mod common;
+
+use std::net::TcpListener;
+
+use common::{field, run, serve_ollama, workdir, Outcome};
+
+fn privileged(outcome: &Outcome) -> bool {
+    !outcome.stderr.contains("--syn needs raw sockets")
+}
+
+#[test]
+fn probes_only_the_hosts_that_answer_the_syn() {
+    serve_ollama("127.0.7.5");
+    // Open, so it gets probed, but never answers HTTP
+    let _other = TcpListener::bind("127.0.7.6:11434").unwrap();
+    let dir = workdir("syn", Some("127.0.7.0/28\n"));
+    let outcome = run(&dir, &["--syn", "--syn-rate", "1000", "--syn-wait", "1s", "--read-timeout", "1s"], "y\n");
+    if !privileged(&outcome) {
+        return;
+    }
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Pre-scan: built-in SYN scan at 1000 packets/s, waiting 1s for late answers"), "{}", outcome.stderr);
+    assert!(
+        outcome.stderr.contains("The SYN scan reported 2 open hosts in CIDR (ip-ranges.txt:1); 12 ports refused, 0 unreachable"),
+        "{}",
+        outcome.stderr
+    );
+    assert!(outcome.stderr.contains("IPs attempted: 2\n"), "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.7.5", "Status Code"), "200");
+}
+
+#[test]
+fn cannot_sample_the_sweep() {
+    let dir = workdir("syn-sample", Some("127.0.7.16/28\n"));
+    let outcome = run(&dir, &["--syn", "--sample", "0.5"], "y\n");
+    assert_ne!(outcome.code, 0);
+    assert!(outcome.stderr.contains("cannot be used with"), "{}", outcome.stderr);
+}
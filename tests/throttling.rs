@@ -0,0 +1,30 @@
+//! 429 answers from `/api/tags`: one retry after Retry-After, and the
+//! Throttling column.
+
+mod common;
+
+use common::{field, run, serve_tags_in_turn, workdir, TAGS};
+
+#[test]
+fn records_the_endpoint_after_one_retry() {
+    serve_tags_in_turn("127.0.0.58", &[("429 Too Many Requests", "Retry-After: 1\r\n", ""), ("200 OK", "", TAGS)]);
+    let dir = workdir("throttling", Some("127.0.0.58\n"));
+    let outcome = run(&dir, &["--subnet-rate", "5"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    assert_eq!(field(&endpoints, "127.0.0.58", "Throttling"), "429, retried after 1s");
+    assert!(!field(&dir.join("llm_models.csv"), "127.0.0.58", "Model Name").is_empty());
+    assert!(outcome.stderr.contains("Throttled: 429, retried after 1s"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Rate-limited (429): 1 (from 1 /24s)"), "{}", outcome.stderr);
+}
+
+#[test]
+fn gives_up_after_a_second_429() {
+    serve_tags_in_turn("127.0.0.59", &[("429 Too Many Requests", "Retry-After: 0\r\n", "")]);
+    let dir = workdir("throttling-again", Some("127.0.0.59\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("http://127.0.0.59:11434 answered 429 again after waiting; skipped"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Rate-limited (429): 2"), "{}", outcome.stderr);
+}
@@ -0,0 +1,27 @@
+//! `--connect-timeout` and `--read-timeout`.
+
+mod common;
+
+use common::{field, run, serve_ollama_slow_once, workdir};
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn waits_for_a_slow_answer_within_the_read_timeout() {
+    serve_ollama_slow_once("127.0.0.62", Duration::from_millis(1200));
+    let dir = workdir("read-timeout", Some("127.0.0.62\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.0.62", "Pass"), "1");
+}
+
+#[test]
+fn reports_which_timeout_fired() {
+    serve_ollama_slow_once("127.0.0.63", Duration::from_millis(1200));
+    let dir = workdir("read-timeout-short", Some("127.0.0.63\n"));
+    let outcome = run(&dir, &["--read-timeout", "500ms", "--retry-queue", "0", "--errors-csv"], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("read timeout"), "{}", outcome.stderr);
+    let errors = fs::read_to_string(dir.join("errors.csv")).unwrap();
+    assert!(errors.contains("\nread timeout,127.0.0.63,"), "{}", errors);
+}
@@ -0,0 +1,42 @@
+//! Verification of /api/tags answers: fakes go to unverified_endpoints.csv
+//! instead of the endpoints, unless `--no-verify` is given.
+
+mod common;
+
+use common::{run, serve_json, serve_ollama, workdir};
+
+/// Shaped like Ollama's model list, but with a made-up digest
+const HONEYPOT_TAGS: &str = r#"{"models":[{"name":"llama3:8b","model":"llama3:8b","modified_at":"2024-05-01T10:00:00Z","size":4661224676,"digest":"deadbeef","details":{"family":"llama"}}]}"#;
+
+#[test]
+fn fakes_are_recorded_apart_unless_verification_is_off() {
+    serve_json("127.0.0.13", 11434, "/api/tags", "", HONEYPOT_TAGS);
+
+    let dir = workdir("verify-honeypot", Some("127.0.0.13\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 2, "{}", outcome.stderr);
+    let unverified = std::fs::read_to_string(dir.join("unverified_endpoints.csv")).unwrap();
+    assert!(unverified.contains(r#"http://127.0.0.13:11434,http://127.0.0.13:11434/api/tags,200,"digest of ""llama3:8b"" is not a sha256""#), "{}", unverified);
+    assert!(unverified.contains("deadbeef"), "{}", unverified);
+    let endpoints = std::fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(!endpoints.contains("127.0.0.13"), "{}", endpoints);
+
+    let dir = workdir("verify-off", Some("127.0.0.13\n"));
+    assert_eq!(run(&dir, &["--no-verify"], "y\n").code, 0);
+    assert!(!dir.join("unverified_endpoints.csv").exists());
+}
+
+#[test]
+fn strict_mode_needs_the_version_endpoint() {
+    serve_ollama("127.0.0.14");
+    // Passes the model list checks but has no /api/version
+    serve_json("127.0.0.15", 11434, "/api/tags", "", r#"{"models":[]}"#);
+
+    let dir = workdir("verify-strict", Some("127.0.0.14\n127.0.0.15\n"));
+    let outcome = run(&dir, &["--strict"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    let endpoints = std::fs::read_to_string(dir.join("ollama_endpoints.csv")).unwrap();
+    assert!(endpoints.contains("http://127.0.0.14:11434") && !endpoints.contains("127.0.0.15"), "{}", endpoints);
+    let unverified = std::fs::read_to_string(dir.join("unverified_endpoints.csv")).unwrap();
+    assert!(unverified.contains("http://127.0.0.15:11434,http://127.0.0.15:11434/api/tags,200,no /api/version"), "{}", unverified);
+}
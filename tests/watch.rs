@@ -0,0 +1,37 @@
+//! `--watch` probes the lines of a feed file as they are written, until it
+//! is stopped.
+
+mod common;
+
+#[cfg(unix)]
+#[test]
+fn probes_lines_as_they_are_written() {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use common::{field, finish, serve_ollama_with, spawn, workdir};
+
+    let probes = Arc::new(Mutex::new(Vec::new()));
+    for host in 1..=2 {
+        serve_ollama_with(&format!("127.0.5.{}", host), Duration::ZERO, probes.clone());
+    }
+    let dir = workdir("watch", None);
+    std::fs::write(dir.join("feed.txt"), "127.0.5.1\n").unwrap();
+    let child = spawn(&dir, &["--watch", "feed.txt"], "y\n");
+    std::thread::sleep(Duration::from_millis(1500));
+    let mut file = OpenOptions::new().append(true).open(dir.join("feed.txt")).unwrap();
+    file.write_all(b"# added later\n127.0.5.2\n").unwrap();
+    drop(file);
+    std::thread::sleep(Duration::from_millis(1500));
+    Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+
+    let outcome = finish(child);
+    assert_eq!(outcome.code, 3, "{}", outcome.stderr);
+    for host in ["127.0.5.1", "127.0.5.2"] {
+        assert_eq!(field(&dir.join("ollama_endpoints.csv"), &format!("http://{}:11434", host), "Status Code"), "200");
+    }
+    assert_eq!(*probes.lock().unwrap(), ["127.0.5.1", "127.0.5.2"]);
+}
@@ -0,0 +1,40 @@
+//! `--check-write-access` against fake Ollamas answering /api/copy in each way.
+
+mod common;
+
+use common::{field, run, serve_ollama, serve_ollama_copy, workdir};
+
+#[test]
+fn records_write_access_per_endpoint() {
+    let servers = [
+        ("127.0.0.19", "400 Bad Request", "open"),
+        ("127.0.0.20", "422 Unprocessable Entity", "open"),
+        ("127.0.0.21", "401 Unauthorized", "blocked"),
+        ("127.0.0.22", "403 Forbidden", "blocked"),
+        ("127.0.0.23", "404 Not Found", "blocked"),
+        ("127.0.0.24", "500 Internal Server Error", "unknown"),
+    ];
+    for (ip, status, _) in servers {
+        serve_ollama_copy(ip, status);
+    }
+    let dir = workdir("write-access", Some("127.0.0.19-127.0.0.24\n"));
+    let outcome = run(&dir, &["--check-write-access"], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("--check-write-access is enabled"), "{}", outcome.stderr);
+
+    let endpoints = dir.join("ollama_endpoints.csv");
+    for (ip, _, access) in servers {
+        assert_eq!(field(&endpoints, ip, "Write Access"), access, "{}", ip);
+    }
+}
+
+#[test]
+fn leaves_the_column_empty_without_the_flag() {
+    serve_ollama("127.0.0.25");
+    let dir = workdir("write-access-off", Some("127.0.0.25\n"));
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 0, "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("--check-write-access"), "{}", outcome.stderr);
+
+    assert_eq!(field(&dir.join("ollama_endpoints.csv"), "127.0.0.25", "Write Access"), "");
+}
@@ -0,0 +1,54 @@
+//! Output files that can't be opened or written: reported, never a panic.
+
+mod common;
+
+use common::{run, workdir};
+
+#[test]
+fn refuses_to_start_without_its_output_files() {
+    let dir = workdir("unopenable-output", Some("127.0.5.15\n"));
+    std::fs::create_dir(dir.join("ollama_endpoints.csv")).unwrap();
+    let outcome = run(&dir, &[], "y\n");
+    assert_eq!(outcome.code, 1, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Error: ") && outcome.stderr.contains("ollama_endpoints.csv"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("panicked"), "{}", outcome.stderr);
+}
+
+/// /dev/full takes every open but fails every write with ENOSPC.
+#[cfg(target_os = "linux")]
+#[test]
+fn stops_once_rows_keep_failing() {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use common::{finish, serve_ollama, spawn};
+
+    for host in 16..=18 {
+        serve_ollama(&format!("127.0.5.{}", host));
+    }
+    let dir = workdir("unwritable-output", None);
+    std::os::unix::fs::symlink("/dev/full", dir.join("ollama_endpoints.csv")).unwrap();
+    std::fs::write(dir.join("feed.txt"), "127.0.5.16\n").unwrap();
+    let mut child = spawn(&dir, &["--watch", "feed.txt"], "y\n");
+    // The first failure shows at the first flush, two seconds in
+    std::thread::sleep(Duration::from_millis(3000));
+    for host in ["127.0.5.17\n", "127.0.5.18\n"] {
+        OpenOptions::new().append(true).open(dir.join("feed.txt")).unwrap().write_all(host.as_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(1000));
+    }
+    let started = Instant::now();
+    while child.try_wait().unwrap().is_none() && started.elapsed() < Duration::from_secs(10) {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    // Stopped by hand if the writer didn't, so the assertions below show why
+    Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+
+    let outcome = finish(child);
+    assert_eq!(outcome.code, 3, "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("No space left on device"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("Stopping the scan: results can't be written"), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("CSV rows not written: 3"), "{}", outcome.stderr);
+    assert!(!outcome.stderr.contains("panicked"), "{}", outcome.stderr);
+}